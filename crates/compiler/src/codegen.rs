@@ -0,0 +1,37 @@
+use maya_classfile_ir::{
+	access::MethodAccessFlags, builder::ClassBuilder, class_pool::IRClassfileError, code::Instructions, IRClassFile,
+};
+
+use crate::parse::{Modifiers, ParsedClass, ParsedInstruction, ParsedMethod};
+
+pub fn emit_class(parsed: &ParsedClass) -> Result<IRClassFile, IRClassfileError> {
+	let mut builder = ClassBuilder::new(&parsed.name);
+	for method in &parsed.methods {
+		emit_method(&mut builder, method)?;
+	}
+	Ok(builder.build())
+}
+
+fn emit_method(class: &mut ClassBuilder, method: &ParsedMethod) -> Result<(), IRClassfileError> {
+	let mut access_flags = MethodAccessFlags::PUBLIC;
+	for modifier in &method.modifiers {
+		match modifier {
+			Modifiers::Static => access_flags |= MethodAccessFlags::STATIC,
+		}
+	}
+
+	let mut builder = class.method(access_flags, &method.name, &method.signature)?;
+	for instruction in &method.instructions {
+		match instruction {
+			ParsedInstruction::Println(text) => {
+				builder
+					.get_static("java/lang/System", "out", "Ljava/io/PrintStream;")?
+					.push_string(text)?
+					.invoke_virtual("java/io/PrintStream", "println", "(Ljava/lang/String;)V")?;
+			}
+		}
+	}
+	builder.op(Instructions::RETURN);
+	builder.finish()?;
+	Ok(())
+}
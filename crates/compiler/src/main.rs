@@ -3,6 +3,7 @@
 use lex::Lexer;
 use parse::Parser;
 
+mod codegen;
 mod lex;
 mod parse;
 
@@ -10,8 +11,14 @@ fn main() {
 	let src = include_str!("../assets/test.mommy");
 	let mut lexer = Lexer::new(src);
 	let tokens = lexer.lex();
-	println!("{:?}", &tokens);
+	println!("{:?}", tokens);
 
 	let mut parser = Parser::new(tokens);
-	println!("{:?}", parser.parse());
+	let parsed = parser.parse();
+	println!("{:?}", parsed);
+
+	let class = codegen::emit_class(&parsed).unwrap();
+	let mut bytes = Vec::new();
+	class.write(&mut bytes).unwrap();
+	std::fs::write(format!("{}.class", parsed.name), bytes).unwrap();
 }
@@ -173,11 +173,6 @@ impl Parser {
 		};
 		self.expect_token("expected '}'", Token::Ascii(AsciiToken::RBrace));
 
-		// getstatic
-		// ldc (constant pool)
-		// invokevirtual
-		// return
-
 		ParsedMethod {
 			name,
 			signature: String::from("()V"),
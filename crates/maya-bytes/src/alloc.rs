@@ -0,0 +1,53 @@
+use std::cell::Cell;
+
+use crate::BytesError;
+
+thread_local! {
+	static MAX_ALLOC: Cell<Option<u64>> = const { Cell::new(None) };
+}
+
+/// Caps how many bytes a single read may allocate on this thread, returning the previous cap.
+/// Checked by [`crate::BytesReadExt::read_n_bytes_vec`] and [`vec_with_capacity`], so a length field
+/// claiming gigabytes fails fast instead of allocating. `None` removes the cap.
+///
+/// The byte traits are implemented for every reader, so the cap is per thread rather than stored in the reader.
+/// Use [`with_max_alloc`] to cap a single read instead.
+pub fn set_max_alloc(limit: Option<u64>) -> Option<u64> {
+	MAX_ALLOC.replace(limit)
+}
+
+pub fn max_alloc() -> Option<u64> {
+	MAX_ALLOC.get()
+}
+
+/// Runs `f` with the cap lowered to `limit`, restoring the previous cap afterwards, even if `f` panics.
+/// A cap already in place that is lower than `limit` stays.
+pub fn with_max_alloc<T>(limit: Option<u64>, f: impl FnOnce() -> T) -> T {
+	struct Restore(Option<u64>);
+
+	impl Drop for Restore {
+		fn drop(&mut self) {
+			set_max_alloc(self.0);
+		}
+	}
+
+	let limit = match (max_alloc(), limit) {
+		(Some(current), Some(limit)) => Some(current.min(limit)),
+		(current, limit) => current.or(limit),
+	};
+	let _restore = Restore(set_max_alloc(limit));
+	f()
+}
+
+pub(crate) fn check_alloc(requested: u64) -> Result<(), BytesError> {
+	match max_alloc() {
+		Some(limit) if requested > limit => Err(BytesError::AllocationTooLarge { requested, limit }),
+		_ => Ok(()),
+	}
+}
+
+/// [`Vec::with_capacity`] that respects [`set_max_alloc`], for capacities that came out of the input.
+pub fn vec_with_capacity<T>(capacity: usize) -> Result<Vec<T>, BytesError> {
+	check_alloc((capacity as u64).saturating_mul(size_of::<T>() as u64))?;
+	Ok(Vec::with_capacity(capacity))
+}
@@ -0,0 +1,162 @@
+//! Async counterparts of [`BytesReadExt`](crate::BytesReadExt) and [`BytesWriteExt`](crate::BytesWriteExt)
+//! on top of tokio's `AsyncRead`/`AsyncWrite`.
+//!
+//! tokio's own `AsyncReadExt` has methods with the same names (returning `io::Result`),
+//! so with both traits in scope calls have to be disambiguated.
+
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use crate::{define_integral_r_async, define_write_async, BytesError};
+
+/// Async version of [`crate::fill`].
+pub(crate) async fn fill<R: AsyncRead + Unpin + ?Sized>(reader: &mut R, buf: &mut [u8]) -> Result<(), BytesError> {
+	let mut filled = 0;
+	while filled < buf.len() {
+		match reader.read(&mut buf[filled..]).await? {
+			0 => return Err(BytesError::not_enough_data(buf.len() as u64, filled as u64)),
+			n => filled += n,
+		}
+	}
+
+	Ok(())
+}
+
+#[allow(async_fn_in_trait)]
+pub trait AsyncBytesReadExt: AsyncRead + Unpin {
+	define_integral_r_async!(i8, 1);
+	define_integral_r_async!(u8, 1);
+
+	define_integral_r_async!(i16, 2);
+	define_integral_r_async!(u16, 2);
+
+	define_integral_r_async!(i32, 4);
+	define_integral_r_async!(u32, 4);
+
+	define_integral_r_async!(i64, 8);
+	define_integral_r_async!(u64, 8);
+
+	async fn read_to_vec(&mut self) -> Result<Vec<u8>, BytesError> {
+		let mut bytes = Vec::new();
+		self.read_to_end(&mut bytes).await?;
+		Ok(bytes)
+	}
+
+	async fn read_n_bytes<const N: usize>(&mut self) -> Result<[u8; N], BytesError> {
+		let mut bytes = [0u8; N];
+		fill(self, &mut bytes).await?;
+		Ok(bytes)
+	}
+
+	async fn read_n_bytes_vec(&mut self, amount: usize) -> Result<Vec<u8>, BytesError> {
+		let mut bytes = Vec::new();
+		AsyncReadExt::take(&mut *self, amount as u64)
+			.read_to_end(&mut bytes)
+			.await?;
+		if bytes.len() != amount {
+			return Err(BytesError::not_enough_data(amount as u64, bytes.len() as u64));
+		}
+
+		Ok(bytes)
+	}
+
+	async fn read_f32(&mut self) -> Result<f32, BytesError> {
+		let v = AsyncBytesReadExt::read_u32(self).await?;
+		Ok(f32::from_bits(v))
+	}
+
+	async fn read_f64(&mut self) -> Result<f64, BytesError> {
+		let v = AsyncBytesReadExt::read_u64(self).await?;
+		Ok(f64::from_bits(v))
+	}
+
+	async fn read_f32_le(&mut self) -> Result<f32, BytesError> {
+		let v = AsyncBytesReadExt::read_u32_le(self).await?;
+		Ok(f32::from_bits(v))
+	}
+
+	async fn read_f64_le(&mut self) -> Result<f64, BytesError> {
+		let v = AsyncBytesReadExt::read_u64_le(self).await?;
+		Ok(f64::from_bits(v))
+	}
+}
+
+#[allow(async_fn_in_trait)]
+pub trait AsyncBytesWriteExt: AsyncWrite + Unpin {
+	define_write_async!(i8);
+	define_write_async!(u8);
+
+	define_write_async!(i16);
+	define_write_async!(u16);
+
+	define_write_async!(i32);
+	define_write_async!(u32);
+
+	define_write_async!(i64);
+	define_write_async!(u64);
+
+	define_write_async!(f32);
+	define_write_async!(f64);
+}
+
+impl<R: AsyncRead + Unpin> AsyncBytesReadExt for R {}
+impl<W: AsyncWrite + Unpin> AsyncBytesWriteExt for W {}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	macro_rules! define_test {
+		($ty:ty) => {
+			paste::item! {
+				#[tokio::test]
+				async fn [<$ty>]() {
+					const VALUES: [$ty; 2] = [$ty::MAX, $ty::MIN];
+
+					for v in VALUES {
+						let mut buffer: Vec<u8> = Vec::new();
+						AsyncBytesWriteExt::[<write_$ty>](&mut buffer, v).await.expect("Error when writing");
+						AsyncBytesWriteExt::[<write_$ty _le>](&mut buffer, v).await.expect("Error when writing");
+
+						let mut reader = buffer.as_slice();
+						assert_eq!(v, AsyncBytesReadExt::[<read_$ty>](&mut reader).await.expect("Error when reading"));
+						assert_eq!(v, AsyncBytesReadExt::[<read_$ty _le>](&mut reader).await.expect("Error when reading"));
+					}
+				}
+			}
+		};
+	}
+
+	define_test!(i8);
+	define_test!(u8);
+	define_test!(i16);
+	define_test!(u16);
+	define_test!(i32);
+	define_test!(u32);
+	define_test!(i64);
+	define_test!(u64);
+	define_test!(f32);
+	define_test!(f64);
+
+	#[tokio::test]
+	async fn not_enough_data() {
+		let mut reader: &[u8] = &[0x01];
+		assert!(matches!(
+			AsyncBytesReadExt::read_u16(&mut reader).await,
+			Err(BytesError::NotEnoughData { .. })
+		));
+
+		let mut reader: &[u8] = &[0x01, 0x02];
+		assert!(matches!(
+			AsyncBytesReadExt::read_n_bytes_vec(&mut reader, 3).await,
+			Err(BytesError::NotEnoughData { .. })
+		));
+	}
+
+	#[tokio::test]
+	async fn read_to_vec() {
+		let mut reader: &[u8] = &[0x01, 0x02, 0x03];
+		assert_eq!(AsyncBytesReadExt::read_u8(&mut reader).await.unwrap(), 0x01);
+		assert_eq!(AsyncBytesReadExt::read_to_vec(&mut reader).await.unwrap(), [0x02, 0x03]);
+		assert!(AsyncBytesReadExt::read_to_vec(&mut reader).await.unwrap().is_empty());
+	}
+}
@@ -0,0 +1,42 @@
+use std::io::Write;
+
+use crate::BytesError;
+
+/// A writer that throws the data away and only counts how many bytes were written.
+/// Useful for learning the size of something (e.g. an attribute body) before writing it for real.
+#[derive(Debug, Default)]
+pub struct CountingWriter {
+	count: u64,
+}
+
+impl CountingWriter {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Bytes written so far.
+	pub fn count(&self) -> u64 {
+		self.count
+	}
+
+	/// Runs `f` against a fresh counter and returns how many bytes it wrote.
+	pub fn measure<F>(f: F) -> Result<u64, BytesError>
+	where
+		F: FnOnce(&mut Self) -> Result<(), BytesError>,
+	{
+		let mut counter = Self::new();
+		f(&mut counter)?;
+		Ok(counter.count)
+	}
+}
+
+impl Write for CountingWriter {
+	fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+		self.count += buf.len() as u64;
+		Ok(buf.len())
+	}
+
+	fn flush(&mut self) -> std::io::Result<()> {
+		Ok(())
+	}
+}
@@ -0,0 +1,74 @@
+use std::io::Write;
+
+use crc32fast::Hasher;
+
+/// Passes writes through to the inner writer while keeping a CRC32 (and optionally
+/// a SHA-256) of everything written, e.g. for jar entry headers.
+pub struct DigestWriter<W> {
+	inner: W,
+	crc: Hasher,
+	written: u64,
+	#[cfg(feature = "sha256")]
+	sha: Option<sha2::Sha256>,
+}
+
+impl<W: Write> DigestWriter<W> {
+	pub fn new(inner: W) -> Self {
+		Self {
+			inner,
+			crc: Hasher::new(),
+			written: 0,
+			#[cfg(feature = "sha256")]
+			sha: None,
+		}
+	}
+
+	/// Like [`DigestWriter::new`], but also computes a SHA-256.
+	#[cfg(feature = "sha256")]
+	pub fn with_sha256(inner: W) -> Self {
+		use sha2::Digest;
+
+		Self {
+			sha: Some(sha2::Sha256::new()),
+			..Self::new(inner)
+		}
+	}
+
+	/// CRC32 of everything written so far.
+	pub fn crc32(&self) -> u32 {
+		self.crc.clone().finalize()
+	}
+
+	/// SHA-256 of everything written so far, if enabled.
+	#[cfg(feature = "sha256")]
+	pub fn sha256(&self) -> Option<[u8; 32]> {
+		use sha2::Digest;
+
+		self.sha.as_ref().map(|sha| sha.clone().finalize().into())
+	}
+
+	pub fn bytes_written(&self) -> u64 {
+		self.written
+	}
+
+	pub fn into_inner(self) -> W {
+		self.inner
+	}
+}
+
+impl<W: Write> Write for DigestWriter<W> {
+	fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+		let n = self.inner.write(buf)?;
+		self.crc.update(&buf[..n]);
+		self.written += n as u64;
+		#[cfg(feature = "sha256")]
+		if let Some(sha) = &mut self.sha {
+			sha2::Digest::update(sha, &buf[..n]);
+		}
+		Ok(n)
+	}
+
+	fn flush(&mut self) -> std::io::Result<()> {
+		self.inner.flush()
+	}
+}
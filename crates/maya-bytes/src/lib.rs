@@ -1,20 +1,122 @@
-#![feature(seek_stream_len)]
-
+mod alloc;
+mod counting;
+mod digest;
+mod limited;
 mod macros;
+mod offset;
+mod patch;
+
+// not re-exported at the root, most readers are both Read and AsyncRead so a glob import
+// would make every call ambiguous
+#[cfg(feature = "tokio")]
+pub mod async_ext;
 
-use std::io::{Read, Seek, Write};
+pub use alloc::{max_alloc, set_max_alloc, vec_with_capacity, with_max_alloc};
+use std::io::{ErrorKind, Read, Seek, SeekFrom, Write};
 
+pub use counting::CountingWriter;
+pub use digest::DigestWriter;
+pub use limited::LimitedReader;
+use maya_mutf8::MUTFError;
+pub use offset::OffsetReader;
+pub use patch::{Patch, PatchWriter, Patchable};
 use thiserror::Error;
 
 #[derive(Debug, Error)]
 pub enum BytesError {
-	#[error("Not enough data left in the buffer")]
-	NotEnoughData,
-	#[error("IO Error: {0}")]
-	IO(#[from] std::io::Error),
+	#[error("Not enough data left in the buffer: needed {requested} bytes, got {available}{}", fmt_position(.position))]
+	NotEnoughData {
+		requested: u64,
+		available: u64,
+		position: Option<u64>,
+	},
+	#[error("refusing to allocate {requested} bytes, the limit is {limit}")]
+	AllocationTooLarge { requested: u64, limit: u64 },
+	#[error("{0} bytes were left unread")]
+	TrailingData(u64),
+	#[error("LEB128 value doesn't fit in 64 bits")]
+	VarIntOverflow,
+	#[error("Modified UTF-8 error: {0}")]
+	Mutf8(#[from] MUTFError),
+	#[error("encoded string is {0} bytes, longer than the u16 length prefix allows")]
+	StringTooLong(usize),
+	#[error("IO Error: {source}{}", fmt_position(.position))]
+	IO {
+		source: std::io::Error,
+		position: Option<u64>,
+	},
+}
+
+fn fmt_position(position: &Option<u64>) -> String {
+	match position {
+		Some(position) => format!(" at offset {position}"),
+		None => String::new(),
+	}
+}
+
+impl BytesError {
+	pub fn not_enough_data(requested: u64, available: u64) -> Self {
+		Self::NotEnoughData {
+			requested,
+			available,
+			position: None,
+		}
+	}
+
+	/// Fills in the position if the error doesn't have one yet.
+	/// `offset` is where the reader is now, so for [`BytesError::NotEnoughData`] the bytes that were
+	/// available are subtracted to get where the failed read started.
+	pub fn at(self, offset: u64) -> Self {
+		match self {
+			Self::NotEnoughData {
+				requested,
+				available,
+				position: None,
+			} => Self::NotEnoughData {
+				requested,
+				available,
+				position: Some(offset.saturating_sub(available)),
+			},
+			Self::IO { source, position: None } => Self::IO {
+				source,
+				position: Some(offset),
+			},
+			e => e,
+		}
+	}
+
+	pub fn position(&self) -> Option<u64> {
+		match self {
+			Self::NotEnoughData { position, .. } | Self::IO { position, .. } => *position,
+			_ => None,
+		}
+	}
+}
+
+impl From<std::io::Error> for BytesError {
+	fn from(source: std::io::Error) -> Self {
+		Self::IO { source, position: None }
+	}
+}
+
+/// Like [`Read::read_exact`], but reports how much data was actually there.
+pub(crate) fn fill<R: Read + ?Sized>(reader: &mut R, buf: &mut [u8]) -> Result<(), BytesError> {
+	let mut filled = 0;
+	while filled < buf.len() {
+		match reader.read(&mut buf[filled..]) {
+			Ok(0) => return Err(BytesError::not_enough_data(buf.len() as u64, filled as u64)),
+			Ok(n) => filled += n,
+			Err(e) if e.kind() == ErrorKind::Interrupted => {}
+			Err(e) => return Err(e.into()),
+		}
+	}
+
+	Ok(())
 }
 
-pub trait BytesReadExt: Read + Seek {
+/// Reading helpers that only need [`Read`], so they work on sockets, stdin or decompressing streams.
+/// Running out of data is reported as [`BytesError::NotEnoughData`].
+pub trait BytesReadExt: Read {
 	define_integral_r!(i8, 1);
 	define_integral_r!(u8, 1);
 
@@ -27,50 +129,179 @@ pub trait BytesReadExt: Read + Seek {
 	define_integral_r!(i64, 8);
 	define_integral_r!(u64, 8);
 
-	fn len_check(&mut self, needed: u64) -> Result<(), BytesError> {
-		let len = self.stream_len()?;
-		if len < self.stream_position()? + needed {
-			return Err(BytesError::NotEnoughData);
-		}
-
-		Ok(())
+	/// Borrows the reader with a budget of `len` bytes.
+	fn limited(&mut self, len: u64) -> LimitedReader<'_, Self> {
+		LimitedReader::new(self, len)
 	}
 
+	/// Reads everything left in the reader, up to and including its last byte.
 	fn read_to_vec(&mut self) -> Result<Vec<u8>, BytesError> {
-		let len = self.stream_len()? as usize;
-		let pos = self.stream_position()? as usize;
-		self.read_n_bytes_vec(len - pos - 1)
+		let mut bytes = Vec::new();
+		self.read_to_end(&mut bytes)?;
+		Ok(bytes)
 	}
 
 	fn read_n_bytes<const N: usize>(&mut self) -> Result<[u8; N], BytesError> {
-		self.len_check(N as u64)?;
-
 		let mut bytes = [0u8; N];
-		self.read_exact(&mut bytes)?;
+		fill(self, &mut bytes)?;
 		Ok(bytes)
 	}
 
+	/// Fills `buf` completely, for callers with a reusable scratch buffer.
+	fn read_into(&mut self, buf: &mut [u8]) -> Result<(), BytesError> {
+		fill(self, buf)
+	}
+
 	fn read_n_bytes_vec(&mut self, amount: usize) -> Result<Vec<u8>, BytesError> {
-		self.len_check(amount as u64)?;
+		alloc::check_alloc(amount as u64)?;
+		// grow as data actually arrives instead of trusting `amount` for the allocation
+		let mut bytes = Vec::new();
+		Read::take(&mut *self, amount as u64).read_to_end(&mut bytes)?;
+		if bytes.len() != amount {
+			return Err(BytesError::not_enough_data(amount as u64, bytes.len() as u64));
+		}
 
-		let mut bytes = vec![0; amount];
-		self.read_exact(&mut bytes)?;
 		Ok(bytes)
 	}
 
-	fn read_f32(&mut self) -> Result<f32, BytesError> {
-		self.len_check(4)?;
+	/// Reads `n` big-endian u16s with one bulk read.
+	fn read_u16_vec(&mut self, n: usize) -> Result<Vec<u16>, BytesError> {
+		let bytes = self.read_n_bytes_vec(n * 2)?;
+		Ok(bytes
+			.chunks_exact(2)
+			.map(|c| u16::from_be_bytes([c[0], c[1]]))
+			.collect())
+	}
+
+	/// Reads `n` big-endian u32s with one bulk read.
+	fn read_u32_vec(&mut self, n: usize) -> Result<Vec<u32>, BytesError> {
+		let bytes = self.read_n_bytes_vec(n * 4)?;
+		Ok(bytes
+			.chunks_exact(4)
+			.map(|c| u32::from_be_bytes([c[0], c[1], c[2], c[3]]))
+			.collect())
+	}
+
+	/// Reads a u16 length prefixed Modified UTF-8 string, the layout of `CONSTANT_Utf8_info`.
+	fn read_jvm_utf8(&mut self) -> Result<String, BytesError> {
+		let len = self.read_u16()?;
+		let bytes = self.read_n_bytes_vec(len as usize)?;
+		Ok(maya_mutf8::decode(&bytes)?)
+	}
 
+	fn read_f32(&mut self) -> Result<f32, BytesError> {
 		let v = self.read_u32()?;
 		Ok(f32::from_bits(v))
 	}
 
 	fn read_f64(&mut self) -> Result<f64, BytesError> {
-		self.len_check(8)?;
-
 		let v = self.read_u64()?;
 		Ok(f64::from_bits(v))
 	}
+
+	fn read_uleb128(&mut self) -> Result<u64, BytesError> {
+		let mut value = 0u64;
+		let mut shift = 0;
+		loop {
+			let byte = self.read_u8()?;
+			if shift == 63 && byte > 1 {
+				return Err(BytesError::VarIntOverflow);
+			}
+
+			value |= ((byte & 0x7F) as u64) << shift;
+			if byte & 0x80 == 0 {
+				return Ok(value);
+			}
+
+			shift += 7;
+			if shift > 63 {
+				return Err(BytesError::VarIntOverflow);
+			}
+		}
+	}
+
+	fn read_sleb128(&mut self) -> Result<i64, BytesError> {
+		let mut value = 0i64;
+		let mut shift = 0;
+		loop {
+			let byte = self.read_u8()?;
+			if shift == 63 && byte != 0 && byte != 0x7F {
+				return Err(BytesError::VarIntOverflow);
+			}
+
+			value |= ((byte & 0x7F) as i64) << shift;
+			shift += 7;
+			if byte & 0x80 == 0 {
+				// sign extend from the last byte's sign bit
+				if shift < 64 && byte & 0x40 != 0 {
+					value |= -1 << shift;
+				}
+				return Ok(value);
+			}
+
+			if shift > 63 {
+				return Err(BytesError::VarIntOverflow);
+			}
+		}
+	}
+
+	fn read_f32_le(&mut self) -> Result<f32, BytesError> {
+		let v = self.read_u32_le()?;
+		Ok(f32::from_bits(v))
+	}
+
+	fn read_f64_le(&mut self) -> Result<f64, BytesError> {
+		let v = self.read_u64_le()?;
+		Ok(f64::from_bits(v))
+	}
+}
+
+/// Extra helpers for readers that know where they are.
+pub trait BytesSeekExt: BytesReadExt + Seek {
+	/// Bytes left between the current position and the end of the stream.
+	fn remaining(&mut self) -> Result<u64, BytesError> {
+		// same as the unstable Seek::stream_len, seek to the end and back
+		let pos = self.stream_position()?;
+		let len = self.seek(SeekFrom::End(0))?;
+		if pos != len {
+			self.seek(SeekFrom::Start(pos))?;
+		}
+
+		Ok(len.saturating_sub(pos))
+	}
+
+	fn len_check(&mut self, needed: u64) -> Result<(), BytesError> {
+		let remaining = self.remaining()?;
+		if remaining < needed {
+			return Err(BytesError::NotEnoughData {
+				requested: needed,
+				available: remaining,
+				position: Some(self.stream_position()?),
+			});
+		}
+
+		Ok(())
+	}
+
+	/// Reads `N` bytes without moving the stream position, even if the read fails.
+	fn peek_n_bytes<const N: usize>(&mut self) -> Result<[u8; N], BytesError> {
+		let pos = self.stream_position()?;
+		let bytes = self.read_n_bytes::<N>();
+		self.seek(SeekFrom::Start(pos))?;
+		bytes
+	}
+
+	fn peek_u8(&mut self) -> Result<u8, BytesError> {
+		Ok(u8::from_be_bytes(self.peek_n_bytes()?))
+	}
+
+	fn peek_u16(&mut self) -> Result<u16, BytesError> {
+		Ok(u16::from_be_bytes(self.peek_n_bytes()?))
+	}
+
+	fn peek_u32(&mut self) -> Result<u32, BytesError> {
+		Ok(u32::from_be_bytes(self.peek_n_bytes()?))
+	}
 }
 
 pub trait BytesWriteExt: Write {
@@ -88,9 +319,43 @@ pub trait BytesWriteExt: Write {
 
 	define_write!(f32);
 	define_write!(f64);
+
+	/// Writes `string` as Modified UTF-8 with a u16 length prefix.
+	fn write_jvm_utf8(&mut self, string: &str) -> Result<(), BytesError> {
+		let len = maya_mutf8::encoded_len(string);
+		self.write_u16(u16::try_from(len).map_err(|_| BytesError::StringTooLong(len))?)?;
+		maya_mutf8::encode_to(string, self)?;
+		Ok(())
+	}
+
+	fn write_uleb128(&mut self, mut value: u64) -> Result<(), BytesError> {
+		loop {
+			let byte = (value & 0x7F) as u8;
+			value >>= 7;
+			if value == 0 {
+				return self.write_u8(byte);
+			}
+
+			self.write_u8(byte | 0x80)?;
+		}
+	}
+
+	fn write_sleb128(&mut self, mut value: i64) -> Result<(), BytesError> {
+		loop {
+			let byte = (value & 0x7F) as u8;
+			value >>= 7;
+			// done once the rest is only sign bits and the sign bit of this byte agrees
+			if (value == 0 && byte & 0x40 == 0) || (value == -1 && byte & 0x40 != 0) {
+				return self.write_u8(byte);
+			}
+
+			self.write_u8(byte | 0x80)?;
+		}
+	}
 }
 
-impl<R: Read + Seek> BytesReadExt for R {}
+impl<R: Read> BytesReadExt for R {}
+impl<R: Read + Seek> BytesSeekExt for R {}
 impl<R: Write> BytesWriteExt for R {}
 
 #[cfg(test)]
@@ -117,6 +382,23 @@ mod tests {
 						);
 					}
 				}
+
+				#[test]
+				fn [<$ty _le>]() {
+					const VALUES: [$ty; 2] = [$ty::MAX, $ty::MIN];
+
+					for v in VALUES {
+						let mut buffer: Cursor<Vec<u8>> =
+							Cursor::new(Vec::new());
+						buffer.[<write_$ty _le>](v).expect("Error when writing");
+						assert_eq!(buffer.get_ref().as_slice(), v.to_le_bytes());
+						buffer.set_position(0);
+						assert_eq!(
+							v,
+							buffer.[<read_$ty _le>]().expect("Error when reading")
+						);
+					}
+				}
 			}
 		};
 	}
@@ -131,4 +413,245 @@ mod tests {
 	define_test!(u64);
 	define_test!(f32);
 	define_test!(f64);
+
+	#[test]
+	fn streaming_not_enough_data() {
+		// &[u8] is Read but not Seek
+		let mut buffer: &[u8] = &[0x01, 0x02, 0x03];
+		assert_eq!(buffer.read_u16().unwrap(), 0x0102);
+		assert!(matches!(buffer.read_u16(), Err(BytesError::NotEnoughData { .. })));
+
+		let mut buffer: &[u8] = &[0x01, 0x02, 0x03];
+		assert!(matches!(
+			buffer.read_n_bytes_vec(4),
+			Err(BytesError::NotEnoughData { .. })
+		));
+	}
+
+	#[test]
+	fn bulk_reads() {
+		let mut buffer: &[u8] = &[0x00, 0x01, 0x00, 0x02, 0xCA, 0xFE, 0xBA, 0xBE, 0xFF];
+		assert_eq!(buffer.read_u16_vec(2).unwrap(), [1, 2]);
+		assert_eq!(buffer.read_u32_vec(1).unwrap(), [0xCAFEBABE]);
+		assert!(matches!(
+			buffer.read_u16_vec(1),
+			Err(BytesError::NotEnoughData {
+				requested: 2,
+				available: 1,
+				..
+			})
+		));
+	}
+
+	#[test]
+	fn read_to_vec() {
+		// the rest of a seekable reader, then of a plain one, without stream_len
+		let mut buffer = Cursor::new(vec![0x01, 0x02, 0x03, 0x04]);
+		buffer.set_position(1);
+		assert_eq!(buffer.read_to_vec().unwrap(), [0x02, 0x03, 0x04]);
+		assert!(buffer.read_to_vec().unwrap().is_empty());
+
+		let mut buffer: &[u8] = &[0x01, 0x02, 0x03];
+		assert_eq!(buffer.read_u8().unwrap(), 0x01);
+		assert_eq!(buffer.read_to_vec().unwrap(), [0x02, 0x03]);
+	}
+
+	#[test]
+	fn read_into() {
+		let mut buffer: &[u8] = &[0x01, 0x02, 0x03];
+		let mut scratch = [0u8; 2];
+		buffer.read_into(&mut scratch).unwrap();
+		assert_eq!(scratch, [0x01, 0x02]);
+		assert!(matches!(
+			buffer.read_into(&mut scratch),
+			Err(BytesError::NotEnoughData {
+				requested: 2,
+				available: 1,
+				..
+			})
+		));
+	}
+
+	#[test]
+	fn jvm_utf8() {
+		let mut buffer = Vec::new();
+		buffer.write_jvm_utf8("a\0b").unwrap();
+		assert_eq!(buffer, [0x00, 0x04, b'a', 0xC0, 0x80, b'b']);
+		assert_eq!(buffer.as_slice().read_jvm_utf8().unwrap(), "a\0b");
+
+		let long = "x".repeat(u16::MAX as usize + 1);
+		assert!(matches!(
+			Vec::new().write_jvm_utf8(&long),
+			Err(BytesError::StringTooLong(65536))
+		));
+	}
+
+	#[test]
+	fn max_alloc() {
+		let previous = set_max_alloc(Some(4));
+		let mut buffer: &[u8] = &[0; 8];
+		assert!(matches!(
+			buffer.read_n_bytes_vec(5),
+			Err(BytesError::AllocationTooLarge { requested: 5, limit: 4 })
+		));
+		assert_eq!(buffer.read_n_bytes_vec(4).unwrap(), [0; 4]);
+		assert!(vec_with_capacity::<u16>(3).is_err());
+		set_max_alloc(previous);
+	}
+
+	#[test]
+	fn scoped_max_alloc() {
+		let mut buffer: &[u8] = &[0; 8];
+		let result = with_max_alloc(Some(2), || {
+			// a cap already in place only gets lowered
+			with_max_alloc(Some(6), || assert_eq!(crate::max_alloc(), Some(2)));
+			buffer.read_n_bytes_vec(3)
+		});
+		assert!(matches!(
+			result,
+			Err(BytesError::AllocationTooLarge { requested: 3, limit: 2 })
+		));
+		assert_eq!(crate::max_alloc(), None);
+		assert_eq!(buffer.read_n_bytes_vec(3).unwrap(), [0; 3]);
+
+		let panicked = std::panic::catch_unwind(|| with_max_alloc(Some(1), || panic!()));
+		assert!(panicked.is_err());
+		assert_eq!(crate::max_alloc(), None);
+	}
+
+	#[test]
+	fn limited() {
+		let mut buffer: &[u8] = &[0x01, 0x02, 0x03, 0x04];
+		let mut limited = buffer.limited(3);
+		assert_eq!(limited.read_u16().unwrap(), 0x0102);
+		assert_eq!(limited.consumed(), 2);
+		assert!(matches!(limited.read_u16(), Err(BytesError::NotEnoughData { .. })));
+
+		let mut limited = buffer.limited(2);
+		limited.read_u8().unwrap();
+		assert!(matches!(limited.finish(), Err(BytesError::TrailingData(1))));
+	}
+
+	#[test]
+	fn counting() {
+		let len = CountingWriter::measure(|w| {
+			w.write_u16(0xCAFE)?;
+			w.write_u32_le(1)?;
+			w.write_uleb128(300)
+		})
+		.unwrap();
+		assert_eq!(len, 8);
+	}
+
+	#[test]
+	fn patch() {
+		let mut writer = PatchWriter::new(Cursor::new(Vec::new()));
+		writer.write_u16(0x0001).unwrap();
+		let length = writer.reserve_u32().unwrap();
+		writer.write_all(b"hello").unwrap();
+		let len = writer.written_since(length).unwrap();
+		writer.patch(length, len as u32).unwrap();
+		writer.write_u8(0xFF).unwrap();
+
+		assert_eq!(
+			writer.into_inner().into_inner(),
+			[0x00, 0x01, 0x00, 0x00, 0x00, 0x05, b'h', b'e', b'l', b'l', b'o', 0xFF]
+		);
+	}
+
+	#[test]
+	fn digest() {
+		let mut writer = DigestWriter::new(Vec::new());
+		writer.write_all(b"123456789").unwrap();
+		assert_eq!(writer.crc32(), 0xCBF43926);
+		assert_eq!(writer.bytes_written(), 9);
+		assert_eq!(writer.into_inner(), b"123456789");
+	}
+
+	#[cfg(feature = "sha256")]
+	#[test]
+	fn digest_sha256() {
+		let mut writer = DigestWriter::with_sha256(std::io::sink());
+		writer.write_all(b"abc").unwrap();
+		assert_eq!(writer.sha256().unwrap()[..4], [0xBA, 0x78, 0x16, 0xBF],);
+	}
+
+	#[test]
+	fn leb128() {
+		const UNSIGNED: [(u64, &[u8]); 5] = [
+			(0, &[0x00]),
+			(127, &[0x7F]),
+			(128, &[0x80, 0x01]),
+			(624485, &[0xE5, 0x8E, 0x26]),
+			(u64::MAX, &[0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x01]),
+		];
+		for (value, bytes) in UNSIGNED {
+			let mut buffer = Vec::new();
+			buffer.write_uleb128(value).unwrap();
+			assert_eq!(buffer, bytes);
+			assert_eq!(bytes.as_ref().read_uleb128().unwrap(), value);
+		}
+
+		const SIGNED: [(i64, &[u8]); 7] = [
+			(0, &[0x00]),
+			(-1, &[0x7F]),
+			(63, &[0x3F]),
+			(64, &[0xC0, 0x00]),
+			(-123456, &[0xC0, 0xBB, 0x78]),
+			(i64::MAX, &[0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x00]),
+			(i64::MIN, &[0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x7F]),
+		];
+		for (value, bytes) in SIGNED {
+			let mut buffer = Vec::new();
+			buffer.write_sleb128(value).unwrap();
+			assert_eq!(buffer, bytes);
+			assert_eq!(bytes.as_ref().read_sleb128().unwrap(), value);
+		}
+
+		let too_long: &[u8] = &[0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x02];
+		assert!(matches!(
+			too_long.as_ref().read_uleb128(),
+			Err(BytesError::VarIntOverflow)
+		));
+	}
+
+	#[test]
+	fn offset_in_errors() {
+		let data: &[u8] = &[0x00, 0x01, 0x02];
+		let mut reader = OffsetReader::new(data);
+		reader.read_u16().unwrap();
+		let err = reader.read_u32().unwrap_err().at(reader.offset());
+		assert!(matches!(
+			err,
+			BytesError::NotEnoughData {
+				requested: 4,
+				available: 1,
+				position: Some(2)
+			}
+		));
+		assert_eq!(
+			err.to_string(),
+			"Not enough data left in the buffer: needed 4 bytes, got 1 at offset 2"
+		);
+	}
+
+	#[test]
+	fn peek() {
+		let mut buffer = Cursor::new(vec![0x01, 0x02, 0x03]);
+		assert_eq!(buffer.peek_u8().unwrap(), 0x01);
+		assert_eq!(buffer.peek_u16().unwrap(), 0x0102);
+		assert!(matches!(buffer.peek_u32(), Err(BytesError::NotEnoughData { .. })));
+		assert_eq!(buffer.position(), 0);
+		assert_eq!(buffer.read_u16().unwrap(), 0x0102);
+		assert_eq!(buffer.peek_n_bytes::<1>().unwrap(), [0x03]);
+	}
+
+	#[test]
+	fn seek_len_check() {
+		let mut buffer = Cursor::new(vec![0u8; 4]);
+		buffer.read_u8().unwrap();
+		assert_eq!(buffer.remaining().unwrap(), 3);
+		assert!(buffer.len_check(3).is_ok());
+		assert!(matches!(buffer.len_check(4), Err(BytesError::NotEnoughData { .. })));
+	}
 }
@@ -0,0 +1,47 @@
+use std::io::Read;
+
+use crate::BytesError;
+
+/// A reader that refuses to read more than `limit` bytes from the inner reader.
+/// Reading past the budget fails with [`BytesError::NotEnoughData`], and [`LimitedReader::finish`]
+/// reports if the budget wasn't used up.
+pub struct LimitedReader<'a, R: ?Sized> {
+	inner: &'a mut R,
+	limit: u64,
+	consumed: u64,
+}
+
+impl<'a, R: Read + ?Sized> LimitedReader<'a, R> {
+	pub fn new(inner: &'a mut R, limit: u64) -> Self {
+		Self {
+			inner,
+			limit,
+			consumed: 0,
+		}
+	}
+
+	pub fn consumed(&self) -> u64 {
+		self.consumed
+	}
+
+	pub fn remaining(&self) -> u64 {
+		self.limit - self.consumed
+	}
+
+	/// Checks that exactly `limit` bytes were read.
+	pub fn finish(self) -> Result<(), BytesError> {
+		match self.remaining() {
+			0 => Ok(()),
+			remaining => Err(BytesError::TrailingData(remaining)),
+		}
+	}
+}
+
+impl<R: Read + ?Sized> Read for LimitedReader<'_, R> {
+	fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+		let max = buf.len().min(self.remaining().try_into().unwrap_or(usize::MAX));
+		let n = self.inner.read(&mut buf[..max])?;
+		self.consumed += n as u64;
+		Ok(n)
+	}
+}
@@ -10,6 +10,15 @@ macro_rules! define_write {
 			self.write_all(&bytes)?;
 			Ok(())
 		}
+
+		fn [<write_$ty _le>](
+			&mut self,
+			value: $ty,
+		) -> Result<(), BytesError> {
+			let bytes = value.to_le_bytes();
+			self.write_all(&bytes)?;
+			Ok(())
+		}
 		}
 	};
 }
@@ -21,12 +30,66 @@ macro_rules! define_integral_r {
 		fn [<read_$ty>](
 			&mut self,
 		) -> Result<$ty, BytesError> {
-			self.len_check($bytes)?;
+			let mut data = [0u8; $bytes];
+			$crate::fill(self, &mut data)?;
+			Ok($ty::from_be_bytes(data))
+		}
 
+		fn [<read_$ty _le>](
+			&mut self,
+		) -> Result<$ty, BytesError> {
 			let mut data = [0u8; $bytes];
-			self.read_exact(&mut data)?;
+			$crate::fill(self, &mut data)?;
+			Ok($ty::from_le_bytes(data))
+		}
+		}
+	};
+}
+
+#[macro_export]
+macro_rules! define_write_async {
+	($ty:ty) => {
+		paste::item! {
+		async fn [<write_$ty>](
+			&mut self,
+			value: $ty,
+		) -> Result<(), BytesError> {
+			let bytes = value.to_be_bytes();
+			self.write_all(&bytes).await?;
+			Ok(())
+		}
+
+		async fn [<write_$ty _le>](
+			&mut self,
+			value: $ty,
+		) -> Result<(), BytesError> {
+			let bytes = value.to_le_bytes();
+			self.write_all(&bytes).await?;
+			Ok(())
+		}
+		}
+	};
+}
+
+#[macro_export]
+macro_rules! define_integral_r_async {
+	($ty:ty, $bytes:expr) => {
+		paste::item! {
+		async fn [<read_$ty>](
+			&mut self,
+		) -> Result<$ty, BytesError> {
+			let mut data = [0u8; $bytes];
+			$crate::async_ext::fill(self, &mut data).await?;
 			Ok($ty::from_be_bytes(data))
 		}
+
+		async fn [<read_$ty _le>](
+			&mut self,
+		) -> Result<$ty, BytesError> {
+			let mut data = [0u8; $bytes];
+			$crate::async_ext::fill(self, &mut data).await?;
+			Ok($ty::from_le_bytes(data))
+		}
 		}
 	};
 }
@@ -0,0 +1,31 @@
+use std::io::Read;
+
+/// Wraps a reader and counts how many bytes went through it,
+/// so errors from readers that can't seek still know where they happened.
+pub struct OffsetReader<R> {
+	inner: R,
+	offset: u64,
+}
+
+impl<R: Read> OffsetReader<R> {
+	pub fn new(inner: R) -> Self {
+		Self { inner, offset: 0 }
+	}
+
+	/// Bytes read so far.
+	pub fn offset(&self) -> u64 {
+		self.offset
+	}
+
+	pub fn into_inner(self) -> R {
+		self.inner
+	}
+}
+
+impl<R: Read> Read for OffsetReader<R> {
+	fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+		let n = self.inner.read(buf)?;
+		self.offset += n as u64;
+		Ok(n)
+	}
+}
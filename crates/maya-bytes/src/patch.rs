@@ -0,0 +1,111 @@
+use std::{
+	io::{Seek, SeekFrom, Write},
+	marker::PhantomData,
+};
+
+use crate::BytesError;
+
+/// A spot reserved by [`PatchWriter`] that still needs its real value.
+#[must_use = "a reserved spot should be patched"]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Patch<T> {
+	position: u64,
+	_ty: PhantomData<T>,
+}
+
+impl<T> Patch<T> {
+	/// Stream position of the reserved bytes.
+	pub fn position(&self) -> u64 {
+		self.position
+	}
+}
+
+pub trait Patchable: Copy + sealed::Sealed {
+	#[doc(hidden)]
+	const SIZE: usize;
+	#[doc(hidden)]
+	fn write_be<W: Write>(self, writer: &mut W) -> std::io::Result<()>;
+}
+
+mod sealed {
+	pub trait Sealed {}
+}
+
+macro_rules! impl_patchable {
+	($($ty:ty),*) => {
+		$(
+		impl sealed::Sealed for $ty {}
+		impl Patchable for $ty {
+			const SIZE: usize = size_of::<$ty>();
+
+			fn write_be<W: Write>(self, writer: &mut W) -> std::io::Result<()> {
+				writer.write_all(&self.to_be_bytes())
+			}
+		}
+		)*
+	};
+}
+
+impl_patchable!(u8, u16, u32, i8, i16, i32);
+
+/// Wraps a seekable writer so values that aren't known yet (lengths, branch offsets)
+/// can be reserved, written past, and filled in afterwards in a single pass.
+pub struct PatchWriter<W> {
+	inner: W,
+}
+
+impl<W: Write + Seek> PatchWriter<W> {
+	pub fn new(inner: W) -> Self {
+		Self { inner }
+	}
+
+	pub fn position(&mut self) -> Result<u64, BytesError> {
+		Ok(self.inner.stream_position()?)
+	}
+
+	/// Writes zeroes where a `T` will go and returns a handle to patch it later.
+	pub fn reserve<T: Patchable>(&mut self) -> Result<Patch<T>, BytesError> {
+		let position = self.position()?;
+		self.inner.write_all(&[0u8; 8][..T::SIZE])?;
+		Ok(Patch {
+			position,
+			_ty: PhantomData,
+		})
+	}
+
+	pub fn reserve_u16(&mut self) -> Result<Patch<u16>, BytesError> {
+		self.reserve()
+	}
+
+	pub fn reserve_u32(&mut self) -> Result<Patch<u32>, BytesError> {
+		self.reserve()
+	}
+
+	/// Fills in a reserved spot, leaving the stream position where it was.
+	pub fn patch<T: Patchable>(&mut self, handle: Patch<T>, value: T) -> Result<(), BytesError> {
+		let current = self.position()?;
+		self.inner.seek(SeekFrom::Start(handle.position))?;
+		value.write_be(&mut self.inner)?;
+		self.inner.seek(SeekFrom::Start(current))?;
+		Ok(())
+	}
+
+	/// Bytes written since `handle`'s reserved spot ended, e.g. the length of a body that follows a length field.
+	pub fn written_since<T: Patchable>(&mut self, handle: Patch<T>) -> Result<u64, BytesError> {
+		Ok(self.position()? - handle.position - T::SIZE as u64)
+	}
+
+	pub fn into_inner(self) -> W {
+		self.inner
+	}
+}
+
+impl<W: Write> Write for PatchWriter<W> {
+	fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+		self.inner.write(buf)
+	}
+
+	fn flush(&mut self) -> std::io::Result<()> {
+		self.inner.flush()
+	}
+}
@@ -0,0 +1,321 @@
+use maya_bytes::*;
+
+use crate::{
+	class_pool::IOCpTag,
+	lazy::{self, LazyAttribute, LazyMember},
+	options::ReadOptions,
+	IOAttributeInfo, IOClassFile, IOClassfileError, IOFieldInfo, IOMethodInfo,
+};
+
+/// An [`IOClassFile`] that borrows Utf8 constants and attribute bodies from the input
+/// instead of copying each of them into its own allocation.
+#[derive(Debug)]
+pub struct IOClassFileRef<'a> {
+	pub magic: u32,
+	pub minor_version: u16,
+	pub major_version: u16,
+	pub cp: Vec<CpEntryRef<'a>>,
+	pub access_flags: u16,
+	pub this_class: u16,
+	pub super_class: u16,
+	pub interfaces: Vec<u16>,
+	pub fields: Vec<MemberRef<'a>>,
+	pub methods: Vec<MemberRef<'a>>,
+	pub attributes: Vec<AttributeRef<'a>>,
+	/// Whatever follows the last attribute.
+	pub trailing: &'a [u8],
+}
+
+/// Utf8 constants are the only ones that own heap data, every other tag is kept as is.
+#[derive(Debug)]
+pub enum CpEntryRef<'a> {
+	Utf8(&'a [u8]),
+	Other(IOCpTag),
+}
+
+#[derive(Debug)]
+pub struct MemberRef<'a> {
+	pub access_flags: u16,
+	pub name_index: u16,
+	pub descriptor_index: u16,
+	pub attributes: Vec<AttributeRef<'a>>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct AttributeRef<'a> {
+	pub attribute_name_index: u16,
+	pub info: &'a [u8],
+}
+
+impl IOClassFile {
+	/// Parses without copying Utf8 constants or attribute bodies, see [`IOClassFileRef`].
+	pub fn read_borrowed(data: &[u8]) -> Result<IOClassFileRef<'_>, IOClassfileError> {
+		Self::read_borrowed_with(data, &ReadOptions::whole_input())
+	}
+
+	/// [`read_borrowed`](Self::read_borrowed) following `options`, except for `sections`: every
+	/// table is read, borrowing its attribute bodies costs no more than skipping them.
+	pub fn read_borrowed_with<'a>(
+		data: &'a [u8],
+		options: &ReadOptions,
+	) -> Result<IOClassFileRef<'a>, IOClassfileError> {
+		let mut buffer = data;
+		maya_bytes::with_max_alloc(Some(options.limits.max_alloc), || {
+			IOClassFileRef::read_inner(data, &mut buffer, options)
+		})
+		.map_err(|e| e.at((data.len() - buffer.len()) as u64))
+	}
+}
+
+impl<'a> IOClassFileRef<'a> {
+	fn read_inner(data: &'a [u8], buffer: &mut &'a [u8], options: &ReadOptions) -> Result<Self, IOClassfileError> {
+		let (magic, minor_version, major_version, cp_count) = lazy::read_header(buffer, options)?;
+		let slots = (cp_count as usize).saturating_sub(1);
+		let mut cp = maya_bytes::vec_with_capacity(slots)?;
+		while cp.len() < slots {
+			if buffer.first() == Some(&1) {
+				buffer.read_u8()?;
+				let len = buffer.read_u16()? as usize;
+				if buffer.len() < len {
+					let available = buffer.len() as u64;
+					*buffer = &[];
+					return Err(BytesError::not_enough_data(len as u64, available).into());
+				}
+
+				let (bytes, rest) = buffer.split_at(len);
+				*buffer = rest;
+				cp.push(CpEntryRef::Utf8(bytes));
+				continue;
+			}
+
+			let tag = IOCpTag::read(buffer)?;
+			let wide = tag.is_wide();
+			cp.push(CpEntryRef::Other(tag));
+			if wide {
+				cp.push(CpEntryRef::Other(IOCpTag::Unusable));
+			}
+		}
+		let access_flags = buffer.read_u16()?;
+		let this_class = buffer.read_u16()?;
+		let super_class = buffer.read_u16()?;
+		let interface_count = buffer.read_u16()?;
+		let interfaces = buffer.read_u16_vec(interface_count as usize)?;
+
+		let attributes = |attrs: Vec<LazyAttribute>| {
+			attrs
+				.into_iter()
+				.map(|a| AttributeRef {
+					attribute_name_index: a.attribute_name_index,
+					info: &data[a.offset..a.offset + a.length as usize],
+				})
+				.collect::<Vec<_>>()
+		};
+		let members = |members: Vec<LazyMember>| {
+			members
+				.into_iter()
+				.map(|m| MemberRef {
+					access_flags: m.access_flags,
+					name_index: m.name_index,
+					descriptor_index: m.descriptor_index,
+					attributes: attributes(m.attributes),
+				})
+				.collect::<Vec<_>>()
+		};
+		let limits = &options.limits;
+		let fields = members(LazyMember::read_all(data, buffer, "field_count", limits)?);
+		let methods = members(LazyMember::read_all(data, buffer, "method_count", limits)?);
+		let attributes = attributes(LazyAttribute::read_all(data, buffer, limits)?);
+		if !options.keep_trailing {
+			*buffer = &[];
+		}
+
+		Ok(Self {
+			magic,
+			minor_version,
+			major_version,
+			cp,
+			access_flags,
+			this_class,
+			super_class,
+			interfaces,
+			fields,
+			methods,
+			attributes,
+			trailing: buffer,
+		})
+	}
+
+	/// The Utf8 constant at `index`, if it is one.
+	pub fn utf8(&self, index: u16) -> Option<&'a [u8]> {
+		match self.cp.get((index as usize).checked_sub(1)?)? {
+			CpEntryRef::Utf8(bytes) => Some(bytes),
+			CpEntryRef::Other(_) => None,
+		}
+	}
+
+	/// Copies everything out into an owned [`IOClassFile`].
+	pub fn to_owned(&self) -> IOClassFile {
+		let attributes = |attrs: &[AttributeRef]| {
+			attrs
+				.iter()
+				.map(|a| IOAttributeInfo {
+					attribute_name_index: a.attribute_name_index,
+					attribute_length: a.info.len() as u32,
+					info: a.info.to_vec(),
+				})
+				.collect::<Vec<_>>()
+		};
+		let cp = self
+			.cp
+			.iter()
+			.map(|entry| match entry {
+				CpEntryRef::Utf8(bytes) => IOCpTag::Utf8 {
+					length: bytes.len() as u16,
+					bytes: bytes.to_vec(),
+				},
+				CpEntryRef::Other(tag) => tag.clone(),
+			})
+			.collect::<Vec<_>>();
+		let fields = self
+			.fields
+			.iter()
+			.map(|f| IOFieldInfo {
+				access_flags: f.access_flags,
+				name_index: f.name_index,
+				descriptor_index: f.descriptor_index,
+				attributes_count: f.attributes.len() as u16,
+				attributes: attributes(&f.attributes),
+			})
+			.collect::<Vec<_>>();
+		let methods = self
+			.methods
+			.iter()
+			.map(|m| IOMethodInfo {
+				access_flags: m.access_flags,
+				name_index: m.name_index,
+				descriptor_index: m.descriptor_index,
+				attributes_count: m.attributes.len() as u16,
+				attributes: attributes(&m.attributes),
+			})
+			.collect::<Vec<_>>();
+		let class_attributes = attributes(&self.attributes);
+
+		IOClassFile {
+			magic: self.magic,
+			minor_version: self.minor_version,
+			major_version: self.major_version,
+			cp_count: cp.len() as u16 + 1,
+			cp,
+			access_flags: self.access_flags,
+			this_class: self.this_class,
+			super_class: self.super_class,
+			interface_count: self.interfaces.len() as u16,
+			interfaces: self.interfaces.clone(),
+			field_count: fields.len() as u16,
+			fields,
+			method_count: methods.len() as u16,
+			methods,
+			attribute_count: class_attributes.len() as u16,
+			attributes: class_attributes,
+			skipped: Vec::new(),
+			trailing: self.trailing.to_vec(),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const HELLO: &[u8] = include_bytes!("../../maya-test-bin/data/out/a/a/Hello.class");
+
+	#[test]
+	fn same_as_read() {
+		let class = IOClassFile::read_borrowed(HELLO).unwrap();
+		assert_eq!(class.cp.len(), IOClassFile::read_from_slice(HELLO).unwrap().cp.len());
+		let mut out = Vec::new();
+		class.to_owned().write(&mut out).unwrap();
+		assert_eq!(out, HELLO);
+	}
+
+	#[test]
+	fn empty_constant_pool() {
+		// a constant_pool_count of 0 is malformed, but mustn't underflow the slot count
+		let mut class = vec![0xCA, 0xFE, 0xBA, 0xBE, 0, 0, 0, 52, 0, 0];
+		class.extend([0; 14]);
+		assert!(IOClassFile::read_borrowed(&class).unwrap().cp.is_empty());
+		assert!(IOClassFile::read_lazy(&class).unwrap().cp.is_empty());
+	}
+
+	#[test]
+	fn truncated_utf8() {
+		let class = [0xCA, 0xFE, 0xBA, 0xBE, 0, 0, 0, 52, 0, 2, 1, 0, 5, b'a'];
+		let error = IOClassFile::read_borrowed(&class).unwrap_err();
+		assert!(matches!(error, IOClassfileError::Bytes(_)), "{error}");
+	}
+
+	#[test]
+	fn options() {
+		let mut patched = vec![0xAA, 0xBB];
+		patched.extend_from_slice(HELLO);
+		patched[2..6].copy_from_slice(&[0xDE, 0xAD, 0xBE, 0xEF]);
+		assert!(matches!(
+			IOClassFile::read_borrowed(&patched[2..]),
+			Err(IOClassfileError::InvalidMagic(0xDEADBEEF))
+		));
+		let options = ReadOptions {
+			require_magic: false,
+			skip_prefix: 2,
+			..ReadOptions::default()
+		};
+		let class = IOClassFile::read_borrowed_with(&patched, &options).unwrap();
+		assert_eq!(class.to_owned().magic, 0xDEADBEEF);
+		assert_eq!(
+			class.methods.len(),
+			IOClassFile::read_borrowed(HELLO).unwrap().methods.len()
+		);
+		let lazy = IOClassFile::read_lazy_with(&patched, &options).unwrap();
+		assert_eq!(lazy.attribute_bytes(&lazy.attributes[0]), class.attributes[0].info);
+	}
+
+	#[test]
+	fn limits() {
+		let mut options = ReadOptions::default();
+		options.limits.max_cp_entries = 8;
+		for error in [
+			IOClassFile::read_borrowed_with(HELLO, &options).unwrap_err(),
+			IOClassFile::read_lazy_with(HELLO, &options).unwrap_err(),
+		] {
+			assert!(
+				matches!(
+					error,
+					IOClassfileError::LimitExceeded {
+						what: "constant pool size",
+						limit: 8,
+						..
+					}
+				),
+				"{error}"
+			);
+		}
+
+		let mut options = ReadOptions::default();
+		options.limits.max_attribute_size = 16;
+		assert!(matches!(
+			IOClassFile::read_lazy_with(HELLO, &options),
+			Err(IOClassfileError::LimitExceeded {
+				what: "attribute_length",
+				..
+			})
+		));
+		let mut options = ReadOptions::default();
+		options.limits.max_members = 1;
+		assert!(matches!(
+			IOClassFile::read_borrowed_with(HELLO, &options),
+			Err(IOClassfileError::LimitExceeded {
+				what: "method_count",
+				..
+			})
+		));
+	}
+}
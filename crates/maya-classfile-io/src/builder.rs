@@ -0,0 +1,216 @@
+use crate::{class_pool::IOCpTag, validate::ValidationError, IOAttributeInfo, IOClassFile, IOFieldInfo, IOMethodInfo};
+
+/// Assembles an [`IOClassFile`] without having to keep the count and length fields in sync by hand.
+/// They are all recomputed in [`build`](Self::build), whatever the pushed values say.
+#[derive(Debug, Default)]
+pub struct IOClassFileBuilder {
+	minor_version: u16,
+	major_version: u16,
+	cp: Vec<IOCpTag>,
+	access_flags: u16,
+	this_class: u16,
+	super_class: u16,
+	interfaces: Vec<u16>,
+	fields: Vec<IOFieldInfo>,
+	methods: Vec<IOMethodInfo>,
+	attributes: Vec<IOAttributeInfo>,
+}
+
+impl IOClassFileBuilder {
+	pub fn new(major_version: u16, minor_version: u16) -> Self {
+		Self {
+			major_version,
+			minor_version,
+			..Default::default()
+		}
+	}
+
+	/// Appends a constant pool entry and returns its index. Wide entries get their unusable slot added too.
+	pub fn push_cp(&mut self, tag: IOCpTag) -> u16 {
+		let wide = tag.is_wide();
+		self.cp.push(tag);
+		let index = self.cp.len() as u16;
+		if wide {
+			self.cp.push(IOCpTag::Unusable);
+		}
+		index
+	}
+
+	pub fn utf8(&mut self, string: &str) -> u16 {
+		let bytes = maya_mutf8::encode(string);
+		self.push_cp(IOCpTag::Utf8 { length: 0, bytes })
+	}
+
+	pub fn class(&mut self, name: &str) -> u16 {
+		let name_index = self.utf8(name);
+		self.push_cp(IOCpTag::Class { name_index })
+	}
+
+	pub fn access_flags(&mut self, access_flags: u16) -> &mut Self {
+		self.access_flags = access_flags;
+		self
+	}
+
+	pub fn this_class(&mut self, index: u16) -> &mut Self {
+		self.this_class = index;
+		self
+	}
+
+	pub fn super_class(&mut self, index: u16) -> &mut Self {
+		self.super_class = index;
+		self
+	}
+
+	pub fn interface(&mut self, index: u16) -> &mut Self {
+		self.interfaces.push(index);
+		self
+	}
+
+	pub fn field(&mut self, field: IOFieldInfo) -> &mut Self {
+		self.fields.push(field);
+		self
+	}
+
+	pub fn method(&mut self, method: IOMethodInfo) -> &mut Self {
+		self.methods.push(method);
+		self
+	}
+
+	pub fn attribute(&mut self, attribute: IOAttributeInfo) -> &mut Self {
+		self.attributes.push(attribute);
+		self
+	}
+
+	/// Fills in every count and length, then [`validate`](IOClassFile::validate)s the result.
+	pub fn build(self) -> Result<IOClassFile, ValidationError> {
+		let mut cp = self.cp;
+		for tag in &mut cp {
+			if let IOCpTag::Utf8 { length, bytes } = tag {
+				*length = fit("Utf8 length", bytes.len())?;
+			}
+		}
+
+		let mut fields = self.fields;
+		for field in &mut fields {
+			field.attributes_count = fit_attributes(&mut field.attributes)?;
+		}
+		let mut methods = self.methods;
+		for method in &mut methods {
+			method.attributes_count = fit_attributes(&mut method.attributes)?;
+		}
+		let mut attributes = self.attributes;
+
+		let class = IOClassFile {
+			magic: 0xCAFEBABE,
+			minor_version: self.minor_version,
+			major_version: self.major_version,
+			cp_count: fit("cp_count", cp.len() + 1)?,
+			cp,
+			access_flags: self.access_flags,
+			this_class: self.this_class,
+			super_class: self.super_class,
+			interface_count: fit("interface_count", self.interfaces.len())?,
+			interfaces: self.interfaces,
+			field_count: fit("field_count", fields.len())?,
+			fields,
+			method_count: fit("method_count", methods.len())?,
+			methods,
+			attribute_count: fit_attributes(&mut attributes)?,
+			attributes,
+			skipped: Vec::new(),
+			trailing: Vec::new(),
+		};
+		class.validate()?;
+		Ok(class)
+	}
+}
+
+fn fit(what: &'static str, len: usize) -> Result<u16, ValidationError> {
+	u16::try_from(len).map_err(|_| ValidationError::TooMany { what, actual: len })
+}
+
+fn fit_attributes(attributes: &mut [IOAttributeInfo]) -> Result<u16, ValidationError> {
+	for attr in attributes.iter_mut() {
+		attr.attribute_length = u32::try_from(attr.info.len()).map_err(|_| ValidationError::TooMany {
+			what: "attribute_length",
+			actual: attr.info.len(),
+		})?;
+	}
+	fit("attributes_count", attributes.len())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn counts_are_filled_in() {
+		let mut builder = IOClassFileBuilder::new(61, 0);
+		let this_class = builder.class("a/B");
+		let super_class = builder.class("java/lang/Object");
+		let iface = builder.class("java/lang/Runnable");
+		let long = builder.push_cp(IOCpTag::Long { bytes: [0; 8] });
+		let name = builder.utf8("x");
+		let descriptor = builder.utf8("J");
+		let constant_value = builder.utf8("ConstantValue");
+		builder
+			.access_flags(0x0021)
+			.this_class(this_class)
+			.super_class(super_class)
+			.interface(iface)
+			.field(IOFieldInfo {
+				access_flags: 0x0018,
+				name_index: name,
+				descriptor_index: descriptor,
+				// wrong on purpose, build recomputes it
+				attributes_count: 7,
+				attributes: vec![IOAttributeInfo {
+					attribute_name_index: constant_value,
+					attribute_length: 0,
+					info: long.to_be_bytes().to_vec(),
+				}],
+			});
+		let class = builder.build().unwrap();
+
+		assert_eq!(long, 7);
+		assert!(matches!(class.cp[long as usize], IOCpTag::Unusable));
+		assert_eq!(class.cp_count as usize, class.cp.len() + 1);
+		assert!(matches!(class.cp[0], IOCpTag::Utf8 { length: 3, .. }));
+		assert_eq!(class.interface_count, 1);
+		assert_eq!(class.field_count, 1);
+		assert_eq!(class.fields[0].attributes_count, 1);
+		assert_eq!(class.fields[0].attributes[0].attribute_length, 2);
+		assert_eq!(class.method_count, 0);
+
+		let bytes = class.to_bytes().unwrap();
+		let read = IOClassFile::read_from_slice(&bytes).unwrap();
+		assert_eq!(read.this_class_name().unwrap(), "a/B");
+		assert_eq!(read.class_name_at(read.interfaces[0]).unwrap(), "java/lang/Runnable");
+	}
+
+	#[test]
+	fn invalid_indices_fail() {
+		let mut builder = IOClassFileBuilder::new(61, 0);
+		builder.this_class(1);
+		assert_eq!(
+			builder.build().unwrap_err(),
+			ValidationError::BadIndex {
+				what: "this_class".to_string(),
+				index: 1,
+			}
+		);
+
+		let mut builder = IOClassFileBuilder::new(61, 0);
+		let iface = builder.class("java/lang/Runnable");
+		for _ in 0..=u16::MAX as usize {
+			builder.interface(iface);
+		}
+		assert_eq!(
+			builder.build().unwrap_err(),
+			ValidationError::TooMany {
+				what: "interface_count",
+				actual: 65536,
+			}
+		);
+	}
+}
@@ -1,10 +1,14 @@
 use maya_bytes::*;
 
-use crate::IOClassfileError;
+use crate::{round_trip::RoundTripError, IOClassfileError, Structure};
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(u8)]
 pub enum IOCpTag {
+	/// The unusable slot following a Long or Double (see below). It has no bytes in the file,
+	/// but keeping it in the pool means `cp[index - 1]` is always the entry at `index`.
+	Unusable = 0,
 	// https://docs.oracle.com/javase/specs/jvms/se7/html/jvms-4.html#jvms-4.4.7
 	Utf8 {
 		length: u16,
@@ -62,6 +66,11 @@ pub enum IOCpTag {
 	MethodType {
 		descriptor_index: u16,
 	} = 16,
+	// https://docs.oracle.com/javase/specs/jvms/se21/html/jvms-4.html#jvms-4.4.10
+	Dynamic {
+		bootstrap_method_attr_index: u16,
+		name_and_ty_index: u16,
+	} = 17,
 	// https://docs.oracle.com/javase/specs/jvms/se7/html/jvms-4.html#jvms-4.4.10
 	InvokeDynamic {
 		bootstrap_method_attr_index: u16,
@@ -78,125 +87,158 @@ pub enum IOCpTag {
 impl IOCpTag {
 	pub fn read<B: BytesReadExt>(buffer: &mut B) -> Result<IOCpTag, IOClassfileError> {
 		let tag = buffer.read_u8()?;
-		match tag {
-			1 => {
+		let Some(kind) = CpTagKind::from_tag(tag) else {
+			return Err(IOClassfileError::UnknownCpTag { tag, offset: None });
+		};
+
+		match kind {
+			CpTagKind::Unusable => unreachable!("from_tag never returns Unusable"),
+			CpTagKind::Utf8 => {
 				let len = buffer.read_u16()?;
-				let mut bytes = Vec::with_capacity(len as usize);
-				for _ in 0..len {
-					bytes.push(buffer.read_u8()?);
-				}
+				let bytes = buffer.read_n_bytes_vec(len as usize)?;
 
 				Ok(IOCpTag::Utf8 { length: len, bytes })
 			}
-			3 => Ok(IOCpTag::Integer {
+			CpTagKind::Integer => Ok(IOCpTag::Integer {
 				bytes: buffer.read_n_bytes::<4>()?,
 			}),
-			4 => Ok(IOCpTag::Float {
+			CpTagKind::Float => Ok(IOCpTag::Float {
 				bytes: buffer.read_n_bytes::<4>()?,
 			}),
-			5 => Ok(IOCpTag::Long {
+			CpTagKind::Long => Ok(IOCpTag::Long {
 				bytes: buffer.read_n_bytes::<8>()?,
 				// high_bytes: buffer.read_n_bytes::<4>()?,
 				// low_bytes: buffer.read_n_bytes::<4>()?,
 			}),
-			6 => Ok(IOCpTag::Double {
+			CpTagKind::Double => Ok(IOCpTag::Double {
 				bytes: buffer.read_n_bytes::<8>()?,
 				// high_bytes: buffer.read_n_bytes::<4>()?,
 				// low_bytes: buffer.read_n_bytes::<4>()?,
 			}),
-			7 => Ok(IOCpTag::Class {
+			CpTagKind::Class => Ok(IOCpTag::Class {
 				name_index: buffer.read_u16()?,
 			}),
-			8 => Ok(IOCpTag::String {
+			CpTagKind::String => Ok(IOCpTag::String {
 				utf8_index: buffer.read_u16()?,
 			}),
-			9 => Ok(IOCpTag::FieldRef {
+			CpTagKind::FieldRef => Ok(IOCpTag::FieldRef {
 				class_index: buffer.read_u16()?,
 				name_and_ty_index: buffer.read_u16()?,
 			}),
-			10 => Ok(IOCpTag::MethodRef {
+			CpTagKind::MethodRef => Ok(IOCpTag::MethodRef {
 				class_index: buffer.read_u16()?,
 				name_and_ty_index: buffer.read_u16()?,
 			}),
-			11 => Ok(IOCpTag::InterfaceMethodRef {
+			CpTagKind::InterfaceMethodRef => Ok(IOCpTag::InterfaceMethodRef {
 				class_index: buffer.read_u16()?,
 				name_and_ty_index: buffer.read_u16()?,
 			}),
-			12 => Ok(IOCpTag::NameAndType {
+			CpTagKind::NameAndType => Ok(IOCpTag::NameAndType {
 				name_index: buffer.read_u16()?,
 				descriptor_index: buffer.read_u16()?,
 			}),
-			15 => Ok(IOCpTag::MethodHandle {
+			CpTagKind::MethodHandle => Ok(IOCpTag::MethodHandle {
 				reference_kind: buffer.read_u8()?,
 				reference_index: buffer.read_u16()?,
 			}),
-			16 => Ok(IOCpTag::MethodType {
+			CpTagKind::MethodType => Ok(IOCpTag::MethodType {
 				descriptor_index: buffer.read_u16()?,
 			}),
-			18 => Ok(IOCpTag::InvokeDynamic {
+			CpTagKind::Dynamic => Ok(IOCpTag::Dynamic {
 				bootstrap_method_attr_index: buffer.read_u16()?,
 				name_and_ty_index: buffer.read_u16()?,
 			}),
-			19 => Ok(IOCpTag::Module {
+			CpTagKind::InvokeDynamic => Ok(IOCpTag::InvokeDynamic {
+				bootstrap_method_attr_index: buffer.read_u16()?,
+				name_and_ty_index: buffer.read_u16()?,
+			}),
+			CpTagKind::Module => Ok(IOCpTag::Module {
 				name_index: buffer.read_u16()?,
 			}),
-			20 => Ok(IOCpTag::Package {
+			CpTagKind::Package => Ok(IOCpTag::Package {
 				name_index: buffer.read_u16()?,
 			}),
-			_ => unimplemented!("unimplemented tag: {tag}"),
+		}
+	}
+
+	/// Reads the `cp_count - 1` slots of a constant pool, filling in [`IOCpTag::Unusable`] after wide entries.
+	pub fn read_pool<B: BytesReadExt>(buffer: &mut B, cp_count: u16) -> Result<Vec<IOCpTag>, IOClassfileError> {
+		let slots = (cp_count as usize).saturating_sub(1);
+		let mut cp = maya_bytes::vec_with_capacity(slots)?;
+		while cp.len() < slots {
+			let index = cp.len() as u16 + 1;
+			let tag = IOCpTag::read(buffer).map_err(|e| e.within(Structure::CpEntry(index)))?;
+			let wide = tag.is_wide();
+			cp.push(tag);
+			if wide {
+				cp.push(IOCpTag::Unusable);
+			}
+		}
+		Ok(cp)
+	}
+
+	/// Whether this entry takes up two constant pool slots.
+	pub fn is_wide(&self) -> bool {
+		self.kind().is_wide()
+	}
+
+	pub fn kind(&self) -> CpTagKind {
+		match self {
+			IOCpTag::Unusable => CpTagKind::Unusable,
+			IOCpTag::Utf8 { .. } => CpTagKind::Utf8,
+			IOCpTag::Integer { .. } => CpTagKind::Integer,
+			IOCpTag::Float { .. } => CpTagKind::Float,
+			IOCpTag::Long { .. } => CpTagKind::Long,
+			IOCpTag::Double { .. } => CpTagKind::Double,
+			IOCpTag::Class { .. } => CpTagKind::Class,
+			IOCpTag::String { .. } => CpTagKind::String,
+			IOCpTag::FieldRef { .. } => CpTagKind::FieldRef,
+			IOCpTag::MethodRef { .. } => CpTagKind::MethodRef,
+			IOCpTag::InterfaceMethodRef { .. } => CpTagKind::InterfaceMethodRef,
+			IOCpTag::NameAndType { .. } => CpTagKind::NameAndType,
+			IOCpTag::MethodHandle { .. } => CpTagKind::MethodHandle,
+			IOCpTag::MethodType { .. } => CpTagKind::MethodType,
+			IOCpTag::Dynamic { .. } => CpTagKind::Dynamic,
+			IOCpTag::InvokeDynamic { .. } => CpTagKind::InvokeDynamic,
+			IOCpTag::Module { .. } => CpTagKind::Module,
+			IOCpTag::Package { .. } => CpTagKind::Package,
 		}
 	}
 
 	pub fn id(&self) -> u8 {
+		self.kind().id()
+	}
+
+	/// Bytes this entry takes up in the file, tag included.
+	pub fn byte_len(&self) -> u64 {
 		match self {
-			IOCpTag::Utf8 { length: _, bytes: _ } => 1,
-			IOCpTag::Integer { bytes: _ } => 3,
-			IOCpTag::Float { bytes: _ } => 4,
-			IOCpTag::Long {
-				bytes: _,
-				// high_bytes: _,
-				// low_bytes: _,
-			} => 5,
-			IOCpTag::Double {
-				bytes: _,
-				// high_bytes: _,
-				// low_bytes: _,
-			} => 6,
-			IOCpTag::Class { name_index: _ } => 7,
-			IOCpTag::String { utf8_index: _ } => 8,
-			IOCpTag::FieldRef {
-				class_index: _,
-				name_and_ty_index: _,
-			} => 9,
-			IOCpTag::MethodRef {
-				class_index: _,
-				name_and_ty_index: _,
-			} => 10,
-			IOCpTag::InterfaceMethodRef {
-				class_index: _,
-				name_and_ty_index: _,
-			} => 11,
-			IOCpTag::NameAndType {
-				name_index: _,
-				descriptor_index: _,
-			} => 12,
-			IOCpTag::MethodHandle {
-				reference_kind: _,
-				reference_index: _,
-			} => 15,
-			IOCpTag::MethodType { descriptor_index: _ } => 16,
-			IOCpTag::InvokeDynamic {
-				bootstrap_method_attr_index: _,
-				name_and_ty_index: _,
-			} => 18,
-			Self::Module { name_index: _ } => 19,
-			Self::Package { name_index: _ } => 19,
+			IOCpTag::Unusable => 0,
+			IOCpTag::Utf8 { bytes, .. } => 3 + bytes.len() as u64,
+			IOCpTag::Integer { .. } | IOCpTag::Float { .. } => 5,
+			IOCpTag::Long { .. } | IOCpTag::Double { .. } => 9,
+			IOCpTag::Class { .. }
+			| IOCpTag::String { .. }
+			| IOCpTag::MethodType { .. }
+			| IOCpTag::Module { .. }
+			| IOCpTag::Package { .. } => 3,
+			IOCpTag::MethodHandle { .. } => 4,
+			IOCpTag::FieldRef { .. }
+			| IOCpTag::MethodRef { .. }
+			| IOCpTag::InterfaceMethodRef { .. }
+			| IOCpTag::NameAndType { .. }
+			| IOCpTag::Dynamic { .. }
+			| IOCpTag::InvokeDynamic { .. } => 5,
 		}
 	}
 
 	pub fn write<B: BytesWriteExt>(&self, buffer: &mut B) -> Result<(), IOClassfileError> {
+		if let IOCpTag::Unusable = self {
+			return Ok(());
+		}
+
 		buffer.write_u8(self.id())?;
 		match self {
+			IOCpTag::Unusable => unreachable!(),
 			IOCpTag::Utf8 { length, bytes } => {
 				buffer.write_u16(*length)?;
 				buffer.write_all(bytes)?;
@@ -261,7 +303,11 @@ impl IOCpTag {
 			IOCpTag::MethodType { descriptor_index } => {
 				buffer.write_u16(*descriptor_index)?;
 			}
-			IOCpTag::InvokeDynamic {
+			IOCpTag::Dynamic {
+				bootstrap_method_attr_index,
+				name_and_ty_index: name_and_type_index,
+			}
+			| IOCpTag::InvokeDynamic {
 				bootstrap_method_attr_index,
 				name_and_ty_index: name_and_type_index,
 			} => {
@@ -278,3 +324,231 @@ impl IOCpTag {
 		Ok(())
 	}
 }
+
+/// The tag of a constant pool entry without its data. Reading, [`IOCpTag::id`] and writing all
+/// dispatch through this, so the tag bytes are only spelled out once.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[repr(u8)]
+pub enum CpTagKind {
+	Unusable = 0,
+	Utf8 = 1,
+	Integer = 3,
+	Float = 4,
+	Long = 5,
+	Double = 6,
+	Class = 7,
+	String = 8,
+	FieldRef = 9,
+	MethodRef = 10,
+	InterfaceMethodRef = 11,
+	NameAndType = 12,
+	MethodHandle = 15,
+	MethodType = 16,
+	Dynamic = 17,
+	InvokeDynamic = 18,
+	Module = 19,
+	Package = 20,
+}
+
+impl CpTagKind {
+	/// Every kind that can appear in a classfile, i.e. all but [`CpTagKind::Unusable`].
+	pub const ALL: [CpTagKind; 17] = [
+		Self::Utf8,
+		Self::Integer,
+		Self::Float,
+		Self::Long,
+		Self::Double,
+		Self::Class,
+		Self::String,
+		Self::FieldRef,
+		Self::MethodRef,
+		Self::InterfaceMethodRef,
+		Self::NameAndType,
+		Self::MethodHandle,
+		Self::MethodType,
+		Self::Dynamic,
+		Self::InvokeDynamic,
+		Self::Module,
+		Self::Package,
+	];
+
+	pub fn from_tag(tag: u8) -> Option<Self> {
+		Self::ALL.into_iter().find(|kind| kind.id() == tag)
+	}
+
+	pub const fn id(self) -> u8 {
+		self as u8
+	}
+
+	pub const fn is_wide(self) -> bool {
+		matches!(self, Self::Long | Self::Double)
+	}
+}
+
+/// Writes an entry of every [`CpTagKind`] and reads it back, failing if the kind or the bytes
+/// don't survive. Catches a tag byte drifting between reading and writing.
+pub fn round_trip_self_test() -> Result<(), RoundTripError> {
+	for kind in CpTagKind::ALL {
+		let tag = sample(kind);
+		let mut written = Vec::new();
+		tag.write(&mut written)?;
+
+		let read = IOCpTag::read(&mut written.as_slice())?;
+		if read.kind() != kind {
+			return Err(RoundTripError::KindMismatch {
+				expected: kind,
+				found: read.kind(),
+			});
+		}
+
+		let mut rewritten = Vec::new();
+		read.write(&mut rewritten)?;
+		if let Some(offset) = written.iter().zip(&rewritten).position(|(a, b)| a != b) {
+			return Err(RoundTripError::Mismatch {
+				offset,
+				expected: Some(written[offset]),
+				found: Some(rewritten[offset]),
+			});
+		}
+	}
+	Ok(())
+}
+
+fn sample(kind: CpTagKind) -> IOCpTag {
+	let (a, b) = (0x0102, 0x0304);
+	match kind {
+		CpTagKind::Unusable => IOCpTag::Unusable,
+		CpTagKind::Utf8 => IOCpTag::Utf8 {
+			length: 3,
+			bytes: b"abc".to_vec(),
+		},
+		CpTagKind::Integer => IOCpTag::Integer { bytes: [1, 2, 3, 4] },
+		CpTagKind::Float => IOCpTag::Float { bytes: [1, 2, 3, 4] },
+		CpTagKind::Long => IOCpTag::Long {
+			bytes: [1, 2, 3, 4, 5, 6, 7, 8],
+		},
+		CpTagKind::Double => IOCpTag::Double {
+			bytes: [1, 2, 3, 4, 5, 6, 7, 8],
+		},
+		CpTagKind::Class => IOCpTag::Class { name_index: a },
+		CpTagKind::String => IOCpTag::String { utf8_index: a },
+		CpTagKind::FieldRef => IOCpTag::FieldRef {
+			class_index: a,
+			name_and_ty_index: b,
+		},
+		CpTagKind::MethodRef => IOCpTag::MethodRef {
+			class_index: a,
+			name_and_ty_index: b,
+		},
+		CpTagKind::InterfaceMethodRef => IOCpTag::InterfaceMethodRef {
+			class_index: a,
+			name_and_ty_index: b,
+		},
+		CpTagKind::NameAndType => IOCpTag::NameAndType {
+			name_index: a,
+			descriptor_index: b,
+		},
+		CpTagKind::MethodHandle => IOCpTag::MethodHandle {
+			reference_kind: 5,
+			reference_index: a,
+		},
+		CpTagKind::MethodType => IOCpTag::MethodType { descriptor_index: a },
+		CpTagKind::Dynamic => IOCpTag::Dynamic {
+			bootstrap_method_attr_index: a,
+			name_and_ty_index: b,
+		},
+		CpTagKind::InvokeDynamic => IOCpTag::InvokeDynamic {
+			bootstrap_method_attr_index: a,
+			name_and_ty_index: b,
+		},
+		CpTagKind::Module => IOCpTag::Module { name_index: a },
+		CpTagKind::Package => IOCpTag::Package { name_index: a },
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn self_test() {
+		round_trip_self_test().unwrap();
+	}
+
+	#[test]
+	fn kinds() {
+		for kind in CpTagKind::ALL {
+			assert_eq!(CpTagKind::from_tag(kind.id()), Some(kind));
+			assert_eq!(sample(kind).kind(), kind);
+			assert_eq!(sample(kind).byte_len(), {
+				let mut out = Vec::new();
+				sample(kind).write(&mut out).unwrap();
+				out.len() as u64
+			});
+		}
+		assert_eq!(CpTagKind::from_tag(0), None);
+		assert_eq!(CpTagKind::from_tag(2), None);
+		assert_eq!(IOCpTag::Package { name_index: 1 }.id(), 20);
+	}
+
+	#[test]
+	fn wide_entries_take_two_slots() {
+		let mut data = vec![5];
+		data.extend_from_slice(&1234567890123i64.to_be_bytes());
+		data.push(6);
+		data.extend_from_slice(&3.5f64.to_be_bytes());
+		data.extend_from_slice(&[3, 0, 1, 0xE2, 0x40]);
+
+		let cp = IOCpTag::read_pool(&mut &data[..], 6).unwrap();
+		assert_eq!(
+			cp.iter().map(IOCpTag::kind).collect::<Vec<_>>(),
+			[
+				CpTagKind::Long,
+				CpTagKind::Unusable,
+				CpTagKind::Double,
+				CpTagKind::Unusable,
+				CpTagKind::Integer
+			]
+		);
+		assert_eq!(
+			cp[4],
+			IOCpTag::Integer {
+				bytes: 123456i32.to_be_bytes()
+			}
+		);
+
+		// the unusable slots have no bytes of their own
+		let mut out = Vec::new();
+		for tag in &cp {
+			tag.write(&mut out).unwrap();
+		}
+		assert_eq!(out, data);
+	}
+
+	#[test]
+	fn unknown_tags() {
+		for tag in 0..=u8::MAX {
+			if CpTagKind::from_tag(tag).is_some() {
+				continue;
+			}
+			assert!(matches!(
+				IOCpTag::read(&mut &[tag, 0, 1, 0, 1][..]),
+				Err(IOClassfileError::UnknownCpTag { tag: t, offset: None }) if t == tag
+			));
+		}
+
+		// an Integer, then a tag from some future JVM
+		let data = [3, 0, 0, 0, 1, 21, 0, 1];
+		let mut buffer = OffsetReader::new(&data[..]);
+		let error = IOCpTag::read_pool(&mut buffer, 3).unwrap_err().at(buffer.offset());
+		assert_eq!(error.structures(), [&Structure::CpEntry(2)]);
+		assert!(matches!(
+			error.root(),
+			IOClassfileError::UnknownCpTag {
+				tag: 21,
+				offset: Some(5)
+			}
+		));
+	}
+}
@@ -0,0 +1,285 @@
+use maya_bytes::*;
+
+use crate::{
+	class_pool::IOCpTag,
+	options::{Limits, ReadOptions},
+	sections, IOAttributeInfo, IOClassFile, IOClassfileError, IOFieldInfo, IOMethodInfo,
+};
+
+/// A classfile where only the constant pool and the member headers were parsed.
+/// Attribute bodies are recorded as offsets into the input and only copied out on request.
+#[derive(Debug)]
+pub struct LazyClassFile<'a> {
+	data: &'a [u8],
+	pub magic: u32,
+	pub minor_version: u16,
+	pub major_version: u16,
+	pub cp: Vec<IOCpTag>,
+	pub access_flags: u16,
+	pub this_class: u16,
+	pub super_class: u16,
+	pub interfaces: Vec<u16>,
+	pub fields: Vec<LazyMember>,
+	pub methods: Vec<LazyMember>,
+	pub attributes: Vec<LazyAttribute>,
+	/// Whatever follows the last attribute.
+	pub trailing: &'a [u8],
+}
+
+#[derive(Debug)]
+pub struct LazyMember {
+	pub access_flags: u16,
+	pub name_index: u16,
+	pub descriptor_index: u16,
+	pub attributes: Vec<LazyAttribute>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct LazyAttribute {
+	pub attribute_name_index: u16,
+	/// Offset of the attribute body (after the length) in the input.
+	pub offset: usize,
+	pub length: u32,
+}
+
+impl IOClassFile {
+	/// Parses everything but attribute bodies, see [`LazyClassFile`].
+	pub fn read_lazy(data: &[u8]) -> Result<LazyClassFile<'_>, IOClassfileError> {
+		Self::read_lazy_with(data, &ReadOptions::whole_input())
+	}
+
+	/// [`read_lazy`](Self::read_lazy) following `options`, except for `sections`: every table is
+	/// walked, as only the headers of its entries are kept anyway.
+	pub fn read_lazy_with<'a>(data: &'a [u8], options: &ReadOptions) -> Result<LazyClassFile<'a>, IOClassfileError> {
+		let mut buffer = data;
+		maya_bytes::with_max_alloc(Some(options.limits.max_alloc), || {
+			LazyClassFile::read_inner(data, &mut buffer, options)
+		})
+		.map_err(|e| e.at((data.len() - buffer.len()) as u64))
+	}
+}
+
+/// Skips the prefix and checks the magic, returning it along with the versions and
+/// `constant_pool_count`.
+pub(crate) fn read_header(buffer: &mut &[u8], options: &ReadOptions) -> Result<(u32, u16, u16, u16), IOClassfileError> {
+	sections::skip(buffer, options.skip_prefix as u64)?;
+	let magic = buffer.read_u32()?;
+	if options.require_magic && magic != 0xCAFEBABE {
+		return Err(IOClassfileError::InvalidMagic(magic));
+	}
+
+	let minor_version = buffer.read_u16()?;
+	let major_version = buffer.read_u16()?;
+	let cp_count = buffer.read_u16()?;
+	Limits::check(
+		"constant pool size",
+		cp_count.saturating_sub(1) as u64,
+		options.limits.max_cp_entries as u64,
+	)?;
+	Ok((magic, minor_version, major_version, cp_count))
+}
+
+impl<'a> LazyClassFile<'a> {
+	fn read_inner(data: &'a [u8], buffer: &mut &'a [u8], options: &ReadOptions) -> Result<Self, IOClassfileError> {
+		let (magic, minor_version, major_version, cp_count) = read_header(buffer, options)?;
+		let cp = IOCpTag::read_pool(buffer, cp_count)?;
+		let access_flags = buffer.read_u16()?;
+		let this_class = buffer.read_u16()?;
+		let super_class = buffer.read_u16()?;
+		let interface_count = buffer.read_u16()?;
+		let interfaces = buffer.read_u16_vec(interface_count as usize)?;
+		let limits = &options.limits;
+		let fields = LazyMember::read_all(data, buffer, "field_count", limits)?;
+		let methods = LazyMember::read_all(data, buffer, "method_count", limits)?;
+		let attributes = LazyAttribute::read_all(data, buffer, limits)?;
+		if !options.keep_trailing {
+			*buffer = &[];
+		}
+
+		Ok(Self {
+			data,
+			magic,
+			minor_version,
+			major_version,
+			cp,
+			access_flags,
+			this_class,
+			super_class,
+			interfaces,
+			fields,
+			methods,
+			attributes,
+			trailing: buffer,
+		})
+	}
+
+	/// The body of `attribute`, borrowed from the input.
+	pub fn attribute_bytes(&self, attribute: &LazyAttribute) -> &'a [u8] {
+		&self.data[attribute.offset..attribute.offset + attribute.length as usize]
+	}
+
+	pub fn attribute(&self, attribute: &LazyAttribute) -> IOAttributeInfo {
+		IOAttributeInfo {
+			attribute_name_index: attribute.attribute_name_index,
+			attribute_length: attribute.length,
+			info: self.attribute_bytes(attribute).to_vec(),
+		}
+	}
+
+	/// The bytes of the Utf8 constant at `index`, if it is one.
+	pub fn utf8(&self, index: u16) -> Option<&[u8]> {
+		match self.cp.get((index as usize).checked_sub(1)?)? {
+			IOCpTag::Utf8 { bytes, .. } => Some(bytes),
+			_ => None,
+		}
+	}
+
+	/// Copies out every attribute, giving the same result as [`IOClassFile::read`].
+	pub fn into_owned(self) -> IOClassFile {
+		let attributes = |attrs: &[LazyAttribute]| attrs.iter().map(|a| self.attribute(a)).collect::<Vec<_>>();
+		let fields = self
+			.fields
+			.iter()
+			.map(|f| IOFieldInfo {
+				access_flags: f.access_flags,
+				name_index: f.name_index,
+				descriptor_index: f.descriptor_index,
+				attributes_count: f.attributes.len() as u16,
+				attributes: attributes(&f.attributes),
+			})
+			.collect::<Vec<_>>();
+		let methods = self
+			.methods
+			.iter()
+			.map(|m| IOMethodInfo {
+				access_flags: m.access_flags,
+				name_index: m.name_index,
+				descriptor_index: m.descriptor_index,
+				attributes_count: m.attributes.len() as u16,
+				attributes: attributes(&m.attributes),
+			})
+			.collect::<Vec<_>>();
+		let class_attributes = attributes(&self.attributes);
+
+		IOClassFile {
+			magic: self.magic,
+			minor_version: self.minor_version,
+			major_version: self.major_version,
+			cp_count: self.cp.len() as u16 + 1,
+			access_flags: self.access_flags,
+			this_class: self.this_class,
+			super_class: self.super_class,
+			interface_count: self.interfaces.len() as u16,
+			interfaces: self.interfaces,
+			field_count: fields.len() as u16,
+			fields,
+			method_count: methods.len() as u16,
+			methods,
+			attribute_count: class_attributes.len() as u16,
+			attributes: class_attributes,
+			skipped: Vec::new(),
+			trailing: self.trailing.to_vec(),
+			cp: self.cp,
+		}
+	}
+}
+
+impl LazyMember {
+	pub(crate) fn read_all<'a>(
+		data: &'a [u8],
+		buffer: &mut &'a [u8],
+		what: &'static str,
+		limits: &Limits,
+	) -> Result<Vec<Self>, IOClassfileError> {
+		let count = buffer.read_u16()?;
+		Limits::check(what, count as u64, limits.max_members as u64)?;
+		let mut members = maya_bytes::vec_with_capacity(count as usize)?;
+		for _ in 0..count {
+			members.push(Self {
+				access_flags: buffer.read_u16()?,
+				name_index: buffer.read_u16()?,
+				descriptor_index: buffer.read_u16()?,
+				attributes: LazyAttribute::read_all(data, buffer, limits)?,
+			});
+		}
+		Ok(members)
+	}
+}
+
+impl LazyAttribute {
+	pub(crate) fn read_all<'a>(
+		data: &'a [u8],
+		buffer: &mut &'a [u8],
+		limits: &Limits,
+	) -> Result<Vec<Self>, IOClassfileError> {
+		let count = buffer.read_u16()?;
+		Limits::check("attributes_count", count as u64, limits.max_attributes as u64)?;
+		let mut attributes = maya_bytes::vec_with_capacity(count as usize)?;
+		for _ in 0..count {
+			let attribute_name_index = buffer.read_u16()?;
+			let length = buffer.read_u32()?;
+			Limits::check("attribute_length", length as u64, limits.max_attribute_size as u64)?;
+			let offset = data.len() - buffer.len();
+			if buffer.len() < length as usize {
+				let available = buffer.len() as u64;
+				// consume what's there, like a streaming read would, so the error offset works out
+				*buffer = &[];
+				return Err(BytesError::not_enough_data(length as u64, available).into());
+			}
+
+			*buffer = &buffer[length as usize..];
+			attributes.push(Self {
+				attribute_name_index,
+				offset,
+				length,
+			});
+		}
+		Ok(attributes)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const CLASSES: &[&[u8]] = &[
+		include_bytes!("../../maya-test-bin/data/out/a/a/Hello.class"),
+		include_bytes!("../../maya-test-bin/data/out/a/a/Constants.class"),
+		include_bytes!("../../maya-test-bin/data/out/a/module-info.class"),
+		include_bytes!("../../maya-test-bin/data/out/b/b/internal/Typed.class"),
+	];
+
+	fn written(class: IOClassFile) -> Vec<u8> {
+		let mut out = Vec::new();
+		class.write(&mut out).unwrap();
+		out
+	}
+
+	#[test]
+	fn into_owned_same_as_read() {
+		for bytes in CLASSES {
+			let lazy = IOClassFile::read_lazy(bytes).unwrap();
+			let owned = lazy.into_owned();
+			let read = IOClassFile::read_from_slice(bytes).unwrap();
+			assert_eq!(owned.magic, read.magic);
+			assert_eq!(written(owned), written(read));
+		}
+	}
+
+	#[test]
+	fn into_owned_keeps_magic() {
+		let mut patched = CLASSES[0].to_vec();
+		patched[..4].copy_from_slice(&[0xDE, 0xAD, 0xBE, 0xEF]);
+		let options = ReadOptions {
+			require_magic: false,
+			..ReadOptions::whole_input()
+		};
+		let lazy = IOClassFile::read_lazy_with(&patched, &options).unwrap();
+		assert_eq!(lazy.magic, 0xDEADBEEF);
+		let owned = lazy.into_owned();
+		let read = IOClassFile::read_with(&mut patched.as_slice(), &options).unwrap();
+		assert_eq!(owned.magic, read.magic);
+		assert_eq!(written(owned), patched);
+		assert_eq!(written(read), patched);
+	}
+}
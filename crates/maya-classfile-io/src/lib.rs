@@ -1,20 +1,84 @@
+pub mod borrowed;
+pub mod builder;
 pub mod class_pool;
+pub mod lazy;
+pub mod options;
+pub mod pool_builder;
+mod query;
+pub mod round_trip;
+pub mod sections;
+pub mod summary;
+pub mod validate;
+
+use std::fmt;
 
 use class_pool::IOCpTag;
 use maya_bytes::*;
+use options::{Limits, ReadOptions};
+use sections::{Section, SkippedSection};
 use thiserror::Error;
 
 #[derive(Debug, Error)]
 pub enum IOClassfileError {
-	#[error("First 4 bytes were not 0xCAFEBABE")]
-	InvalidMagic,
+	#[error("First 4 bytes were 0x{0:08X}, not 0xCAFEBABE")]
+	InvalidMagic(u32),
+	/// A tag from a newer JVM or a corrupt file. Constants don't store their length, so there's no
+	/// telling where one with an unknown tag ends and the pool can't be read past it.
+	#[error("Unknown constant pool tag {tag}{}", match .offset {
+		Some(offset) => format!(" at offset {offset}"),
+		None => String::new(),
+	})]
+	UnknownCpTag { tag: u8, offset: Option<u64> },
+	#[error("Constant pool index {0} is out of range or has the wrong type")]
+	BadCpIndex(u16),
+	#[error("Constant pool is full, it holds at most 65534 entries")]
+	CpFull,
+	#[error("{0}")]
+	Invalid(#[from] validate::ValidationError),
+	#[error("{0}")]
+	Mutf8(#[from] maya_mutf8::MUTFError),
 	#[error("{0}")]
 	Bytes(#[from] BytesError),
 	#[error("IO Error: {0}")]
 	IO(#[from] std::io::Error),
+	#[error("{what} is {value}, above the limit of {limit}")]
+	LimitExceeded { what: &'static str, value: u64, limit: u64 },
+	#[error("The {0:?} section was skipped while reading, load it before writing")]
+	SectionSkipped(Section),
+	#[error("{source}, in {structure}")]
+	In {
+		structure: Structure,
+		source: Box<IOClassfileError>,
+	},
+}
+
+/// The part of a classfile that was being read when an error happened.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Structure {
+	CpEntry(u16),
+	Field(u16),
+	Method(u16),
+	/// `name` is only known if the constant pool resolved `name_index` to a Utf8 entry.
+	Attribute {
+		name_index: u16,
+		name: Option<String>,
+	},
+}
+
+impl fmt::Display for Structure {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::CpEntry(index) => write!(f, "constant pool entry #{index}"),
+			Self::Field(index) => write!(f, "field #{index}"),
+			Self::Method(index) => write!(f, "method #{index}"),
+			Self::Attribute { name: Some(name), .. } => write!(f, "attribute `{name}`"),
+			Self::Attribute { name_index, name: None } => write!(f, "attribute with name index {name_index}"),
+		}
+	}
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct IOClassFile {
 	pub magic: u32,
 	pub minor_version: u16,
@@ -32,45 +96,170 @@ pub struct IOClassFile {
 	pub methods: Vec<IOMethodInfo>,
 	pub attribute_count: u16,
 	pub attributes: Vec<IOAttributeInfo>,
+	/// Tables left out by [`ReadSections`](sections::ReadSections), their Vecs are empty.
+	pub skipped: Vec<SkippedSection>,
+	/// Bytes after the last attribute, e.g. signing blobs or packer data. Written back after the class.
+	pub trailing: Vec<u8>,
+}
+
+impl IOClassfileError {
+	/// Attaches the reader offset to errors that came from the byte layer.
+	pub fn at(self, offset: u64) -> Self {
+		match self {
+			Self::Bytes(e) => Self::Bytes(e.at(offset)),
+			// the tag byte itself was the last thing read
+			Self::UnknownCpTag { tag, offset: None } => Self::UnknownCpTag {
+				tag,
+				offset: Some(offset - 1),
+			},
+			Self::In { structure, source } => Self::In {
+				structure,
+				source: Box::new(source.at(offset)),
+			},
+			e => e,
+		}
+	}
+
+	/// Wraps the error to say which structure was being read.
+	pub fn within(self, structure: Structure) -> Self {
+		Self::In {
+			structure,
+			source: Box::new(self),
+		}
+	}
+
+	/// The error without any [`IOClassfileError::In`] wrapping.
+	pub fn root(&self) -> &IOClassfileError {
+		match self {
+			Self::In { source, .. } => source.root(),
+			e => e,
+		}
+	}
+
+	/// The structures being read, outermost first.
+	pub fn structures(&self) -> Vec<&Structure> {
+		let mut structures = Vec::new();
+		let mut error = self;
+		while let Self::In { structure, source } = error {
+			structures.push(structure);
+			error = source;
+		}
+		structures
+	}
 }
 
 impl IOClassFile {
 	pub fn read<B: BytesReadExt>(buffer: &mut B) -> Result<IOClassFile, IOClassfileError> {
+		Self::read_with(buffer, &ReadOptions::default())
+	}
+
+	#[cfg_attr(
+		feature = "tracing",
+		tracing::instrument(level = "debug", name = "read_class", skip_all, err)
+	)]
+	pub fn read_with<B: BytesReadExt>(buffer: &mut B, options: &ReadOptions) -> Result<IOClassFile, IOClassfileError> {
+		let mut buffer = OffsetReader::new(buffer);
+		maya_bytes::with_max_alloc(Some(options.limits.max_alloc), || {
+			Self::read_inner(&mut buffer, options)
+		})
+		.map_err(|e| e.at(buffer.offset()))
+	}
+
+	/// Reads the class in `bytes`, keeping whatever follows it in [`trailing`](Self::trailing).
+	pub fn read_from_slice(bytes: &[u8]) -> Result<IOClassFile, IOClassfileError> {
+		Self::read_with(&mut &bytes[..], &ReadOptions::whole_input())
+	}
+
+	pub fn read_from_path<P: AsRef<std::path::Path>>(path: P) -> Result<IOClassFile, IOClassfileError> {
+		Self::read_from_slice(&std::fs::read(path)?)
+	}
+
+	/// Parses a classfile straight out of a memory mapping of `path`, without copying it into memory first.
+	#[cfg(feature = "mmap")]
+	pub fn read_mmap<P: AsRef<std::path::Path>>(path: P) -> Result<IOClassFile, IOClassfileError> {
+		let file = std::fs::File::open(path)?;
+		// SAFETY: the mapping only lives for this call and is only read from. If another process
+		// truncates the file meanwhile we can fault, which is the documented caveat of memmap2.
+		let map = unsafe { memmap2::Mmap::map(&file)? };
+		Self::read_from_slice(&map)
+	}
+
+	fn read_inner<B: std::io::Read>(
+		buffer: &mut OffsetReader<B>,
+		options: &ReadOptions,
+	) -> Result<IOClassFile, IOClassfileError> {
+		sections::skip(buffer, options.skip_prefix as u64)?;
+
 		let magic = buffer.read_u32()?;
-		if magic != 0xCAFEBABE {
-			return Err(IOClassfileError::InvalidMagic);
+		if options.require_magic && magic != 0xCAFEBABE {
+			return Err(IOClassfileError::InvalidMagic(magic));
 		}
 
 		let minor_version = buffer.read_u16()?;
 		let major_version = buffer.read_u16()?;
+		let limits = &options.limits;
 		let cp_count = buffer.read_u16()?;
-		let mut cp = Vec::with_capacity(cp_count as usize - 1);
-		for _ in 0..cp_count - 1 {
-			cp.push(IOCpTag::read(buffer)?);
-		}
+		Limits::check(
+			"constant pool size",
+			cp_count.saturating_sub(1) as u64,
+			limits.max_cp_entries as u64,
+		)?;
+		let cp = IOCpTag::read_pool(buffer, cp_count)?;
+		#[cfg(feature = "tracing")]
+		tracing::trace!(major_version, minor_version, entries = cp.len(), "read constant pool");
 		let access_flags = buffer.read_u16()?;
 		let this_class = buffer.read_u16()?;
 		let super_class = buffer.read_u16()?;
 		let interface_count = buffer.read_u16()?;
-		let mut interfaces = Vec::with_capacity(interface_count as usize);
-		for _ in 0..interface_count {
-			interfaces.push(buffer.read_u16()?);
-		}
+		let interfaces = buffer.read_u16_vec(interface_count as usize)?;
 		let field_count = buffer.read_u16()?;
-		let mut fields = Vec::with_capacity(field_count as usize);
-		for _ in 0..field_count {
-			fields.push(IOFieldInfo::read(buffer)?);
+		Limits::check("field_count", field_count as u64, limits.max_members as u64)?;
+		let mut skipped = Vec::new();
+		let mut fields = Vec::new();
+		if options.sections.fields {
+			fields = maya_bytes::vec_with_capacity(field_count as usize)?;
+			for i in 0..field_count {
+				let field = IOFieldInfo::read_in(buffer, &cp, limits).map_err(|e| e.within(Structure::Field(i)))?;
+				fields.push(field);
+			}
+		} else {
+			skipped.push(sections::skip_section(buffer, Section::Fields, field_count)?);
 		}
 		let method_count = buffer.read_u16()?;
-		let mut methods = Vec::with_capacity(method_count as usize);
-		for _ in 0..method_count {
-			methods.push(IOMethodInfo::read(buffer)?);
+		Limits::check("method_count", method_count as u64, limits.max_members as u64)?;
+		let mut methods = Vec::new();
+		if options.sections.methods {
+			methods = maya_bytes::vec_with_capacity(method_count as usize)?;
+			for i in 0..method_count {
+				let method = IOMethodInfo::read_in(buffer, &cp, limits).map_err(|e| e.within(Structure::Method(i)))?;
+				methods.push(method);
+			}
+		} else {
+			skipped.push(sections::skip_section(buffer, Section::Methods, method_count)?);
 		}
 		let attribute_count = buffer.read_u16()?;
-		let mut attributes = Vec::with_capacity(attribute_count as usize);
-		for _ in 0..attribute_count {
-			attributes.push(IOAttributeInfo::read(buffer)?);
+		let mut attributes = Vec::new();
+		if options.sections.class_attributes {
+			attributes = IOAttributeInfo::read_all(buffer, &cp, attribute_count, limits)?;
+		} else {
+			skipped.push(sections::skip_section(
+				buffer,
+				Section::ClassAttributes,
+				attribute_count,
+			)?);
 		}
+		let trailing = match options.keep_trailing {
+			true => buffer.read_to_vec()?,
+			false => Vec::new(),
+		};
+		#[cfg(feature = "tracing")]
+		tracing::debug!(
+			fields = fields.len(),
+			methods = methods.len(),
+			attributes = attributes.len(),
+			skipped = skipped.len(),
+			"read class"
+		);
 
 		Ok(Self {
 			magic,
@@ -89,10 +278,26 @@ impl IOClassFile {
 			methods,
 			attribute_count,
 			attributes,
+			skipped,
+			trailing,
 		})
 	}
 
+	/// Size of the class as [`write`](Self::write) would emit it, without writing anything.
+	pub fn byte_len(&self) -> u64 {
+		24 + self.cp.iter().map(IOCpTag::byte_len).sum::<u64>()
+			+ 2 * self.interfaces.len() as u64
+			+ self.fields.iter().map(IOFieldInfo::byte_len).sum::<u64>()
+			+ self.methods.iter().map(IOMethodInfo::byte_len).sum::<u64>()
+			+ self.attributes.iter().map(IOAttributeInfo::byte_len).sum::<u64>()
+			+ self.trailing.len() as u64
+	}
+
 	pub fn write<B: BytesWriteExt>(&self, buffer: &mut B) -> Result<(), IOClassfileError> {
+		if let Some(skipped) = self.skipped.first() {
+			return Err(IOClassfileError::SectionSkipped(skipped.section));
+		}
+
 		buffer.write_u32(self.magic)?;
 		buffer.write_u16(self.minor_version)?;
 		buffer.write_u16(self.major_version)?;
@@ -119,11 +324,13 @@ impl IOClassFile {
 		for attr in &self.attributes {
 			attr.write(buffer)?;
 		}
+		buffer.write_all(&self.trailing)?;
 		Ok(())
 	}
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct IOAttributeInfo {
 	pub attribute_name_index: u16,
 	pub attribute_length: u32,
@@ -131,6 +338,14 @@ pub struct IOAttributeInfo {
 }
 
 impl IOAttributeInfo {
+	pub fn new(attribute_name_index: u16, info: Vec<u8>) -> Self {
+		Self {
+			attribute_name_index,
+			attribute_length: info.len() as u32,
+			info,
+		}
+	}
+
 	pub fn read<B: BytesReadExt>(buffer: &mut B) -> Result<IOAttributeInfo, BytesError> {
 		let attribute_name_index = buffer.read_u16()?;
 		let attribute_length = buffer.read_u32()?;
@@ -141,6 +356,53 @@ impl IOAttributeInfo {
 		})
 	}
 
+	fn read_body<B: BytesReadExt>(
+		attribute_name_index: u16,
+		buffer: &mut B,
+		limits: &Limits,
+	) -> Result<IOAttributeInfo, IOClassfileError> {
+		let attribute_length = buffer.read_u32()?;
+		Limits::check(
+			"attribute_length",
+			attribute_length as u64,
+			limits.max_attribute_size as u64,
+		)?;
+		Ok(IOAttributeInfo {
+			attribute_name_index,
+			attribute_length,
+			info: buffer.read_n_bytes_vec(attribute_length as usize)?,
+		})
+	}
+
+	/// Reads `count` attributes, naming the failing one (resolved through `cp`) in errors.
+	fn read_all<B: BytesReadExt>(
+		buffer: &mut B,
+		cp: &[IOCpTag],
+		count: u16,
+		limits: &Limits,
+	) -> Result<Vec<IOAttributeInfo>, IOClassfileError> {
+		Limits::check("attributes_count", count as u64, limits.max_attributes as u64)?;
+		let mut attributes = maya_bytes::vec_with_capacity(count as usize)?;
+		for _ in 0..count {
+			let name_index = buffer.read_u16()?;
+			let attr = Self::read_body(name_index, buffer, limits).map_err(|e| {
+				let name = match cp.get((name_index as usize).wrapping_sub(1)) {
+					Some(IOCpTag::Utf8 { bytes, .. }) => maya_mutf8::decode(bytes).ok(),
+					_ => None,
+				};
+				e.within(Structure::Attribute { name_index, name })
+			})?;
+			#[cfg(feature = "tracing")]
+			tracing::trace!(name_index, length = attr.attribute_length, "read attribute");
+			attributes.push(attr);
+		}
+		Ok(attributes)
+	}
+
+	pub fn byte_len(&self) -> u64 {
+		6 + self.info.len() as u64
+	}
+
 	pub fn write<B: BytesWriteExt>(&self, buffer: &mut B) -> Result<(), IOClassfileError> {
 		buffer.write_u16(self.attribute_name_index)?;
 		buffer.write_u32(self.attribute_length)?;
@@ -150,6 +412,7 @@ impl IOAttributeInfo {
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct IOFieldInfo {
 	pub access_flags: u16,
 	pub name_index: u16,
@@ -164,7 +427,7 @@ impl IOFieldInfo {
 		let name_index = buffer.read_u16()?;
 		let descriptor_index = buffer.read_u16()?;
 		let attributes_count = buffer.read_u16()?;
-		let mut attributes = Vec::with_capacity(attributes_count as usize);
+		let mut attributes = maya_bytes::vec_with_capacity(attributes_count as usize)?;
 		for _ in 0..attributes_count {
 			attributes.push(IOAttributeInfo::read(buffer)?);
 		}
@@ -178,6 +441,30 @@ impl IOFieldInfo {
 		})
 	}
 
+	fn read_in<B: BytesReadExt>(
+		buffer: &mut B,
+		cp: &[IOCpTag],
+		limits: &Limits,
+	) -> Result<IOFieldInfo, IOClassfileError> {
+		let access_flags = buffer.read_u16()?;
+		let name_index = buffer.read_u16()?;
+		let descriptor_index = buffer.read_u16()?;
+		let attributes_count = buffer.read_u16()?;
+		let attributes = IOAttributeInfo::read_all(buffer, cp, attributes_count, limits)?;
+
+		Ok(IOFieldInfo {
+			access_flags,
+			name_index,
+			descriptor_index,
+			attributes_count,
+			attributes,
+		})
+	}
+
+	pub fn byte_len(&self) -> u64 {
+		8 + self.attributes.iter().map(IOAttributeInfo::byte_len).sum::<u64>()
+	}
+
 	pub fn write<B: BytesWriteExt>(&self, buffer: &mut B) -> Result<(), IOClassfileError> {
 		buffer.write_u16(self.access_flags)?;
 		buffer.write_u16(self.name_index)?;
@@ -191,6 +478,7 @@ impl IOFieldInfo {
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct IOMethodInfo {
 	pub access_flags: u16,
 	pub name_index: u16,
@@ -205,7 +493,7 @@ impl IOMethodInfo {
 		let name_index = buffer.read_u16()?;
 		let descriptor_index = buffer.read_u16()?;
 		let attributes_count = buffer.read_u16()?;
-		let mut attributes = Vec::with_capacity(attributes_count as usize);
+		let mut attributes = maya_bytes::vec_with_capacity(attributes_count as usize)?;
 		for _ in 0..attributes_count {
 			attributes.push(IOAttributeInfo::read(buffer)?);
 		}
@@ -219,6 +507,30 @@ impl IOMethodInfo {
 		})
 	}
 
+	fn read_in<B: BytesReadExt>(
+		buffer: &mut B,
+		cp: &[IOCpTag],
+		limits: &Limits,
+	) -> Result<IOMethodInfo, IOClassfileError> {
+		let access_flags = buffer.read_u16()?;
+		let name_index = buffer.read_u16()?;
+		let descriptor_index = buffer.read_u16()?;
+		let attributes_count = buffer.read_u16()?;
+		let attributes = IOAttributeInfo::read_all(buffer, cp, attributes_count, limits)?;
+
+		Ok(IOMethodInfo {
+			access_flags,
+			name_index,
+			descriptor_index,
+			attributes_count,
+			attributes,
+		})
+	}
+
+	pub fn byte_len(&self) -> u64 {
+		8 + self.attributes.iter().map(IOAttributeInfo::byte_len).sum::<u64>()
+	}
+
 	pub fn write<B: BytesWriteExt>(&self, buffer: &mut B) -> Result<(), IOClassfileError> {
 		buffer.write_u16(self.access_flags)?;
 		buffer.write_u16(self.name_index)?;
@@ -230,3 +542,167 @@ impl IOMethodInfo {
 		Ok(())
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const HELLO: &[u8] = include_bytes!("../../maya-test-bin/data/out/a/a/Hello.class");
+
+	#[test]
+	fn trailing() {
+		let mut data = HELLO.to_vec();
+		data.extend_from_slice(b"SIG");
+
+		let mut stream = &data[..];
+		let class = IOClassFile::read(&mut stream).unwrap();
+		assert!(class.trailing.is_empty());
+		assert_eq!(stream, b"SIG");
+
+		let class = IOClassFile::read_from_slice(&data).unwrap();
+		assert_eq!(class.trailing, b"SIG");
+		assert_eq!(IOClassFile::read_lazy(&data).unwrap().trailing, b"SIG");
+		let mut out = Vec::new();
+		class.write(&mut out).unwrap();
+		assert_eq!(out, data);
+	}
+
+	#[test]
+	fn read_from_path() {
+		let path = concat!(env!("CARGO_MANIFEST_DIR"), "/../maya-test-bin/data/out/a/a/Hello.class");
+		let class = IOClassFile::read_from_path(path).unwrap();
+		assert_eq!(class.this_class_name().unwrap(), "a/Hello");
+		assert_eq!(class.to_bytes().unwrap(), HELLO);
+
+		let error = IOClassFile::read_from_path("does/not/exist.class").unwrap_err();
+		assert!(
+			matches!(&error, IOClassfileError::IO(e) if e.kind() == std::io::ErrorKind::NotFound),
+			"{error}"
+		);
+	}
+
+	#[test]
+	fn error_structures() {
+		let mut data = HELLO.to_vec();
+		data[10] = 2;
+		let error = IOClassFile::read_from_slice(&data).unwrap_err();
+		assert_eq!(error.structures(), [&Structure::CpEntry(1)]);
+		assert!(matches!(
+			error.root(),
+			IOClassfileError::UnknownCpTag {
+				tag: 2,
+				offset: Some(10)
+			}
+		));
+		assert_eq!(
+			error.to_string(),
+			"Unknown constant pool tag 2 at offset 10, in constant pool entry #1"
+		);
+
+		let mut class = IOClassFile::read_from_slice(HELLO).unwrap();
+		class.methods[1].attributes[0].attribute_length += HELLO.len() as u32;
+		let error = IOClassFile::read_from_slice(&class.to_bytes().unwrap()).unwrap_err();
+		assert_eq!(
+			error.structures(),
+			[
+				&Structure::Method(1),
+				&Structure::Attribute {
+					name_index: class.methods[1].attributes[0].attribute_name_index,
+					name: Some("Code".to_string()),
+				}
+			]
+		);
+		assert!(matches!(error.root(), IOClassfileError::Bytes(_)), "{error}");
+		assert!(
+			error.to_string().ends_with("in attribute `Code`, in method #1"),
+			"{error}"
+		);
+		assert!(error.to_string().contains("at offset"), "{error}");
+	}
+
+	#[test]
+	fn byte_len() {
+		fn written(write: impl FnOnce(&mut Vec<u8>) -> Result<(), IOClassfileError>) -> u64 {
+			let mut out = Vec::new();
+			write(&mut out).unwrap();
+			out.len() as u64
+		}
+
+		let constants = include_bytes!("../../maya-test-bin/data/out/a/a/Constants.class");
+		for bytes in [HELLO, constants, &[HELLO, b"SIG"].concat()] {
+			let class = IOClassFile::read_from_slice(bytes).unwrap();
+			assert_eq!(class.byte_len(), bytes.len() as u64);
+			for field in &class.fields {
+				assert_eq!(field.byte_len(), written(|out| field.write(out)));
+			}
+			for method in &class.methods {
+				assert_eq!(method.byte_len(), written(|out| method.write(out)));
+			}
+			for attribute in &class.attributes {
+				assert_eq!(attribute.byte_len(), written(|out| attribute.write(out)));
+			}
+		}
+
+		// an attribute is as long as its bytes, whatever attribute_length says
+		let attribute = IOAttributeInfo {
+			attribute_name_index: 1,
+			attribute_length: 0,
+			info: vec![0; 5],
+		};
+		assert_eq!(attribute.byte_len(), 11);
+	}
+
+	#[cfg(feature = "mmap")]
+	#[test]
+	fn read_mmap() {
+		let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/../maya-test-bin/data/out/a/a");
+		let class = IOClassFile::read_mmap(format!("{dir}/Hello.class")).unwrap();
+		assert_eq!(class.to_bytes().unwrap(), HELLO);
+		assert!(class.trailing.is_empty());
+
+		let empty = std::env::temp_dir().join(format!("maya-empty-{}.class", std::process::id()));
+		std::fs::write(&empty, []).unwrap();
+		let error = IOClassFile::read_mmap(&empty).unwrap_err();
+		std::fs::remove_file(&empty).unwrap();
+		assert!(matches!(error, IOClassfileError::Bytes(_)), "{error}");
+
+		let error = IOClassFile::read_mmap(format!("{dir}/Missing.class")).unwrap_err();
+		assert!(
+			matches!(&error, IOClassfileError::IO(e) if e.kind() == std::io::ErrorKind::NotFound),
+			"{error}"
+		);
+	}
+
+	#[test]
+	fn max_alloc() {
+		let mut options = ReadOptions::default();
+		options.limits.max_alloc = 64;
+		let error = IOClassFile::read_with(&mut &HELLO[..], &options).unwrap_err();
+		assert!(
+			matches!(
+				error.root(),
+				IOClassfileError::Bytes(BytesError::AllocationTooLarge { limit: 64, .. })
+			),
+			"{error}"
+		);
+		assert!(IOClassFile::read_lazy_with(HELLO, &options).is_err());
+		// the cap only lasts for the read
+		assert_eq!(maya_bytes::max_alloc(), None);
+		IOClassFile::read_from_slice(HELLO).unwrap();
+	}
+
+	#[test]
+	fn truncated_is_not_trailing() {
+		let error = IOClassFile::read_from_slice(&HELLO[..HELLO.len() - 1]).unwrap_err();
+		assert!(matches!(error.root(), IOClassfileError::Bytes(_)), "{error}");
+	}
+
+	#[cfg(feature = "serde")]
+	#[test]
+	fn serde_round_trip() {
+		let class = IOClassFile::read_from_slice(HELLO).unwrap();
+		let json = serde_json::to_string(&class).unwrap();
+		let class: IOClassFile = serde_json::from_str(&json).unwrap();
+		assert_eq!(class.to_bytes().unwrap(), HELLO);
+	}
+}
@@ -0,0 +1,212 @@
+use crate::{sections::ReadSections, IOClassfileError};
+
+/// Controls how forgiving [`IOClassFile::read_with`](crate::IOClassFile::read_with) is.
+#[derive(Debug, Clone)]
+pub struct ReadOptions {
+	/// Fail with [`InvalidMagic`](crate::IOClassfileError::InvalidMagic) unless the magic is 0xCAFEBABE.
+	/// When off, whatever was found is kept in [`IOClassFile::magic`](crate::IOClassFile::magic).
+	pub require_magic: bool,
+	/// Bytes to discard before the magic, for toolchains that prepend their own header.
+	/// They are not kept, so writing the class back leaves them out.
+	pub skip_prefix: usize,
+	pub limits: Limits,
+	pub sections: ReadSections,
+	/// Keep whatever follows the class in [`IOClassFile::trailing`](crate::IOClassFile::trailing).
+	/// This reads to the end of the stream, so it's off by default and a stream is left right after
+	/// the class. Reading from a slice or a path turns it on, as there the input is the whole class.
+	pub keep_trailing: bool,
+}
+
+impl Default for ReadOptions {
+	fn default() -> Self {
+		Self {
+			require_magic: true,
+			skip_prefix: 0,
+			limits: Limits::NONE,
+			sections: ReadSections::default(),
+			keep_trailing: false,
+		}
+	}
+}
+
+impl ReadOptions {
+	/// The defaults for input that ends where the class does, which keep the trailing bytes.
+	pub(crate) fn whole_input() -> Self {
+		Self {
+			keep_trailing: true,
+			..Self::default()
+		}
+	}
+}
+
+/// Upper bounds on what a classfile may claim, checked before anything is allocated for it.
+/// Exceeding one fails the read with [`LimitExceeded`](crate::IOClassfileError::LimitExceeded).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Limits {
+	pub max_cp_entries: u16,
+	/// Applies to fields and methods separately.
+	pub max_members: u16,
+	/// Attributes per class, field or method.
+	pub max_attributes: u16,
+	/// Bytes in a single attribute body.
+	pub max_attribute_size: u32,
+	/// Bytes a single allocation sized by the input may take, applied for the duration of the read
+	/// with [`maya_bytes::with_max_alloc`]. A lower cap set with [`maya_bytes::set_max_alloc`] wins.
+	pub max_alloc: u64,
+	/// How deep annotations and element_value arrays may nest, and how many steps a type annotation's
+	/// type_path may take. These are only parsed when lifting to the IR, so this is checked there, by
+	/// `IRClassFile::from_io_limited`. Lifting recurses once per level, so even [`Limits::NONE`] stops
+	/// at 256.
+	pub max_nesting: u16,
+}
+
+impl Limits {
+	/// Only what the classfile format itself allows.
+	pub const NONE: Limits = Limits {
+		max_cp_entries: u16::MAX,
+		max_members: u16::MAX,
+		max_attributes: u16::MAX,
+		max_attribute_size: u32::MAX,
+		max_alloc: u64::MAX,
+		max_nesting: 256,
+	};
+
+	/// For classfiles from untrusted sources. Still well above what javac emits for real code.
+	pub const UNTRUSTED: Limits = Limits {
+		max_cp_entries: 32768,
+		max_members: 8192,
+		max_attributes: 256,
+		max_attribute_size: 4 << 20,
+		max_alloc: 16 << 20,
+		max_nesting: 32,
+	};
+
+	pub(crate) fn check(what: &'static str, value: u64, limit: u64) -> Result<(), IOClassfileError> {
+		match value <= limit {
+			true => Ok(()),
+			false => Err(IOClassfileError::LimitExceeded { what, value, limit }),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::IOClassFile;
+
+	const HELLO: &[u8] = include_bytes!("../../maya-test-bin/data/out/a/a/Hello.class");
+
+	#[test]
+	fn magic() {
+		let mut data = HELLO.to_vec();
+		data[..4].copy_from_slice(&0xDEADBEEFu32.to_be_bytes());
+		assert!(matches!(
+			IOClassFile::read(&mut &data[..]),
+			Err(IOClassfileError::InvalidMagic(0xDEADBEEF))
+		));
+
+		let options = ReadOptions {
+			require_magic: false,
+			..ReadOptions::default()
+		};
+		let class = IOClassFile::read_with(&mut &data[..], &options).unwrap();
+		assert_eq!(class.magic, 0xDEADBEEF);
+		assert_eq!(class.to_bytes().unwrap(), data);
+	}
+
+	#[test]
+	fn prefix() {
+		let data = [b"HDR!".as_slice(), HELLO].concat();
+		let options = ReadOptions {
+			skip_prefix: 4,
+			..ReadOptions::default()
+		};
+		let class = IOClassFile::read_with(&mut &data[..], &options).unwrap();
+		// the prefix isn't kept
+		assert_eq!(class.to_bytes().unwrap(), HELLO);
+
+		assert!(matches!(
+			IOClassFile::read(&mut &data[..]),
+			Err(IOClassfileError::InvalidMagic(_))
+		));
+		let error = IOClassFile::read_with(&mut &data[..3], &options).unwrap_err();
+		assert!(matches!(error, IOClassfileError::Bytes(_)), "{error}");
+	}
+
+	fn read_limited(data: &[u8], limits: Limits) -> Result<IOClassFile, IOClassfileError> {
+		let options = ReadOptions {
+			limits,
+			..ReadOptions::default()
+		};
+		IOClassFile::read_with(&mut &data[..], &options)
+	}
+
+	#[test]
+	fn limits() {
+		read_limited(HELLO, Limits::UNTRUSTED).unwrap();
+		let class = IOClassFile::read_from_slice(HELLO).unwrap();
+
+		let limited = |limits: Limits| read_limited(HELLO, limits).unwrap_err();
+		let error = limited(Limits {
+			max_cp_entries: 10,
+			..Limits::NONE
+		});
+		assert!(matches!(
+			error.root(),
+			IOClassfileError::LimitExceeded { what: "constant pool size", limit: 10, value }
+				if *value == class.cp_count as u64 - 1
+		));
+		let error = limited(Limits {
+			max_members: 1,
+			..Limits::NONE
+		});
+		assert!(matches!(
+			error.root(),
+			IOClassfileError::LimitExceeded {
+				what: "method_count",
+				limit: 1,
+				..
+			}
+		));
+		let error = limited(Limits {
+			max_attributes: 1,
+			..Limits::NONE
+		});
+		assert!(matches!(
+			error.root(),
+			IOClassfileError::LimitExceeded {
+				what: "attributes_count",
+				limit: 1,
+				..
+			}
+		));
+		let error = limited(Limits {
+			max_attribute_size: 16,
+			..Limits::NONE
+		});
+		assert!(matches!(
+			error.root(),
+			IOClassfileError::LimitExceeded {
+				what: "attribute_length",
+				limit: 16,
+				..
+			}
+		));
+	}
+
+	#[test]
+	fn limits_come_before_allocating() {
+		// a class claiming the most constants it can, with none of them there
+		let mut data = HELLO[..8].to_vec();
+		data.extend_from_slice(&u16::MAX.to_be_bytes());
+		let error = read_limited(&data, Limits::UNTRUSTED).unwrap_err();
+		assert!(matches!(
+			error.root(),
+			IOClassfileError::LimitExceeded {
+				what: "constant pool size",
+				value: 65534,
+				limit: 32768,
+			}
+		));
+	}
+}
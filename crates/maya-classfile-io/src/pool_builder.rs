@@ -0,0 +1,308 @@
+use std::collections::HashMap;
+
+use crate::{class_pool::IOCpTag, IOClassfileError};
+
+/// Highest index a constant pool entry can have, `constant_pool_count` is a u16 and counts from 1.
+pub const MAX_CP_INDEX: u16 = u16::MAX - 1;
+
+/// Builds a constant pool, handing out the existing index when an equal entry was already added.
+///
+/// Every helper adds the entries it refers to first, e.g. [`method_ref`](Self::method_ref) also
+/// interns the Class, NameAndType and Utf8 entries behind it.
+#[derive(Debug, Default, Clone)]
+pub struct ConstantPoolBuilder {
+	cp: Vec<IOCpTag>,
+	indices: HashMap<IOCpTag, u16>,
+}
+
+impl ConstantPoolBuilder {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Continues an existing pool. Its entries keep their indices and are reused by later adds.
+	pub fn from_pool(cp: Vec<IOCpTag>) -> Result<Self, IOClassfileError> {
+		if cp.len() > MAX_CP_INDEX as usize {
+			return Err(IOClassfileError::CpFull);
+		}
+
+		let mut indices = HashMap::with_capacity(cp.len());
+		for (i, tag) in cp.iter().enumerate() {
+			if !matches!(tag, IOCpTag::Unusable) {
+				indices.entry(tag.clone()).or_insert(i as u16 + 1);
+			}
+		}
+		Ok(Self { cp, indices })
+	}
+
+	/// Number of slots used, wide entries count twice.
+	pub fn len(&self) -> usize {
+		self.cp.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.cp.is_empty()
+	}
+
+	pub fn get(&self, index: u16) -> Option<&IOCpTag> {
+		self.cp.get((index as usize).checked_sub(1)?)
+	}
+
+	/// The pool so far, entry `i` has index `i + 1`.
+	pub fn entries(&self) -> &[IOCpTag] {
+		&self.cp
+	}
+
+	/// Returns the index of `tag`, adding it if the pool doesn't have it yet.
+	/// Long and Double entries take their unusable second slot with them, adding `Unusable` itself is an error.
+	pub fn add(&mut self, tag: IOCpTag) -> Result<u16, IOClassfileError> {
+		if let IOCpTag::Unusable = tag {
+			return Err(IOClassfileError::BadCpIndex(0));
+		}
+		if let Some(index) = self.indices.get(&tag) {
+			return Ok(*index);
+		}
+
+		let slots = if tag.is_wide() { 2 } else { 1 };
+		if self.cp.len() + slots > MAX_CP_INDEX as usize {
+			return Err(IOClassfileError::CpFull);
+		}
+
+		let index = self.cp.len() as u16 + 1;
+		self.cp.push(tag.clone());
+		if slots == 2 {
+			self.cp.push(IOCpTag::Unusable);
+		}
+		self.indices.insert(tag, index);
+		Ok(index)
+	}
+
+	/// Appends `tag` as is, without looking for an equal entry or adding the second slot of a wide one.
+	/// For mirroring a pool that is kept elsewhere, an equal earlier entry stays the one handed out.
+	pub fn push(&mut self, tag: IOCpTag) -> Result<u16, IOClassfileError> {
+		if self.cp.len() + 1 > MAX_CP_INDEX as usize {
+			return Err(IOClassfileError::CpFull);
+		}
+
+		let index = self.cp.len() as u16 + 1;
+		if !matches!(tag, IOCpTag::Unusable) {
+			self.indices.entry(tag.clone()).or_insert(index);
+		}
+		self.cp.push(tag);
+		Ok(index)
+	}
+
+	/// Swaps the entry at `index` for `tag`, which must take as many slots. The old entry is no longer
+	/// handed out for this index.
+	pub fn replace(&mut self, index: u16, tag: IOCpTag) -> Result<(), IOClassfileError> {
+		let old = match self.get(index) {
+			Some(old) if !matches!(old, IOCpTag::Unusable) && old.is_wide() == tag.is_wide() => old.clone(),
+			_ => return Err(IOClassfileError::BadCpIndex(index)),
+		};
+		if let IOCpTag::Unusable = tag {
+			return Err(IOClassfileError::BadCpIndex(index));
+		}
+
+		if self.indices.get(&old) == Some(&index) {
+			self.indices.remove(&old);
+		}
+		self.indices.entry(tag.clone()).or_insert(index);
+		self.cp[index as usize - 1] = tag;
+		Ok(())
+	}
+
+	pub fn utf8(&mut self, string: &str) -> Result<u16, IOClassfileError> {
+		let bytes = maya_mutf8::encode(string);
+		let length = u16::try_from(bytes.len()).map_err(|_| maya_bytes::BytesError::StringTooLong(bytes.len()))?;
+		self.add(IOCpTag::Utf8 { length, bytes })
+	}
+
+	pub fn integer(&mut self, value: i32) -> Result<u16, IOClassfileError> {
+		self.add(IOCpTag::Integer {
+			bytes: value.to_be_bytes(),
+		})
+	}
+
+	/// Floats are compared bit for bit, so `0.0` and `-0.0` get separate entries.
+	pub fn float(&mut self, value: f32) -> Result<u16, IOClassfileError> {
+		self.add(IOCpTag::Float {
+			bytes: value.to_be_bytes(),
+		})
+	}
+
+	pub fn long(&mut self, value: i64) -> Result<u16, IOClassfileError> {
+		self.add(IOCpTag::Long {
+			bytes: value.to_be_bytes(),
+		})
+	}
+
+	pub fn double(&mut self, value: f64) -> Result<u16, IOClassfileError> {
+		self.add(IOCpTag::Double {
+			bytes: value.to_be_bytes(),
+		})
+	}
+
+	pub fn class(&mut self, name: &str) -> Result<u16, IOClassfileError> {
+		let name_index = self.utf8(name)?;
+		self.add(IOCpTag::Class { name_index })
+	}
+
+	pub fn string(&mut self, value: &str) -> Result<u16, IOClassfileError> {
+		let utf8_index = self.utf8(value)?;
+		self.add(IOCpTag::String { utf8_index })
+	}
+
+	pub fn name_and_type(&mut self, name: &str, descriptor: &str) -> Result<u16, IOClassfileError> {
+		let name_index = self.utf8(name)?;
+		let descriptor_index = self.utf8(descriptor)?;
+		self.add(IOCpTag::NameAndType {
+			name_index,
+			descriptor_index,
+		})
+	}
+
+	pub fn field_ref(&mut self, class: &str, name: &str, descriptor: &str) -> Result<u16, IOClassfileError> {
+		let class_index = self.class(class)?;
+		let name_and_ty_index = self.name_and_type(name, descriptor)?;
+		self.add(IOCpTag::FieldRef {
+			class_index,
+			name_and_ty_index,
+		})
+	}
+
+	pub fn method_ref(&mut self, class: &str, name: &str, descriptor: &str) -> Result<u16, IOClassfileError> {
+		let class_index = self.class(class)?;
+		let name_and_ty_index = self.name_and_type(name, descriptor)?;
+		self.add(IOCpTag::MethodRef {
+			class_index,
+			name_and_ty_index,
+		})
+	}
+
+	pub fn interface_method_ref(&mut self, class: &str, name: &str, descriptor: &str) -> Result<u16, IOClassfileError> {
+		let class_index = self.class(class)?;
+		let name_and_ty_index = self.name_and_type(name, descriptor)?;
+		self.add(IOCpTag::InterfaceMethodRef {
+			class_index,
+			name_and_ty_index,
+		})
+	}
+
+	/// `reference_index` is the FieldRef, MethodRef or InterfaceMethodRef the handle points at.
+	pub fn method_handle(&mut self, reference_kind: u8, reference_index: u16) -> Result<u16, IOClassfileError> {
+		self.add(IOCpTag::MethodHandle {
+			reference_kind,
+			reference_index,
+		})
+	}
+
+	pub fn method_type(&mut self, descriptor: &str) -> Result<u16, IOClassfileError> {
+		let descriptor_index = self.utf8(descriptor)?;
+		self.add(IOCpTag::MethodType { descriptor_index })
+	}
+
+	pub fn dynamic(
+		&mut self,
+		bootstrap_method_attr_index: u16,
+		name: &str,
+		descriptor: &str,
+	) -> Result<u16, IOClassfileError> {
+		let name_and_ty_index = self.name_and_type(name, descriptor)?;
+		self.add(IOCpTag::Dynamic {
+			bootstrap_method_attr_index,
+			name_and_ty_index,
+		})
+	}
+
+	pub fn invoke_dynamic(
+		&mut self,
+		bootstrap_method_attr_index: u16,
+		name: &str,
+		descriptor: &str,
+	) -> Result<u16, IOClassfileError> {
+		let name_and_ty_index = self.name_and_type(name, descriptor)?;
+		self.add(IOCpTag::InvokeDynamic {
+			bootstrap_method_attr_index,
+			name_and_ty_index,
+		})
+	}
+
+	pub fn module(&mut self, name: &str) -> Result<u16, IOClassfileError> {
+		let name_index = self.utf8(name)?;
+		self.add(IOCpTag::Module { name_index })
+	}
+
+	pub fn package(&mut self, name: &str) -> Result<u16, IOClassfileError> {
+		let name_index = self.utf8(name)?;
+		self.add(IOCpTag::Package { name_index })
+	}
+
+	/// `constant_pool_count` for the built pool.
+	pub fn cp_count(&self) -> u16 {
+		self.cp.len() as u16 + 1
+	}
+
+	pub fn build(self) -> Vec<IOCpTag> {
+		self.cp
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn dedup() {
+		let mut pool = ConstantPoolBuilder::new();
+		let method = pool.method_ref("a/B", "run", "()V").unwrap();
+		assert_eq!(pool.method_ref("a/B", "run", "()V").unwrap(), method);
+		assert_eq!(pool.utf8("a/B").unwrap(), 1);
+		assert_eq!(pool.class("a/B").unwrap(), 2);
+		// Utf8 a/B, Class, run, ()V, NameAndType, MethodRef
+		assert_eq!(pool.len(), 6);
+	}
+
+	#[test]
+	fn wide_entries() {
+		let mut pool = ConstantPoolBuilder::new();
+		assert_eq!(pool.long(1).unwrap(), 1);
+		assert_eq!(pool.integer(1).unwrap(), 3);
+		assert!(matches!(pool.get(2), Some(IOCpTag::Unusable)));
+		assert_eq!(pool.double(1.0).unwrap(), 4);
+		assert_eq!(pool.cp_count(), 6);
+	}
+
+	#[test]
+	fn mirror() {
+		let mut pool = ConstantPoolBuilder::new();
+		let first = pool.utf8("a").unwrap();
+		// a duplicate pushed as is takes a slot, lookups keep finding the first one
+		let duplicate = pool.push(pool.get(first).unwrap().clone()).unwrap();
+		assert_eq!((first, duplicate), (1, 2));
+		assert_eq!(pool.utf8("a").unwrap(), first);
+
+		pool.replace(first, IOCpTag::Integer { bytes: [0; 4] }).unwrap();
+		assert_eq!(pool.integer(0).unwrap(), first);
+		assert_eq!(pool.utf8("a").unwrap(), 3);
+		assert!(matches!(
+			pool.replace(first, IOCpTag::Long { bytes: [0; 8] }),
+			Err(IOClassfileError::BadCpIndex(1))
+		));
+		assert!(matches!(
+			pool.replace(4, IOCpTag::Integer { bytes: [0; 4] }),
+			Err(IOClassfileError::BadCpIndex(4))
+		));
+	}
+
+	#[test]
+	fn full() {
+		let mut pool = ConstantPoolBuilder::new();
+		for i in 0..MAX_CP_INDEX as i32 - 1 {
+			pool.integer(i).unwrap();
+		}
+		assert!(matches!(pool.long(0), Err(IOClassfileError::CpFull)));
+		assert_eq!(pool.integer(-1).unwrap(), MAX_CP_INDEX);
+		assert!(matches!(pool.integer(-2), Err(IOClassfileError::CpFull)));
+		assert_eq!(pool.integer(-1).unwrap(), MAX_CP_INDEX);
+	}
+}
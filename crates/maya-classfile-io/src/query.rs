@@ -0,0 +1,116 @@
+use crate::{class_pool::IOCpTag, IOClassFile, IOClassfileError};
+
+/// Constant pool lookups that don't need the IR.
+impl IOClassFile {
+	/// The entry at the 1-based `index`, as the classfile refers to it.
+	pub fn cp_entry(&self, index: u16) -> Option<&IOCpTag> {
+		entry(&self.cp, index)
+	}
+
+	pub fn utf8_bytes_at(&self, index: u16) -> Option<&[u8]> {
+		match self.cp_entry(index)? {
+			IOCpTag::Utf8 { bytes, .. } => Some(bytes),
+			_ => None,
+		}
+	}
+
+	pub fn utf8_at(&self, index: u16) -> Result<String, IOClassfileError> {
+		utf8_at(&self.cp, index)
+	}
+
+	/// Name of the Class entry at `index`, e.g. `java/lang/String`.
+	pub fn class_name_at(&self, index: u16) -> Result<String, IOClassfileError> {
+		class_name_at(&self.cp, index)
+	}
+
+	/// Name of the class this file defines.
+	pub fn this_class_name(&self) -> Result<String, IOClassfileError> {
+		class_name_at(&self.cp, self.this_class)
+	}
+
+	/// Index of the first Utf8 entry holding `string`. Compares the encoded bytes, so nothing is decoded.
+	pub fn find_utf8(&self, string: &str) -> Option<u16> {
+		let encoded = maya_mutf8::encode(string);
+		self.position(|tag| matches!(tag, IOCpTag::Utf8 { bytes, .. } if *bytes == encoded))
+	}
+
+	/// Index of the first Class entry named `name`.
+	pub fn find_class(&self, name: &str) -> Option<u16> {
+		let name = maya_mutf8::encode(name);
+		self.position(|tag| match tag {
+			IOCpTag::Class { name_index } => {
+				matches!(self.cp_entry(*name_index), Some(IOCpTag::Utf8 { bytes, .. }) if *bytes == name)
+			}
+			_ => false,
+		})
+	}
+
+	fn position(&self, predicate: impl Fn(&IOCpTag) -> bool) -> Option<u16> {
+		self.cp.iter().position(predicate).map(|i| i as u16 + 1)
+	}
+}
+
+fn entry(cp: &[IOCpTag], index: u16) -> Option<&IOCpTag> {
+	(index as usize).checked_sub(1).and_then(|i| cp.get(i))
+}
+
+pub(crate) fn utf8_at(cp: &[IOCpTag], index: u16) -> Result<String, IOClassfileError> {
+	match entry(cp, index) {
+		Some(IOCpTag::Utf8 { bytes, .. }) => Ok(maya_mutf8::decode(bytes)?),
+		_ => Err(IOClassfileError::BadCpIndex(index)),
+	}
+}
+
+pub(crate) fn class_name_at(cp: &[IOCpTag], index: u16) -> Result<String, IOClassfileError> {
+	match entry(cp, index) {
+		Some(IOCpTag::Class { name_index }) => utf8_at(cp, *name_index),
+		_ => Err(IOClassfileError::BadCpIndex(index)),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const HELLO: &[u8] = include_bytes!("../../maya-test-bin/data/out/a/a/Hello.class");
+
+	#[test]
+	fn lookups() {
+		let class = IOClassFile::read_from_slice(HELLO).unwrap();
+		assert_eq!(class.this_class_name().unwrap(), "a/Hello");
+		assert_eq!(class.class_name_at(class.super_class).unwrap(), "java/lang/Object");
+
+		let object = class.find_class("java/lang/Object").unwrap();
+		assert_eq!(object, class.super_class);
+		let Some(IOCpTag::Class { name_index }) = class.cp_entry(object) else {
+			panic!("not a class");
+		};
+		assert_eq!(class.find_utf8("java/lang/Object"), Some(*name_index));
+		assert_eq!(class.utf8_bytes_at(*name_index), Some(b"java/lang/Object".as_slice()));
+		assert_eq!(class.utf8_at(*name_index).unwrap(), "java/lang/Object");
+
+		assert_eq!(class.find_utf8("not/in/Pool"), None);
+		assert_eq!(class.find_class("not/in/Pool"), None);
+	}
+
+	#[test]
+	fn bad_indices() {
+		let class = IOClassFile::read_from_slice(HELLO).unwrap();
+		assert!(class.cp_entry(0).is_none());
+		assert!(class.cp_entry(class.cp_count).is_none());
+		assert!(class.cp_entry(class.cp_count - 1).is_some());
+
+		// a Class entry isn't a Utf8 one and the other way around
+		assert!(class.utf8_bytes_at(class.this_class).is_none());
+		assert!(matches!(
+			class.utf8_at(class.this_class),
+			Err(IOClassfileError::BadCpIndex(index)) if index == class.this_class
+		));
+		let name = class.find_utf8("a/Hello").unwrap();
+		assert!(matches!(
+			class.class_name_at(name),
+			Err(IOClassfileError::BadCpIndex(_))
+		));
+		assert!(matches!(class.class_name_at(0), Err(IOClassfileError::BadCpIndex(0))));
+	}
+}
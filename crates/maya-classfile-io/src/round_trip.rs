@@ -0,0 +1,117 @@
+use thiserror::Error;
+
+use crate::{class_pool::CpTagKind, IOClassFile, IOClassfileError};
+
+#[derive(Debug, Error)]
+pub enum RoundTripError {
+	#[error("{0}")]
+	Classfile(#[from] IOClassfileError),
+	#[error("Written class differs from the input at offset {offset}: expected {expected:?}, wrote {found:?}")]
+	Mismatch {
+		offset: usize,
+		/// `None` if the output is longer than the input.
+		expected: Option<u8>,
+		/// `None` if the output is shorter than the input.
+		found: Option<u8>,
+	},
+	#[error("Constant pool entry written as {expected:?} was read back as {found:?}")]
+	KindMismatch { expected: CpTagKind, found: CpTagKind },
+}
+
+impl IOClassFile {
+	pub fn to_bytes(&self) -> Result<Vec<u8>, IOClassfileError> {
+		let mut out = Vec::with_capacity(self.byte_len() as usize);
+		self.write(&mut out)?;
+		Ok(out)
+	}
+
+	/// Reads `bytes`, failing unless [`write`](Self::write) reproduces them byte for byte.
+	///
+	/// The IO layer keeps Utf8 entries, attribute bodies and the order of everything as raw data,
+	/// so untouched parts of a class read this way are written back exactly as they were.
+	pub fn read_exact_layout(bytes: &[u8]) -> Result<IOClassFile, RoundTripError> {
+		let class = IOClassFile::read_from_slice(bytes)?;
+		class.assert_writes(bytes)?;
+		Ok(class)
+	}
+
+	/// Checks that writing this class gives exactly `expected`.
+	pub fn assert_writes(&self, expected: &[u8]) -> Result<(), RoundTripError> {
+		let found = self.to_bytes()?;
+		match first_difference(expected, &found) {
+			Some(offset) => Err(RoundTripError::Mismatch {
+				offset,
+				expected: expected.get(offset).copied(),
+				found: found.get(offset).copied(),
+			}),
+			None => Ok(()),
+		}
+	}
+}
+
+fn first_difference(a: &[u8], b: &[u8]) -> Option<usize> {
+	match a.iter().zip(b).position(|(a, b)| a != b) {
+		Some(offset) => Some(offset),
+		None if a.len() != b.len() => Some(a.len().min(b.len())),
+		None => None,
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const CLASSES: &[&[u8]] = &[
+		include_bytes!("../../maya-test-bin/data/out/a/a/Hello.class"),
+		include_bytes!("../../maya-test-bin/data/out/a/a/Constants.class"),
+		include_bytes!("../../maya-test-bin/data/out/a/module-info.class"),
+		include_bytes!("../../maya-test-bin/data/out/b/b/internal/Typed.class"),
+	];
+
+	#[test]
+	fn fixtures_write_back_exactly() {
+		for bytes in CLASSES {
+			IOClassFile::read_exact_layout(bytes).unwrap();
+		}
+	}
+
+	#[test]
+	fn mismatches() {
+		let bytes = CLASSES[0];
+		let mut class = IOClassFile::read_exact_layout(bytes).unwrap();
+		class.minor_version = 1;
+		assert!(matches!(
+			class.assert_writes(bytes),
+			Err(RoundTripError::Mismatch {
+				offset: 5,
+				expected: Some(0),
+				found: Some(1),
+			})
+		));
+
+		class.minor_version = 0;
+		class.trailing.push(0);
+		assert!(matches!(
+			class.assert_writes(bytes),
+			Err(RoundTripError::Mismatch {
+				expected: None,
+				found: Some(0),
+				offset,
+			}) if offset == bytes.len()
+		));
+		assert!(matches!(
+			class.assert_writes(&[bytes, &[0, 0]].concat()),
+			Err(RoundTripError::Mismatch {
+				expected: Some(0),
+				found: None,
+				..
+			})
+		));
+
+		// a class that can't be read at all
+		assert!(matches!(
+			IOClassFile::read_exact_layout(&bytes[..100]),
+			Err(RoundTripError::Classfile(_))
+		));
+	}
+}
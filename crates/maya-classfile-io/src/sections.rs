@@ -0,0 +1,217 @@
+use std::ops::Range;
+
+use maya_bytes::*;
+
+use crate::{options::Limits, IOAttributeInfo, IOClassFile, IOClassfileError, IOFieldInfo, IOMethodInfo};
+
+/// Which tables [`IOClassFile::read_with`] keeps. Skipped tables are only walked to find where
+/// they end, and are recorded in [`IOClassFile::skipped`] so they can be loaded later.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReadSections {
+	pub fields: bool,
+	pub methods: bool,
+	pub class_attributes: bool,
+}
+
+impl Default for ReadSections {
+	fn default() -> Self {
+		Self {
+			fields: true,
+			methods: true,
+			class_attributes: true,
+		}
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Section {
+	Fields,
+	Methods,
+	ClassAttributes,
+}
+
+/// A table that was not read. `range` covers its entries (after the count) in the input stream.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SkippedSection {
+	pub section: Section,
+	pub count: u16,
+	pub range: Range<u64>,
+}
+
+impl IOClassFile {
+	/// Reads a skipped table out of `data`, the same bytes the class was read from.
+	/// Does nothing if `section` was not skipped.
+	pub fn load_section(&mut self, data: &[u8], section: Section) -> Result<(), IOClassfileError> {
+		let Some(pos) = self.skipped.iter().position(|s| s.section == section) else {
+			return Ok(());
+		};
+
+		let SkippedSection { count, range, .. } = self.skipped[pos].clone();
+		let bytes = data
+			.get(range.start as usize..range.end as usize)
+			.ok_or_else(|| BytesError::not_enough_data(range.end, data.len() as u64))?;
+		let mut buffer = OffsetReader::new(bytes);
+		self.load_section_inner(&mut buffer, section, count)
+			.map_err(|e| e.at(range.start + buffer.offset()))?;
+
+		self.skipped.remove(pos);
+		Ok(())
+	}
+
+	fn load_section_inner<B: BytesReadExt>(
+		&mut self,
+		buffer: &mut B,
+		section: Section,
+		count: u16,
+	) -> Result<(), IOClassfileError> {
+		let limits = &Limits::NONE;
+		match section {
+			Section::Fields => {
+				let mut fields = maya_bytes::vec_with_capacity(count as usize)?;
+				for _ in 0..count {
+					fields.push(IOFieldInfo::read_in(buffer, &self.cp, limits)?);
+				}
+				self.fields = fields;
+			}
+			Section::Methods => {
+				let mut methods = maya_bytes::vec_with_capacity(count as usize)?;
+				for _ in 0..count {
+					methods.push(IOMethodInfo::read_in(buffer, &self.cp, limits)?);
+				}
+				self.methods = methods;
+			}
+			Section::ClassAttributes => self.attributes = IOAttributeInfo::read_all(buffer, &self.cp, count, limits)?,
+		}
+		Ok(())
+	}
+}
+
+/// Walks past a table of `count` entries, recording where it was.
+pub(crate) fn skip_section<R: std::io::Read>(
+	buffer: &mut OffsetReader<R>,
+	section: Section,
+	count: u16,
+) -> Result<SkippedSection, IOClassfileError> {
+	let start = buffer.offset();
+	match section {
+		Section::Fields | Section::Methods => skip_members(buffer, count)?,
+		Section::ClassAttributes => skip_attributes(buffer, count)?,
+	}
+	Ok(SkippedSection {
+		section,
+		count,
+		range: start..buffer.offset(),
+	})
+}
+
+fn skip_members<B: BytesReadExt>(buffer: &mut B, count: u16) -> Result<(), IOClassfileError> {
+	for _ in 0..count {
+		skip(buffer, 6)?;
+		let attributes_count = buffer.read_u16()?;
+		skip_attributes(buffer, attributes_count)?;
+	}
+	Ok(())
+}
+
+fn skip_attributes<B: BytesReadExt>(buffer: &mut B, count: u16) -> Result<(), IOClassfileError> {
+	for _ in 0..count {
+		skip(buffer, 2)?;
+		let length = buffer.read_u32()?;
+		skip(buffer, length as u64)?;
+	}
+	Ok(())
+}
+
+pub(crate) fn skip<B: BytesReadExt>(buffer: &mut B, amount: u64) -> Result<(), IOClassfileError> {
+	let skipped = std::io::copy(&mut buffer.limited(amount), &mut std::io::sink())?;
+	match skipped == amount {
+		true => Ok(()),
+		false => Err(BytesError::not_enough_data(amount, skipped).into()),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::options::ReadOptions;
+
+	const HELLO: &[u8] = include_bytes!("../../maya-test-bin/data/out/a/a/Hello.class");
+
+	fn read_skipping(data: &[u8]) -> IOClassFile {
+		let options = ReadOptions {
+			sections: ReadSections {
+				fields: false,
+				methods: false,
+				class_attributes: true,
+			},
+			..ReadOptions::default()
+		};
+		IOClassFile::read_with(&mut &data[..], &options).unwrap()
+	}
+
+	#[test]
+	fn skip_and_load() {
+		let full = IOClassFile::read_from_slice(HELLO).unwrap();
+		let mut class = read_skipping(HELLO);
+		assert!(class.fields.is_empty() && class.methods.is_empty());
+		assert_eq!(class.attributes.len(), full.attributes.len());
+		assert_eq!(
+			class.skipped.iter().map(|s| (s.section, s.count)).collect::<Vec<_>>(),
+			[
+				(Section::Fields, full.field_count),
+				(Section::Methods, full.method_count)
+			]
+		);
+
+		class.load_section(HELLO, Section::Methods).unwrap();
+		assert_eq!(class.methods.len(), full.methods.len());
+		// loading twice or a section that wasn't skipped does nothing
+		class.load_section(HELLO, Section::Methods).unwrap();
+		class.load_section(HELLO, Section::ClassAttributes).unwrap();
+		class.load_section(HELLO, Section::Fields).unwrap();
+		assert!(class.skipped.is_empty());
+		assert_eq!(class.to_bytes().unwrap(), HELLO);
+	}
+
+	#[test]
+	fn write_needs_every_section() {
+		let class = read_skipping(HELLO);
+		let mut out = Vec::new();
+		assert!(matches!(
+			class.write(&mut out),
+			Err(IOClassfileError::SectionSkipped(Section::Fields))
+		));
+	}
+
+	#[test]
+	fn load_from_other_bytes() {
+		let mut class = read_skipping(HELLO);
+		let range = class.skipped[1].range.clone();
+		let error = class
+			.load_section(&HELLO[..range.end as usize - 1], Section::Methods)
+			.unwrap_err();
+		assert!(matches!(error, IOClassfileError::Bytes(_)), "{error}");
+		// still skipped, so it can be loaded from the right bytes
+		assert_eq!(class.skipped.len(), 2);
+		class.load_section(HELLO, Section::Methods).unwrap();
+	}
+
+	#[test]
+	fn truncated_skipped_section() {
+		let class = IOClassFile::read_from_slice(HELLO).unwrap();
+		let options = ReadOptions {
+			sections: ReadSections {
+				class_attributes: false,
+				..ReadSections::default()
+			},
+			..ReadOptions::default()
+		};
+		let error = IOClassFile::read_with(&mut &HELLO[..HELLO.len() - 1], &options).unwrap_err();
+		assert!(matches!(error, IOClassfileError::Bytes(_)), "{error}");
+		let skipped = IOClassFile::read_with(&mut &HELLO[..], &options).unwrap();
+		assert_eq!(skipped.skipped[0].count, class.attribute_count);
+		assert_eq!(skipped.skipped[0].range.end, HELLO.len() as u64);
+	}
+}
@@ -0,0 +1,110 @@
+use maya_bytes::*;
+
+use crate::{class_pool::IOCpTag, query::class_name_at, IOClassFile, IOClassfileError};
+
+/// The class level metadata of a classfile, see [`IOClassFile::scan_header`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ClassSummary {
+	pub magic: u32,
+	pub minor_version: u16,
+	pub major_version: u16,
+	pub access_flags: u16,
+	pub this_class: String,
+	/// `None` for `java/lang/Object` and `module-info`.
+	pub super_class: Option<String>,
+	pub interfaces: Vec<String>,
+}
+
+impl IOClassFile {
+	/// Reads up to and including the interfaces table and stops, fields, methods and
+	/// attributes are never touched.
+	pub fn scan_header<B: BytesReadExt>(buffer: &mut B) -> Result<ClassSummary, IOClassfileError> {
+		let mut buffer = OffsetReader::new(buffer);
+		Self::scan_header_inner(&mut buffer).map_err(|e| e.at(buffer.offset()))
+	}
+
+	fn scan_header_inner<B: BytesReadExt>(buffer: &mut B) -> Result<ClassSummary, IOClassfileError> {
+		let magic = buffer.read_u32()?;
+		if magic != 0xCAFEBABE {
+			return Err(IOClassfileError::InvalidMagic(magic));
+		}
+
+		let minor_version = buffer.read_u16()?;
+		let major_version = buffer.read_u16()?;
+		let cp_count = buffer.read_u16()?;
+		let cp = IOCpTag::read_pool(buffer, cp_count)?;
+		let access_flags = buffer.read_u16()?;
+		let this_class = buffer.read_u16()?;
+		let super_class = buffer.read_u16()?;
+		let interface_count = buffer.read_u16()?;
+		let interfaces = buffer.read_u16_vec(interface_count as usize)?;
+
+		Ok(ClassSummary {
+			magic,
+			minor_version,
+			major_version,
+			access_flags,
+			this_class: class_name_at(&cp, this_class)?,
+			super_class: match super_class {
+				0 => None,
+				index => Some(class_name_at(&cp, index)?),
+			},
+			interfaces: interfaces
+				.into_iter()
+				.map(|index| class_name_at(&cp, index))
+				.collect::<Result<_, _>>()?,
+		})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const IMPL: &[u8] = include_bytes!("../../maya-test-bin/data/out/b/b/internal/Impl.class");
+	const MODULE_INFO: &[u8] = include_bytes!("../../maya-test-bin/data/out/a/module-info.class");
+
+	#[test]
+	fn scan() {
+		let mut buffer = IMPL;
+		let summary = IOClassFile::scan_header(&mut buffer).unwrap();
+		assert_eq!(
+			summary,
+			ClassSummary {
+				magic: 0xCAFEBABE,
+				minor_version: 0,
+				major_version: 61,
+				access_flags: 0x0021,
+				this_class: "b/internal/Impl".to_string(),
+				super_class: Some("java/lang/Object".to_string()),
+				interfaces: vec!["b/api/Service".to_string()],
+			}
+		);
+		// stopped right before the field count
+		let class = IOClassFile::read_from_slice(IMPL).unwrap();
+		assert_eq!(buffer[..2], class.field_count.to_be_bytes());
+
+		// nothing after the interfaces is needed
+		let header = &IMPL[..IMPL.len() - buffer.len()];
+		assert_eq!(IOClassFile::scan_header(&mut &header[..]).unwrap(), summary);
+
+		let summary = IOClassFile::scan_header(&mut &MODULE_INFO[..]).unwrap();
+		assert_eq!(summary.this_class, "module-info");
+		assert_eq!(summary.super_class, None);
+	}
+
+	#[test]
+	fn errors() {
+		let mut data = IMPL.to_vec();
+		data[0] = 0;
+		assert!(matches!(
+			IOClassFile::scan_header(&mut &data[..]),
+			Err(IOClassfileError::InvalidMagic(0x00FEBABE))
+		));
+
+		let error = IOClassFile::scan_header(&mut &IMPL[..20]).unwrap_err();
+		assert!(matches!(error.root(), IOClassfileError::Bytes(_)), "{error}");
+		assert!(error.to_string().contains("offset"), "{error}");
+	}
+}
@@ -0,0 +1,238 @@
+use thiserror::Error;
+
+use crate::{class_pool::IOCpTag, IOAttributeInfo, IOClassFile, IOClassfileError};
+
+/// A structural inconsistency that would make [`IOClassFile::write`] emit a corrupt file.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum ValidationError {
+	#[error("{what} is {count} but there are {actual} entries")]
+	CountMismatch {
+		what: &'static str,
+		count: u32,
+		actual: usize,
+	},
+	#[error("{what} cannot hold {actual}")]
+	TooMany { what: &'static str, actual: usize },
+	#[error("{what} refers to constant pool index {index}, which is out of range or unusable")]
+	BadIndex { what: String, index: u16 },
+	#[error("Attribute with name index {name_index} declares {declared} bytes but has {actual}")]
+	AttributeLength {
+		name_index: u16,
+		declared: u32,
+		actual: usize,
+	},
+	#[error("Constant pool slot {0} should be the unusable half of the preceding Long or Double")]
+	MisplacedWideSlot(u16),
+}
+
+impl IOClassFile {
+	/// Checks that every count field matches its Vec, every constant pool index is in range,
+	/// and every attribute_length matches the attribute's bytes.
+	pub fn validate(&self) -> Result<(), ValidationError> {
+		count("cp_count", self.cp_count as u32, self.cp.len() + 1)?;
+		self.validate_pool()?;
+
+		self.index("this_class", self.this_class)?;
+		if self.super_class != 0 {
+			self.index("super_class", self.super_class)?;
+		}
+
+		count("interface_count", self.interface_count as u32, self.interfaces.len())?;
+		for (i, iface) in self.interfaces.iter().enumerate() {
+			self.index(format!("interface #{i}"), *iface)?;
+		}
+
+		count("field_count", self.field_count as u32, self.fields.len())?;
+		for (i, field) in self.fields.iter().enumerate() {
+			self.index(format!("field #{i} name"), field.name_index)?;
+			self.index(format!("field #{i} descriptor"), field.descriptor_index)?;
+			count(
+				"field attributes_count",
+				field.attributes_count as u32,
+				field.attributes.len(),
+			)?;
+			self.validate_attributes(&field.attributes)?;
+		}
+
+		count("method_count", self.method_count as u32, self.methods.len())?;
+		for (i, method) in self.methods.iter().enumerate() {
+			self.index(format!("method #{i} name"), method.name_index)?;
+			self.index(format!("method #{i} descriptor"), method.descriptor_index)?;
+			count(
+				"method attributes_count",
+				method.attributes_count as u32,
+				method.attributes.len(),
+			)?;
+			self.validate_attributes(&method.attributes)?;
+		}
+
+		count("attribute_count", self.attribute_count as u32, self.attributes.len())?;
+		self.validate_attributes(&self.attributes)
+	}
+
+	/// [`validate`](Self::validate)s before writing, so nothing is written for an inconsistent file.
+	pub fn write_validated<B: maya_bytes::BytesWriteExt>(&self, buffer: &mut B) -> Result<(), IOClassfileError> {
+		self.validate()?;
+		self.write(buffer)
+	}
+
+	fn index(&self, what: impl Into<String>, index: u16) -> Result<(), ValidationError> {
+		match self.cp.get((index as usize).wrapping_sub(1)) {
+			Some(tag) if !matches!(tag, IOCpTag::Unusable) => Ok(()),
+			_ => Err(ValidationError::BadIndex {
+				what: what.into(),
+				index,
+			}),
+		}
+	}
+
+	fn validate_pool(&self) -> Result<(), ValidationError> {
+		let mut prev_wide = false;
+		for (slot, tag) in self.cp.iter().enumerate() {
+			let index = slot as u16 + 1;
+			if prev_wide != matches!(tag, IOCpTag::Unusable) {
+				return Err(ValidationError::MisplacedWideSlot(index));
+			}
+			prev_wide = tag.is_wide();
+
+			let what = || format!("constant pool entry #{index}");
+			match tag {
+				IOCpTag::Utf8 { length, bytes } => count("Utf8 length", *length as u32, bytes.len())?,
+				IOCpTag::Class { name_index } | IOCpTag::Module { name_index } | IOCpTag::Package { name_index } => {
+					self.index(what(), *name_index)?
+				}
+				IOCpTag::String { utf8_index } => self.index(what(), *utf8_index)?,
+				IOCpTag::FieldRef {
+					class_index,
+					name_and_ty_index,
+				}
+				| IOCpTag::MethodRef {
+					class_index,
+					name_and_ty_index,
+				}
+				| IOCpTag::InterfaceMethodRef {
+					class_index,
+					name_and_ty_index,
+				} => {
+					self.index(what(), *class_index)?;
+					self.index(what(), *name_and_ty_index)?;
+				}
+				IOCpTag::NameAndType {
+					name_index,
+					descriptor_index,
+				} => {
+					self.index(what(), *name_index)?;
+					self.index(what(), *descriptor_index)?;
+				}
+				IOCpTag::MethodHandle { reference_index, .. } => self.index(what(), *reference_index)?,
+				IOCpTag::MethodType { descriptor_index } => self.index(what(), *descriptor_index)?,
+				IOCpTag::Dynamic { name_and_ty_index, .. } | IOCpTag::InvokeDynamic { name_and_ty_index, .. } => {
+					self.index(what(), *name_and_ty_index)?
+				}
+				IOCpTag::Unusable
+				| IOCpTag::Integer { .. }
+				| IOCpTag::Float { .. }
+				| IOCpTag::Long { .. }
+				| IOCpTag::Double { .. } => {}
+			}
+		}
+
+		// a trailing wide entry needs its unusable half too
+		match prev_wide {
+			true => Err(ValidationError::MisplacedWideSlot(self.cp.len() as u16 + 1)),
+			false => Ok(()),
+		}
+	}
+
+	fn validate_attributes(&self, attributes: &[IOAttributeInfo]) -> Result<(), ValidationError> {
+		for attr in attributes {
+			self.index("attribute name", attr.attribute_name_index)?;
+			if attr.attribute_length as usize != attr.info.len() {
+				return Err(ValidationError::AttributeLength {
+					name_index: attr.attribute_name_index,
+					declared: attr.attribute_length,
+					actual: attr.info.len(),
+				});
+			}
+		}
+		Ok(())
+	}
+}
+
+fn count(what: &'static str, count: u32, actual: usize) -> Result<(), ValidationError> {
+	match count as usize == actual {
+		true => Ok(()),
+		false => Err(ValidationError::CountMismatch { what, count, actual }),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const HELLO: &[u8] = include_bytes!("../../maya-test-bin/data/out/a/a/Hello.class");
+	const CONSTANTS: &[u8] = include_bytes!("../../maya-test-bin/data/out/a/a/Constants.class");
+
+	fn hello() -> IOClassFile {
+		IOClassFile::read_from_slice(HELLO).unwrap()
+	}
+
+	#[test]
+	fn fixtures_are_valid() {
+		for bytes in [HELLO, CONSTANTS] {
+			let class = IOClassFile::read_from_slice(bytes).unwrap();
+			class.validate().unwrap();
+			let mut out = Vec::new();
+			class.write_validated(&mut out).unwrap();
+			assert_eq!(out, bytes);
+		}
+	}
+
+	#[test]
+	fn counts() {
+		let mut class = hello();
+		class.method_count += 1;
+		assert_eq!(
+			class.validate(),
+			Err(ValidationError::CountMismatch {
+				what: "method_count",
+				count: class.methods.len() as u32 + 1,
+				actual: class.methods.len(),
+			})
+		);
+
+		// nothing is written for an invalid class
+		let mut out = Vec::new();
+		let error = class.write_validated(&mut out).unwrap_err();
+		assert!(matches!(error, IOClassfileError::Invalid(_)), "{error}");
+		assert!(out.is_empty());
+	}
+
+	#[test]
+	fn indices() {
+		let mut class = hello();
+		class.this_class = class.cp_count;
+		assert_eq!(
+			class.validate(),
+			Err(ValidationError::BadIndex {
+				what: "this_class".to_string(),
+				index: class.cp_count,
+			})
+		);
+
+		let mut class = hello();
+		class.methods[0].attributes[0].info.pop();
+		assert!(matches!(class.validate(), Err(ValidationError::AttributeLength { .. })));
+	}
+
+	#[test]
+	fn wide_slots() {
+		let mut class = IOClassFile::read_from_slice(CONSTANTS).unwrap();
+		let wide = class.cp.iter().position(IOCpTag::is_wide).unwrap();
+		class.cp[wide + 1] = IOCpTag::Integer { bytes: [0; 4] };
+		assert_eq!(
+			class.validate(),
+			Err(ValidationError::MisplacedWideSlot(wide as u16 + 2))
+		);
+	}
+}
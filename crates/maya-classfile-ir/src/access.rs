@@ -0,0 +1,337 @@
+//! Access flags of classes, fields, methods and method parameters, plus the flags of the Module attribute.
+//!
+//! [`Display`](fmt::Display) writes the set flags as lowercase words in bit order, e.g. `public static final`,
+//! and [`FromStr`] reads them back. Bits without a name are written as a hex number.
+
+use std::{fmt, str::FromStr};
+
+use thiserror::Error;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum AccessFlagsError {
+	#[error("`{0}` and `{1}` can't be set together")]
+	Conflict(&'static str, &'static str),
+	#[error("`{0}` requires `{1}`")]
+	Requires(&'static str, &'static str),
+	#[error("`{0}` is not allowed on interface members")]
+	NotInInterface(&'static str),
+	#[error("interface {0} must be {1}")]
+	InterfaceMember(&'static str, &'static str),
+	#[error("unknown access flag `{0}`")]
+	Unknown(String),
+}
+
+trait Words: bitflags::Flags + Copy + PartialEq + 'static {
+	const WORDS: &'static [(Self, &'static str)];
+}
+
+fn conflict<F: Words>(flags: F, a: F, b: F) -> Result<(), AccessFlagsError> {
+	if flags.contains(a) && flags.contains(b) {
+		let word = |flag| {
+			F::WORDS
+				.iter()
+				.find(|(f, _)| *f == flag)
+				.map_or("unknown", |(_, word)| *word)
+		};
+		return Err(AccessFlagsError::Conflict(word(a), word(b)));
+	}
+	Ok(())
+}
+
+macro_rules! access_flags {
+	(
+		$(#[$meta:meta])*
+		pub struct $name:ident {
+			$($flag:ident = $value:literal, $word:literal;)*
+		}
+	) => {
+		bitflags::bitflags! {
+			$(#[$meta])*
+			#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+			#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(transparent))]
+			pub struct $name: u16 {
+				$(const $flag = $value;)*
+			}
+		}
+
+		impl Words for $name {
+			const WORDS: &'static [(Self, &'static str)] = &[$((Self::$flag, $word)),*];
+		}
+
+		impl $name {
+			/// The word for a single flag, as used by `Display`.
+			pub fn word(flag: Self) -> Option<&'static str> {
+				Self::WORDS.iter().find(|(f, _)| *f == flag).map(|(_, word)| *word)
+			}
+		}
+
+		impl fmt::Display for $name {
+			fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+				let mut first = true;
+				for (flag, word) in Self::WORDS {
+					if self.contains(*flag) {
+						if !first {
+							f.write_str(" ")?;
+						}
+						f.write_str(word)?;
+						first = false;
+					}
+				}
+
+				let unknown = self.bits() & !Self::all().bits();
+				if unknown != 0 {
+					if !first {
+						f.write_str(" ")?;
+					}
+					write!(f, "{unknown:#06x}")?;
+				}
+				Ok(())
+			}
+		}
+
+		impl FromStr for $name {
+			type Err = AccessFlagsError;
+
+			fn from_str(s: &str) -> Result<Self, Self::Err> {
+				s.split_whitespace().try_fold(Self::empty(), |flags, word| {
+					if let Some((flag, _)) = Self::WORDS.iter().find(|(_, w)| *w == word) {
+						return Ok(flags | *flag);
+					}
+					match word.strip_prefix("0x").map(|hex| u16::from_str_radix(hex, 16)) {
+						Some(Ok(bits)) => Ok(flags | Self::from_bits_retain(bits)),
+						_ => Err(AccessFlagsError::Unknown(word.to_string())),
+					}
+				})
+			}
+		}
+	};
+}
+
+access_flags! {
+	/// `ClassFile.access_flags`.
+	pub struct ClassAccessFlags {
+		PUBLIC = 0x0001, "public";
+		FINAL = 0x0010, "final";
+		SUPER = 0x0020, "super";
+		INTERFACE = 0x0200, "interface";
+		ABSTRACT = 0x0400, "abstract";
+		SYNTHETIC = 0x1000, "synthetic";
+		ANNOTATION = 0x2000, "annotation";
+		ENUM = 0x4000, "enum";
+		MODULE = 0x8000, "module";
+	}
+}
+
+access_flags! {
+	/// `field_info.access_flags`.
+	pub struct FieldAccessFlags {
+		PUBLIC = 0x0001, "public";
+		PRIVATE = 0x0002, "private";
+		PROTECTED = 0x0004, "protected";
+		STATIC = 0x0008, "static";
+		FINAL = 0x0010, "final";
+		VOLATILE = 0x0040, "volatile";
+		TRANSIENT = 0x0080, "transient";
+		SYNTHETIC = 0x1000, "synthetic";
+		ENUM = 0x4000, "enum";
+	}
+}
+
+access_flags! {
+	/// `method_info.access_flags`.
+	pub struct MethodAccessFlags {
+		PUBLIC = 0x0001, "public";
+		PRIVATE = 0x0002, "private";
+		PROTECTED = 0x0004, "protected";
+		STATIC = 0x0008, "static";
+		FINAL = 0x0010, "final";
+		SYNCHRONIZED = 0x0020, "synchronized";
+		BRIDGE = 0x0040, "bridge";
+		VARARGS = 0x0080, "varargs";
+		NATIVE = 0x0100, "native";
+		ABSTRACT = 0x0400, "abstract";
+		STRICT = 0x0800, "strictfp";
+		SYNTHETIC = 0x1000, "synthetic";
+	}
+}
+
+access_flags! {
+	/// Flags of an entry in the `MethodParameters` attribute.
+	pub struct ParameterAccessFlags {
+		FINAL = 0x0010, "final";
+		SYNTHETIC = 0x1000, "synthetic";
+		MANDATED = 0x8000, "mandated";
+	}
+}
+
+access_flags! {
+	/// `Module.module_flags`.
+	pub struct ModuleFlags {
+		OPEN = 0x0020, "open";
+		SYNTHETIC = 0x1000, "synthetic";
+		MANDATED = 0x8000, "mandated";
+	}
+}
+
+access_flags! {
+	/// `requires_flags` of a Module `requires` entry.
+	pub struct RequiresFlags {
+		TRANSITIVE = 0x0020, "transitive";
+		STATIC_PHASE = 0x0040, "static";
+		SYNTHETIC = 0x1000, "synthetic";
+		MANDATED = 0x8000, "mandated";
+	}
+}
+
+access_flags! {
+	/// `exports_flags` of a Module `exports` entry.
+	pub struct ExportsFlags {
+		SYNTHETIC = 0x1000, "synthetic";
+		MANDATED = 0x8000, "mandated";
+	}
+}
+
+access_flags! {
+	/// `opens_flags` of a Module `opens` entry.
+	pub struct OpensFlags {
+		SYNTHETIC = 0x1000, "synthetic";
+		MANDATED = 0x8000, "mandated";
+	}
+}
+
+impl ClassAccessFlags {
+	/// Checks the combinations JVMS 4.1 rules out.
+	pub fn validate(self) -> Result<(), AccessFlagsError> {
+		if self.contains(Self::MODULE) && self != Self::MODULE {
+			let other = Self::WORDS
+				.iter()
+				.find(|(flag, _)| *flag != Self::MODULE && self.contains(*flag));
+			return Err(AccessFlagsError::Conflict(
+				"module",
+				other.map_or("unknown", |(_, word)| word),
+			));
+		}
+
+		if self.contains(Self::INTERFACE) {
+			if !self.contains(Self::ABSTRACT) {
+				return Err(AccessFlagsError::Requires("interface", "abstract"));
+			}
+			for flag in [Self::FINAL, Self::SUPER, Self::ENUM] {
+				conflict(self, Self::INTERFACE, flag)?;
+			}
+		} else if self.contains(Self::ANNOTATION) {
+			return Err(AccessFlagsError::Requires("annotation", "interface"));
+		}
+		conflict(self, Self::FINAL, Self::ABSTRACT)
+	}
+}
+
+impl FieldAccessFlags {
+	/// Checks the combinations JVMS 4.5 rules out, `class` is the flags of the declaring class.
+	pub fn validate(self, class: ClassAccessFlags) -> Result<(), AccessFlagsError> {
+		conflict(self, Self::PUBLIC, Self::PRIVATE)?;
+		conflict(self, Self::PUBLIC, Self::PROTECTED)?;
+		conflict(self, Self::PRIVATE, Self::PROTECTED)?;
+		conflict(self, Self::FINAL, Self::VOLATILE)?;
+
+		let interface = Self::PUBLIC | Self::STATIC | Self::FINAL;
+		if class.contains(ClassAccessFlags::INTERFACE)
+			&& (!self.contains(interface) || self.intersects(Self::all() - interface - Self::SYNTHETIC))
+		{
+			return Err(AccessFlagsError::InterfaceMember("fields", "public static final"));
+		}
+		Ok(())
+	}
+}
+
+impl MethodAccessFlags {
+	/// Checks the combinations JVMS 4.6 rules out, `class` is the flags of the declaring class.
+	pub fn validate(self, class: ClassAccessFlags) -> Result<(), AccessFlagsError> {
+		conflict(self, Self::PUBLIC, Self::PRIVATE)?;
+		conflict(self, Self::PUBLIC, Self::PROTECTED)?;
+		conflict(self, Self::PRIVATE, Self::PROTECTED)?;
+
+		if self.contains(Self::ABSTRACT) {
+			for flag in [
+				Self::PRIVATE,
+				Self::STATIC,
+				Self::FINAL,
+				Self::SYNCHRONIZED,
+				Self::NATIVE,
+				Self::STRICT,
+			] {
+				conflict(self, Self::ABSTRACT, flag)?;
+			}
+		}
+
+		if class.contains(ClassAccessFlags::INTERFACE) {
+			for flag in [Self::PROTECTED, Self::FINAL, Self::SYNCHRONIZED, Self::NATIVE] {
+				if self.contains(flag) {
+					return Err(AccessFlagsError::NotInInterface(Self::word(flag).unwrap()));
+				}
+			}
+			if !self.intersects(Self::PUBLIC | Self::PRIVATE) {
+				return Err(AccessFlagsError::InterfaceMember("methods", "public or private"));
+			}
+		}
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn display_round_trip() {
+		let flags = MethodAccessFlags::PUBLIC | MethodAccessFlags::STATIC | MethodAccessFlags::VARARGS;
+		assert_eq!(flags.to_string(), "public static varargs");
+		assert_eq!("public static varargs".parse::<MethodAccessFlags>().unwrap(), flags);
+
+		let unknown = FieldAccessFlags::from_bits_retain(0x0101);
+		assert_eq!(unknown.to_string(), "public 0x0100");
+		assert_eq!(unknown.to_string().parse::<FieldAccessFlags>().unwrap(), unknown);
+
+		assert_eq!(
+			"public volatile".parse::<ClassAccessFlags>(),
+			Err(AccessFlagsError::Unknown("volatile".to_string()))
+		);
+	}
+
+	#[test]
+	fn illegal_combinations() {
+		let class = ClassAccessFlags::PUBLIC | ClassAccessFlags::SUPER;
+		let interface = ClassAccessFlags::INTERFACE | ClassAccessFlags::ABSTRACT;
+		assert!(class.validate().is_ok());
+		assert!(interface.validate().is_ok());
+		assert_eq!(
+			ClassAccessFlags::INTERFACE.validate(),
+			Err(AccessFlagsError::Requires("interface", "abstract"))
+		);
+		assert_eq!(
+			(ClassAccessFlags::FINAL | ClassAccessFlags::ABSTRACT).validate(),
+			Err(AccessFlagsError::Conflict("final", "abstract"))
+		);
+
+		assert_eq!(
+			(FieldAccessFlags::FINAL | FieldAccessFlags::VOLATILE).validate(class),
+			Err(AccessFlagsError::Conflict("final", "volatile"))
+		);
+		assert!(FieldAccessFlags::PRIVATE.validate(interface).is_err());
+
+		let abstract_method = MethodAccessFlags::PUBLIC | MethodAccessFlags::ABSTRACT;
+		assert!(abstract_method.validate(interface).is_ok());
+		assert_eq!(
+			(abstract_method | MethodAccessFlags::NATIVE).validate(class),
+			Err(AccessFlagsError::Conflict("abstract", "native"))
+		);
+		assert_eq!(
+			(abstract_method | MethodAccessFlags::STRICT).validate(class),
+			Err(AccessFlagsError::Conflict("abstract", "strictfp"))
+		);
+		assert_eq!(
+			(MethodAccessFlags::PUBLIC | MethodAccessFlags::SYNCHRONIZED).validate(interface),
+			Err(AccessFlagsError::NotInInterface("synchronized"))
+		);
+	}
+}
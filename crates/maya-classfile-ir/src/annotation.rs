@@ -0,0 +1,409 @@
+//! Creating annotations and attaching them to a class, see [`AnnotationBuilder`].
+
+use crate::{
+	attribute::{IRAttribute, IRAttributeInfo, RuntimeAnnotation, RuntimeAnnotationEVPair, RuntimeAnnotationValue},
+	class_pool::{CPConstValueRef, ConstantPool, IRClassfileError, IRCpTag},
+	intern_constant, intern_utf8, IRClassFile,
+};
+
+/// An annotation element value before it's been given constant pool entries.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ElementValue {
+	Byte(i8),
+	Char(u16),
+	Double(f64),
+	Float(f32),
+	Int(i32),
+	Long(i64),
+	Short(i16),
+	Boolean(bool),
+	String(String),
+	/// `type_name` is the enum's descriptor, like `Ljava/lang/annotation/RetentionPolicy;`.
+	Enum {
+		type_name: String,
+		const_name: String,
+	},
+	/// A return descriptor, like `Ljava/lang/String;`, `[I` or `V`.
+	Class(String),
+	Annotation(AnnotationBuilder),
+	Array(Vec<ElementValue>),
+}
+
+macro_rules! element_value_from {
+	($($ty:ty => $variant:ident),* $(,)?) => {
+		$(
+			impl From<$ty> for ElementValue {
+				fn from(value: $ty) -> Self {
+					Self::$variant(value.into())
+				}
+			}
+		)*
+	};
+}
+
+element_value_from! {
+	i8 => Byte,
+	f64 => Double,
+	f32 => Float,
+	i32 => Int,
+	i64 => Long,
+	i16 => Short,
+	bool => Boolean,
+	String => String,
+	&str => String,
+	AnnotationBuilder => Annotation,
+}
+
+impl<T: Into<ElementValue>> From<Vec<T>> for ElementValue {
+	fn from(values: Vec<T>) -> Self {
+		Self::Array(values.into_iter().map(Into::into).collect())
+	}
+}
+
+impl ElementValue {
+	pub fn enum_value(type_name: impl Into<String>, const_name: impl Into<String>) -> Self {
+		Self::Enum {
+			type_name: type_name.into(),
+			const_name: const_name.into(),
+		}
+	}
+
+	/// Resolves the value against `cp`, adding whatever entries it's missing.
+	pub fn build(&self, cp: &mut ConstantPool) -> Result<RuntimeAnnotationValue, IRClassfileError> {
+		let (tag, constant) = match self {
+			Self::Byte(value) => (b'B', IRCpTag::Integer(*value as i32)),
+			Self::Char(value) => (b'C', IRCpTag::Integer(*value as i32)),
+			Self::Double(value) => (b'D', IRCpTag::Double(*value)),
+			Self::Float(value) => (b'F', IRCpTag::Float(*value)),
+			Self::Int(value) => (b'I', IRCpTag::Integer(*value)),
+			Self::Long(value) => (b'J', IRCpTag::Long(*value)),
+			Self::Short(value) => (b'S', IRCpTag::Integer(*value as i32)),
+			Self::Boolean(value) => (b'Z', IRCpTag::Integer(*value as i32)),
+			Self::String(value) => {
+				let utf8 = intern_utf8(cp, value)?;
+				let value = CPConstValueRef::new(utf8.index, &cp[utf8.index as usize - 1]);
+				return Ok(RuntimeAnnotationValue::ConstValueIndex { tag: b's', value });
+			}
+			Self::Enum { type_name, const_name } => {
+				return Ok(RuntimeAnnotationValue::EnumConstValue {
+					type_name: intern_utf8(cp, type_name)?,
+					const_name: intern_utf8(cp, const_name)?,
+				})
+			}
+			Self::Class(descriptor) => return Ok(RuntimeAnnotationValue::ClassInfoIndex(intern_utf8(cp, descriptor)?)),
+			Self::Annotation(annotation) => {
+				return Ok(RuntimeAnnotationValue::Annotation(Box::new(annotation.build(cp)?)))
+			}
+			Self::Array(values) => {
+				let values = values.iter().map(|value| value.build(cp)).collect::<Result<_, _>>()?;
+				return Ok(RuntimeAnnotationValue::ArrayValue { values });
+			}
+		};
+
+		let index = intern_constant(cp, constant)?;
+		let value = CPConstValueRef::new(index, &cp[index as usize - 1]);
+		Ok(RuntimeAnnotationValue::ConstValueIndex { tag, value })
+	}
+}
+
+/// An annotation with its element values, turned into a [`RuntimeAnnotation`] by
+/// [`build`](Self::build) or attached directly with [`IRClassFile::annotate_class`] and friends.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnnotationBuilder {
+	/// The annotation interface's descriptor, like `Ljava/lang/Deprecated;`.
+	pub ty: String,
+	pub values: Vec<(String, ElementValue)>,
+}
+
+impl AnnotationBuilder {
+	pub fn new(ty: impl Into<String>) -> Self {
+		Self {
+			ty: ty.into(),
+			values: Vec::new(),
+		}
+	}
+
+	pub fn value(mut self, name: impl Into<String>, value: impl Into<ElementValue>) -> Self {
+		self.values.push((name.into(), value.into()));
+		self
+	}
+
+	/// Resolves the annotation against `cp`, adding whatever entries it's missing.
+	pub fn build(&self, cp: &mut ConstantPool) -> Result<RuntimeAnnotation, IRClassfileError> {
+		let ty = intern_utf8(cp, &self.ty)?;
+		let pairs = self
+			.values
+			.iter()
+			.map(|(name, value)| {
+				Ok(RuntimeAnnotationEVPair {
+					name: intern_utf8(cp, name)?,
+					value: value.build(cp)?,
+				})
+			})
+			.collect::<Result<_, IRClassfileError>>()?;
+		Ok(RuntimeAnnotation { ty, pairs })
+	}
+}
+
+impl IRClassFile {
+	/// Adds `annotation` to the class, to its `RuntimeVisibleAnnotations` if `visible` and its
+	/// `RuntimeInvisibleAnnotations` otherwise.
+	pub fn annotate_class(&mut self, annotation: &AnnotationBuilder, visible: bool) -> Result<(), IRClassfileError> {
+		let annotation = annotation.build(&mut self.cp)?;
+		attach(&mut self.cp, &mut self.attributes, visible, annotation)
+	}
+
+	/// Like [`annotate_class`](Self::annotate_class) for a field. Returns whether the field exists.
+	pub fn annotate_field(
+		&mut self,
+		name: &str,
+		descriptor: &str,
+		annotation: &AnnotationBuilder,
+		visible: bool,
+	) -> Result<bool, IRClassfileError> {
+		let Some(index) = self
+			.fields
+			.iter()
+			.position(|field| field.name() == name && field.descriptor() == descriptor)
+		else {
+			return Ok(false);
+		};
+		let annotation = annotation.build(&mut self.cp)?;
+		attach(&mut self.cp, &mut self.fields[index].attributes, visible, annotation)?;
+		Ok(true)
+	}
+
+	/// Like [`annotate_class`](Self::annotate_class) for a method. Returns whether the method exists.
+	pub fn annotate_method(
+		&mut self,
+		name: &str,
+		descriptor: &str,
+		annotation: &AnnotationBuilder,
+		visible: bool,
+	) -> Result<bool, IRClassfileError> {
+		let Some(index) = self.method_index(name, descriptor) else {
+			return Ok(false);
+		};
+		let annotation = annotation.build(&mut self.cp)?;
+		attach(&mut self.cp, &mut self.methods[index].attributes, visible, annotation)?;
+		Ok(true)
+	}
+
+	/// Adds `annotation` to the `parameter`th parameter of a method, counting from 0 and not
+	/// counting `this`. Returns whether the method exists.
+	pub fn annotate_parameter(
+		&mut self,
+		name: &str,
+		descriptor: &str,
+		parameter: usize,
+		annotation: &AnnotationBuilder,
+		visible: bool,
+	) -> Result<bool, IRClassfileError> {
+		let Some(index) = self.method_index(name, descriptor) else {
+			return Ok(false);
+		};
+		let parameters = parameter_count(descriptor);
+		if parameter >= parameters {
+			return Err(IRClassfileError::TooMany {
+				what: "annotated parameter",
+				actual: parameter + 1,
+			});
+		}
+
+		let annotation = annotation.build(&mut self.cp)?;
+		let attributes = &mut self.methods[index].attributes;
+		let existing = attributes.iter_mut().find_map(|attribute| match &mut attribute.attr {
+			IRAttribute::RuntimeVisibleParameterAnnotations { params } if visible => Some(params),
+			IRAttribute::RuntimeInvisibleParameterAnnotations { params } if !visible => Some(params),
+			_ => None,
+		});
+		let params = match existing {
+			Some(params) => params,
+			None => {
+				let (name, attr) = match visible {
+					true => (
+						"RuntimeVisibleParameterAnnotations",
+						IRAttribute::RuntimeVisibleParameterAnnotations { params: Vec::new() },
+					),
+					false => (
+						"RuntimeInvisibleParameterAnnotations",
+						IRAttribute::RuntimeInvisibleParameterAnnotations { params: Vec::new() },
+					),
+				};
+				attributes.push(IRAttributeInfo {
+					name: intern_utf8(&mut self.cp, name)?,
+					length: 0,
+					attr,
+				});
+				match &mut attributes.last_mut().unwrap().attr {
+					IRAttribute::RuntimeVisibleParameterAnnotations { params }
+					| IRAttribute::RuntimeInvisibleParameterAnnotations { params } => params,
+					_ => unreachable!(),
+				}
+			}
+		};
+		if params.len() < parameters {
+			params.resize_with(parameters, Vec::new);
+		}
+		params[parameter].push(annotation);
+		Ok(true)
+	}
+
+	fn method_index(&self, name: &str, descriptor: &str) -> Option<usize> {
+		self.methods
+			.iter()
+			.position(|method| method.name() == name && method.descriptor() == descriptor)
+	}
+}
+
+fn attach(
+	cp: &mut ConstantPool,
+	attributes: &mut Vec<IRAttributeInfo>,
+	visible: bool,
+	annotation: RuntimeAnnotation,
+) -> Result<(), IRClassfileError> {
+	let existing = attributes.iter_mut().find_map(|attribute| match &mut attribute.attr {
+		IRAttribute::RuntimeVisibleAnnotations { annotations } if visible => Some(annotations),
+		IRAttribute::RuntimeInvisibleAnnotations { annotations } if !visible => Some(annotations),
+		_ => None,
+	});
+	if let Some(annotations) = existing {
+		annotations.push(annotation);
+		return Ok(());
+	}
+
+	let (name, attr) = match visible {
+		true => (
+			"RuntimeVisibleAnnotations",
+			IRAttribute::RuntimeVisibleAnnotations {
+				annotations: vec![annotation],
+			},
+		),
+		false => (
+			"RuntimeInvisibleAnnotations",
+			IRAttribute::RuntimeInvisibleAnnotations {
+				annotations: vec![annotation],
+			},
+		),
+	};
+	attributes.push(IRAttributeInfo {
+		name: intern_utf8(cp, name)?,
+		length: 0,
+		attr,
+	});
+	Ok(())
+}
+
+/// Number of parameters in a method descriptor.
+fn parameter_count(descriptor: &str) -> usize {
+	let parameters = descriptor
+		.strip_prefix('(')
+		.and_then(|rest| rest.split(')').next())
+		.unwrap_or_default();
+	let mut chars = parameters.chars();
+	let mut count = 0;
+	while let Some(c) = chars.next() {
+		match c {
+			'[' => continue,
+			'L' => {
+				chars.by_ref().find(|&c| c == ';');
+			}
+			_ => {}
+		}
+		count += 1;
+	}
+	count
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		class_pool::CPConstValueRefKind,
+		test_util::{class, find_attr, HELLO},
+	};
+
+	const STACKMAPPER: &str = "(ILjava/lang/Object;)V";
+
+	fn generated() -> AnnotationBuilder {
+		let nested = AnnotationBuilder::new("La/Hello$HelloAnno;").value("value", "nested");
+		AnnotationBuilder::new("La/Generated;")
+			.value("id", 7i64)
+			.value("ratio", 0.5f32)
+			.value("tags", vec!["a", "b"])
+			.value("kind", ElementValue::enum_value("La/Kind;", "FAST"))
+			.value("type", ElementValue::Class("Ljava/lang/String;".to_string()))
+			.value("inner", nested)
+	}
+
+	fn reread(annotated: &IRClassFile) -> IRClassFile {
+		let mut written = Vec::new();
+		annotated.write(&mut written).unwrap();
+		class(&written)
+	}
+
+	#[test]
+	fn annotate_class() {
+		let mut hello = class(HELLO);
+		hello.annotate_class(&generated(), false).unwrap();
+		let hello = reread(&hello);
+
+		let IRAttribute::RuntimeInvisibleAnnotations { annotations } =
+			find_attr(&hello.attributes, "RuntimeInvisibleAnnotations")
+		else {
+			unreachable!()
+		};
+		let pairs = &annotations[0].pairs;
+		assert_eq!(annotations[0].ty.data.as_str(), "La/Generated;");
+		assert_eq!(pairs.len(), 6);
+		assert!(
+			matches!(&pairs[0].value, RuntimeAnnotationValue::ConstValueIndex { tag: b'J', value }
+			if matches!(value.kind, CPConstValueRefKind::Long(7)))
+		);
+		assert!(matches!(&pairs[2].value, RuntimeAnnotationValue::ArrayValue { values } if values.len() == 2));
+		assert!(matches!(&pairs[5].value, RuntimeAnnotationValue::Annotation(inner)
+			if inner.ty.data.as_str() == "La/Hello$HelloAnno;"));
+	}
+
+	#[test]
+	fn annotate_method() {
+		let mut hello = class(HELLO);
+		assert!(hello
+			.annotate_method("stackmapper", STACKMAPPER, &generated(), true)
+			.unwrap());
+		assert!(!hello.annotate_method("main", "()V", &generated(), true).unwrap());
+		let hello = reread(&hello);
+
+		// the existing RuntimeVisibleAnnotations grew instead of a second one being added
+		let stackmapper = hello.find_method("stackmapper", STACKMAPPER).unwrap();
+		let visible = stackmapper
+			.attributes
+			.iter()
+			.filter_map(|attr| match &attr.attr {
+				IRAttribute::RuntimeVisibleAnnotations { annotations } => Some(annotations.len()),
+				_ => None,
+			})
+			.collect::<Vec<_>>();
+		assert_eq!(visible, [2]);
+	}
+
+	#[test]
+	fn annotate_parameter() {
+		let mut hello = class(HELLO);
+		assert!(hello
+			.annotate_parameter("stackmapper", STACKMAPPER, 1, &generated(), false)
+			.unwrap());
+		assert!(hello
+			.annotate_parameter("stackmapper", STACKMAPPER, 2, &generated(), false)
+			.is_err());
+		let hello = reread(&hello);
+
+		let stackmapper = hello.find_method("stackmapper", STACKMAPPER).unwrap();
+		let IRAttribute::RuntimeInvisibleParameterAnnotations { params } =
+			find_attr(&stackmapper.attributes, "RuntimeInvisibleParameterAnnotations")
+		else {
+			unreachable!()
+		};
+		assert_eq!(params.iter().map(Vec::len).collect::<Vec<_>>(), [0, 1]);
+	}
+}
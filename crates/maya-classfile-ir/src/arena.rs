@@ -0,0 +1,485 @@
+//! Parsing a class into an arena, see [`ArenaClassFile`], for pipelines that parse a class, look at
+//! it and throw it away over many classes.
+//!
+//! ```ignore
+//! let mut arena = Bump::new();
+//! for bytes in classes {
+//!     let class = ArenaClassFile::parse(&arena, bytes)?;
+//!     // ...
+//!     arena.reset();
+//! }
+//! ```
+
+pub use bumpalo::Bump;
+use maya_bytes::{BytesError, BytesReadExt};
+use maya_classfile_io::{
+	borrowed::{AttributeRef, CpEntryRef, MemberRef},
+	class_pool::IOCpTag,
+	IOClassFile, IOClassfileError,
+};
+
+use crate::{
+	access::{ClassAccessFlags, FieldAccessFlags, MethodAccessFlags},
+	class_pool::IRClassfileError,
+	code::instruction_len,
+	opcode::OpcodeInfo,
+	ClassFileVersion,
+};
+
+/// A class with its constants, members, attributes and the instructions of its code all in one
+/// [`Bump`], which frees them together when it's reset or dropped instead of one by one. Strings are
+/// decoded into the arena and references between constants are resolved to them, attributes other
+/// than Code are kept as their bytes. Nothing in it needs dropping, nor refers to the input.
+#[derive(Debug, Clone, Copy)]
+pub struct ArenaClassFile<'a> {
+	pub version: ClassFileVersion,
+	pub access_flags: ClassAccessFlags,
+	pub cp: &'a [ArenaCpTag<'a>],
+	pub this_class: &'a str,
+	/// `None` for `java/lang/Object` and modules.
+	pub super_class: Option<&'a str>,
+	pub interfaces: &'a [&'a str],
+	pub fields: &'a [ArenaField<'a>],
+	pub methods: &'a [ArenaMethod<'a>],
+	pub attributes: &'a [ArenaAttribute<'a>],
+}
+
+/// A constant pool entry, with the constants it refers to resolved to the strings they hold where
+/// that's all they are.
+#[derive(Debug, Clone, Copy)]
+pub enum ArenaCpTag<'a> {
+	Unusable,
+	Utf8(&'a str),
+	Integer(i32),
+	Float(f32),
+	Long(i64),
+	Double(f64),
+	Class(&'a str),
+	String(&'a str),
+	FieldRef(ArenaMemberRef<'a>),
+	MethodRef(ArenaMemberRef<'a>),
+	InterfaceMethodRef(ArenaMemberRef<'a>),
+	NameAndType {
+		name: &'a str,
+		descriptor: &'a str,
+	},
+	MethodHandle {
+		kind: u8,
+		/// Index of the field or method constant.
+		reference: u16,
+	},
+	MethodType(&'a str),
+	Dynamic {
+		bootstrap_method: u16,
+		name: &'a str,
+		descriptor: &'a str,
+	},
+	InvokeDynamic {
+		bootstrap_method: u16,
+		name: &'a str,
+		descriptor: &'a str,
+	},
+	Module(&'a str),
+	Package(&'a str),
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ArenaMemberRef<'a> {
+	pub owner: &'a str,
+	pub name: &'a str,
+	pub descriptor: &'a str,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ArenaField<'a> {
+	pub access_flags: FieldAccessFlags,
+	pub name: &'a str,
+	pub descriptor: &'a str,
+	pub attributes: &'a [ArenaAttribute<'a>],
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ArenaMethod<'a> {
+	pub access_flags: MethodAccessFlags,
+	pub name: &'a str,
+	pub descriptor: &'a str,
+	/// The Code attribute, which isn't in `attributes`.
+	pub code: Option<ArenaCode<'a>>,
+	pub attributes: &'a [ArenaAttribute<'a>],
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ArenaAttribute<'a> {
+	pub name: &'a str,
+	pub data: &'a [u8],
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ArenaCode<'a> {
+	pub max_stack: u16,
+	pub max_locals: u16,
+	pub code: &'a [u8],
+	pub insns: &'a [ArenaInsn<'a>],
+	pub exception_table: &'a [ArenaHandler<'a>],
+	pub attributes: &'a [ArenaAttribute<'a>],
+}
+
+/// An instruction, not decoded past finding where it starts and ends.
+#[derive(Debug, Clone, Copy)]
+pub struct ArenaInsn<'a> {
+	pub pc: u32,
+	/// The opcode followed by the operands, `wide` included.
+	pub bytes: &'a [u8],
+}
+
+impl ArenaInsn<'_> {
+	pub fn opcode(&self) -> u8 {
+		self.bytes[0]
+	}
+
+	pub fn info(&self) -> &'static OpcodeInfo {
+		// only instructions with a known opcode get this far
+		OpcodeInfo::of(self.opcode()).unwrap()
+	}
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ArenaHandler<'a> {
+	pub start_pc: u16,
+	pub end_pc: u16,
+	pub handler_pc: u16,
+	/// `None` for handlers of any exception.
+	pub catch_type: Option<&'a str>,
+}
+
+impl<'a> ArenaClassFile<'a> {
+	/// Parses the classfile in `bytes` into `arena`. `bytes` is only borrowed while parsing.
+	pub fn parse(arena: &'a Bump, bytes: &[u8]) -> Result<Self, IRClassfileError> {
+		let raw = IOClassFile::read_borrowed(bytes)?;
+		let utf8 = raw
+			.cp
+			.iter()
+			.map(|entry| match entry {
+				CpEntryRef::Utf8(bytes) => Ok(Some(&*arena.alloc_str(&maya_mutf8::decode(bytes)?))),
+				CpEntryRef::Other(_) => Ok(None),
+			})
+			.collect::<Result<Vec<_>, IRClassfileError>>()?;
+		let pool = Pool { raw: &raw.cp, utf8 };
+
+		let cp = raw
+			.cp
+			.iter()
+			.zip(&pool.utf8)
+			.map(|(entry, utf8)| match (entry, utf8) {
+				(CpEntryRef::Other(tag), _) => pool.tag(tag),
+				(CpEntryRef::Utf8(_), utf8) => Ok(ArenaCpTag::Utf8(utf8.unwrap())),
+			})
+			.collect::<Result<Vec<_>, _>>()?;
+		let interfaces = raw
+			.interfaces
+			.iter()
+			.map(|&index| pool.class(index))
+			.collect::<Result<Vec<_>, _>>()?;
+		let fields = raw
+			.fields
+			.iter()
+			.map(|field| {
+				Ok(ArenaField {
+					access_flags: FieldAccessFlags::from_bits_retain(field.access_flags),
+					name: pool.utf8(field.name_index)?,
+					descriptor: pool.utf8(field.descriptor_index)?,
+					attributes: attributes(arena, &pool, &field.attributes)?,
+				})
+			})
+			.collect::<Result<Vec<_>, IRClassfileError>>()?;
+		let methods = raw
+			.methods
+			.iter()
+			.map(|method| method_from(arena, &pool, method))
+			.collect::<Result<Vec<_>, _>>()?;
+
+		Ok(Self {
+			version: ClassFileVersion {
+				major: raw.major_version,
+				minor: raw.minor_version,
+			},
+			access_flags: ClassAccessFlags::from_bits_retain(raw.access_flags),
+			cp: arena.alloc_slice_copy(&cp),
+			this_class: pool.class(raw.this_class)?,
+			super_class: match raw.super_class {
+				0 => None,
+				index => Some(pool.class(index)?),
+			},
+			interfaces: arena.alloc_slice_copy(&interfaces),
+			fields: arena.alloc_slice_copy(&fields),
+			methods: arena.alloc_slice_copy(&methods),
+			attributes: attributes(arena, &pool, &raw.attributes)?,
+		})
+	}
+
+	pub fn method(&self, name: &str, descriptor: &str) -> Option<&'a ArenaMethod<'a>> {
+		self.methods
+			.iter()
+			.find(|method| method.name == name && method.descriptor == descriptor)
+	}
+}
+
+/// The constant pool while it's being resolved.
+struct Pool<'r, 'a> {
+	raw: &'r [CpEntryRef<'r>],
+	/// The decoded Utf8 constants, by index - 1.
+	utf8: Vec<Option<&'a str>>,
+}
+
+impl<'a> Pool<'_, 'a> {
+	fn raw(&self, index: u16) -> Result<&IOCpTag, IRClassfileError> {
+		match self.raw.get((index as usize).wrapping_sub(1)) {
+			Some(CpEntryRef::Other(tag)) => Ok(tag),
+			_ => Err(IOClassfileError::BadCpIndex(index).into()),
+		}
+	}
+
+	fn utf8(&self, index: u16) -> Result<&'a str, IRClassfileError> {
+		self.utf8
+			.get((index as usize).wrapping_sub(1))
+			.copied()
+			.flatten()
+			.ok_or(IOClassfileError::BadCpIndex(index).into())
+	}
+
+	fn class(&self, index: u16) -> Result<&'a str, IRClassfileError> {
+		match self.raw(index)? {
+			IOCpTag::Class { name_index } => self.utf8(*name_index),
+			_ => Err(IOClassfileError::BadCpIndex(index).into()),
+		}
+	}
+
+	fn name_and_type(&self, index: u16) -> Result<(&'a str, &'a str), IRClassfileError> {
+		match self.raw(index)? {
+			IOCpTag::NameAndType {
+				name_index,
+				descriptor_index,
+			} => Ok((self.utf8(*name_index)?, self.utf8(*descriptor_index)?)),
+			_ => Err(IOClassfileError::BadCpIndex(index).into()),
+		}
+	}
+
+	fn member(&self, class_index: u16, name_and_ty_index: u16) -> Result<ArenaMemberRef<'a>, IRClassfileError> {
+		let (name, descriptor) = self.name_and_type(name_and_ty_index)?;
+		Ok(ArenaMemberRef {
+			owner: self.class(class_index)?,
+			name,
+			descriptor,
+		})
+	}
+
+	fn tag(&self, tag: &IOCpTag) -> Result<ArenaCpTag<'a>, IRClassfileError> {
+		Ok(match tag {
+			IOCpTag::Unusable => ArenaCpTag::Unusable,
+			IOCpTag::Utf8 { .. } => unreachable!("borrowed pools keep Utf8 constants apart"),
+			IOCpTag::Integer { bytes } => ArenaCpTag::Integer(i32::from_be_bytes(*bytes)),
+			IOCpTag::Float { bytes } => ArenaCpTag::Float(f32::from_be_bytes(*bytes)),
+			IOCpTag::Long { bytes } => ArenaCpTag::Long(i64::from_be_bytes(*bytes)),
+			IOCpTag::Double { bytes } => ArenaCpTag::Double(f64::from_be_bytes(*bytes)),
+			IOCpTag::Class { name_index } => ArenaCpTag::Class(self.utf8(*name_index)?),
+			IOCpTag::String { utf8_index } => ArenaCpTag::String(self.utf8(*utf8_index)?),
+			IOCpTag::FieldRef {
+				class_index,
+				name_and_ty_index,
+			} => ArenaCpTag::FieldRef(self.member(*class_index, *name_and_ty_index)?),
+			IOCpTag::MethodRef {
+				class_index,
+				name_and_ty_index,
+			} => ArenaCpTag::MethodRef(self.member(*class_index, *name_and_ty_index)?),
+			IOCpTag::InterfaceMethodRef {
+				class_index,
+				name_and_ty_index,
+			} => ArenaCpTag::InterfaceMethodRef(self.member(*class_index, *name_and_ty_index)?),
+			IOCpTag::NameAndType {
+				name_index,
+				descriptor_index,
+			} => ArenaCpTag::NameAndType {
+				name: self.utf8(*name_index)?,
+				descriptor: self.utf8(*descriptor_index)?,
+			},
+			IOCpTag::MethodHandle {
+				reference_kind,
+				reference_index,
+			} => ArenaCpTag::MethodHandle {
+				kind: *reference_kind,
+				reference: *reference_index,
+			},
+			IOCpTag::MethodType { descriptor_index } => ArenaCpTag::MethodType(self.utf8(*descriptor_index)?),
+			IOCpTag::Dynamic {
+				bootstrap_method_attr_index,
+				name_and_ty_index,
+			} => {
+				let (name, descriptor) = self.name_and_type(*name_and_ty_index)?;
+				ArenaCpTag::Dynamic {
+					bootstrap_method: *bootstrap_method_attr_index,
+					name,
+					descriptor,
+				}
+			}
+			IOCpTag::InvokeDynamic {
+				bootstrap_method_attr_index,
+				name_and_ty_index,
+			} => {
+				let (name, descriptor) = self.name_and_type(*name_and_ty_index)?;
+				ArenaCpTag::InvokeDynamic {
+					bootstrap_method: *bootstrap_method_attr_index,
+					name,
+					descriptor,
+				}
+			}
+			IOCpTag::Module { name_index } => ArenaCpTag::Module(self.utf8(*name_index)?),
+			IOCpTag::Package { name_index } => ArenaCpTag::Package(self.utf8(*name_index)?),
+		})
+	}
+}
+
+fn attributes<'a>(
+	arena: &'a Bump,
+	pool: &Pool<'_, 'a>,
+	raw: &[AttributeRef],
+) -> Result<&'a [ArenaAttribute<'a>], IRClassfileError> {
+	let attributes = raw
+		.iter()
+		.map(|attribute| {
+			Ok(ArenaAttribute {
+				name: pool.utf8(attribute.attribute_name_index)?,
+				data: arena.alloc_slice_copy(attribute.info),
+			})
+		})
+		.collect::<Result<Vec<_>, IRClassfileError>>()?;
+	Ok(arena.alloc_slice_copy(&attributes))
+}
+
+fn method_from<'a>(arena: &'a Bump, pool: &Pool<'_, 'a>, raw: &MemberRef) -> Result<ArenaMethod<'a>, IRClassfileError> {
+	let mut code = None;
+	let mut rest = Vec::with_capacity(raw.attributes.len());
+	for attribute in &raw.attributes {
+		match pool.utf8(attribute.attribute_name_index)? {
+			"Code" => code = Some(code_from(arena, pool, attribute.info)?),
+			_ => rest.push(*attribute),
+		}
+	}
+	Ok(ArenaMethod {
+		access_flags: MethodAccessFlags::from_bits_retain(raw.access_flags),
+		name: pool.utf8(raw.name_index)?,
+		descriptor: pool.utf8(raw.descriptor_index)?,
+		code,
+		attributes: attributes(arena, pool, &rest)?,
+	})
+}
+
+fn code_from<'a>(arena: &'a Bump, pool: &Pool<'_, 'a>, mut data: &[u8]) -> Result<ArenaCode<'a>, IRClassfileError> {
+	let max_stack = data.read_u16()?;
+	let max_locals = data.read_u16()?;
+	let code_len = data.read_u32()? as usize;
+	let code = &*arena.alloc_slice_copy(take(&mut data, code_len)?);
+
+	let mut insns = Vec::new();
+	let mut pc = 0;
+	while pc < code.len() {
+		let len = instruction_len(code, pc)?;
+		let bytes = code.get(pc..pc + len).ok_or(IRClassfileError::TruncatedCode(pc))?;
+		insns.push(ArenaInsn { pc: pc as u32, bytes });
+		pc += len;
+	}
+
+	let handlers = data.read_u16()?;
+	let mut exception_table = Vec::with_capacity(handlers as usize);
+	for _ in 0..handlers {
+		exception_table.push(ArenaHandler {
+			start_pc: data.read_u16()?,
+			end_pc: data.read_u16()?,
+			handler_pc: data.read_u16()?,
+			catch_type: match data.read_u16()? {
+				0 => None,
+				index => Some(pool.class(index)?),
+			},
+		});
+	}
+
+	let count = data.read_u16()?;
+	let mut raw = Vec::with_capacity(count as usize);
+	for _ in 0..count {
+		let attribute_name_index = data.read_u16()?;
+		let len = data.read_u32()? as usize;
+		raw.push(AttributeRef {
+			attribute_name_index,
+			info: take(&mut data, len)?,
+		});
+	}
+
+	Ok(ArenaCode {
+		max_stack,
+		max_locals,
+		code,
+		insns: arena.alloc_slice_copy(&insns),
+		exception_table: arena.alloc_slice_copy(&exception_table),
+		attributes: attributes(arena, pool, &raw)?,
+	})
+}
+
+fn take<'b>(data: &mut &'b [u8], len: usize) -> Result<&'b [u8], BytesError> {
+	if data.len() < len {
+		return Err(BytesError::not_enough_data(len as u64, data.len() as u64));
+	}
+	let (taken, rest) = data.split_at(len);
+	*data = rest;
+	Ok(taken)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		code::Opcodes,
+		insn::Insn,
+		test_util::{class, A_MODULE_INFO, CONSTANTS, HELLO},
+	};
+
+	#[test]
+	fn arena_parse() {
+		let arena = Bump::new();
+		let hello = ArenaClassFile::parse(&arena, HELLO).unwrap();
+		let mut ir = class(HELLO);
+		assert_eq!(hello.this_class, "a/Hello");
+		assert_eq!(hello.super_class, Some("java/lang/Object"));
+		assert_eq!(hello.cp.len(), ir.cp.len());
+		assert!(hello.cp.iter().any(|tag| matches!(
+			tag,
+			ArenaCpTag::MethodRef(method) if method.owner == "java/io/PrintStream" && method.name == "println"
+		)));
+		assert_eq!(hello.methods.len(), ir.methods.len());
+
+		let main = hello.method("main", "([Ljava/lang/String;)V").unwrap();
+		let code = main.code.unwrap();
+		let insns = ir.method_insns("main", "([Ljava/lang/String;)V").unwrap().unwrap();
+		let ops = insns
+			.insns
+			.iter()
+			.filter(|insn| !matches!(insn, Insn::Label(_) | Insn::Line(_) | Insn::Frame { .. }));
+		assert_eq!(code.insns.len(), ops.count());
+		assert_eq!(code.insns[0].info().name, "getstatic");
+		assert_eq!(code.insns.last().unwrap().bytes, [Opcodes::RETURN]);
+		assert!(code
+			.attributes
+			.iter()
+			.any(|attribute| attribute.name == "LineNumberTable"));
+		assert!(main.attributes.iter().all(|attribute| attribute.name != "Code"));
+	}
+
+	#[test]
+	fn arena_reset() {
+		let mut arena = Bump::new();
+		// the whole lot is freed together before parsing the next
+		for bytes in [HELLO, CONSTANTS, A_MODULE_INFO] {
+			arena.reset();
+			ArenaClassFile::parse(&arena, bytes).unwrap();
+		}
+		assert!(ArenaClassFile::parse(&arena, &HELLO[..100]).is_err());
+	}
+}
@@ -0,0 +1,900 @@
+//! A Jasmin-like text form of a class that can be edited and assembled back, see [`to_text`],
+//! [`assemble`] and [`IRClassFile::assemble`].
+//!
+//! ```text
+//! .version 52 0
+//! .class public super Main
+//! .super java/lang/Object
+//! .field private static count I
+//! .method public static main ([Ljava/lang/String;)V
+//!   .limit stack 2
+//!   .limit locals 1
+//! L0:
+//!   .line 3
+//!   getstatic java/lang/System.out:Ljava/io/PrintStream;
+//!   ldc "Hello World"
+//!   invokevirtual java/io/PrintStream.println:(Ljava/lang/String;)V
+//!   return
+//! .end method
+//! ```
+//!
+//! Classes, members and constants are written by name or value and looked up in the pool, or added
+//! to it, when assembling. `ldc` of a constant without a text form is written as its pool index,
+//! like `ldc #12`, and `invokedynamic` by the index of its bootstrap method. Branches, exception
+//! ranges, local variables and frames refer to labels. Everything without a text form, like
+//! annotations, signatures and bootstrap methods, is left as it is on the class, so a listing can
+//! be assembled back onto the class it came from.
+
+use std::{
+	collections::HashMap,
+	fmt::{self, Write},
+	io::Cursor,
+};
+
+use thiserror::Error;
+
+use crate::{
+	access::{ClassAccessFlags, FieldAccessFlags, MethodAccessFlags},
+	attribute::{CodeAttribute, IRAttribute, IRAttributeInfo, VerificationTypeInfo},
+	builder::ClassBuilder,
+	class_pool::{CPInvokeDynamicRef, CPTagRef, CPUtf8Ref, IRClassfileError, IRCpTag},
+	code::{descriptor_slots, mnemonic, Instructions, Opcodes},
+	insn::{FrameValue, Insn, InsnList, Label, LocalVariable, TryCatchBlock},
+	intern_constant,
+	opcode::OpcodeInfo,
+	IRClassFile,
+};
+
+#[derive(Debug, Error)]
+pub enum AsmError {
+	#[error("line {line}: {message}")]
+	Syntax { line: usize, message: String },
+	#[error("{0}")]
+	Class(#[from] IRClassfileError),
+}
+
+/// Writes the class's header, fields and methods. Takes the class mutably because decoding stack
+/// map frames can add Class entries, see [`IRClassFile::initial_locals`].
+pub fn to_text(class: &mut IRClassFile) -> Result<String, IRClassfileError> {
+	let mut out = String::new();
+	let methods = class
+		.methods
+		.iter()
+		.map(|method| (method.name().to_string(), method.descriptor().to_string()))
+		.collect::<Vec<_>>();
+	let mut bodies = Vec::with_capacity(methods.len());
+	for (name, descriptor) in &methods {
+		bodies.push(class.method_insns(name, descriptor)?);
+	}
+	write_class(&mut out, class, &bodies).expect("writing to a String can't fail");
+	Ok(out)
+}
+
+/// Assembles a class from scratch, see [`IRClassFile::assemble`].
+pub fn assemble(text: &str) -> Result<IRClassFile, AsmError> {
+	let name = text
+		.lines()
+		.find_map(|line| line.trim().strip_prefix(".class "))
+		.and_then(|line| line.split_whitespace().last())
+		.ok_or_else(|| AsmError::Syntax {
+			line: 1,
+			message: "missing .class".to_string(),
+		})?;
+	let mut class = ClassBuilder::new(name).build();
+	class.assemble(text)?;
+	Ok(class)
+}
+
+fn write_class(out: &mut String, class: &IRClassFile, bodies: &[Option<InsnList>]) -> fmt::Result {
+	writeln!(out, ".version {} {}", class.version.major, class.version.minor)?;
+	writeln!(out, ".class {}", header(class.access_flags, [class.class_name()]))?;
+	if let Some(name) = class.super_name() {
+		writeln!(out, ".super {name}")?;
+	}
+	for name in class.interface_names() {
+		writeln!(out, ".implements {name}")?;
+	}
+	for field in &class.fields {
+		writeln!(
+			out,
+			".field {}",
+			header(field.access_flags, [field.name(), field.descriptor()])
+		)?;
+	}
+	for (method, body) in class.methods.iter().zip(bodies) {
+		writeln!(
+			out,
+			".method {}",
+			header(method.access_flags, [method.name(), method.descriptor()])
+		)?;
+		let code = method.attributes.iter().find_map(|attribute| match &attribute.attr {
+			IRAttribute::Code(code) => Some(code),
+			_ => None,
+		});
+		if let (Some(code), Some(insns)) = (code, body) {
+			write_code(out, class, code, insns)?;
+		}
+		writeln!(out, ".end method")?;
+	}
+	Ok(())
+}
+
+fn header<const N: usize>(flags: impl fmt::Display, rest: [&str; N]) -> String {
+	let flags = flags.to_string();
+	let rest = rest.join(" ");
+	match flags.is_empty() {
+		true => rest,
+		false => format!("{flags} {rest}"),
+	}
+}
+
+fn write_code(out: &mut String, class: &IRClassFile, code: &CodeAttribute, insns: &InsnList) -> fmt::Result {
+	let mut names = HashMap::new();
+	for insn in &insns.insns {
+		if let Insn::Label(label) = insn {
+			let name = format!("L{}", names.len());
+			names.entry(*label).or_insert(name);
+		}
+	}
+	let label = |label: &Label| names.get(label).map_or("L?", String::as_str);
+	let class_name = |index: u16| match class.cp.get((index as usize).wrapping_sub(1)) {
+		Some(IRCpTag::Class(name)) => name.data.to_string(),
+		_ => format!("#{index}"),
+	};
+
+	writeln!(out, "  .limit stack {}", code.max_stack)?;
+	writeln!(out, "  .limit locals {}", code.max_locals)?;
+	for block in &insns.try_catch_blocks {
+		let catch_type = match block.catch_type {
+			0 => "any".to_string(),
+			index => class_name(index),
+		};
+		writeln!(
+			out,
+			"  .catch {catch_type} from {} to {} using {}",
+			label(&block.start),
+			label(&block.end),
+			label(&block.handler)
+		)?;
+	}
+	for (directive, variables) in [
+		(".var", &insns.local_variables),
+		(".vartype", &insns.local_variable_types),
+	] {
+		for variable in variables {
+			writeln!(
+				out,
+				"  {directive} {} is {} {} from {} to {}",
+				variable.index,
+				variable.name.data,
+				variable.descriptor.data,
+				label(&variable.start),
+				label(&variable.end)
+			)?;
+		}
+	}
+
+	for insn in &insns.insns {
+		match insn {
+			Insn::Label(l) => writeln!(out, "{}:", label(l))?,
+			Insn::Line(line) => writeln!(out, "  .line {line}")?,
+			Insn::Frame { locals, stack } => {
+				let value = |value: &FrameValue| match value {
+					FrameValue::Uninitialized(l) => format!("uninitialized {}", label(l)),
+					FrameValue::Type(info) => match info {
+						VerificationTypeInfo::TopVariableInfo => "top".to_string(),
+						VerificationTypeInfo::IntegerVariableInfo => "int".to_string(),
+						VerificationTypeInfo::FloatVariableInfo => "float".to_string(),
+						VerificationTypeInfo::LongVariableInfo => "long".to_string(),
+						VerificationTypeInfo::DoubleVariableInfo => "double".to_string(),
+						VerificationTypeInfo::NullVariableInfo => "null".to_string(),
+						VerificationTypeInfo::UninitializedThisVariableInfo => "uninitializedThis".to_string(),
+						VerificationTypeInfo::ObjectVariableInfo { cpool_idx } => {
+							format!("object {}", class_name(*cpool_idx))
+						}
+						VerificationTypeInfo::UninitializedVariableInfo { offset } => {
+							format!("uninitialized @{offset}")
+						}
+					},
+				};
+				let mut line = String::from("  .frame");
+				for local in locals {
+					write!(line, " {}", value(local))?;
+				}
+				line.push_str(" |");
+				for entry in stack {
+					write!(line, " {}", value(entry))?;
+				}
+				writeln!(out, "{line}")?;
+			}
+			Insn::Op(instruction) => writeln!(out, "  {}", instruction_text(&class.cp, instruction))?,
+			Insn::Jump { opcode, target } => writeln!(out, "  {} {}", mnemonic(*opcode).unwrap_or("?"), label(target))?,
+			Insn::TableSwitch { default, low, targets } => {
+				let mut line = format!("  tableswitch {low}");
+				for target in targets {
+					write!(line, " {}", label(target))?;
+				}
+				writeln!(out, "{line} default {}", label(default))?;
+			}
+			Insn::LookupSwitch { default, pairs } => {
+				let mut line = String::from("  lookupswitch");
+				for (value, target) in pairs {
+					write!(line, " {value} {}", label(target))?;
+				}
+				writeln!(out, "{line} default {}", label(default))?;
+			}
+		}
+	}
+	Ok(())
+}
+
+/// The text form of an instruction, its mnemonic and its operands.
+pub(crate) fn instruction_text(cp: &[IRCpTag], instruction: &Instructions) -> String {
+	let name = mnemonic(instruction.opcode()).unwrap_or("?");
+	let member = |owner: &CPUtf8Ref, name: &CPUtf8Ref, descriptor: &CPUtf8Ref| {
+		format!("{}.{}:{}", owner.data, name.data, descriptor.data)
+	};
+	let operands = match instruction {
+		Instructions::BIPUSH(value) => value.to_string(),
+		Instructions::SIPUSH(value) => value.to_string(),
+		Instructions::LDC(constant) | Instructions::LDC_W(constant) | Instructions::LDC2_W(constant) => {
+			match &constant.tag {
+				IRCpTag::Integer(value) => value.to_string(),
+				IRCpTag::Float(value) => format!("{value:?}f"),
+				IRCpTag::Long(value) => format!("{value}L"),
+				IRCpTag::Double(value) => format!("{value:?}d"),
+				IRCpTag::String(utf8) => format!("\"{}\"", utf8.data.escape_debug()),
+				IRCpTag::Class(name) => format!("class {}", name.data),
+				_ => format!("#{}", constant.index),
+			}
+		}
+		Instructions::ILOAD(index)
+		| Instructions::LLOAD(index)
+		| Instructions::FLOAD(index)
+		| Instructions::DLOAD(index)
+		| Instructions::ALOAD(index)
+		| Instructions::ISTORE(index)
+		| Instructions::LSTORE(index)
+		| Instructions::FSTORE(index)
+		| Instructions::DSTORE(index)
+		| Instructions::ASTORE(index)
+		| Instructions::RET(index) => index.to_string(),
+		Instructions::IINC(index, value) => format!("{index} {value}"),
+		Instructions::GETSTATIC(field)
+		| Instructions::PUTSTATIC(field)
+		| Instructions::GETFIELD(field)
+		| Instructions::PUTFIELD(field) => member(&field.class.data, &field.name_and_ty.name, &field.name_and_ty.ty),
+		Instructions::INVOKEVIRTUAL(method)
+		| Instructions::INVOKESPECIAL(method)
+		| Instructions::INVOKESTATIC(method)
+		| Instructions::INVOKEINTERFACE(method, _) => {
+			let text = member(&method.class.data, &method.name_and_ty.name, &method.name_and_ty.ty);
+			let interface = matches!(
+				cp.get((method.index as usize).wrapping_sub(1)),
+				Some(IRCpTag::InterfaceMethodRef { .. })
+			);
+			match interface && !matches!(instruction, Instructions::INVOKEINTERFACE(..)) {
+				true => format!("interface {text}"),
+				false => text,
+			}
+		}
+		Instructions::INVOKEDYNAMIC(call_site) => format!(
+			"{} {}:{}",
+			call_site.bootstrap_method_attr_index, call_site.name_and_ty.name.data, call_site.name_and_ty.ty.data
+		),
+		Instructions::NEW(class)
+		| Instructions::ANEWARRAY(class)
+		| Instructions::CHECKCAST(class)
+		| Instructions::INSTANCEOF(class) => class.data.data.to_string(),
+		Instructions::NEWARRAY(ty) => ARRAY_TYPES
+			.iter()
+			.find(|(code, _)| code == ty)
+			.map_or_else(|| ty.to_string(), |(_, name)| name.to_string()),
+		Instructions::MULTIANEWARRAY(class, dimensions) => format!("{} {dimensions}", class.data.data),
+		_ => return name.to_string(),
+	};
+	format!("{name} {operands}")
+}
+
+const ARRAY_TYPES: [(u8, &str); 8] = [
+	(4, "boolean"),
+	(5, "char"),
+	(6, "float"),
+	(7, "double"),
+	(8, "byte"),
+	(9, "short"),
+	(10, "int"),
+	(11, "long"),
+];
+
+impl IRClassFile {
+	/// Applies a listing in the format of [`to_text`] to the class: the header replaces the class's
+	/// version, flags, names and interfaces, fields and methods are added or have their flags
+	/// replaced, and every method body replaces the method's code. A method listed without a body
+	/// loses its code. Members the listing doesn't mention are left alone.
+	///
+	/// `.limit`s that are left out are worked out from the code.
+	pub fn assemble(&mut self, text: &str) -> Result<(), AsmError> {
+		let mut lines = text
+			.lines()
+			.enumerate()
+			.map(|(i, line)| (i + 1, line.trim()))
+			.filter(|(_, line)| !line.is_empty() && !line.starts_with("//"));
+		let mut interfaces = Vec::new();
+		while let Some((line, text)) = lines.next() {
+			let syntax = |message: String| AsmError::Syntax { line, message };
+			let tokens = tokenize(text).map_err(syntax)?;
+			match tokens[0].as_str() {
+				".version" => {
+					let [major, minor] = operands(&tokens).map_err(syntax)?;
+					self.version.major = number(major).map_err(syntax)?;
+					self.version.minor = number(minor).map_err(syntax)?;
+				}
+				".class" => {
+					let (flags, [name]) = flags_and::<ClassAccessFlags, 1>(&tokens).map_err(syntax)?;
+					self.access_flags = flags;
+					if name != self.class_name() {
+						self.this_class = self.intern_class(name)?;
+					}
+				}
+				".super" => {
+					let [name] = operands(&tokens).map_err(syntax)?;
+					self.set_super_class(name)?;
+				}
+				".implements" => {
+					let [name] = operands(&tokens).map_err(syntax)?;
+					interfaces.push(name.clone());
+				}
+				".field" => {
+					let (flags, [name, descriptor]) = flags_and::<FieldAccessFlags, 2>(&tokens).map_err(syntax)?;
+					match self.find_field_mut(name) {
+						Some(field) => field.access_flags = flags,
+						None => {
+							self.add_field(flags, name, descriptor, Vec::new())?;
+						}
+					}
+				}
+				".method" => {
+					let (flags, [name, descriptor]) = flags_and::<MethodAccessFlags, 2>(&tokens).map_err(syntax)?;
+					let mut body = Vec::new();
+					loop {
+						let Some((line, text)) = lines.next() else {
+							return Err(syntax("missing .end method".to_string()));
+						};
+						let tokens = tokenize(text).map_err(|message| AsmError::Syntax { line, message })?;
+						if tokens[0] == ".end" {
+							break;
+						}
+						body.push((line, tokens));
+					}
+					self.assemble_method(flags, name, descriptor, &body)?;
+				}
+				other => return Err(syntax(format!("unexpected `{other}`"))),
+			}
+		}
+
+		self.interfaces = interfaces
+			.iter()
+			.map(|name| self.intern_class(name))
+			.collect::<Result<_, _>>()?;
+		Ok(())
+	}
+
+	fn assemble_method(
+		&mut self,
+		access_flags: MethodAccessFlags,
+		name: &str,
+		descriptor: &str,
+		body: &[(usize, Vec<String>)],
+	) -> Result<(), AsmError> {
+		if self.find_method(name, descriptor).is_none() {
+			self.add_method(access_flags, name, descriptor, Vec::new())?;
+		}
+		let method = self.find_method_mut(name, descriptor).unwrap();
+		method.access_flags = access_flags;
+		if body.is_empty() {
+			method
+				.attributes
+				.retain(|attribute| !matches!(attribute.attr, IRAttribute::Code(_)));
+			return Ok(());
+		}
+
+		let is_static = access_flags.contains(MethodAccessFlags::STATIC);
+		let initial_locals = self.entry_locals(is_static, name, descriptor)?;
+		let mut assembler = MethodAssembler {
+			insns: InsnList::new(initial_locals),
+			labels: HashMap::new(),
+			max_stack: None,
+			max_locals: None,
+		};
+		for (line, tokens) in body {
+			assembler.line(self, tokens).map_err(|error| match error {
+				LineError::Syntax(message) => AsmError::Syntax { line: *line, message },
+				LineError::Class(error) => AsmError::Class(error),
+			})?;
+		}
+		let insns = assembler.insns;
+		let max_stack = assembler.max_stack.map_or_else(|| insns.max_stack(), Ok)?;
+		let max_locals = assembler.max_locals.unwrap_or_else(|| insns.max_locals());
+
+		let has_code = self
+			.find_method(name, descriptor)
+			.unwrap()
+			.attributes
+			.iter()
+			.any(|attribute| matches!(attribute.attr, IRAttribute::Code(_)));
+		if !has_code {
+			let attribute = IRAttributeInfo {
+				name: self.intern_utf8("Code")?,
+				length: 0,
+				attr: IRAttribute::Code(CodeAttribute {
+					max_stack,
+					max_locals,
+					code: Vec::new(),
+					exception_table: Vec::new(),
+					attributes: Vec::new(),
+				}),
+			};
+			self.find_method_mut(name, descriptor)
+				.unwrap()
+				.attributes
+				.push(attribute);
+		}
+		self.set_method_insns(name, descriptor, &insns)?;
+		for attribute in &mut self.find_method_mut(name, descriptor).unwrap().attributes {
+			if let IRAttribute::Code(code) = &mut attribute.attr {
+				code.max_stack = max_stack;
+				code.max_locals = max_locals;
+			}
+		}
+		Ok(())
+	}
+}
+
+enum LineError {
+	Syntax(String),
+	Class(IRClassfileError),
+}
+
+impl From<String> for LineError {
+	fn from(message: String) -> Self {
+		Self::Syntax(message)
+	}
+}
+
+impl From<&str> for LineError {
+	fn from(message: &str) -> Self {
+		Self::Syntax(message.to_string())
+	}
+}
+
+impl From<IRClassfileError> for LineError {
+	fn from(error: IRClassfileError) -> Self {
+		Self::Class(error)
+	}
+}
+
+struct MethodAssembler {
+	insns: InsnList,
+	labels: HashMap<String, Label>,
+	max_stack: Option<u16>,
+	max_locals: Option<u16>,
+}
+
+impl MethodAssembler {
+	fn label(&mut self, name: &str) -> Label {
+		if let Some(label) = self.labels.get(name) {
+			return *label;
+		}
+		let label = self.insns.new_label();
+		self.labels.insert(name.to_string(), label);
+		label
+	}
+
+	fn line(&mut self, class: &mut IRClassFile, tokens: &[String]) -> Result<(), LineError> {
+		let first = tokens[0].as_str();
+		if let Some(name) = first.strip_suffix(':') {
+			if tokens.len() != 1 {
+				return Err(format!("unexpected `{}` after a label", tokens[1]).into());
+			}
+			let label = self.label(name);
+			self.insns.insns.push(Insn::Label(label));
+			return Ok(());
+		}
+
+		match first {
+			".limit" => {
+				let [what, value] = operands(tokens)?;
+				match what.as_str() {
+					"stack" => self.max_stack = Some(number(value)?),
+					"locals" => self.max_locals = Some(number(value)?),
+					_ => return Err(format!("unknown limit `{what}`").into()),
+				}
+			}
+			".line" => {
+				let [line] = operands(tokens)?;
+				self.insns.insns.push(Insn::Line(number(line)?));
+			}
+			".frame" => {
+				let separator = tokens
+					.iter()
+					.position(|token| token == "|")
+					.ok_or("a frame needs a `|` between its locals and stack")?;
+				let locals = self.frame_values(class, &tokens[1..separator])?;
+				let stack = self.frame_values(class, &tokens[separator + 1..])?;
+				self.insns.insns.push(Insn::Frame { locals, stack });
+			}
+			".catch" => {
+				let [catch_type, from, start, to, end, using, handler] = operands(tokens)?;
+				if (from.as_str(), to.as_str(), using.as_str()) != ("from", "to", "using") {
+					return Err("expected `.catch <class> from <label> to <label> using <label>`".into());
+				}
+				let catch_type = match catch_type.as_str() {
+					"any" => 0,
+					name => class.intern_class(name)?.index,
+				};
+				let block = TryCatchBlock {
+					start: self.label(start),
+					end: self.label(end),
+					handler: self.label(handler),
+					catch_type,
+				};
+				self.insns.try_catch_blocks.push(block);
+			}
+			".var" | ".vartype" => {
+				let [index, is, name, descriptor, from, start, to, end] = operands(tokens)?;
+				if (is.as_str(), from.as_str(), to.as_str()) != ("is", "from", "to") {
+					return Err(format!("expected `{first} <index> is <name> <type> from <label> to <label>`").into());
+				}
+				let variable = LocalVariable {
+					start: self.label(start),
+					end: self.label(end),
+					name: class.intern_utf8(name)?,
+					descriptor: class.intern_utf8(descriptor)?,
+					index: number(index)?,
+				};
+				match first {
+					".var" => self.insns.local_variables.push(variable),
+					_ => self.insns.local_variable_types.push(variable),
+				}
+			}
+			mnemonic => {
+				let insn = self.instruction(class, mnemonic, &tokens[1..])?;
+				self.insns.insns.push(insn);
+			}
+		}
+		Ok(())
+	}
+
+	fn frame_values(&mut self, class: &mut IRClassFile, tokens: &[String]) -> Result<Vec<FrameValue>, LineError> {
+		let mut values = Vec::new();
+		let mut tokens = tokens.iter();
+		while let Some(token) = tokens.next() {
+			let mut next = || tokens.next().ok_or(format!("`{token}` needs an operand"));
+			let info = match token.as_str() {
+				"top" => VerificationTypeInfo::TopVariableInfo,
+				"int" => VerificationTypeInfo::IntegerVariableInfo,
+				"float" => VerificationTypeInfo::FloatVariableInfo,
+				"long" => VerificationTypeInfo::LongVariableInfo,
+				"double" => VerificationTypeInfo::DoubleVariableInfo,
+				"null" => VerificationTypeInfo::NullVariableInfo,
+				"uninitializedThis" => VerificationTypeInfo::UninitializedThisVariableInfo,
+				"object" => VerificationTypeInfo::ObjectVariableInfo {
+					cpool_idx: class.intern_class(next()?)?.index,
+				},
+				"uninitialized" => {
+					let label = next()?;
+					values.push(FrameValue::Uninitialized(self.label(label)));
+					continue;
+				}
+				other => return Err(format!("unknown frame type `{other}`").into()),
+			};
+			values.push(FrameValue::Type(info));
+		}
+		Ok(values)
+	}
+
+	fn instruction(&mut self, class: &mut IRClassFile, name: &str, operands: &[String]) -> Result<Insn, LineError> {
+		let opcode = OpcodeInfo::by_name(name)
+			.map(|info| info.opcode)
+			.filter(|opcode| *opcode != Opcodes::WIDE)
+			.ok_or_else(|| format!("unknown instruction `{name}`"))?;
+		let operand = |i: usize| {
+			operands
+				.get(i)
+				.map(String::as_str)
+				.ok_or_else(|| format!("`{name}` is missing an operand"))
+		};
+
+		let instruction = match opcode {
+			Opcodes::IFEQ..=Opcodes::JSR | Opcodes::IFNULL | Opcodes::IFNONNULL | Opcodes::GOTO_W | Opcodes::JSR_W => {
+				let target = self.label(operand(0)?);
+				return Ok(Insn::Jump { opcode, target });
+			}
+			Opcodes::TABLESWITCH => {
+				let low = number(operand(0)?)?;
+				let default = operands.iter().position(|operand| operand == "default");
+				let default = default.ok_or("a switch needs a `default` label")?;
+				let targets = operands[1..default].iter().map(|target| self.label(target)).collect();
+				let default = self.label(operand(default + 1)?);
+				return Ok(Insn::TableSwitch { default, low, targets });
+			}
+			Opcodes::LOOKUPSWITCH => {
+				let default = operands.iter().position(|operand| operand == "default");
+				let default = default.ok_or("a switch needs a `default` label")?;
+				if default % 2 != 0 {
+					return Err("lookupswitch cases are a value and a label".into());
+				}
+				let mut pairs = Vec::with_capacity(default / 2);
+				for pair in operands[..default].chunks(2) {
+					pairs.push((number(&pair[0])?, self.label(&pair[1])));
+				}
+				let default = self.label(operand(default + 1)?);
+				return Ok(Insn::LookupSwitch { default, pairs });
+			}
+			Opcodes::BIPUSH => Instructions::BIPUSH(number(operand(0)?)?),
+			Opcodes::SIPUSH => Instructions::SIPUSH(number(operand(0)?)?),
+			Opcodes::LDC | Opcodes::LDC_W | Opcodes::LDC2_W => {
+				let constant = constant(class, operand(0)?, operands.get(1).map(String::as_str))?;
+				match opcode {
+					Opcodes::LDC => Instructions::LDC(constant),
+					Opcodes::LDC_W => Instructions::LDC_W(constant),
+					_ => Instructions::LDC2_W(constant),
+				}
+			}
+			Opcodes::ILOAD => Instructions::ILOAD(number(operand(0)?)?),
+			Opcodes::LLOAD => Instructions::LLOAD(number(operand(0)?)?),
+			Opcodes::FLOAD => Instructions::FLOAD(number(operand(0)?)?),
+			Opcodes::DLOAD => Instructions::DLOAD(number(operand(0)?)?),
+			Opcodes::ALOAD => Instructions::ALOAD(number(operand(0)?)?),
+			Opcodes::ISTORE => Instructions::ISTORE(number(operand(0)?)?),
+			Opcodes::LSTORE => Instructions::LSTORE(number(operand(0)?)?),
+			Opcodes::FSTORE => Instructions::FSTORE(number(operand(0)?)?),
+			Opcodes::DSTORE => Instructions::DSTORE(number(operand(0)?)?),
+			Opcodes::ASTORE => Instructions::ASTORE(number(operand(0)?)?),
+			Opcodes::RET => Instructions::RET(number(operand(0)?)?),
+			Opcodes::IINC => Instructions::IINC(number(operand(0)?)?, number(operand(1)?)?),
+			Opcodes::GETSTATIC | Opcodes::PUTSTATIC | Opcodes::GETFIELD | Opcodes::PUTFIELD => {
+				let (owner, member, descriptor) = member(operand(0)?)?;
+				let field = class.intern_field_ref(owner, member, descriptor)?;
+				match opcode {
+					Opcodes::GETSTATIC => Instructions::GETSTATIC(field),
+					Opcodes::PUTSTATIC => Instructions::PUTSTATIC(field),
+					Opcodes::GETFIELD => Instructions::GETFIELD(field),
+					_ => Instructions::PUTFIELD(field),
+				}
+			}
+			Opcodes::INVOKEVIRTUAL | Opcodes::INVOKESPECIAL | Opcodes::INVOKESTATIC | Opcodes::INVOKEINTERFACE => {
+				let (interface, reference) = match operand(0)? {
+					"interface" => (true, operand(1)?),
+					reference => (opcode == Opcodes::INVOKEINTERFACE, reference),
+				};
+				let (owner, member, descriptor) = member(reference)?;
+				let method = class.intern_method_ref(owner, member, descriptor, interface)?;
+				match opcode {
+					Opcodes::INVOKEVIRTUAL => Instructions::INVOKEVIRTUAL(method),
+					Opcodes::INVOKESPECIAL => Instructions::INVOKESPECIAL(method),
+					Opcodes::INVOKESTATIC => Instructions::INVOKESTATIC(method),
+					_ => {
+						let count = (1 + descriptor_slots(descriptor).0).min(u8::MAX as u16) as u8;
+						Instructions::INVOKEINTERFACE(method, count)
+					}
+				}
+			}
+			Opcodes::INVOKEDYNAMIC => {
+				let bootstrap = number(operand(0)?)?;
+				let (member, descriptor) = operand(1)?
+					.split_once(':')
+					.ok_or_else(|| format!("expected `<name>:<descriptor>`, found `{}`", operands[1]))?;
+				Instructions::INVOKEDYNAMIC(intern_invoke_dynamic(class, bootstrap, member, descriptor)?)
+			}
+			Opcodes::NEW => Instructions::NEW(class.intern_class(operand(0)?)?),
+			Opcodes::ANEWARRAY => Instructions::ANEWARRAY(class.intern_class(operand(0)?)?),
+			Opcodes::CHECKCAST => Instructions::CHECKCAST(class.intern_class(operand(0)?)?),
+			Opcodes::INSTANCEOF => Instructions::INSTANCEOF(class.intern_class(operand(0)?)?),
+			Opcodes::NEWARRAY => {
+				let ty = operand(0)?;
+				let code = ARRAY_TYPES.iter().find(|(_, name)| *name == ty);
+				Instructions::NEWARRAY(code.ok_or_else(|| format!("unknown array type `{ty}`"))?.0)
+			}
+			Opcodes::MULTIANEWARRAY => {
+				let array = class.intern_class(operand(0)?)?;
+				Instructions::MULTIANEWARRAY(array, number(operand(1)?)?)
+			}
+			// everything else has no operands, which includes the short forms like iload_0
+			opcode => {
+				if let Some(extra) = operands.first() {
+					return Err(format!("unexpected `{extra}` after `{name}`").into());
+				}
+				Instructions::read(&class.cp, &mut Cursor::new([opcode]))?
+			}
+		};
+		Ok(Insn::Op(instruction))
+	}
+}
+
+/// The constant `ldc` and friends load: `"string"`, `class <name>`, `#<pool index>` or a number,
+/// suffixed `L` for a long, `f` for a float and `d` for a double.
+fn constant(class: &mut IRClassFile, text: &str, next: Option<&str>) -> Result<CPTagRef, LineError> {
+	let index = if let Some(value) = text.strip_prefix('"') {
+		class.intern_string(value)?.index
+	} else if text == "class" {
+		let name = next.ok_or("`class` needs a name")?;
+		class.intern_class(name)?.index
+	} else if let Some(index) = text.strip_prefix('#') {
+		let index = number::<u16>(index)?;
+		match class.cp.get((index as usize).wrapping_sub(1)) {
+			None | Some(IRCpTag::Unusable) => return Err(format!("#{index} isn't a constant pool entry").into()),
+			Some(_) => index,
+		}
+	} else {
+		let constant = if let Some(value) = text.strip_suffix('L') {
+			IRCpTag::Long(number(value)?)
+		} else if let Some(value) = text.strip_suffix('d') {
+			IRCpTag::Double(number(value)?)
+		} else if let Some(value) = text.strip_suffix('f') {
+			IRCpTag::Float(number(value)?)
+		} else {
+			IRCpTag::Integer(number(text)?)
+		};
+		intern_constant(&mut class.cp, constant)?
+	};
+	Ok(CPTagRef::from_cp(&class.cp, index))
+}
+
+fn intern_invoke_dynamic(
+	class: &mut IRClassFile,
+	bootstrap: u16,
+	name: &str,
+	descriptor: &str,
+) -> Result<CPInvokeDynamicRef, IRClassfileError> {
+	let index = class.cp.edit(|pool| pool.invoke_dynamic(bootstrap, name, descriptor))?;
+	Ok(CPInvokeDynamicRef::from_cp(&class.cp, index))
+}
+
+/// Splits `owner.name:descriptor`.
+fn member(text: &str) -> Result<(&str, &str, &str), String> {
+	text.split_once(':')
+		.and_then(|(owner, descriptor)| {
+			let (owner, name) = owner.rsplit_once('.')?;
+			Some((owner, name, descriptor))
+		})
+		.ok_or_else(|| format!("expected `<owner>.<name>:<descriptor>`, found `{text}`"))
+}
+
+fn number<T: std::str::FromStr>(text: &str) -> Result<T, String> {
+	text.parse().map_err(|_| format!("`{text}` isn't a valid number here"))
+}
+
+/// The operands of a directive, which has to have exactly `N` of them.
+fn operands<const N: usize>(tokens: &[String]) -> Result<&[String; N], String> {
+	tokens[1..]
+		.try_into()
+		.map_err(|_| format!("`{}` takes {N} operands", tokens[0]))
+}
+
+/// The flags of a `.class`, `.field` or `.method` line followed by its `N` names.
+fn flags_and<F: std::str::FromStr<Err = crate::access::AccessFlagsError>, const N: usize>(
+	tokens: &[String],
+) -> Result<(F, [&str; N]), String> {
+	if tokens.len() < N + 1 {
+		return Err(format!("`{}` needs {N} names after its flags", tokens[0]));
+	}
+	let (flags, names) = tokens[1..].split_at(tokens.len() - 1 - N);
+	let flags = flags.join(" ").parse().map_err(|error| format!("{error}"))?;
+	Ok((flags, std::array::from_fn(|i| names[i].as_str())))
+}
+
+/// Splits a line on whitespace. A quoted string is one token, unescaped and starting with the
+/// opening `"` so it can't be mistaken for a name.
+pub(crate) fn tokenize(line: &str) -> Result<Vec<String>, String> {
+	let mut tokens = Vec::new();
+	let mut chars = line.chars().peekable();
+	while let Some(&c) = chars.peek() {
+		if c.is_whitespace() {
+			chars.next();
+			continue;
+		}
+		if c != '"' {
+			let mut token = String::new();
+			while let Some(&c) = chars.peek() {
+				if c.is_whitespace() {
+					break;
+				}
+				token.push(c);
+				chars.next();
+			}
+			tokens.push(token);
+			continue;
+		}
+
+		chars.next();
+		let mut token = String::from('"');
+		loop {
+			match chars.next().ok_or("unterminated string")? {
+				'"' => break,
+				'\\' => token.push(match chars.next().ok_or("unterminated string")? {
+					'n' => '\n',
+					't' => '\t',
+					'r' => '\r',
+					'0' => '\0',
+					'u' => {
+						if chars.next() != Some('{') {
+							return Err("expected `{` after `\\u`".to_string());
+						}
+						let hex = chars.by_ref().take_while(|c| *c != '}').collect::<String>();
+						u32::from_str_radix(&hex, 16)
+							.ok()
+							.and_then(char::from_u32)
+							.ok_or_else(|| format!("`\\u{{{hex}}}` isn't a character"))?
+					}
+					c @ ('\\' | '"' | '\'') => c,
+					c => return Err(format!("unknown escape `\\{c}`")),
+				}),
+				c => token.push(c),
+			}
+		}
+		tokens.push(token);
+	}
+	Ok(tokens)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::test_util::{class, method_code, CLASSES, HELLO};
+
+	#[test]
+	fn text_round_trip() {
+		for bytes in CLASSES {
+			let mut original = class(bytes);
+			let text = to_text(&mut original).unwrap();
+			let mut assembled = class(bytes);
+			assembled.assemble(&text).unwrap();
+			assert_eq!(
+				assembled.to_io().unwrap().to_bytes().unwrap(),
+				original.to_io().unwrap().to_bytes().unwrap()
+			);
+		}
+
+		let text = to_text(&mut class(HELLO)).unwrap();
+		assert!(text.contains("  invokedynamic 0 get:()Ljava/util/function/Supplier;\n"));
+		assert!(text.contains("  .frame int object java/lang/Object int int object java/lang/Object long |\n"));
+	}
+
+	#[test]
+	fn assemble_text() {
+		let text = r#"
+			.class public gen/Counter
+			.field private static count I
+			// limits are worked out when left out
+			.method public static next (Ljava/lang/String;)I
+				getstatic gen/Counter.count:I
+				iconst_1
+				iadd
+				dup
+				putstatic gen/Counter.count:I
+				ifgt L0
+				ldc "first\tcall"
+				pop
+			L0:
+				.frame object java/lang/String |
+				getstatic gen/Counter.count:I
+				ireturn
+			.end method
+		"#;
+		let mut class = assemble(text).unwrap();
+		assert_eq!(class.class_name(), "gen/Counter");
+		assert_eq!(class.super_name(), Some("java/lang/Object"));
+		let code = method_code(&class, "next");
+		assert_eq!((code.max_stack, code.max_locals), (2, 1));
+		assert_eq!(code.code[9..12], [0x9D, 0, 6]);
+		assert!(to_text(&mut class).unwrap().contains("  ldc \"first\\tcall\"\n"));
+	}
+
+	#[test]
+	fn assemble_errors() {
+		let error = assemble(".class X\n.method f ()V\n  iload\n.end method").unwrap_err();
+		assert_eq!(error.to_string(), "line 3: `iload` is missing an operand");
+	}
+}
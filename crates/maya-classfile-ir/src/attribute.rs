@@ -1,28 +1,105 @@
-use std::{io::Cursor, rc::Rc};
+use std::{cell::Cell, io::Cursor};
+
+use maya_bytes::{BytesError, BytesReadExt, BytesWriteExt};
+use maya_classfile_io::{options::Limits, IOAttributeInfo, IOClassfileError};
+
+#[cfg(feature = "preview")]
+use crate::preview::{is_preview_attribute, PreviewAttribute};
+use crate::{
+	access::{ExportsFlags, ModuleFlags, OpensFlags, ParameterAccessFlags, RequiresFlags},
+	class_pool::{
+		CPClassRef, CPConstValueRef, CPMethodHandleRef, CPModuleInfoRef, CPNameAndTypeRef, CPPackageInfoRef,
+		CPStringRef, CPTagRef, CPUtf8Ref, IRClassfileError, IRCpTag, Shared,
+	},
+	stackmap::StackMapError,
+};
 
-use maya_bytes::BytesReadExt;
-use maya_classfile_io::IOAttributeInfo;
+thread_local! {
+	static MAX_NESTING: Cell<u16> = const { Cell::new(Limits::NONE.max_nesting) };
+}
 
-use crate::class_pool::{
-	CPClassRef, CPConstValueRef, CPMethodHandleRef, CPModuleInfoRef, CPNameAndTypeRef, CPPackageInfoRef, CPTagRef,
-	CPUtf8Ref, IRClassfileError, IRCpTag,
-};
+/// Runs `f` with [`Limits::max_nesting`] lowered to `limit` for the attributes it lifts, restoring the
+/// previous one afterwards. It's per thread like [`maya_bytes::with_max_alloc`], so the attribute
+/// readers don't all have to be handed the limits.
+pub(crate) fn with_max_nesting<T>(limit: u16, f: impl FnOnce() -> T) -> T {
+	struct Restore(u16);
+
+	impl Drop for Restore {
+		fn drop(&mut self) {
+			MAX_NESTING.set(self.0);
+		}
+	}
+
+	let _restore = Restore(MAX_NESTING.replace(MAX_NESTING.get().min(limit)));
+	f()
+}
+
+fn check_nesting(what: &'static str, value: usize) -> Result<(), IRClassfileError> {
+	let limit = MAX_NESTING.get();
+	match value <= limit as usize {
+		true => Ok(()),
+		false => Err(IOClassfileError::LimitExceeded {
+			what,
+			value: value as u64,
+			limit: limit as u64,
+		}
+		.into()),
+	}
+}
+
+/// A table length as its u16 count field.
+pub(crate) fn count(what: &'static str, len: usize) -> Result<u16, IRClassfileError> {
+	u16::try_from(len).map_err(|_| IRClassfileError::TooMany { what, actual: len })
+}
+
+fn write_indices<B: BytesWriteExt>(
+	buffer: &mut B,
+	what: &'static str,
+	indices: impl ExactSizeIterator<Item = u16>,
+) -> Result<(), IRClassfileError> {
+	buffer.write_u16(count(what, indices.len())?)?;
+	for index in indices {
+		buffer.write_u16(index)?;
+	}
+	Ok(())
+}
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ConstantValueAttribute {
 	Long { cp_idx: u16, value: i64 },
 	Float { cp_idx: u16, value: f32 },
 	Double { cp_idx: u16, value: f64 },
 	Int { cp_idx: u16, value: i32 },
-	String(CPUtf8Ref),
+	String(CPStringRef),
 }
 
-#[derive(Debug, Clone)]
+impl ConstantValueAttribute {
+	/// The constant pool index the attribute points at.
+	pub fn cp_index(&self) -> u16 {
+		match self {
+			Self::Long { cp_idx, .. }
+			| Self::Float { cp_idx, .. }
+			| Self::Double { cp_idx, .. }
+			| Self::Int { cp_idx, .. } => *cp_idx,
+			Self::String(string) => string.index,
+		}
+	}
+
+	pub fn write<B: BytesWriteExt>(&self, buffer: &mut B) -> Result<(), IRClassfileError> {
+		buffer.write_u16(self.cp_index())?;
+		Ok(())
+	}
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct StackMapTableAttribute {
 	pub entries: Vec<StackMapFrame>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(u8)]
 pub enum VerificationTypeInfo {
 	TopVariableInfo = 0,
@@ -56,9 +133,31 @@ impl VerificationTypeInfo {
 			_ => unreachable!("invalid tag {tag}"),
 		})
 	}
+
+	fn write<B: BytesWriteExt>(&self, buffer: &mut B) -> Result<(), IRClassfileError> {
+		match self {
+			Self::TopVariableInfo => buffer.write_u8(0)?,
+			Self::IntegerVariableInfo => buffer.write_u8(1)?,
+			Self::FloatVariableInfo => buffer.write_u8(2)?,
+			Self::LongVariableInfo => buffer.write_u8(4)?,
+			Self::DoubleVariableInfo => buffer.write_u8(3)?,
+			Self::NullVariableInfo => buffer.write_u8(5)?,
+			Self::UninitializedThisVariableInfo => buffer.write_u8(6)?,
+			Self::ObjectVariableInfo { cpool_idx } => {
+				buffer.write_u8(7)?;
+				buffer.write_u16(*cpool_idx)?;
+			}
+			Self::UninitializedVariableInfo { offset } => {
+				buffer.write_u8(8)?;
+				buffer.write_u16(*offset)?;
+			}
+		}
+		Ok(())
+	}
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum StackMapFrame {
 	SameFrame {
 		frame_type: u8,
@@ -79,7 +178,7 @@ pub enum StackMapFrame {
 	   it means that the operand stack is empty and the current locals are the same as the locals in the previous frame,-
 	   except that the k last locals are absent. The value of k is given by the formula 251 - frame_type.
 	*/
-	// TODO: do we store `k` for convenience? wtf is this shit
+	// `k` is available from `StackMapFrame::chop_count`.
 	ChopFrame {
 		frame_type: u8,
 		offset_delta: u16,
@@ -115,7 +214,7 @@ impl StackMapFrame {
 			},
 			64..=127 => Self::SameLocals1StackItemFrame {
 				frame_type,
-				offset_delta: (64 - frame_type) as u16,
+				offset_delta: (frame_type - 64) as u16,
 				stack: VerificationTypeInfo::read(attribute_data)?,
 			},
 			247 => Self::SameLocals1StackItemFrameExtended {
@@ -135,7 +234,7 @@ impl StackMapFrame {
 				let offset_delta = attribute_data.read_u16()?;
 
 				let n_locals = (frame_type - 251) as usize;
-				let mut locals = Vec::with_capacity(n_locals);
+				let mut locals = maya_bytes::vec_with_capacity(n_locals)?;
 				for _ in 0..n_locals {
 					locals.push(VerificationTypeInfo::read(attribute_data)?);
 				}
@@ -150,13 +249,13 @@ impl StackMapFrame {
 				let offset_delta = attribute_data.read_u16()?;
 
 				let n_locals = attribute_data.read_u16()? as usize;
-				let mut locals = Vec::with_capacity(n_locals);
+				let mut locals = maya_bytes::vec_with_capacity(n_locals)?;
 				for _ in 0..n_locals {
 					locals.push(VerificationTypeInfo::read(attribute_data)?);
 				}
 
 				let n_stack = attribute_data.read_u16()? as usize;
-				let mut stack = Vec::with_capacity(n_stack);
+				let mut stack = maya_bytes::vec_with_capacity(n_stack)?;
 				for _ in 0..n_stack {
 					stack.push(VerificationTypeInfo::read(attribute_data)?);
 				}
@@ -169,12 +268,73 @@ impl StackMapFrame {
 				}
 			}
 
-			_ => panic!("invalid frame tag {frame_type}"),
+			128..=246 => return Err(StackMapError::ReservedFrameType(frame_type).into()),
 		})
 	}
+
+	/// Writes the frame using its stored `frame_type`, compact frames keep their delta in it.
+	pub fn write<B: BytesWriteExt>(&self, buffer: &mut B) -> Result<(), IRClassfileError> {
+		match self {
+			Self::SameFrame { frame_type, .. } => buffer.write_u8(*frame_type)?,
+			Self::SameLocals1StackItemFrame { frame_type, stack, .. } => {
+				buffer.write_u8(*frame_type)?;
+				stack.write(buffer)?;
+			}
+			Self::SameLocals1StackItemFrameExtended {
+				frame_type,
+				offset_delta,
+				stack,
+			} => {
+				buffer.write_u8(*frame_type)?;
+				buffer.write_u16(*offset_delta)?;
+				stack.write(buffer)?;
+			}
+			Self::ChopFrame {
+				frame_type,
+				offset_delta,
+			}
+			| Self::SameFrameExtended {
+				frame_type,
+				offset_delta,
+			} => {
+				buffer.write_u8(*frame_type)?;
+				buffer.write_u16(*offset_delta)?;
+			}
+			Self::AppendFrame {
+				frame_type,
+				offset_delta,
+				locals,
+			} => {
+				buffer.write_u8(*frame_type)?;
+				buffer.write_u16(*offset_delta)?;
+				for local in locals {
+					local.write(buffer)?;
+				}
+			}
+			Self::FullFrame {
+				frame_type,
+				offset_delta,
+				locals,
+				stack,
+			} => {
+				buffer.write_u8(*frame_type)?;
+				buffer.write_u16(*offset_delta)?;
+				buffer.write_u16(count("locals", locals.len())?)?;
+				for local in locals {
+					local.write(buffer)?;
+				}
+				buffer.write_u16(count("stack items", stack.len())?)?;
+				for item in stack {
+					item.write(buffer)?;
+				}
+			}
+		}
+		Ok(())
+	}
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct InnerClassesAttributeClass {
 	pub inner_class_info: CPClassRef,
 	pub outer_class_info: Option<CPClassRef>,
@@ -208,14 +368,24 @@ impl InnerClassesAttributeClass {
 			inner_class_access_flags,
 		})
 	}
+
+	pub fn write<B: BytesWriteExt>(&self, buffer: &mut B) -> Result<(), IRClassfileError> {
+		buffer.write_u16(self.inner_class_info.index)?;
+		buffer.write_u16(self.outer_class_info.as_ref().map_or(0, |class| class.index))?;
+		buffer.write_u16(self.inner_name.as_ref().map_or(0, |name| name.index))?;
+		buffer.write_u16(self.inner_class_access_flags)?;
+		Ok(())
+	}
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct InnerClassesAttribute {
 	pub classes: Vec<InnerClassesAttributeClass>,
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CodeAttributeException {
 	pub start_pc: u16,
 	pub end_pc: u16,
@@ -232,9 +402,18 @@ impl CodeAttributeException {
 			catch_type: buffer.read_u16()?,
 		})
 	}
+
+	fn write<B: BytesWriteExt>(&self, buffer: &mut B) -> Result<(), IRClassfileError> {
+		buffer.write_u16(self.start_pc)?;
+		buffer.write_u16(self.end_pc)?;
+		buffer.write_u16(self.handler_pc)?;
+		buffer.write_u16(self.catch_type)?;
+		Ok(())
+	}
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CodeAttribute {
 	pub max_stack: u16,
 	pub max_locals: u16,
@@ -248,19 +427,16 @@ impl CodeAttribute {
 		let max_stack = buffer.read_u16()?;
 		let max_locals = buffer.read_u16()?;
 		let code_len = buffer.read_u32()? as usize;
-		let mut code = Vec::with_capacity(code_len);
-		for _ in 0..code_len {
-			code.push(buffer.read_u8()?);
-		}
+		let code = buffer.read_n_bytes_vec(code_len)?;
 
 		let exception_table_len = buffer.read_u16()? as usize;
-		let mut exception_table = Vec::with_capacity(exception_table_len);
+		let mut exception_table = maya_bytes::vec_with_capacity(exception_table_len)?;
 		for _ in 0..exception_table_len {
 			exception_table.push(CodeAttributeException::new(buffer)?);
 		}
 
 		let attribute_len = buffer.read_u16()? as usize;
-		let mut attributes = Vec::with_capacity(attribute_len);
+		let mut attributes = maya_bytes::vec_with_capacity(attribute_len)?;
 		for _ in 0..attribute_len {
 			attributes.push(Box::new(IRAttributeInfo::from_io(cp, IOAttributeInfo::read(buffer)?)?));
 		}
@@ -272,15 +448,65 @@ impl CodeAttribute {
 			attributes,
 		})
 	}
+
+	pub fn write<B: BytesWriteExt>(&self, cp: &[IRCpTag], buffer: &mut B) -> Result<(), IRClassfileError> {
+		buffer.write_u16(self.max_stack)?;
+		buffer.write_u16(self.max_locals)?;
+		buffer.write_u32(u32::try_from(self.code.len()).map_err(|_| IRClassfileError::TooMany {
+			what: "code bytes",
+			actual: self.code.len(),
+		})?)?;
+		buffer.write_all(&self.code).map_err(BytesError::from)?;
+
+		buffer.write_u16(count("exception table entries", self.exception_table.len())?)?;
+		for exception in &self.exception_table {
+			exception.write(buffer)?;
+		}
+
+		buffer.write_u16(count("attributes", self.attributes.len())?)?;
+		for attr in &self.attributes {
+			attr.to_io(cp)?.write(buffer)?;
+		}
+		Ok(())
+	}
+
+	/// Entries of every LocalVariableTable attribute on this code, javac may split them over several.
+	pub fn local_variables(&self) -> impl Iterator<Item = &LocalVariableTableEntry> {
+		self.attributes.iter().flat_map(|attr| match &attr.attr {
+			IRAttribute::LocalVariableTable { table } => table.as_slice(),
+			_ => &[],
+		})
+	}
+
+	pub fn local_variable_types(&self) -> impl Iterator<Item = &LocalVariableTypeTableEntry> {
+		self.attributes.iter().flat_map(|attr| match &attr.attr {
+			IRAttribute::LocalVariableTypeTable { table } => table.as_slice(),
+			_ => &[],
+		})
+	}
+
+	/// The variable stored in local `slot` at `pc`, if the method was compiled with `-g`.
+	pub fn local_variable_at(&self, pc: u16, slot: u16) -> Option<&LocalVariableTableEntry> {
+		self.local_variables()
+			.find(|entry| entry.index == slot && entry.is_live_at(pc))
+	}
+
+	/// The generic signature of the variable in `slot` at `pc`, only present for generic types.
+	pub fn local_variable_type_at(&self, pc: u16, slot: u16) -> Option<&LocalVariableTypeTableEntry> {
+		self.local_variable_types()
+			.find(|entry| entry.index == slot && entry.is_live_at(pc))
+	}
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct LineNumberTableAttributeEntry {
 	pub start_pc: u16,
 	pub line_number: u16,
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct LineNumberTableAttribute {
 	pub line_number_table: Vec<LineNumberTableAttributeEntry>,
 }
@@ -288,7 +514,7 @@ pub struct LineNumberTableAttribute {
 impl LineNumberTableAttribute {
 	pub fn new<B: BytesReadExt>(buffer: &mut B) -> Result<Self, IRClassfileError> {
 		let table_len = buffer.read_u16()? as usize;
-		let mut line_number_table = Vec::with_capacity(table_len);
+		let mut line_number_table = maya_bytes::vec_with_capacity(table_len)?;
 
 		for _ in 0..table_len {
 			line_number_table.push(LineNumberTableAttributeEntry {
@@ -299,12 +525,22 @@ impl LineNumberTableAttribute {
 
 		Ok(Self { line_number_table })
 	}
+
+	pub fn write<B: BytesWriteExt>(&self, buffer: &mut B) -> Result<(), IRClassfileError> {
+		buffer.write_u16(count("line numbers", self.line_number_table.len())?)?;
+		for entry in &self.line_number_table {
+			buffer.write_u16(entry.start_pc)?;
+			buffer.write_u16(entry.line_number)?;
+		}
+		Ok(())
+	}
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MethodParametersParam {
 	pub name: Option<CPUtf8Ref>,
-	pub access_flags: u16,
+	pub access_flags: ParameterAccessFlags,
 }
 
 impl MethodParametersParam {
@@ -320,14 +556,25 @@ impl MethodParametersParam {
 					cp.get(name_index as usize - 1).expect("expected utf8"),
 				))
 			},
-			access_flags: buffer.read_u16()?,
+			access_flags: ParameterAccessFlags::from_bits_retain(buffer.read_u16()?),
 		})
 	}
+
+	pub fn write<B: BytesWriteExt>(&self, buffer: &mut B) -> Result<(), IRClassfileError> {
+		buffer.write_u16(self.name.as_ref().map_or(0, |name| name.index))?;
+		buffer.write_u16(self.access_flags.bits())?;
+		Ok(())
+	}
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum RuntimeAnnotationValue {
-	ConstValueIndex(CPConstValueRef),
+	ConstValueIndex {
+		/// One of `BCDFIJSZs`, `B`, `C`, `S` and `Z` all point at an Integer constant.
+		tag: u8,
+		value: CPConstValueRef,
+	},
 	EnumConstValue {
 		type_name: CPUtf8Ref,
 		const_name: CPUtf8Ref,
@@ -341,11 +588,18 @@ pub enum RuntimeAnnotationValue {
 
 impl RuntimeAnnotationValue {
 	pub fn new<B: BytesReadExt>(cp: &[IRCpTag], buffer: &mut B) -> Result<Self, IRClassfileError> {
+		Self::read(cp, buffer, 0)
+	}
+
+	/// `depth` counts the annotations and arrays this value is nested in.
+	fn read<B: BytesReadExt>(cp: &[IRCpTag], buffer: &mut B, depth: usize) -> Result<Self, IRClassfileError> {
+		check_nesting("annotation nesting", depth)?;
 		let tag = buffer.read_u8()?;
 		Ok(match tag {
-			b'B' | b'C' | b'D' | b'F' | b'I' | b'J' | b'S' | b'Z' | b's' => {
-				Self::ConstValueIndex(CPConstValueRef::from_cp(cp, buffer.read_u16()?))
-			}
+			b'B' | b'C' | b'D' | b'F' | b'I' | b'J' | b'S' | b'Z' | b's' => Self::ConstValueIndex {
+				tag,
+				value: CPConstValueRef::from_cp(cp, buffer.read_u16()?),
+			},
 
 			b'e' => Self::EnumConstValue {
 				type_name: CPUtf8Ref::from_cp(cp, buffer.read_u16()?),
@@ -353,29 +607,72 @@ impl RuntimeAnnotationValue {
 			},
 
 			b'c' => Self::ClassInfoIndex(CPUtf8Ref::from_cp(cp, buffer.read_u16()?)),
-			b'@' => Self::Annotation(Box::new(RuntimeAnnotation::new(cp, buffer)?)),
+			b'@' => Self::Annotation(Box::new(RuntimeAnnotation::read(cp, buffer, depth + 1)?)),
 			b'[' => {
 				let n_values = buffer.read_u16()? as usize;
-				let mut values = Vec::with_capacity(n_values);
+				let mut values = maya_bytes::vec_with_capacity(n_values)?;
 
 				for _ in 0..n_values {
-					values.push(RuntimeAnnotationValue::new(cp, buffer)?);
+					values.push(RuntimeAnnotationValue::read(cp, buffer, depth + 1)?);
 				}
 
 				Self::ArrayValue { values }
 			}
-			_ => panic!("invalid tag: {tag}"),
+			_ => return Err(IRClassfileError::InvalidAnnotationTag(tag)),
 		})
 	}
+
+	pub fn write<B: BytesWriteExt>(&self, buffer: &mut B) -> Result<(), IRClassfileError> {
+		match self {
+			Self::ConstValueIndex { tag, value } => {
+				buffer.write_u8(*tag)?;
+				buffer.write_u16(value.index)?;
+			}
+			Self::EnumConstValue { type_name, const_name } => {
+				buffer.write_u8(b'e')?;
+				buffer.write_u16(type_name.index)?;
+				buffer.write_u16(const_name.index)?;
+			}
+			Self::ClassInfoIndex(class) => {
+				buffer.write_u8(b'c')?;
+				buffer.write_u16(class.index)?;
+			}
+			Self::Annotation(annotation) => {
+				buffer.write_u8(b'@')?;
+				annotation.write(buffer)?;
+			}
+			Self::ArrayValue { values } => {
+				buffer.write_u8(b'[')?;
+				buffer.write_u16(count("array values", values.len())?)?;
+				for value in values {
+					value.write(buffer)?;
+				}
+			}
+		}
+		Ok(())
+	}
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct RuntimeAnnotationEVPair {
 	pub name: CPUtf8Ref,
 	pub value: RuntimeAnnotationValue,
 }
 
+impl RuntimeAnnotationEVPair {
+	fn write_all<B: BytesWriteExt>(pairs: &[RuntimeAnnotationEVPair], buffer: &mut B) -> Result<(), IRClassfileError> {
+		buffer.write_u16(count("element value pairs", pairs.len())?)?;
+		for pair in pairs {
+			buffer.write_u16(pair.name.index)?;
+			pair.value.write(buffer)?;
+		}
+		Ok(())
+	}
+}
+
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct RuntimeAnnotation {
 	pub ty: CPUtf8Ref,
 	pub pairs: Vec<RuntimeAnnotationEVPair>,
@@ -383,11 +680,15 @@ pub struct RuntimeAnnotation {
 
 impl RuntimeAnnotation {
 	pub fn new<B: BytesReadExt>(cp: &[IRCpTag], buffer: &mut B) -> Result<Self, IRClassfileError> {
+		Self::read(cp, buffer, 0)
+	}
+
+	fn read<B: BytesReadExt>(cp: &[IRCpTag], buffer: &mut B, depth: usize) -> Result<Self, IRClassfileError> {
 		let ty_idx = buffer.read_u16()?;
 		let ty = CPUtf8Ref::new(ty_idx, cp.get(ty_idx as usize - 1).expect("expected utf8"));
 
 		let n_pairs = buffer.read_u16()? as usize;
-		let mut pairs = Vec::with_capacity(n_pairs);
+		let mut pairs = maya_bytes::vec_with_capacity(n_pairs)?;
 
 		for _ in 0..n_pairs {
 			let name_idx = buffer.read_u16()?;
@@ -395,15 +696,29 @@ impl RuntimeAnnotation {
 
 			pairs.push(RuntimeAnnotationEVPair {
 				name,
-				value: RuntimeAnnotationValue::new(cp, buffer)?,
+				value: RuntimeAnnotationValue::read(cp, buffer, depth)?,
 			});
 		}
 
 		Ok(Self { ty, pairs })
 	}
+
+	pub fn write<B: BytesWriteExt>(&self, buffer: &mut B) -> Result<(), IRClassfileError> {
+		buffer.write_u16(self.ty.index)?;
+		RuntimeAnnotationEVPair::write_all(&self.pairs, buffer)
+	}
+
+	fn write_all<B: BytesWriteExt>(annotations: &[RuntimeAnnotation], buffer: &mut B) -> Result<(), IRClassfileError> {
+		buffer.write_u16(count("annotations", annotations.len())?)?;
+		for annotation in annotations {
+			annotation.write(buffer)?;
+		}
+		Ok(())
+	}
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct RecordComponentInfo {
 	pub name: CPUtf8Ref,
 	pub descriptor: CPUtf8Ref,
@@ -415,7 +730,7 @@ impl RecordComponentInfo {
 		let name_idx = buffer.read_u16()?;
 		let descriptor_idx = buffer.read_u16()?;
 		let n_attributes = buffer.read_u16()? as usize;
-		let mut attributes = Vec::with_capacity(n_attributes);
+		let mut attributes = maya_bytes::vec_with_capacity(n_attributes)?;
 		for _ in 0..n_attributes {
 			attributes.push(IRAttributeInfo::from_io(cp, IOAttributeInfo::read(buffer)?)?);
 		}
@@ -426,9 +741,37 @@ impl RecordComponentInfo {
 			attributes,
 		})
 	}
+
+	pub fn write<B: BytesWriteExt>(&self, cp: &[IRCpTag], buffer: &mut B) -> Result<(), IRClassfileError> {
+		buffer.write_u16(self.name.index)?;
+		buffer.write_u16(self.descriptor.index)?;
+		buffer.write_u16(count("attributes", self.attributes.len())?)?;
+		for attr in &self.attributes {
+			attr.to_io(cp)?.write(buffer)?;
+		}
+		Ok(())
+	}
+
+	/// The generic signature, present when the component's type uses type variables or parameters.
+	pub fn signature(&self) -> Option<&CPUtf8Ref> {
+		self.attributes.iter().find_map(|attr| match &attr.attr {
+			IRAttribute::Signature(signature) => Some(signature),
+			_ => None,
+		})
+	}
+
+	/// Visible and invisible annotations on the component.
+	pub fn annotations(&self) -> impl Iterator<Item = &RuntimeAnnotation> {
+		self.attributes.iter().flat_map(|attr| match &attr.attr {
+			IRAttribute::RuntimeVisibleAnnotations { annotations }
+			| IRAttribute::RuntimeInvisibleAnnotations { annotations } => annotations.as_slice(),
+			_ => &[],
+		})
+	}
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct BootstrapMethodsMethod {
 	pub method: CPMethodHandleRef,
 	pub arguments: Vec<CPTagRef>,
@@ -438,7 +781,7 @@ impl BootstrapMethodsMethod {
 	pub fn new<B: BytesReadExt>(cp: &[IRCpTag], buffer: &mut B) -> Result<Self, IRClassfileError> {
 		let method_idx = buffer.read_u16()?;
 		let n_args = buffer.read_u16()? as usize;
-		let mut arguments = Vec::with_capacity(n_args);
+		let mut arguments = maya_bytes::vec_with_capacity(n_args)?;
 
 		for _ in 0..n_args {
 			arguments.push(buffer.read_u16()?);
@@ -449,9 +792,19 @@ impl BootstrapMethodsMethod {
 			arguments: arguments.into_iter().map(|idx| CPTagRef::from_cp(cp, idx)).collect(),
 		})
 	}
+
+	pub fn write<B: BytesWriteExt>(&self, buffer: &mut B) -> Result<(), IRClassfileError> {
+		buffer.write_u16(self.method.index)?;
+		buffer.write_u16(count("bootstrap arguments", self.arguments.len())?)?;
+		for argument in &self.arguments {
+			buffer.write_u16(argument.index)?;
+		}
+		Ok(())
+	}
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct LocalVariableTableEntry {
 	pub start_pc: u16,
 	pub length: u16,
@@ -476,9 +829,24 @@ impl LocalVariableTableEntry {
 			index,
 		})
 	}
+
+	pub fn write<B: BytesWriteExt>(&self, buffer: &mut B) -> Result<(), IRClassfileError> {
+		buffer.write_u16(self.start_pc)?;
+		buffer.write_u16(self.length)?;
+		buffer.write_u16(self.name.index)?;
+		buffer.write_u16(self.descriptor.index)?;
+		buffer.write_u16(self.index)?;
+		Ok(())
+	}
+
+	/// Whether the variable is in scope at `pc`, i.e. `start_pc <= pc < start_pc + length`.
+	pub fn is_live_at(&self, pc: u16) -> bool {
+		pc >= self.start_pc && (pc as u32) < self.start_pc as u32 + self.length as u32
+	}
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct LocalVariableTypeTableEntry {
 	pub start_pc: u16,
 	pub length: u16,
@@ -503,9 +871,24 @@ impl LocalVariableTypeTableEntry {
 			index,
 		})
 	}
+
+	pub fn write<B: BytesWriteExt>(&self, buffer: &mut B) -> Result<(), IRClassfileError> {
+		buffer.write_u16(self.start_pc)?;
+		buffer.write_u16(self.length)?;
+		buffer.write_u16(self.name.index)?;
+		buffer.write_u16(self.signature.index)?;
+		buffer.write_u16(self.index)?;
+		Ok(())
+	}
+
+	/// Whether the variable is in scope at `pc`, i.e. `start_pc <= pc < start_pc + length`.
+	pub fn is_live_at(&self, pc: u16) -> bool {
+		pc >= self.start_pc && (pc as u32) < self.start_pc as u32 + self.length as u32
+	}
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct RuntimeTypeAnnotationLocalVarTargetTableEntry {
 	pub start_pc: u16,
 	pub length: u16,
@@ -513,6 +896,7 @@ pub struct RuntimeTypeAnnotationLocalVarTargetTableEntry {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum RuntimeTypeAnnotationTargetInfo {
 	TypeParameterTarget {
 		type_param_index: u8,
@@ -547,14 +931,42 @@ pub enum RuntimeTypeAnnotationTargetInfo {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct RuntimeTypeAnnotationTypePathPart {
-	// TODO: Parse this into Enum of some sort? Maybe.
-	// see: https://docs.oracle.com/javase/specs/jvms/se22/html/jvms-4.html#jvms-4.7.20
+	// see: https://docs.oracle.com/javase/specs/jvms/se22/html/jvms-4.html#jvms-4.7.20.2
 	pub type_path_kind: u8,
+	/// Which type argument is annotated, 0 unless `type_path_kind` is [`TypePathKind::TypeArgument`].
 	pub type_argument_kind: u8,
 }
 
+impl RuntimeTypeAnnotationTypePathPart {
+	pub fn kind(&self) -> Option<TypePathKind> {
+		Some(match self.type_path_kind {
+			0 => TypePathKind::Array,
+			1 => TypePathKind::Nested,
+			2 => TypePathKind::WildcardBound,
+			3 => TypePathKind::TypeArgument,
+			_ => return None,
+		})
+	}
+}
+
+/// A step of a type_path, from the outermost type towards the annotated one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TypePathKind {
+	/// Into the element type of an array.
+	Array,
+	/// Into a nested type, e.g. from `Map` to `Map.Entry`.
+	Nested,
+	/// Into the bound of a wildcard type argument.
+	WildcardBound,
+	/// Into a type argument of a parameterized type.
+	TypeArgument,
+}
+
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct RuntimeTypeAnnotation {
 	pub target_type: u8,
 	pub target_info: RuntimeTypeAnnotationTargetInfo,
@@ -589,7 +1001,7 @@ impl RuntimeTypeAnnotation {
 			// 4.7.20-B
 			0x40 | 0x41 => {
 				let n_entries = buffer.read_u16()? as usize;
-				let mut table = Vec::with_capacity(n_entries);
+				let mut table = maya_bytes::vec_with_capacity(n_entries)?;
 
 				for _ in 0..n_entries {
 					table.push(RuntimeTypeAnnotationLocalVarTargetTableEntry {
@@ -604,7 +1016,7 @@ impl RuntimeTypeAnnotation {
 			0x42 => RuntimeTypeAnnotationTargetInfo::CatchTarget {
 				exception_table_index: buffer.read_u16()?,
 			},
-			0x43..=0x45 => RuntimeTypeAnnotationTargetInfo::OffsetTarget {
+			0x43..=0x46 => RuntimeTypeAnnotationTargetInfo::OffsetTarget {
 				offset: buffer.read_u16()?,
 			},
 			0x47..=0x4B => RuntimeTypeAnnotationTargetInfo::TypeArgumentTarget {
@@ -612,11 +1024,12 @@ impl RuntimeTypeAnnotation {
 				type_argument_index: buffer.read_u8()?,
 			},
 
-			_ => panic!("unexpected target_type: {target_type}"),
+			_ => return Err(IRClassfileError::InvalidTargetType(target_type)),
 		};
 
 		let n_parts = buffer.read_u8()? as usize;
-		let mut target_path = Vec::with_capacity(n_parts);
+		check_nesting("type_path length", n_parts)?;
+		let mut target_path = maya_bytes::vec_with_capacity(n_parts)?;
 		for _ in 0..n_parts {
 			target_path.push(RuntimeTypeAnnotationTypePathPart {
 				type_path_kind: buffer.read_u8()?,
@@ -627,7 +1040,7 @@ impl RuntimeTypeAnnotation {
 		let type_index = buffer.read_u16()?;
 
 		let n_pairs = buffer.read_u16()? as usize;
-		let mut pairs = Vec::with_capacity(n_pairs);
+		let mut pairs = maya_bytes::vec_with_capacity(n_pairs)?;
 
 		for _ in 0..n_pairs {
 			let name_idx = buffer.read_u16()?;
@@ -647,19 +1060,90 @@ impl RuntimeTypeAnnotation {
 			pairs,
 		})
 	}
+
+	pub fn write<B: BytesWriteExt>(&self, buffer: &mut B) -> Result<(), IRClassfileError> {
+		buffer.write_u8(self.target_type)?;
+		match &self.target_info {
+			RuntimeTypeAnnotationTargetInfo::TypeParameterTarget { type_param_index } => {
+				buffer.write_u8(*type_param_index)?
+			}
+			RuntimeTypeAnnotationTargetInfo::SupertypeTarget { supertype_index } => {
+				buffer.write_u16(*supertype_index)?
+			}
+			RuntimeTypeAnnotationTargetInfo::TypeParameterBoundTarget {
+				type_param_index,
+				bound_index,
+			} => {
+				buffer.write_u8(*type_param_index)?;
+				buffer.write_u8(*bound_index)?;
+			}
+			RuntimeTypeAnnotationTargetInfo::EmptyTarget => {}
+			RuntimeTypeAnnotationTargetInfo::FormalParameterTarget { formal_param_index } => {
+				buffer.write_u8(*formal_param_index)?
+			}
+			RuntimeTypeAnnotationTargetInfo::ThrowsTarget { throws_type_index } => {
+				buffer.write_u16(*throws_type_index)?
+			}
+			RuntimeTypeAnnotationTargetInfo::LocalvarTarget { table } => {
+				buffer.write_u16(count("localvar targets", table.len())?)?;
+				for entry in table {
+					buffer.write_u16(entry.start_pc)?;
+					buffer.write_u16(entry.length)?;
+					buffer.write_u16(entry.index)?;
+				}
+			}
+			RuntimeTypeAnnotationTargetInfo::CatchTarget { exception_table_index } => {
+				buffer.write_u16(*exception_table_index)?
+			}
+			RuntimeTypeAnnotationTargetInfo::OffsetTarget { offset } => buffer.write_u16(*offset)?,
+			RuntimeTypeAnnotationTargetInfo::TypeArgumentTarget {
+				offset,
+				type_argument_index,
+			} => {
+				buffer.write_u16(*offset)?;
+				buffer.write_u8(*type_argument_index)?;
+			}
+		}
+
+		buffer.write_u8(
+			u8::try_from(self.target_path.len()).map_err(|_| IRClassfileError::TooMany {
+				what: "type path parts",
+				actual: self.target_path.len(),
+			})?,
+		)?;
+		for part in &self.target_path {
+			buffer.write_u8(part.type_path_kind)?;
+			buffer.write_u8(part.type_argument_kind)?;
+		}
+
+		buffer.write_u16(self.type_index)?;
+		RuntimeAnnotationEVPair::write_all(&self.pairs, buffer)
+	}
+
+	fn write_all<B: BytesWriteExt>(
+		annotations: &[RuntimeTypeAnnotation],
+		buffer: &mut B,
+	) -> Result<(), IRClassfileError> {
+		buffer.write_u16(count("type annotations", annotations.len())?)?;
+		for annotation in annotations {
+			annotation.write(buffer)?;
+		}
+		Ok(())
+	}
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ModuleRequiresEntry {
 	pub module: CPModuleInfoRef,
-	pub flags: u16,
+	pub flags: RequiresFlags,
 	pub version: Option<CPUtf8Ref>,
 }
 
 impl ModuleRequiresEntry {
 	pub fn new<B: BytesReadExt>(cp: &[IRCpTag], buffer: &mut B) -> Result<Self, IRClassfileError> {
 		let module_idx = buffer.read_u16()?;
-		let flags = buffer.read_u16()?;
+		let flags = RequiresFlags::from_bits_retain(buffer.read_u16()?);
 		let version_idx = buffer.read_u16()?;
 
 		Ok(Self {
@@ -672,22 +1156,31 @@ impl ModuleRequiresEntry {
 			},
 		})
 	}
+
+	pub fn write<B: BytesWriteExt>(&self, buffer: &mut B) -> Result<(), IRClassfileError> {
+		buffer.write_u16(self.module.index)?;
+		buffer.write_u16(self.flags.bits())?;
+		buffer.write_u16(self.version.as_ref().map_or(0, |version| version.index))?;
+		Ok(())
+	}
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ModuleExportsEntry {
 	pub package: CPPackageInfoRef,
-	pub flags: u16,
+	pub flags: ExportsFlags,
+	/// Modules the package is exported to, empty for an unqualified export.
 	pub exports: Vec<CPModuleInfoRef>,
 }
 
 impl ModuleExportsEntry {
 	pub fn new<B: BytesReadExt>(cp: &[IRCpTag], buffer: &mut B) -> Result<Self, IRClassfileError> {
 		let package_idx = buffer.read_u16()?;
-		let flags = buffer.read_u16()?;
+		let flags = ExportsFlags::from_bits_retain(buffer.read_u16()?);
 
 		let n_exports = buffer.read_u16()? as usize;
-		let mut exports = Vec::with_capacity(n_exports);
+		let mut exports = maya_bytes::vec_with_capacity(n_exports)?;
 
 		for _ in 0..n_exports {
 			exports.push(CPModuleInfoRef::from_cp(cp, buffer.read_u16()?));
@@ -699,22 +1192,30 @@ impl ModuleExportsEntry {
 			exports,
 		})
 	}
+
+	pub fn write<B: BytesWriteExt>(&self, buffer: &mut B) -> Result<(), IRClassfileError> {
+		buffer.write_u16(self.package.index)?;
+		buffer.write_u16(self.flags.bits())?;
+		write_indices(buffer, "exports", self.exports.iter().map(|module| module.index))
+	}
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ModuleOpensEntry {
 	pub package: CPPackageInfoRef,
-	pub flags: u16,
+	pub flags: OpensFlags,
+	/// Modules the package is opened to, empty for an unqualified open.
 	pub opens: Vec<CPModuleInfoRef>,
 }
 
 impl ModuleOpensEntry {
 	pub fn new<B: BytesReadExt>(cp: &[IRCpTag], buffer: &mut B) -> Result<Self, IRClassfileError> {
 		let package_idx = buffer.read_u16()?;
-		let flags = buffer.read_u16()?;
+		let flags = OpensFlags::from_bits_retain(buffer.read_u16()?);
 
 		let n_opens = buffer.read_u16()? as usize;
-		let mut opens = Vec::with_capacity(n_opens);
+		let mut opens = maya_bytes::vec_with_capacity(n_opens)?;
 
 		for _ in 0..n_opens {
 			opens.push(CPModuleInfoRef::from_cp(cp, buffer.read_u16()?));
@@ -726,36 +1227,48 @@ impl ModuleOpensEntry {
 			opens,
 		})
 	}
+
+	pub fn write<B: BytesWriteExt>(&self, buffer: &mut B) -> Result<(), IRClassfileError> {
+		buffer.write_u16(self.package.index)?;
+		buffer.write_u16(self.flags.bits())?;
+		write_indices(buffer, "opens", self.opens.iter().map(|module| module.index))
+	}
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ModuleProvidesEntry {
+	/// The service interface.
 	pub class: CPClassRef,
-	pub flags: u16,
+	/// Its implementations.
 	pub provides: Vec<CPClassRef>,
 }
 
 impl ModuleProvidesEntry {
 	pub fn new<B: BytesReadExt>(cp: &[IRCpTag], buffer: &mut B) -> Result<Self, IRClassfileError> {
-		let package_idx = buffer.read_u16()?;
-		let flags = buffer.read_u16()?;
+		let class_idx = buffer.read_u16()?;
 
-		let n_exports = buffer.read_u16()? as usize;
-		let mut provides = Vec::with_capacity(n_exports);
+		let n_provides = buffer.read_u16()? as usize;
+		let mut provides = maya_bytes::vec_with_capacity(n_provides)?;
 
-		for _ in 0..n_exports {
+		for _ in 0..n_provides {
 			provides.push(CPClassRef::from_cp(cp, buffer.read_u16()?));
 		}
 
 		Ok(Self {
-			class: CPClassRef::from_cp(cp, package_idx),
-			flags,
+			class: CPClassRef::from_cp(cp, class_idx),
 			provides,
 		})
 	}
+
+	pub fn write<B: BytesWriteExt>(&self, buffer: &mut B) -> Result<(), IRClassfileError> {
+		buffer.write_u16(self.class.index)?;
+		write_indices(buffer, "provides", self.provides.iter().map(|class| class.index))
+	}
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct IRAttributeInfo {
 	pub name: CPUtf8Ref,
 	pub length: u32,
@@ -769,22 +1282,41 @@ impl IRAttributeInfo {
 			cp.get(raw.attribute_name_index as usize - 1).expect("invalid index"),
 		);
 
-		let mut buffer = Cursor::new(raw.info);
+		#[cfg(feature = "tracing")]
+		tracing::trace!(
+			name = name.data.as_str(),
+			length = raw.attribute_length,
+			"parsing attribute"
+		);
+		let mut cursor = Cursor::new(raw.info);
+		let mut buffer = cursor.limited(raw.attribute_length as u64);
+		let attr = IRAttribute::new(name.clone(), cp, &mut buffer)?;
+		// attribute_length has to match what the attribute actually contains
+		buffer.finish()?;
+
 		Ok(Self {
 			length: raw.attribute_length,
-			attr: IRAttribute::new(name.clone(), cp, &mut buffer)?,
+			attr,
 			name,
 		})
 	}
+
+	/// Serializes the attribute back into raw bytes, `length` is recomputed rather than copied.
+	pub fn to_io(&self, cp: &[IRCpTag]) -> Result<IOAttributeInfo, IRClassfileError> {
+		let mut info = Vec::new();
+		self.attr.write(cp, &mut info)?;
+		Ok(IOAttributeInfo::new(self.name.index, info))
+	}
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum IRAttribute {
 	ConstantValue(ConstantValueAttribute),
 	Code(CodeAttribute),
 	StackMapTable(StackMapTableAttribute),
 	Exceptions {
-		exception_index_table: Vec<CPUtf8Ref>,
+		exception_index_table: Vec<CPClassRef>,
 	},
 	InnerClasses(InnerClassesAttribute),
 	EnclosingMethod {
@@ -794,7 +1326,7 @@ pub enum IRAttribute {
 	Synthetic,
 	Signature(CPUtf8Ref),
 	SourceFile(CPUtf8Ref),
-	SourceDebugExtension(Rc<String>),
+	SourceDebugExtension(Shared<String>),
 	LineNumberTable(LineNumberTableAttribute),
 	LocalVariableTable {
 		table: Vec<LocalVariableTableEntry>,
@@ -842,7 +1374,7 @@ pub enum IRAttribute {
 	},
 	Module {
 		module_name: CPModuleInfoRef,
-		module_flags: u16,
+		module_flags: ModuleFlags,
 		module_version: Option<CPUtf8Ref>,
 
 		requires: Vec<ModuleRequiresEntry>,
@@ -859,6 +1391,14 @@ pub enum IRAttribute {
 	ModuleMainClass {
 		class: CPClassRef,
 	},
+	#[cfg(feature = "preview")]
+	Preview(PreviewAttribute),
+	/// An attribute we don't parse, e.g. from another compiler or a vendor. Kept as raw bytes and
+	/// written back unchanged.
+	Unknown {
+		name: CPUtf8Ref,
+		data: Vec<u8>,
+	},
 }
 
 impl IRAttribute {
@@ -878,7 +1418,9 @@ impl IRAttribute {
 					IRCpTag::Double(value) => {
 						Self::ConstantValue(ConstantValueAttribute::Double { cp_idx, value: *value })
 					}
-					IRCpTag::String(value) => Self::ConstantValue(ConstantValueAttribute::String(value.clone())),
+					IRCpTag::String(_) => {
+						Self::ConstantValue(ConstantValueAttribute::String(CPStringRef::new(cp_idx, tag)))
+					}
 					_ => panic!("didnt expect tag: {tag:?}"),
 				}
 			}
@@ -887,7 +1429,7 @@ impl IRAttribute {
 
 			"StackMapTable" => {
 				let n_entries = buffer.read_u16()? as usize;
-				let mut entries = Vec::with_capacity(n_entries);
+				let mut entries = maya_bytes::vec_with_capacity(n_entries)?;
 
 				for _ in 0..n_entries {
 					entries.push(StackMapFrame::new(buffer)?);
@@ -898,11 +1440,12 @@ impl IRAttribute {
 
 			"Exceptions" => {
 				let n_exceptions = buffer.read_u16()? as usize;
-				let mut exception_index_table = Vec::with_capacity(n_exceptions);
+				let mut exception_index_table = maya_bytes::vec_with_capacity(n_exceptions)?;
 
 				for _ in 0..n_exceptions {
-					let idx = buffer.read_u16()?;
-					exception_index_table.push(CPUtf8Ref::new(idx, cp.get(idx as usize).expect("expected utf8")));
+					let index = buffer.read_u16()?;
+					let tag = cp.get(index as usize - 1).expect("expected class");
+					exception_index_table.push(CPClassRef::new(index, tag));
 				}
 
 				Self::Exceptions { exception_index_table }
@@ -916,7 +1459,7 @@ impl IRAttribute {
 			}
 			"NestMembers" => {
 				let n_classes = buffer.read_u16()? as usize;
-				let mut classes = Vec::with_capacity(n_classes);
+				let mut classes = maya_bytes::vec_with_capacity(n_classes)?;
 
 				for _ in 0..n_classes {
 					let index = buffer.read_u16()?;
@@ -928,7 +1471,7 @@ impl IRAttribute {
 			}
 			"InnerClasses" => {
 				let n_classes = buffer.read_u16()? as usize;
-				let mut classes = Vec::with_capacity(n_classes);
+				let mut classes = maya_bytes::vec_with_capacity(n_classes)?;
 
 				for _ in 0..n_classes {
 					classes.push(InnerClassesAttributeClass::new(cp, buffer)?);
@@ -947,7 +1490,7 @@ impl IRAttribute {
 			}
 			"MethodParameters" => {
 				let n_params = buffer.read_u8()? as usize;
-				let mut parameters = Vec::with_capacity(n_params);
+				let mut parameters = maya_bytes::vec_with_capacity(n_params)?;
 
 				for _ in 0..n_params {
 					parameters.push(MethodParametersParam::new(cp, buffer)?);
@@ -958,7 +1501,7 @@ impl IRAttribute {
 			"Deprecated" => Self::Deprecated,
 			"RuntimeVisibleAnnotations" => {
 				let n_annotations = buffer.read_u16()? as usize;
-				let mut annotations = Vec::with_capacity(n_annotations);
+				let mut annotations = maya_bytes::vec_with_capacity(n_annotations)?;
 
 				for _ in 0..n_annotations {
 					annotations.push(RuntimeAnnotation::new(cp, buffer)?);
@@ -968,7 +1511,7 @@ impl IRAttribute {
 			}
 			"RuntimeInvisibleAnnotations" => {
 				let n_annotations = buffer.read_u16()? as usize;
-				let mut annotations = Vec::with_capacity(n_annotations);
+				let mut annotations = maya_bytes::vec_with_capacity(n_annotations)?;
 
 				for _ in 0..n_annotations {
 					annotations.push(RuntimeAnnotation::new(cp, buffer)?);
@@ -978,11 +1521,11 @@ impl IRAttribute {
 			}
 			"RuntimeVisibleParameterAnnotations" => {
 				let n_params = buffer.read_u8()? as usize;
-				let mut params = Vec::with_capacity(n_params);
+				let mut params = maya_bytes::vec_with_capacity(n_params)?;
 
 				for _ in 0..n_params {
 					let n_annotations = buffer.read_u16()? as usize;
-					let mut annotations = Vec::with_capacity(n_annotations);
+					let mut annotations = maya_bytes::vec_with_capacity(n_annotations)?;
 
 					for _ in 0..n_annotations {
 						annotations.push(RuntimeAnnotation::new(cp, buffer)?);
@@ -995,11 +1538,11 @@ impl IRAttribute {
 			}
 			"RuntimeInvisibleParameterAnnotations" => {
 				let n_params = buffer.read_u8()? as usize;
-				let mut params = Vec::with_capacity(n_params);
+				let mut params = maya_bytes::vec_with_capacity(n_params)?;
 
 				for _ in 0..n_params {
 					let n_annotations = buffer.read_u16()? as usize;
-					let mut annotations = Vec::with_capacity(n_annotations);
+					let mut annotations = maya_bytes::vec_with_capacity(n_annotations)?;
 
 					for _ in 0..n_annotations {
 						annotations.push(RuntimeAnnotation::new(cp, buffer)?);
@@ -1012,7 +1555,7 @@ impl IRAttribute {
 			}
 			"Record" => {
 				let n_components = buffer.read_u16()? as usize;
-				let mut components = Vec::with_capacity(n_components);
+				let mut components = maya_bytes::vec_with_capacity(n_components)?;
 
 				for _ in 0..n_components {
 					components.push(RecordComponentInfo::new(cp, buffer)?);
@@ -1022,7 +1565,7 @@ impl IRAttribute {
 			}
 			"BootstrapMethods" => {
 				let n_methods = buffer.read_u16()? as usize;
-				let mut methods = Vec::with_capacity(n_methods);
+				let mut methods = maya_bytes::vec_with_capacity(n_methods)?;
 
 				for _ in 0..n_methods {
 					methods.push(BootstrapMethodsMethod::new(cp, buffer)?);
@@ -1032,7 +1575,7 @@ impl IRAttribute {
 			}
 			"PermittedSubclasses" => {
 				let n_classes = buffer.read_u16()? as usize;
-				let mut classes = Vec::with_capacity(n_classes);
+				let mut classes = maya_bytes::vec_with_capacity(n_classes)?;
 
 				for _ in 0..n_classes {
 					classes.push(CPClassRef::from_cp(cp, buffer.read_u16()?));
@@ -1040,10 +1583,12 @@ impl IRAttribute {
 
 				Self::PermittedSubclasses { classes }
 			}
-			"SourceDebugExtension" => Self::SourceDebugExtension(Rc::new(String::from_utf8(buffer.read_to_vec()?)?)),
+			"SourceDebugExtension" => {
+				Self::SourceDebugExtension(Shared::new(maya_mutf8::decode(&buffer.read_to_vec()?)?))
+			}
 			"LocalVariableTable" => {
 				let n_entries = buffer.read_u16()? as usize;
-				let mut table = Vec::with_capacity(n_entries);
+				let mut table = maya_bytes::vec_with_capacity(n_entries)?;
 
 				for _ in 0..n_entries {
 					table.push(LocalVariableTableEntry::new(cp, buffer)?);
@@ -1053,7 +1598,7 @@ impl IRAttribute {
 			}
 			"LocalVariableTypeTable" => {
 				let n_entries = buffer.read_u16()? as usize;
-				let mut table = Vec::with_capacity(n_entries);
+				let mut table = maya_bytes::vec_with_capacity(n_entries)?;
 
 				for _ in 0..n_entries {
 					table.push(LocalVariableTypeTableEntry::new(cp, buffer)?);
@@ -1067,7 +1612,7 @@ impl IRAttribute {
 			},
 			"RuntimeVisibleTypeAnnotations" => {
 				let n_annotations = buffer.read_u16()? as usize;
-				let mut annotations = Vec::with_capacity(n_annotations);
+				let mut annotations = maya_bytes::vec_with_capacity(n_annotations)?;
 
 				for _ in 0..n_annotations {
 					annotations.push(RuntimeTypeAnnotation::new(cp, buffer)?);
@@ -1077,7 +1622,7 @@ impl IRAttribute {
 			}
 			"RuntimeInvisibleTypeAnnotations" => {
 				let n_annotations = buffer.read_u16()? as usize;
-				let mut annotations = Vec::with_capacity(n_annotations);
+				let mut annotations = maya_bytes::vec_with_capacity(n_annotations)?;
 
 				for _ in 0..n_annotations {
 					annotations.push(RuntimeTypeAnnotation::new(cp, buffer)?);
@@ -1090,35 +1635,35 @@ impl IRAttribute {
 			},
 			"Module" => {
 				let module_name_idx = buffer.read_u16()?;
-				let module_flags = buffer.read_u16()?;
+				let module_flags = ModuleFlags::from_bits_retain(buffer.read_u16()?);
 				let module_version_idx = buffer.read_u16()?;
 
 				let n_requires = buffer.read_u16()? as usize;
-				let mut requires = Vec::with_capacity(n_requires);
+				let mut requires = maya_bytes::vec_with_capacity(n_requires)?;
 				for _ in 0..n_requires {
 					requires.push(ModuleRequiresEntry::new(cp, buffer)?);
 				}
 
 				let n_exports = buffer.read_u16()? as usize;
-				let mut exports = Vec::with_capacity(n_exports);
+				let mut exports = maya_bytes::vec_with_capacity(n_exports)?;
 				for _ in 0..n_exports {
 					exports.push(ModuleExportsEntry::new(cp, buffer)?);
 				}
 
 				let n_opens = buffer.read_u16()? as usize;
-				let mut opens = Vec::with_capacity(n_opens);
+				let mut opens = maya_bytes::vec_with_capacity(n_opens)?;
 				for _ in 0..n_opens {
 					opens.push(ModuleOpensEntry::new(cp, buffer)?);
 				}
 
 				let n_uses = buffer.read_u16()? as usize;
-				let mut uses = Vec::with_capacity(n_uses);
+				let mut uses = maya_bytes::vec_with_capacity(n_uses)?;
 				for _ in 0..n_uses {
 					uses.push(CPClassRef::from_cp(cp, buffer.read_u16()?));
 				}
 
 				let n_provides = buffer.read_u16()? as usize;
-				let mut provides = Vec::with_capacity(n_provides);
+				let mut provides = maya_bytes::vec_with_capacity(n_provides)?;
 				for _ in 0..n_provides {
 					provides.push(ModuleProvidesEntry::new(cp, buffer)?);
 				}
@@ -1140,7 +1685,7 @@ impl IRAttribute {
 			}
 			"ModulePackages" => {
 				let n_packages = buffer.read_u16()? as usize;
-				let mut packages = Vec::with_capacity(n_packages);
+				let mut packages = maya_bytes::vec_with_capacity(n_packages)?;
 				for _ in 0..n_packages {
 					packages.push(CPPackageInfoRef::from_cp(cp, buffer.read_u16()?));
 				}
@@ -1150,11 +1695,148 @@ impl IRAttribute {
 				class: CPClassRef::from_cp(cp, buffer.read_u16()?),
 			},
 
-			n => panic!("unparsed attribute: {n}"),
+			#[cfg(feature = "preview")]
+			n if is_preview_attribute(n) => Self::Preview(PreviewAttribute::new(n, cp, buffer)?),
+
+			_ => {
+				#[cfg(feature = "tracing")]
+				tracing::debug!(name = name.data.as_str(), "kept unknown attribute as bytes");
+				Self::Unknown {
+					data: buffer.read_to_vec()?,
+					name,
+				}
+			}
 		})
 	}
 
-	pub const fn name(&self) -> &'static str {
+	/// Writes the attribute body, i.e. what goes in `info` after the name index and length.
+	pub fn write<B: BytesWriteExt>(&self, cp: &[IRCpTag], buffer: &mut B) -> Result<(), IRClassfileError> {
+		match self {
+			Self::ConstantValue(value) => value.write(buffer)?,
+			Self::Code(code) => code.write(cp, buffer)?,
+			Self::StackMapTable(table) => {
+				buffer.write_u16(count("stack map frames", table.entries.len())?)?;
+				for frame in &table.entries {
+					frame.write(buffer)?;
+				}
+			}
+			Self::Exceptions { exception_index_table } => write_indices(
+				buffer,
+				"exceptions",
+				exception_index_table.iter().map(|class| class.index),
+			)?,
+			Self::InnerClasses(inner) => {
+				buffer.write_u16(count("inner classes", inner.classes.len())?)?;
+				for class in &inner.classes {
+					class.write(buffer)?;
+				}
+			}
+			Self::EnclosingMethod { class, method } => {
+				buffer.write_u16(class.index)?;
+				buffer.write_u16(method.index)?;
+			}
+			Self::Synthetic | Self::Deprecated => {}
+			Self::Signature(utf8) | Self::SourceFile(utf8) => buffer.write_u16(utf8.index)?,
+			Self::SourceDebugExtension(debug) => {
+				buffer.write_all(&maya_mutf8::encode(debug)).map_err(BytesError::from)?
+			}
+			Self::LineNumberTable(table) => table.write(buffer)?,
+			Self::LocalVariableTable { table } => {
+				buffer.write_u16(count("local variables", table.len())?)?;
+				for entry in table {
+					entry.write(buffer)?;
+				}
+			}
+			Self::LocalVariableTypeTable { table } => {
+				buffer.write_u16(count("local variable types", table.len())?)?;
+				for entry in table {
+					entry.write(buffer)?;
+				}
+			}
+			Self::RuntimeVisibleAnnotations { annotations } | Self::RuntimeInvisibleAnnotations { annotations } => {
+				RuntimeAnnotation::write_all(annotations, buffer)?
+			}
+			Self::RuntimeVisibleParameterAnnotations { params }
+			| Self::RuntimeInvisibleParameterAnnotations { params } => {
+				buffer.write_u8(u8::try_from(params.len()).map_err(|_| IRClassfileError::TooMany {
+					what: "annotated parameters",
+					actual: params.len(),
+				})?)?;
+				for annotations in params {
+					RuntimeAnnotation::write_all(annotations, buffer)?;
+				}
+			}
+			Self::AnnotationDefault { default_value } => default_value.write(buffer)?,
+			Self::BootstrapMethods { methods } => {
+				buffer.write_u16(count("bootstrap methods", methods.len())?)?;
+				for method in methods {
+					method.write(buffer)?;
+				}
+			}
+			Self::NestMembers { classes } | Self::PermittedSubclasses { classes } => {
+				write_indices(buffer, "classes", classes.iter().map(|class| class.index))?
+			}
+			Self::NestHost(class) | Self::ModuleMainClass { class } => buffer.write_u16(class.index)?,
+			Self::MethodParameters { parameters } => {
+				buffer.write_u8(u8::try_from(parameters.len()).map_err(|_| IRClassfileError::TooMany {
+					what: "method parameters",
+					actual: parameters.len(),
+				})?)?;
+				for parameter in parameters {
+					parameter.write(buffer)?;
+				}
+			}
+			Self::Record { components } => {
+				buffer.write_u16(count("record components", components.len())?)?;
+				for component in components {
+					component.write(cp, buffer)?;
+				}
+			}
+			Self::RuntimeVisibleTypeAnnotations { annotations }
+			| Self::RuntimeInvisibleTypeAnnotations { annotations } => RuntimeTypeAnnotation::write_all(annotations, buffer)?,
+			Self::Module {
+				module_name,
+				module_flags,
+				module_version,
+				requires,
+				exports,
+				opens,
+				uses,
+				provides,
+			} => {
+				buffer.write_u16(module_name.index)?;
+				buffer.write_u16(module_flags.bits())?;
+				buffer.write_u16(module_version.as_ref().map_or(0, |version| version.index))?;
+
+				buffer.write_u16(count("requires", requires.len())?)?;
+				for entry in requires {
+					entry.write(buffer)?;
+				}
+				buffer.write_u16(count("exports", exports.len())?)?;
+				for entry in exports {
+					entry.write(buffer)?;
+				}
+				buffer.write_u16(count("opens", opens.len())?)?;
+				for entry in opens {
+					entry.write(buffer)?;
+				}
+				write_indices(buffer, "uses", uses.iter().map(|class| class.index))?;
+				buffer.write_u16(count("provides", provides.len())?)?;
+				for entry in provides {
+					entry.write(buffer)?;
+				}
+			}
+			Self::ModulePackages { packages } => {
+				write_indices(buffer, "packages", packages.iter().map(|package| package.index))?
+			}
+			#[cfg(feature = "preview")]
+			Self::Preview(preview) => preview.write(buffer)?,
+			Self::Unknown { name: _, data } => buffer.write_all(data).map_err(BytesError::from)?,
+		}
+		Ok(())
+	}
+
+	pub fn name(&self) -> &str {
 		match self {
 			Self::ConstantValue(_) => "ConstantValue",
 			Self::Code(_) => "Code",
@@ -1197,6 +1879,322 @@ impl IRAttribute {
 			} => "Module",
 			Self::ModulePackages { packages: _ } => "ModulePackages",
 			Self::ModuleMainClass { class: _ } => "ModuleMainClass",
+			#[cfg(feature = "preview")]
+			Self::Preview(preview) => preview.name(),
+			Self::Unknown { name, data: _ } => name.data.as_str(),
 		}
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use maya_classfile_io::IOClassFile;
+
+	use super::*;
+	use crate::{
+		access::MethodAccessFlags,
+		builder::ClassBuilder,
+		class_pool::CPConstValueRefKind,
+		code::Instructions,
+		disasm,
+		remap::Mappings,
+		test_util::{class, find_attr, B_MODULE_INFO, HELLO, HELLO_ANNO, PAIR, TYPED},
+		IRClassFile,
+	};
+
+	#[test]
+	fn exceptions_round_trip() {
+		let mut builder = ClassBuilder::new("gen/Thrower");
+		let io = builder.class().intern_class("java/io/IOException").unwrap();
+		let name = builder.class().intern_utf8("Exceptions").unwrap();
+		let mut f = builder
+			.method(MethodAccessFlags::PUBLIC | MethodAccessFlags::STATIC, "f", "()V")
+			.unwrap();
+		f.op(Instructions::RETURN);
+		f.finish().unwrap().attributes.push(IRAttributeInfo {
+			name,
+			length: 0,
+			attr: IRAttribute::Exceptions {
+				exception_index_table: vec![io.clone()],
+			},
+		});
+
+		let mut written = Vec::new();
+		builder.build().write(&mut written).unwrap();
+		let thrower = class(&written);
+		let method = thrower.find_method("f", "()V").unwrap();
+		let IRAttribute::Exceptions { exception_index_table } = find_attr(&method.attributes, "Exceptions") else {
+			unreachable!();
+		};
+		assert_eq!(exception_index_table.len(), 1);
+		assert_eq!(exception_index_table[0].index, io.index);
+		assert_eq!(exception_index_table[0].data.data.as_str(), "java/io/IOException");
+		assert!(disasm::disassemble(&thrower).contains("throws java/io/IOException"));
+
+		// compacting renumbers the pool, the diff still sees the same class thrown
+		let mut compacted = class(&written);
+		compacted.compact_constant_pool().unwrap();
+		assert!(thrower.structural_eq(&compacted));
+		let IRAttribute::Exceptions { exception_index_table } =
+			find_attr(&compacted.find_method("f", "()V").unwrap().attributes, "Exceptions")
+		else {
+			unreachable!();
+		};
+		assert_eq!(exception_index_table[0].data.data.as_str(), "java/io/IOException");
+
+		let mut mappings = Mappings::new();
+		mappings.add_class("java/io/IOException", "x/Failure");
+		compacted.remap(&mappings).unwrap();
+		let IRAttribute::Exceptions { exception_index_table } =
+			find_attr(&compacted.find_method("f", "()V").unwrap().attributes, "Exceptions")
+		else {
+			unreachable!();
+		};
+		assert_eq!(exception_index_table[0].data.data.as_str(), "x/Failure");
+	}
+
+	#[test]
+	fn local_variable_lookup() {
+		let utf8 = |index: u16, data: &str| CPUtf8Ref {
+			data: Shared::new(data.to_string()),
+			index,
+		};
+		let entry = |start_pc, length, name, index| LocalVariableTableEntry {
+			start_pc,
+			length,
+			name: utf8(1, name),
+			descriptor: utf8(2, "I"),
+			index,
+		};
+		let code = CodeAttribute {
+			max_stack: 1,
+			max_locals: 2,
+			code: vec![0; 20],
+			exception_table: Vec::new(),
+			attributes: vec![Box::new(IRAttributeInfo {
+				name: utf8(3, "LocalVariableTable"),
+				length: 22,
+				attr: IRAttribute::LocalVariableTable {
+					table: vec![entry(0, 20, "this", 0), entry(4, 6, "a", 1), entry(10, 10, "b", 1)],
+				},
+			})],
+		};
+
+		assert_eq!(code.local_variable_at(5, 0).unwrap().name.data.as_str(), "this");
+		assert_eq!(code.local_variable_at(4, 1).unwrap().name.data.as_str(), "a");
+		assert_eq!(code.local_variable_at(10, 1).unwrap().name.data.as_str(), "b");
+		assert!(code.local_variable_at(2, 1).is_none());
+		assert!(code.local_variable_at(20, 0).is_none());
+		assert!(code.local_variable_type_at(5, 0).is_none());
+	}
+
+	#[test]
+	fn nested_annotations() {
+		// @HelloAnnoRec(value = @HelloAnno(value = "Hi"))
+		let hello = class(HELLO);
+		let stackmapper = hello
+			.methods
+			.iter()
+			.find(|m| m.name.data.as_str() == "stackmapper")
+			.unwrap();
+		let IRAttribute::RuntimeVisibleAnnotations { annotations } =
+			find_attr(&stackmapper.attributes, "RuntimeVisibleAnnotations")
+		else {
+			unreachable!()
+		};
+		assert_eq!(annotations[0].ty.data.as_str(), "La/Hello$HelloAnnoRec;");
+		let RuntimeAnnotationValue::Annotation(inner) = &annotations[0].pairs[0].value else {
+			panic!("expected a nested annotation");
+		};
+		assert_eq!(inner.ty.data.as_str(), "La/Hello$HelloAnno;");
+		let RuntimeAnnotationValue::ConstValueIndex { tag: b's', value } = &inner.pairs[0].value else {
+			panic!("expected a string constant");
+		};
+		assert!(matches!(&value.kind, CPConstValueRefKind::String(s) if s.as_str() == "Hi"));
+
+		// String value() default "WAWAWAW", with @Target({...}) on the class
+		let anno = class(HELLO_ANNO);
+		let value = &anno.methods[0];
+		assert!(matches!(
+			find_attr(&value.attributes, "AnnotationDefault"),
+			IRAttribute::AnnotationDefault {
+				default_value: RuntimeAnnotationValue::ConstValueIndex { tag: b's', .. }
+			}
+		));
+		let IRAttribute::RuntimeVisibleAnnotations { annotations } =
+			find_attr(&anno.attributes, "RuntimeVisibleAnnotations")
+		else {
+			unreachable!()
+		};
+		let RuntimeAnnotationValue::ArrayValue { values } = &annotations[0].pairs[0].value else {
+			panic!("expected @Target array");
+		};
+		assert_eq!(values.len(), 9);
+		assert!(values
+			.iter()
+			.all(|v| matches!(v, RuntimeAnnotationValue::EnumConstValue { .. })));
+
+		// even without limits, nesting stops before it can overflow the stack
+		let mut deep = [b'[', 0, 1].repeat(Limits::NONE.max_nesting as usize + 2);
+		deep.push(b'Z');
+		assert!(matches!(
+			RuntimeAnnotationValue::new(&[], &mut std::io::Cursor::new(deep)),
+			Err(IRClassfileError::IO(IOClassfileError::LimitExceeded {
+				what: "annotation nesting",
+				limit: 256,
+				..
+			}))
+		));
+		assert!(matches!(
+			RuntimeAnnotationValue::new(&[], &mut std::io::Cursor::new(b"x")),
+			Err(IRClassfileError::InvalidAnnotationTag(b'x'))
+		));
+	}
+
+	#[test]
+	fn module_attribute() {
+		let module = class(B_MODULE_INFO);
+		let IRAttribute::Module {
+			module_name,
+			module_flags,
+			requires,
+			exports,
+			opens,
+			uses,
+			provides,
+			..
+		} = find_attr(&module.attributes, "Module")
+		else {
+			unreachable!()
+		};
+		assert_eq!(module_name.data.data.as_str(), "b");
+		assert_eq!(*module_flags, ModuleFlags::empty());
+		assert_eq!(requires[0].flags, RequiresFlags::MANDATED);
+		assert_eq!(requires[1].flags, RequiresFlags::TRANSITIVE);
+		assert_eq!(requires[2].flags, RequiresFlags::STATIC_PHASE);
+		assert!(exports[0].exports.is_empty());
+		assert_eq!(exports[1].exports[0].data.data.as_str(), "java.logging");
+		assert_eq!(opens[0].package.data.data.as_str(), "b/internal");
+		assert_eq!(uses[0].data.data.as_str(), "b/api/Service");
+		assert_eq!(provides[0].class.data.data.as_str(), "b/api/Service");
+		let implementations: Vec<_> = provides[0].provides.iter().map(|c| c.data.data.as_str()).collect();
+		assert_eq!(implementations, ["b/internal/Impl", "b/internal/OtherImpl"]);
+	}
+
+	#[test]
+	fn record_components() {
+		// record Pair<T>(@Named("head") T first, List<T> rest, int size)
+		let pair = class(PAIR);
+		let components = pair.record_components().unwrap();
+		let names: Vec<_> = components.iter().map(|c| c.name.data.as_str()).collect();
+		assert_eq!(names, ["first", "rest", "size"]);
+
+		assert_eq!(components[0].descriptor.data.as_str(), "Ljava/lang/Object;");
+		assert_eq!(components[0].signature().unwrap().data.as_str(), "TT;");
+		assert_eq!(
+			components[1].signature().unwrap().data.as_str(),
+			"Ljava/util/List<TT;>;"
+		);
+		assert!(components[2].signature().is_none());
+
+		let named: Vec<_> = components[0].annotations().collect();
+		assert_eq!(named.len(), 1);
+		assert_eq!(named[0].ty.data.as_str(), "Lb/api/Pair$Named;");
+		assert_eq!(components[1].annotations().count(), 0);
+
+		assert!(class(HELLO).record_components().is_none());
+	}
+
+	#[test]
+	fn type_annotation_targets() {
+		use RuntimeTypeAnnotationTargetInfo as Target;
+
+		fn collect<'a>(attributes: &'a [IRAttributeInfo], out: &mut Vec<&'a RuntimeTypeAnnotation>) {
+			for attr in attributes {
+				match &attr.attr {
+					IRAttribute::RuntimeVisibleTypeAnnotations { annotations }
+					| IRAttribute::RuntimeInvisibleTypeAnnotations { annotations } => out.extend(annotations),
+					IRAttribute::Code(code) => {
+						for attr in &code.attributes {
+							collect(std::slice::from_ref(attr.as_ref()), out);
+						}
+					}
+					_ => {}
+				}
+			}
+		}
+
+		let typed = class(TYPED);
+		let mut annotations = Vec::new();
+		collect(&typed.attributes, &mut annotations);
+		for member in typed
+			.fields
+			.iter()
+			.map(|f| &f.attributes)
+			.chain(typed.methods.iter().map(|m| &m.attributes))
+		{
+			collect(member, &mut annotations);
+		}
+
+		let mut targets: Vec<u8> = annotations.iter().map(|a| a.target_type).collect();
+		targets.sort();
+		targets.dedup();
+		assert_eq!(
+			targets,
+			[0x00, 0x01, 0x10, 0x11, 0x13, 0x14, 0x16, 0x17, 0x40, 0x41, 0x42, 0x43, 0x44, 0x45, 0x46, 0x47, 0x49]
+		);
+
+		let method_reference = annotations.iter().find(|a| a.target_type == 0x46).unwrap();
+		assert!(matches!(
+			method_reference.target_info,
+			Target::OffsetTarget { offset: 20 }
+		));
+		let local = annotations.iter().find(|a| a.target_type == 0x40).unwrap();
+		assert!(matches!(&local.target_info, Target::LocalvarTarget { table } if table[0].index == 2));
+
+		let paths: Vec<Vec<_>> = annotations
+			.iter()
+			.filter(|a| a.target_type == 0x13)
+			.map(|a| a.target_path.iter().map(|part| part.kind().unwrap()).collect())
+			.collect();
+		assert!(paths.contains(&vec![TypePathKind::Array]));
+		assert!(paths.contains(&vec![TypePathKind::TypeArgument, TypePathKind::WildcardBound]));
+		assert!(paths.contains(&vec![]));
+	}
+
+	#[test]
+	fn nesting_limit() {
+		let lift = |data: &[u8], max_nesting| {
+			let limits = Limits {
+				max_nesting,
+				..Limits::NONE
+			};
+			IRClassFile::from_io_limited(IOClassFile::read_from_slice(data).unwrap(), &limits)
+		};
+
+		// @HelloAnnoRec(value = @HelloAnno(value = "Hi")) nests one annotation in another
+		lift(HELLO, 1).unwrap();
+		assert!(matches!(
+			lift(HELLO, 0),
+			Err(IRClassfileError::IO(IOClassfileError::LimitExceeded {
+				what: "annotation nesting",
+				value: 1,
+				limit: 0,
+			}))
+		));
+
+		// List<? extends @TypeUse(4) Number> takes two type_path steps
+		lift(TYPED, 2).unwrap();
+		assert!(matches!(
+			lift(TYPED, 1),
+			Err(IRClassfileError::IO(IOClassfileError::LimitExceeded {
+				what: "type_path length",
+				value: 2,
+				limit: 1,
+			}))
+		));
+
+		// the limit only holds for the lift it was given to
+		class(HELLO);
+	}
+}
@@ -0,0 +1,643 @@
+//! Creating classes from scratch and writing method bodies instruction by instruction, see
+//! [`ClassBuilder`] and [`MethodBuilder`].
+
+use maya_classfile_io::IOClassFile;
+
+use crate::{
+	access::{ClassAccessFlags, FieldAccessFlags, MethodAccessFlags},
+	attribute::{CodeAttribute, IRAttribute, IRAttributeInfo, VerificationTypeInfo},
+	class_pool::{CPClassRef, CPTagRef, CPUtf8Ref, ConstantPool, IRClassfileError, IRCpTag, Shared},
+	code::{descriptor_slots, Instructions, Opcodes},
+	insn::{FrameValue, Insn, InsnList, Label, TryCatchBlock},
+	intern_constant, ClassFileVersion, IRClassFile, IRFieldInfo, IRMethodInfo,
+};
+
+/// Builds a class that didn't come from a class file. It starts out as a public class extending
+/// `java/lang/Object` with version 52 (Java 8), and no members.
+#[derive(Debug)]
+pub struct ClassBuilder {
+	class: IRClassFile,
+}
+
+impl ClassBuilder {
+	/// `name` is the class's internal name, like `a/b/Main`.
+	pub fn new(name: &str) -> Self {
+		let mut cp = ConstantPool::new();
+		let this_class = class_entry(&mut cp, name);
+		let super_class = class_entry(&mut cp, "java/lang/Object");
+		Self {
+			class: IRClassFile {
+				magic: 0xCAFEBABE,
+				version: ClassFileVersion { major: 52, minor: 0 },
+				cp,
+				access_flags: ClassAccessFlags::PUBLIC | ClassAccessFlags::SUPER,
+				this_class,
+				super_class,
+				interfaces: Vec::new(),
+				fields: Vec::new(),
+				methods: Vec::new(),
+				attributes: Vec::new(),
+			},
+		}
+	}
+
+	/// The class so far, for interning entries and anything else the builder has no helper for.
+	pub fn class(&mut self) -> &mut IRClassFile {
+		&mut self.class
+	}
+
+	pub fn version(&mut self, version: ClassFileVersion) -> &mut Self {
+		self.class.version = version;
+		self
+	}
+
+	pub fn access_flags(&mut self, access_flags: ClassAccessFlags) -> &mut Self {
+		self.class.access_flags = access_flags;
+		self
+	}
+
+	pub fn super_class(&mut self, name: &str) -> Result<&mut Self, IRClassfileError> {
+		self.class.set_super_class(name)?;
+		Ok(self)
+	}
+
+	pub fn interface(&mut self, name: &str) -> Result<&mut Self, IRClassfileError> {
+		self.class.add_interface(name)?;
+		Ok(self)
+	}
+
+	/// Declares a field, returned so attributes like ConstantValue can be added to it.
+	pub fn field(
+		&mut self,
+		access_flags: FieldAccessFlags,
+		name: &str,
+		descriptor: &str,
+	) -> Result<&mut IRFieldInfo, IRClassfileError> {
+		self.class.add_field(access_flags, name, descriptor, Vec::new())
+	}
+
+	/// Starts a method with a body, see [`IRClassFile::build_method`].
+	pub fn method(
+		&mut self,
+		access_flags: MethodAccessFlags,
+		name: &str,
+		descriptor: &str,
+	) -> Result<MethodBuilder<'_>, IRClassfileError> {
+		self.class.build_method(access_flags, name, descriptor)
+	}
+
+	/// Declares a method without a body, for abstract and native methods.
+	pub fn declare_method(
+		&mut self,
+		access_flags: MethodAccessFlags,
+		name: &str,
+		descriptor: &str,
+	) -> Result<&mut IRMethodInfo, IRClassfileError> {
+		self.class.add_method(access_flags, name, descriptor, Vec::new())
+	}
+
+	/// Adds a class attribute, which must already point into the class's pool.
+	pub fn attribute(&mut self, attribute: IRAttributeInfo) -> &mut Self {
+		self.class.attributes.push(attribute);
+		self
+	}
+
+	pub fn build(self) -> IRClassFile {
+		self.class
+	}
+
+	pub fn build_io(self) -> Result<IOClassFile, IRClassfileError> {
+		self.class.to_io()
+	}
+}
+
+/// Appends a Utf8 entry and a Class entry for `name` to a pool that can't be full yet.
+fn class_entry(cp: &mut ConstantPool, name: &str) -> CPClassRef {
+	let name = cp.push(IRCpTag::Utf8(Shared::new(name.to_string()))).unwrap();
+	let utf8 = CPUtf8Ref::from_cp(cp, name);
+	let class = cp.push(IRCpTag::Class(utf8)).unwrap();
+	CPClassRef::from_cp(cp, class)
+}
+
+/// Builds the body of a new method of a class, adding the constant pool entries its instructions
+/// refer to as it goes. [`finish`](Self::finish) lays the code out, works out `max_stack` and
+/// `max_locals` and adds the method to the class.
+///
+/// Stack map frames aren't computed, except for handlers started with [`handler`](Self::handler),
+/// code that branches has to add them with [`frame`](Self::frame) for class files from version 50
+/// on.
+pub struct MethodBuilder<'a> {
+	class: &'a mut IRClassFile,
+	access_flags: MethodAccessFlags,
+	name: String,
+	descriptor: String,
+	insns: InsnList,
+}
+
+/// A protected range of a method being built, see [`MethodBuilder::begin_try`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TryRegion {
+	pub start: Label,
+	pub end: Label,
+}
+
+impl IRClassFile {
+	/// Starts building a method, erroring if one with the same name and descriptor exists.
+	pub fn build_method(
+		&mut self,
+		access_flags: MethodAccessFlags,
+		name: &str,
+		descriptor: &str,
+	) -> Result<MethodBuilder<'_>, IRClassfileError> {
+		if self.find_method(name, descriptor).is_some() {
+			return Err(IRClassfileError::Duplicate {
+				what: "method",
+				name: format!("{name}{descriptor}"),
+			});
+		}
+		let is_static = access_flags.contains(MethodAccessFlags::STATIC);
+		let initial_locals = self.entry_locals(is_static, name, descriptor)?;
+		Ok(MethodBuilder {
+			class: self,
+			access_flags,
+			name: name.to_string(),
+			descriptor: descriptor.to_string(),
+			insns: InsnList::new(initial_locals),
+		})
+	}
+}
+
+impl<'a> MethodBuilder<'a> {
+	/// The class the method is being added to, for interning entries the builder has no helper for.
+	pub fn class(&mut self) -> &mut IRClassFile {
+		self.class
+	}
+
+	/// The instructions so far.
+	pub fn insns(&mut self) -> &mut InsnList {
+		&mut self.insns
+	}
+
+	pub fn new_label(&mut self) -> Label {
+		self.insns.new_label()
+	}
+
+	/// Places `label` before the next instruction.
+	pub fn label(&mut self, label: Label) -> &mut Self {
+		self.insns.insns.push(Insn::Label(label));
+		self
+	}
+
+	/// Attributes the next instructions to source line `line`.
+	pub fn line(&mut self, line: u16) -> &mut Self {
+		self.insns.insns.push(Insn::Line(line));
+		self
+	}
+
+	/// The stack map frame of the next instruction.
+	pub fn frame(&mut self, locals: Vec<FrameValue>, stack: Vec<FrameValue>) -> &mut Self {
+		self.insns.insns.push(Insn::Frame { locals, stack });
+		self
+	}
+
+	/// Appends an instruction that doesn't branch, see [`Insn::Op`].
+	pub fn op(&mut self, instruction: Instructions) -> &mut Self {
+		self.insns.insns.push(Insn::Op(instruction));
+		self
+	}
+
+	/// Appends a branch to `target`, see [`Insn::Jump`].
+	pub fn jump(&mut self, opcode: u8, target: Label) -> &mut Self {
+		self.insns.insns.push(Insn::Jump { opcode, target });
+		self
+	}
+
+	pub fn goto(&mut self, target: Label) -> &mut Self {
+		self.jump(Opcodes::GOTO, target)
+	}
+
+	pub fn table_switch(&mut self, default: Label, low: i32, targets: Vec<Label>) -> &mut Self {
+		self.insns.insns.push(Insn::TableSwitch { default, low, targets });
+		self
+	}
+
+	pub fn lookup_switch(&mut self, default: Label, pairs: Vec<(i32, Label)>) -> &mut Self {
+		self.insns.insns.push(Insn::LookupSwitch { default, pairs });
+		self
+	}
+
+	/// Protects `start` up to `end` with the handler at `handler`, for exceptions of class
+	/// `exception` or anything if it's `None`.
+	pub fn try_catch(
+		&mut self,
+		start: Label,
+		end: Label,
+		handler: Label,
+		exception: Option<&str>,
+	) -> Result<&mut Self, IRClassfileError> {
+		let catch_type = match exception {
+			Some(exception) => self.class.intern_class(exception)?.index,
+			None => 0,
+		};
+		self.insns.try_catch_blocks.push(TryCatchBlock {
+			start,
+			end,
+			handler,
+			catch_type,
+		});
+		Ok(self)
+	}
+
+	/// Starts a protected range before the next instruction, which goes up to
+	/// [`end_try`](Self::end_try).
+	pub fn begin_try(&mut self) -> TryRegion {
+		let region = TryRegion {
+			start: self.new_label(),
+			end: self.new_label(),
+		};
+		self.label(region.start);
+		region
+	}
+
+	/// Ends `region` before the next instruction.
+	pub fn end_try(&mut self, region: TryRegion) -> &mut Self {
+		self.label(region.end)
+	}
+
+	/// Starts a handler before the next instruction for what `region`, which has to be ended, throws
+	/// of class `exception`, or anything if it's `None`. For class files from version 50 on it gets a
+	/// frame with the exception on the stack and the locals that keep their type all through the
+	/// region, which is worked out from the code and the frames in it.
+	pub fn handler(&mut self, region: TryRegion, exception: Option<&str>) -> Result<&mut Self, IRClassfileError> {
+		if !self
+			.insns
+			.insns
+			.iter()
+			.any(|insn| matches!(insn, Insn::Label(label) if *label == region.end))
+		{
+			return Err(IRClassfileError::UnplacedLabel(region.end));
+		}
+		let handler = self.new_label();
+		self.label(handler);
+		if self.class.version.major >= 50 {
+			let locals = self
+				.insns
+				.handler_locals(&mut self.class.cp, region.start, region.end)
+				.ok_or(IRClassfileError::HandlerFrame(handler))?;
+			let class = self.class.intern_class(exception.unwrap_or("java/lang/Throwable"))?;
+			let stack = vec![FrameValue::Type(VerificationTypeInfo::ObjectVariableInfo {
+				cpool_idx: class.index,
+			})];
+			self.frame(locals, stack);
+		}
+		self.try_catch(region.start, region.end, handler, exception)
+	}
+
+	/// Pushes `value` with the shortest instruction that can: `iconst_*`, `bipush`, `sipush` or
+	/// `ldc`.
+	pub fn push_int(&mut self, value: i32) -> Result<&mut Self, IRClassfileError> {
+		let instruction = match value {
+			-1 => Instructions::ICONST_M1,
+			0 => Instructions::ICONST_0,
+			1 => Instructions::ICONST_1,
+			2 => Instructions::ICONST_2,
+			3 => Instructions::ICONST_3,
+			4 => Instructions::ICONST_4,
+			5 => Instructions::ICONST_5,
+			_ => match (i8::try_from(value), i16::try_from(value)) {
+				(Ok(value), _) => Instructions::BIPUSH(value),
+				(_, Ok(value)) => Instructions::SIPUSH(value),
+				_ => Instructions::LDC(self.constant(IRCpTag::Integer(value))?),
+			},
+		};
+		Ok(self.op(instruction))
+	}
+
+	pub fn push_long(&mut self, value: i64) -> Result<&mut Self, IRClassfileError> {
+		let instruction = match value {
+			0 => Instructions::LCONST_0,
+			1 => Instructions::LCONST_1,
+			_ => Instructions::LDC2_W(self.constant(IRCpTag::Long(value))?),
+		};
+		Ok(self.op(instruction))
+	}
+
+	/// Like [`push_int`](Self::push_int), `-0.0` is loaded from the pool.
+	pub fn push_float(&mut self, value: f32) -> Result<&mut Self, IRClassfileError> {
+		let instruction = match value.to_bits() {
+			bits if bits == 0f32.to_bits() => Instructions::FCONST_0,
+			bits if bits == 1f32.to_bits() => Instructions::FCONST_1,
+			bits if bits == 2f32.to_bits() => Instructions::FCONST_2,
+			_ => Instructions::LDC(self.constant(IRCpTag::Float(value))?),
+		};
+		Ok(self.op(instruction))
+	}
+
+	pub fn push_double(&mut self, value: f64) -> Result<&mut Self, IRClassfileError> {
+		let instruction = match value.to_bits() {
+			bits if bits == 0f64.to_bits() => Instructions::DCONST_0,
+			bits if bits == 1f64.to_bits() => Instructions::DCONST_1,
+			_ => Instructions::LDC2_W(self.constant(IRCpTag::Double(value))?),
+		};
+		Ok(self.op(instruction))
+	}
+
+	pub fn push_string(&mut self, value: &str) -> Result<&mut Self, IRClassfileError> {
+		let index = self.class.intern_string(value)?.index;
+		let constant = CPTagRef::from_cp(&self.class.cp, index);
+		Ok(self.op(Instructions::LDC(constant)))
+	}
+
+	/// Pushes the `Class` object of `name`, an internal name or an array descriptor.
+	pub fn push_class(&mut self, name: &str) -> Result<&mut Self, IRClassfileError> {
+		let index = self.class.intern_class(name)?.index;
+		let constant = CPTagRef::from_cp(&self.class.cp, index);
+		Ok(self.op(Instructions::LDC(constant)))
+	}
+
+	pub fn get_static(&mut self, owner: &str, name: &str, descriptor: &str) -> Result<&mut Self, IRClassfileError> {
+		let field = self.class.intern_field_ref(owner, name, descriptor)?;
+		Ok(self.op(Instructions::GETSTATIC(field)))
+	}
+
+	pub fn put_static(&mut self, owner: &str, name: &str, descriptor: &str) -> Result<&mut Self, IRClassfileError> {
+		let field = self.class.intern_field_ref(owner, name, descriptor)?;
+		Ok(self.op(Instructions::PUTSTATIC(field)))
+	}
+
+	pub fn get_field(&mut self, owner: &str, name: &str, descriptor: &str) -> Result<&mut Self, IRClassfileError> {
+		let field = self.class.intern_field_ref(owner, name, descriptor)?;
+		Ok(self.op(Instructions::GETFIELD(field)))
+	}
+
+	pub fn put_field(&mut self, owner: &str, name: &str, descriptor: &str) -> Result<&mut Self, IRClassfileError> {
+		let field = self.class.intern_field_ref(owner, name, descriptor)?;
+		Ok(self.op(Instructions::PUTFIELD(field)))
+	}
+
+	pub fn invoke_virtual(&mut self, owner: &str, name: &str, descriptor: &str) -> Result<&mut Self, IRClassfileError> {
+		let method = self.class.intern_method_ref(owner, name, descriptor, false)?;
+		Ok(self.op(Instructions::INVOKEVIRTUAL(method)))
+	}
+
+	/// `interface` picks an InterfaceMethodref, for private and default methods of interfaces.
+	pub fn invoke_special(
+		&mut self,
+		owner: &str,
+		name: &str,
+		descriptor: &str,
+		interface: bool,
+	) -> Result<&mut Self, IRClassfileError> {
+		let method = self.class.intern_method_ref(owner, name, descriptor, interface)?;
+		Ok(self.op(Instructions::INVOKESPECIAL(method)))
+	}
+
+	/// `interface` picks an InterfaceMethodref, for static methods of interfaces.
+	pub fn invoke_static(
+		&mut self,
+		owner: &str,
+		name: &str,
+		descriptor: &str,
+		interface: bool,
+	) -> Result<&mut Self, IRClassfileError> {
+		let method = self.class.intern_method_ref(owner, name, descriptor, interface)?;
+		Ok(self.op(Instructions::INVOKESTATIC(method)))
+	}
+
+	pub fn invoke_interface(
+		&mut self,
+		owner: &str,
+		name: &str,
+		descriptor: &str,
+	) -> Result<&mut Self, IRClassfileError> {
+		let method = self.class.intern_method_ref(owner, name, descriptor, true)?;
+		// the receiver and the arguments, a leftover from before the JVM worked it out itself
+		let count = (1 + descriptor_slots(descriptor).0).min(u8::MAX as u16) as u8;
+		Ok(self.op(Instructions::INVOKEINTERFACE(method, count)))
+	}
+
+	pub fn new_object(&mut self, class: &str) -> Result<&mut Self, IRClassfileError> {
+		let class = self.class.intern_class(class)?;
+		Ok(self.op(Instructions::NEW(class)))
+	}
+
+	/// `anewarray` of the component type `class`.
+	pub fn new_array(&mut self, class: &str) -> Result<&mut Self, IRClassfileError> {
+		let class = self.class.intern_class(class)?;
+		Ok(self.op(Instructions::ANEWARRAY(class)))
+	}
+
+	pub fn checkcast(&mut self, class: &str) -> Result<&mut Self, IRClassfileError> {
+		let class = self.class.intern_class(class)?;
+		Ok(self.op(Instructions::CHECKCAST(class)))
+	}
+
+	pub fn instanceof(&mut self, class: &str) -> Result<&mut Self, IRClassfileError> {
+		let class = self.class.intern_class(class)?;
+		Ok(self.op(Instructions::INSTANCEOF(class)))
+	}
+
+	/// Creates an array of the array type `descriptor` from the `dimensions` lengths on the stack.
+	pub fn new_multi_array(&mut self, descriptor: &str, dimensions: u8) -> Result<&mut Self, IRClassfileError> {
+		let class = self.class.intern_class(descriptor)?;
+		Ok(self.op(Instructions::MULTIANEWARRAY(class, dimensions)))
+	}
+
+	/// Lays the code out into a Code attribute and adds the method to the class.
+	pub fn finish(self) -> Result<&'a mut IRMethodInfo, IRClassfileError> {
+		let mut code = CodeAttribute {
+			max_stack: self.insns.max_stack()?,
+			max_locals: self.insns.max_locals(),
+			code: Vec::new(),
+			exception_table: Vec::new(),
+			attributes: Vec::new(),
+		};
+		self.insns.write_to(&mut self.class.cp, &mut code)?;
+		let attribute = IRAttributeInfo {
+			name: self.class.intern_utf8("Code")?,
+			length: 0,
+			attr: IRAttribute::Code(code),
+		};
+		self.class
+			.add_method(self.access_flags, &self.name, &self.descriptor, vec![attribute])
+	}
+
+	fn constant(&mut self, constant: IRCpTag) -> Result<CPTagRef, IRClassfileError> {
+		let index = intern_constant(&mut self.class.cp, constant)?;
+		Ok(CPTagRef::from_cp(&self.class.cp, index))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::test_util::{class, find_attr, CLASSES, HELLO};
+
+	#[test]
+	fn try_regions() {
+		let mut builder = ClassBuilder::new("gen/Guarded");
+		let mut f = builder
+			.method(MethodAccessFlags::PUBLIC | MethodAccessFlags::STATIC, "f", "(I)I")
+			.unwrap();
+		let after = f.new_label();
+		f.op(Instructions::ICONST_0).op(Instructions::ISTORE(1));
+		let region = f.begin_try();
+		f.op(Instructions::BIPUSH(100))
+			.op(Instructions::ILOAD(0))
+			.op(Instructions::IDIV)
+			.op(Instructions::ISTORE(1))
+			.push_string("done")
+			.unwrap()
+			.op(Instructions::ASTORE(2));
+		assert!(matches!(
+			f.handler(region, None),
+			Err(IRClassfileError::UnplacedLabel(label)) if label == region.end
+		));
+		f.end_try(region).goto(after);
+		f.handler(region, Some("java/lang/ArithmeticException"))
+			.unwrap()
+			.op(Instructions::POP)
+			.op(Instructions::ICONST_M1)
+			.op(Instructions::ISTORE(1));
+		let int = FrameValue::Type(VerificationTypeInfo::IntegerVariableInfo);
+		f.label(after)
+			.frame(vec![int.clone(), int.clone()], Vec::new())
+			.op(Instructions::ILOAD(1))
+			.op(Instructions::IRETURN);
+		let insns = f.insns().insns.clone();
+		f.finish().unwrap();
+
+		let arithmetic = builder
+			.class()
+			.intern_class("java/lang/ArithmeticException")
+			.unwrap()
+			.index;
+		let frame = insns
+			.iter()
+			.find_map(|insn| match insn {
+				Insn::Frame { locals, stack } if !stack.is_empty() => Some((locals, stack)),
+				_ => None,
+			})
+			.unwrap();
+		assert_eq!(frame.0, &[int.clone(), int]);
+		assert_eq!(
+			frame.1,
+			&[FrameValue::Type(VerificationTypeInfo::ObjectVariableInfo {
+				cpool_idx: arithmetic
+			})]
+		);
+		let class = builder.build();
+		let IRAttribute::Code(code) = find_attr(&class.find_method("f", "(I)I").unwrap().attributes, "Code") else {
+			unreachable!();
+		};
+		assert_eq!(code.exception_table.len(), 1);
+		assert_eq!(code.exception_table[0].catch_type, arithmetic);
+	}
+	#[test]
+	fn class_builder() {
+		let mut builder = ClassBuilder::new("gen/Main");
+		builder.interface("java/lang/Runnable").unwrap();
+		builder
+			.field(FieldAccessFlags::PRIVATE | FieldAccessFlags::STATIC, "count", "I")
+			.unwrap();
+		let mut init = builder.method(MethodAccessFlags::PUBLIC, "<init>", "()V").unwrap();
+		init.op(Instructions::ALOAD(0))
+			.invoke_special("java/lang/Object", "<init>", "()V", false)
+			.unwrap()
+			.op(Instructions::RETURN);
+		init.finish().unwrap();
+		let mut main = builder
+			.method(
+				MethodAccessFlags::PUBLIC | MethodAccessFlags::STATIC,
+				"main",
+				"([Ljava/lang/String;)V",
+			)
+			.unwrap();
+		main.get_static("java/lang/System", "out", "Ljava/io/PrintStream;")
+			.unwrap()
+			.push_string("Hello World")
+			.unwrap()
+			.invoke_virtual("java/io/PrintStream", "println", "(Ljava/lang/String;)V")
+			.unwrap()
+			.op(Instructions::RETURN);
+		main.finish().unwrap();
+		builder
+			.declare_method(MethodAccessFlags::PUBLIC | MethodAccessFlags::ABSTRACT, "run", "()V")
+			.unwrap();
+
+		let bytes = builder.build_io().unwrap().to_bytes().unwrap();
+		let class = class(&bytes);
+		assert_eq!(class.class_name(), "gen/Main");
+		assert_eq!(class.super_class.data.data.as_str(), "java/lang/Object");
+		assert_eq!(class.interface_names().collect::<Vec<_>>(), ["java/lang/Runnable"]);
+		assert_eq!(class.fields[0].name(), "count");
+		assert_eq!(class.methods.len(), 3);
+		let IRAttribute::Code(code) = find_attr(&class.find_method("<init>", "()V").unwrap().attributes, "Code") else {
+			unreachable!();
+		};
+		assert_eq!((code.max_stack, code.max_locals), (1, 1));
+		assert_eq!(code.code, [0x2A, 0xB7, 0, 12, 0xB1]);
+	}
+
+	#[test]
+	fn computed_limits() {
+		// the computed limits match javac's
+		for bytes in CLASSES {
+			let mut class = class(bytes);
+			let methods = class
+				.methods
+				.iter()
+				.map(|method| (method.name().to_string(), method.descriptor().to_string()))
+				.collect::<Vec<_>>();
+			for (name, descriptor) in &methods {
+				let Some(insns) = class.method_insns(name, descriptor).unwrap() else {
+					continue;
+				};
+				let IRAttribute::Code(code) =
+					find_attr(&class.find_method(name, descriptor).unwrap().attributes, "Code")
+				else {
+					unreachable!();
+				};
+				assert_eq!(insns.max_stack().unwrap(), code.max_stack, "{name}{descriptor}");
+				assert_eq!(insns.max_locals(), code.max_locals, "{name}{descriptor}");
+			}
+		}
+	}
+
+	#[test]
+	fn method_builder() {
+		let mut class = class(HELLO);
+		let cp_len = class.cp.len();
+		let mut builder = class.build_method(MethodAccessFlags::STATIC, "greet", "(JI)V").unwrap();
+		builder
+			.get_static("java/lang/System", "out", "Ljava/io/PrintStream;")
+			.unwrap()
+			.push_string("Hello World!")
+			.unwrap()
+			.invoke_virtual("java/io/PrintStream", "println", "(Ljava/lang/String;)V")
+			.unwrap()
+			.push_int(100)
+			.unwrap()
+			.push_int(1000)
+			.unwrap()
+			.push_int(100000)
+			.unwrap()
+			.push_long(7)
+			.unwrap()
+			.op(Instructions::RETURN);
+		let method = builder.finish().unwrap();
+		let IRAttribute::Code(code) = find_attr(&method.attributes, "Code") else {
+			unreachable!();
+		};
+		assert_eq!((code.max_stack, code.max_locals), (5, 3));
+		// System.out, println and the string were already there, 100000, 7 and the method's name
+		// and descriptor are added
+		assert_eq!(code.code[..9], [0xB2, 0, 7, 0x12, 15, 0xB6, 0, 17, 0x10]);
+		assert_eq!(code.code[9..13], [100, 0x11, 0x03, 0xE8]);
+		assert_eq!(class.cp.len(), cp_len + 5);
+		assert!(matches!(
+			class.build_method(MethodAccessFlags::STATIC, "greet", "(JI)V"),
+			Err(IRClassfileError::Duplicate { .. })
+		));
+	}
+}
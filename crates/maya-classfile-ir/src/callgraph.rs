@@ -0,0 +1,259 @@
+//! Which methods call which across a set of classes, see [`CallGraph`].
+
+use std::{
+	collections::{BTreeSet, HashSet},
+	fmt::{self, Write},
+	io::Cursor,
+};
+
+use crate::{attribute::IRAttribute, class_pool::IRClassfileError, code::Instructions, IRClassFile};
+
+/// A method by the internal name of its class, its name and its descriptor.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct MethodId {
+	pub owner: String,
+	pub name: String,
+	pub descriptor: String,
+}
+
+impl MethodId {
+	pub fn new(owner: &str, name: &str, descriptor: &str) -> Self {
+		Self {
+			owner: owner.to_string(),
+			name: name.to_string(),
+			descriptor: descriptor.to_string(),
+		}
+	}
+}
+
+impl fmt::Display for MethodId {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{}.{}:{}", self.owner, self.name, self.descriptor)
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CallKind {
+	Virtual,
+	Special,
+	Static,
+	Interface,
+	/// The implementation of a lambda or method reference made by an `invokedynamic`, which runs
+	/// when the functional interface's method is called, counted as called where it's made.
+	Lambda,
+	/// The bootstrap method of any other `invokedynamic`, which picks what it calls. String
+	/// concatenation calls nothing.
+	Bootstrap,
+}
+
+impl CallKind {
+	fn name(self) -> &'static str {
+		match self {
+			Self::Virtual => "virtual",
+			Self::Special => "special",
+			Self::Static => "static",
+			Self::Interface => "interface",
+			Self::Lambda => "lambda",
+			Self::Bootstrap => "bootstrap",
+		}
+	}
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Call {
+	pub caller: MethodId,
+	/// The method the instruction names, not what it dispatches to.
+	pub callee: MethodId,
+	pub kind: CallKind,
+	/// Offset of the instruction in the caller's code.
+	pub pc: u16,
+}
+
+/// The calls made by the code of a set of classes.
+#[derive(Debug, Clone, Default)]
+pub struct CallGraph {
+	declared: HashSet<MethodId>,
+	calls: Vec<Call>,
+}
+
+impl CallGraph {
+	/// Decodes the code of every method of `classes`.
+	pub fn build<'a>(classes: impl IntoIterator<Item = &'a IRClassFile>) -> Result<Self, IRClassfileError> {
+		let mut graph = Self::default();
+		for class in classes {
+			graph.add_class(class)?;
+		}
+		Ok(graph)
+	}
+
+	pub fn add_class(&mut self, class: &IRClassFile) -> Result<(), IRClassfileError> {
+		for method in &class.methods {
+			let caller = MethodId::new(class.class_name(), method.name(), method.descriptor());
+			self.declared.insert(caller.clone());
+			let code = method.attributes.iter().find_map(|attribute| match &attribute.attr {
+				IRAttribute::Code(code) => Some(code),
+				_ => None,
+			});
+			let Some(code) = code else {
+				continue;
+			};
+
+			let mut buffer = Cursor::new(&code.code);
+			while (buffer.position() as usize) < code.code.len() {
+				let pc = buffer.position() as u16;
+				let (callee, kind) = match Instructions::read(&class.cp, &mut buffer)? {
+					Instructions::INVOKEVIRTUAL(method) => (method, CallKind::Virtual),
+					Instructions::INVOKESPECIAL(method) => (method, CallKind::Special),
+					Instructions::INVOKESTATIC(method) => (method, CallKind::Static),
+					Instructions::INVOKEINTERFACE(method, _) => (method, CallKind::Interface),
+					Instructions::INVOKEDYNAMIC(call_site) => {
+						let Some(call_site) = class.call_site(call_site.index) else {
+							continue;
+						};
+						let (handle, kind) = match call_site.lambda() {
+							Some(lambda) => (lambda.implementation, CallKind::Lambda),
+							None if call_site.string_concat().is_some() => continue,
+							None => (&call_site.bootstrap.method, CallKind::Bootstrap),
+						};
+						self.calls.push(Call {
+							caller: caller.clone(),
+							callee: MethodId::new(
+								&handle.owner.data.data,
+								&handle.name_and_ty.name.data,
+								&handle.name_and_ty.ty.data,
+							),
+							kind,
+							pc,
+						});
+						continue;
+					}
+					_ => continue,
+				};
+				self.calls.push(Call {
+					caller: caller.clone(),
+					callee: MethodId::new(
+						&callee.class.data.data,
+						&callee.name_and_ty.name.data,
+						&callee.name_and_ty.ty.data,
+					),
+					kind,
+					pc,
+				});
+			}
+		}
+		Ok(())
+	}
+
+	/// Whether `method` is one of the classes' own, rather than only called by them.
+	pub fn is_declared(&self, method: &MethodId) -> bool {
+		self.declared.contains(method)
+	}
+
+	/// Every call, in the order of the classes and their code.
+	pub fn calls(&self) -> &[Call] {
+		&self.calls
+	}
+
+	/// The calls `method` makes.
+	pub fn callees<'a>(&'a self, method: &'a MethodId) -> impl Iterator<Item = &'a Call> + 'a {
+		self.calls.iter().filter(move |call| call.caller == *method)
+	}
+
+	/// The calls to `method`.
+	pub fn callers<'a>(&'a self, method: &'a MethodId) -> impl Iterator<Item = &'a Call> + 'a {
+		self.calls.iter().filter(move |call| call.callee == *method)
+	}
+
+	/// The methods `roots` call, directly or not, and `roots` themselves.
+	pub fn reachable_from<'a>(&'a self, roots: impl IntoIterator<Item = &'a MethodId>) -> BTreeSet<&'a MethodId> {
+		let mut reachable = BTreeSet::new();
+		let mut pending = roots.into_iter().collect::<Vec<_>>();
+		while let Some(method) = pending.pop() {
+			if reachable.insert(method) {
+				pending.extend(self.callees(method).map(|call| &call.callee));
+			}
+		}
+		reachable
+	}
+
+	/// The graph in Graphviz's DOT language, an edge for each caller, callee and kind of call.
+	pub fn to_dot(&self) -> String {
+		let quote =
+			|method: &MethodId| format!("\"{}\"", method.to_string().replace('\\', "\\\\").replace('"', "\\\""));
+		let mut out = String::from("digraph calls {\n");
+		let mut seen = HashSet::new();
+		for call in &self.calls {
+			if seen.insert((&call.caller, &call.callee, call.kind)) {
+				// writing to a String can't fail
+				let _ = writeln!(
+					out,
+					"\t{} -> {} [label=\"{}\"];",
+					quote(&call.caller),
+					quote(&call.callee),
+					call.kind.name()
+				);
+			}
+		}
+		out.push_str("}\n");
+		out
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::test_util::{class, HELLO, PAIR, TYPED};
+
+	#[test]
+	fn call_graph() {
+		let classes = [HELLO, PAIR, TYPED].map(class);
+		let graph = CallGraph::build(&classes).unwrap();
+		let stackmapper = MethodId::new("a/Hello", "stackmapper", "(ILjava/lang/Object;)V");
+		let lambda = MethodId::new("a/Hello", "lambda$stackmapper$0", "()Ljava/lang/String;");
+		assert!(graph.is_declared(&stackmapper));
+		assert!(!graph.is_declared(&MethodId::new("java/lang/Object", "<init>", "()V")));
+		let callees = graph
+			.callees(&stackmapper)
+			.map(|call| (call.callee.to_string(), call.kind, call.pc))
+			.collect::<Vec<_>>();
+		assert_eq!(
+			callees,
+			[
+				(lambda.to_string(), CallKind::Lambda, 33),
+				("a/Hello$1.<init>:()V".to_string(), CallKind::Special, 44),
+			]
+		);
+		assert_eq!(
+			graph.callers(&lambda).map(|call| &call.caller).collect::<Vec<_>>(),
+			[&stackmapper]
+		);
+
+		// record methods are made by their bootstrap method and string concatenation calls nothing
+		let to_string = MethodId::new("b/api/Pair", "toString", "()Ljava/lang/String;");
+		let call = graph.callees(&to_string).next().unwrap();
+		assert_eq!(call.kind, CallKind::Bootstrap);
+		assert_eq!(call.callee.owner, "java/lang/runtime/ObjectMethods");
+		assert!(graph
+			.calls()
+			.iter()
+			.all(|call| call.callee.owner != "java/lang/invoke/StringConcatFactory"));
+
+		let reachable = graph.reachable_from([&stackmapper]);
+		assert_eq!(reachable.len(), 3);
+		assert!(reachable.contains(&lambda));
+		let typed = MethodId::new("b/internal/Typed", "method", "(I)Ljava/lang/String;");
+		let reachable = graph.reachable_from([&typed]);
+		assert!(reachable.contains(&MethodId::new(
+			"java/lang/String",
+			"valueOf",
+			"(Ljava/lang/Object;)Ljava/lang/String;"
+		)));
+
+		let dot = graph.to_dot();
+		assert!(dot.starts_with("digraph calls {\n"));
+		assert!(dot.contains(
+			"\t\"a/Hello.stackmapper:(ILjava/lang/Object;)V\" -> \"a/Hello.lambda$stackmapper$0:()Ljava/lang/String;\" [label=\"lambda\"];\n"
+		));
+		assert_eq!(dot.lines().count(), graph.calls().len() + 2);
+	}
+}
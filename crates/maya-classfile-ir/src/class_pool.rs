@@ -1,10 +1,23 @@
-use std::{rc::Rc, string::FromUtf8Error};
+use std::{
+	fmt,
+	ops::{Deref, DerefMut},
+	string::FromUtf8Error,
+};
 
 use maya_bytes::BytesError;
-use maya_classfile_io::class_pool::IOCpTag;
+use maya_classfile_io::{class_pool::IOCpTag, pool_builder::ConstantPoolBuilder, IOClassfileError};
 use maya_mutf8::MUTFError;
 use thiserror::Error;
 
+use crate::{attribute::BootstrapMethodsMethod, interner::StringInterner, IRClassFile};
+
+/// The shared pointer behind constant pool strings, `Arc` with the `sync` feature so the IR is
+/// `Send + Sync`, `Rc` otherwise.
+#[cfg(not(feature = "sync"))]
+pub type Shared<T> = std::rc::Rc<T>;
+#[cfg(feature = "sync")]
+pub type Shared<T> = std::sync::Arc<T>;
+
 #[derive(Debug, Error)]
 pub enum IRClassfileError {
 	#[error("{0}")]
@@ -13,10 +26,41 @@ pub enum IRClassfileError {
 	Bytes(#[from] BytesError),
 	#[error("{0}")]
 	Utf8(#[from] FromUtf8Error),
+	#[error("{0}")]
+	IO(#[from] IOClassfileError),
+	#[error("too many {what} for the classfile format: {actual}")]
+	TooMany { what: &'static str, actual: usize },
+	#[error("invalid element_value tag {0:#04x}")]
+	InvalidAnnotationTag(u8),
+	#[error("invalid type annotation target_type {0:#04x}")]
+	InvalidTargetType(u8),
+	#[error("invalid opcode {opcode:#04x} at pc {pc}")]
+	InvalidOpcode { opcode: u8, pc: usize },
+	#[error("unknown opcode {0:#04x}")]
+	UnknownOpcode(u8),
+	#[error("offset {0} isn't the start of an instruction")]
+	NotAnInstruction(usize),
+	#[error("{0:?} is used but never placed")]
+	UnplacedLabel(crate::insn::Label),
+	#[error("the frame of the handler at {0:?} can't be worked out")]
+	HandlerFrame(crate::insn::Label),
+	#[error("the branch at pc {pc} can't reach {target}")]
+	BranchTooFar { pc: usize, target: usize },
+	#[error("{0}")]
+	StackMap(#[from] crate::stackmap::StackMapError),
+	#[error("instruction at pc {0} runs past the end of the code")]
+	TruncatedCode(usize),
+	#[error("can't renumber constant pool indices inside the unparsed {0} attribute")]
+	OpaqueAttribute(String),
+	#[error("{what} {name} is already declared")]
+	Duplicate { what: &'static str, name: String },
+	#[error("invalid descriptor {0}")]
+	InvalidDescriptor(String),
 }
 
 // https://docs.oracle.com/javase/specs/jvms/se7/html/jvms-5.html#jvms-5.4.3.5
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(u8)]
 pub enum IRMethodRefKind {
 	GetField = 1,
@@ -48,15 +92,17 @@ impl IRMethodRefKind {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum CPConstValueRefKind {
 	Double(f64),
 	Float(f32),
 	Int(i32),
 	Long(i64),
-	String(Rc<String>),
+	String(Shared<String>),
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CPConstValueRef {
 	pub index: u16,
 	pub kind: CPConstValueRefKind,
@@ -96,8 +142,9 @@ impl CPConstValueRef {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CPUtf8Ref {
-	pub data: Rc<String>,
+	pub data: Shared<String>,
 	pub index: u16,
 }
 
@@ -119,6 +166,7 @@ impl CPUtf8Ref {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CPClassRef {
 	pub data: CPUtf8Ref,
 	pub index: u16,
@@ -142,6 +190,31 @@ impl CPClassRef {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CPStringRef {
+	pub data: CPUtf8Ref,
+	pub index: u16,
+}
+
+impl CPStringRef {
+	pub fn new(index: u16, utf8_tag: &IRCpTag) -> Self {
+		match utf8_tag {
+			IRCpTag::String(this) => Self {
+				data: this.clone(),
+				index,
+			},
+			_ => panic!("trying to make CPStringRef from non-String tag. {utf8_tag:?}"),
+		}
+	}
+
+	pub fn from_cp(cp: &[IRCpTag], index: u16) -> Self {
+		let tag = cp.get(index as usize - 1).expect("expected tag");
+		Self::new(index, tag)
+	}
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CPNameAndTypeRef {
 	pub index: u16,
 	pub name: CPUtf8Ref,
@@ -168,6 +241,7 @@ impl CPNameAndTypeRef {
 
 // https://docs.oracle.com/javase/specs/jvms/se7/html/jvms-4.html#jvms-4.4.8
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CPMethodHandleRef {
 	pub ref_kind: IRMethodRefKind,
 	pub ref_tag: Box<IRCpTag>,
@@ -199,6 +273,7 @@ impl CPMethodHandleRef {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CPModuleInfoRef {
 	pub data: CPUtf8Ref,
 	pub index: u16,
@@ -222,6 +297,7 @@ impl CPModuleInfoRef {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CPPackageInfoRef {
 	pub data: CPUtf8Ref,
 	pub index: u16,
@@ -256,6 +332,7 @@ macro_rules! get_from_cp {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CPFieldRef {
 	pub class: CPClassRef,
 	pub name_and_ty: CPNameAndTypeRef,
@@ -286,7 +363,9 @@ impl CPFieldRef {
 	}
 }
 
+/// A Methodref or, for the calls that allow it, an InterfaceMethodref.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CPMethodRef {
 	pub class: CPClassRef,
 	pub name_and_ty: CPNameAndTypeRef,
@@ -299,6 +378,10 @@ impl CPMethodRef {
 			IRCpTag::MethodRef {
 				class_index,
 				name_and_ty,
+			}
+			| IRCpTag::InterfaceMethodRef {
+				class_index,
+				name_and_ty,
 			} => {
 				let class_tag = cp.get(class_index.saturating_sub(1) as usize).expect("fuck");
 				Self {
@@ -317,7 +400,43 @@ impl CPMethodRef {
 	}
 }
 
+/// A `CONSTANT_Dynamic` constant, loaded with `ldc` and friends or passed as a bootstrap argument.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CPDynamicRef {
+	pub bootstrap_method_attr_index: u16,
+	pub name_and_ty: CPNameAndTypeRef,
+	pub index: u16,
+}
+
+impl CPDynamicRef {
+	pub fn new(index: u16, tag: &IRCpTag) -> Self {
+		match tag {
+			IRCpTag::Dynamic {
+				bootstrap_method_attr_index,
+				name_and_ty,
+			} => Self {
+				bootstrap_method_attr_index: *bootstrap_method_attr_index,
+				name_and_ty: name_and_ty.clone(),
+				index,
+			},
+			_ => panic!("trying to make CPDynamicRef from non-Dynamic tag. {tag:?}"),
+		}
+	}
+
+	pub fn from_cp(cp: &[IRCpTag], index: u16) -> Self {
+		let tag = cp.get(index as usize - 1).expect("expected tag");
+		Self::new(index, tag)
+	}
+
+	/// Looks up the bootstrap method this constant is linked by in `class`.
+	pub fn bootstrap_method<'a>(&self, class: &'a IRClassFile) -> Option<&'a BootstrapMethodsMethod> {
+		class.bootstrap_method(self.bootstrap_method_attr_index)
+	}
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CPInvokeDynamicRef {
 	pub bootstrap_method_attr_index: u16,
 	pub name_and_ty: CPNameAndTypeRef,
@@ -325,7 +444,7 @@ pub struct CPInvokeDynamicRef {
 }
 
 impl CPInvokeDynamicRef {
-	pub fn new(cp: &[IRCpTag], index: u16, utf8_tag: &IRCpTag) -> Self {
+	pub fn new(_cp: &[IRCpTag], index: u16, utf8_tag: &IRCpTag) -> Self {
 		match utf8_tag {
 			IRCpTag::InvokeDynamic {
 				bootstrap_method_attr_index,
@@ -343,9 +462,15 @@ impl CPInvokeDynamicRef {
 		let tag = cp.get(index as usize - 1).expect("expected tag");
 		Self::new(cp, index, tag)
 	}
+
+	/// Looks up the bootstrap method this constant is linked by in `class`.
+	pub fn bootstrap_method<'a>(&self, class: &'a IRClassFile) -> Option<&'a BootstrapMethodsMethod> {
+		class.bootstrap_method(self.bootstrap_method_attr_index)
+	}
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CPTagRef {
 	pub tag: IRCpTag,
 	pub index: u16,
@@ -362,9 +487,12 @@ impl CPTagRef {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(u8)]
 pub enum IRCpTag {
-	Utf8(Rc<String>) = 1,
+	/// Second slot of a Long or Double, see [`IOCpTag::Unusable`].
+	Unusable = 0,
+	Utf8(Shared<String>) = 1,
 	Integer(i32) = 3,
 	Float(f32) = 4,
 	Long(i64) = 5,
@@ -395,6 +523,11 @@ pub enum IRCpTag {
 	} = 15,
 	// https://docs.oracle.com/javase/specs/jvms/se7/html/jvms-4.html#jvms-4.4.9
 	MethodType(CPUtf8Ref) = 16,
+	// https://docs.oracle.com/javase/specs/jvms/se21/html/jvms-4.html#jvms-4.4.10
+	Dynamic {
+		bootstrap_method_attr_index: u16,
+		name_and_ty: CPNameAndTypeRef,
+	} = 17,
 	// https://docs.oracle.com/javase/specs/jvms/se7/html/jvms-4.html#jvms-4.4.10
 	InvokeDynamic {
 		bootstrap_method_attr_index: u16,
@@ -409,36 +542,48 @@ pub enum IRCpTag {
 }
 
 macro_rules! parse_tag_idx {
-	($idx:ident, $raw_tags:ident, $formed_tags:ident) => {
+	($idx:ident, $raw_tags:ident, $formed_tags:ident, $interner:ident) => {
 		$formed_tags.get(*$idx as usize - 1).cloned().or(Some(Self::parse_tag(
 			&$raw_tags[*$idx as usize - 1],
 			$raw_tags,
 			$formed_tags,
+			$interner.as_deref_mut(),
 		)?))
 	};
 }
 
 impl IRCpTag {
-	fn parse_tag(tag: &IOCpTag, raw_tags: &[IOCpTag], formed_tags: &[IRCpTag]) -> Result<IRCpTag, IRClassfileError> {
+	pub(crate) fn parse_tag(
+		tag: &IOCpTag,
+		raw_tags: &[IOCpTag],
+		formed_tags: &[IRCpTag],
+		mut interner: Option<&mut StringInterner>,
+	) -> Result<IRCpTag, IRClassfileError> {
 		Ok(match tag {
-			IOCpTag::Utf8 { length: _, bytes } => IRCpTag::Utf8(Rc::new(maya_mutf8::decode(bytes)?)),
+			IOCpTag::Unusable => IRCpTag::Unusable,
+			IOCpTag::Utf8 { length: _, bytes } => IRCpTag::Utf8(match interner {
+				Some(interner) => interner.intern_mutf8(bytes)?,
+				None => Shared::new(maya_mutf8::decode(bytes)?),
+			}),
 			IOCpTag::Integer { bytes } => IRCpTag::Integer(i32::from_be_bytes(*bytes)),
 			IOCpTag::Float { bytes } => IRCpTag::Float(f32::from_be_bytes(*bytes)),
 			IOCpTag::Long { bytes } => IRCpTag::Long(i64::from_be_bytes(*bytes)),
 			IOCpTag::Double { bytes } => IRCpTag::Double(f64::from_be_bytes(*bytes)),
 			IOCpTag::Class { name_index } => {
-				let utf8_tag = parse_tag_idx!(name_index, raw_tags, formed_tags).expect("invalid Class name_index");
+				let utf8_tag =
+					parse_tag_idx!(name_index, raw_tags, formed_tags, interner).expect("invalid Class name_index");
 				IRCpTag::Class(CPUtf8Ref::new(*name_index, &utf8_tag))
 			}
 			IOCpTag::String { utf8_index } => {
-				let utf8_tag = parse_tag_idx!(utf8_index, raw_tags, formed_tags).expect("invalid String utf8_index");
+				let utf8_tag =
+					parse_tag_idx!(utf8_index, raw_tags, formed_tags, interner).expect("invalid String utf8_index");
 				IRCpTag::String(CPUtf8Ref::new(*utf8_index, &utf8_tag))
 			}
 			IOCpTag::FieldRef {
 				class_index,
 				name_and_ty_index,
 			} => {
-				let (name, ty) = match parse_tag_idx!(name_and_ty_index, raw_tags, formed_tags)
+				let (name, ty) = match parse_tag_idx!(name_and_ty_index, raw_tags, formed_tags, interner)
 					.expect("invalid FieldRef name_and_ty_index")
 				{
 					IRCpTag::NameAndType { name, descriptor } => (name, descriptor),
@@ -457,7 +602,7 @@ impl IRCpTag {
 				class_index,
 				name_and_ty_index,
 			} => {
-				let (name, ty) = match parse_tag_idx!(name_and_ty_index, raw_tags, formed_tags)
+				let (name, ty) = match parse_tag_idx!(name_and_ty_index, raw_tags, formed_tags, interner)
 					.expect("invalid MethodRef name_and_ty_index")
 				{
 					IRCpTag::NameAndType { name, descriptor } => (name, descriptor),
@@ -476,7 +621,7 @@ impl IRCpTag {
 				class_index,
 				name_and_ty_index,
 			} => {
-				let (name, ty) = match parse_tag_idx!(name_and_ty_index, raw_tags, formed_tags)
+				let (name, ty) = match parse_tag_idx!(name_and_ty_index, raw_tags, formed_tags, interner)
 					.expect("invalid InterfaceMethodRef name_and_ty_index")
 				{
 					IRCpTag::NameAndType { name, descriptor } => (name, descriptor),
@@ -495,9 +640,9 @@ impl IRCpTag {
 				name_index,
 				descriptor_index,
 			} => {
-				let name_tag = parse_tag_idx!(name_index, raw_tags, formed_tags).expect("expected utf8 tag");
+				let name_tag = parse_tag_idx!(name_index, raw_tags, formed_tags, interner).expect("expected utf8 tag");
 				let descriptor_tag =
-					parse_tag_idx!(descriptor_index, raw_tags, formed_tags).expect("expected utf8 tag");
+					parse_tag_idx!(descriptor_index, raw_tags, formed_tags, interner).expect("expected utf8 tag");
 				IRCpTag::NameAndType {
 					name: CPUtf8Ref::new(*name_index, &name_tag),
 					descriptor: CPUtf8Ref::new(*descriptor_index, &descriptor_tag),
@@ -508,7 +653,7 @@ impl IRCpTag {
 				reference_index,
 			} => {
 				let kind = IRMethodRefKind::from(*reference_kind_idx);
-				let tag = parse_tag_idx!(reference_index, raw_tags, formed_tags).expect("expected tag");
+				let tag = parse_tag_idx!(reference_index, raw_tags, formed_tags, interner).expect("expected tag");
 				IRCpTag::MethodHandle {
 					ref_kind: kind,
 					ref_tag: Box::new(tag.clone()),
@@ -516,14 +661,33 @@ impl IRCpTag {
 				}
 			}
 			IOCpTag::MethodType { descriptor_index } => {
-				let tag = parse_tag_idx!(descriptor_index, raw_tags, formed_tags).expect("expected utf8 tag");
+				let tag = parse_tag_idx!(descriptor_index, raw_tags, formed_tags, interner).expect("expected utf8 tag");
 				IRCpTag::MethodType(CPUtf8Ref::new(*descriptor_index, &tag))
 			}
+			IOCpTag::Dynamic {
+				bootstrap_method_attr_index,
+				name_and_ty_index,
+			} => {
+				let (name, ty) = match parse_tag_idx!(name_and_ty_index, raw_tags, formed_tags, interner)
+					.expect("invalid Dynamic name_and_ty_index")
+				{
+					IRCpTag::NameAndType { name, descriptor } => (name, descriptor),
+					t => panic!("expected NameAndType. got {t:?}"),
+				};
+				IRCpTag::Dynamic {
+					bootstrap_method_attr_index: *bootstrap_method_attr_index,
+					name_and_ty: CPNameAndTypeRef {
+						index: *name_and_ty_index,
+						name: name.clone(),
+						ty: ty.clone(),
+					},
+				}
+			}
 			IOCpTag::InvokeDynamic {
 				bootstrap_method_attr_index,
 				name_and_ty_index,
 			} => {
-				let (name, ty) = match parse_tag_idx!(name_and_ty_index, raw_tags, formed_tags)
+				let (name, ty) = match parse_tag_idx!(name_and_ty_index, raw_tags, formed_tags, interner)
 					.expect("invalid InvokeDynamic name_and_ty_index")
 				{
 					IRCpTag::NameAndType { name, descriptor } => (name, descriptor),
@@ -539,13 +703,13 @@ impl IRCpTag {
 				}
 			}
 			IOCpTag::Module { name_index } => {
-				let name_tag = parse_tag_idx!(name_index, raw_tags, formed_tags).expect("expected utf8 tag");
+				let name_tag = parse_tag_idx!(name_index, raw_tags, formed_tags, interner).expect("expected utf8 tag");
 				IRCpTag::Module {
 					name: CPUtf8Ref::new(*name_index, &name_tag),
 				}
 			}
 			IOCpTag::Package { name_index } => {
-				let name_tag = parse_tag_idx!(name_index, raw_tags, formed_tags).expect("expected utf8 tag");
+				let name_tag = parse_tag_idx!(name_index, raw_tags, formed_tags, interner).expect("expected utf8 tag");
 				IRCpTag::Package {
 					name: CPUtf8Ref::new(*name_index, &name_tag),
 				}
@@ -553,14 +717,318 @@ impl IRCpTag {
 		})
 	}
 
+	/// Lowers the entry back to its raw form, every reference keeps the index it was read with.
+	pub fn to_io(&self) -> Result<IOCpTag, IRClassfileError> {
+		Ok(match self {
+			IRCpTag::Unusable => IOCpTag::Unusable,
+			IRCpTag::Utf8(data) => {
+				let bytes = maya_mutf8::encode(data);
+				IOCpTag::Utf8 {
+					length: u16::try_from(bytes.len()).map_err(|_| BytesError::StringTooLong(bytes.len()))?,
+					bytes,
+				}
+			}
+			IRCpTag::Integer(value) => IOCpTag::Integer {
+				bytes: value.to_be_bytes(),
+			},
+			IRCpTag::Float(value) => IOCpTag::Float {
+				bytes: value.to_be_bytes(),
+			},
+			IRCpTag::Long(value) => IOCpTag::Long {
+				bytes: value.to_be_bytes(),
+			},
+			IRCpTag::Double(value) => IOCpTag::Double {
+				bytes: value.to_be_bytes(),
+			},
+			IRCpTag::Class(name) => IOCpTag::Class { name_index: name.index },
+			IRCpTag::String(utf8) => IOCpTag::String { utf8_index: utf8.index },
+			IRCpTag::FieldRef {
+				class_index,
+				name_and_ty,
+			} => IOCpTag::FieldRef {
+				class_index: *class_index,
+				name_and_ty_index: name_and_ty.index,
+			},
+			IRCpTag::MethodRef {
+				class_index,
+				name_and_ty,
+			} => IOCpTag::MethodRef {
+				class_index: *class_index,
+				name_and_ty_index: name_and_ty.index,
+			},
+			IRCpTag::InterfaceMethodRef {
+				class_index,
+				name_and_ty,
+			} => IOCpTag::InterfaceMethodRef {
+				class_index: *class_index,
+				name_and_ty_index: name_and_ty.index,
+			},
+			IRCpTag::NameAndType { name, descriptor } => IOCpTag::NameAndType {
+				name_index: name.index,
+				descriptor_index: descriptor.index,
+			},
+			IRCpTag::MethodHandle {
+				ref_kind,
+				ref_index,
+				ref_tag: _,
+			} => IOCpTag::MethodHandle {
+				reference_kind: ref_kind.clone() as u8,
+				reference_index: *ref_index,
+			},
+			IRCpTag::MethodType(descriptor) => IOCpTag::MethodType {
+				descriptor_index: descriptor.index,
+			},
+			IRCpTag::Dynamic {
+				bootstrap_method_attr_index,
+				name_and_ty,
+			} => IOCpTag::Dynamic {
+				bootstrap_method_attr_index: *bootstrap_method_attr_index,
+				name_and_ty_index: name_and_ty.index,
+			},
+			IRCpTag::InvokeDynamic {
+				bootstrap_method_attr_index,
+				name_and_ty,
+			} => IOCpTag::InvokeDynamic {
+				bootstrap_method_attr_index: *bootstrap_method_attr_index,
+				name_and_ty_index: name_and_ty.index,
+			},
+			IRCpTag::Module { name } => IOCpTag::Module { name_index: name.index },
+			IRCpTag::Package { name } => IOCpTag::Package { name_index: name.index },
+		})
+	}
+
 	pub fn from_io(raw_tags: Vec<IOCpTag>) -> Result<Vec<IRCpTag>, IRClassfileError> {
+		Self::from_io_with(raw_tags, None)
+	}
+
+	/// Like [`IRCpTag::from_io`], taking the strings from `interner`.
+	pub fn from_io_interned(
+		raw_tags: Vec<IOCpTag>,
+		interner: &mut StringInterner,
+	) -> Result<Vec<IRCpTag>, IRClassfileError> {
+		Self::from_io_with(raw_tags, Some(interner))
+	}
+
+	fn from_io_with(
+		raw_tags: Vec<IOCpTag>,
+		mut interner: Option<&mut StringInterner>,
+	) -> Result<Vec<IRCpTag>, IRClassfileError> {
 		let mut res = Vec::with_capacity(raw_tags.len());
 
 		for raw_tag in &raw_tags {
-			let tag = Self::parse_tag(raw_tag, &raw_tags, &res)?;
+			let tag = Self::parse_tag(raw_tag, &raw_tags, &res, interner.as_deref_mut())?;
 			res.push(tag);
 		}
 
 		Ok(res)
 	}
 }
+
+/// The constant pool of an [`IRClassFile`]. Reads go straight to the entries, it derefs to a
+/// `Vec<IRCpTag>`.
+///
+/// Interning goes through a [`ConstantPoolBuilder`] kept next to the entries, so an existing entry
+/// is found by hash. It is set up on the first edit and kept up to date from then on: entries
+/// added with [`push`](Self::push) or [`set`](Self::set) are mirrored as they come, any other
+/// mutable access has it rebuilt on the next edit.
+#[derive(Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(transparent))]
+pub struct ConstantPool {
+	tags: Vec<IRCpTag>,
+	/// Mirrors `tags[..pool.len()]` unless `stale`.
+	#[cfg_attr(feature = "serde", serde(skip))]
+	pool: ConstantPoolBuilder,
+	#[cfg_attr(feature = "serde", serde(skip))]
+	stale: bool,
+}
+
+impl ConstantPool {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Appends `tag` without looking for an equal entry. Long and Double entries need their
+	/// [`IRCpTag::Unusable`] slot pushed after them.
+	pub fn push(&mut self, tag: IRCpTag) -> Result<u16, IRClassfileError> {
+		// the highest usable index is 65534, constant_pool_count is one more than that
+		crate::attribute::count("constant pool entries", self.tags.len() + 2)?;
+		self.tags.push(tag);
+		Ok(self.tags.len() as u16)
+	}
+
+	/// Replaces the entry at `index` with one taking as many slots, e.g. to point a Class entry at
+	/// another name. Fails with [`BadCpIndex`](IOClassfileError::BadCpIndex) if there's no entry at `index`.
+	pub fn set(&mut self, index: u16, tag: IRCpTag) -> Result<(), IRClassfileError> {
+		let slot = (index as usize)
+			.checked_sub(1)
+			.filter(|&slot| slot < self.tags.len())
+			.ok_or(IOClassfileError::BadCpIndex(index))?;
+		if !self.stale && slot < self.pool.len() {
+			if let Err(err) = tag.to_io().and_then(|raw| Ok(self.pool.replace(index, raw)?)) {
+				self.stale = true;
+				return Err(err);
+			}
+		}
+		self.tags[slot] = tag;
+		Ok(())
+	}
+
+	/// Runs `edit` on the [`ConstantPoolBuilder`] over the pool and lifts whatever it appended,
+	/// entries already in the pool keep their index.
+	pub fn edit<T>(
+		&mut self,
+		edit: impl FnOnce(&mut ConstantPoolBuilder) -> Result<T, IOClassfileError>,
+	) -> Result<T, IRClassfileError> {
+		self.sync()?;
+		let result = match edit(&mut self.pool) {
+			Ok(result) => result,
+			Err(err) => {
+				// whatever it appended before failing isn't in `tags`
+				self.stale = true;
+				return Err(err.into());
+			}
+		};
+
+		while self.tags.len() < self.pool.len() {
+			let raw = self.pool.entries();
+			let tag = IRCpTag::parse_tag(&raw[self.tags.len()], raw, &self.tags, None)?;
+			self.tags.push(tag);
+		}
+		Ok(result)
+	}
+
+	pub fn into_vec(self) -> Vec<IRCpTag> {
+		self.tags
+	}
+
+	fn sync(&mut self) -> Result<(), IRClassfileError> {
+		if self.stale || self.pool.len() > self.tags.len() {
+			let raw = self.tags.iter().map(IRCpTag::to_io).collect::<Result<Vec<_>, _>>()?;
+			self.pool = ConstantPoolBuilder::from_pool(raw)?;
+			self.stale = false;
+			return Ok(());
+		}
+
+		for tag in &self.tags[self.pool.len()..] {
+			self.pool.push(tag.to_io()?)?;
+		}
+		Ok(())
+	}
+}
+
+impl From<Vec<IRCpTag>> for ConstantPool {
+	fn from(tags: Vec<IRCpTag>) -> Self {
+		Self {
+			tags,
+			..Self::default()
+		}
+	}
+}
+
+impl Deref for ConstantPool {
+	type Target = Vec<IRCpTag>;
+
+	fn deref(&self) -> &Self::Target {
+		&self.tags
+	}
+}
+
+impl DerefMut for ConstantPool {
+	fn deref_mut(&mut self) -> &mut Self::Target {
+		self.stale = true;
+		&mut self.tags
+	}
+}
+
+impl<'a> IntoIterator for &'a ConstantPool {
+	type Item = &'a IRCpTag;
+	type IntoIter = std::slice::Iter<'a, IRCpTag>;
+
+	fn into_iter(self) -> Self::IntoIter {
+		self.tags.iter()
+	}
+}
+
+impl fmt::Debug for ConstantPool {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		self.tags.fmt(f)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::io::Cursor;
+
+	use maya_classfile_io::pool_builder::ConstantPoolBuilder;
+
+	use super::*;
+	use crate::code::Instructions;
+
+	#[test]
+	fn dynamic_constants() {
+		let mut pool = ConstantPoolBuilder::new();
+		let int = pool.dynamic(0, "answer", "I").unwrap();
+		let long = pool.dynamic(1, "big", "J").unwrap();
+		let raw = pool.build();
+		let cp = IRCpTag::from_io(raw.clone()).unwrap();
+
+		let constant = CPDynamicRef::from_cp(&cp, int);
+		assert_eq!(constant.bootstrap_method_attr_index, 0);
+		assert_eq!(constant.name_and_ty.name.data.as_str(), "answer");
+		assert_eq!(constant.name_and_ty.ty.data.as_str(), "I");
+		assert_eq!(CPDynamicRef::from_cp(&cp, long).bootstrap_method_attr_index, 1);
+		for (tag, raw) in cp.iter().zip(&raw) {
+			assert_eq!(&tag.to_io().unwrap(), raw);
+		}
+
+		for code in [vec![18, int as u8], vec![19, 0, int as u8], vec![20, 0, long as u8]] {
+			let instruction = Instructions::read(&cp, &mut Cursor::new(&code)).unwrap();
+			let (Instructions::LDC(constant) | Instructions::LDC_W(constant) | Instructions::LDC2_W(constant)) =
+				&instruction
+			else {
+				panic!("{instruction:?}");
+			};
+			assert!(matches!(constant.tag, IRCpTag::Dynamic { .. }));
+			let mut out = Cursor::new(Vec::new());
+			instruction.write(&mut out).unwrap();
+			assert_eq!(out.into_inner(), code);
+		}
+	}
+
+	#[test]
+	fn constant_pool_index() {
+		let mut cp = ConstantPool::from(vec![IRCpTag::Utf8(Shared::new("a".to_string()))]);
+		assert_eq!(cp.edit(|pool| pool.utf8("a")).unwrap(), 1);
+
+		// pushed entries are picked up by the next edit
+		let b = cp.push(IRCpTag::Utf8(Shared::new("b".to_string()))).unwrap();
+		assert_eq!(cp.edit(|pool| pool.utf8("b")).unwrap(), b);
+
+		// so are entries swapped in place
+		let class = cp.edit(|pool| pool.class("a")).unwrap();
+		cp.set(class, IRCpTag::Class(CPUtf8Ref::from_cp(&cp, b))).unwrap();
+		assert_eq!(cp.edit(|pool| pool.class("b")).unwrap(), class);
+		assert_eq!(cp.edit(|pool| pool.class("a")).unwrap(), 4);
+
+		// and anything done through the Vec
+		cp[0] = IRCpTag::Integer(7);
+		assert_eq!(cp.edit(|pool| pool.integer(7)).unwrap(), 1);
+		assert_eq!(cp.edit(|pool| pool.utf8("a")).unwrap(), 5);
+		assert_eq!(cp.len(), 5);
+	}
+
+	#[test]
+	fn set_out_of_range() {
+		let mut cp = ConstantPool::from(vec![IRCpTag::Integer(1), IRCpTag::Integer(2)]);
+		for index in [0, 3, u16::MAX] {
+			assert!(matches!(
+				cp.set(index, IRCpTag::Integer(7)),
+				Err(IRClassfileError::IO(IOClassfileError::BadCpIndex(i))) if i == index
+			));
+		}
+		assert!(matches!(cp[..], [IRCpTag::Integer(1), IRCpTag::Integer(2)]));
+
+		cp.set(2, IRCpTag::Integer(7)).unwrap();
+		assert_eq!(cp.edit(|pool| pool.integer(7)).unwrap(), 2);
+	}
+}
@@ -1,7 +1,10 @@
-use maya_bytes::BytesReadExt;
+use std::io::Seek;
 
-use crate::class_pool::{
-	CPClassRef, CPFieldRef, CPInvokeDynamicRef, CPMethodHandleRef, CPMethodRef, CPUtf8Ref, IRClassfileError, IRCpTag,
+use maya_bytes::{BytesError, BytesReadExt, BytesWriteExt};
+
+use crate::{
+	class_pool::{CPClassRef, CPFieldRef, CPInvokeDynamicRef, CPMethodRef, CPTagRef, IRClassfileError, IRCpTag},
+	opcode::{OpcodeInfo, Operands, OPCODES},
 };
 
 #[allow(non_camel_case_types)]
@@ -9,169 +12,178 @@ use crate::class_pool::{
 pub struct Opcodes {}
 
 impl Opcodes {
-	const NOP: u8 = 0;
-	const ACONST_NULL: u8 = 1;
-	const ICONST_M1: u8 = 2;
-	const ICONST_0: u8 = 3;
-	const ICONST_1: u8 = 4;
-	const ICONST_2: u8 = 5;
-	const ICONST_3: u8 = 6;
-	const ICONST_4: u8 = 7;
-	const ICONST_5: u8 = 8;
-	const LCONST_0: u8 = 9;
-	const LCONST_1: u8 = 10;
-	const FCONST_0: u8 = 11;
-	const FCONST_1: u8 = 12;
-	const FCONST_2: u8 = 13;
-	const DCONST_0: u8 = 14;
-	const DCONST_1: u8 = 15;
-	const BIPUSH: u8 = 16;
-	const SIPUSH: u8 = 17;
-	const LDC: u8 = 18;
-	const ILOAD: u8 = 21;
-	const LLOAD: u8 = 22;
-	const FLOAD: u8 = 23;
-	const DLOAD: u8 = 24;
-	const ALOAD: u8 = 25;
-	const IALOAD: u8 = 46;
-	const LALOAD: u8 = 47;
-	const FALOAD: u8 = 48;
-	const DALOAD: u8 = 49;
-	const AALOAD: u8 = 50;
-	const BALOAD: u8 = 51;
-	const CALOAD: u8 = 52;
-	const SALOAD: u8 = 53;
-	const ISTORE: u8 = 54;
-	const LSTORE: u8 = 55;
-	const FSTORE: u8 = 56;
-	const DSTORE: u8 = 57;
-	const ASTORE: u8 = 58;
-	const IASTORE: u8 = 79;
-	const LASTORE: u8 = 80;
-	const FASTORE: u8 = 81;
-	const DASTORE: u8 = 82;
-	const AASTORE: u8 = 83;
-	const BASTORE: u8 = 84;
-	const CASTORE: u8 = 85;
-	const SASTORE: u8 = 86;
-	const POP: u8 = 87;
-	const POP2: u8 = 88;
-	const DUP: u8 = 89;
-	const DUP_X1: u8 = 90;
-	const DUP_X2: u8 = 91;
-	const DUP2: u8 = 92;
-	const DUP2_X1: u8 = 93;
-	const DUP2_X2: u8 = 94;
-	const SWAP: u8 = 95;
-	const IADD: u8 = 96;
-	const LADD: u8 = 97;
-	const FADD: u8 = 98;
-	const DADD: u8 = 99;
-	const ISUB: u8 = 100;
-	const LSUB: u8 = 101;
-	const FSUB: u8 = 102;
-	const DSUB: u8 = 103;
-	const IMUL: u8 = 104;
-	const LMUL: u8 = 105;
-	const FMUL: u8 = 106;
-	const DMUL: u8 = 107;
-	const IDIV: u8 = 108;
-	const LDIV: u8 = 109;
-	const FDIV: u8 = 110;
-	const DDIV: u8 = 111;
-	const IREM: u8 = 112;
-	const LREM: u8 = 113;
-	const FREM: u8 = 114;
-	const DREM: u8 = 115;
-	const INEG: u8 = 116;
-	const LNEG: u8 = 117;
-	const FNEG: u8 = 118;
-	const DNEG: u8 = 119;
-	const ISHL: u8 = 120;
-	const LSHL: u8 = 121;
-	const ISHR: u8 = 122;
-	const LSHR: u8 = 123;
-	const IUSHR: u8 = 124;
-	const LUSHR: u8 = 125;
-	const IAND: u8 = 126;
-	const LAND: u8 = 127;
-	const IOR: u8 = 128;
-	const LOR: u8 = 129;
-	const IXOR: u8 = 130;
-	const LXOR: u8 = 131;
-	const IINC: u8 = 132;
-	const I2L: u8 = 133;
-	const I2F: u8 = 134;
-	const I2D: u8 = 135;
-	const L2I: u8 = 136;
-	const L2F: u8 = 137;
-	const L2D: u8 = 138;
-	const F2I: u8 = 139;
-	const F2L: u8 = 140;
-	const F2D: u8 = 141;
-	const D2I: u8 = 142;
-	const D2L: u8 = 143;
-	const D2F: u8 = 144;
-	const I2B: u8 = 145;
-	const I2C: u8 = 146;
-	const I2S: u8 = 147;
-	const LCMP: u8 = 148;
-	const FCMPL: u8 = 149;
-	const FCMPG: u8 = 150;
-	const DCMPL: u8 = 151;
-	const DCMPG: u8 = 152;
-	const IFEQ: u8 = 153;
-	const IFNE: u8 = 154;
-	const IFLT: u8 = 155;
-	const IFGE: u8 = 156;
-	const IFGT: u8 = 157;
-	const IFLE: u8 = 158;
-	const IF_ICMPEQ: u8 = 159;
-	const IF_ICMPNE: u8 = 160;
-	const IF_ICMPLT: u8 = 161;
-	const IF_ICMPGE: u8 = 162;
-	const IF_ICMPGT: u8 = 163;
-	const IF_ICMPLE: u8 = 164;
-	const IF_ACMPEQ: u8 = 165;
-	const IF_ACMPNE: u8 = 166;
-	const GOTO: u8 = 167;
-	const JSR: u8 = 168;
-	const RET: u8 = 169;
-	const TABLESWITCH: u8 = 170;
-	const LOOKUPSWITCH: u8 = 171;
-	const IRETURN: u8 = 172;
-	const LRETURN: u8 = 173;
-	const FRETURN: u8 = 174;
-	const DRETURN: u8 = 175;
-	const ARETURN: u8 = 176;
-	const RETURN: u8 = 177;
-	const GETSTATIC: u8 = 178;
-	const PUTSTATIC: u8 = 179;
-	const GETFIELD: u8 = 180;
-	const PUTFIELD: u8 = 181;
-	const INVOKEVIRTUAL: u8 = 182;
-	const INVOKESPECIAL: u8 = 183;
-	const INVOKESTATIC: u8 = 184;
-	const INVOKEINTERFACE: u8 = 185;
-	const INVOKEDYNAMIC: u8 = 186;
-	const NEW: u8 = 187;
-	const NEWARRAY: u8 = 188;
-	const ANEWARRAY: u8 = 189;
-	const ARRAYLENGTH: u8 = 190;
-	const ATHROW: u8 = 191;
-	const CHECKCAST: u8 = 192;
-	const INSTANCEOF: u8 = 193;
-	const MONITORENTER: u8 = 194;
-	const MONITOREXIT: u8 = 195;
-	const MULTIANEWARRAY: u8 = 197;
-	const IFNULL: u8 = 198;
-	const IFNONNULL: u8 = 199;
+	pub const NOP: u8 = 0;
+	pub const ACONST_NULL: u8 = 1;
+	pub const ICONST_M1: u8 = 2;
+	pub const ICONST_0: u8 = 3;
+	pub const ICONST_1: u8 = 4;
+	pub const ICONST_2: u8 = 5;
+	pub const ICONST_3: u8 = 6;
+	pub const ICONST_4: u8 = 7;
+	pub const ICONST_5: u8 = 8;
+	pub const LCONST_0: u8 = 9;
+	pub const LCONST_1: u8 = 10;
+	pub const FCONST_0: u8 = 11;
+	pub const FCONST_1: u8 = 12;
+	pub const FCONST_2: u8 = 13;
+	pub const DCONST_0: u8 = 14;
+	pub const DCONST_1: u8 = 15;
+	pub const BIPUSH: u8 = 16;
+	pub const SIPUSH: u8 = 17;
+	pub const LDC: u8 = 18;
+	pub const LDC_W: u8 = 19;
+	pub const LDC2_W: u8 = 20;
+	pub const ILOAD: u8 = 21;
+	pub const LLOAD: u8 = 22;
+	pub const FLOAD: u8 = 23;
+	pub const DLOAD: u8 = 24;
+	pub const ALOAD: u8 = 25;
+	pub const IALOAD: u8 = 46;
+	pub const LALOAD: u8 = 47;
+	pub const FALOAD: u8 = 48;
+	pub const DALOAD: u8 = 49;
+	pub const AALOAD: u8 = 50;
+	pub const BALOAD: u8 = 51;
+	pub const CALOAD: u8 = 52;
+	pub const SALOAD: u8 = 53;
+	pub const ISTORE: u8 = 54;
+	pub const LSTORE: u8 = 55;
+	pub const FSTORE: u8 = 56;
+	pub const DSTORE: u8 = 57;
+	pub const ASTORE: u8 = 58;
+	pub const IASTORE: u8 = 79;
+	pub const LASTORE: u8 = 80;
+	pub const FASTORE: u8 = 81;
+	pub const DASTORE: u8 = 82;
+	pub const AASTORE: u8 = 83;
+	pub const BASTORE: u8 = 84;
+	pub const CASTORE: u8 = 85;
+	pub const SASTORE: u8 = 86;
+	pub const POP: u8 = 87;
+	pub const POP2: u8 = 88;
+	pub const DUP: u8 = 89;
+	pub const DUP_X1: u8 = 90;
+	pub const DUP_X2: u8 = 91;
+	pub const DUP2: u8 = 92;
+	pub const DUP2_X1: u8 = 93;
+	pub const DUP2_X2: u8 = 94;
+	pub const SWAP: u8 = 95;
+	pub const IADD: u8 = 96;
+	pub const LADD: u8 = 97;
+	pub const FADD: u8 = 98;
+	pub const DADD: u8 = 99;
+	pub const ISUB: u8 = 100;
+	pub const LSUB: u8 = 101;
+	pub const FSUB: u8 = 102;
+	pub const DSUB: u8 = 103;
+	pub const IMUL: u8 = 104;
+	pub const LMUL: u8 = 105;
+	pub const FMUL: u8 = 106;
+	pub const DMUL: u8 = 107;
+	pub const IDIV: u8 = 108;
+	pub const LDIV: u8 = 109;
+	pub const FDIV: u8 = 110;
+	pub const DDIV: u8 = 111;
+	pub const IREM: u8 = 112;
+	pub const LREM: u8 = 113;
+	pub const FREM: u8 = 114;
+	pub const DREM: u8 = 115;
+	pub const INEG: u8 = 116;
+	pub const LNEG: u8 = 117;
+	pub const FNEG: u8 = 118;
+	pub const DNEG: u8 = 119;
+	pub const ISHL: u8 = 120;
+	pub const LSHL: u8 = 121;
+	pub const ISHR: u8 = 122;
+	pub const LSHR: u8 = 123;
+	pub const IUSHR: u8 = 124;
+	pub const LUSHR: u8 = 125;
+	pub const IAND: u8 = 126;
+	pub const LAND: u8 = 127;
+	pub const IOR: u8 = 128;
+	pub const LOR: u8 = 129;
+	pub const IXOR: u8 = 130;
+	pub const LXOR: u8 = 131;
+	pub const IINC: u8 = 132;
+	pub const I2L: u8 = 133;
+	pub const I2F: u8 = 134;
+	pub const I2D: u8 = 135;
+	pub const L2I: u8 = 136;
+	pub const L2F: u8 = 137;
+	pub const L2D: u8 = 138;
+	pub const F2I: u8 = 139;
+	pub const F2L: u8 = 140;
+	pub const F2D: u8 = 141;
+	pub const D2I: u8 = 142;
+	pub const D2L: u8 = 143;
+	pub const D2F: u8 = 144;
+	pub const I2B: u8 = 145;
+	pub const I2C: u8 = 146;
+	pub const I2S: u8 = 147;
+	pub const LCMP: u8 = 148;
+	pub const FCMPL: u8 = 149;
+	pub const FCMPG: u8 = 150;
+	pub const DCMPL: u8 = 151;
+	pub const DCMPG: u8 = 152;
+	pub const IFEQ: u8 = 153;
+	pub const IFNE: u8 = 154;
+	pub const IFLT: u8 = 155;
+	pub const IFGE: u8 = 156;
+	pub const IFGT: u8 = 157;
+	pub const IFLE: u8 = 158;
+	pub const IF_ICMPEQ: u8 = 159;
+	pub const IF_ICMPNE: u8 = 160;
+	pub const IF_ICMPLT: u8 = 161;
+	pub const IF_ICMPGE: u8 = 162;
+	pub const IF_ICMPGT: u8 = 163;
+	pub const IF_ICMPLE: u8 = 164;
+	pub const IF_ACMPEQ: u8 = 165;
+	pub const IF_ACMPNE: u8 = 166;
+	pub const GOTO: u8 = 167;
+	pub const JSR: u8 = 168;
+	pub const RET: u8 = 169;
+	pub const TABLESWITCH: u8 = 170;
+	pub const LOOKUPSWITCH: u8 = 171;
+	pub const IRETURN: u8 = 172;
+	pub const LRETURN: u8 = 173;
+	pub const FRETURN: u8 = 174;
+	pub const DRETURN: u8 = 175;
+	pub const ARETURN: u8 = 176;
+	pub const RETURN: u8 = 177;
+	pub const GETSTATIC: u8 = 178;
+	pub const PUTSTATIC: u8 = 179;
+	pub const GETFIELD: u8 = 180;
+	pub const PUTFIELD: u8 = 181;
+	pub const INVOKEVIRTUAL: u8 = 182;
+	pub const INVOKESPECIAL: u8 = 183;
+	pub const INVOKESTATIC: u8 = 184;
+	pub const INVOKEINTERFACE: u8 = 185;
+	pub const INVOKEDYNAMIC: u8 = 186;
+	pub const NEW: u8 = 187;
+	pub const NEWARRAY: u8 = 188;
+	pub const ANEWARRAY: u8 = 189;
+	pub const ARRAYLENGTH: u8 = 190;
+	pub const ATHROW: u8 = 191;
+	pub const CHECKCAST: u8 = 192;
+	pub const INSTANCEOF: u8 = 193;
+	pub const MONITORENTER: u8 = 194;
+	pub const MONITOREXIT: u8 = 195;
+	pub const WIDE: u8 = 196;
+	pub const MULTIANEWARRAY: u8 = 197;
+	pub const IFNULL: u8 = 198;
+	pub const IFNONNULL: u8 = 199;
+	pub const GOTO_W: u8 = 200;
+	pub const JSR_W: u8 = 201;
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(u8)]
 #[allow(non_camel_case_types)]
 /// An 'Instructions' variant represents an Opcode with the data it contains, if any.
+///
+/// The `<x>load_<n>` and `<x>store_<n>` short forms are read as their long form with `n` as the
+/// index, and `wide` forms as the instruction they widen. Branch operands are offsets relative to the branching instruction.
 pub enum Instructions {
 	NOP = 0,
 	ACONST_NULL = 1,
@@ -189,14 +201,16 @@ pub enum Instructions {
 	FCONST_2 = 13,
 	DCONST_0 = 14,
 	DCONST_1 = 15,
-	BIPUSH = 16,
-	SIPUSH(u16) = 17,
-	LDC(IRCpTag) = 18,
-	ILOAD(u8) = 21,
-	LLOAD(u8) = 22,
-	FLOAD(u8) = 23,
-	DLOAD(u8) = 24,
-	ALOAD(u8) = 25,
+	BIPUSH(i8) = 16,
+	SIPUSH(i16) = 17,
+	LDC(CPTagRef) = 18,
+	LDC_W(CPTagRef) = 19,
+	LDC2_W(CPTagRef) = 20,
+	ILOAD(u16) = 21,
+	LLOAD(u16) = 22,
+	FLOAD(u16) = 23,
+	DLOAD(u16) = 24,
+	ALOAD(u16) = 25,
 	IALOAD = 46,
 	LALOAD = 47,
 	FALOAD = 48,
@@ -205,11 +219,11 @@ pub enum Instructions {
 	BALOAD = 51,
 	CALOAD = 52,
 	SALOAD = 53,
-	ISTORE(u8) = 54,
-	LSTORE(u8) = 55,
-	FSTORE(u8) = 56,
-	DSTORE(u8) = 57,
-	ASTORE(u8) = 58,
+	ISTORE(u16) = 54,
+	LSTORE(u16) = 55,
+	FSTORE(u16) = 56,
+	DSTORE(u16) = 57,
+	ASTORE(u16) = 58,
 	IASTORE = 79,
 	LASTORE = 80,
 	FASTORE = 81,
@@ -263,7 +277,7 @@ pub enum Instructions {
 	LOR = 129,
 	IXOR = 130,
 	LXOR = 131,
-	IINC = 132,
+	IINC(u16, i16) = 132,
 	I2L = 133,
 	I2F = 134,
 	I2D = 135,
@@ -284,25 +298,36 @@ pub enum Instructions {
 	FCMPG = 150,
 	DCMPL = 151,
 	DCMPG = 152,
-	IFEQ(u16) = 153,
-	IFNE(u16) = 154,
-	IFLT(u16) = 155,
-	IFGE(u16) = 156,
-	IFGT(u16) = 157,
-	IFLE(u16) = 158,
-	IF_ICMPEQ(u16) = 159,
-	IF_ICMPNE(u16) = 160,
-	IF_ICMPLT(u16) = 161,
-	IF_ICMPGE(u16) = 162,
-	IF_ICMPGT(u16) = 163,
-	IF_ICMPLE(u16) = 164,
-	IF_ACMPEQ(u16) = 165,
-	IF_ACMPNE(u16) = 166,
-	GOTO = 167,
-	JSR = 168,
-	RET = 169,
-	TABLESWITCH = 170,
-	LOOKUPSWITCH = 171,
+	IFEQ(i16) = 153,
+	IFNE(i16) = 154,
+	IFLT(i16) = 155,
+	IFGE(i16) = 156,
+	IFGT(i16) = 157,
+	IFLE(i16) = 158,
+	IF_ICMPEQ(i16) = 159,
+	IF_ICMPNE(i16) = 160,
+	IF_ICMPLT(i16) = 161,
+	IF_ICMPGE(i16) = 162,
+	IF_ICMPGT(i16) = 163,
+	IF_ICMPLE(i16) = 164,
+	IF_ACMPEQ(i16) = 165,
+	IF_ACMPNE(i16) = 166,
+	GOTO(i16) = 167,
+	JSR(i16) = 168,
+	RET(u16) = 169,
+	/// Jumps to `offsets[value - low]` when `low <= value <= high`, and to `default` otherwise.
+	TABLESWITCH {
+		default: i32,
+		low: i32,
+		high: i32,
+		offsets: Vec<i32>,
+	} = 170,
+	/// Jumps to the offset paired with the value, or to `default` if none matches. Pairs are sorted
+	/// by value.
+	LOOKUPSWITCH {
+		default: i32,
+		pairs: Vec<(i32, i32)>,
+	} = 171,
 	IRETURN = 172,
 	LRETURN = 173,
 	FRETURN = 174,
@@ -316,54 +341,32 @@ pub enum Instructions {
 	INVOKEVIRTUAL(CPMethodRef) = 182,
 	INVOKESPECIAL(CPMethodRef) = 183,
 	INVOKESTATIC(CPMethodRef) = 184,
-	INVOKEINTERFACE(CPMethodRef) = 185,
+	INVOKEINTERFACE(CPMethodRef, u8) = 185,
 	INVOKEDYNAMIC(CPInvokeDynamicRef) = 186,
 	NEW(CPClassRef) = 187,
-	NEWARRAY = 188,
-	ANEWARRAY = 189,
+	NEWARRAY(u8) = 188,
+	ANEWARRAY(CPClassRef) = 189,
 	ARRAYLENGTH = 190,
 	ATHROW = 191,
-	CHECKCAST = 192,
-	INSTANCEOF = 193,
+	CHECKCAST(CPClassRef) = 192,
+	INSTANCEOF(CPClassRef) = 193,
 	MONITORENTER = 194,
 	MONITOREXIT = 195,
-	MULTIANEWARRAY = 197,
-	IFNULL = 198,
-	IFNONNULL = 199,
+	MULTIANEWARRAY(CPClassRef, u8) = 197,
+	IFNULL(i16) = 198,
+	IFNONNULL(i16) = 199,
+	GOTO_W(i32) = 200,
+	JSR_W(i32) = 201,
 }
 
 impl Instructions {
-	pub fn read<B: BytesReadExt>(cp: &[IRCpTag], buffer: &mut B) -> Result<Instructions, IRClassfileError> {
-		Ok(match buffer.read_u8()? {
-			Opcodes::GETFIELD => Instructions::GETFIELD(CPFieldRef::from_cp(cp, buffer.read_u16()?)),
-			Opcodes::GETSTATIC => Instructions::GETSTATIC(CPFieldRef::from_cp(cp, buffer.read_u16()?)),
-			Opcodes::LDC => Instructions::LDC(
-				cp.get(buffer.read_u8()?.saturating_sub(1) as usize)
-					.cloned()
-					.expect("fuck"),
-			),
-			Opcodes::INVOKEVIRTUAL => Instructions::INVOKEVIRTUAL(CPMethodRef::from_cp(cp, buffer.read_u16()?)),
-			Opcodes::INVOKESPECIAL => Instructions::INVOKESPECIAL(CPMethodRef::from_cp(cp, buffer.read_u16()?)),
-			Opcodes::INVOKEDYNAMIC => {
-				let s = Instructions::INVOKEDYNAMIC(CPInvokeDynamicRef::from_cp(cp, buffer.read_u16()?));
-				buffer.read_u16()?;
-				s
-			}
-			Opcodes::RETURN => Instructions::RETURN,
-			Opcodes::ALOAD => Instructions::ALOAD(buffer.read_u8()?),
-			/* aload_0 */ 0x2A => Instructions::ALOAD(0),
-			/* aload_1 */ 0x2B => Instructions::ALOAD(1),
-			/* aload_2 */ 0x2C => Instructions::ALOAD(2),
-			/* aload_3 */ 0x2D => Instructions::ALOAD(3),
-
-			Opcodes::ASTORE => Instructions::ASTORE(buffer.read_u8()?),
-			/* astore_0 */ 0x4B => Instructions::ASTORE(0),
-			/* astore_1 */ 0x4C => Instructions::ASTORE(1),
-			/* astore_2 */ 0x4D => Instructions::ASTORE(2),
-			/* astore_3 */ 0x4E => Instructions::ASTORE(3),
-			Opcodes::NEW => Instructions::NEW(CPClassRef::from_cp(cp, buffer.read_u16()?)),
-			Opcodes::DUP => Instructions::DUP,
-			Opcodes::PUTFIELD => Instructions::PUTFIELD(CPFieldRef::from_cp(cp, buffer.read_u16()?)),
+	/// Reads the instruction at the buffer's position, which has to be its offset in the code array
+	/// for switch padding to line up.
+	pub fn read<B: BytesReadExt + Seek>(cp: &[IRCpTag], buffer: &mut B) -> Result<Instructions, IRClassfileError> {
+		let opcode = buffer.read_u8()?;
+		Ok(match opcode {
+			Opcodes::NOP => Instructions::NOP,
+			Opcodes::ACONST_NULL => Instructions::ACONST_NULL,
 			Opcodes::ICONST_M1 => Instructions::ICONST_M1,
 			Opcodes::ICONST_0 => Instructions::ICONST_0,
 			Opcodes::ICONST_1 => Instructions::ICONST_1,
@@ -371,54 +374,980 @@ impl Instructions {
 			Opcodes::ICONST_3 => Instructions::ICONST_3,
 			Opcodes::ICONST_4 => Instructions::ICONST_4,
 			Opcodes::ICONST_5 => Instructions::ICONST_5,
-			Opcodes::IRETURN => Instructions::IRETURN,
-			Opcodes::ARETURN => Instructions::ARETURN,
-			Opcodes::ILOAD => Instructions::ILOAD(buffer.read_u8()?),
+			Opcodes::LCONST_0 => Instructions::LCONST_0,
+			Opcodes::LCONST_1 => Instructions::LCONST_1,
+			Opcodes::FCONST_0 => Instructions::FCONST_0,
+			Opcodes::FCONST_1 => Instructions::FCONST_1,
+			Opcodes::FCONST_2 => Instructions::FCONST_2,
+			Opcodes::DCONST_0 => Instructions::DCONST_0,
+			Opcodes::DCONST_1 => Instructions::DCONST_1,
+			Opcodes::BIPUSH => Instructions::BIPUSH(buffer.read_i8()?),
+			Opcodes::SIPUSH => Instructions::SIPUSH(buffer.read_i16()?),
+			Opcodes::LDC => Instructions::LDC(CPTagRef::from_cp(cp, buffer.read_u8()?.into())),
+			Opcodes::LDC_W => Instructions::LDC_W(CPTagRef::from_cp(cp, buffer.read_u16()?)),
+			Opcodes::LDC2_W => Instructions::LDC2_W(CPTagRef::from_cp(cp, buffer.read_u16()?)),
+
+			Opcodes::ILOAD => Instructions::ILOAD(buffer.read_u8()?.into()),
+			Opcodes::LLOAD => Instructions::LLOAD(buffer.read_u8()?.into()),
+			Opcodes::FLOAD => Instructions::FLOAD(buffer.read_u8()?.into()),
+			Opcodes::DLOAD => Instructions::DLOAD(buffer.read_u8()?.into()),
+			Opcodes::ALOAD => Instructions::ALOAD(buffer.read_u8()?.into()),
 			/* iload_0 */ 0x1A => Instructions::ILOAD(0),
 			/* iload_1 */ 0x1B => Instructions::ILOAD(1),
 			/* iload_2 */ 0x1C => Instructions::ILOAD(2),
 			/* iload_3 */ 0x1D => Instructions::ILOAD(3),
 
-			Opcodes::ISTORE => Instructions::ISTORE(buffer.read_u8()?),
-			/* istore_0 */ 0x3B => Instructions::ISTORE(0),
-			/* istore_1 */ 0x3C => Instructions::ISTORE(1),
-			/* istore_2 */ 0x3D => Instructions::ISTORE(2),
-			/* istore_3 */ 0x3E => Instructions::ISTORE(3),
-
-			Opcodes::IFEQ => Instructions::IFEQ(buffer.read_u16()?),
-			Opcodes::IFNE => Instructions::IFNE(buffer.read_u16()?),
-			Opcodes::IFLT => Instructions::IFLT(buffer.read_u16()?),
-			Opcodes::IFGE => Instructions::IFGE(buffer.read_u16()?),
-			Opcodes::IFGT => Instructions::IFGT(buffer.read_u16()?),
-			Opcodes::IFLE => Instructions::IFLE(buffer.read_u16()?),
-			Opcodes::IF_ICMPEQ => Instructions::IF_ICMPEQ(buffer.read_u16()?),
-			Opcodes::IF_ICMPNE => Instructions::IF_ICMPNE(buffer.read_u16()?),
-			Opcodes::IF_ICMPLT => Instructions::IF_ICMPLT(buffer.read_u16()?),
-			Opcodes::IF_ICMPGE => Instructions::IF_ICMPGE(buffer.read_u16()?),
-			Opcodes::IF_ICMPGT => Instructions::IF_ICMPGT(buffer.read_u16()?),
-			Opcodes::IF_ICMPLE => Instructions::IF_ICMPLE(buffer.read_u16()?),
-			Opcodes::IF_ACMPEQ => Instructions::IF_ACMPEQ(buffer.read_u16()?),
-			Opcodes::IF_ACMPNE => Instructions::IF_ACMPNE(buffer.read_u16()?),
-
-			Opcodes::LCONST_0 => Instructions::LCONST_0,
-			Opcodes::LCONST_1 => Instructions::LCONST_1,
-
-			Opcodes::LLOAD => Instructions::LLOAD(buffer.read_u8()?),
 			/* lload_0 */ 0x1E => Instructions::LLOAD(0),
 			/* lload_1 */ 0x1F => Instructions::LLOAD(1),
 			/* lload_2 */ 0x20 => Instructions::LLOAD(2),
 			/* lload_3 */ 0x21 => Instructions::LLOAD(3),
 
-			Opcodes::LSTORE => Instructions::LSTORE(buffer.read_u8()?),
+			/* fload_0 */ 0x22 => Instructions::FLOAD(0),
+			/* fload_1 */ 0x23 => Instructions::FLOAD(1),
+			/* fload_2 */ 0x24 => Instructions::FLOAD(2),
+			/* fload_3 */ 0x25 => Instructions::FLOAD(3),
+
+			/* dload_0 */ 0x26 => Instructions::DLOAD(0),
+			/* dload_1 */ 0x27 => Instructions::DLOAD(1),
+			/* dload_2 */ 0x28 => Instructions::DLOAD(2),
+			/* dload_3 */ 0x29 => Instructions::DLOAD(3),
+
+			/* aload_0 */ 0x2A => Instructions::ALOAD(0),
+			/* aload_1 */ 0x2B => Instructions::ALOAD(1),
+			/* aload_2 */ 0x2C => Instructions::ALOAD(2),
+			/* aload_3 */ 0x2D => Instructions::ALOAD(3),
+
+			Opcodes::IALOAD => Instructions::IALOAD,
+			Opcodes::LALOAD => Instructions::LALOAD,
+			Opcodes::FALOAD => Instructions::FALOAD,
+			Opcodes::DALOAD => Instructions::DALOAD,
+			Opcodes::AALOAD => Instructions::AALOAD,
+			Opcodes::BALOAD => Instructions::BALOAD,
+			Opcodes::CALOAD => Instructions::CALOAD,
+			Opcodes::SALOAD => Instructions::SALOAD,
+
+			Opcodes::ISTORE => Instructions::ISTORE(buffer.read_u8()?.into()),
+			Opcodes::LSTORE => Instructions::LSTORE(buffer.read_u8()?.into()),
+			Opcodes::FSTORE => Instructions::FSTORE(buffer.read_u8()?.into()),
+			Opcodes::DSTORE => Instructions::DSTORE(buffer.read_u8()?.into()),
+			Opcodes::ASTORE => Instructions::ASTORE(buffer.read_u8()?.into()),
+			/* istore_0 */ 0x3B => Instructions::ISTORE(0),
+			/* istore_1 */ 0x3C => Instructions::ISTORE(1),
+			/* istore_2 */ 0x3D => Instructions::ISTORE(2),
+			/* istore_3 */ 0x3E => Instructions::ISTORE(3),
+
 			/* lstore_0 */ 0x3F => Instructions::LSTORE(0),
 			/* lstore_1 */ 0x40 => Instructions::LSTORE(1),
 			/* lstore_2 */ 0x41 => Instructions::LSTORE(2),
 			/* lstore_3 */ 0x42 => Instructions::LSTORE(3),
 
+			/* fstore_0 */ 0x43 => Instructions::FSTORE(0),
+			/* fstore_1 */ 0x44 => Instructions::FSTORE(1),
+			/* fstore_2 */ 0x45 => Instructions::FSTORE(2),
+			/* fstore_3 */ 0x46 => Instructions::FSTORE(3),
+
+			/* dstore_0 */ 0x47 => Instructions::DSTORE(0),
+			/* dstore_1 */ 0x48 => Instructions::DSTORE(1),
+			/* dstore_2 */ 0x49 => Instructions::DSTORE(2),
+			/* dstore_3 */ 0x4A => Instructions::DSTORE(3),
+
+			/* astore_0 */ 0x4B => Instructions::ASTORE(0),
+			/* astore_1 */ 0x4C => Instructions::ASTORE(1),
+			/* astore_2 */ 0x4D => Instructions::ASTORE(2),
+			/* astore_3 */ 0x4E => Instructions::ASTORE(3),
+
+			Opcodes::IASTORE => Instructions::IASTORE,
+			Opcodes::LASTORE => Instructions::LASTORE,
+			Opcodes::FASTORE => Instructions::FASTORE,
+			Opcodes::DASTORE => Instructions::DASTORE,
+			Opcodes::AASTORE => Instructions::AASTORE,
+			Opcodes::BASTORE => Instructions::BASTORE,
+			Opcodes::CASTORE => Instructions::CASTORE,
+			Opcodes::SASTORE => Instructions::SASTORE,
+
+			Opcodes::POP => Instructions::POP,
+			Opcodes::POP2 => Instructions::POP2,
+			Opcodes::DUP => Instructions::DUP,
+			Opcodes::DUP_X1 => Instructions::DUP_X1,
+			Opcodes::DUP_X2 => Instructions::DUP_X2,
+			Opcodes::DUP2 => Instructions::DUP2,
+			Opcodes::DUP2_X1 => Instructions::DUP2_X1,
+			Opcodes::DUP2_X2 => Instructions::DUP2_X2,
+			Opcodes::SWAP => Instructions::SWAP,
+
+			Opcodes::IADD => Instructions::IADD,
 			Opcodes::LADD => Instructions::LADD,
-			Opcodes::SIPUSH => Instructions::SIPUSH(buffer.read_u16()?),
+			Opcodes::FADD => Instructions::FADD,
+			Opcodes::DADD => Instructions::DADD,
+			Opcodes::ISUB => Instructions::ISUB,
+			Opcodes::LSUB => Instructions::LSUB,
+			Opcodes::FSUB => Instructions::FSUB,
+			Opcodes::DSUB => Instructions::DSUB,
+			Opcodes::IMUL => Instructions::IMUL,
+			Opcodes::LMUL => Instructions::LMUL,
+			Opcodes::FMUL => Instructions::FMUL,
+			Opcodes::DMUL => Instructions::DMUL,
+			Opcodes::IDIV => Instructions::IDIV,
+			Opcodes::LDIV => Instructions::LDIV,
+			Opcodes::FDIV => Instructions::FDIV,
+			Opcodes::DDIV => Instructions::DDIV,
+			Opcodes::IREM => Instructions::IREM,
+			Opcodes::LREM => Instructions::LREM,
+			Opcodes::FREM => Instructions::FREM,
+			Opcodes::DREM => Instructions::DREM,
+			Opcodes::INEG => Instructions::INEG,
+			Opcodes::LNEG => Instructions::LNEG,
+			Opcodes::FNEG => Instructions::FNEG,
+			Opcodes::DNEG => Instructions::DNEG,
+			Opcodes::ISHL => Instructions::ISHL,
+			Opcodes::LSHL => Instructions::LSHL,
+			Opcodes::ISHR => Instructions::ISHR,
+			Opcodes::LSHR => Instructions::LSHR,
+			Opcodes::IUSHR => Instructions::IUSHR,
+			Opcodes::LUSHR => Instructions::LUSHR,
+			Opcodes::IAND => Instructions::IAND,
+			Opcodes::LAND => Instructions::LAND,
+			Opcodes::IOR => Instructions::IOR,
+			Opcodes::LOR => Instructions::LOR,
+			Opcodes::IXOR => Instructions::IXOR,
+			Opcodes::LXOR => Instructions::LXOR,
+
+			Opcodes::IINC => Instructions::IINC(buffer.read_u8()?.into(), buffer.read_i8()?.into()),
+
+			Opcodes::I2L => Instructions::I2L,
+			Opcodes::I2F => Instructions::I2F,
+			Opcodes::I2D => Instructions::I2D,
+			Opcodes::L2I => Instructions::L2I,
+			Opcodes::L2F => Instructions::L2F,
+			Opcodes::L2D => Instructions::L2D,
+			Opcodes::F2I => Instructions::F2I,
+			Opcodes::F2L => Instructions::F2L,
+			Opcodes::F2D => Instructions::F2D,
+			Opcodes::D2I => Instructions::D2I,
+			Opcodes::D2L => Instructions::D2L,
+			Opcodes::D2F => Instructions::D2F,
+			Opcodes::I2B => Instructions::I2B,
+			Opcodes::I2C => Instructions::I2C,
+			Opcodes::I2S => Instructions::I2S,
+
+			Opcodes::LCMP => Instructions::LCMP,
+			Opcodes::FCMPL => Instructions::FCMPL,
+			Opcodes::FCMPG => Instructions::FCMPG,
+			Opcodes::DCMPL => Instructions::DCMPL,
+			Opcodes::DCMPG => Instructions::DCMPG,
+
+			Opcodes::IFEQ => Instructions::IFEQ(buffer.read_i16()?),
+			Opcodes::IFNE => Instructions::IFNE(buffer.read_i16()?),
+			Opcodes::IFLT => Instructions::IFLT(buffer.read_i16()?),
+			Opcodes::IFGE => Instructions::IFGE(buffer.read_i16()?),
+			Opcodes::IFGT => Instructions::IFGT(buffer.read_i16()?),
+			Opcodes::IFLE => Instructions::IFLE(buffer.read_i16()?),
+			Opcodes::IF_ICMPEQ => Instructions::IF_ICMPEQ(buffer.read_i16()?),
+			Opcodes::IF_ICMPNE => Instructions::IF_ICMPNE(buffer.read_i16()?),
+			Opcodes::IF_ICMPLT => Instructions::IF_ICMPLT(buffer.read_i16()?),
+			Opcodes::IF_ICMPGE => Instructions::IF_ICMPGE(buffer.read_i16()?),
+			Opcodes::IF_ICMPGT => Instructions::IF_ICMPGT(buffer.read_i16()?),
+			Opcodes::IF_ICMPLE => Instructions::IF_ICMPLE(buffer.read_i16()?),
+			Opcodes::IF_ACMPEQ => Instructions::IF_ACMPEQ(buffer.read_i16()?),
+			Opcodes::IF_ACMPNE => Instructions::IF_ACMPNE(buffer.read_i16()?),
+			Opcodes::GOTO => Instructions::GOTO(buffer.read_i16()?),
+			Opcodes::JSR => Instructions::JSR(buffer.read_i16()?),
+			Opcodes::RET => Instructions::RET(buffer.read_u8()?.into()),
+			Opcodes::TABLESWITCH | Opcodes::LOOKUPSWITCH => {
+				let position = position(buffer)?;
+				let pc = position as usize - 1;
+				// operands start at the next multiple of 4 from the start of the code
+				buffer.read_into(&mut [0; 3][..((4 - position % 4) % 4) as usize])?;
+				let default = buffer.read_i32()?;
+				if opcode == Opcodes::TABLESWITCH {
+					let low = buffer.read_i32()?;
+					let high = buffer.read_i32()?;
+					if high < low {
+						return Err(IRClassfileError::InvalidOpcode { opcode, pc });
+					}
+					let offsets = buffer
+						.read_u32_vec((high as i64 - low as i64 + 1) as usize)?
+						.into_iter()
+						.map(|offset| offset as i32)
+						.collect();
+					Instructions::TABLESWITCH {
+						default,
+						low,
+						high,
+						offsets,
+					}
+				} else {
+					let npairs = buffer.read_i32()?;
+					if npairs < 0 {
+						return Err(IRClassfileError::InvalidOpcode { opcode, pc });
+					}
+					let pairs = buffer
+						.read_u32_vec(npairs as usize * 2)?
+						.chunks_exact(2)
+						.map(|pair| (pair[0] as i32, pair[1] as i32))
+						.collect();
+					Instructions::LOOKUPSWITCH { default, pairs }
+				}
+			}
+			Opcodes::WIDE => {
+				let widened = buffer.read_u8()?;
+				let index = buffer.read_u16()?;
+				match widened {
+					Opcodes::ILOAD => Instructions::ILOAD(index),
+					Opcodes::LLOAD => Instructions::LLOAD(index),
+					Opcodes::FLOAD => Instructions::FLOAD(index),
+					Opcodes::DLOAD => Instructions::DLOAD(index),
+					Opcodes::ALOAD => Instructions::ALOAD(index),
+					Opcodes::ISTORE => Instructions::ISTORE(index),
+					Opcodes::LSTORE => Instructions::LSTORE(index),
+					Opcodes::FSTORE => Instructions::FSTORE(index),
+					Opcodes::DSTORE => Instructions::DSTORE(index),
+					Opcodes::ASTORE => Instructions::ASTORE(index),
+					Opcodes::RET => Instructions::RET(index),
+					Opcodes::IINC => Instructions::IINC(index, buffer.read_i16()?),
+					_ => {
+						return Err(IRClassfileError::InvalidOpcode {
+							opcode: widened,
+							pc: position(buffer)? as usize - 3,
+						})
+					}
+				}
+			}
+
+			Opcodes::IRETURN => Instructions::IRETURN,
+			Opcodes::LRETURN => Instructions::LRETURN,
+			Opcodes::FRETURN => Instructions::FRETURN,
+			Opcodes::DRETURN => Instructions::DRETURN,
+			Opcodes::ARETURN => Instructions::ARETURN,
+			Opcodes::RETURN => Instructions::RETURN,
+
+			Opcodes::GETSTATIC => Instructions::GETSTATIC(CPFieldRef::from_cp(cp, buffer.read_u16()?)),
+			Opcodes::PUTSTATIC => Instructions::PUTSTATIC(CPFieldRef::from_cp(cp, buffer.read_u16()?)),
+			Opcodes::GETFIELD => Instructions::GETFIELD(CPFieldRef::from_cp(cp, buffer.read_u16()?)),
+			Opcodes::PUTFIELD => Instructions::PUTFIELD(CPFieldRef::from_cp(cp, buffer.read_u16()?)),
+			Opcodes::INVOKEVIRTUAL => Instructions::INVOKEVIRTUAL(CPMethodRef::from_cp(cp, buffer.read_u16()?)),
+			Opcodes::INVOKESPECIAL => Instructions::INVOKESPECIAL(CPMethodRef::from_cp(cp, buffer.read_u16()?)),
+			Opcodes::INVOKESTATIC => Instructions::INVOKESTATIC(CPMethodRef::from_cp(cp, buffer.read_u16()?)),
+			Opcodes::INVOKEINTERFACE => {
+				let method = CPMethodRef::from_cp(cp, buffer.read_u16()?);
+				let count = buffer.read_u8()?;
+				// always 0
+				buffer.read_u8()?;
+				Instructions::INVOKEINTERFACE(method, count)
+			}
+			Opcodes::INVOKEDYNAMIC => {
+				let s = Instructions::INVOKEDYNAMIC(CPInvokeDynamicRef::from_cp(cp, buffer.read_u16()?));
+				buffer.read_u16()?;
+				s
+			}
 
-			b => todo!("unparsed opcode 0x{b:02X}"),
+			Opcodes::NEW => Instructions::NEW(CPClassRef::from_cp(cp, buffer.read_u16()?)),
+			Opcodes::NEWARRAY => Instructions::NEWARRAY(buffer.read_u8()?),
+			Opcodes::ANEWARRAY => Instructions::ANEWARRAY(CPClassRef::from_cp(cp, buffer.read_u16()?)),
+			Opcodes::ARRAYLENGTH => Instructions::ARRAYLENGTH,
+			Opcodes::ATHROW => Instructions::ATHROW,
+			Opcodes::CHECKCAST => Instructions::CHECKCAST(CPClassRef::from_cp(cp, buffer.read_u16()?)),
+			Opcodes::INSTANCEOF => Instructions::INSTANCEOF(CPClassRef::from_cp(cp, buffer.read_u16()?)),
+			Opcodes::MONITORENTER => Instructions::MONITORENTER,
+			Opcodes::MONITOREXIT => Instructions::MONITOREXIT,
+			Opcodes::MULTIANEWARRAY => {
+				Instructions::MULTIANEWARRAY(CPClassRef::from_cp(cp, buffer.read_u16()?), buffer.read_u8()?)
+			}
+			Opcodes::IFNULL => Instructions::IFNULL(buffer.read_i16()?),
+			Opcodes::IFNONNULL => Instructions::IFNONNULL(buffer.read_i16()?),
+			Opcodes::GOTO_W => Instructions::GOTO_W(buffer.read_i32()?),
+			Opcodes::JSR_W => Instructions::JSR_W(buffer.read_i32()?),
+
+			opcode => return Err(IRClassfileError::UnknownOpcode(opcode)),
 		})
 	}
+
+	/// The opcode of the instruction's long form, which is what [`write`](Self::write) starts from
+	/// before picking a short or wide form.
+	pub fn opcode(&self) -> u8 {
+		// SAFETY: the enum is `repr(u8)`, so it starts with its u8 discriminant, which is the opcode
+		unsafe { *(self as *const Self).cast::<u8>() }
+	}
+
+	/// Operand stack slots the instruction pops and pushes, longs and doubles taking two. `jsr`
+	/// counts the return address it pushes for its target.
+	pub fn stack_effect(&self) -> (u16, u16) {
+		use Instructions::*;
+		match self {
+			GETSTATIC(field) => (0, value_slots(field.name_and_ty.ty.data.as_str())),
+			PUTSTATIC(field) => (value_slots(field.name_and_ty.ty.data.as_str()), 0),
+			GETFIELD(field) => (1, value_slots(field.name_and_ty.ty.data.as_str())),
+			PUTFIELD(field) => (1 + value_slots(field.name_and_ty.ty.data.as_str()), 0),
+			INVOKEVIRTUAL(method) | INVOKESPECIAL(method) | INVOKEINTERFACE(method, _) => {
+				let (parameters, result) = descriptor_slots(method.name_and_ty.ty.data.as_str());
+				(1 + parameters, result)
+			}
+			INVOKESTATIC(method) => descriptor_slots(method.name_and_ty.ty.data.as_str()),
+			INVOKEDYNAMIC(call_site) => descriptor_slots(call_site.name_and_ty.ty.data.as_str()),
+			MULTIANEWARRAY(_, dimensions) => (*dimensions as u16, 1),
+			// everything else has the same effect whatever its operands
+			_ => OPCODES[self.opcode() as usize].stack_effect.unwrap(),
+		}
+	}
+
+	/// Writes the instruction at the buffer's position, which has to be its offset in the code array
+	/// for switch padding to line up. Loads, stores, `iinc` and `ret` take the shortest form their
+	/// operands fit in, and `ldc` is written as `ldc_w` if its index doesn't fit in a byte.
+	pub fn write<B: BytesWriteExt + Seek>(&self, buffer: &mut B) -> Result<(), IRClassfileError> {
+		let opcode = self.opcode();
+		match self {
+			Instructions::BIPUSH(value) => {
+				buffer.write_u8(opcode)?;
+				buffer.write_i8(*value)?;
+			}
+			Instructions::SIPUSH(value) => {
+				buffer.write_u8(opcode)?;
+				buffer.write_i16(*value)?;
+			}
+			Instructions::LDC(constant) => match u8::try_from(constant.index) {
+				Ok(index) => {
+					buffer.write_u8(Opcodes::LDC)?;
+					buffer.write_u8(index)?;
+				}
+				Err(_) => {
+					buffer.write_u8(Opcodes::LDC_W)?;
+					buffer.write_u16(constant.index)?;
+				}
+			},
+			Instructions::LDC_W(constant) | Instructions::LDC2_W(constant) => {
+				buffer.write_u8(opcode)?;
+				buffer.write_u16(constant.index)?;
+			}
+
+			Instructions::ILOAD(index) => write_local(buffer, opcode, 0x1A, *index)?,
+			Instructions::LLOAD(index) => write_local(buffer, opcode, 0x1E, *index)?,
+			Instructions::FLOAD(index) => write_local(buffer, opcode, 0x22, *index)?,
+			Instructions::DLOAD(index) => write_local(buffer, opcode, 0x26, *index)?,
+			Instructions::ALOAD(index) => write_local(buffer, opcode, 0x2A, *index)?,
+			Instructions::ISTORE(index) => write_local(buffer, opcode, 0x3B, *index)?,
+			Instructions::LSTORE(index) => write_local(buffer, opcode, 0x3F, *index)?,
+			Instructions::FSTORE(index) => write_local(buffer, opcode, 0x43, *index)?,
+			Instructions::DSTORE(index) => write_local(buffer, opcode, 0x47, *index)?,
+			Instructions::ASTORE(index) => write_local(buffer, opcode, 0x4B, *index)?,
+			Instructions::RET(index) => match u8::try_from(*index) {
+				Ok(index) => {
+					buffer.write_u8(opcode)?;
+					buffer.write_u8(index)?;
+				}
+				Err(_) => {
+					buffer.write_u8(Opcodes::WIDE)?;
+					buffer.write_u8(opcode)?;
+					buffer.write_u16(*index)?;
+				}
+			},
+			Instructions::IINC(index, value) => match (u8::try_from(*index), i8::try_from(*value)) {
+				(Ok(index), Ok(value)) => {
+					buffer.write_u8(opcode)?;
+					buffer.write_u8(index)?;
+					buffer.write_i8(value)?;
+				}
+				_ => {
+					buffer.write_u8(Opcodes::WIDE)?;
+					buffer.write_u8(opcode)?;
+					buffer.write_u16(*index)?;
+					buffer.write_i16(*value)?;
+				}
+			},
+
+			Instructions::IFEQ(offset)
+			| Instructions::IFNE(offset)
+			| Instructions::IFLT(offset)
+			| Instructions::IFGE(offset)
+			| Instructions::IFGT(offset)
+			| Instructions::IFLE(offset)
+			| Instructions::IF_ICMPEQ(offset)
+			| Instructions::IF_ICMPNE(offset)
+			| Instructions::IF_ICMPLT(offset)
+			| Instructions::IF_ICMPGE(offset)
+			| Instructions::IF_ICMPGT(offset)
+			| Instructions::IF_ICMPLE(offset)
+			| Instructions::IF_ACMPEQ(offset)
+			| Instructions::IF_ACMPNE(offset)
+			| Instructions::GOTO(offset)
+			| Instructions::JSR(offset)
+			| Instructions::IFNULL(offset)
+			| Instructions::IFNONNULL(offset) => {
+				buffer.write_u8(opcode)?;
+				buffer.write_i16(*offset)?;
+			}
+			Instructions::GOTO_W(offset) | Instructions::JSR_W(offset) => {
+				buffer.write_u8(opcode)?;
+				buffer.write_i32(*offset)?;
+			}
+			Instructions::TABLESWITCH {
+				default,
+				low,
+				high,
+				offsets,
+			} => {
+				let pc = write_switch_header(buffer, opcode, *default)?;
+				if *high < *low || offsets.len() as i64 != *high as i64 - *low as i64 + 1 {
+					return Err(IRClassfileError::InvalidOpcode { opcode, pc });
+				}
+				buffer.write_i32(*low)?;
+				buffer.write_i32(*high)?;
+				for offset in offsets {
+					buffer.write_i32(*offset)?;
+				}
+			}
+			Instructions::LOOKUPSWITCH { default, pairs } => {
+				let pc = write_switch_header(buffer, opcode, *default)?;
+				let npairs = i32::try_from(pairs.len()).map_err(|_| IRClassfileError::InvalidOpcode { opcode, pc })?;
+				buffer.write_i32(npairs)?;
+				for (value, offset) in pairs {
+					buffer.write_i32(*value)?;
+					buffer.write_i32(*offset)?;
+				}
+			}
+
+			Instructions::GETSTATIC(field)
+			| Instructions::PUTSTATIC(field)
+			| Instructions::GETFIELD(field)
+			| Instructions::PUTFIELD(field) => {
+				buffer.write_u8(opcode)?;
+				buffer.write_u16(field.index)?;
+			}
+			Instructions::INVOKEVIRTUAL(method)
+			| Instructions::INVOKESPECIAL(method)
+			| Instructions::INVOKESTATIC(method) => {
+				buffer.write_u8(opcode)?;
+				buffer.write_u16(method.index)?;
+			}
+			Instructions::INVOKEINTERFACE(method, count) => {
+				buffer.write_u8(opcode)?;
+				buffer.write_u16(method.index)?;
+				buffer.write_u8(*count)?;
+				buffer.write_u8(0)?;
+			}
+			Instructions::INVOKEDYNAMIC(call_site) => {
+				buffer.write_u8(opcode)?;
+				buffer.write_u16(call_site.index)?;
+				buffer.write_u16(0)?;
+			}
+			Instructions::NEW(class)
+			| Instructions::ANEWARRAY(class)
+			| Instructions::CHECKCAST(class)
+			| Instructions::INSTANCEOF(class) => {
+				buffer.write_u8(opcode)?;
+				buffer.write_u16(class.index)?;
+			}
+			Instructions::NEWARRAY(ty) => {
+				buffer.write_u8(opcode)?;
+				buffer.write_u8(*ty)?;
+			}
+			Instructions::MULTIANEWARRAY(class, dimensions) => {
+				buffer.write_u8(opcode)?;
+				buffer.write_u16(class.index)?;
+				buffer.write_u8(*dimensions)?;
+			}
+
+			_ => buffer.write_u8(opcode)?,
+		}
+		Ok(())
+	}
+}
+
+/// Stack or local slots a value of the field descriptor `descriptor` takes, 0 for `V`.
+pub(crate) fn value_slots(descriptor: &str) -> u16 {
+	match descriptor.as_bytes().first() {
+		Some(b'J' | b'D') => 2,
+		Some(b'V') | None => 0,
+		Some(_) => 1,
+	}
+}
+
+/// Slots taken by the parameters of the method descriptor `descriptor`, and by its result.
+pub(crate) fn descriptor_slots(descriptor: &str) -> (u16, u16) {
+	let result = descriptor.split_once(')').map_or("", |(_, result)| result);
+	(parameters(descriptor).map(value_slots).sum(), value_slots(result))
+}
+
+/// The descriptors of the parameters of the method descriptor `descriptor`.
+pub(crate) fn parameters(descriptor: &str) -> impl Iterator<Item = &str> {
+	let mut rest = descriptor
+		.strip_prefix('(')
+		.and_then(|rest| rest.split_once(')'))
+		.map_or("", |(parameters, _)| parameters);
+	std::iter::from_fn(move || {
+		if rest.is_empty() {
+			return None;
+		}
+		let dimensions = rest.len() - rest.trim_start_matches('[').len();
+		let end = match rest.as_bytes().get(dimensions) {
+			Some(b'L') => rest.find(';').map_or(rest.len(), |end| end + 1),
+			_ => (dimensions + 1).min(rest.len()),
+		};
+		let (parameter, tail) = rest.split_at(end);
+		rest = tail;
+		Some(parameter)
+	})
+}
+
+/// `short` is the `_0` form of `opcode`.
+fn write_local<B: BytesWriteExt>(buffer: &mut B, opcode: u8, short: u8, index: u16) -> Result<(), IRClassfileError> {
+	match index {
+		0..=3 => buffer.write_u8(short + index as u8)?,
+		4..=255 => {
+			buffer.write_u8(opcode)?;
+			buffer.write_u8(index as u8)?;
+		}
+		_ => {
+			buffer.write_u8(Opcodes::WIDE)?;
+			buffer.write_u8(opcode)?;
+			buffer.write_u16(index)?;
+		}
+	}
+	Ok(())
+}
+
+/// Writes the opcode, the padding and the default offset, returning the switch's pc.
+fn write_switch_header<B: BytesWriteExt + Seek>(
+	buffer: &mut B,
+	opcode: u8,
+	default: i32,
+) -> Result<usize, IRClassfileError> {
+	let pc = position(buffer)?;
+	buffer.write_u8(opcode)?;
+	for _ in 0..(4 - (pc + 1) % 4) % 4 {
+		buffer.write_u8(0)?;
+	}
+	buffer.write_i32(default)?;
+	Ok(pc as usize)
+}
+
+fn position<B: Seek>(buffer: &mut B) -> Result<u64, IRClassfileError> {
+	Ok(buffer
+		.stream_position()
+		.map_err(|source| BytesError::IO { source, position: None })?)
+}
+
+/// The javap mnemonic of `opcode`, `None` for the reserved and unassigned opcodes.
+pub fn mnemonic(opcode: u8) -> Option<&'static str> {
+	OpcodeInfo::of(opcode).map(|info| info.name)
+}
+
+/// Length in bytes of the instruction starting at `pc`, including its operands and any switch
+/// padding.
+pub fn instruction_len(code: &[u8], pc: usize) -> Result<usize, IRClassfileError> {
+	let opcode = *code.get(pc).ok_or(IRClassfileError::TruncatedCode(pc))?;
+	let info = OpcodeInfo::of(opcode).ok_or(IRClassfileError::InvalidOpcode { opcode, pc })?;
+	let len = match info.operands {
+		Operands::Wide => match code.get(pc + 1) {
+			Some(&Opcodes::IINC) => 6,
+			Some(Opcodes::ILOAD..=Opcodes::ALOAD | Opcodes::ISTORE..=Opcodes::ASTORE | &Opcodes::RET) => 4,
+			Some(&opcode) => return Err(IRClassfileError::InvalidOpcode { opcode, pc: pc + 1 }),
+			None => return Err(IRClassfileError::TruncatedCode(pc)),
+		},
+		Operands::TableSwitch | Operands::LookupSwitch => {
+			// operands start at the next multiple of 4 from the start of the code
+			let operands = (pc + 4) & !3;
+			let word = |n: usize| -> Result<i32, IRClassfileError> {
+				let at = operands + n * 4;
+				code.get(at..at + 4)
+					.map(|bytes| i32::from_be_bytes(bytes.try_into().unwrap()))
+					.ok_or(IRClassfileError::TruncatedCode(pc))
+			};
+			let (header, entries) = match opcode {
+				Opcodes::TABLESWITCH => {
+					let (low, high) = (word(1)?, word(2)?);
+					if high < low {
+						return Err(IRClassfileError::InvalidOpcode { opcode, pc });
+					}
+					(12, (high as i64 - low as i64 + 1) as usize * 4)
+				}
+				_ => {
+					let npairs = word(1)?;
+					if npairs < 0 {
+						return Err(IRClassfileError::InvalidOpcode { opcode, pc });
+					}
+					(8, npairs as usize * 8)
+				}
+			};
+			operands - pc + header + entries
+		}
+		operands => 1 + operands.size().unwrap(),
+	};
+	if pc + len > code.len() {
+		return Err(IRClassfileError::TruncatedCode(pc));
+	}
+	Ok(len)
+}
+
+/// Calls `f` with every constant pool index in the operands of `code` and writes back what it
+/// leaves there. `ldc` only has a byte for its index, `f` must not push it past 255.
+pub(crate) fn visit_cp_operands(code: &mut [u8], f: &mut dyn FnMut(&mut u16)) -> Result<(), IRClassfileError> {
+	let mut pc = 0;
+	while pc < code.len() {
+		let len = instruction_len(code, pc)?;
+		match code[pc] {
+			Opcodes::LDC => {
+				let mut index = code[pc + 1] as u16;
+				f(&mut index);
+				code[pc + 1] = index as u8;
+			}
+			Opcodes::LDC_W
+			| Opcodes::LDC2_W
+			| Opcodes::GETSTATIC..=Opcodes::INVOKEDYNAMIC
+			| Opcodes::NEW
+			| Opcodes::ANEWARRAY
+			| Opcodes::CHECKCAST
+			| Opcodes::INSTANCEOF
+			| Opcodes::MULTIANEWARRAY => {
+				let mut index = u16::from_be_bytes([code[pc + 1], code[pc + 2]]);
+				f(&mut index);
+				code[pc + 1..pc + 3].copy_from_slice(&index.to_be_bytes());
+			}
+			_ => {}
+		}
+		pc += len;
+	}
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use std::io::Cursor;
+
+	use super::*;
+	use crate::{
+		attribute::IRAttribute,
+		class_pool::CPTagRef,
+		test_util::{class, CLASSES, HELLO},
+	};
+
+	fn words(values: &[i32]) -> Vec<u8> {
+		values.iter().flat_map(|value| value.to_be_bytes()).collect()
+	}
+
+	fn read_at(code: &[u8], pc: usize) -> Result<Instructions, IRClassfileError> {
+		let mut buffer = Cursor::new(code);
+		buffer.set_position(pc as u64);
+		Instructions::read(&[], &mut buffer)
+	}
+
+	#[test]
+	fn switch_decoding() {
+		// at pc 1 two bytes of padding bring the operands to pc 4, counted from the start of the code
+		let mut code = vec![Opcodes::NOP, Opcodes::TABLESWITCH, 0, 0];
+		code.extend(words(&[-8, -2, -1, -100, 7]));
+		assert!(matches!(
+			read_at(&code, 1),
+			Ok(Instructions::TABLESWITCH { default: -8, low: -2, high: -1, offsets }) if offsets == [-100, 7]
+		));
+		assert_eq!(instruction_len(&code, 1).unwrap(), code.len() - 1);
+
+		let mut code = vec![Opcodes::NOP, Opcodes::NOP, Opcodes::LOOKUPSWITCH, 0];
+		code.extend(words(&[-4, 2, i32::MIN, -40, 3, 40]));
+		assert!(matches!(
+			read_at(&code, 2),
+			Ok(Instructions::LOOKUPSWITCH { default: -4, pairs }) if pairs == [(i32::MIN, -40), (3, 40)]
+		));
+		assert_eq!(instruction_len(&code, 2).unwrap(), code.len() - 2);
+	}
+
+	#[test]
+	fn switch_padding() {
+		let switches = [
+			Instructions::TABLESWITCH {
+				default: 100,
+				low: 10,
+				high: 12,
+				offsets: vec![-1, 0, 1],
+			},
+			Instructions::LOOKUPSWITCH {
+				default: -100,
+				pairs: vec![(-5, 8), (5, -8)],
+			},
+		];
+		for switch in &switches {
+			for pc in 0..4 {
+				let code = encode_at(switch, pc);
+				let operands = (pc + 4) & !3;
+				assert_eq!(code[pc], switch.opcode());
+				assert!(code[pc + 1..operands].iter().all(|&byte| byte == 0));
+				assert_eq!(instruction_len(&code, pc).unwrap(), code.len() - pc);
+				// decoding reads the padding too, so writing it out again lands on the same bytes
+				assert_eq!(encode_at(&read_at(&code, pc).unwrap(), pc), code);
+
+				// the offsets and pairs are read in one go, which fails as a whole when cut short
+				let cut = &code[..code.len() - 1];
+				assert!(matches!(read_at(cut, pc), Err(IRClassfileError::Bytes(_))));
+				assert!(matches!(instruction_len(cut, pc), Err(IRClassfileError::TruncatedCode(p)) if p == pc));
+			}
+		}
+	}
+
+	#[test]
+	fn invalid_switches() {
+		// high < low
+		let mut code = vec![Opcodes::TABLESWITCH, 0, 0, 0];
+		code.extend(words(&[0, 5, 4]));
+		assert!(matches!(
+			read_at(&code, 0),
+			Err(IRClassfileError::InvalidOpcode { opcode: 0xAA, pc: 0 })
+		));
+		assert!(matches!(
+			instruction_len(&code, 0),
+			Err(IRClassfileError::InvalidOpcode { opcode: 0xAA, pc: 0 })
+		));
+
+		// npairs < 0
+		let mut code = vec![Opcodes::NOP, Opcodes::LOOKUPSWITCH, 0, 0];
+		code.extend(words(&[0, -1]));
+		assert!(matches!(
+			read_at(&code, 1),
+			Err(IRClassfileError::InvalidOpcode { opcode: 0xAB, pc: 1 })
+		));
+		assert!(matches!(
+			instruction_len(&code, 1),
+			Err(IRClassfileError::InvalidOpcode { opcode: 0xAB, pc: 1 })
+		));
+	}
+
+	fn encode(instruction: &Instructions) -> Vec<u8> {
+		encode_at(instruction, 0)
+	}
+
+	fn encode_all(instructions: &[Instructions]) -> Vec<u8> {
+		let mut buffer = Cursor::new(Vec::new());
+		for instruction in instructions {
+			instruction.write(&mut buffer).unwrap();
+		}
+		buffer.into_inner()
+	}
+
+	/// `instruction` after `pc` nops.
+	fn encode_at(instruction: &Instructions, pc: usize) -> Vec<u8> {
+		let mut buffer = Cursor::new(vec![Opcodes::NOP; pc]);
+		buffer.set_position(pc as u64);
+		instruction.write(&mut buffer).unwrap();
+		buffer.into_inner()
+	}
+
+	#[test]
+	fn wide_round_trip() {
+		let locals: [fn(u16) -> Instructions; 11] = [
+			Instructions::ILOAD,
+			Instructions::LLOAD,
+			Instructions::FLOAD,
+			Instructions::DLOAD,
+			Instructions::ALOAD,
+			Instructions::ISTORE,
+			Instructions::LSTORE,
+			Instructions::FSTORE,
+			Instructions::DSTORE,
+			Instructions::ASTORE,
+			Instructions::RET,
+		];
+		for make in locals {
+			let opcode = make(0).opcode();
+			let code = encode(&make(300));
+			assert_eq!(code, [Opcodes::WIDE, opcode, 0x01, 0x2C]);
+			let read = read_at(&code, 0).unwrap();
+			assert_eq!(read.opcode(), opcode);
+			assert_eq!(encode(&read), code);
+			assert_eq!(instruction_len(&code, 0).unwrap(), 4);
+			// 255 still fits the short form
+			assert_eq!(encode(&make(255)), [opcode, 255]);
+		}
+
+		for (index, value, wide) in [(300, -1000, true), (1, 200, true), (300, 1, true), (255, -128, false)] {
+			let code = encode(&Instructions::IINC(index, value));
+			assert_eq!(code[0] == Opcodes::WIDE, wide);
+			assert!(matches!(read_at(&code, 0), Ok(Instructions::IINC(i, v)) if i == index && v == value));
+			assert_eq!(instruction_len(&code, 0).unwrap(), code.len());
+		}
+	}
+
+	#[test]
+	fn invalid_wide() {
+		for widened in [Opcodes::NOP, Opcodes::BIPUSH, Opcodes::GOTO, Opcodes::WIDE] {
+			let code = [Opcodes::NOP, Opcodes::WIDE, widened, 0, 0, 0, 0];
+			assert!(matches!(
+				read_at(&code, 1),
+				Err(IRClassfileError::InvalidOpcode { opcode, pc: 2 }) if opcode == widened
+			));
+			assert!(matches!(
+				instruction_len(&code, 1),
+				Err(IRClassfileError::InvalidOpcode { opcode, pc: 2 }) if opcode == widened
+			));
+		}
+
+		let code = [Opcodes::WIDE, Opcodes::IINC, 0, 1, 0];
+		assert!(matches!(read_at(&code, 0), Err(IRClassfileError::Bytes(_))));
+		assert!(matches!(
+			instruction_len(&code, 0),
+			Err(IRClassfileError::TruncatedCode(0))
+		));
+	}
+
+	#[test]
+	fn fixtures_decode() {
+		for bytes in CLASSES {
+			let class = class(bytes);
+			for method in &class.methods {
+				let Some(IRAttribute::Code(code)) =
+					method.attributes.iter().map(|a| &a.attr).find(|a| a.name() == "Code")
+				else {
+					continue;
+				};
+				let mut buffer = Cursor::new(&code.code);
+				while (buffer.position() as usize) < code.code.len() {
+					let pc = buffer.position() as usize;
+					Instructions::read(&class.cp, &mut buffer).unwrap();
+					assert_eq!(
+						buffer.position() as usize - pc,
+						instruction_len(&code.code, pc).unwrap()
+					);
+				}
+			}
+		}
+	}
+
+	#[test]
+	fn decode_operands() {
+		let mut class = class(HELLO);
+		let string = class.intern_class("[[Ljava/lang/String;").unwrap().index.to_be_bytes();
+		#[rustfmt::skip]
+		let code = [
+			0x84, 3, 0xFF, // iinc 3, -1
+			0xC5, string[0], string[1], 2, // multianewarray [[Ljava/lang/String;, 2
+			0xC8, 0xFF, 0xFF, 0xFF, 0xF0, // goto_w -16
+			0x11, 0x80, 0x00, // sipush -32768
+			0x47, // dstore_0
+			0xC4, 0x84, 0x01, 0x2C, 0x80, 0x00, // wide iinc 300, -32768
+			0xC4, 0x19, 0x01, 0x00, // wide aload 256
+			0xC4, 0x10, 0x00, 0x00, // wide bipush
+			0xCA, // breakpoint
+		];
+		let mut buffer = Cursor::new(&code);
+		let mut read = || Instructions::read(&class.cp, &mut buffer);
+		assert!(matches!(read(), Ok(Instructions::IINC(3, -1))));
+		assert!(
+			matches!(read(), Ok(Instructions::MULTIANEWARRAY(ty, 2)) if ty.data.data.as_str() == "[[Ljava/lang/String;")
+		);
+		assert!(matches!(read(), Ok(Instructions::GOTO_W(-16))));
+		assert!(matches!(read(), Ok(Instructions::SIPUSH(i16::MIN))));
+		assert!(matches!(read(), Ok(Instructions::DSTORE(0))));
+		assert!(matches!(read(), Ok(Instructions::IINC(300, i16::MIN))));
+		assert!(matches!(read(), Ok(Instructions::ALOAD(256))));
+		assert!(matches!(
+			read(),
+			Err(IRClassfileError::InvalidOpcode { opcode: 0x10, pc: 27 })
+		));
+		assert_eq!(instruction_len(&code, 16).unwrap(), 6);
+		assert!(matches!(
+			instruction_len(&code, 26),
+			Err(IRClassfileError::InvalidOpcode { opcode: 0x10, pc: 27 })
+		));
+		assert!(matches!(read(), Err(IRClassfileError::UnknownOpcode(0xCA))));
+	}
+
+	#[test]
+	fn switches_align_to_code_start() {
+		// switch operands are aligned to the start of the code, not of the instruction
+		let word = |value: i32| value.to_be_bytes();
+		let code: Vec<u8> = [
+			&[0x00, 0xAA, 0, 0][..],
+			&word(20),
+			&word(1),
+			&word(2),
+			&word(8),
+			&word(12),
+		]
+		.into_iter()
+		.chain([&[0xAB, 0, 0, 0][..], &word(-24), &word(1), &word(5), &word(10)])
+		.flatten()
+		.copied()
+		.collect();
+		assert_eq!(instruction_len(&code, 1).unwrap(), 23);
+		assert_eq!(instruction_len(&code, 24).unwrap(), 20);
+		let mut buffer = Cursor::new(&code);
+		let mut read = || Instructions::read(&[], &mut buffer);
+		assert!(matches!(read(), Ok(Instructions::NOP)));
+		assert!(matches!(
+			read(),
+			Ok(Instructions::TABLESWITCH { default: 20, low: 1, high: 2, offsets }) if offsets == [8, 12]
+		));
+		assert!(matches!(
+			read(),
+			Ok(Instructions::LOOKUPSWITCH { default: -24, pairs }) if pairs == [(5, 10)]
+		));
+		assert_eq!(buffer.position() as usize, code.len());
+	}
+
+	#[test]
+	fn fixtures_encode_back() {
+		// javac always picks the shortest form, so decoding and encoding gives back the same bytes
+		for bytes in CLASSES {
+			let class = class(bytes);
+			for method in &class.methods {
+				let Some(IRAttribute::Code(code)) =
+					method.attributes.iter().map(|a| &a.attr).find(|a| a.name() == "Code")
+				else {
+					continue;
+				};
+				let mut buffer = Cursor::new(&code.code);
+				let mut instructions = Vec::new();
+				while (buffer.position() as usize) < code.code.len() {
+					instructions.push(Instructions::read(&class.cp, &mut buffer).unwrap());
+				}
+				assert_eq!(encode_all(&instructions), code.code, "{}", method.name());
+			}
+		}
+	}
+
+	#[test]
+	fn shortest_forms() {
+		let class = class(HELLO);
+		let constant = CPTagRef::from_cp(&class.cp, 1);
+		let code = encode_all(&[
+			Instructions::ILOAD(2),
+			Instructions::ALOAD(200),
+			Instructions::DSTORE(300),
+			Instructions::IINC(1, 200),
+			Instructions::LDC(CPTagRef {
+				index: 256,
+				..constant.clone()
+			}),
+			Instructions::LDC(constant),
+			Instructions::TABLESWITCH {
+				default: 0,
+				low: 0,
+				high: 0,
+				offsets: vec![4],
+			},
+		]);
+		#[rustfmt::skip]
+		assert_eq!(code, [
+			0x1C, // iload_2
+			0x19, 200, // aload 200
+			0xC4, 0x39, 0x01, 0x2C, // wide dstore 300
+			0xC4, 0x84, 0x00, 0x01, 0x00, 200, // wide iinc 1, 200
+			0x13, 0x01, 0x00, // ldc_w #256
+			0x12, 0x01, // ldc #1
+			0xAA, 0, // tableswitch, padded to pc 20
+			0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 4,
+		]);
+	}
+
+	#[test]
+	fn write_invalid_tableswitch() {
+		let mut buffer = Cursor::new(Vec::new());
+		let invalid = Instructions::TABLESWITCH {
+			default: 0,
+			low: 1,
+			high: 0,
+			offsets: Vec::new(),
+		};
+		assert!(matches!(
+			invalid.write(&mut buffer),
+			Err(IRClassfileError::InvalidOpcode { opcode: 0xAA, pc: 0 })
+		));
+	}
 }
@@ -0,0 +1,461 @@
+//! Dropping unreferenced constant pool entries, see [`IRClassFile::compact_constant_pool`].
+
+use crate::{
+	attribute::{
+		CodeAttribute, ConstantValueAttribute, IRAttribute, IRAttributeInfo, RuntimeAnnotation, RuntimeAnnotationValue,
+		RuntimeTypeAnnotation, StackMapFrame, VerificationTypeInfo,
+	},
+	class_pool::{CPMethodHandleRef, CPNameAndTypeRef, IRClassfileError, IRCpTag},
+	code::visit_cp_operands,
+	IRClassFile,
+};
+
+type Visitor<'a> = &'a mut dyn FnMut(&mut u16);
+
+impl IRClassFile {
+	/// Removes every constant pool entry nothing in the class refers to and renumbers the rest,
+	/// keeping their order. Every index in the class, its members, attributes and bytecode is
+	/// rewritten to match. Returns how many slots were freed.
+	///
+	/// Unknown attributes are kept as raw bytes and might hold indices, so a class with any of
+	/// them is left untouched and [`IRClassfileError::OpaqueAttribute`] is returned.
+	pub fn compact_constant_pool(&mut self) -> Result<usize, IRClassfileError> {
+		let mut used = vec![false; self.cp.len() + 1];
+		let mut pending = Vec::new();
+		let mut mark = |index: &mut u16| {
+			if !used[*index as usize] {
+				used[*index as usize] = true;
+				pending.push(*index);
+			}
+		};
+		self.visit_cp_indices(&mut mark)?;
+
+		// entries pull in whatever they refer to themselves
+		while let Some(index) = pending.pop() {
+			visit_tag(&mut self.cp[index as usize - 1], &mut |index| {
+				if !used[*index as usize] {
+					used[*index as usize] = true;
+					pending.push(*index);
+				}
+			});
+		}
+
+		let mut remap = vec![0u16; self.cp.len() + 1];
+		let mut next = 1;
+		for index in 1..=self.cp.len() {
+			let keep = used[index] || (matches!(self.cp[index - 1], IRCpTag::Unusable) && used[index - 1]);
+			if keep {
+				remap[index] = next;
+				next += 1;
+			}
+		}
+		let removed = self.cp.len() + 1 - next as usize;
+		if removed == 0 {
+			return Ok(0);
+		}
+
+		let mut renumber = |index: &mut u16| *index = remap[*index as usize];
+		self.visit_cp_indices(&mut renumber)?;
+		for tag in self.cp.iter_mut() {
+			visit_tag(tag, &mut renumber);
+		}
+		let mut index = 0;
+		self.cp.retain(|_| {
+			index += 1;
+			remap[index] != 0
+		});
+		Ok(removed)
+	}
+
+	/// Every constant pool index held outside the pool itself. Index 0 means "none" wherever it's
+	/// allowed and is never passed to `f`.
+	fn visit_cp_indices(&mut self, f: Visitor) -> Result<(), IRClassfileError> {
+		let f = &mut |index: &mut u16| {
+			if *index != 0 {
+				f(index)
+			}
+		};
+
+		visit_named(&mut self.this_class.index, &mut self.this_class.data.index, f);
+		visit_named(&mut self.super_class.index, &mut self.super_class.data.index, f);
+		for interface in &mut self.interfaces {
+			visit_named(&mut interface.index, &mut interface.data.index, f);
+		}
+		for field in &mut self.fields {
+			f(&mut field.name.index);
+			f(&mut field.descriptor.index);
+			visit_attributes(&mut field.attributes, f)?;
+		}
+		for method in &mut self.methods {
+			f(&mut method.name.index);
+			f(&mut method.descriptor.index);
+			visit_attributes(&mut method.attributes, f)?;
+		}
+		visit_attributes(&mut self.attributes, f)
+	}
+}
+
+/// A Class, Module or Package ref, along with the Utf8 index the IR keeps next to it.
+fn visit_named(index: &mut u16, name: &mut u16, f: Visitor) {
+	f(index);
+	f(name);
+}
+
+fn visit_name_and_type(name_and_ty: &mut CPNameAndTypeRef, f: Visitor) {
+	f(&mut name_and_ty.index);
+	f(&mut name_and_ty.name.index);
+	f(&mut name_and_ty.ty.index);
+}
+
+fn visit_method_handle(handle: &mut CPMethodHandleRef, f: Visitor) {
+	f(&mut handle.index);
+	f(&mut handle.ref_index);
+	visit_tag(&mut handle.ref_tag, f);
+}
+
+/// The indices a pool entry refers to, including the copies the IR keeps of nested entries.
+fn visit_tag(tag: &mut IRCpTag, f: Visitor) {
+	match tag {
+		IRCpTag::Unusable
+		| IRCpTag::Utf8(_)
+		| IRCpTag::Integer(_)
+		| IRCpTag::Float(_)
+		| IRCpTag::Long(_)
+		| IRCpTag::Double(_) => {}
+		IRCpTag::Class(name)
+		| IRCpTag::String(name)
+		| IRCpTag::MethodType(name)
+		| IRCpTag::Module { name }
+		| IRCpTag::Package { name } => f(&mut name.index),
+		IRCpTag::FieldRef {
+			class_index,
+			name_and_ty,
+		}
+		| IRCpTag::MethodRef {
+			class_index,
+			name_and_ty,
+		}
+		| IRCpTag::InterfaceMethodRef {
+			class_index,
+			name_and_ty,
+		} => {
+			f(class_index);
+			visit_name_and_type(name_and_ty, f);
+		}
+		IRCpTag::NameAndType { name, descriptor } => {
+			f(&mut name.index);
+			f(&mut descriptor.index);
+		}
+		IRCpTag::MethodHandle { ref_index, ref_tag, .. } => {
+			f(ref_index);
+			visit_tag(ref_tag, f);
+		}
+		IRCpTag::Dynamic { name_and_ty, .. } | IRCpTag::InvokeDynamic { name_and_ty, .. } => {
+			visit_name_and_type(name_and_ty, f)
+		}
+	}
+}
+
+fn visit_attributes(attributes: &mut [IRAttributeInfo], f: Visitor) -> Result<(), IRClassfileError> {
+	for attribute in attributes {
+		visit_attribute(attribute, f)?;
+	}
+	Ok(())
+}
+
+pub(crate) fn visit_attribute(attribute: &mut IRAttributeInfo, f: Visitor) -> Result<(), IRClassfileError> {
+	f(&mut attribute.name.index);
+	match &mut attribute.attr {
+		IRAttribute::ConstantValue(value) => match value {
+			ConstantValueAttribute::Long { cp_idx, .. }
+			| ConstantValueAttribute::Float { cp_idx, .. }
+			| ConstantValueAttribute::Double { cp_idx, .. }
+			| ConstantValueAttribute::Int { cp_idx, .. } => f(cp_idx),
+			ConstantValueAttribute::String(string) => visit_named(&mut string.index, &mut string.data.index, f),
+		},
+		IRAttribute::Code(code) => visit_code(code, f)?,
+		IRAttribute::StackMapTable(table) => {
+			for frame in &mut table.entries {
+				visit_frame(frame, f);
+			}
+		}
+		IRAttribute::Exceptions { exception_index_table } => {
+			for exception in exception_index_table {
+				visit_named(&mut exception.index, &mut exception.data.index, f);
+			}
+		}
+		IRAttribute::InnerClasses(inner) => {
+			for class in &mut inner.classes {
+				visit_named(
+					&mut class.inner_class_info.index,
+					&mut class.inner_class_info.data.index,
+					f,
+				);
+				if let Some(outer) = &mut class.outer_class_info {
+					visit_named(&mut outer.index, &mut outer.data.index, f);
+				}
+				if let Some(name) = &mut class.inner_name {
+					f(&mut name.index);
+				}
+			}
+		}
+		IRAttribute::EnclosingMethod { class, method } => {
+			visit_named(&mut class.index, &mut class.data.index, f);
+			visit_name_and_type(method, f);
+		}
+		IRAttribute::Synthetic
+		| IRAttribute::Deprecated
+		| IRAttribute::SourceDebugExtension(_)
+		| IRAttribute::LineNumberTable(_) => {}
+		IRAttribute::Signature(utf8) | IRAttribute::SourceFile(utf8) => f(&mut utf8.index),
+		IRAttribute::LocalVariableTable { table } => {
+			for entry in table {
+				f(&mut entry.name.index);
+				f(&mut entry.descriptor.index);
+			}
+		}
+		IRAttribute::LocalVariableTypeTable { table } => {
+			for entry in table {
+				f(&mut entry.name.index);
+				f(&mut entry.signature.index);
+			}
+		}
+		IRAttribute::RuntimeVisibleAnnotations { annotations }
+		| IRAttribute::RuntimeInvisibleAnnotations { annotations } => {
+			for annotation in annotations {
+				visit_annotation(annotation, f);
+			}
+		}
+		IRAttribute::RuntimeVisibleParameterAnnotations { params }
+		| IRAttribute::RuntimeInvisibleParameterAnnotations { params } => {
+			for annotation in params.iter_mut().flatten() {
+				visit_annotation(annotation, f);
+			}
+		}
+		IRAttribute::AnnotationDefault { default_value } => visit_annotation_value(default_value, f),
+		IRAttribute::BootstrapMethods { methods } => {
+			for method in methods {
+				visit_method_handle(&mut method.method, f);
+				for argument in &mut method.arguments {
+					f(&mut argument.index);
+					visit_tag(&mut argument.tag, f);
+				}
+			}
+		}
+		IRAttribute::NestMembers { classes } | IRAttribute::PermittedSubclasses { classes } => {
+			for class in classes {
+				visit_named(&mut class.index, &mut class.data.index, f);
+			}
+		}
+		IRAttribute::NestHost(class) | IRAttribute::ModuleMainClass { class } => {
+			visit_named(&mut class.index, &mut class.data.index, f)
+		}
+		IRAttribute::MethodParameters { parameters } => {
+			for name in parameters.iter_mut().filter_map(|param| param.name.as_mut()) {
+				f(&mut name.index);
+			}
+		}
+		IRAttribute::Record { components } => {
+			for component in components {
+				f(&mut component.name.index);
+				f(&mut component.descriptor.index);
+				visit_attributes(&mut component.attributes, f)?;
+			}
+		}
+		IRAttribute::RuntimeVisibleTypeAnnotations { annotations }
+		| IRAttribute::RuntimeInvisibleTypeAnnotations { annotations } => {
+			for annotation in annotations {
+				visit_type_annotation(annotation, f);
+			}
+		}
+		IRAttribute::Module {
+			module_name,
+			module_version,
+			requires,
+			exports,
+			opens,
+			uses,
+			provides,
+			..
+		} => {
+			visit_named(&mut module_name.index, &mut module_name.data.index, f);
+			if let Some(version) = module_version {
+				f(&mut version.index);
+			}
+			for entry in requires {
+				visit_named(&mut entry.module.index, &mut entry.module.data.index, f);
+				if let Some(version) = &mut entry.version {
+					f(&mut version.index);
+				}
+			}
+			for entry in exports {
+				visit_named(&mut entry.package.index, &mut entry.package.data.index, f);
+				for module in &mut entry.exports {
+					visit_named(&mut module.index, &mut module.data.index, f);
+				}
+			}
+			for entry in opens {
+				visit_named(&mut entry.package.index, &mut entry.package.data.index, f);
+				for module in &mut entry.opens {
+					visit_named(&mut module.index, &mut module.data.index, f);
+				}
+			}
+			for class in uses {
+				visit_named(&mut class.index, &mut class.data.index, f);
+			}
+			for entry in provides {
+				visit_named(&mut entry.class.index, &mut entry.class.data.index, f);
+				for class in &mut entry.provides {
+					visit_named(&mut class.index, &mut class.data.index, f);
+				}
+			}
+		}
+		IRAttribute::ModulePackages { packages } => {
+			for package in packages {
+				visit_named(&mut package.index, &mut package.data.index, f);
+			}
+		}
+		#[cfg(feature = "preview")]
+		IRAttribute::Preview(preview) => {
+			if let crate::preview::PreviewAttribute::LoadableDescriptors { descriptors } = preview {
+				for descriptor in descriptors {
+					f(&mut descriptor.index);
+				}
+			}
+		}
+		IRAttribute::Unknown { name, .. } => return Err(IRClassfileError::OpaqueAttribute(name.data.to_string())),
+	}
+	Ok(())
+}
+
+fn visit_code(code: &mut CodeAttribute, f: Visitor) -> Result<(), IRClassfileError> {
+	visit_cp_operands(&mut code.code, f)?;
+	for exception in &mut code.exception_table {
+		f(&mut exception.catch_type);
+	}
+	for attribute in &mut code.attributes {
+		visit_attribute(attribute, f)?;
+	}
+	Ok(())
+}
+
+fn visit_frame(frame: &mut StackMapFrame, f: Visitor) {
+	let (locals, stack): (&mut [VerificationTypeInfo], &mut [VerificationTypeInfo]) = match frame {
+		StackMapFrame::SameFrame { .. } | StackMapFrame::ChopFrame { .. } | StackMapFrame::SameFrameExtended { .. } => {
+			return
+		}
+		StackMapFrame::SameLocals1StackItemFrame { stack, .. }
+		| StackMapFrame::SameLocals1StackItemFrameExtended { stack, .. } => (&mut [], std::slice::from_mut(stack)),
+		StackMapFrame::AppendFrame { locals, .. } => (locals, &mut []),
+		StackMapFrame::FullFrame { locals, stack, .. } => (locals, stack),
+	};
+	for info in locals.iter_mut().chain(stack) {
+		if let VerificationTypeInfo::ObjectVariableInfo { cpool_idx } = info {
+			f(cpool_idx);
+		}
+	}
+}
+
+fn visit_annotation(annotation: &mut RuntimeAnnotation, f: Visitor) {
+	f(&mut annotation.ty.index);
+	for pair in &mut annotation.pairs {
+		f(&mut pair.name.index);
+		visit_annotation_value(&mut pair.value, f);
+	}
+}
+
+fn visit_annotation_value(value: &mut RuntimeAnnotationValue, f: Visitor) {
+	match value {
+		RuntimeAnnotationValue::ConstValueIndex { value, .. } => f(&mut value.index),
+		RuntimeAnnotationValue::EnumConstValue { type_name, const_name } => {
+			f(&mut type_name.index);
+			f(&mut const_name.index);
+		}
+		RuntimeAnnotationValue::ClassInfoIndex(utf8) => f(&mut utf8.index),
+		RuntimeAnnotationValue::Annotation(annotation) => visit_annotation(annotation, f),
+		RuntimeAnnotationValue::ArrayValue { values } => {
+			for value in values {
+				visit_annotation_value(value, f);
+			}
+		}
+	}
+}
+
+fn visit_type_annotation(annotation: &mut RuntimeTypeAnnotation, f: Visitor) {
+	f(&mut annotation.type_index);
+	for pair in &mut annotation.pairs {
+		f(&mut pair.name.index);
+		visit_annotation_value(&mut pair.value, f);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use maya_classfile_io::{class_pool::IOCpTag, IOAttributeInfo, IOClassFile};
+
+	use super::*;
+	use crate::{
+		code::Instructions,
+		test_util::{class, method_code, CLASSES, HELLO},
+	};
+
+	#[test]
+	fn fixtures_read_back() {
+		for (i, bytes) in CLASSES.iter().enumerate() {
+			let mut compacted = class(bytes);
+			let before = compacted.cp.len();
+			let removed = compacted.compact_constant_pool().unwrap();
+			assert_eq!(compacted.cp.len(), before - removed, "class {i}");
+			assert_eq!(compacted.compact_constant_pool().unwrap(), 0, "class {i}");
+
+			// nothing but the pool moved, so the class still reads back the same way
+			let mut written = Vec::new();
+			compacted.write(&mut written).unwrap();
+			let reread = class(&written);
+			assert_eq!(reread.class_name(), class(bytes).class_name());
+			assert_eq!(reread.methods.len(), compacted.methods.len());
+		}
+	}
+
+	#[test]
+	fn drops_unused_entries() {
+		let mut hello = class(HELLO);
+		let before = hello.cp.len();
+		hello.remove_method("thrower", "()V").unwrap();
+		hello.remove_method("stackmapper", "(ILjava/lang/Object;)V").unwrap();
+		let removed = hello.compact_constant_pool().unwrap();
+		assert!(removed > 0);
+		assert_eq!(hello.cp.len(), before - removed);
+		assert!(!hello
+			.cp
+			.iter()
+			.any(|tag| matches!(tag, IRCpTag::Utf8(data) if data.as_str() == "thrower")));
+
+		let mut written = Vec::new();
+		hello.write(&mut written).unwrap();
+		let reread = class(&written);
+		let code = method_code(&reread, "main");
+		let mut instructions = std::io::Cursor::new(&code.code);
+		while (instructions.position() as usize) < code.code.len() {
+			Instructions::read(&reread.cp, &mut instructions).unwrap();
+		}
+	}
+
+	#[test]
+	fn opaque_attribute() {
+		let mut io = IOClassFile::read_from_slice(HELLO).unwrap();
+		io.cp.push(IOCpTag::Utf8 {
+			length: 8,
+			bytes: b"ScalaSig".to_vec(),
+		});
+		io.cp_count += 1;
+		io.attributes.push(IOAttributeInfo::new(io.cp_count - 1, vec![0, 1]));
+		let mut class = IRClassFile::from_io(io).unwrap();
+		let before = class.cp.len();
+		assert!(matches!(
+			class.compact_constant_pool(),
+			Err(IRClassfileError::OpaqueAttribute(name)) if name == "ScalaSig"
+		));
+		assert_eq!(class.cp.len(), before);
+	}
+}
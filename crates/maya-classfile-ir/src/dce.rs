@@ -0,0 +1,243 @@
+//! Taking out code that can't run, see [`InsnList::remove_unreachable`].
+
+use std::collections::HashMap;
+
+use crate::{
+	class_pool::IRClassfileError,
+	code::{Instructions, Opcodes},
+	insn::{Insn, InsnList, Label, TryCatchBlock},
+	IRClassFile,
+};
+
+/// What [`InsnList::remove_unreachable`] took out.
+#[derive(Debug, Clone, Default)]
+pub struct RemovedCode {
+	/// The instructions, in the order they were in, without labels, lines and frames.
+	pub insns: Vec<Insn>,
+	/// Exception table entries left without any instruction to protect.
+	pub try_catch_blocks: Vec<TryCatchBlock>,
+}
+
+impl RemovedCode {
+	pub fn is_empty(&self) -> bool {
+		self.insns.is_empty() && self.try_catch_blocks.is_empty()
+	}
+}
+
+impl InsnList {
+	/// Removes the instructions that can't be reached from the method's entry, following branches,
+	/// subroutine calls and the handlers of protected ranges with a reachable instruction in them.
+	/// The frames in front of removed instructions go with them, and so do the exception table
+	/// entries that protect nothing anymore. Labels, lines and local variable ranges stay.
+	pub fn remove_unreachable(&mut self) -> Result<RemovedCode, IRClassfileError> {
+		let reachable = self.reachable()?;
+
+		let mut removed = RemovedCode::default();
+		let mut keep = reachable.clone();
+		for (i, insn) in self.insns.iter().enumerate() {
+			match insn {
+				Insn::Label(_) | Insn::Line(_) => keep[i] = true,
+				Insn::Frame { .. } => {
+					// a frame belongs to the next instruction, which there has to be
+					let next = self.insns[i + 1..]
+						.iter()
+						.position(|insn| !matches!(insn, Insn::Label(_) | Insn::Line(_) | Insn::Frame { .. }));
+					keep[i] = next.is_some_and(|next| reachable[i + 1 + next]);
+				}
+				_ if !reachable[i] => removed.insns.push(insn.clone()),
+				_ => {}
+			}
+		}
+		let mut keep = keep.into_iter();
+		self.insns.retain(|_| keep.next().unwrap());
+
+		let positions = self.label_positions();
+		self.try_catch_blocks.retain(|block| {
+			let protects = match (positions.get(&block.start), positions.get(&block.end)) {
+				(Some(&start), Some(&end)) => self.insns[start..end.max(start)]
+					.iter()
+					.any(|insn| !matches!(insn, Insn::Label(_) | Insn::Line(_) | Insn::Frame { .. })),
+				_ => false,
+			};
+			if !protects {
+				removed.try_catch_blocks.push(block.clone());
+			}
+			protects
+		});
+		Ok(removed)
+	}
+
+	/// Which of the instructions can run, labels, lines and frames count as running when the code
+	/// around them does.
+	fn reachable(&self) -> Result<Vec<bool>, IRClassfileError> {
+		let positions = self.label_positions();
+		let index = |label: &Label| {
+			positions
+				.get(label)
+				.copied()
+				.ok_or(IRClassfileError::UnplacedLabel(*label))
+		};
+		let ranges = self
+			.try_catch_blocks
+			.iter()
+			.map(|block| Ok((index(&block.start)?..index(&block.end)?, index(&block.handler)?)))
+			.collect::<Result<Vec<_>, IRClassfileError>>()?;
+
+		let mut reachable = vec![false; self.insns.len()];
+		let mut pending = vec![0];
+		while let Some(mut i) = pending.pop() {
+			while i < self.insns.len() && !reachable[i] {
+				reachable[i] = true;
+				let falls_through = match &self.insns[i] {
+					Insn::Label(_) | Insn::Line(_) | Insn::Frame { .. } => true,
+					Insn::Op(instruction) => !ends_flow(instruction),
+					Insn::Jump { opcode, target } => {
+						pending.push(index(target)?);
+						!matches!(*opcode, Opcodes::GOTO | Opcodes::GOTO_W)
+					}
+					Insn::TableSwitch { default, targets, .. } => {
+						for target in targets.iter().chain([default]) {
+							pending.push(index(target)?);
+						}
+						false
+					}
+					Insn::LookupSwitch { default, pairs } => {
+						for target in pairs.iter().map(|(_, target)| target).chain([default]) {
+							pending.push(index(target)?);
+						}
+						false
+					}
+				};
+				if !matches!(self.insns[i], Insn::Label(_) | Insn::Line(_) | Insn::Frame { .. }) {
+					for (range, handler) in &ranges {
+						if range.contains(&i) {
+							pending.push(*handler);
+						}
+					}
+				}
+				if !falls_through {
+					break;
+				}
+				i += 1;
+			}
+		}
+		Ok(reachable)
+	}
+
+	fn label_positions(&self) -> HashMap<Label, usize> {
+		self.insns
+			.iter()
+			.enumerate()
+			.filter_map(|(i, insn)| match insn {
+				Insn::Label(label) => Some((*label, i)),
+				_ => None,
+			})
+			.collect()
+	}
+}
+
+fn ends_flow(instruction: &Instructions) -> bool {
+	use Instructions::*;
+
+	matches!(
+		instruction,
+		IRETURN | LRETURN | FRETURN | DRETURN | ARETURN | RETURN | ATHROW | RET(_)
+	)
+}
+
+impl IRClassFile {
+	/// Removes a method's unreachable code, see [`InsnList::remove_unreachable`], and writes it back
+	/// if there was any. `None` if the method doesn't exist or has no code.
+	pub fn remove_unreachable_code(
+		&mut self,
+		name: &str,
+		descriptor: &str,
+	) -> Result<Option<RemovedCode>, IRClassfileError> {
+		let Some(mut insns) = self.method_insns(name, descriptor)? else {
+			return Ok(None);
+		};
+		let removed = insns.remove_unreachable()?;
+		if !removed.is_empty() {
+			self.set_method_insns(name, descriptor, &insns)?;
+		}
+		Ok(Some(removed))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		asm,
+		attribute::IRAttribute,
+		test_util::{class, find_attr, method_code, HELLO},
+	};
+
+	const DEAD: &str = r#"
+		.class public gen/Dead
+		.method public static f (I)I
+			goto L1
+		L0:
+			.frame int |
+			iconst_5
+			ireturn
+		L1:
+			.frame int |
+			iload_0
+			ifeq L3
+			iload_0
+			ireturn
+		L3:
+			.frame int |
+			iload_0
+			iconst_1
+			idiv
+		L4:
+			ireturn
+		L5:
+			.frame int | object java/lang/ArithmeticException
+			pop
+			iconst_0
+			ireturn
+		L6:
+			.frame int | object java/lang/Throwable
+			athrow
+		.catch java/lang/Throwable from L0 to L1 using L6
+		.catch java/lang/ArithmeticException from L3 to L4 using L5
+		.end method
+	"#;
+
+	#[test]
+	fn unreachable_code() {
+		let mut dead = asm::assemble(DEAD).unwrap();
+		let removed = dead.remove_unreachable_code("f", "(I)I").unwrap().unwrap();
+		assert_eq!(removed.insns.len(), 3);
+		assert!(matches!(removed.insns[2], Insn::Op(Instructions::ATHROW)));
+		assert_eq!(removed.try_catch_blocks.len(), 1);
+
+		let code = method_code(&dead, "f");
+		assert_eq!(code.code[..4], [0xA7, 0, 3, 0x1A]);
+		assert_eq!(code.exception_table.len(), 1);
+		let IRAttribute::StackMapTable(frames) = find_attr(&code.attributes, "StackMapTable") else {
+			unreachable!();
+		};
+		assert_eq!(frames.offsets().unwrap().len(), 3);
+		assert!(dead.remove_unreachable_code("f", "(I)I").unwrap().unwrap().is_empty());
+	}
+
+	#[test]
+	fn javac_code_has_none() {
+		let mut hello = class(HELLO);
+		for (name, descriptor) in hello
+			.methods
+			.iter()
+			.map(|m| (m.name().to_string(), m.descriptor().to_string()))
+			.collect::<Vec<_>>()
+		{
+			assert!(hello
+				.remove_unreachable_code(&name, &descriptor)
+				.unwrap()
+				.is_none_or(|removed| removed.is_empty()));
+		}
+	}
+}
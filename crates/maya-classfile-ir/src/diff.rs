@@ -0,0 +1,372 @@
+//! Comparing classes by content rather than layout, see [`IRClassFile::diff`].
+
+use std::{collections::HashMap, fmt};
+
+use crate::{
+	access::{ClassAccessFlags, FieldAccessFlags, MethodAccessFlags},
+	attribute::{IRAttribute, IRAttributeInfo},
+	compact::visit_attribute,
+	disasm::{cp_entry, cp_value},
+	ClassFileVersion, IRClassFile,
+};
+
+/// A field or method, identified the way the JVM resolves it.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Member {
+	pub name: String,
+	pub descriptor: String,
+}
+
+impl Member {
+	fn new(name: &str, descriptor: &str) -> Self {
+		Self {
+			name: name.to_string(),
+			descriptor: descriptor.to_string(),
+		}
+	}
+}
+
+impl fmt::Display for Member {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{}:{}", self.name, self.descriptor)
+	}
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Difference {
+	Version {
+		before: ClassFileVersion,
+		after: ClassFileVersion,
+	},
+	Name {
+		before: String,
+		after: String,
+	},
+	ClassFlags {
+		before: ClassAccessFlags,
+		after: ClassAccessFlags,
+	},
+	SuperClass {
+		before: Option<String>,
+		after: Option<String>,
+	},
+	InterfaceAdded(String),
+	InterfaceRemoved(String),
+	FieldAdded(Member),
+	FieldRemoved(Member),
+	FieldFlags {
+		field: Member,
+		before: FieldAccessFlags,
+		after: FieldAccessFlags,
+	},
+	FieldAttributes(Member),
+	MethodAdded(Member),
+	MethodRemoved(Member),
+	MethodFlags {
+		method: Member,
+		before: MethodAccessFlags,
+		after: MethodAccessFlags,
+	},
+	/// The Code attribute differs: the instructions, exception table, or anything nested in it.
+	MethodCode(Member),
+	MethodAttributes(Member),
+	ClassAttributes,
+}
+
+impl fmt::Display for Difference {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Difference::Version { before, after } => write!(
+				f,
+				"version {}.{} -> {}.{}",
+				before.major, before.minor, after.major, after.minor
+			),
+			Difference::Name { before, after } => write!(f, "name {before} -> {after}"),
+			Difference::ClassFlags { before, after } => write!(f, "flags '{before}' -> '{after}'"),
+			Difference::SuperClass { before, after } => write!(
+				f,
+				"super class {} -> {}",
+				before.as_deref().unwrap_or("<none>"),
+				after.as_deref().unwrap_or("<none>")
+			),
+			Difference::InterfaceAdded(name) => write!(f, "interface {name} added"),
+			Difference::InterfaceRemoved(name) => write!(f, "interface {name} removed"),
+			Difference::FieldAdded(field) => write!(f, "field {field} added"),
+			Difference::FieldRemoved(field) => write!(f, "field {field} removed"),
+			Difference::FieldFlags { field, before, after } => {
+				write!(f, "field {field} flags '{before}' -> '{after}'")
+			}
+			Difference::FieldAttributes(field) => write!(f, "field {field} attributes changed"),
+			Difference::MethodAdded(method) => write!(f, "method {method} added"),
+			Difference::MethodRemoved(method) => write!(f, "method {method} removed"),
+			Difference::MethodFlags { method, before, after } => {
+				write!(f, "method {method} flags '{before}' -> '{after}'")
+			}
+			Difference::MethodCode(method) => write!(f, "method {method} code changed"),
+			Difference::MethodAttributes(method) => write!(f, "method {method} attributes changed"),
+			Difference::ClassAttributes => write!(f, "class attributes changed"),
+		}
+	}
+}
+
+/// Everything [`IRClassFile::diff`] found, in class file order.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ClassDiff {
+	pub differences: Vec<Difference>,
+}
+
+impl ClassDiff {
+	pub fn is_empty(&self) -> bool {
+		self.differences.is_empty()
+	}
+}
+
+impl fmt::Display for ClassDiff {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		for difference in &self.differences {
+			writeln!(f, "{difference}")?;
+		}
+		Ok(())
+	}
+}
+
+impl IRClassFile {
+	/// Compares two classes by what they contain. Constant pool indices are compared by the
+	/// entries they resolve to, and attributes (including those nested in Code) by content in any
+	/// order. Member order doesn't matter either, members are matched by name and descriptor.
+	///
+	/// Unknown attributes can't be resolved, so they only compare equal when their raw bytes are.
+	pub fn diff(&self, other: &IRClassFile) -> ClassDiff {
+		let mut canonical = Canonical::default();
+		let mut differences = Vec::new();
+
+		if self.version != other.version {
+			differences.push(Difference::Version {
+				before: self.version,
+				after: other.version,
+			});
+		}
+		if self.class_name() != other.class_name() {
+			differences.push(Difference::Name {
+				before: self.class_name().to_string(),
+				after: other.class_name().to_string(),
+			});
+		}
+		if self.access_flags != other.access_flags {
+			differences.push(Difference::ClassFlags {
+				before: self.access_flags,
+				after: other.access_flags,
+			});
+		}
+		if self.super_name() != other.super_name() {
+			differences.push(Difference::SuperClass {
+				before: self.super_name().map(str::to_string),
+				after: other.super_name().map(str::to_string),
+			});
+		}
+
+		let before: Vec<_> = self.interface_names().collect();
+		let after: Vec<_> = other.interface_names().collect();
+		for name in before.iter().filter(|name| !after.contains(name)) {
+			differences.push(Difference::InterfaceRemoved(name.to_string()));
+		}
+		for name in after.iter().filter(|name| !before.contains(name)) {
+			differences.push(Difference::InterfaceAdded(name.to_string()));
+		}
+
+		for field in &self.fields {
+			let member = Member::new(field.name(), field.descriptor());
+			let Some(theirs) = other.find_field_typed(field.name(), field.descriptor()) else {
+				differences.push(Difference::FieldRemoved(member));
+				continue;
+			};
+			if field.access_flags != theirs.access_flags {
+				differences.push(Difference::FieldFlags {
+					field: member.clone(),
+					before: field.access_flags,
+					after: theirs.access_flags,
+				});
+			}
+			if canonical.attributes(self, &field.attributes) != canonical.attributes(other, &theirs.attributes) {
+				differences.push(Difference::FieldAttributes(member));
+			}
+		}
+		for field in &other.fields {
+			if self.find_field_typed(field.name(), field.descriptor()).is_none() {
+				differences.push(Difference::FieldAdded(Member::new(field.name(), field.descriptor())));
+			}
+		}
+
+		let is_code = |attribute: &&IRAttributeInfo| matches!(attribute.attr, IRAttribute::Code(_));
+		for method in &self.methods {
+			let member = Member::new(method.name(), method.descriptor());
+			let Some(theirs) = other.find_method(method.name(), method.descriptor()) else {
+				differences.push(Difference::MethodRemoved(member));
+				continue;
+			};
+			if method.access_flags != theirs.access_flags {
+				differences.push(Difference::MethodFlags {
+					method: member.clone(),
+					before: method.access_flags,
+					after: theirs.access_flags,
+				});
+			}
+			let (code, attributes): (Vec<_>, Vec<_>) = method.attributes.iter().partition(is_code);
+			let (their_code, their_attributes): (Vec<_>, Vec<_>) = theirs.attributes.iter().partition(is_code);
+			if canonical.attributes(self, code) != canonical.attributes(other, their_code) {
+				differences.push(Difference::MethodCode(member.clone()));
+			}
+			if canonical.attributes(self, attributes) != canonical.attributes(other, their_attributes) {
+				differences.push(Difference::MethodAttributes(member));
+			}
+		}
+		for method in &other.methods {
+			if self.find_method(method.name(), method.descriptor()).is_none() {
+				differences.push(Difference::MethodAdded(Member::new(method.name(), method.descriptor())));
+			}
+		}
+
+		if canonical.attributes(self, &self.attributes) != canonical.attributes(other, &other.attributes) {
+			differences.push(Difference::ClassAttributes);
+		}
+
+		ClassDiff { differences }
+	}
+
+	/// Whether [`diff`](Self::diff) finds nothing.
+	pub fn structural_eq(&self, other: &IRClassFile) -> bool {
+		self.diff(other).is_empty()
+	}
+}
+
+/// Gives every constant pool entry an id by what it resolves to, shared between both classes, so
+/// attributes can be compared once their indices are swapped for ids.
+#[derive(Default)]
+struct Canonical {
+	ids: HashMap<String, u32>,
+}
+
+impl Canonical {
+	/// One comparable string per attribute, sorted so their order doesn't matter.
+	fn attributes<'a>(
+		&mut self,
+		class: &IRClassFile,
+		attributes: impl IntoIterator<Item = &'a IRAttributeInfo>,
+	) -> Vec<String> {
+		let mut out: Vec<_> = attributes
+			.into_iter()
+			.map(|attribute| self.attribute(class, attribute))
+			.collect();
+		out.sort();
+		out
+	}
+
+	fn attribute(&mut self, class: &IRClassFile, attribute: &IRAttributeInfo) -> String {
+		let mut copy = attribute.clone();
+		let nested = match &mut copy.attr {
+			IRAttribute::Code(code) => {
+				let nested = std::mem::take(&mut code.attributes);
+				self.attributes(class, nested.iter().map(|attribute| &**attribute))
+			}
+			_ => Vec::new(),
+		};
+
+		// ids can outgrow a u8 ldc operand, so the full ids are compared as well as the rewritten copy
+		let mut ids = Vec::new();
+		let visited = visit_attribute(&mut copy, &mut |index| {
+			let id = match class.cp.get((*index as usize).wrapping_sub(1)) {
+				Some(tag) => {
+					let next = self.ids.len() as u32 + 1;
+					let key = format!("{} {}", cp_entry(tag).0, cp_value(class, *index));
+					*self.ids.entry(key).or_insert(next)
+				}
+				None => 0,
+			};
+			ids.push(id);
+			*index = id as u16;
+		});
+		match visited {
+			Ok(()) => format!("{copy:?} {ids:?} {nested:?}"),
+			Err(_) => format!("{attribute:?}"),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		class_pool::IRCpTag,
+		test_util::{class, CONSTANTS, HELLO},
+	};
+
+	#[test]
+	fn ignores_pool_layout_and_attribute_order() {
+		let mut original = class(HELLO);
+		original.remove_method("thrower", "()V").unwrap();
+		let mut rewritten = class(HELLO);
+		rewritten.remove_method("thrower", "()V").unwrap();
+		rewritten.compact_constant_pool().unwrap();
+		rewritten.attributes.reverse();
+		assert!(rewritten.cp.len() < original.cp.len());
+		assert!(original.structural_eq(&rewritten));
+		assert!(!class(HELLO).structural_eq(&class(CONSTANTS)));
+	}
+
+	#[test]
+	fn differences() {
+		let mut original = class(HELLO);
+		original.remove_method("thrower", "()V").unwrap();
+		let mut rewritten = class(HELLO);
+		rewritten.remove_method("thrower", "()V").unwrap();
+		rewritten.compact_constant_pool().unwrap();
+
+		rewritten
+			.remove_method("stackmapper", "(ILjava/lang/Object;)V")
+			.unwrap();
+		rewritten.find_method_mut("<init>", "()V").unwrap().access_flags = MethodAccessFlags::PRIVATE;
+		let other_string = rewritten
+			.cp
+			.iter()
+			.position(|tag| matches!(tag, IRCpTag::String(utf8) if utf8.data.as_str() == "fucker"))
+			.unwrap() as u8
+			+ 1;
+		let main = rewritten.find_method_mut("main", "([Ljava/lang/String;)V").unwrap();
+		let IRAttribute::Code(code) = &mut main.attributes[0].attr else {
+			panic!("expected code");
+		};
+		// ldc "Hello World!" -> ldc "fucker"
+		assert_eq!(code.code[3], 0x12);
+		code.code[4] = other_string;
+		rewritten
+			.add_field(FieldAccessFlags::PRIVATE, "added", "I", Vec::new())
+			.unwrap();
+
+		let diff = original.diff(&rewritten);
+		assert_eq!(
+			diff.differences,
+			vec![
+				Difference::FieldAdded(Member {
+					name: "added".to_string(),
+					descriptor: "I".to_string(),
+				}),
+				Difference::MethodFlags {
+					method: Member {
+						name: "<init>".to_string(),
+						descriptor: "()V".to_string(),
+					},
+					before: MethodAccessFlags::PUBLIC,
+					after: MethodAccessFlags::PRIVATE,
+				},
+				Difference::MethodCode(Member {
+					name: "main".to_string(),
+					descriptor: "([Ljava/lang/String;)V".to_string(),
+				}),
+				Difference::MethodRemoved(Member {
+					name: "stackmapper".to_string(),
+					descriptor: "(ILjava/lang/Object;)V".to_string(),
+				}),
+			]
+		);
+	}
+}
@@ -0,0 +1,504 @@
+//! A `javap -v -p` style listing of a class, see [`disassemble`].
+//!
+//! Names and types are printed in their internal form (`java/lang/String`, `(I)V`) rather than
+//! javap's Java syntax, everything else follows its layout.
+
+use std::fmt::{self, Write};
+
+use crate::{
+	attribute::{CodeAttribute, IRAttribute, IRAttributeInfo},
+	class_pool::{IRCpTag, IRMethodRefKind},
+	code::{instruction_len, mnemonic},
+	IRClassFile,
+};
+
+/// Renders the whole class: header, constant pool, members with their bytecode, then the class
+/// attributes.
+pub fn disassemble(class: &IRClassFile) -> String {
+	let mut out = String::new();
+	write_class(&mut out, class).expect("writing to a String can't fail");
+	out
+}
+
+fn write_class(out: &mut String, class: &IRClassFile) -> fmt::Result {
+	writeln!(out, "class {}", class.class_name())?;
+	writeln!(out, "  minor version: {}", class.version.minor)?;
+	writeln!(out, "  major version: {}", class.version.major)?;
+	writeln!(
+		out,
+		"  flags: ({:#06x}) {}",
+		class.access_flags.bits(),
+		class.access_flags
+	)?;
+	writeln!(
+		out,
+		"  this_class: #{:<27}// {}",
+		class.this_class.index,
+		class.class_name()
+	)?;
+	match class.super_name() {
+		Some(name) => writeln!(out, "  super_class: #{:<26}// {name}", class.super_class.index)?,
+		None => writeln!(out, "  super_class: #0")?,
+	}
+	writeln!(
+		out,
+		"  interfaces: {}, fields: {}, methods: {}, attributes: {}",
+		class.interfaces.len(),
+		class.fields.len(),
+		class.methods.len(),
+		class.attributes.len()
+	)?;
+
+	writeln!(out, "Constant pool:")?;
+	let width = class.cp.len().to_string().len() + 1;
+	for (i, tag) in class.cp.iter().enumerate() {
+		if matches!(tag, IRCpTag::Unusable) {
+			continue;
+		}
+		let index = format!("#{}", i + 1);
+		let (kind, operands) = cp_entry(tag);
+		let comment = match tag {
+			IRCpTag::Utf8(_) | IRCpTag::Integer(_) | IRCpTag::Float(_) | IRCpTag::Long(_) | IRCpTag::Double(_) => {
+				String::new()
+			}
+			_ => format!("// {}", cp_value(class, i as u16 + 1)),
+		};
+		let line = format!("  {index:>width$} = {kind:<18} {operands:<14} {comment}");
+		writeln!(out, "{}", line.trim_end())?;
+	}
+
+	writeln!(out, "{{")?;
+	for field in &class.fields {
+		writeln!(out, "  {} {}: {}", field.access_flags, field.name(), field.descriptor())?;
+		writeln!(out, "    descriptor: {}", field.descriptor())?;
+		writeln!(
+			out,
+			"    flags: ({:#06x}) {}",
+			field.access_flags.bits(),
+			field.access_flags
+		)?;
+		write_attributes(out, class, &field.attributes, 4)?;
+		writeln!(out)?;
+	}
+	for method in &class.methods {
+		writeln!(
+			out,
+			"  {} {}{}",
+			method.access_flags,
+			method.name(),
+			method.descriptor()
+		)?;
+		writeln!(out, "    descriptor: {}", method.descriptor())?;
+		writeln!(
+			out,
+			"    flags: ({:#06x}) {}",
+			method.access_flags.bits(),
+			method.access_flags
+		)?;
+		write_attributes(out, class, &method.attributes, 4)?;
+		writeln!(out)?;
+	}
+	writeln!(out, "}}")?;
+	write_attributes(out, class, &class.attributes, 0)
+}
+
+fn write_attributes(
+	out: &mut String,
+	class: &IRClassFile,
+	attributes: &[IRAttributeInfo],
+	indent: usize,
+) -> fmt::Result {
+	for attribute in attributes {
+		let pad = " ".repeat(indent);
+		match &attribute.attr {
+			IRAttribute::Code(code) => write_code(out, class, code, indent)?,
+			IRAttribute::SourceFile(name) => writeln!(out, "{pad}SourceFile: \"{}\"", name.data)?,
+			IRAttribute::Signature(signature) => {
+				writeln!(out, "{pad}Signature: #{:<27}// {}", signature.index, signature.data)?
+			}
+			IRAttribute::ConstantValue(value) => {
+				writeln!(out, "{pad}ConstantValue: {}", cp_comment(class, value.cp_index()))?
+			}
+			IRAttribute::Exceptions { exception_index_table } => {
+				writeln!(out, "{pad}Exceptions:")?;
+				for exception in exception_index_table {
+					writeln!(out, "{pad}  throws {}", exception.data.data)?;
+				}
+			}
+			IRAttribute::NestHost(host) => writeln!(out, "{pad}NestHost: class {}", host.data.data)?,
+			IRAttribute::NestMembers { classes } | IRAttribute::PermittedSubclasses { classes } => {
+				writeln!(out, "{pad}{}:", attribute.attr.name())?;
+				for member in classes {
+					writeln!(out, "{pad}  {}", member.data.data)?;
+				}
+			}
+			IRAttribute::InnerClasses(inner) => {
+				writeln!(out, "{pad}InnerClasses:")?;
+				for class in &inner.classes {
+					let name = class
+						.inner_name
+						.as_ref()
+						.map_or("<anonymous>", |name| name.data.as_str());
+					match &class.outer_class_info {
+						Some(outer) => writeln!(
+							out,
+							"{pad}  {name} = {} of {}",
+							class.inner_class_info.data.data, outer.data.data
+						)?,
+						None => writeln!(out, "{pad}  {name} = {}", class.inner_class_info.data.data)?,
+					}
+				}
+			}
+			IRAttribute::BootstrapMethods { methods } => {
+				writeln!(out, "{pad}BootstrapMethods:")?;
+				for (i, method) in methods.iter().enumerate() {
+					writeln!(
+						out,
+						"{pad}  {i}: #{} {}",
+						method.method.index,
+						cp_value(class, method.method.index)
+					)?;
+					writeln!(out, "{pad}    Method arguments:")?;
+					for argument in &method.arguments {
+						writeln!(
+							out,
+							"{pad}      #{} {}",
+							argument.index,
+							cp_value(class, argument.index)
+						)?;
+					}
+				}
+			}
+			other => writeln!(out, "{pad}{}", other.name())?,
+		}
+	}
+	Ok(())
+}
+
+fn write_code(out: &mut String, class: &IRClassFile, code: &CodeAttribute, indent: usize) -> fmt::Result {
+	let pad = " ".repeat(indent);
+	writeln!(out, "{pad}Code:")?;
+	writeln!(out, "{pad}  stack={}, locals={}", code.max_stack, code.max_locals)?;
+
+	let mut pc = 0;
+	while pc < code.code.len() {
+		let len = match instruction_len(&code.code, pc) {
+			Ok(len) => len,
+			Err(e) => {
+				writeln!(out, "{pad}  {pc:>6}: <{e}>")?;
+				break;
+			}
+		};
+		write_instruction(out, class, &code.code, pc, indent)?;
+		pc += len;
+	}
+
+	if !code.exception_table.is_empty() {
+		writeln!(out, "{pad}  Exception table:")?;
+		writeln!(out, "{pad}     from    to  target type")?;
+		for entry in &code.exception_table {
+			let ty = match entry.catch_type {
+				0 => "any".to_string(),
+				index => format!("Class {}", cp_value(class, index)),
+			};
+			writeln!(
+				out,
+				"{pad}    {:>5} {:>5} {:>5}   {ty}",
+				entry.start_pc, entry.end_pc, entry.handler_pc
+			)?;
+		}
+	}
+
+	for attribute in &code.attributes {
+		match &attribute.attr {
+			IRAttribute::LineNumberTable(table) => {
+				writeln!(out, "{pad}  LineNumberTable:")?;
+				for entry in &table.line_number_table {
+					writeln!(out, "{pad}    line {}: {}", entry.line_number, entry.start_pc)?;
+				}
+			}
+			IRAttribute::LocalVariableTable { table } => {
+				writeln!(out, "{pad}  LocalVariableTable:")?;
+				writeln!(out, "{pad}    Start  Length  Slot  Name   Signature")?;
+				for entry in table {
+					writeln!(
+						out,
+						"{pad}    {:>5}  {:>6}  {:>4}  {:>4}   {}",
+						entry.start_pc, entry.length, entry.index, entry.name.data, entry.descriptor.data
+					)?;
+				}
+			}
+			other => writeln!(out, "{pad}  {}", other.name())?,
+		}
+	}
+	Ok(())
+}
+
+fn write_instruction(out: &mut String, class: &IRClassFile, code: &[u8], pc: usize, indent: usize) -> fmt::Result {
+	let pad = " ".repeat(indent);
+	let opcode = code[pc];
+	let name = mnemonic(opcode).unwrap_or("<invalid>");
+	let u8_at = |at: usize| code[pc + at];
+	let u16_at = |at: usize| u16::from_be_bytes([code[pc + at], code[pc + at + 1]]);
+	let i32_at = |at: usize| i32::from_be_bytes(code[at..at + 4].try_into().unwrap());
+	let cp = |index: u16| format!("#{index}");
+
+	let (operands, comment) = match opcode {
+		0x10 => ((u8_at(1) as i8).to_string(), None),
+		0x11 => ((u16_at(1) as i16).to_string(), None),
+		0x12 => (cp(u8_at(1) as u16), Some(cp_comment(class, u8_at(1) as u16))),
+		0x13 | 0x14 | 0xB2..=0xB8 | 0xBB | 0xBD | 0xC0 | 0xC1 => (cp(u16_at(1)), Some(cp_comment(class, u16_at(1)))),
+		0xB9 => (
+			format!("{},  {}", cp(u16_at(1)), u8_at(3)),
+			Some(cp_comment(class, u16_at(1))),
+		),
+		0xBA => (format!("{},  0", cp(u16_at(1))), Some(cp_comment(class, u16_at(1)))),
+		0xC5 => (
+			format!("{},  {}", cp(u16_at(1)), u8_at(3)),
+			Some(cp_comment(class, u16_at(1))),
+		),
+		0x15..=0x19 | 0x36..=0x3A | 0xA9 => (u8_at(1).to_string(), None),
+		0x84 => (format!("{}, {}", u8_at(1), u8_at(2) as i8), None),
+		0x99..=0xA8 | 0xC6 | 0xC7 => ((pc as i64 + u16_at(1) as i16 as i64).to_string(), None),
+		0xC8 | 0xC9 => ((pc as i64 + i32_at(pc + 1) as i64).to_string(), None),
+		0xBC => (
+			match u8_at(1) {
+				4 => "boolean",
+				5 => "char",
+				6 => "float",
+				7 => "double",
+				8 => "byte",
+				9 => "short",
+				10 => "int",
+				11 => "long",
+				_ => "<invalid>",
+			}
+			.to_string(),
+			None,
+		),
+		0xC4 => {
+			let widened = mnemonic(u8_at(1)).unwrap_or("<invalid>");
+			let operands = if u8_at(1) == 0x84 {
+				format!("{widened} {}, {}", u16_at(2), u16_at(4) as i16)
+			} else {
+				format!("{widened} {}", u16_at(2))
+			};
+			(operands, None)
+		}
+		0xAA | 0xAB => {
+			let operands = (pc + 4) & !3;
+			let default = pc as i64 + i32_at(operands) as i64;
+			let mut cases = Vec::new();
+			let header = if opcode == 0xAA {
+				let (low, high) = (i32_at(operands + 4), i32_at(operands + 8));
+				for (i, key) in (low..=high).enumerate() {
+					cases.push((key, pc as i64 + i32_at(operands + 12 + i * 4) as i64));
+				}
+				format!("{{ // {low} to {high}")
+			} else {
+				let pairs = i32_at(operands + 4) as usize;
+				for i in 0..pairs {
+					let at = operands + 8 + i * 8;
+					cases.push((i32_at(at), pc as i64 + i32_at(at + 4) as i64));
+				}
+				format!("{{ // {pairs}")
+			};
+
+			writeln!(out, "{pad}  {pc:>6}: {name:<13} {header}")?;
+			for (key, target) in cases {
+				writeln!(out, "{pad}  {key:>22}: {target}")?;
+			}
+			writeln!(out, "{pad}  {:>22}: {default}", "default")?;
+			return writeln!(out, "{pad}          }}");
+		}
+		_ => (String::new(), None),
+	};
+
+	let line = match comment {
+		Some(comment) => format!("{pad}  {pc:>6}: {name:<13} {operands:<18} // {comment}"),
+		None => format!("{pad}  {pc:>6}: {name:<13} {operands}"),
+	};
+	writeln!(out, "{}", line.trim_end())
+}
+
+/// The comment javap prints next to a constant pool operand, the entry's kind and its value.
+fn cp_comment(class: &IRClassFile, index: u16) -> String {
+	let kind = match class.cp.get((index as usize).wrapping_sub(1)) {
+		Some(IRCpTag::FieldRef { .. }) => "Field ",
+		Some(IRCpTag::MethodRef { .. }) => "Method ",
+		Some(IRCpTag::InterfaceMethodRef { .. }) => "InterfaceMethod ",
+		Some(IRCpTag::Class(_)) => "class ",
+		Some(IRCpTag::String(_)) => "String ",
+		Some(IRCpTag::Integer(_)) => "int ",
+		Some(IRCpTag::Float(_)) => "float ",
+		Some(IRCpTag::Long(_)) => "long ",
+		Some(IRCpTag::Double(_)) => "double ",
+		Some(IRCpTag::MethodType(_)) => "MethodType ",
+		Some(IRCpTag::MethodHandle { .. }) => "MethodHandle ",
+		Some(IRCpTag::Dynamic { .. }) => "Dynamic ",
+		Some(IRCpTag::InvokeDynamic { .. }) => "InvokeDynamic ",
+		_ => "",
+	};
+	format!("{kind}{}", cp_value(class, index))
+}
+
+/// The kind and raw operands of an entry as in javap's pool table.
+pub(crate) fn cp_entry(tag: &IRCpTag) -> (&'static str, String) {
+	match tag {
+		IRCpTag::Unusable => ("Unusable", String::new()),
+		IRCpTag::Utf8(data) => ("Utf8", data.to_string()),
+		IRCpTag::Integer(value) => ("Integer", value.to_string()),
+		IRCpTag::Float(value) => ("Float", format!("{value:?}f")),
+		IRCpTag::Long(value) => ("Long", format!("{value}l")),
+		IRCpTag::Double(value) => ("Double", format!("{value:?}d")),
+		IRCpTag::Class(name) => ("Class", format!("#{}", name.index)),
+		IRCpTag::String(utf8) => ("String", format!("#{}", utf8.index)),
+		IRCpTag::FieldRef {
+			class_index,
+			name_and_ty,
+		} => ("Fieldref", format!("#{class_index}.#{}", name_and_ty.index)),
+		IRCpTag::MethodRef {
+			class_index,
+			name_and_ty,
+		} => ("Methodref", format!("#{class_index}.#{}", name_and_ty.index)),
+		IRCpTag::InterfaceMethodRef {
+			class_index,
+			name_and_ty,
+		} => ("InterfaceMethodref", format!("#{class_index}.#{}", name_and_ty.index)),
+		IRCpTag::NameAndType { name, descriptor } => ("NameAndType", format!("#{}:#{}", name.index, descriptor.index)),
+		IRCpTag::MethodHandle {
+			ref_kind, ref_index, ..
+		} => ("MethodHandle", format!("{}:#{ref_index}", ref_kind.clone() as u8)),
+		IRCpTag::MethodType(descriptor) => ("MethodType", format!("#{}", descriptor.index)),
+		IRCpTag::Dynamic {
+			bootstrap_method_attr_index,
+			name_and_ty,
+		} => (
+			"Dynamic",
+			format!("#{bootstrap_method_attr_index}:#{}", name_and_ty.index),
+		),
+		IRCpTag::InvokeDynamic {
+			bootstrap_method_attr_index,
+			name_and_ty,
+		} => (
+			"InvokeDynamic",
+			format!("#{bootstrap_method_attr_index}:#{}", name_and_ty.index),
+		),
+		IRCpTag::Module { name } => ("Module", format!("#{}", name.index)),
+		IRCpTag::Package { name } => ("Package", format!("#{}", name.index)),
+	}
+}
+
+/// The resolved value of the entry at `index`, followed through every reference.
+pub(crate) fn cp_value(class: &IRClassFile, index: u16) -> String {
+	let Some(tag) = class.cp.get((index as usize).wrapping_sub(1)) else {
+		return format!("<invalid #{index}>");
+	};
+	let class_name = |index: u16| match class.cp.get((index as usize).wrapping_sub(1)) {
+		Some(IRCpTag::Class(name)) => name.data.to_string(),
+		_ => format!("<invalid #{index}>"),
+	};
+
+	match tag {
+		IRCpTag::Unusable => "<unusable>".to_string(),
+		IRCpTag::Utf8(data) => data.to_string(),
+		IRCpTag::Integer(value) => value.to_string(),
+		IRCpTag::Float(value) => format!("{value:?}f"),
+		IRCpTag::Long(value) => format!("{value}l"),
+		IRCpTag::Double(value) => format!("{value:?}d"),
+		IRCpTag::Class(name) => name.data.to_string(),
+		IRCpTag::String(utf8) => utf8.data.escape_debug().to_string(),
+		IRCpTag::FieldRef {
+			class_index,
+			name_and_ty,
+		}
+		| IRCpTag::MethodRef {
+			class_index,
+			name_and_ty,
+		}
+		| IRCpTag::InterfaceMethodRef {
+			class_index,
+			name_and_ty,
+		} => {
+			let name = match name_and_ty.name.data.as_str() {
+				name @ ("<init>" | "<clinit>") => format!("\"{name}\""),
+				name => name.to_string(),
+			};
+			format!("{}.{name}:{}", class_name(*class_index), name_and_ty.ty.data)
+		}
+		IRCpTag::NameAndType { name, descriptor } => format!("{}:{}", name.data, descriptor.data),
+		IRCpTag::MethodHandle {
+			ref_kind, ref_index, ..
+		} => {
+			format!("{} {}", ref_kind_name(ref_kind), cp_value(class, *ref_index))
+		}
+		IRCpTag::MethodType(descriptor) => descriptor.data.to_string(),
+		IRCpTag::Dynamic {
+			bootstrap_method_attr_index,
+			name_and_ty,
+		}
+		| IRCpTag::InvokeDynamic {
+			bootstrap_method_attr_index,
+			name_and_ty,
+		} => format!(
+			"#{bootstrap_method_attr_index}:{}:{}",
+			name_and_ty.name.data, name_and_ty.ty.data
+		),
+		IRCpTag::Module { name } | IRCpTag::Package { name } => name.data.to_string(),
+	}
+}
+
+fn ref_kind_name(kind: &IRMethodRefKind) -> &'static str {
+	match kind {
+		IRMethodRefKind::GetField => "REF_getField",
+		IRMethodRefKind::GetStatic => "REF_getStatic",
+		IRMethodRefKind::PutField => "REF_putField",
+		IRMethodRefKind::PutStatic => "REF_putStatic",
+		IRMethodRefKind::InvokeVirtual => "REF_invokeVirtual",
+		IRMethodRefKind::InvokeStatic => "REF_invokeStatic",
+		IRMethodRefKind::InvokeSpecial => "REF_invokeSpecial",
+		IRMethodRefKind::NewInvokeSpecial => "REF_newInvokeSpecial",
+		IRMethodRefKind::InvokeInterface => "REF_invokeInterface",
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::test_util::{class, HELLO};
+
+	fn hello() -> String {
+		disassemble(&class(HELLO))
+	}
+
+	#[test]
+	fn class_header() {
+		let listing = hello();
+		assert!(listing.starts_with("class a/Hello\n"));
+		assert!(listing.contains("  flags: (0x0031) public final super\n"));
+	}
+
+	#[test]
+	fn constant_pool() {
+		assert!(hello()
+			.contains("   #7 = Fieldref           #8.#9          // java/lang/System.out:Ljava/io/PrintStream;\n"));
+	}
+
+	#[test]
+	fn code_listing() {
+		let listing = hello();
+		assert!(listing.contains(
+			"           0: getstatic     #7                 // Field java/lang/System.out:Ljava/io/PrintStream;\n"
+		));
+		assert!(listing.contains(
+			"          33: invokedynamic #25,  0            // InvokeDynamic #0:get:()Ljava/util/function/Supplier;\n"
+		));
+	}
+
+	#[test]
+	fn attributes() {
+		let listing = hello();
+		assert!(listing.contains("    ConstantValue: String Hello World!\n"));
+		assert!(listing.contains("SourceFile: \"Hello.java\"\n"));
+	}
+}
@@ -0,0 +1,201 @@
+//! Editing a method's code by the offsets of its instructions, see [`IRClassFile::edit_code`].
+//!
+//! The edits are made to an [`InsnList`], so exception ranges, line numbers, local variable ranges,
+//! frames and branches stay with the instructions they point at and get their new offsets when
+//! [`CodeEditor::finish`] writes the code back.
+
+use std::{collections::HashMap, io::Cursor};
+
+use crate::{
+	attribute::IRAttribute,
+	class_pool::IRClassfileError,
+	code::Instructions,
+	insn::{Insn, InsnList, Label},
+	IRClassFile,
+};
+
+/// Edits to one method's code. Offsets are the ones the instructions had when editing started,
+/// however much has been inserted or removed since, and an instruction that was removed or
+/// replaced can't be edited again.
+pub struct CodeEditor<'a> {
+	class: &'a mut IRClassFile,
+	name: String,
+	descriptor: String,
+	insns: InsnList,
+	/// A label placed right before each original instruction still there, by its offset.
+	instructions: HashMap<usize, Label>,
+}
+
+impl IRClassFile {
+	/// Starts editing a method's code, `None` if the method doesn't exist or has no code.
+	pub fn edit_code(&mut self, name: &str, descriptor: &str) -> Result<Option<CodeEditor<'_>>, IRClassfileError> {
+		let Some(mut insns) = self.method_insns(name, descriptor)? else {
+			return Ok(None);
+		};
+		let code = self
+			.find_method(name, descriptor)
+			.unwrap()
+			.attributes
+			.iter()
+			.find_map(|attribute| match &attribute.attr {
+				IRAttribute::Code(code) => Some(&code.code),
+				_ => None,
+			})
+			.unwrap();
+		let mut pcs = Vec::new();
+		let mut buffer = Cursor::new(code);
+		while (buffer.position() as usize) < code.len() {
+			pcs.push(buffer.position() as usize);
+			Instructions::read(&self.cp, &mut buffer)?;
+		}
+
+		// every instruction decodes to one of these, in order
+		let mut pcs = pcs.into_iter();
+		let mut instructions = HashMap::with_capacity(pcs.len());
+		let mut marked = Vec::with_capacity(insns.insns.len() + pcs.len());
+		for insn in std::mem::take(&mut insns.insns) {
+			if matches!(
+				insn,
+				Insn::Op(_) | Insn::Jump { .. } | Insn::TableSwitch { .. } | Insn::LookupSwitch { .. }
+			) {
+				let label = insns.new_label();
+				instructions.insert(pcs.next().unwrap(), label);
+				marked.push(Insn::Label(label));
+			}
+			marked.push(insn);
+		}
+		insns.insns = marked;
+
+		Ok(Some(CodeEditor {
+			class: self,
+			name: name.to_string(),
+			descriptor: descriptor.to_string(),
+			insns,
+			instructions,
+		}))
+	}
+}
+
+impl CodeEditor<'_> {
+	/// The code being edited, for changes that don't go by offset.
+	pub fn insns(&mut self) -> &mut InsnList {
+		&mut self.insns
+	}
+
+	/// Inserts `insns` before the instruction at `pc`. Branches to `pc` and ranges that start there
+	/// take in the inserted code, ranges that end there don't.
+	pub fn insert_before(&mut self, pc: usize, insns: impl IntoIterator<Item = Insn>) -> Result<(), IRClassfileError> {
+		let i = self.position(pc)?;
+		self.insns.insns.splice(i..i, insns);
+		Ok(())
+	}
+
+	/// Inserts `insns` after the instruction at `pc`, where ranges covering the instruction cover them
+	/// too.
+	pub fn insert_after(&mut self, pc: usize, insns: impl IntoIterator<Item = Insn>) -> Result<(), IRClassfileError> {
+		let i = self.position(pc)? + 2;
+		self.insns.insns.splice(i..i, insns);
+		Ok(())
+	}
+
+	/// Removes the instruction at `pc`, whatever pointed at it points at what comes after it.
+	pub fn remove(&mut self, pc: usize) -> Result<Insn, IRClassfileError> {
+		let i = self.position(pc)?;
+		self.instructions.remove(&pc);
+		Ok(self.insns.insns.remove(i + 1))
+	}
+
+	/// Replaces the instruction at `pc` with `insns`, whatever pointed at it points at them.
+	pub fn replace(&mut self, pc: usize, insns: impl IntoIterator<Item = Insn>) -> Result<Insn, IRClassfileError> {
+		let i = self.position(pc)?;
+		self.instructions.remove(&pc);
+		Ok(self.insns.insns.splice(i + 1..i + 2, insns).next().unwrap())
+	}
+
+	/// Writes the code back, raising `max_stack` and `max_locals` if it needs more.
+	pub fn finish(self) -> Result<(), IRClassfileError> {
+		self.class
+			.store_method_insns(&self.name, &self.descriptor, &self.insns)?;
+		Ok(())
+	}
+
+	/// Where the label before the instruction at `pc` is.
+	fn position(&self, pc: usize) -> Result<usize, IRClassfileError> {
+		let label = self.instructions.get(&pc);
+		self.insns
+			.insns
+			.iter()
+			.position(|insn| matches!(insn, Insn::Label(placed) if Some(placed) == label))
+			.ok_or(IRClassfileError::NotAnInstruction(pc))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::test_util::{class, find_attr, method_code, TYPED};
+
+	#[test]
+	fn insert_and_replace() {
+		let mut class = class(TYPED);
+		let descriptor = "(I)Ljava/lang/String;";
+		let mut editor = class.edit_code("method", descriptor).unwrap().unwrap();
+		// the try block starts at 20, so this goes in it
+		editor
+			.insert_before(20, [Insn::Op(Instructions::NOP), Insn::Op(Instructions::NOP)])
+			.unwrap();
+		let removed = editor
+			.replace(58, [Insn::Op(Instructions::ACONST_NULL), Insn::Op(Instructions::NOP)])
+			.unwrap();
+		assert!(matches!(removed, Insn::Op(Instructions::ACONST_NULL)));
+		assert!(matches!(editor.remove(58), Err(IRClassfileError::NotAnInstruction(58))));
+		assert!(matches!(editor.remove(21), Err(IRClassfileError::NotAnInstruction(21))));
+		editor.finish().unwrap();
+
+		let code = method_code(&class, "method");
+		let handler = &code.exception_table[0];
+		assert_eq!((handler.start_pc, handler.end_pc, handler.handler_pc), (20, 58, 59));
+		assert_eq!(code.code[20..22], [0x00, 0x00]);
+		assert_eq!(code.code[59..], [0x4E, 0x01, 0x00, 0xB0]);
+		let IRAttribute::LineNumberTable(lines) = find_attr(&code.attributes, "LineNumberTable") else {
+			unreachable!();
+		};
+		let lines = lines
+			.line_number_table
+			.iter()
+			.map(|entry| (entry.start_pc, entry.line_number))
+			.collect::<Vec<_>>();
+		assert_eq!(
+			lines,
+			[
+				(0, 15),
+				(8, 16),
+				(15, 17),
+				(20, 20),
+				(28, 21),
+				(35, 22),
+				(59, 23),
+				(60, 24)
+			]
+		);
+	}
+
+	#[test]
+	fn empty_try_block() {
+		// emptying the try block of `run` drops its exception table entry
+		let mut class = class(TYPED);
+		let mut editor = class.edit_code("run", "()V").unwrap().unwrap();
+		for pc in [0, 5, 6, 7, 10, 11] {
+			editor.remove(pc).unwrap();
+		}
+		editor.finish().unwrap();
+		let code = method_code(&class, "run");
+		assert!(code.exception_table.is_empty());
+		assert_eq!(code.code, [0xA7, 0, 4, 0x4C, 0xB1]);
+	}
+
+	#[test]
+	fn missing_method() {
+		assert!(class(TYPED).edit_code("missing", "()V").unwrap().is_none());
+	}
+}
@@ -0,0 +1,470 @@
+//! `invokedynamic` call sites and dynamic constants together with their bootstrap method and its
+//! static arguments, see [`DynamicCallSite`], and recognizing the ones javac makes for lambdas and
+//! string concatenation.
+
+use crate::{
+	attribute::{BootstrapMethodsMethod, IRAttribute},
+	class_pool::{CPClassRef, CPDynamicRef, CPInvokeDynamicRef, CPNameAndTypeRef, CPUtf8Ref, IRCpTag, IRMethodRefKind},
+	code::parameters,
+	IRClassFile,
+};
+
+const LAMBDA_METAFACTORY: &str = "java/lang/invoke/LambdaMetafactory";
+const STRING_CONCAT_FACTORY: &str = "java/lang/invoke/StringConcatFactory";
+
+// flags of `LambdaMetafactory.altMetafactory`
+const FLAG_SERIALIZABLE: i32 = 1;
+const FLAG_MARKERS: i32 = 2;
+const FLAG_BRIDGES: i32 = 4;
+
+/// A method handle constant with the member it refers to looked up.
+#[derive(Debug, Clone)]
+pub struct MethodHandle {
+	pub kind: IRMethodRefKind,
+	pub owner: CPClassRef,
+	pub name_and_ty: CPNameAndTypeRef,
+	pub index: u16,
+}
+
+impl MethodHandle {
+	/// The MethodHandle constant at `index`, `None` if there's none or what it refers to isn't a
+	/// field or method.
+	pub fn from_cp(cp: &[IRCpTag], index: u16) -> Option<Self> {
+		let IRCpTag::MethodHandle {
+			ref_kind, ref_index, ..
+		} = cp.get((index as usize).checked_sub(1)?)?
+		else {
+			return None;
+		};
+		let (class_index, name_and_ty) = match cp.get((*ref_index as usize).checked_sub(1)?)? {
+			IRCpTag::FieldRef {
+				class_index,
+				name_and_ty,
+			}
+			| IRCpTag::MethodRef {
+				class_index,
+				name_and_ty,
+			}
+			| IRCpTag::InterfaceMethodRef {
+				class_index,
+				name_and_ty,
+			} => (*class_index, name_and_ty),
+			_ => return None,
+		};
+		let owner = match cp.get((class_index as usize).checked_sub(1)?)? {
+			tag @ IRCpTag::Class(_) => CPClassRef::new(class_index, tag),
+			_ => return None,
+		};
+		Some(Self {
+			kind: ref_kind.clone(),
+			owner,
+			name_and_ty: name_and_ty.clone(),
+			index,
+		})
+	}
+
+	/// Whether this is `owner.name` called statically.
+	fn is_static_call(&self, owner: &str, name: &str) -> bool {
+		matches!(self.kind, IRMethodRefKind::InvokeStatic)
+			&& self.owner.data.data.as_str() == owner
+			&& self.name_and_ty.name.data.as_str() == name
+	}
+}
+
+/// A static argument of a bootstrap method.
+#[derive(Debug, Clone)]
+pub enum BootstrapArgument {
+	Integer(i32),
+	Float(f32),
+	Long(i64),
+	Double(f64),
+	String(CPUtf8Ref),
+	/// The internal name of a class, or the descriptor of an array class.
+	Class(CPUtf8Ref),
+	MethodHandle(MethodHandle),
+	/// A method descriptor.
+	MethodType(CPUtf8Ref),
+	Dynamic(DynamicConstant),
+}
+
+/// A bootstrap method and its static arguments.
+#[derive(Debug, Clone)]
+pub struct Bootstrap {
+	pub method: MethodHandle,
+	pub arguments: Vec<BootstrapArgument>,
+}
+
+impl Bootstrap {
+	/// The entry of the BootstrapMethods attribute at `attr_index`. `visiting` has the dynamic
+	/// constants being resolved, a constant that is its own argument can't be.
+	fn resolve(class: &IRClassFile, attr_index: u16, visiting: &mut Vec<u16>) -> Option<Self> {
+		let entry = class.bootstrap_method(attr_index)?;
+		let method = MethodHandle::from_cp(&class.cp, entry.method.index)?;
+		let arguments = entry
+			.arguments
+			.iter()
+			.map(|argument| {
+				Some(match &argument.tag {
+					IRCpTag::Integer(value) => BootstrapArgument::Integer(*value),
+					IRCpTag::Float(value) => BootstrapArgument::Float(*value),
+					IRCpTag::Long(value) => BootstrapArgument::Long(*value),
+					IRCpTag::Double(value) => BootstrapArgument::Double(*value),
+					IRCpTag::String(value) => BootstrapArgument::String(value.clone()),
+					IRCpTag::Class(name) => BootstrapArgument::Class(name.clone()),
+					IRCpTag::MethodHandle { .. } => {
+						BootstrapArgument::MethodHandle(MethodHandle::from_cp(&class.cp, argument.index)?)
+					}
+					IRCpTag::MethodType(descriptor) => BootstrapArgument::MethodType(descriptor.clone()),
+					IRCpTag::Dynamic { .. } => {
+						BootstrapArgument::Dynamic(DynamicConstant::resolve(class, argument.index, visiting)?)
+					}
+					_ => return None,
+				})
+			})
+			.collect::<Option<_>>()?;
+		Some(Self { method, arguments })
+	}
+}
+
+/// A `CONSTANT_Dynamic` constant and how it's made.
+#[derive(Debug, Clone)]
+pub struct DynamicConstant {
+	pub constant: CPDynamicRef,
+	pub bootstrap: Bootstrap,
+}
+
+impl DynamicConstant {
+	fn resolve(class: &IRClassFile, index: u16, visiting: &mut Vec<u16>) -> Option<Self> {
+		if !matches!(class.cp.get((index as usize).checked_sub(1)?)?, IRCpTag::Dynamic { .. })
+			|| visiting.contains(&index)
+		{
+			return None;
+		}
+		let constant = CPDynamicRef::from_cp(&class.cp, index);
+		visiting.push(index);
+		let bootstrap = Bootstrap::resolve(class, constant.bootstrap_method_attr_index, visiting);
+		visiting.pop();
+		Some(Self {
+			constant,
+			bootstrap: bootstrap?,
+		})
+	}
+}
+
+/// An `invokedynamic` call site and how it's linked.
+#[derive(Debug, Clone)]
+pub struct DynamicCallSite {
+	pub call_site: CPInvokeDynamicRef,
+	pub bootstrap: Bootstrap,
+}
+
+impl DynamicCallSite {
+	/// The name the call site is linked with, for a lambda the name of the interface's method.
+	pub fn name(&self) -> &str {
+		self.call_site.name_and_ty.name.data.as_str()
+	}
+
+	/// The descriptor of the call: the values the call site takes off the stack and what it leaves.
+	pub fn descriptor(&self) -> &str {
+		self.call_site.name_and_ty.ty.data.as_str()
+	}
+
+	/// The lambda or method reference this call site makes, if it's linked by `LambdaMetafactory`.
+	pub fn lambda(&self) -> Option<Lambda<'_>> {
+		let method = &self.bootstrap.method;
+		let alternate = method.is_static_call(LAMBDA_METAFACTORY, "altMetafactory");
+		if !alternate && !method.is_static_call(LAMBDA_METAFACTORY, "metafactory") {
+			return None;
+		}
+		let arguments = &self.bootstrap.arguments;
+		let (
+			Some(BootstrapArgument::MethodType(erased)),
+			Some(BootstrapArgument::MethodHandle(implementation)),
+			Some(BootstrapArgument::MethodType(instantiated)),
+		) = (arguments.first(), arguments.get(1), arguments.get(2))
+		else {
+			return None;
+		};
+		let mut lambda = Lambda {
+			interface: self.descriptor().split_once(")L")?.1.strip_suffix(';')?,
+			method: self.name(),
+			erased,
+			implementation,
+			instantiated,
+			serializable: false,
+			markers: Vec::new(),
+			bridges: Vec::new(),
+		};
+		if !alternate {
+			return (arguments.len() == 3).then_some(lambda);
+		}
+
+		let mut rest = arguments[3..].iter();
+		let Some(BootstrapArgument::Integer(flags)) = rest.next() else {
+			return None;
+		};
+		lambda.serializable = flags & FLAG_SERIALIZABLE != 0;
+		if flags & FLAG_MARKERS != 0 {
+			let Some(BootstrapArgument::Integer(count)) = rest.next() else {
+				return None;
+			};
+			for _ in 0..*count {
+				match rest.next()? {
+					BootstrapArgument::Class(name) => lambda.markers.push(name),
+					_ => return None,
+				}
+			}
+		}
+		if flags & FLAG_BRIDGES != 0 {
+			let Some(BootstrapArgument::Integer(count)) = rest.next() else {
+				return None;
+			};
+			for _ in 0..*count {
+				match rest.next()? {
+					BootstrapArgument::MethodType(descriptor) => lambda.bridges.push(descriptor),
+					_ => return None,
+				}
+			}
+		}
+		rest.next().is_none().then_some(lambda)
+	}
+
+	/// The pieces of the string this call site puts together, if it's linked by
+	/// `StringConcatFactory`.
+	pub fn string_concat(&self) -> Option<Vec<ConcatPart<'_>>> {
+		let method = &self.bootstrap.method;
+		if method.is_static_call(STRING_CONCAT_FACTORY, "makeConcat") {
+			return Some(
+				(0..parameters(self.descriptor()).count())
+					.map(ConcatPart::Argument)
+					.collect(),
+			);
+		}
+		if !method.is_static_call(STRING_CONCAT_FACTORY, "makeConcatWithConstants") {
+			return None;
+		}
+
+		let Some((BootstrapArgument::String(recipe), mut constants)) = self
+			.bootstrap
+			.arguments
+			.split_first()
+			.map(|(recipe, constants)| (recipe, constants.iter()))
+		else {
+			return None;
+		};
+		let mut parts = Vec::new();
+		let mut text = String::new();
+		let mut argument = 0;
+		for c in recipe.data.chars() {
+			let part = match c {
+				'\u{1}' => {
+					argument += 1;
+					ConcatPart::Argument(argument - 1)
+				}
+				'\u{2}' => ConcatPart::Constant(constants.next()?),
+				c => {
+					text.push(c);
+					continue;
+				}
+			};
+			if !text.is_empty() {
+				parts.push(ConcatPart::Text(std::mem::take(&mut text)));
+			}
+			parts.push(part);
+		}
+		if !text.is_empty() {
+			parts.push(ConcatPart::Text(text));
+		}
+		(argument == parameters(self.descriptor()).count() && constants.next().is_none()).then_some(parts)
+	}
+}
+
+/// What `LambdaMetafactory` is asked to make.
+#[derive(Debug, Clone)]
+pub struct Lambda<'a> {
+	/// Internal name of the functional interface.
+	pub interface: &'a str,
+	/// Name of the interface's method.
+	pub method: &'a str,
+	/// Descriptor of the interface's method, after erasure.
+	pub erased: &'a CPUtf8Ref,
+	/// The method called with the captured values followed by the interface method's arguments.
+	pub implementation: &'a MethodHandle,
+	/// Descriptor of the interface's method with the type arguments of the interface filled in.
+	pub instantiated: &'a CPUtf8Ref,
+	pub serializable: bool,
+	/// More interfaces the lambda implements.
+	pub markers: Vec<&'a CPUtf8Ref>,
+	/// More descriptors the interface's method is implemented with.
+	pub bridges: Vec<&'a CPUtf8Ref>,
+}
+
+/// A piece of a string concatenation.
+#[derive(Debug, Clone)]
+pub enum ConcatPart<'a> {
+	Text(String),
+	/// The argument of the call site at this index, counting long and double as one.
+	Argument(usize),
+	Constant(&'a BootstrapArgument),
+}
+
+impl IRClassFile {
+	/// Entries of the class's BootstrapMethods attribute, empty if it has none.
+	pub fn bootstrap_methods(&self) -> &[BootstrapMethodsMethod] {
+		self.attributes
+			.iter()
+			.find_map(|attr| match &attr.attr {
+				IRAttribute::BootstrapMethods { methods } => Some(methods.as_slice()),
+				_ => None,
+			})
+			.unwrap_or_default()
+	}
+
+	/// The entry a `bootstrap_method_attr_index` points at.
+	pub fn bootstrap_method(&self, attr_index: u16) -> Option<&BootstrapMethodsMethod> {
+		self.bootstrap_methods().get(attr_index as usize)
+	}
+
+	/// The bootstrap method of the Dynamic or InvokeDynamic constant at `cp_index`.
+	pub fn bootstrap_method_for(&self, cp_index: u16) -> Option<&BootstrapMethodsMethod> {
+		match self.cp.get((cp_index as usize).checked_sub(1)?)? {
+			IRCpTag::Dynamic {
+				bootstrap_method_attr_index,
+				..
+			}
+			| IRCpTag::InvokeDynamic {
+				bootstrap_method_attr_index,
+				..
+			} => self.bootstrap_method(*bootstrap_method_attr_index),
+			_ => None,
+		}
+	}
+
+	/// The InvokeDynamic constant at `cp_index` with its bootstrap method, `None` if there's no such
+	/// constant or the bootstrap method or one of its arguments is missing.
+	pub fn call_site(&self, cp_index: u16) -> Option<DynamicCallSite> {
+		let Some(IRCpTag::InvokeDynamic {
+			bootstrap_method_attr_index,
+			..
+		}) = self.cp.get((cp_index as usize).checked_sub(1)?)
+		else {
+			return None;
+		};
+		Some(DynamicCallSite {
+			call_site: CPInvokeDynamicRef::from_cp(&self.cp, cp_index),
+			bootstrap: Bootstrap::resolve(self, *bootstrap_method_attr_index, &mut Vec::new())?,
+		})
+	}
+
+	/// The Dynamic constant at `cp_index` with its bootstrap method, like [`IRClassFile::call_site`].
+	pub fn dynamic_constant(&self, cp_index: u16) -> Option<DynamicConstant> {
+		DynamicConstant::resolve(self, cp_index, &mut Vec::new())
+	}
+
+	/// Every InvokeDynamic constant of the class that can be resolved, in constant pool order.
+	pub fn call_sites(&self) -> impl Iterator<Item = DynamicCallSite> + '_ {
+		(1..=self.cp.len() as u16).filter_map(|index| self.call_site(index))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		attribute::{BootstrapMethodsMethod, IRAttribute},
+		class_pool::{CPMethodHandleRef, CPTagRef},
+		intern_constant, intern_name_and_type,
+		test_util::{class, HELLO},
+	};
+
+	#[test]
+	fn lambda() {
+		let class = class(HELLO);
+		let call_site = class.call_site(25).unwrap();
+		assert_eq!(call_site.name(), "get");
+		let lambda = call_site.lambda().unwrap();
+		assert_eq!(lambda.interface, "java/util/function/Supplier");
+		assert_eq!(lambda.erased.data.as_str(), "()Ljava/lang/Object;");
+		assert_eq!(lambda.implementation.owner.data.data.as_str(), "a/Hello");
+		assert_eq!(
+			lambda.implementation.name_and_ty.name.data.as_str(),
+			"lambda$stackmapper$0"
+		);
+		assert!(!lambda.serializable);
+		assert!(call_site.string_concat().is_none());
+		assert_eq!(class.call_sites().count(), 1);
+		assert!(class.call_site(26).is_none());
+	}
+
+	#[test]
+	fn string_concat() {
+		let mut class = class(HELLO);
+		let factory = class
+			.intern_method_ref(
+				"java/lang/invoke/StringConcatFactory",
+				"makeConcatWithConstants",
+				"(Ljava/lang/invoke/MethodHandles$Lookup;Ljava/lang/String;Ljava/lang/invoke/MethodType;Ljava/lang/String;[Ljava/lang/Object;)Ljava/lang/invoke/CallSite;",
+				false,
+			)
+			.unwrap();
+		let ref_tag = Box::new(class.cp[factory.index as usize - 1].clone());
+		let handle = class
+			.cp
+			.push(IRCpTag::MethodHandle {
+				ref_kind: IRMethodRefKind::InvokeStatic,
+				ref_index: factory.index,
+				ref_tag,
+			})
+			.unwrap();
+		let recipe = class.intern_string("x=\u{1}, \u{2}!").unwrap().index;
+		let constant = intern_constant(&mut class.cp, IRCpTag::Integer(7)).unwrap();
+		let Some(IRAttribute::BootstrapMethods { methods }) = class
+			.attributes
+			.iter_mut()
+			.map(|attr| &mut attr.attr)
+			.find(|attr| matches!(attr, IRAttribute::BootstrapMethods { .. }))
+		else {
+			panic!("no BootstrapMethods");
+		};
+		methods.push(BootstrapMethodsMethod {
+			method: CPMethodHandleRef::from_cp(&class.cp, handle),
+			arguments: vec![
+				CPTagRef::from_cp(&class.cp, recipe),
+				CPTagRef::from_cp(&class.cp, constant),
+			],
+		});
+		let name_and_ty =
+			intern_name_and_type(&mut class.cp, "makeConcatWithConstants", "(J)Ljava/lang/String;").unwrap();
+		let index = class
+			.cp
+			.push(IRCpTag::InvokeDynamic {
+				bootstrap_method_attr_index: 1,
+				name_and_ty,
+			})
+			.unwrap();
+		let call_site = class.call_site(index).unwrap();
+		assert!(call_site.lambda().is_none());
+		let parts = call_site.string_concat().unwrap();
+		assert_eq!(parts.len(), 5);
+		assert!(matches!(&parts[0], ConcatPart::Text(text) if text == "x="));
+		assert!(matches!(parts[1], ConcatPart::Argument(0)));
+		assert!(matches!(&parts[2], ConcatPart::Text(text) if text == ", "));
+		assert!(matches!(parts[3], ConcatPart::Constant(BootstrapArgument::Integer(7))));
+		assert!(matches!(&parts[4], ConcatPart::Text(text) if text == "!"));
+		assert_eq!(class.call_sites().count(), 2);
+	}
+
+	#[test]
+	fn invokedynamic_linkage() {
+		let class = class(HELLO);
+		let bootstrap = class.bootstrap_method_for(25).unwrap();
+		assert_eq!(bootstrap.method.index, 89);
+		assert_eq!(bootstrap.arguments.len(), 3);
+		assert_eq!(bootstrap.arguments[1].index, 85);
+
+		let indy = CPInvokeDynamicRef::from_cp(&class.cp, 25);
+		assert_eq!(indy.bootstrap_method(&class).unwrap().method.index, 89);
+		assert!(class.bootstrap_method_for(26).is_none());
+		assert!(class.bootstrap_method(1).is_none());
+	}
+}
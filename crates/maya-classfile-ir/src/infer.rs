@@ -0,0 +1,350 @@
+//! Working out a stack map frame from the code leading up to it, for the frames
+//! [`InsnList::write_to`] adds when it rewrites a conditional branch that can't reach its target
+//! and the ones [`MethodBuilder::handler`](crate::builder::MethodBuilder::handler) adds.
+
+use crate::{
+	attribute::VerificationTypeInfo,
+	class_pool::{ConstantPool, IRCpTag},
+	code::{parameters, Instructions, Opcodes},
+	insn::{FrameValue, Insn, InsnList, Label},
+	intern_class,
+	interp::{execute, Domain, Frame, Kind, KindDomain},
+};
+
+impl InsnList {
+	/// The locals and stack right before `insns[index]`, from the frame nearest before it, or the
+	/// method's entry, and the code in between. `None` if that code doesn't only fall through, or a
+	/// type in the frame depends on the class hierarchy or on the class the method belongs to.
+	pub(crate) fn frame_before(
+		&self,
+		cp: &mut ConstantPool,
+		index: usize,
+	) -> Option<(Vec<FrameValue>, Vec<FrameValue>)> {
+		let start = self.insns[..index]
+			.iter()
+			.rposition(|insn| matches!(insn, Insn::Frame { .. }));
+		let (locals, stack) = match start.map(|start| &self.insns[start]) {
+			Some(Insn::Frame { locals, stack }) => (locals.clone(), stack.clone()),
+			_ => (
+				self.initial_locals.iter().cloned().map(FrameValue::Type).collect(),
+				Vec::new(),
+			),
+		};
+		let mut frame = Frame::empty();
+		let mut slot = 0;
+		for value in locals {
+			let wide = is_wide(&value);
+			frame.store(slot, Some(value), wide);
+			slot += 1 + wide as u16;
+		}
+		for value in stack {
+			let wide = is_wide(&value);
+			frame.push(Some(value), wide);
+		}
+
+		let mut domain = VerificationDomain::new(cp, &self.insns);
+		for i in start.map_or(0, |start| start + 1)..index {
+			match &self.insns[i] {
+				Insn::Label(_) | Insn::Line(_) => {}
+				Insn::Op(instruction) => {
+					// the constructor call turns every copy of the object it initializes into its class
+					let receiver = match instruction {
+						Instructions::INVOKESPECIAL(method) if method.name_and_ty.name.data.as_str() == "<init>" => {
+							let arguments = parameters(&method.name_and_ty.ty.data).count();
+							Some(frame.top(arguments + 1)?[0].clone())
+						}
+						_ => None,
+					};
+					if !execute(&mut domain, i, instruction, &mut frame).ok()? {
+						return None;
+					}
+					if let Some(receiver) = receiver {
+						let initialized = match &receiver {
+							Some(FrameValue::Uninitialized(label)) => domain.created(*label)?,
+							_ => return None,
+						};
+						for value in frame.locals.iter_mut().flatten().chain(&mut frame.stack) {
+							if *value == receiver {
+								*value = Some(initialized.clone());
+							}
+						}
+					}
+				}
+				Insn::Jump { opcode, .. }
+					if !matches!(*opcode, Opcodes::GOTO | Opcodes::GOTO_W | Opcodes::JSR | Opcodes::JSR_W) =>
+				{
+					let operands = match *opcode {
+						Opcodes::IF_ICMPEQ..=Opcodes::IF_ACMPNE => 2,
+						_ => 1,
+					};
+					frame.pop_values(operands, i).ok()?;
+				}
+				_ => return None,
+			}
+		}
+
+		let top = FrameValue::Type(VerificationTypeInfo::TopVariableInfo);
+		let mut locals = Vec::with_capacity(frame.locals.len());
+		let mut slot = 0;
+		while slot < frame.locals.len() {
+			locals.push(match &frame.locals[slot] {
+				Some(value) => value.clone()?,
+				None => top.clone(),
+			});
+			slot += 1 + frame.is_wide_local(slot) as usize;
+		}
+		while locals.last() == Some(&top) {
+			locals.pop();
+		}
+		let stack = frame.stack.into_iter().collect::<Option<Vec<_>>>()?;
+		Some((locals, stack))
+	}
+
+	/// The locals a handler of the range from `start` up to `end` can count on: the ones of the same
+	/// type before every instruction in it. `None` if a label isn't placed or the frame before one of
+	/// the instructions can't be told, see [`frame_before`](Self::frame_before).
+	pub(crate) fn handler_locals(&self, cp: &mut ConstantPool, start: Label, end: Label) -> Option<Vec<FrameValue>> {
+		let position = |label: Label| {
+			self.insns
+				.iter()
+				.position(|insn| matches!(insn, Insn::Label(placed) if *placed == label))
+		};
+		let top = FrameValue::Type(VerificationTypeInfo::TopVariableInfo);
+
+		// a slot each, `None` for the second half of a long or double
+		let mut common: Option<Vec<Option<FrameValue>>> = None;
+		for i in position(start)?..position(end)? {
+			if matches!(self.insns[i], Insn::Label(_) | Insn::Line(_) | Insn::Frame { .. }) {
+				continue;
+			}
+			let (locals, _) = self.frame_before(cp, i)?;
+			let mut slots = Vec::new();
+			for value in locals {
+				let wide = is_wide(&value);
+				slots.push(Some(value));
+				if wide {
+					slots.push(None);
+				}
+			}
+			common = Some(match common {
+				None => slots,
+				Some(common) => common
+					.into_iter()
+					.zip(slots)
+					.map(|(a, b)| match a == b {
+						true => a,
+						false => Some(top.clone()),
+					})
+					.collect(),
+			});
+		}
+
+		let common = common.unwrap_or_default();
+		let mut locals = Vec::new();
+		let mut slot = 0;
+		while slot < common.len() {
+			match &common[slot] {
+				Some(value) => {
+					locals.push(value.clone());
+					slot += 1 + is_wide(value) as usize;
+				}
+				None => {
+					locals.push(top.clone());
+					slot += 1;
+				}
+			}
+		}
+		while locals.last() == Some(&top) {
+			locals.pop();
+		}
+		Some(locals)
+	}
+}
+
+fn is_wide(value: &FrameValue) -> bool {
+	matches!(
+		value,
+		FrameValue::Type(VerificationTypeInfo::LongVariableInfo | VerificationTypeInfo::DoubleVariableInfo)
+	)
+}
+
+/// The verification types of values, `None` when it can't be told.
+pub(crate) struct VerificationDomain<'a> {
+	cp: &'a mut ConstantPool,
+	insns: &'a [Insn],
+}
+
+impl<'a> VerificationDomain<'a> {
+	/// Classes of the types are interned in `cp`, `new` is typed as the object made after a label
+	/// placed in `insns`.
+	pub(crate) fn new(cp: &'a mut ConstantPool, insns: &'a [Insn]) -> Self {
+		Self { cp, insns }
+	}
+
+	fn class(&mut self, name: &str) -> Option<FrameValue> {
+		let class = intern_class(self.cp, name).ok()?;
+		Some(FrameValue::Type(VerificationTypeInfo::ObjectVariableInfo {
+			cpool_idx: class.index,
+		}))
+	}
+
+	/// The type of a value with the field descriptor `descriptor`.
+	fn of(&mut self, descriptor: &str) -> Option<FrameValue> {
+		match descriptor.as_bytes().first()? {
+			b'L' => self.class(descriptor.strip_prefix('L')?.strip_suffix(';')?),
+			b'[' => self.class(descriptor),
+			_ => primitive(Kind::of(descriptor)?),
+		}
+	}
+
+	/// The class of the object made by the `new` right after `label`, once it's initialized.
+	fn created(&self, label: Label) -> Option<FrameValue> {
+		let at = self
+			.insns
+			.iter()
+			.position(|insn| matches!(insn, Insn::Label(placed) if *placed == label))?;
+		match self.insns.get(at + 1)? {
+			Insn::Op(Instructions::NEW(class)) => Some(FrameValue::Type(VerificationTypeInfo::ObjectVariableInfo {
+				cpool_idx: class.index,
+			})),
+			_ => None,
+		}
+	}
+}
+
+fn primitive(kind: Kind) -> Option<FrameValue> {
+	Some(FrameValue::Type(match kind {
+		Kind::Int => VerificationTypeInfo::IntegerVariableInfo,
+		Kind::Float => VerificationTypeInfo::FloatVariableInfo,
+		Kind::Long => VerificationTypeInfo::LongVariableInfo,
+		Kind::Double => VerificationTypeInfo::DoubleVariableInfo,
+		Kind::Reference | Kind::Unknown => return None,
+	}))
+}
+
+impl Domain for VerificationDomain<'_> {
+	type Value = Option<FrameValue>;
+
+	fn unknown(&mut self) -> Option<FrameValue> {
+		None
+	}
+
+	fn merge(&mut self, a: &Option<FrameValue>, b: &Option<FrameValue>) -> Option<FrameValue> {
+		a.clone().filter(|_| a == b)
+	}
+
+	fn execute(
+		&mut self,
+		index: usize,
+		instruction: &Instructions,
+		inputs: &[Option<FrameValue>],
+	) -> Option<Option<FrameValue>> {
+		use Instructions::*;
+
+		Some(match instruction {
+			ACONST_NULL => Some(FrameValue::Type(VerificationTypeInfo::NullVariableInfo)),
+			NEW(_) => match self.insns[..index].last() {
+				Some(Insn::Label(label)) => Some(FrameValue::Uninitialized(*label)),
+				_ => None,
+			},
+			NEWARRAY(ty) => self.class(match ty {
+				4 => "[Z",
+				5 => "[C",
+				6 => "[F",
+				7 => "[D",
+				8 => "[B",
+				9 => "[S",
+				10 => "[I",
+				11 => "[J",
+				_ => return None,
+			}),
+			ANEWARRAY(class) => {
+				let name = class.data.data.as_str();
+				match name.starts_with('[') {
+					true => self.class(&format!("[{name}")),
+					false => self.class(&format!("[L{name};")),
+				}
+			}
+			CHECKCAST(class) | MULTIANEWARRAY(class, _) => {
+				Some(FrameValue::Type(VerificationTypeInfo::ObjectVariableInfo {
+					cpool_idx: class.index,
+				}))
+			}
+			AALOAD => match inputs.first()? {
+				Some(FrameValue::Type(VerificationTypeInfo::ObjectVariableInfo { cpool_idx })) => {
+					let array = match self.cp.get((*cpool_idx as usize).checked_sub(1)?)? {
+						IRCpTag::Class(name) => name.data.to_string(),
+						_ => return None,
+					};
+					self.of(array.strip_prefix('[')?)
+				}
+				Some(FrameValue::Type(VerificationTypeInfo::NullVariableInfo)) => {
+					Some(FrameValue::Type(VerificationTypeInfo::NullVariableInfo))
+				}
+				_ => None,
+			},
+			LDC(constant) | LDC_W(constant) | LDC2_W(constant) => match &constant.tag {
+				IRCpTag::String(_) => self.class("java/lang/String"),
+				IRCpTag::Class(_) => self.class("java/lang/Class"),
+				IRCpTag::MethodType(_) => self.class("java/lang/invoke/MethodType"),
+				IRCpTag::MethodHandle { .. } => self.class("java/lang/invoke/MethodHandle"),
+				IRCpTag::Dynamic { name_and_ty, .. } => self.of(&name_and_ty.ty.data),
+				_ => primitive(KindDomain.execute(index, instruction, &[])?),
+			},
+			GETSTATIC(field) | GETFIELD(field) => self.of(&field.name_and_ty.ty.data),
+			INVOKEVIRTUAL(method) | INVOKESPECIAL(method) | INVOKESTATIC(method) | INVOKEINTERFACE(method, _) => {
+				self.of(method.name_and_ty.ty.data.split_once(')')?.1)
+			}
+			INVOKEDYNAMIC(call_site) => self.of(call_site.name_and_ty.ty.data.split_once(')')?.1),
+			_ => primitive(KindDomain.execute(index, instruction, &[])?),
+		})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		attribute::IRAttribute,
+		test_util::{class, find_attr, method_code, HELLO},
+	};
+
+	#[test]
+	fn long_branches() {
+		// 33000 nops between the first ifle and its target turn it into ifgt over a goto_w
+		let mut class = class(HELLO);
+		let descriptor = "(ILjava/lang/Object;)V";
+		let initial = class.initial_locals("stackmapper", descriptor).unwrap().unwrap();
+		assert!(class
+			.instrument_method("stackmapper", descriptor, |insns| {
+				let ifle = insns
+					.insns
+					.iter()
+					.position(|insn| {
+						matches!(
+							insn,
+							Insn::Jump {
+								opcode: Opcodes::IFLE,
+								..
+							}
+						)
+					})
+					.unwrap();
+				insns
+					.insns
+					.splice(ifle + 1..ifle + 1, (0..33000).map(|_| Insn::Op(Instructions::NOP)));
+			})
+			.unwrap());
+		let code = method_code(&class, "stackmapper");
+		assert_eq!(code.code[8..16], [0x9D, 0, 8, 0xC8, 0, 0, 0x81, 0x03]);
+
+		// where it falls through gets a frame with the locals stored before it
+		let IRAttribute::StackMapTable(table) = find_attr(&code.attributes, "StackMapTable") else {
+			unreachable!();
+		};
+		let frames = table.resolve(&initial).unwrap();
+		assert_eq!(table.offsets().unwrap(), [16, 33034, 33038]);
+		assert_eq!(frames[0].locals, frames[2].locals);
+		assert!(frames[0].stack.is_empty());
+	}
+}
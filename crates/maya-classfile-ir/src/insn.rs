@@ -0,0 +1,931 @@
+//! A method's code as a list of instructions that refer to each other through labels instead of
+//! offsets, see [`InsnList`].
+
+use std::{
+	borrow::Cow,
+	collections::{BTreeMap, BTreeSet, HashMap},
+	io::{Cursor, Write},
+};
+
+use maya_bytes::BytesError;
+
+use crate::{
+	attribute::{
+		CodeAttribute, CodeAttributeException, IRAttribute, IRAttributeInfo, LineNumberTableAttribute,
+		LineNumberTableAttributeEntry, LocalVariableTableEntry, LocalVariableTypeTableEntry, StackMapTableAttribute,
+		VerificationTypeInfo,
+	},
+	class_pool::{CPUtf8Ref, ConstantPool, IRClassfileError, IRCpTag},
+	code::{Instructions, Opcodes},
+	intern_utf8,
+	stackmap::ResolvedFrame,
+	IRClassFile,
+};
+
+/// A position in an [`InsnList`], placed with [`Insn::Label`] and made by [`InsnList::new_label`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Label(u32);
+
+/// A stack map frame entry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FrameValue {
+	/// Anything but an uninitialized object, [`VerificationTypeInfo::UninitializedVariableInfo`]
+	/// isn't used.
+	Type(VerificationTypeInfo),
+	/// An object made by the `new` right after the label that hasn't been initialized yet.
+	Uninitialized(Label),
+}
+
+#[derive(Debug, Clone)]
+pub enum Insn {
+	Label(Label),
+	/// The source line of the instructions that follow.
+	Line(u16),
+	/// The stack map frame of the instruction that follows.
+	Frame {
+		locals: Vec<FrameValue>,
+		stack: Vec<FrameValue>,
+	},
+	/// Any instruction that doesn't branch.
+	Op(Instructions),
+	/// `goto`, `jsr`, one of the `if`s, or `goto_w` and `jsr_w` to always use the long form.
+	/// `goto` and `jsr` are written as their long form when the target is out of reach, the `if`s as
+	/// the opposite condition jumping over a `goto_w`.
+	Jump {
+		opcode: u8,
+		target: Label,
+	},
+	/// Jumps to `targets[value - low]`, or to `default` if there's no such target.
+	TableSwitch {
+		default: Label,
+		low: i32,
+		targets: Vec<Label>,
+	},
+	/// Jumps to the label paired with the value, or to `default` if none matches. Pairs are sorted
+	/// when written.
+	LookupSwitch {
+		default: Label,
+		pairs: Vec<(i32, Label)>,
+	},
+}
+
+/// A protected range, ending before `end`, and the handler for what it throws.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TryCatchBlock {
+	pub start: Label,
+	pub end: Label,
+	pub handler: Label,
+	/// The Class entry of the caught exception, 0 to catch anything.
+	pub catch_type: u16,
+}
+
+/// A LocalVariableTable or LocalVariableTypeTable entry, the variable is live from `start` up to
+/// `end`. `descriptor` is the signature for LocalVariableTypeTable entries.
+#[derive(Debug, Clone)]
+pub struct LocalVariable {
+	pub start: Label,
+	pub end: Label,
+	pub name: CPUtf8Ref,
+	pub descriptor: CPUtf8Ref,
+	pub index: u16,
+}
+
+/// A method body where branches, exception ranges, line numbers, local variable ranges and stack
+/// map frames point at labels, so instructions can be added and removed freely. Offsets are
+/// worked out again by [`write_to`](Self::write_to).
+///
+/// Other attributes nested in the Code attribute are left alone, including type annotations,
+/// which keep whatever offsets they had.
+#[derive(Debug, Clone)]
+pub struct InsnList {
+	pub insns: Vec<Insn>,
+	pub try_catch_blocks: Vec<TryCatchBlock>,
+	pub local_variables: Vec<LocalVariable>,
+	pub local_variable_types: Vec<LocalVariable>,
+	/// The locals of the implicit frame at the method's entry, see [`IRClassFile::initial_locals`].
+	pub initial_locals: Vec<VerificationTypeInfo>,
+	next_label: u32,
+	/// Slots below this were handed out by [`new_local`](Self::new_local).
+	reserved_locals: u16,
+}
+
+impl InsnList {
+	pub fn new(initial_locals: Vec<VerificationTypeInfo>) -> Self {
+		Self {
+			insns: Vec::new(),
+			try_catch_blocks: Vec::new(),
+			local_variables: Vec::new(),
+			local_variable_types: Vec::new(),
+			initial_locals,
+			next_label: 0,
+			reserved_locals: 0,
+		}
+	}
+
+	pub fn new_label(&mut self) -> Label {
+		self.next_label += 1;
+		Label(self.next_label - 1)
+	}
+
+	/// Reserves a slot for a new local, or two for a long or double, past every slot the parameters,
+	/// the code and earlier reservations use.
+	pub fn new_local(&mut self, wide: bool) -> u16 {
+		let local = self.max_locals();
+		self.reserved_locals = local + 1 + wide as u16;
+		local
+	}
+
+	/// Decodes `code`, giving a label to every offset something refers to.
+	pub fn from_code(
+		cp: &[IRCpTag],
+		code: &CodeAttribute,
+		initial_locals: &[VerificationTypeInfo],
+	) -> Result<Self, IRClassfileError> {
+		let mut list = Self::new(initial_locals.to_vec());
+		let mut labels = BTreeMap::new();
+		let mut next = 0;
+		let mut label = |offset: usize| {
+			*labels.entry(offset).or_insert_with(|| {
+				next += 1;
+				Label(next - 1)
+			})
+		};
+
+		let mut buffer = Cursor::new(&code.code);
+		let mut decoded = Vec::new();
+		while (buffer.position() as usize) < code.code.len() {
+			let pc = buffer.position() as usize;
+			let instruction = Instructions::read(cp, &mut buffer)?;
+			let target = |offset: i32| {
+				let target = pc as i64 + offset as i64;
+				usize::try_from(target).map_err(|_| IRClassfileError::NotAnInstruction(pc))
+			};
+			let insn = match instruction {
+				Instructions::TABLESWITCH {
+					default, low, offsets, ..
+				} => Insn::TableSwitch {
+					default: label(target(default)?),
+					low,
+					targets: offsets
+						.into_iter()
+						.map(|offset| target(offset).map(&mut label))
+						.collect::<Result<_, _>>()?,
+				},
+				Instructions::LOOKUPSWITCH { default, pairs } => Insn::LookupSwitch {
+					default: label(target(default)?),
+					pairs: pairs
+						.into_iter()
+						.map(|(value, offset)| Ok((value, label(target(offset)?))))
+						.collect::<Result<_, IRClassfileError>>()?,
+				},
+				instruction => match branch_offset(&instruction) {
+					Some(offset) => Insn::Jump {
+						opcode: short_jump(instruction.opcode()),
+						target: label(target(offset)?),
+					},
+					None => Insn::Op(instruction),
+				},
+			};
+			decoded.push((pc, insn));
+		}
+
+		for exception in &code.exception_table {
+			list.try_catch_blocks.push(TryCatchBlock {
+				start: label(exception.start_pc as usize),
+				end: label(exception.end_pc as usize),
+				handler: label(exception.handler_pc as usize),
+				catch_type: exception.catch_type,
+			});
+		}
+
+		let mut lines: BTreeMap<usize, Vec<u16>> = BTreeMap::new();
+		let mut frames = BTreeMap::new();
+		for attribute in &code.attributes {
+			match &attribute.attr {
+				IRAttribute::LineNumberTable(table) => {
+					for entry in &table.line_number_table {
+						lines
+							.entry(entry.start_pc as usize)
+							.or_default()
+							.push(entry.line_number);
+					}
+				}
+				IRAttribute::StackMapTable(table) => {
+					for frame in table.resolve(initial_locals)? {
+						let mut value = |info: VerificationTypeInfo| match info {
+							VerificationTypeInfo::UninitializedVariableInfo { offset } => {
+								FrameValue::Uninitialized(label(offset as usize))
+							}
+							info => FrameValue::Type(info),
+						};
+						let locals = frame.locals.into_iter().map(&mut value).collect();
+						let stack = frame.stack.into_iter().map(&mut value).collect();
+						frames.insert(frame.offset as usize, Insn::Frame { locals, stack });
+					}
+				}
+				IRAttribute::LocalVariableTable { table } => {
+					for entry in table {
+						list.local_variables.push(LocalVariable {
+							start: label(entry.start_pc as usize),
+							end: label(entry.start_pc as usize + entry.length as usize),
+							name: entry.name.clone(),
+							descriptor: entry.descriptor.clone(),
+							index: entry.index,
+						});
+					}
+				}
+				IRAttribute::LocalVariableTypeTable { table } => {
+					for entry in table {
+						list.local_variable_types.push(LocalVariable {
+							start: label(entry.start_pc as usize),
+							end: label(entry.start_pc as usize + entry.length as usize),
+							name: entry.name.clone(),
+							descriptor: entry.signature.clone(),
+							index: entry.index,
+						});
+					}
+				}
+				_ => {}
+			}
+		}
+
+		let boundaries: BTreeSet<_> = decoded.iter().map(|(pc, _)| *pc).chain([code.code.len()]).collect();
+		let stray = labels
+			.keys()
+			.chain(lines.keys())
+			.chain(frames.keys())
+			.find(|offset| !boundaries.contains(offset));
+		if let Some(offset) = stray {
+			return Err(IRClassfileError::NotAnInstruction(*offset));
+		}
+
+		for (pc, insn) in decoded {
+			if let Some(label) = labels.get(&pc) {
+				list.insns.push(Insn::Label(*label));
+			}
+			for line in lines.remove(&pc).unwrap_or_default() {
+				list.insns.push(Insn::Line(line));
+			}
+			if let Some(frame) = frames.remove(&pc) {
+				list.insns.push(frame);
+			}
+			list.insns.push(insn);
+		}
+		if let Some(label) = labels.get(&code.code.len()) {
+			list.insns.push(Insn::Label(*label));
+		}
+		list.next_label = labels.len() as u32;
+		Ok(list)
+	}
+
+	/// Lays the instructions out and replaces the bytecode, exception table, LineNumberTable,
+	/// StackMapTable, LocalVariableTable and LocalVariableTypeTable of `code` with the result.
+	/// Exception ranges left empty are dropped, as are frames followed by another one before any
+	/// instruction. `max_stack` and `max_locals` are left as they are.
+	///
+	/// A conditional jump that can't reach its target is written as the opposite condition jumping
+	/// over a `goto_w` to the target, with a frame where it falls through, which is worked out from
+	/// the code since the frame before it. If that code jumps away or the frame can't be told
+	/// without the class hierarchy, like for an object whose class comes from a merge, it's
+	/// [`IRClassfileError::BranchTooFar`].
+	pub fn write_to(&self, cp: &mut ConstantPool, code: &mut CodeAttribute) -> Result<(), IRClassfileError> {
+		let layout = self.layout(cp)?;
+		let offset = |label: Label| -> Result<u16, IRClassfileError> {
+			let offset = *layout
+				.labels
+				.get(&label)
+				.ok_or(IRClassfileError::UnplacedLabel(label))?;
+			// the code is at most 65535 bytes, so only a label at its very end can be out of range
+			u16::try_from(offset).map_err(|_| IRClassfileError::TooMany {
+				what: "code bytes",
+				actual: offset,
+			})
+		};
+
+		let mut exception_table = Vec::with_capacity(self.try_catch_blocks.len());
+		for block in &self.try_catch_blocks {
+			let (start_pc, end_pc) = (offset(block.start)?, offset(block.end)?);
+			// edits can leave a range with nothing in it, which the JVM rejects
+			if start_pc >= end_pc {
+				continue;
+			}
+			exception_table.push(CodeAttributeException {
+				start_pc,
+				end_pc,
+				handler_pc: offset(block.handler)?,
+				catch_type: block.catch_type,
+			});
+		}
+
+		let range = |variable: &LocalVariable| -> Result<(u16, u16), IRClassfileError> {
+			let start = offset(variable.start)?;
+			Ok((start, offset(variable.end)?.saturating_sub(start)))
+		};
+		let mut local_variables = Vec::with_capacity(self.local_variables.len());
+		for variable in &self.local_variables {
+			let (start_pc, length) = range(variable)?;
+			local_variables.push(LocalVariableTableEntry {
+				start_pc,
+				length,
+				name: variable.name.clone(),
+				descriptor: variable.descriptor.clone(),
+				index: variable.index,
+			});
+		}
+		let mut local_variable_types = Vec::with_capacity(self.local_variable_types.len());
+		for variable in &self.local_variable_types {
+			let (start_pc, length) = range(variable)?;
+			local_variable_types.push(LocalVariableTypeTableEntry {
+				start_pc,
+				length,
+				name: variable.name.clone(),
+				signature: variable.descriptor.clone(),
+				index: variable.index,
+			});
+		}
+
+		let mut frames: Vec<ResolvedFrame> = Vec::with_capacity(layout.frames.len());
+		for (pc, locals, stack) in &layout.frames {
+			let value = |value: &FrameValue| -> Result<VerificationTypeInfo, IRClassfileError> {
+				Ok(match value {
+					FrameValue::Type(info) => info.clone(),
+					FrameValue::Uninitialized(label) => VerificationTypeInfo::UninitializedVariableInfo {
+						offset: offset(*label)?,
+					},
+				})
+			};
+			// frames that edits moved to the same offset, the last one describes the instruction there
+			if frames.last().is_some_and(|frame| frame.offset == *pc as u16) {
+				frames.pop();
+			}
+			frames.push(ResolvedFrame {
+				offset: *pc as u16,
+				locals: locals.iter().map(value).collect::<Result<_, _>>()?,
+				stack: stack.iter().map(value).collect::<Result<_, _>>()?,
+			});
+		}
+		let stack_map = StackMapTableAttribute::from_resolved(&self.initial_locals, &frames)?;
+
+		let line_number_table = layout
+			.lines
+			.iter()
+			.map(|(pc, line_number)| LineNumberTableAttributeEntry {
+				start_pc: *pc as u16,
+				line_number: *line_number,
+			})
+			.collect::<Vec<_>>();
+
+		replace_attribute(
+			cp,
+			code,
+			"LineNumberTable",
+			(!line_number_table.is_empty()).then_some(IRAttribute::LineNumberTable(LineNumberTableAttribute {
+				line_number_table,
+			})),
+		)?;
+		replace_attribute(
+			cp,
+			code,
+			"StackMapTable",
+			(!stack_map.entries.is_empty()).then_some(IRAttribute::StackMapTable(stack_map)),
+		)?;
+		replace_attribute(
+			cp,
+			code,
+			"LocalVariableTable",
+			(!local_variables.is_empty()).then_some(IRAttribute::LocalVariableTable { table: local_variables }),
+		)?;
+		replace_attribute(
+			cp,
+			code,
+			"LocalVariableTypeTable",
+			(!local_variable_types.is_empty()).then_some(IRAttribute::LocalVariableTypeTable {
+				table: local_variable_types,
+			}),
+		)?;
+		code.code = layout.code;
+		code.exception_table = exception_table;
+		Ok(())
+	}
+
+	/// The deepest the operand stack gets on any path through the code, see
+	/// [`stack_depths`](Self::stack_depths).
+	pub fn max_stack(&self) -> Result<u16, IRClassfileError> {
+		Ok(self.stack_depths()?.into_iter().flatten().max().unwrap_or(0))
+	}
+
+	/// Every instruction with the stack depth before it, see [`stack_depths`](Self::stack_depths).
+	pub fn with_stack_depths(&self) -> Result<impl Iterator<Item = (&Insn, Option<u16>)>, IRClassfileError> {
+		Ok(self.insns.iter().zip(self.stack_depths()?))
+	}
+
+	/// The operand stack depth in slots before every instruction, `None` where no path reaches, with
+	/// exception handlers entered with the exception on the stack. Each instruction is visited once,
+	/// with the depth of the first path that reaches it, a mismatch between paths is left for the
+	/// verifier to find. [`analyze`](Self::analyze) tells what is on the stack.
+	pub fn stack_depths(&self) -> Result<Vec<Option<u16>>, IRClassfileError> {
+		let mut labels = HashMap::new();
+		for (i, insn) in self.insns.iter().enumerate() {
+			if let Insn::Label(label) = insn {
+				labels.insert(*label, i);
+			}
+		}
+		let index = |label: &Label| {
+			labels
+				.get(label)
+				.copied()
+				.ok_or(IRClassfileError::UnplacedLabel(*label))
+		};
+
+		let mut depths = vec![None; self.insns.len()];
+		let mut pending = vec![(0, 0u16)];
+		for block in &self.try_catch_blocks {
+			pending.push((index(&block.handler)?, 1));
+		}
+		while let Some((mut i, mut depth)) = pending.pop() {
+			while i < self.insns.len() && depths[i].is_none() {
+				depths[i] = Some(depth);
+				let falls_through = match &self.insns[i] {
+					Insn::Label(_) | Insn::Line(_) | Insn::Frame { .. } => true,
+					Insn::Op(instruction) => {
+						let (pops, pushes) = instruction.stack_effect();
+						depth = depth.saturating_sub(pops) + pushes;
+						!matches!(
+							instruction,
+							Instructions::IRETURN
+								| Instructions::LRETURN | Instructions::FRETURN
+								| Instructions::DRETURN | Instructions::ARETURN
+								| Instructions::RETURN | Instructions::ATHROW
+								| Instructions::RET(_)
+						)
+					}
+					Insn::Jump { opcode, target } => {
+						let (pops, pushes) = jump(*opcode, 0).stack_effect();
+						// the return address is only on the stack at the subroutine
+						pending.push((index(target)?, depth.saturating_sub(pops) + pushes));
+						depth = depth.saturating_sub(pops);
+						!matches!(*opcode, Opcodes::GOTO | Opcodes::GOTO_W)
+					}
+					Insn::TableSwitch { default, targets, .. } => {
+						depth = depth.saturating_sub(1);
+						for target in targets.iter().chain([default]) {
+							pending.push((index(target)?, depth));
+						}
+						false
+					}
+					Insn::LookupSwitch { default, pairs } => {
+						depth = depth.saturating_sub(1);
+						for target in pairs.iter().map(|(_, target)| target).chain([default]) {
+							pending.push((index(target)?, depth));
+						}
+						false
+					}
+				};
+				if !falls_through {
+					break;
+				}
+				i += 1;
+			}
+		}
+		Ok(depths)
+	}
+
+	/// The number of local slots the code needs: enough for [`initial_locals`](Self::initial_locals)
+	/// and every local it loads, stores, increments or returns through, and for the ones reserved by
+	/// [`new_local`](Self::new_local).
+	pub fn max_locals(&self) -> u16 {
+		let parameters = self
+			.initial_locals
+			.iter()
+			.map(|local| match local {
+				VerificationTypeInfo::LongVariableInfo | VerificationTypeInfo::DoubleVariableInfo => 2,
+				_ => 1,
+			})
+			.sum::<u16>();
+		let mut max = parameters.max(self.reserved_locals);
+		for insn in &self.insns {
+			let Insn::Op(instruction) = insn else {
+				continue;
+			};
+			let end = match instruction {
+				Instructions::ILOAD(index)
+				| Instructions::FLOAD(index)
+				| Instructions::ALOAD(index)
+				| Instructions::ISTORE(index)
+				| Instructions::FSTORE(index)
+				| Instructions::ASTORE(index)
+				| Instructions::IINC(index, _)
+				| Instructions::RET(index) => *index as u32 + 1,
+				Instructions::LLOAD(index)
+				| Instructions::DLOAD(index)
+				| Instructions::LSTORE(index)
+				| Instructions::DSTORE(index) => *index as u32 + 2,
+				_ => continue,
+			};
+			max = max.max(end.min(u16::MAX as u32) as u16);
+		}
+		max
+	}
+
+	/// Sizes every instruction, widening jumps until every branch reaches its target, then encodes
+	/// them. Interns the classes of the frames added for widened conditional jumps in `cp`.
+	fn layout(&self, cp: &mut ConstantPool) -> Result<Layout<'_>, IRClassfileError> {
+		let mut ops = HashMap::new();
+		let mut long = vec![false; self.insns.len()];
+		for (i, insn) in self.insns.iter().enumerate() {
+			match insn {
+				Insn::Op(instruction) => {
+					if branch_offset(instruction).is_some()
+						|| matches!(
+							instruction,
+							Instructions::TABLESWITCH { .. } | Instructions::LOOKUPSWITCH { .. }
+						) {
+						return Err(IRClassfileError::InvalidOpcode {
+							opcode: instruction.opcode(),
+							pc: i,
+						});
+					}
+					let mut bytes = Cursor::new(Vec::new());
+					instruction.write(&mut bytes)?;
+					ops.insert(i, bytes.into_inner());
+				}
+				Insn::Jump { opcode, .. } => match *opcode {
+					Opcodes::GOTO_W | Opcodes::JSR_W => long[i] = true,
+					Opcodes::IFEQ..=Opcodes::JSR | Opcodes::IFNULL | Opcodes::IFNONNULL => {}
+					opcode => return Err(IRClassfileError::InvalidOpcode { opcode, pc: i }),
+				},
+				_ => {}
+			}
+		}
+
+		let (pcs, labels) = loop {
+			let mut pcs = Vec::with_capacity(self.insns.len());
+			let mut labels = HashMap::new();
+			let mut pc = 0;
+			for (i, insn) in self.insns.iter().enumerate() {
+				pcs.push(pc);
+				pc += match insn {
+					Insn::Label(label) => {
+						labels.insert(*label, pc);
+						0
+					}
+					Insn::Line(_) | Insn::Frame { .. } => 0,
+					Insn::Op(_) => ops[&i].len(),
+					Insn::Jump { opcode, .. } => match (long[i], *opcode) {
+						(false, _) => 3,
+						(true, Opcodes::GOTO | Opcodes::GOTO_W | Opcodes::JSR | Opcodes::JSR_W) => 5,
+						// the opposite condition and a goto_w
+						(true, _) => 8,
+					},
+					Insn::TableSwitch { targets, .. } => 1 + padding(pc) + 12 + targets.len() * 4,
+					Insn::LookupSwitch { pairs, .. } => 1 + padding(pc) + 8 + pairs.len() * 8,
+				};
+			}
+			if pc > u16::MAX as usize {
+				return Err(IRClassfileError::TooMany {
+					what: "code bytes",
+					actual: pc,
+				});
+			}
+
+			let mut widened = false;
+			for (i, insn) in self.insns.iter().enumerate() {
+				let Insn::Jump { target, .. } = insn else {
+					continue;
+				};
+				let target = *labels.get(target).ok_or(IRClassfileError::UnplacedLabel(*target))?;
+				if long[i] || i16::try_from(target as i64 - pcs[i] as i64).is_ok() {
+					continue;
+				}
+				long[i] = true;
+				widened = true;
+			}
+			if !widened {
+				break (pcs, labels);
+			}
+		};
+
+		let mut code = Cursor::new(Vec::new());
+		let mut lines = Vec::new();
+		let mut frames = Vec::new();
+		for (i, insn) in self.insns.iter().enumerate() {
+			let pc = pcs[i];
+			let target = |label: &Label| -> Result<i32, IRClassfileError> {
+				let target = *labels.get(label).ok_or(IRClassfileError::UnplacedLabel(*label))?;
+				Ok((target as i64 - pc as i64) as i32)
+			};
+			match insn {
+				Insn::Label(_) => {}
+				Insn::Line(line) => lines.push((pc, *line)),
+				Insn::Frame { locals, stack } => {
+					frames.push((pc, Cow::Borrowed(&locals[..]), Cow::Borrowed(&stack[..])))
+				}
+				Insn::Op(_) => code.write_all(&ops[&i]).map_err(BytesError::from)?,
+				Insn::Jump { opcode, target: label } => {
+					let opcode = match (*opcode, long[i]) {
+						(Opcodes::GOTO, true) => Opcodes::GOTO_W,
+						(Opcodes::JSR, true) => Opcodes::JSR_W,
+						(opcode @ (Opcodes::GOTO_W | Opcodes::JSR_W), _) | (opcode, false) => opcode,
+						(opcode, true) => {
+							let offset = target(label)?;
+							let (locals, stack) =
+								self.frame_before(cp, i + 1).ok_or(IRClassfileError::BranchTooFar {
+									pc,
+									target: (pc as i64 + offset as i64) as usize,
+								})?;
+							jump(opposite(opcode), 8).write(&mut code)?;
+							Instructions::GOTO_W(offset - 3).write(&mut code)?;
+							frames.push((pc + 8, Cow::Owned(locals), Cow::Owned(stack)));
+							continue;
+						}
+					};
+					jump(opcode, target(label)?).write(&mut code)?;
+				}
+				Insn::TableSwitch { default, low, targets } => {
+					let high = *low as i64 + targets.len() as i64 - 1;
+					Instructions::TABLESWITCH {
+						default: target(default)?,
+						low: *low,
+						high: i32::try_from(high).map_err(|_| IRClassfileError::InvalidOpcode {
+							opcode: Opcodes::TABLESWITCH,
+							pc,
+						})?,
+						offsets: targets.iter().map(target).collect::<Result<_, _>>()?,
+					}
+					.write(&mut code)?;
+				}
+				Insn::LookupSwitch { default, pairs } => {
+					let mut pairs = pairs
+						.iter()
+						.map(|(value, label)| Ok((*value, target(label)?)))
+						.collect::<Result<Vec<_>, IRClassfileError>>()?;
+					pairs.sort_by_key(|(value, _)| *value);
+					Instructions::LOOKUPSWITCH {
+						default: target(default)?,
+						pairs,
+					}
+					.write(&mut code)?;
+				}
+			}
+		}
+
+		Ok(Layout {
+			code: code.into_inner(),
+			labels,
+			lines,
+			frames,
+		})
+	}
+}
+
+/// The locals or stack of a frame, borrowed from the frame placed in the code or worked out for a
+/// widened jump.
+type FrameValues<'a> = Cow<'a, [FrameValue]>;
+
+struct Layout<'a> {
+	code: Vec<u8>,
+	labels: HashMap<Label, usize>,
+	lines: Vec<(usize, u16)>,
+	frames: Vec<(usize, FrameValues<'a>, FrameValues<'a>)>,
+}
+
+fn padding(pc: usize) -> usize {
+	(4 - (pc + 1) % 4) % 4
+}
+
+fn branch_offset(instruction: &Instructions) -> Option<i32> {
+	Some(match instruction {
+		Instructions::IFEQ(offset)
+		| Instructions::IFNE(offset)
+		| Instructions::IFLT(offset)
+		| Instructions::IFGE(offset)
+		| Instructions::IFGT(offset)
+		| Instructions::IFLE(offset)
+		| Instructions::IF_ICMPEQ(offset)
+		| Instructions::IF_ICMPNE(offset)
+		| Instructions::IF_ICMPLT(offset)
+		| Instructions::IF_ICMPGE(offset)
+		| Instructions::IF_ICMPGT(offset)
+		| Instructions::IF_ICMPLE(offset)
+		| Instructions::IF_ACMPEQ(offset)
+		| Instructions::IF_ACMPNE(offset)
+		| Instructions::GOTO(offset)
+		| Instructions::JSR(offset)
+		| Instructions::IFNULL(offset)
+		| Instructions::IFNONNULL(offset) => *offset as i32,
+		Instructions::GOTO_W(offset) | Instructions::JSR_W(offset) => *offset,
+		_ => return None,
+	})
+}
+
+/// `goto_w` and `jsr_w` as their short form, the layout widens them again if it has to.
+fn short_jump(opcode: u8) -> u8 {
+	match opcode {
+		Opcodes::GOTO_W => Opcodes::GOTO,
+		Opcodes::JSR_W => Opcodes::JSR,
+		opcode => opcode,
+	}
+}
+
+/// The conditional jump taken exactly when `opcode` isn't.
+fn opposite(opcode: u8) -> u8 {
+	match opcode {
+		Opcodes::IFNULL => Opcodes::IFNONNULL,
+		Opcodes::IFNONNULL => Opcodes::IFNULL,
+		// they come in pairs, eq and ne, lt and ge, gt and le
+		opcode if (opcode - Opcodes::IFEQ).is_multiple_of(2) => opcode + 1,
+		opcode => opcode - 1,
+	}
+}
+
+/// The branch `opcode` with `offset`, which has to fit unless `opcode` is `goto_w` or `jsr_w`.
+fn jump(opcode: u8, offset: i32) -> Instructions {
+	let short = offset as i16;
+	match opcode {
+		Opcodes::IFEQ => Instructions::IFEQ(short),
+		Opcodes::IFNE => Instructions::IFNE(short),
+		Opcodes::IFLT => Instructions::IFLT(short),
+		Opcodes::IFGE => Instructions::IFGE(short),
+		Opcodes::IFGT => Instructions::IFGT(short),
+		Opcodes::IFLE => Instructions::IFLE(short),
+		Opcodes::IF_ICMPEQ => Instructions::IF_ICMPEQ(short),
+		Opcodes::IF_ICMPNE => Instructions::IF_ICMPNE(short),
+		Opcodes::IF_ICMPLT => Instructions::IF_ICMPLT(short),
+		Opcodes::IF_ICMPGE => Instructions::IF_ICMPGE(short),
+		Opcodes::IF_ICMPGT => Instructions::IF_ICMPGT(short),
+		Opcodes::IF_ICMPLE => Instructions::IF_ICMPLE(short),
+		Opcodes::IF_ACMPEQ => Instructions::IF_ACMPEQ(short),
+		Opcodes::IF_ACMPNE => Instructions::IF_ACMPNE(short),
+		Opcodes::GOTO => Instructions::GOTO(short),
+		Opcodes::JSR => Instructions::JSR(short),
+		Opcodes::IFNULL => Instructions::IFNULL(short),
+		Opcodes::IFNONNULL => Instructions::IFNONNULL(short),
+		Opcodes::GOTO_W => Instructions::GOTO_W(offset),
+		Opcodes::JSR_W => Instructions::JSR_W(offset),
+		_ => unreachable!("not a branch opcode {opcode:#04x}"),
+	}
+}
+
+/// Puts `attr` where the first of `code`'s attributes called `name` was, dropping the others, or appends it
+/// if there was none. `None` just drops them.
+fn replace_attribute(
+	cp: &mut ConstantPool,
+	code: &mut CodeAttribute,
+	name: &str,
+	attr: Option<IRAttribute>,
+) -> Result<(), IRClassfileError> {
+	let attributes = &mut code.attributes;
+	let position = attributes.iter().position(|attribute| attribute.attr.name() == name);
+	let mut attr = attr;
+	let mut index = 0;
+	attributes.retain_mut(|attribute| {
+		index += 1;
+		if attribute.attr.name() != name {
+			return true;
+		}
+		match (Some(index - 1) == position, attr.take()) {
+			(true, Some(attr)) => {
+				attribute.attr = attr;
+				true
+			}
+			_ => false,
+		}
+	});
+	if let Some(attr) = attr {
+		attributes.push(Box::new(IRAttributeInfo {
+			name: intern_utf8(cp, name)?,
+			length: 0,
+			attr,
+		}));
+	}
+	Ok(())
+}
+
+impl IRClassFile {
+	/// A method's code as an [`InsnList`], `None` if the method doesn't exist or has no code.
+	pub fn method_insns(&mut self, name: &str, descriptor: &str) -> Result<Option<InsnList>, IRClassfileError> {
+		let Some(initial_locals) = self.initial_locals(name, descriptor)? else {
+			return Ok(None);
+		};
+		let method = self.find_method(name, descriptor).unwrap();
+		let code = method.attributes.iter().find_map(|attribute| match &attribute.attr {
+			IRAttribute::Code(code) => Some(code),
+			_ => None,
+		});
+		code.map(|code| InsnList::from_code(&self.cp, code, &initial_locals))
+			.transpose()
+	}
+
+	/// Replaces a method's code with `insns`, see [`InsnList::write_to`]. Returns whether the method
+	/// exists and has code.
+	pub fn set_method_insns(
+		&mut self,
+		name: &str,
+		descriptor: &str,
+		insns: &InsnList,
+	) -> Result<bool, IRClassfileError> {
+		let Self { cp, methods, .. } = self;
+		let code = methods
+			.iter_mut()
+			.filter(|method| method.name() == name && method.descriptor() == descriptor)
+			.flat_map(|method| method.attributes.iter_mut())
+			.find_map(|attribute| match &mut attribute.attr {
+				IRAttribute::Code(code) => Some(code),
+				_ => None,
+			});
+		let Some(code) = code else {
+			return Ok(false);
+		};
+		insns.write_to(cp, code)?;
+		Ok(true)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::test_util::{class, method_code, CLASSES, HELLO};
+
+	#[test]
+	fn label_round_trip() {
+		// decoding into labels and laying the code out again gives back the same class
+		for bytes in CLASSES {
+			let mut class = class(bytes);
+			// initial_locals adds the Class entries of parameters, which the original needs too
+			let mut expected = self::class(bytes);
+			let methods = class
+				.methods
+				.iter()
+				.map(|method| (method.name().to_string(), method.descriptor().to_string()))
+				.collect::<Vec<_>>();
+			for (name, descriptor) in &methods {
+				expected.initial_locals(name, descriptor).unwrap();
+				if let Some(insns) = class.method_insns(name, descriptor).unwrap() {
+					assert!(class.set_method_insns(name, descriptor, &insns).unwrap());
+				}
+			}
+			let (mut out, mut original) = (Vec::new(), Vec::new());
+			class.write(&mut out).unwrap();
+			expected.write(&mut original).unwrap();
+			assert_eq!(out, original, "{}", class.class_name());
+		}
+	}
+
+	#[test]
+	fn inserted_insns_move_offsets() {
+		// a nop between the first ifle and its target moves everything after it along
+		let mut class = class(HELLO);
+		let descriptor = "(ILjava/lang/Object;)V";
+		let mut insns = class.method_insns("stackmapper", descriptor).unwrap().unwrap();
+		let ifle = insns
+			.insns
+			.iter()
+			.position(|insn| {
+				matches!(
+					insn,
+					Insn::Jump {
+						opcode: Opcodes::IFLE,
+						..
+					}
+				)
+			})
+			.unwrap();
+		insns.insns.insert(ifle + 1, Insn::Op(Instructions::NOP));
+		class.set_method_insns("stackmapper", descriptor, &insns).unwrap();
+		let code = method_code(&class, "stackmapper");
+		assert_eq!(code.code.len(), 51);
+		assert_eq!(code.code[8..12], [0x9E, 0, 26, 0x00]);
+		for attribute in &code.attributes {
+			match &attribute.attr {
+				IRAttribute::StackMapTable(table) => assert_eq!(table.offsets().unwrap(), [30, 34]),
+				IRAttribute::LineNumberTable(lines) => assert_eq!(lines.line_number_table[4].start_pc, 12),
+				_ => {}
+			}
+		}
+	}
+
+	#[test]
+	fn far_branches() {
+		// goto is widened when its target is out of reach, a conditional branch needs the frame where it
+		// falls through, and there's nothing on the stack for this one to pop
+		let far = |opcode| {
+			let mut insns = InsnList::new(Vec::new());
+			let target = insns.new_label();
+			insns.insns.push(Insn::Jump { opcode, target });
+			insns.insns.extend((0..40000).map(|_| Insn::Op(Instructions::NOP)));
+			insns.insns.push(Insn::Label(target));
+			insns.insns.push(Insn::Op(Instructions::RETURN));
+			insns
+		};
+		let mut class = class(HELLO);
+		let main = "([Ljava/lang/String;)V";
+		class.set_method_insns("main", main, &far(Opcodes::GOTO)).unwrap();
+		let code = method_code(&class, "main");
+		assert_eq!(code.code[..5], [0xC8, 0, 0, 0x9C, 0x45]);
+		assert_eq!(code.code.len(), 40006);
+		assert!(matches!(
+			class.set_method_insns("main", main, &far(Opcodes::IFEQ)),
+			Err(IRClassfileError::BranchTooFar { pc: 0, target: 40008 })
+		));
+	}
+}
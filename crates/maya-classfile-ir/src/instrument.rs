@@ -0,0 +1,280 @@
+//! Adding code around what a method already does: at its entry, before its returns, around calls
+//! and in a `finally` wrapping its body. See [`IRClassFile::instrument_method`].
+//!
+//! Inserted code is given as a snippet of [`Insn`]s. Every insertion gets its own copy, with fresh
+//! labels for the ones placed in the snippet, so one snippet can go in many places. A snippet
+//! starts and ends with the stack as it found it, and if it branches it has to bring its own
+//! frames; everything around it is laid out again from its labels. Locals a snippet keeps values in
+//! should come from [`InsnList::new_local`], so snippets don't end up sharing one.
+
+use std::collections::HashMap;
+
+use crate::{
+	attribute::{IRAttribute, VerificationTypeInfo},
+	class_pool::IRClassfileError,
+	code::Instructions,
+	insn::{FrameValue, Insn, InsnList, Label, TryCatchBlock},
+	IRClassFile,
+};
+
+impl InsnList {
+	/// Inserts `snippet` before the first instruction, so it runs once per call, before anything
+	/// can branch back to the start.
+	pub fn insert_at_entry(&mut self, snippet: &[Insn]) {
+		let snippet = self.instantiate(snippet);
+		self.insns.splice(0..0, snippet);
+	}
+
+	/// Inserts `snippet` before every return instruction, where the value being returned is on the
+	/// stack and has to stay there. Exits through exceptions don't run it, see
+	/// [`wrap_try_finally`](Self::wrap_try_finally).
+	pub fn insert_before_returns(&mut self, snippet: &[Insn]) {
+		self.insert_around(is_return, snippet, &[]);
+	}
+
+	/// Inserts `before` and `after` around every `invoke*` instruction `predicate` picks. `before`
+	/// runs with the arguments on the stack and `after` with the result.
+	pub fn insert_around_invocations(
+		&mut self,
+		mut predicate: impl FnMut(&Instructions) -> bool,
+		before: &[Insn],
+		after: &[Insn],
+	) {
+		self.insert_around(
+			|instruction| is_invocation(instruction) && predicate(instruction),
+			before,
+			after,
+		);
+	}
+
+	/// Runs `finally` however the method exits: before every return, and in a handler that catches
+	/// anything thrown by the body, runs `finally` with the exception on the stack and throws it
+	/// again. The handler comes after every existing one, which keep precedence.
+	///
+	/// `throwable` is the Class entry of `java/lang/Throwable`, for the handler's frame, whose
+	/// locals are [`initial_locals`](Self::initial_locals). Constructors can't be wrapped, their
+	/// `this` isn't the same type before and after the super constructor call.
+	pub fn wrap_try_finally(&mut self, finally: &[Insn], throwable: u16) {
+		if !self
+			.insns
+			.iter()
+			.any(|insn| matches!(insn, Insn::Op(_) | Insn::Jump { .. }))
+		{
+			return;
+		}
+		self.insert_before_returns(finally);
+
+		let start = self.new_label();
+		let end = self.new_label();
+		let handler = self.new_label();
+		self.insns.insert(0, Insn::Label(start));
+		self.insns.push(Insn::Label(end));
+		self.insns.push(Insn::Label(handler));
+		self.insns.push(Insn::Frame {
+			locals: self.initial_locals.iter().cloned().map(FrameValue::Type).collect(),
+			stack: vec![FrameValue::Type(VerificationTypeInfo::ObjectVariableInfo {
+				cpool_idx: throwable,
+			})],
+		});
+		let finally = self.instantiate(finally);
+		self.insns.extend(finally);
+		self.insns.push(Insn::Op(Instructions::ATHROW));
+		self.try_catch_blocks.push(TryCatchBlock {
+			start,
+			end,
+			handler,
+			catch_type: 0,
+		});
+	}
+
+	fn insert_around(&mut self, mut at: impl FnMut(&Instructions) -> bool, before: &[Insn], after: &[Insn]) {
+		let sites = self
+			.insns
+			.iter()
+			.enumerate()
+			.filter(|(_, insn)| matches!(insn, Insn::Op(instruction) if at(instruction)))
+			.map(|(i, _)| i)
+			.collect::<Vec<_>>();
+		// back to front so the indices of the sites still to do don't move
+		for i in sites.into_iter().rev() {
+			let after = self.instantiate(after);
+			self.insns.splice(i + 1..i + 1, after);
+			let before = self.instantiate(before);
+			self.insns.splice(i..i, before);
+		}
+	}
+
+	/// A copy of `snippet` with a new label for every label placed in it.
+	fn instantiate(&mut self, snippet: &[Insn]) -> Vec<Insn> {
+		let mut renamed = HashMap::new();
+		for insn in snippet {
+			if let Insn::Label(label) = insn {
+				if !renamed.contains_key(label) {
+					renamed.insert(*label, self.new_label());
+				}
+			}
+		}
+		let rename = |label: &Label| *renamed.get(label).unwrap_or(label);
+		let value = |value: &FrameValue| match value {
+			FrameValue::Uninitialized(label) => FrameValue::Uninitialized(rename(label)),
+			value => value.clone(),
+		};
+		snippet
+			.iter()
+			.map(|insn| match insn {
+				Insn::Label(label) => Insn::Label(rename(label)),
+				Insn::Jump { opcode, target } => Insn::Jump {
+					opcode: *opcode,
+					target: rename(target),
+				},
+				Insn::TableSwitch { default, low, targets } => Insn::TableSwitch {
+					default: rename(default),
+					low: *low,
+					targets: targets.iter().map(rename).collect(),
+				},
+				Insn::LookupSwitch { default, pairs } => Insn::LookupSwitch {
+					default: rename(default),
+					pairs: pairs.iter().map(|(value, label)| (*value, rename(label))).collect(),
+				},
+				Insn::Frame { locals, stack } => Insn::Frame {
+					locals: locals.iter().map(value).collect(),
+					stack: stack.iter().map(value).collect(),
+				},
+				insn => insn.clone(),
+			})
+			.collect()
+	}
+}
+
+fn is_return(instruction: &Instructions) -> bool {
+	matches!(
+		instruction,
+		Instructions::IRETURN
+			| Instructions::LRETURN
+			| Instructions::FRETURN
+			| Instructions::DRETURN
+			| Instructions::ARETURN
+			| Instructions::RETURN
+	)
+}
+
+fn is_invocation(instruction: &Instructions) -> bool {
+	matches!(
+		instruction,
+		Instructions::INVOKEVIRTUAL(_)
+			| Instructions::INVOKESPECIAL(_)
+			| Instructions::INVOKESTATIC(_)
+			| Instructions::INVOKEINTERFACE(..)
+			| Instructions::INVOKEDYNAMIC(_)
+	)
+}
+
+impl IRClassFile {
+	/// Decodes a method's code, lets `instrument` change it and writes it back, raising `max_stack`
+	/// and `max_locals` if the new code needs more. Returns whether the method exists and has code.
+	pub fn instrument_method(
+		&mut self,
+		name: &str,
+		descriptor: &str,
+		instrument: impl FnOnce(&mut InsnList),
+	) -> Result<bool, IRClassfileError> {
+		let Some(mut insns) = self.method_insns(name, descriptor)? else {
+			return Ok(false);
+		};
+		instrument(&mut insns);
+		self.store_method_insns(name, descriptor, &insns)
+	}
+
+	/// [`set_method_insns`](Self::set_method_insns), also raising `max_stack` and `max_locals` if
+	/// the new code needs more.
+	pub(crate) fn store_method_insns(
+		&mut self,
+		name: &str,
+		descriptor: &str,
+		insns: &InsnList,
+	) -> Result<bool, IRClassfileError> {
+		if !self.set_method_insns(name, descriptor, insns)? {
+			return Ok(false);
+		}
+		let max_stack = insns.max_stack()?;
+		let max_locals = insns.max_locals();
+		let method = self.find_method_mut(name, descriptor).unwrap();
+		for attribute in &mut method.attributes {
+			if let IRAttribute::Code(code) = &mut attribute.attr {
+				code.max_stack = code.max_stack.max(max_stack);
+				code.max_locals = code.max_locals.max(max_locals);
+			}
+		}
+		Ok(true)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		class_pool::CPTagRef,
+		code::Opcodes,
+		test_util::{class, method_code, HELLO},
+	};
+
+	#[test]
+	fn instrumentation() {
+		let mut class = class(HELLO);
+		let out = class
+			.intern_field_ref("java/lang/System", "out", "Ljava/io/PrintStream;")
+			.unwrap();
+		let println = class
+			.intern_method_ref("java/io/PrintStream", "println", "(Ljava/lang/String;)V", false)
+			.unwrap();
+		let message = class.intern_string("entered").unwrap().index;
+		let message = CPTagRef::from_cp(&class.cp, message);
+		let throwable = class.intern_class("java/lang/Throwable").unwrap().index;
+		let log = [
+			Insn::Op(Instructions::GETSTATIC(out)),
+			Insn::Op(Instructions::LDC(message)),
+			Insn::Op(Instructions::INVOKEVIRTUAL(println)),
+		];
+		// a snippet with a label of its own, which every copy renames
+		let mut skip = InsnList::new(Vec::new());
+		let label = skip.new_label();
+		let skip = [
+			Insn::Jump {
+				opcode: Opcodes::GOTO,
+				target: label,
+			},
+			Insn::Label(label),
+		];
+
+		let descriptor = "(ILjava/lang/Object;)V";
+		let found = class
+			.instrument_method("stackmapper", descriptor, |insns| {
+				insns.insert_at_entry(&log);
+				insns.insert_around_invocations(
+					|instruction| matches!(instruction, Instructions::INVOKESPECIAL(_)),
+					&skip,
+					&[Insn::Op(Instructions::NOP)],
+				);
+				insns.wrap_try_finally(&[Insn::Op(Instructions::NOP)], throwable);
+			})
+			.unwrap();
+		assert!(found);
+
+		let code = method_code(&class, "stackmapper");
+		// getstatic, ldc, invokevirtual, then the original code 8 bytes later
+		assert_eq!(code.code[..8], [0xB2, 0, 7, 0x12, 111, 0xB6, 0, 17]);
+		assert_eq!(code.code[8..10], [0x03, 0x3D]);
+		// goto +3 and a nop around the constructor call, then the finally before the return
+		assert_eq!(code.code[51..61], [0x59, 0xA7, 0, 3, 0xB7, 0, 31, 0x00, 0x3A, 6]);
+		assert_eq!(code.code[61..], [0x00, 0xB1, 0x00, 0xBF]);
+		let handler = code.exception_table.last().unwrap();
+		assert_eq!((handler.start_pc, handler.end_pc, handler.handler_pc), (0, 63, 63));
+		assert_eq!(handler.catch_type, 0);
+		assert_eq!(code.max_stack, 4);
+	}
+
+	#[test]
+	fn missing_method() {
+		assert!(!class(HELLO).instrument_method("missing", "()V", |_| {}).unwrap());
+	}
+}
@@ -0,0 +1,121 @@
+//! Sharing constant pool strings between classes, see [`StringInterner`].
+
+use std::collections::HashMap;
+
+use maya_mutf8::MUTFError;
+
+use crate::class_pool::Shared;
+
+/// Gives every distinct string one allocation across all the classes lifted with it through
+/// [`IRClassFile::from_io_interned`](crate::IRClassFile::from_io_interned), so names like
+/// `java/lang/Object` and common descriptors are decoded and stored once rather than once per class.
+/// Keep one for as long as the classes of a jar or an analysis, dropping it doesn't free what the
+/// classes still refer to.
+#[derive(Debug, Default)]
+pub struct StringInterner {
+	/// Keyed by the modified UTF-8 in the classfile, so a string seen before isn't decoded again.
+	strings: HashMap<Vec<u8>, Shared<String>>,
+}
+
+impl StringInterner {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// The shared copy of `string`, made if there's none yet.
+	pub fn intern(&mut self, string: &str) -> Shared<String> {
+		let key = maya_mutf8::encode(string);
+		if let Some(shared) = self.strings.get(&key) {
+			return shared.clone();
+		}
+		let shared = Shared::new(string.to_string());
+		self.strings.insert(key, shared.clone());
+		shared
+	}
+
+	/// The shared copy of the string encoded as `bytes`, decoding it only the first time.
+	pub(crate) fn intern_mutf8(&mut self, bytes: &[u8]) -> Result<Shared<String>, MUTFError> {
+		if let Some(shared) = self.strings.get(bytes) {
+			return Ok(shared.clone());
+		}
+		let shared = Shared::new(maya_mutf8::decode(bytes)?);
+		self.strings.insert(bytes.to_vec(), shared.clone());
+		Ok(shared)
+	}
+
+	/// Number of distinct strings.
+	pub fn len(&self) -> usize {
+		self.strings.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.strings.is_empty()
+	}
+
+	/// Forgets the strings no class refers to anymore.
+	pub fn shrink(&mut self) {
+		self.strings.retain(|_, shared| Shared::strong_count(shared) > 1);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::collections::HashSet;
+
+	use maya_classfile_io::IOClassFile;
+
+	use super::*;
+	use crate::{
+		class_pool::IRCpTag,
+		test_util::{class, HELLO, PAIR},
+		IRClassFile,
+	};
+
+	#[test]
+	fn string_interner() {
+		let mut interner = StringInterner::new();
+		let classes = [HELLO, PAIR]
+			.iter()
+			.map(|bytes| {
+				IRClassFile::from_io_interned(IOClassFile::read_from_slice(bytes).unwrap(), &mut interner).unwrap()
+			})
+			.collect::<Vec<_>>();
+		let (hello, pair) = (&classes[0], &classes[1]);
+		assert_eq!(pair.super_class.data.data.as_str(), "java/lang/Record");
+		let object = interner.intern("java/lang/Object");
+		assert!(Shared::ptr_eq(&hello.super_class.data.data, &object));
+		let object_init = |class: &IRClassFile| {
+			class
+				.cp
+				.iter()
+				.find_map(|tag| match tag {
+					IRCpTag::NameAndType { name, .. } if name.data.as_str() == "<init>" => Some(name.data.clone()),
+					_ => None,
+				})
+				.unwrap()
+		};
+		assert!(Shared::ptr_eq(&object_init(hello), &object_init(pair)));
+		assert!(Shared::ptr_eq(&hello.methods[0].name.data, &object_init(pair)));
+
+		// the same strings as without the interner, once each
+		let distinct = classes
+			.iter()
+			.flat_map(|class| &class.cp)
+			.filter_map(|tag| match tag {
+				IRCpTag::Utf8(data) => Some(data.as_str()),
+				_ => None,
+			})
+			.collect::<HashSet<_>>();
+		assert_eq!(interner.len(), distinct.len());
+		assert_eq!(class(HELLO).cp.len(), hello.cp.len());
+
+		let strings = interner.len();
+		interner.intern("not in any class");
+		interner.shrink();
+		assert_eq!(interner.len(), strings);
+		// only `object` is left holding on to one
+		drop(classes);
+		interner.shrink();
+		assert_eq!(interner.len(), 1);
+	}
+}
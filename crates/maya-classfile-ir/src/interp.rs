@@ -0,0 +1,824 @@
+//! Abstract interpretation of a method's code, see [`InsnList::analyze`].
+//!
+//! Every reachable instruction is run on abstract values until nothing changes, which gives the
+//! locals and stack before each instruction. What the values are is up to a [`Domain`]: it says
+//! what instructions produce and how values coming from different paths merge. [`ConstantDomain`]
+//! tracks constants and newly created objects, which answers questions like which arguments reach
+//! a call, whether a receiver is of an exact class, or whether a branch always goes the same way.
+//! [`KindDomain`] only tracks the kind of value, like int or reference, in every slot.
+
+use std::collections::HashMap;
+
+use thiserror::Error;
+
+use crate::{
+	attribute::VerificationTypeInfo,
+	class_pool::{IRClassfileError, IRCpTag},
+	code::{parameters, Instructions, Opcodes},
+	insn::{Insn, InsnList, Label},
+};
+
+#[derive(Debug, Error)]
+pub enum AnalysisError {
+	#[error("instruction {index} pops more than is on the stack")]
+	StackUnderflow { index: usize },
+	#[error("instruction {index} takes half of a long or double off the stack")]
+	SplitValue { index: usize },
+	#[error("instruction {index} reads local {local}, which has no value there")]
+	EmptyLocal { index: usize, local: u16 },
+	#[error("the paths reaching instruction {index} leave different stacks")]
+	StackMismatch { index: usize },
+	#[error("instruction {index} is a jsr or ret, subroutines aren't supported")]
+	Subroutine { index: usize },
+	#[error("code falls off the end after instruction {index}")]
+	FallsOffEnd { index: usize },
+	#[error("{0}")]
+	Class(#[from] IRClassfileError),
+}
+
+/// The values an analysis works with, and what instructions do to them.
+///
+/// Values form a lattice: [`merge`](Self::merge) joins the values two paths bring to the same
+/// place, and merging has to stop changing them after a while or the analysis won't finish.
+/// Loads, stores and stack shuffling only move values around and are handled by the analysis.
+pub trait Domain {
+	type Value: Clone + PartialEq;
+
+	/// A value nothing is known about.
+	fn unknown(&mut self) -> Self::Value;
+
+	/// The value of `this` or a parameter at the method's entry.
+	fn parameter(&mut self, _ty: &VerificationTypeInfo) -> Self::Value {
+		self.unknown()
+	}
+
+	/// The exception a handler catches, `catch_type` is 0 for any.
+	fn caught(&mut self, _catch_type: u16) -> Self::Value {
+		self.unknown()
+	}
+
+	fn merge(&mut self, a: &Self::Value, b: &Self::Value) -> Self::Value;
+
+	/// Runs the instruction at `index` on the values it pops, bottom of the stack first, and returns
+	/// the value it pushes. `None` pushes [`unknown`](Self::unknown). `iinc` gets the local it
+	/// increments and returns its new value.
+	fn execute(&mut self, index: usize, instruction: &Instructions, inputs: &[Self::Value]) -> Option<Self::Value>;
+
+	/// Whether the conditional jump `opcode` is taken with `inputs`, `None` if it can go either way.
+	fn branch(&mut self, _opcode: u8, _inputs: &[Self::Value]) -> Option<bool> {
+		None
+	}
+
+	/// The key a switch jumps on, `None` if it can be anything.
+	fn switch_key(&mut self, _key: &Self::Value) -> Option<i32> {
+		None
+	}
+}
+
+/// The locals and stack before an instruction.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame<V> {
+	/// One entry per slot. Slots without a value, like ones never written, the second half of a long
+	/// or double or ones the paths reaching here disagree on, are `None`.
+	pub locals: Vec<Option<V>>,
+	/// Bottom to top, a long or double is a single value.
+	pub stack: Vec<V>,
+	wide_locals: Vec<bool>,
+	wide_stack: Vec<bool>,
+}
+
+impl<V: Clone> Frame<V> {
+	pub(crate) fn empty() -> Self {
+		Self {
+			locals: Vec::new(),
+			stack: Vec::new(),
+			wide_locals: Vec::new(),
+			wide_stack: Vec::new(),
+		}
+	}
+
+	/// Whether the value in `local` is a long or double.
+	pub(crate) fn is_wide_local(&self, local: usize) -> bool {
+		self.wide_locals.get(local).copied().unwrap_or_default()
+	}
+
+	/// The `n` values on top of the stack, bottom first.
+	pub fn top(&self, n: usize) -> Option<&[V]> {
+		let start = self.stack.len().checked_sub(n)?;
+		Some(&self.stack[start..])
+	}
+
+	pub(crate) fn push(&mut self, value: V, wide: bool) {
+		self.stack.push(value);
+		self.wide_stack.push(wide);
+	}
+
+	/// Pops the values taking up the top `slots` slots, bottom first.
+	fn pop(&mut self, slots: u16, index: usize) -> Result<Vec<(V, bool)>, AnalysisError> {
+		let mut start = self.stack.len();
+		let mut popped = 0;
+		while popped < slots {
+			start = start.checked_sub(1).ok_or(AnalysisError::StackUnderflow { index })?;
+			popped += if self.wide_stack[start] { 2 } else { 1 };
+		}
+		if popped != slots {
+			return Err(AnalysisError::SplitValue { index });
+		}
+		let wide = self.wide_stack.drain(start..);
+		Ok(self.stack.drain(start..).zip(wide).collect())
+	}
+
+	pub(crate) fn pop_values(&mut self, slots: u16, index: usize) -> Result<Vec<V>, AnalysisError> {
+		Ok(self.pop(slots, index)?.into_iter().map(|(value, _)| value).collect())
+	}
+
+	fn load(&self, local: u16, index: usize) -> Result<V, AnalysisError> {
+		self.locals
+			.get(local as usize)
+			.cloned()
+			.flatten()
+			.ok_or(AnalysisError::EmptyLocal { index, local })
+	}
+
+	pub(crate) fn store(&mut self, local: u16, value: V, wide: bool) {
+		let local = local as usize;
+		let len = local + 1 + wide as usize;
+		if self.locals.len() < len {
+			self.locals.resize(len, None);
+			self.wide_locals.resize(len, false);
+		}
+		// overwriting the second half of a long or double leaves nothing usable in the first
+		if local > 0 && self.wide_locals[local - 1] {
+			self.locals[local - 1] = None;
+			self.wide_locals[local - 1] = false;
+		}
+		self.locals[local] = Some(value);
+		self.wide_locals[local] = wide;
+		if wide {
+			self.locals[local + 1] = None;
+			self.wide_locals[local + 1] = false;
+		}
+	}
+
+	fn merge<D: Domain<Value = V>>(&self, domain: &mut D, other: &Self) -> Self {
+		let len = self.locals.len().max(other.locals.len());
+		let mut locals = Vec::with_capacity(len);
+		let mut wide_locals = Vec::with_capacity(len);
+		for i in 0..len {
+			let a = self.locals.get(i).and_then(Option::as_ref);
+			let b = other.locals.get(i).and_then(Option::as_ref);
+			let wide = self.wide_locals.get(i).copied().unwrap_or_default();
+			match (a, b) {
+				(Some(a), Some(b)) if wide == other.wide_locals[i] => {
+					locals.push(Some(domain.merge(a, b)));
+					wide_locals.push(wide);
+				}
+				_ => {
+					locals.push(None);
+					wide_locals.push(false);
+				}
+			}
+		}
+		let stack = self
+			.stack
+			.iter()
+			.zip(&other.stack)
+			.map(|(a, b)| domain.merge(a, b))
+			.collect();
+		Self {
+			locals,
+			stack,
+			wide_locals,
+			wide_stack: self.wide_stack.clone(),
+		}
+	}
+}
+
+/// The result of [`InsnList::analyze`], a frame per instruction.
+#[derive(Debug, Clone)]
+pub struct Analysis<V> {
+	frames: Vec<Option<Frame<V>>>,
+}
+
+impl<V: Clone> Analysis<V> {
+	/// The locals and stack before `insns[index]`, `None` if no path reaches it.
+	pub fn frame(&self, index: usize) -> Option<&Frame<V>> {
+		self.frames.get(index)?.as_ref()
+	}
+
+	/// Every instruction of `insns` with the frame before it.
+	pub fn iter<'a>(&'a self, insns: &'a InsnList) -> impl Iterator<Item = (&'a Insn, Option<&'a Frame<V>>)> {
+		insns.insns.iter().zip(self.frames.iter().map(Option::as_ref))
+	}
+
+	pub fn is_reachable(&self, index: usize) -> bool {
+		self.frame(index).is_some()
+	}
+
+	/// The values the `invoke*` instruction at `insns[index]` is called with, the receiver first
+	/// unless it's `invokestatic` or `invokedynamic`. `None` if it isn't reached or isn't a call.
+	pub fn arguments(&self, insns: &InsnList, index: usize) -> Option<&[V]> {
+		let (receiver, descriptor) = match insns.insns.get(index)? {
+			Insn::Op(
+				Instructions::INVOKEVIRTUAL(method)
+				| Instructions::INVOKESPECIAL(method)
+				| Instructions::INVOKEINTERFACE(method, _),
+			) => (1, &method.name_and_ty.ty),
+			Insn::Op(Instructions::INVOKESTATIC(method)) => (0, &method.name_and_ty.ty),
+			Insn::Op(Instructions::INVOKEDYNAMIC(call_site)) => (0, &call_site.name_and_ty.ty),
+			_ => return None,
+		};
+		self.frame(index)?.top(receiver + parameters(&descriptor.data).count())
+	}
+}
+
+impl InsnList {
+	/// Runs `domain` over the code until every reachable instruction's frame stops changing, starting
+	/// from [`initial_locals`](Self::initial_locals). Handlers are entered with the locals before
+	/// every instruction they cover. Branches `domain` decides are always or never taken only
+	/// reach one side, which leaves the other unreachable if nothing else gets there.
+	pub fn analyze<D: Domain>(&self, domain: &mut D) -> Result<Analysis<D::Value>, AnalysisError> {
+		let mut labels = HashMap::new();
+		for (i, insn) in self.insns.iter().enumerate() {
+			if let Insn::Label(label) = insn {
+				labels.insert(*label, i);
+			}
+		}
+		let index = |label: &Label| {
+			labels
+				.get(label)
+				.copied()
+				.ok_or(IRClassfileError::UnplacedLabel(*label))
+		};
+		let mut handlers = Vec::with_capacity(self.try_catch_blocks.len());
+		for block in &self.try_catch_blocks {
+			handlers.push((
+				index(&block.start)?..index(&block.end)?,
+				index(&block.handler)?,
+				block.catch_type,
+			));
+		}
+
+		let mut entry = Frame::empty();
+		for ty in &self.initial_locals {
+			let wide = matches!(
+				ty,
+				VerificationTypeInfo::LongVariableInfo | VerificationTypeInfo::DoubleVariableInfo
+			);
+			let value = domain.parameter(ty);
+			let local = entry.locals.len() as u16;
+			entry.store(local, value, wide);
+		}
+
+		let mut analysis = Analysis {
+			frames: vec![None; self.insns.len()],
+		};
+		let mut pending = Vec::new();
+		if !self.insns.is_empty() {
+			analysis.frames[0] = Some(entry);
+			pending.push(0);
+		}
+		while let Some(i) = pending.pop() {
+			let mut frame = analysis.frames[i].clone().unwrap();
+			for (range, handler, catch_type) in &handlers {
+				if range.contains(&i) {
+					let mut caught = Frame {
+						stack: Vec::new(),
+						wide_stack: Vec::new(),
+						..frame.clone()
+					};
+					caught.push(domain.caught(*catch_type), false);
+					analysis.merge(domain, &mut pending, *handler, caught)?;
+				}
+			}
+
+			let next = i + 1;
+			let successors = match &self.insns[i] {
+				Insn::Label(_) | Insn::Line(_) | Insn::Frame { .. } => vec![next],
+				Insn::Op(instruction) => match execute(domain, i, instruction, &mut frame)? {
+					true => vec![next],
+					false => Vec::new(),
+				},
+				Insn::Jump { opcode, target } => match *opcode {
+					Opcodes::GOTO | Opcodes::GOTO_W => vec![index(target)?],
+					Opcodes::JSR | Opcodes::JSR_W => return Err(AnalysisError::Subroutine { index: i }),
+					opcode => {
+						let pops = match opcode {
+							Opcodes::IF_ICMPEQ..=Opcodes::IF_ACMPNE => 2,
+							_ => 1,
+						};
+						let inputs = frame.pop_values(pops, i)?;
+						match domain.branch(opcode, &inputs) {
+							Some(true) => vec![index(target)?],
+							Some(false) => vec![next],
+							None => vec![next, index(target)?],
+						}
+					}
+				},
+				Insn::TableSwitch { default, low, targets } => {
+					let key = frame.pop_values(1, i)?;
+					let target = domain.switch_key(&key[0]).map(|key| {
+						let offset = key as i64 - *low as i64;
+						usize::try_from(offset)
+							.ok()
+							.and_then(|offset| targets.get(offset))
+							.unwrap_or(default)
+					});
+					match target {
+						Some(target) => vec![index(target)?],
+						None => targets.iter().chain([default]).map(index).collect::<Result<_, _>>()?,
+					}
+				}
+				Insn::LookupSwitch { default, pairs } => {
+					let key = frame.pop_values(1, i)?;
+					let target = domain.switch_key(&key[0]).map(|key| {
+						pairs
+							.iter()
+							.find(|(value, _)| *value == key)
+							.map_or(default, |(_, target)| target)
+					});
+					match target {
+						Some(target) => vec![index(target)?],
+						None => pairs
+							.iter()
+							.map(|(_, target)| target)
+							.chain([default])
+							.map(index)
+							.collect::<Result<_, _>>()?,
+					}
+				}
+			};
+			for successor in successors {
+				if successor == self.insns.len() {
+					return Err(AnalysisError::FallsOffEnd { index: i });
+				}
+				analysis.merge(domain, &mut pending, successor, frame.clone())?;
+			}
+		}
+		Ok(analysis)
+	}
+}
+
+impl<V: Clone + PartialEq> Analysis<V> {
+	/// Merges `frame` into the one at `index`, queueing `index` again if that changed it.
+	fn merge<D: Domain<Value = V>>(
+		&mut self,
+		domain: &mut D,
+		pending: &mut Vec<usize>,
+		index: usize,
+		frame: Frame<V>,
+	) -> Result<(), AnalysisError> {
+		let merged = match &self.frames[index] {
+			None => frame,
+			Some(existing) => {
+				if existing.wide_stack != frame.wide_stack {
+					return Err(AnalysisError::StackMismatch { index });
+				}
+				let merged = existing.merge(domain, &frame);
+				if merged == *existing {
+					return Ok(());
+				}
+				merged
+			}
+		};
+		self.frames[index] = Some(merged);
+		if !pending.contains(&index) {
+			pending.push(index);
+		}
+		Ok(())
+	}
+}
+
+/// Runs a non-branching instruction on `frame`, returns whether execution continues with the next
+/// one.
+pub(crate) fn execute<D: Domain>(
+	domain: &mut D,
+	index: usize,
+	instruction: &Instructions,
+	frame: &mut Frame<D::Value>,
+) -> Result<bool, AnalysisError> {
+	use Instructions::*;
+	match instruction {
+		ILOAD(local) | FLOAD(local) | ALOAD(local) => frame.push(frame.load(*local, index)?, false),
+		LLOAD(local) | DLOAD(local) => frame.push(frame.load(*local, index)?, true),
+		ISTORE(local) | FSTORE(local) | ASTORE(local) | LSTORE(local) | DSTORE(local) => {
+			let wide = matches!(instruction, LSTORE(_) | DSTORE(_));
+			let mut popped = frame.pop(1 + wide as u16, index)?;
+			match (popped.pop(), popped.is_empty()) {
+				(Some((value, popped_wide)), true) if popped_wide == wide => frame.store(*local, value, wide),
+				_ => return Err(AnalysisError::SplitValue { index }),
+			}
+		}
+		IINC(local, _) => {
+			let value = frame.load(*local, index)?;
+			let value = domain
+				.execute(index, instruction, &[value])
+				.unwrap_or_else(|| domain.unknown());
+			frame.store(*local, value, false);
+		}
+		POP | POP2 | DUP | DUP_X1 | DUP_X2 | DUP2 | DUP2_X1 | DUP2_X2 | SWAP => {
+			// the slots of the top group and of the one below it that the instruction moves around
+			let (top, below) = match instruction {
+				POP | DUP => (1, 0),
+				POP2 | DUP2 => (2, 0),
+				DUP_X1 | SWAP => (1, 1),
+				DUP_X2 => (1, 2),
+				DUP2_X1 => (2, 1),
+				_ => (2, 2),
+			};
+			let top = frame.pop(top, index)?;
+			let below = frame.pop(below, index)?;
+			let order = match instruction {
+				POP | POP2 => Vec::new(),
+				SWAP => vec![&top, &below],
+				_ => vec![&top, &below, &top],
+			};
+			for (value, wide) in order.into_iter().flatten() {
+				frame.push(value.clone(), *wide);
+			}
+		}
+		RET(_) => return Err(AnalysisError::Subroutine { index }),
+		_ => {
+			let (pops, pushes) = instruction.stack_effect();
+			let inputs = frame.pop_values(pops, index)?;
+			let result = domain.execute(index, instruction, &inputs);
+			if pushes > 0 {
+				let value = result.unwrap_or_else(|| domain.unknown());
+				frame.push(value, pushes == 2);
+			}
+			return Ok(!matches!(
+				instruction,
+				IRETURN | LRETURN | FRETURN | DRETURN | ARETURN | RETURN | ATHROW
+			));
+		}
+	}
+	Ok(true)
+}
+
+/// A value of [`ConstantDomain`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum Constant {
+	Int(i32),
+	Long(i64),
+	Float(f32),
+	Double(f64),
+	Null,
+	String(String),
+	/// A class literal, from `ldc`.
+	Class(String),
+	/// An object of exactly this class, from `new`.
+	New(String),
+	Unknown,
+}
+
+/// Constant propagation: tracks the constants code loads and folds arithmetic, comparisons and
+/// conversions on them. Two different values merge to [`Constant::Unknown`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ConstantDomain;
+
+impl Domain for ConstantDomain {
+	type Value = Constant;
+
+	fn unknown(&mut self) -> Constant {
+		Constant::Unknown
+	}
+
+	fn merge(&mut self, a: &Constant, b: &Constant) -> Constant {
+		match a == b {
+			true => a.clone(),
+			false => Constant::Unknown,
+		}
+	}
+
+	fn execute(&mut self, _index: usize, instruction: &Instructions, inputs: &[Constant]) -> Option<Constant> {
+		use Constant::*;
+		use Instructions as I;
+
+		let value = match (instruction, inputs) {
+			(I::ACONST_NULL, _) => Null,
+			(I::ICONST_M1, _) => Int(-1),
+			(I::ICONST_0, _) => Int(0),
+			(I::ICONST_1, _) => Int(1),
+			(I::ICONST_2, _) => Int(2),
+			(I::ICONST_3, _) => Int(3),
+			(I::ICONST_4, _) => Int(4),
+			(I::ICONST_5, _) => Int(5),
+			(I::LCONST_0, _) => Long(0),
+			(I::LCONST_1, _) => Long(1),
+			(I::FCONST_0, _) => Float(0.0),
+			(I::FCONST_1, _) => Float(1.0),
+			(I::FCONST_2, _) => Float(2.0),
+			(I::DCONST_0, _) => Double(0.0),
+			(I::DCONST_1, _) => Double(1.0),
+			(I::BIPUSH(value), _) => Int(*value as i32),
+			(I::SIPUSH(value), _) => Int(*value as i32),
+			(I::LDC(constant) | I::LDC_W(constant) | I::LDC2_W(constant), _) => match &constant.tag {
+				IRCpTag::Integer(value) => Int(*value),
+				IRCpTag::Float(value) => Float(*value),
+				IRCpTag::Long(value) => Long(*value),
+				IRCpTag::Double(value) => Double(*value),
+				IRCpTag::String(utf8) => String(utf8.data.to_string()),
+				IRCpTag::Class(name) => Class(name.data.to_string()),
+				_ => return None,
+			},
+			(I::IINC(_, by), [Int(value)]) => Int(value.wrapping_add(*by as i32)),
+
+			(I::IADD, [Int(a), Int(b)]) => Int(a.wrapping_add(*b)),
+			(I::ISUB, [Int(a), Int(b)]) => Int(a.wrapping_sub(*b)),
+			(I::IMUL, [Int(a), Int(b)]) => Int(a.wrapping_mul(*b)),
+			(I::IDIV, [Int(a), Int(b)]) if *b != 0 => Int(a.wrapping_div(*b)),
+			(I::IREM, [Int(a), Int(b)]) if *b != 0 => Int(a.wrapping_rem(*b)),
+			(I::ISHL, [Int(a), Int(b)]) => Int(a.wrapping_shl(*b as u32)),
+			(I::ISHR, [Int(a), Int(b)]) => Int(a.wrapping_shr(*b as u32)),
+			(I::IUSHR, [Int(a), Int(b)]) => Int((*a as u32).wrapping_shr(*b as u32) as i32),
+			(I::IAND, [Int(a), Int(b)]) => Int(a & b),
+			(I::IOR, [Int(a), Int(b)]) => Int(a | b),
+			(I::IXOR, [Int(a), Int(b)]) => Int(a ^ b),
+			(I::INEG, [Int(a)]) => Int(a.wrapping_neg()),
+
+			(I::LADD, [Long(a), Long(b)]) => Long(a.wrapping_add(*b)),
+			(I::LSUB, [Long(a), Long(b)]) => Long(a.wrapping_sub(*b)),
+			(I::LMUL, [Long(a), Long(b)]) => Long(a.wrapping_mul(*b)),
+			(I::LDIV, [Long(a), Long(b)]) if *b != 0 => Long(a.wrapping_div(*b)),
+			(I::LREM, [Long(a), Long(b)]) if *b != 0 => Long(a.wrapping_rem(*b)),
+			(I::LSHL, [Long(a), Int(b)]) => Long(a.wrapping_shl(*b as u32)),
+			(I::LSHR, [Long(a), Int(b)]) => Long(a.wrapping_shr(*b as u32)),
+			(I::LUSHR, [Long(a), Int(b)]) => Long((*a as u64).wrapping_shr(*b as u32) as i64),
+			(I::LAND, [Long(a), Long(b)]) => Long(a & b),
+			(I::LOR, [Long(a), Long(b)]) => Long(a | b),
+			(I::LXOR, [Long(a), Long(b)]) => Long(a ^ b),
+			(I::LNEG, [Long(a)]) => Long(a.wrapping_neg()),
+
+			(I::FADD, [Float(a), Float(b)]) => Float(a + b),
+			(I::FSUB, [Float(a), Float(b)]) => Float(a - b),
+			(I::FMUL, [Float(a), Float(b)]) => Float(a * b),
+			(I::FDIV, [Float(a), Float(b)]) => Float(a / b),
+			(I::FREM, [Float(a), Float(b)]) => Float(a % b),
+			(I::FNEG, [Float(a)]) => Float(-a),
+			(I::DADD, [Double(a), Double(b)]) => Double(a + b),
+			(I::DSUB, [Double(a), Double(b)]) => Double(a - b),
+			(I::DMUL, [Double(a), Double(b)]) => Double(a * b),
+			(I::DDIV, [Double(a), Double(b)]) => Double(a / b),
+			(I::DREM, [Double(a), Double(b)]) => Double(a % b),
+			(I::DNEG, [Double(a)]) => Double(-a),
+
+			// `as` saturates and turns NaN into 0 like the JVM does
+			(I::I2L, [Int(a)]) => Long(*a as i64),
+			(I::I2F, [Int(a)]) => Float(*a as f32),
+			(I::I2D, [Int(a)]) => Double(*a as f64),
+			(I::I2B, [Int(a)]) => Int(*a as i8 as i32),
+			(I::I2C, [Int(a)]) => Int(*a as u16 as i32),
+			(I::I2S, [Int(a)]) => Int(*a as i16 as i32),
+			(I::L2I, [Long(a)]) => Int(*a as i32),
+			(I::L2F, [Long(a)]) => Float(*a as f32),
+			(I::L2D, [Long(a)]) => Double(*a as f64),
+			(I::F2I, [Float(a)]) => Int(*a as i32),
+			(I::F2L, [Float(a)]) => Long(*a as i64),
+			(I::F2D, [Float(a)]) => Double(*a as f64),
+			(I::D2I, [Double(a)]) => Int(*a as i32),
+			(I::D2L, [Double(a)]) => Long(*a as i64),
+			(I::D2F, [Double(a)]) => Float(*a as f32),
+
+			(I::LCMP, [Long(a), Long(b)]) => Int(a.cmp(b) as i32),
+			(I::FCMPL, [Float(a), Float(b)]) => Int(a.partial_cmp(b).map_or(-1, |ordering| ordering as i32)),
+			(I::FCMPG, [Float(a), Float(b)]) => Int(a.partial_cmp(b).map_or(1, |ordering| ordering as i32)),
+			(I::DCMPL, [Double(a), Double(b)]) => Int(a.partial_cmp(b).map_or(-1, |ordering| ordering as i32)),
+			(I::DCMPG, [Double(a), Double(b)]) => Int(a.partial_cmp(b).map_or(1, |ordering| ordering as i32)),
+
+			(I::NEW(class), _) => New(class.data.data.to_string()),
+			(I::CHECKCAST(_), [value]) => value.clone(),
+			_ => return None,
+		};
+		Some(value)
+	}
+
+	fn branch(&mut self, opcode: u8, inputs: &[Constant]) -> Option<bool> {
+		use Constant::*;
+
+		match (opcode, inputs) {
+			(Opcodes::IFEQ..=Opcodes::IFLE, [Int(a)]) => Some(compare(opcode - Opcodes::IFEQ, *a, 0)),
+			(Opcodes::IF_ICMPEQ..=Opcodes::IF_ICMPLE, [Int(a), Int(b)]) => {
+				Some(compare(opcode - Opcodes::IF_ICMPEQ, *a, *b))
+			}
+			(Opcodes::IFNULL | Opcodes::IFNONNULL, [value]) => {
+				let null = match value {
+					Null => true,
+					String(_) | Class(_) | New(_) => false,
+					_ => return None,
+				};
+				Some(null == (opcode == Opcodes::IFNULL))
+			}
+			(Opcodes::IF_ACMPEQ | Opcodes::IF_ACMPNE, [Null, Null]) => Some(opcode == Opcodes::IF_ACMPEQ),
+			_ => None,
+		}
+	}
+
+	fn switch_key(&mut self, key: &Constant) -> Option<i32> {
+		match key {
+			Constant::Int(key) => Some(*key),
+			_ => None,
+		}
+	}
+}
+
+/// A value of [`KindDomain`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Kind {
+	Int,
+	Float,
+	Long,
+	Double,
+	/// An object or array reference, or null.
+	Reference,
+	/// What a local holds when the paths reaching it disagree.
+	Unknown,
+}
+
+impl Kind {
+	/// The kind of a value with the field descriptor `descriptor`, `None` for `V`.
+	pub fn of(descriptor: &str) -> Option<Self> {
+		Some(match descriptor.as_bytes().first()? {
+			b'B' | b'C' | b'I' | b'S' | b'Z' => Self::Int,
+			b'F' => Self::Float,
+			b'J' => Self::Long,
+			b'D' => Self::Double,
+			b'V' => return None,
+			_ => Self::Reference,
+		})
+	}
+}
+
+/// Tracks what kind of value, int, long, reference and so on, each local and stack entry holds.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct KindDomain;
+
+impl Domain for KindDomain {
+	type Value = Kind;
+
+	fn unknown(&mut self) -> Kind {
+		Kind::Unknown
+	}
+
+	fn parameter(&mut self, ty: &VerificationTypeInfo) -> Kind {
+		match ty {
+			VerificationTypeInfo::TopVariableInfo => Kind::Unknown,
+			VerificationTypeInfo::IntegerVariableInfo => Kind::Int,
+			VerificationTypeInfo::FloatVariableInfo => Kind::Float,
+			VerificationTypeInfo::LongVariableInfo => Kind::Long,
+			VerificationTypeInfo::DoubleVariableInfo => Kind::Double,
+			_ => Kind::Reference,
+		}
+	}
+
+	fn caught(&mut self, _catch_type: u16) -> Kind {
+		Kind::Reference
+	}
+
+	fn merge(&mut self, a: &Kind, b: &Kind) -> Kind {
+		match a == b {
+			true => *a,
+			false => Kind::Unknown,
+		}
+	}
+
+	fn execute(&mut self, _index: usize, instruction: &Instructions, _inputs: &[Kind]) -> Option<Kind> {
+		use Instructions::*;
+
+		Some(match instruction {
+			ACONST_NULL | AALOAD | NEW(_) | NEWARRAY(_) | ANEWARRAY(_) | CHECKCAST(_) | MULTIANEWARRAY(..) => {
+				Kind::Reference
+			}
+			ICONST_M1 | ICONST_0 | ICONST_1 | ICONST_2 | ICONST_3 | ICONST_4 | ICONST_5 | BIPUSH(_) | SIPUSH(_)
+			| IALOAD | BALOAD | CALOAD | SALOAD | IADD | ISUB | IMUL | IDIV | IREM | INEG | ISHL | ISHR | IUSHR
+			| IAND | IOR | IXOR | IINC(..) | L2I | F2I | D2I | I2B | I2C | I2S | LCMP | FCMPL | FCMPG | DCMPL
+			| DCMPG | ARRAYLENGTH | INSTANCEOF(_) => Kind::Int,
+			LCONST_0 | LCONST_1 | LALOAD | LADD | LSUB | LMUL | LDIV | LREM | LNEG | LSHL | LSHR | LUSHR | LAND
+			| LOR | LXOR | I2L | F2L | D2L => Kind::Long,
+			FCONST_0 | FCONST_1 | FCONST_2 | FALOAD | FADD | FSUB | FMUL | FDIV | FREM | FNEG | I2F | L2F | D2F => {
+				Kind::Float
+			}
+			DCONST_0 | DCONST_1 | DALOAD | DADD | DSUB | DMUL | DDIV | DREM | DNEG | I2D | L2D | F2D => Kind::Double,
+			LDC(constant) | LDC_W(constant) | LDC2_W(constant) => match &constant.tag {
+				IRCpTag::Integer(_) => Kind::Int,
+				IRCpTag::Float(_) => Kind::Float,
+				IRCpTag::Long(_) => Kind::Long,
+				IRCpTag::Double(_) => Kind::Double,
+				IRCpTag::Dynamic { name_and_ty, .. } => Kind::of(&name_and_ty.ty.data)?,
+				_ => Kind::Reference,
+			},
+			GETSTATIC(field) | GETFIELD(field) => Kind::of(&field.name_and_ty.ty.data)?,
+			INVOKEVIRTUAL(method) | INVOKESPECIAL(method) | INVOKESTATIC(method) | INVOKEINTERFACE(method, _) => {
+				Kind::of(method.name_and_ty.ty.data.split_once(')')?.1)?
+			}
+			INVOKEDYNAMIC(call_site) => Kind::of(call_site.name_and_ty.ty.data.split_once(')')?.1)?,
+			_ => return None,
+		})
+	}
+}
+
+/// `condition` counts from `eq` in the order the `if` opcodes come in: eq, ne, lt, ge, gt, le.
+fn compare(condition: u8, a: i32, b: i32) -> bool {
+	match condition {
+		0 => a == b,
+		1 => a != b,
+		2 => a < b,
+		3 => a >= b,
+		4 => a > b,
+		_ => a <= b,
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::test_util::{class, HELLO};
+
+	#[test]
+	fn stack_depths() {
+		let mut class = class(HELLO);
+		let insns = class.method_insns("main", "([Ljava/lang/String;)V").unwrap().unwrap();
+		let depths = insns
+			.with_stack_depths()
+			.unwrap()
+			.filter(|(insn, _)| matches!(insn, Insn::Op(_)))
+			.map(|(_, depth)| depth.unwrap())
+			.collect::<Vec<_>>();
+		assert_eq!(depths, [0, 1, 2, 0]);
+
+		let insns = class
+			.method_insns("stackmapper", "(ILjava/lang/Object;)V")
+			.unwrap()
+			.unwrap();
+		assert_eq!(insns.stack_depths().unwrap().into_iter().flatten().max(), Some(4));
+	}
+
+	#[test]
+	fn kinds() {
+		let mut class = class(HELLO);
+		let insns = class
+			.method_insns("stackmapper", "(ILjava/lang/Object;)V")
+			.unwrap()
+			.unwrap();
+		let analysis = insns.analyze(&mut KindDomain).unwrap();
+		let (_, frame) = analysis
+			.iter(&insns)
+			.find(|(insn, _)| matches!(insn, Insn::Op(Instructions::INVOKESPECIAL(_))))
+			.unwrap();
+		let frame = frame.unwrap();
+		assert_eq!(frame.stack, [Kind::Reference, Kind::Reference]);
+		assert_eq!(
+			frame.locals[..4],
+			[Some(Kind::Int), Some(Kind::Reference), Some(Kind::Int), Some(Kind::Int)]
+		);
+		assert_eq!(frame.locals[5], Some(Kind::Reference));
+	}
+
+	#[test]
+	fn constant_propagation() {
+		let mut class = class(HELLO);
+		let insns = class
+			.method_insns("stackmapper", "(ILjava/lang/Object;)V")
+			.unwrap()
+			.unwrap();
+		let analysis = insns.analyze(&mut ConstantDomain).unwrap();
+		let position = |predicate: fn(&Insn) -> bool| insns.insns.iter().position(predicate).unwrap();
+		// `if (b > 0)` tests a local that always holds 0, so its body is never run
+		let sipush = position(|insn| matches!(insn, Insn::Op(Instructions::SIPUSH(1111))));
+		assert!(!analysis.is_reachable(sipush));
+		let ldc = position(|insn| matches!(insn, Insn::Op(Instructions::LDC(_))));
+		assert!(!analysis.is_reachable(ldc));
+		let init = position(|insn| matches!(insn, Insn::Op(Instructions::INVOKESPECIAL(_))));
+		assert_eq!(
+			analysis.arguments(&insns, init).unwrap(),
+			[Constant::New("a/Hello$1".to_string())]
+		);
+		let frame = analysis.frame(init).unwrap();
+		assert_eq!(
+			frame.locals[..4],
+			[
+				Some(Constant::Unknown),
+				Some(Constant::Unknown),
+				Some(Constant::Int(0)),
+				Some(Constant::Int(0))
+			]
+		);
+		assert_eq!(frame.locals[5], Some(Constant::Unknown));
+		assert_eq!(frame.locals.get(7).cloned().flatten(), None);
+	}
+
+	#[test]
+	fn constant_arguments() {
+		let mut class = class(HELLO);
+		let insns = class.method_insns("main", "([Ljava/lang/String;)V").unwrap().unwrap();
+		let analysis = insns.analyze(&mut ConstantDomain).unwrap();
+		let println = insns
+			.insns
+			.iter()
+			.position(|insn| matches!(insn, Insn::Op(Instructions::INVOKEVIRTUAL(_))))
+			.unwrap();
+		assert_eq!(
+			analysis.arguments(&insns, println).unwrap(),
+			[Constant::Unknown, Constant::String("Hello World!".to_string())]
+		);
+	}
+}
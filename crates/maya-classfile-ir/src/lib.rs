@@ -1,14 +1,58 @@
 use std::cmp::Ordering;
 
-use attribute::IRAttributeInfo;
-use class_pool::{CPClassRef, CPUtf8Ref, IRClassfileError, IRCpTag};
-use maya_classfile_io::{IOClassFile, IOFieldInfo, IOMethodInfo};
+use access::{ClassAccessFlags, FieldAccessFlags, MethodAccessFlags};
+use attribute::{count, IRAttribute, IRAttributeInfo, RecordComponentInfo};
+use class_pool::{
+	CPClassRef, CPFieldRef, CPMethodRef, CPNameAndTypeRef, CPStringRef, CPUtf8Ref, ConstantPool, IRClassfileError,
+	IRCpTag,
+};
+use interner::StringInterner;
+use maya_bytes::BytesWriteExt;
+use maya_classfile_io::{
+	options::Limits, pool_builder::ConstantPoolBuilder, IOAttributeInfo, IOClassFile, IOClassfileError, IOFieldInfo,
+	IOMethodInfo,
+};
 
+pub mod access;
+pub mod annotation;
+#[cfg(feature = "arena")]
+pub mod arena;
+pub mod asm;
 pub mod attribute;
+pub mod builder;
+pub mod callgraph;
 pub mod class_pool;
 pub mod code;
+mod compact;
+pub mod dce;
+pub mod diff;
+pub mod disasm;
+pub mod edit;
+pub mod indy;
+mod infer;
+pub mod insn;
+pub mod instrument;
+pub mod interner;
+pub mod interp;
+pub mod locals;
+pub mod opcode;
+#[cfg(feature = "rayon")]
+pub mod parallel;
+pub mod pattern;
+pub mod preview;
+pub mod remap;
+pub mod resolve;
+pub mod sealed;
+pub mod smap;
+pub mod ssa;
+pub mod stackmap;
+pub mod stats;
+#[cfg(test)]
+pub(crate) mod test_util;
+pub mod version;
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ClassFileVersion {
 	pub major: u16,
 	pub minor: u16,
@@ -30,22 +74,10 @@ impl Ord for ClassFileVersion {
 	}
 }
 
-pub struct AccessFlags;
-impl AccessFlags {
-	pub const PUBLIC: u16 = 0x001;
-	pub const FINAL: u16 = 0x010;
-	pub const SUPER: u16 = 0x020;
-	pub const INTERFACE: u16 = 0x0200;
-	pub const ABSTRACT: u16 = 0x0400;
-	pub const SYNTHETIC: u16 = 0x1000;
-	pub const ANNOTATION: u16 = 0x2000;
-	pub const ENUM: u16 = 0x4000;
-	pub const MODULE: u16 = 0x8000;
-}
-
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct IRFieldInfo {
-	pub access_flags: u16,
+	pub access_flags: FieldAccessFlags,
 	pub name: CPUtf8Ref,
 	pub descriptor: CPUtf8Ref,
 	pub attributes: Vec<IRAttributeInfo>,
@@ -61,6 +93,13 @@ impl IRFieldInfo {
 			raw.descriptor_index,
 			cp.get(raw.descriptor_index as usize - 1).expect("invalid idx"),
 		);
+		#[cfg(feature = "tracing")]
+		let _span = tracing::trace_span!(
+			"field",
+			name = name.data.as_str(),
+			descriptor = descriptor.data.as_str()
+		)
+		.entered();
 		let attributes = raw
 			.attributes
 			.into_iter()
@@ -68,17 +107,37 @@ impl IRFieldInfo {
 			.collect::<Result<Vec<_>, _>>()?;
 
 		Ok(Self {
-			access_flags: raw.access_flags,
+			access_flags: FieldAccessFlags::from_bits_retain(raw.access_flags),
 			name,
 			descriptor,
 			attributes,
 		})
 	}
+
+	pub fn name(&self) -> &str {
+		&self.name.data
+	}
+
+	pub fn descriptor(&self) -> &str {
+		&self.descriptor.data
+	}
+
+	pub fn to_io(&self, cp: &[IRCpTag]) -> Result<IOFieldInfo, IRClassfileError> {
+		let attributes = lower_attributes(cp, &self.attributes)?;
+		Ok(IOFieldInfo {
+			access_flags: self.access_flags.bits(),
+			name_index: self.name.index,
+			descriptor_index: self.descriptor.index,
+			attributes_count: count("attributes", attributes.len())?,
+			attributes,
+		})
+	}
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct IRMethodInfo {
-	pub access_flags: u16,
+	pub access_flags: MethodAccessFlags,
 	pub name: CPUtf8Ref,
 	pub descriptor: CPUtf8Ref,
 	pub attributes: Vec<IRAttributeInfo>,
@@ -94,6 +153,13 @@ impl IRMethodInfo {
 			raw.descriptor_index,
 			cp.get(raw.descriptor_index as usize - 1).expect("invalid idx"),
 		);
+		#[cfg(feature = "tracing")]
+		let _span = tracing::trace_span!(
+			"method",
+			name = name.data.as_str(),
+			descriptor = descriptor.data.as_str()
+		)
+		.entered();
 		let attributes = raw
 			.attributes
 			.into_iter()
@@ -101,20 +167,40 @@ impl IRMethodInfo {
 			.collect::<Result<Vec<_>, _>>()?;
 
 		Ok(Self {
-			access_flags: raw.access_flags,
+			access_flags: MethodAccessFlags::from_bits_retain(raw.access_flags),
 			name,
 			descriptor,
 			attributes,
 		})
 	}
+
+	pub fn name(&self) -> &str {
+		&self.name.data
+	}
+
+	pub fn descriptor(&self) -> &str {
+		&self.descriptor.data
+	}
+
+	pub fn to_io(&self, cp: &[IRCpTag]) -> Result<IOMethodInfo, IRClassfileError> {
+		let attributes = lower_attributes(cp, &self.attributes)?;
+		Ok(IOMethodInfo {
+			access_flags: self.access_flags.bits(),
+			name_index: self.name.index,
+			descriptor_index: self.descriptor.index,
+			attributes_count: count("attributes", attributes.len())?,
+			attributes,
+		})
+	}
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct IRClassFile {
 	pub magic: u32,
 	pub version: ClassFileVersion,
-	pub cp: Vec<IRCpTag>,
-	pub access_flags: u16,
+	pub cp: ConstantPool,
+	pub access_flags: ClassAccessFlags,
 	pub this_class: CPClassRef,
 	pub super_class: CPClassRef,
 	pub interfaces: Vec<CPClassRef>,
@@ -125,17 +211,43 @@ pub struct IRClassFile {
 
 impl IRClassFile {
 	pub fn from_io(raw: IOClassFile) -> Result<Self, IRClassfileError> {
+		Self::from_io_with(raw, None)
+	}
+
+	/// Like [`IRClassFile::from_io`], failing with [`LimitExceeded`](IOClassfileError::LimitExceeded) on
+	/// annotations nested deeper than [`Limits::max_nesting`]. Pass the limits the class was read with.
+	pub fn from_io_limited(raw: IOClassFile, limits: &Limits) -> Result<Self, IRClassfileError> {
+		attribute::with_max_nesting(limits.max_nesting, || Self::from_io_with(raw, None))
+	}
+
+	/// Like [`IRClassFile::from_io`], sharing the constant pool's strings with the other classes lifted
+	/// with `interner`.
+	pub fn from_io_interned(raw: IOClassFile, interner: &mut StringInterner) -> Result<Self, IRClassfileError> {
+		Self::from_io_with(raw, Some(interner))
+	}
+
+	#[cfg_attr(
+		feature = "tracing",
+		tracing::instrument(level = "debug", name = "class", skip_all, fields(name = tracing::field::Empty), err)
+	)]
+	fn from_io_with(raw: IOClassFile, interner: Option<&mut StringInterner>) -> Result<Self, IRClassfileError> {
 		let magic = raw.magic;
 		let version = ClassFileVersion {
 			major: raw.major_version,
 			minor: raw.minor_version,
 		};
-		let cp = IRCpTag::from_io(raw.cp).unwrap();
-		let access_flags = raw.access_flags;
+		let cp = match interner {
+			Some(interner) => IRCpTag::from_io_interned(raw.cp, interner),
+			None => IRCpTag::from_io(raw.cp),
+		}
+		.unwrap();
+		let access_flags = ClassAccessFlags::from_bits_retain(raw.access_flags);
 		let this_class = CPClassRef::new(
 			raw.this_class,
 			cp.get(raw.this_class.saturating_sub(1) as usize).unwrap(),
 		);
+		#[cfg(feature = "tracing")]
+		tracing::Span::current().record("name", this_class.data.data.as_str());
 		let super_class = CPClassRef::new(
 			raw.super_class,
 			cp.get(raw.super_class.saturating_sub(1) as usize).unwrap(),
@@ -165,7 +277,7 @@ impl IRClassFile {
 		Ok(Self {
 			magic,
 			version,
-			cp,
+			cp: cp.into(),
 			access_flags,
 			this_class,
 			super_class,
@@ -175,4 +287,598 @@ impl IRClassFile {
 			attributes,
 		})
 	}
+
+	/// Lowers the class back to an [`IOClassFile`]. The constant pool is rebuilt from `cp` as is and
+	/// every attribute is reserialized, so all indices stored in the IR must point into `cp`.
+	pub fn to_io(&self) -> Result<IOClassFile, IRClassfileError> {
+		let cp = self.cp.iter().map(IRCpTag::to_io).collect::<Result<Vec<_>, _>>()?;
+		let fields = self
+			.fields
+			.iter()
+			.map(|field| field.to_io(&self.cp))
+			.collect::<Result<Vec<_>, _>>()?;
+		let methods = self
+			.methods
+			.iter()
+			.map(|method| method.to_io(&self.cp))
+			.collect::<Result<Vec<_>, _>>()?;
+		let attributes = lower_attributes(&self.cp, &self.attributes)?;
+
+		let class = IOClassFile {
+			magic: self.magic,
+			minor_version: self.version.minor,
+			major_version: self.version.major,
+			cp_count: count("constant pool entries", cp.len() + 1)?,
+			cp,
+			access_flags: self.access_flags.bits(),
+			this_class: self.this_class.index,
+			super_class: self.super_class.index,
+			interface_count: count("interfaces", self.interfaces.len())?,
+			interfaces: self.interfaces.iter().map(|class| class.index).collect(),
+			field_count: count("fields", fields.len())?,
+			fields,
+			method_count: count("methods", methods.len())?,
+			methods,
+			attribute_count: count("attributes", attributes.len())?,
+			attributes,
+			skipped: Vec::new(),
+			trailing: Vec::new(),
+		};
+		class.validate().map_err(IOClassfileError::from)?;
+		Ok(class)
+	}
+
+	pub fn write<B: BytesWriteExt>(&self, buffer: &mut B) -> Result<(), IRClassfileError> {
+		Ok(self.to_io()?.write(buffer)?)
+	}
+
+	pub fn class_name(&self) -> &str {
+		&self.this_class.data.data
+	}
+
+	/// `None` for `java/lang/Object` and `module-info`, which have no superclass.
+	pub fn super_name(&self) -> Option<&str> {
+		(self.super_class.index != 0).then(|| self.super_class.data.data.as_str())
+	}
+
+	pub fn interface_names(&self) -> impl Iterator<Item = &str> {
+		self.interfaces.iter().map(|class| class.data.data.as_str())
+	}
+
+	pub fn find_method(&self, name: &str, descriptor: &str) -> Option<&IRMethodInfo> {
+		self.methods
+			.iter()
+			.find(|method| method.name() == name && method.descriptor() == descriptor)
+	}
+
+	pub fn find_method_mut(&mut self, name: &str, descriptor: &str) -> Option<&mut IRMethodInfo> {
+		self.methods
+			.iter_mut()
+			.find(|method| method.name() == name && method.descriptor() == descriptor)
+	}
+
+	/// Every overload of `name`, in declaration order.
+	pub fn methods_named<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a IRMethodInfo> {
+		self.methods.iter().filter(move |method| method.name() == name)
+	}
+
+	/// The field called `name`. javac never emits two fields with the same name, if a class does
+	/// anyway the first one wins, use [`Self::find_field_typed`] to tell them apart.
+	pub fn find_field(&self, name: &str) -> Option<&IRFieldInfo> {
+		self.fields.iter().find(|field| field.name() == name)
+	}
+
+	pub fn find_field_typed(&self, name: &str, descriptor: &str) -> Option<&IRFieldInfo> {
+		self.fields
+			.iter()
+			.find(|field| field.name() == name && field.descriptor() == descriptor)
+	}
+
+	pub fn find_field_mut(&mut self, name: &str) -> Option<&mut IRFieldInfo> {
+		self.fields.iter_mut().find(|field| field.name() == name)
+	}
+
+	/// Index of the Utf8 entry `value`, appended to the pool if it isn't there yet.
+	pub fn intern_utf8(&mut self, value: &str) -> Result<CPUtf8Ref, IRClassfileError> {
+		intern_utf8(&mut self.cp, value)
+	}
+
+	/// Index of the Class entry named `name`, appended to the pool if it isn't there yet.
+	pub fn intern_class(&mut self, name: &str) -> Result<CPClassRef, IRClassfileError> {
+		intern_class(&mut self.cp, name)
+	}
+
+	/// Index of the String entry holding `value`, appended to the pool if it isn't there yet.
+	pub fn intern_string(&mut self, value: &str) -> Result<CPStringRef, IRClassfileError> {
+		let index = self.edit_pool(|pool| pool.string(value))?;
+		Ok(CPStringRef::new(index, &self.cp[index as usize - 1]))
+	}
+
+	/// Index of the Fieldref entry for `owner.name:descriptor`, appended to the pool if it isn't
+	/// there yet.
+	pub fn intern_field_ref(
+		&mut self,
+		owner: &str,
+		name: &str,
+		descriptor: &str,
+	) -> Result<CPFieldRef, IRClassfileError> {
+		let index = self.edit_pool(|pool| pool.field_ref(owner, name, descriptor))?;
+		Ok(CPFieldRef::from_cp(&self.cp, index))
+	}
+
+	/// Index of the Methodref, or InterfaceMethodref if `interface`, entry for
+	/// `owner.name:descriptor`, appended to the pool if it isn't there yet.
+	pub fn intern_method_ref(
+		&mut self,
+		owner: &str,
+		name: &str,
+		descriptor: &str,
+		interface: bool,
+	) -> Result<CPMethodRef, IRClassfileError> {
+		let index = self.edit_pool(|pool| match interface {
+			true => pool.interface_method_ref(owner, name, descriptor),
+			false => pool.method_ref(owner, name, descriptor),
+		})?;
+		Ok(CPMethodRef::from_cp(&self.cp, index))
+	}
+
+	/// Adds entries through the [`ConstantPoolBuilder`] kept over the pool, reusing any equal entry
+	/// already in it. The entries it appends are lifted into `cp` afterwards, existing ones keep their
+	/// index. See [`ConstantPool::edit`].
+	pub fn edit_pool<T>(
+		&mut self,
+		edit: impl FnOnce(&mut ConstantPoolBuilder) -> Result<T, IOClassfileError>,
+	) -> Result<T, IRClassfileError> {
+		self.cp.edit(edit)
+	}
+
+	/// Utf8 entries for a member's name and descriptor, added in one edit of the pool.
+	fn intern_member(&mut self, name: &str, descriptor: &str) -> Result<(CPUtf8Ref, CPUtf8Ref), IRClassfileError> {
+		let (name, descriptor) = self.edit_pool(|pool| Ok((pool.utf8(name)?, pool.utf8(descriptor)?)))?;
+		Ok((
+			CPUtf8Ref::from_cp(&self.cp, name),
+			CPUtf8Ref::from_cp(&self.cp, descriptor),
+		))
+	}
+
+	/// Declares a new method, erroring if one with the same name and descriptor exists. `attributes`
+	/// must already point into this class's pool.
+	pub fn add_method(
+		&mut self,
+		access_flags: MethodAccessFlags,
+		name: &str,
+		descriptor: &str,
+		attributes: Vec<IRAttributeInfo>,
+	) -> Result<&mut IRMethodInfo, IRClassfileError> {
+		if self.find_method(name, descriptor).is_some() {
+			return Err(IRClassfileError::Duplicate {
+				what: "method",
+				name: format!("{name}{descriptor}"),
+			});
+		}
+		count("methods", self.methods.len() + 1)?;
+
+		let (name, descriptor) = self.intern_member(name, descriptor)?;
+		self.methods.push(IRMethodInfo {
+			access_flags,
+			name,
+			descriptor,
+			attributes,
+		});
+		Ok(self.methods.last_mut().unwrap())
+	}
+
+	pub fn remove_method(&mut self, name: &str, descriptor: &str) -> Option<IRMethodInfo> {
+		let position = self
+			.methods
+			.iter()
+			.position(|method| method.name() == name && method.descriptor() == descriptor)?;
+		Some(self.methods.remove(position))
+	}
+
+	/// Renames a method declaration, call sites in this or other classes are left alone. Returns
+	/// whether the method exists.
+	pub fn rename_method(&mut self, name: &str, descriptor: &str, new_name: &str) -> Result<bool, IRClassfileError> {
+		if self.find_method(name, descriptor).is_none() {
+			return Ok(false);
+		}
+		if name != new_name && self.find_method(new_name, descriptor).is_some() {
+			return Err(IRClassfileError::Duplicate {
+				what: "method",
+				name: format!("{new_name}{descriptor}"),
+			});
+		}
+
+		let new_name = self.intern_utf8(new_name)?;
+		self.find_method_mut(name, descriptor).unwrap().name = new_name;
+		Ok(true)
+	}
+
+	/// Declares a new field, erroring if one with the same name exists. `attributes` must already
+	/// point into this class's pool.
+	pub fn add_field(
+		&mut self,
+		access_flags: FieldAccessFlags,
+		name: &str,
+		descriptor: &str,
+		attributes: Vec<IRAttributeInfo>,
+	) -> Result<&mut IRFieldInfo, IRClassfileError> {
+		if self.find_field(name).is_some() {
+			return Err(IRClassfileError::Duplicate {
+				what: "field",
+				name: name.to_string(),
+			});
+		}
+		count("fields", self.fields.len() + 1)?;
+
+		let (name, descriptor) = self.intern_member(name, descriptor)?;
+		self.fields.push(IRFieldInfo {
+			access_flags,
+			name,
+			descriptor,
+			attributes,
+		});
+		Ok(self.fields.last_mut().unwrap())
+	}
+
+	pub fn remove_field(&mut self, name: &str) -> Option<IRFieldInfo> {
+		let position = self.fields.iter().position(|field| field.name() == name)?;
+		Some(self.fields.remove(position))
+	}
+
+	/// Renames a field declaration, accesses to it are left alone. Returns whether the field exists.
+	pub fn rename_field(&mut self, name: &str, new_name: &str) -> Result<bool, IRClassfileError> {
+		if self.find_field(name).is_none() {
+			return Ok(false);
+		}
+		if name != new_name && self.find_field(new_name).is_some() {
+			return Err(IRClassfileError::Duplicate {
+				what: "field",
+				name: new_name.to_string(),
+			});
+		}
+
+		let new_name = self.intern_utf8(new_name)?;
+		self.find_field_mut(name).unwrap().name = new_name;
+		Ok(true)
+	}
+
+	pub fn set_super_class(&mut self, name: &str) -> Result<(), IRClassfileError> {
+		self.super_class = self.intern_class(name)?;
+		Ok(())
+	}
+
+	/// Adds `name` to the implemented interfaces, returns false if it was already there.
+	pub fn add_interface(&mut self, name: &str) -> Result<bool, IRClassfileError> {
+		if self.interface_names().any(|interface| interface == name) {
+			return Ok(false);
+		}
+		count("interfaces", self.interfaces.len() + 1)?;
+		let class = self.intern_class(name)?;
+		self.interfaces.push(class);
+		Ok(true)
+	}
+
+	pub fn remove_interface(&mut self, name: &str) -> bool {
+		let before = self.interfaces.len();
+		self.interfaces.retain(|class| class.data.data.as_str() != name);
+		self.interfaces.len() != before
+	}
+
+	/// Components of the Record attribute, `None` if this isn't a record class.
+	pub fn record_components(&self) -> Option<&[RecordComponentInfo]> {
+		self.attributes.iter().find_map(|attr| match &attr.attr {
+			IRAttribute::Record { components } => Some(components.as_slice()),
+			_ => None,
+		})
+	}
+}
+
+pub(crate) fn intern_utf8(cp: &mut ConstantPool, value: &str) -> Result<CPUtf8Ref, IRClassfileError> {
+	let index = cp.edit(|pool| pool.utf8(value))?;
+	Ok(CPUtf8Ref::new(index, &cp[index as usize - 1]))
+}
+
+pub(crate) fn intern_class(cp: &mut ConstantPool, name: &str) -> Result<CPClassRef, IRClassfileError> {
+	let index = cp.edit(|pool| pool.class(name))?;
+	Ok(CPClassRef::new(index, &cp[index as usize - 1]))
+}
+
+/// Index of an Integer, Float, Long or Double entry holding `constant`, appended to the pool if
+/// it isn't there yet. Floats are compared by their bits.
+pub(crate) fn intern_constant(cp: &mut ConstantPool, constant: IRCpTag) -> Result<u16, IRClassfileError> {
+	let constant = constant.to_io()?;
+	cp.edit(|pool| pool.add(constant))
+}
+
+pub(crate) fn intern_name_and_type(
+	cp: &mut ConstantPool,
+	name: &str,
+	descriptor: &str,
+) -> Result<CPNameAndTypeRef, IRClassfileError> {
+	let index = cp.edit(|pool| pool.name_and_type(name, descriptor))?;
+	Ok(CPNameAndTypeRef::new(index, &cp[index as usize - 1]))
+}
+
+fn lower_attributes(cp: &[IRCpTag], attributes: &[IRAttributeInfo]) -> Result<Vec<IOAttributeInfo>, IRClassfileError> {
+	attributes.iter().map(|attr| attr.to_io(cp)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::test_util::{class, find_attr, A_MODULE_INFO, CLASSES, CONSTANTS, HELLO};
+
+	#[cfg(feature = "sync")]
+	#[test]
+	fn ir_is_send_and_sync() {
+		fn assert_send_sync<T: Send + Sync>(_: &T) {}
+		let class = class(HELLO);
+		assert_send_sync(&class);
+		let name = std::thread::scope(|s| s.spawn(|| class.this_class.data.data.to_string()).join().unwrap());
+		assert_eq!(name, "a/Hello");
+	}
+
+	#[test]
+	fn unknown_attribute_passthrough() {
+		let mut io = IOClassFile::read_from_slice(HELLO).unwrap();
+		io.cp.push(maya_classfile_io::class_pool::IOCpTag::Utf8 {
+			length: 8,
+			bytes: b"ScalaSig".to_vec(),
+		});
+		io.cp_count += 1;
+		io.attributes
+			.push(IOAttributeInfo::new(io.cp.len() as u16, vec![5, 0, 1]));
+		io.attribute_count += 1;
+		let bytes = io.to_bytes().unwrap();
+
+		let class = IRClassFile::from_io(io).unwrap();
+		let attr = &class.attributes.last().unwrap().attr;
+		assert!(matches!(attr, IRAttribute::Unknown { data, .. } if data == &[5, 0, 1]));
+		assert_eq!(attr.name(), "ScalaSig");
+
+		let mut out = Vec::new();
+		class.write(&mut out).unwrap();
+		assert_eq!(out, bytes);
+	}
+
+	#[cfg(feature = "serde")]
+	#[test]
+	fn serde_round_trip() {
+		for bytes in CLASSES {
+			let json = serde_json::to_string(&class(bytes)).unwrap();
+			let class: IRClassFile = serde_json::from_str(&json).unwrap();
+			let mut out = Vec::new();
+			class.write(&mut out).unwrap();
+			assert_eq!(&out, bytes);
+		}
+	}
+
+	#[test]
+	fn lower_round_trip() {
+		for bytes in CLASSES {
+			let class = IRClassFile::from_io(IOClassFile::read_from_slice(bytes).unwrap()).unwrap();
+			let mut out = Vec::new();
+			class.write(&mut out).unwrap();
+			assert_eq!(&out, bytes);
+		}
+	}
+
+	#[test]
+	fn constant_values() {
+		use attribute::ConstantValueAttribute;
+
+		let class = class(CONSTANTS);
+		let mut kinds = std::collections::HashSet::new();
+		for field in &class.fields {
+			let IRAttribute::ConstantValue(value) = find_attr(&field.attributes, "ConstantValue") else {
+				unreachable!()
+			};
+			let tag = &class.cp[value.cp_index() as usize - 1];
+			let matches = match value {
+				ConstantValueAttribute::Long { value, .. } => matches!(tag, IRCpTag::Long(v) if v == value),
+				ConstantValueAttribute::Float { value, .. } => matches!(tag, IRCpTag::Float(v) if v == value),
+				ConstantValueAttribute::Double { value, .. } => matches!(tag, IRCpTag::Double(v) if v == value),
+				ConstantValueAttribute::Int { value, .. } => matches!(tag, IRCpTag::Integer(v) if v == value),
+				ConstantValueAttribute::String(string) => {
+					string.data.data.as_str() == "after the wide constants"
+						&& matches!(tag, IRCpTag::String(utf8) if utf8.index == string.data.index)
+				}
+			};
+			assert!(matches, "{}", field.name());
+
+			let mut out = Vec::new();
+			value.write(&mut out).unwrap();
+			assert_eq!(out, value.cp_index().to_be_bytes());
+			kinds.insert(std::mem::discriminant(value));
+		}
+		assert_eq!(kinds.len(), 5);
+	}
+
+	#[cfg(feature = "tracing")]
+	#[test]
+	fn parse_tracing() {
+		use std::sync::Mutex;
+
+		use tracing::{span, Event, Metadata, Subscriber};
+
+		/// Keeps the names of the spans and counts the events.
+		#[derive(Default)]
+		struct Recorder {
+			spans: Mutex<Vec<&'static str>>,
+			events: Mutex<usize>,
+		}
+
+		impl Subscriber for Recorder {
+			fn enabled(&self, _: &Metadata<'_>) -> bool {
+				true
+			}
+
+			fn new_span(&self, span: &span::Attributes<'_>) -> span::Id {
+				let mut spans = self.spans.lock().unwrap();
+				spans.push(span.metadata().name());
+				span::Id::from_u64(spans.len() as u64)
+			}
+
+			fn record(&self, _: &span::Id, _: &span::Record<'_>) {}
+
+			fn record_follows_from(&self, _: &span::Id, _: &span::Id) {}
+
+			fn event(&self, _: &Event<'_>) {
+				*self.events.lock().unwrap() += 1;
+			}
+
+			fn enter(&self, _: &span::Id) {}
+
+			fn exit(&self, _: &span::Id) {}
+		}
+
+		let recorder = std::sync::Arc::new(Recorder::default());
+		tracing::subscriber::with_default(recorder.clone(), || class(HELLO));
+		let spans = recorder.spans.lock().unwrap();
+		assert_eq!(spans[..2], ["read_class", "class"]);
+		assert_eq!(spans.iter().filter(|name| **name == "method").count(), 5);
+		// an event for each attribute both when it's read and when it's parsed, and a few more
+		assert!(*recorder.events.lock().unwrap() > 20);
+	}
+
+	#[test]
+	fn intern_through_pool_builder() {
+		let mut class = class(HELLO);
+		let len = class.cp.len();
+		let object = class.super_class.index;
+		assert_eq!(intern_class(&mut class.cp, "java/lang/Object").unwrap().index, object);
+		assert_eq!(intern_utf8(&mut class.cp, "main").unwrap().data.as_str(), "main");
+		assert_eq!(class.cp.len(), len);
+
+		let long = intern_constant(&mut class.cp, IRCpTag::Long(1 << 40)).unwrap();
+		assert_eq!(long as usize, len + 1);
+		assert!(matches!(class.cp[len + 1], IRCpTag::Unusable));
+		assert_eq!(intern_constant(&mut class.cp, IRCpTag::Long(1 << 40)).unwrap(), long);
+		let nat = intern_name_and_type(&mut class.cp, "fresh", "([Ljava/lang/String;)V").unwrap();
+		assert_eq!(nat.name.data.as_str(), "fresh");
+		// the Utf8 for the name and the NameAndType, the descriptor is main's
+		assert_eq!(class.cp.len(), len + 4);
+	}
+
+	#[test]
+	fn member_lookup() {
+		let class = class(HELLO);
+		assert_eq!(class.class_name(), "a/Hello");
+		assert_eq!(class.super_name(), Some("java/lang/Object"));
+		assert_eq!(class.interface_names().count(), 0);
+		assert_eq!(self::class(A_MODULE_INFO).super_name(), None);
+
+		let main = class.find_method("main", "([Ljava/lang/String;)V").unwrap();
+		assert!(main.access_flags.contains(MethodAccessFlags::STATIC));
+		assert!(class.find_method("main", "()V").is_none());
+		assert_eq!(class.methods_named("<init>").count(), 1);
+		assert_eq!(class.methods_named("missing").count(), 0);
+
+		let message = class.find_field("MESSAGE").unwrap();
+		assert_eq!(message.descriptor(), "Ljava/lang/String;");
+		assert!(class.find_field_typed("MESSAGE", "I").is_none());
+	}
+
+	#[test]
+	fn edit_members() {
+		let mut class = class(HELLO);
+		let cp_len = class.cp.len();
+		let main_name = class
+			.find_method("main", "([Ljava/lang/String;)V")
+			.unwrap()
+			.name
+			.clone();
+
+		class
+			.add_method(
+				MethodAccessFlags::PUBLIC | MethodAccessFlags::ABSTRACT,
+				"run",
+				"()V",
+				vec![],
+			)
+			.unwrap();
+		assert!(matches!(
+			class.add_method(MethodAccessFlags::PUBLIC, "run", "()V", vec![]),
+			Err(IRClassfileError::Duplicate { .. })
+		));
+		assert!(class.rename_method("main", "([Ljava/lang/String;)V", "start").unwrap());
+		assert!(!class.rename_method("main", "([Ljava/lang/String;)V", "start").unwrap());
+		assert!(class.remove_method("thrower", "()V").is_some());
+
+		class
+			.add_field(FieldAccessFlags::PRIVATE, "count", "I", vec![])
+			.unwrap();
+		assert!(class.rename_field("MESSAGE", "GREETING").unwrap());
+		assert!(class.remove_field("count").is_some());
+
+		class.set_super_class("java/lang/Thread").unwrap();
+		assert!(class.add_interface("java/lang/Runnable").unwrap());
+		assert!(!class.add_interface("java/lang/Runnable").unwrap());
+
+		// many members in a row share their descriptor entry and only add their name
+		let len = class.cp.len();
+		for i in 0..1000 {
+			class
+				.add_method(MethodAccessFlags::PUBLIC, &format!("generated{i}"), "()V", vec![])
+				.unwrap();
+		}
+		assert_eq!(class.cp.len(), len + 1000);
+		for i in 0..1000 {
+			class.remove_method(&format!("generated{i}"), "()V").unwrap();
+		}
+
+		// existing entries are never moved, only appended to
+		assert!(matches!(&class.cp[main_name.index as usize - 1], IRCpTag::Utf8(data) if data.as_str() == "main"));
+		assert!(class.cp.len() > cp_len);
+
+		let mut bytes = Vec::new();
+		class.write(&mut bytes).unwrap();
+		let class = IRClassFile::from_io(IOClassFile::read_from_slice(&bytes).unwrap()).unwrap();
+		assert!(class.find_method("start", "([Ljava/lang/String;)V").is_some());
+		assert!(class.find_method("run", "()V").is_some());
+		assert!(class.find_method("thrower", "()V").is_none());
+		assert_eq!(class.find_field("GREETING").unwrap().descriptor(), "Ljava/lang/String;");
+		assert!(class.find_field("count").is_none());
+		assert_eq!(class.super_name(), Some("java/lang/Thread"));
+		assert_eq!(class.interface_names().collect::<Vec<_>>(), ["java/lang/Runnable"]);
+	}
+
+	#[test]
+	fn edit_pool() {
+		let mut class = class(HELLO);
+		let println = class
+			.intern_method_ref("java/io/PrintStream", "println", "(Ljava/lang/String;)V", false)
+			.unwrap();
+		let len = class.cp.len();
+		let (method, field) = class
+			.edit_pool(|pool| {
+				Ok((
+					pool.method_ref("java/io/PrintStream", "println", "(Ljava/lang/String;)V")?,
+					pool.field_ref("a/Hello", "fresh", "J")?,
+				))
+			})
+			.unwrap();
+		assert_eq!(method, println.index);
+		let field = CPFieldRef::from_cp(&class.cp, field);
+		assert_eq!(field.class.data.data.as_str(), "a/Hello");
+		assert_eq!(field.name_and_ty.name.data.as_str(), "fresh");
+		assert_eq!(
+			class.intern_string("fresh").unwrap().data.index,
+			field.name_and_ty.name.index
+		);
+
+		let len_before_error = class.cp.len();
+		assert!(len_before_error > len);
+		assert!(matches!(
+			class.edit_pool(|pool| pool.add(maya_classfile_io::class_pool::IOCpTag::Unusable)),
+			Err(IRClassfileError::IO(IOClassfileError::BadCpIndex(0)))
+		));
+		assert_eq!(class.cp.len(), len_before_error);
+		// the pool still finds what it had before the failed edit
+		assert_eq!(
+			class.intern_string("fresh").unwrap().data.index,
+			field.name_and_ty.name.index
+		);
+	}
 }
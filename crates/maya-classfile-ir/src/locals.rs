@@ -0,0 +1,219 @@
+//! Making room for new locals among the existing ones, see [`InsnList::insert_local`] and
+//! [`IRClassFile::add_parameter`]. Fresh slots past every existing local come from
+//! [`InsnList::new_local`].
+//!
+//! Instructions, frames and LocalVariableTable entries are renumbered together. Locals moved past
+//! 255 are written with `wide` when the code is laid out again.
+
+use crate::{
+	access::MethodAccessFlags,
+	attribute::{IRAttribute, VerificationTypeInfo},
+	class_pool::IRClassfileError,
+	code::{parameters, value_slots, Instructions},
+	insn::{FrameValue, Insn, InsnList},
+	IRClassFile,
+};
+
+impl InsnList {
+	/// Makes room for a local at `slot`, moving every local at or above it up one slot, or two for a
+	/// long or double. Frames declare the new slots `top`. `slot` has to come after the parameters,
+	/// see [`insert_parameter`](Self::insert_parameter) for making room among them.
+	pub fn insert_local(&mut self, slot: u16, wide: bool) {
+		let top = FrameValue::Type(VerificationTypeInfo::TopVariableInfo);
+		self.shift_locals(slot, vec![top; 1 + wide as usize]);
+	}
+
+	/// Renumbers the locals for a parameter of type `ty` added at `slot`. The parameter joins
+	/// [`initial_locals`](Self::initial_locals) and every frame whose locals go past it.
+	pub fn insert_parameter(&mut self, slot: u16, ty: VerificationTypeInfo) {
+		let position = position(&self.initial_locals, slot, slots).unwrap_or(self.initial_locals.len());
+		self.initial_locals.insert(position, ty.clone());
+		self.shift_locals(slot, vec![FrameValue::Type(ty)]);
+	}
+
+	fn shift_locals(&mut self, slot: u16, entries: Vec<FrameValue>) {
+		let by = entries.iter().map(frame_value_slots).sum::<u16>();
+		let shift = |local: &mut u16| {
+			if *local >= slot {
+				*local = local.saturating_add(by);
+			}
+		};
+		for insn in &mut self.insns {
+			match insn {
+				Insn::Op(
+					Instructions::ILOAD(local)
+					| Instructions::LLOAD(local)
+					| Instructions::FLOAD(local)
+					| Instructions::DLOAD(local)
+					| Instructions::ALOAD(local)
+					| Instructions::ISTORE(local)
+					| Instructions::LSTORE(local)
+					| Instructions::FSTORE(local)
+					| Instructions::DSTORE(local)
+					| Instructions::ASTORE(local)
+					| Instructions::IINC(local, _)
+					| Instructions::RET(local),
+				) => shift(local),
+				Insn::Frame { locals, .. } => {
+					if let Some(position) = position(locals, slot, frame_value_slots) {
+						locals.splice(position..position, entries.iter().cloned());
+					}
+				}
+				_ => {}
+			}
+		}
+		for variable in self.local_variables.iter_mut().chain(&mut self.local_variable_types) {
+			shift(&mut variable.index);
+		}
+	}
+}
+
+/// The first entry of `locals` at or past `slot`, `None` if they end before it.
+fn position<T>(locals: &[T], slot: u16, size: impl Fn(&T) -> u16) -> Option<usize> {
+	let mut slots = 0;
+	for (i, local) in locals.iter().enumerate() {
+		if slots >= slot {
+			return Some(i);
+		}
+		slots += size(local);
+	}
+	None
+}
+
+fn slots(ty: &VerificationTypeInfo) -> u16 {
+	match ty {
+		VerificationTypeInfo::LongVariableInfo | VerificationTypeInfo::DoubleVariableInfo => 2,
+		_ => 1,
+	}
+}
+
+fn frame_value_slots(value: &FrameValue) -> u16 {
+	match value {
+		FrameValue::Type(ty) => slots(ty),
+		FrameValue::Uninitialized(_) => 1,
+	}
+}
+
+impl IRClassFile {
+	/// Adds a parameter with the descriptor `parameter` to a method, before the parameter at
+	/// `position` or after the last one, and renumbers the method's locals around it. Returns whether
+	/// the method exists.
+	///
+	/// Callers aren't updated, and neither are the method's Signature, MethodParameters or parameter
+	/// annotations.
+	pub fn add_parameter(
+		&mut self,
+		name: &str,
+		descriptor: &str,
+		position: usize,
+		parameter: &str,
+	) -> Result<bool, IRClassfileError> {
+		let Some(method) = self.find_method(name, descriptor) else {
+			return Ok(false);
+		};
+		if parameter == "V" || !parameters(&format!("({parameter})V")).eq([parameter]) {
+			return Err(IRClassfileError::InvalidDescriptor(parameter.to_string()));
+		}
+		let is_static = method.access_flags.contains(MethodAccessFlags::STATIC);
+		let mut parameter_list = parameters(descriptor).collect::<Vec<_>>();
+		let position = position.min(parameter_list.len());
+		let slot = !is_static as u16 + parameter_list[..position].iter().copied().map(value_slots).sum::<u16>();
+		parameter_list.insert(position, parameter);
+		let result = descriptor.split_once(')').map_or("V", |(_, result)| result);
+		let new_descriptor = format!("({}){result}", parameter_list.concat());
+		if self.find_method(name, &new_descriptor).is_some() {
+			return Err(IRClassfileError::Duplicate {
+				what: "method",
+				name: format!("{name}{new_descriptor}"),
+			});
+		}
+
+		let insns = self.method_insns(name, descriptor)?;
+		let ty = self.entry_locals(true, name, &format!("({parameter})V"))?.remove(0);
+		let utf8 = self.intern_utf8(&new_descriptor)?;
+		self.find_method_mut(name, descriptor).unwrap().descriptor = utf8;
+		let Some(mut insns) = insns else {
+			return Ok(true);
+		};
+		insns.insert_parameter(slot, ty);
+		self.set_method_insns(name, &new_descriptor, &insns)?;
+		for attribute in &mut self.find_method_mut(name, &new_descriptor).unwrap().attributes {
+			if let IRAttribute::Code(code) = &mut attribute.attr {
+				code.max_locals = code.max_locals.saturating_add(value_slots(parameter));
+			}
+		}
+		Ok(true)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::test_util::{class, method_code, HELLO};
+
+	#[test]
+	fn new_locals() {
+		let mut class = class(HELLO);
+		let mut insns = class
+			.method_insns("stackmapper", "(ILjava/lang/Object;)V")
+			.unwrap()
+			.unwrap();
+		assert_eq!(insns.new_local(true), 8);
+		assert_eq!(insns.new_local(false), 10);
+		assert_eq!(insns.max_locals(), 11);
+	}
+
+	#[test]
+	fn add_parameter() {
+		let mut class = class(HELLO);
+		assert!(class
+			.add_parameter("stackmapper", "(ILjava/lang/Object;)V", 1, "J")
+			.unwrap());
+		assert!(class.find_method("stackmapper", "(ILjava/lang/Object;)V").is_none());
+		let descriptor = "(IJLjava/lang/Object;)V";
+		let insns = class.method_insns("stackmapper", descriptor).unwrap().unwrap();
+		assert_eq!(insns.initial_locals[1], VerificationTypeInfo::LongVariableInfo);
+		let ops = insns
+			.insns
+			.iter()
+			.filter_map(|insn| match insn {
+				Insn::Op(instruction) => Some(instruction),
+				_ => None,
+			})
+			.take(6)
+			.collect::<Vec<_>>();
+		assert!(matches!(
+			ops[..],
+			[
+				Instructions::ICONST_0,
+				Instructions::ISTORE(4),
+				Instructions::ICONST_0,
+				Instructions::ISTORE(5),
+				Instructions::ALOAD(3),
+				Instructions::ASTORE(6)
+			]
+		));
+		let Some(Insn::Frame { locals, .. }) = insns.insns.iter().find(|insn| matches!(insn, Insn::Frame { .. }))
+		else {
+			unreachable!();
+		};
+		assert_eq!(locals.len(), 7);
+		assert!(matches!(
+			locals[1],
+			FrameValue::Type(VerificationTypeInfo::LongVariableInfo)
+		));
+		assert!(matches!(
+			locals[6],
+			FrameValue::Type(VerificationTypeInfo::LongVariableInfo)
+		));
+		assert_eq!(method_code(&class, "stackmapper").max_locals, 10);
+	}
+
+	#[test]
+	fn void_parameter() {
+		assert!(matches!(
+			class(HELLO).add_parameter("stackmapper", "(ILjava/lang/Object;)V", 0, "V"),
+			Err(IRClassfileError::InvalidDescriptor(_))
+		));
+	}
+}
@@ -0,0 +1,383 @@
+//! What's known about every opcode without looking at its operands: its mnemonic, the layout of
+//! its operands, its effect on the operand stack and its category, see [`OpcodeInfo`].
+
+/// How the operands following an opcode are laid out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Operands {
+	None,
+	/// A signed byte, for `bipush`.
+	Byte,
+	/// A signed short, for `sipush`.
+	Short,
+	/// A byte constant pool index, for `ldc`.
+	ConstantByte,
+	/// A constant pool index.
+	Constant,
+	/// A local variable index, a byte or a short after `wide`.
+	Local,
+	/// `iinc`'s local variable index and signed increment, a byte each or a short each after `wide`.
+	LocalIncrement,
+	/// A signed short branch offset.
+	Branch,
+	/// A signed int branch offset.
+	BranchWide,
+	/// Padding to a multiple of 4, then the default offset, the bounds and an offset for each value.
+	TableSwitch,
+	/// Padding to a multiple of 4, then the default offset, the pair count and the pairs.
+	LookupSwitch,
+	/// `invokeinterface`'s method index, the count of argument slots and a zero byte.
+	InterfaceMethod,
+	/// `invokedynamic`'s call site index and two zero bytes.
+	CallSite,
+	/// `multianewarray`'s class index and dimension count.
+	MultiArray,
+	/// `newarray`'s element type code.
+	ArrayType,
+	/// The opcode `wide` applies to and its widened operands.
+	Wide,
+}
+
+impl Operands {
+	/// Bytes of operands after the opcode, `None` when that depends on the code, for switches and
+	/// `wide`.
+	pub fn size(self) -> Option<usize> {
+		Some(match self {
+			Self::None => 0,
+			Self::Byte | Self::ConstantByte | Self::Local | Self::ArrayType => 1,
+			Self::Short | Self::Constant | Self::LocalIncrement | Self::Branch => 2,
+			Self::MultiArray => 3,
+			Self::BranchWide | Self::InterfaceMethod | Self::CallSite => 4,
+			Self::TableSwitch | Self::LookupSwitch | Self::Wide => return None,
+		})
+	}
+}
+
+/// The groups the JVM specification's opcode list is divided in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Category {
+	Constants,
+	/// Loads from locals and arrays.
+	Loads,
+	/// Stores to locals and arrays.
+	Stores,
+	Stack,
+	Math,
+	Conversions,
+	Comparisons,
+	Control,
+	/// Fields, calls, objects, arrays, exceptions and monitors.
+	References,
+	Extended,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct OpcodeInfo {
+	pub opcode: u8,
+	/// The javap mnemonic.
+	pub name: &'static str,
+	pub operands: Operands,
+	/// Operand stack slots popped and pushed, longs and doubles taking two, `None` when it depends
+	/// on the operands, like for field accesses and calls. `jsr` counts the return address it pushes
+	/// for its target.
+	pub stack_effect: Option<(u16, u16)>,
+	pub category: Category,
+}
+
+impl OpcodeInfo {
+	/// `None` for the reserved and unassigned opcodes.
+	pub fn of(opcode: u8) -> Option<&'static Self> {
+		OPCODES.get(opcode as usize)
+	}
+
+	pub fn by_name(name: &str) -> Option<&'static Self> {
+		OPCODES.iter().find(|info| info.name == name)
+	}
+}
+
+const fn info(
+	opcode: u8,
+	name: &'static str,
+	operands: Operands,
+	category: Category,
+	stack_effect: Option<(u16, u16)>,
+) -> OpcodeInfo {
+	OpcodeInfo {
+		opcode,
+		name,
+		operands,
+		stack_effect,
+		category,
+	}
+}
+
+/// Every opcode up to `jsr_w`, indexed by opcode.
+pub static OPCODES: [OpcodeInfo; 202] = [
+	info(0x00, "nop", Operands::None, Category::Constants, Some((0, 0))),
+	info(0x01, "aconst_null", Operands::None, Category::Constants, Some((0, 1))),
+	info(0x02, "iconst_m1", Operands::None, Category::Constants, Some((0, 1))),
+	info(0x03, "iconst_0", Operands::None, Category::Constants, Some((0, 1))),
+	info(0x04, "iconst_1", Operands::None, Category::Constants, Some((0, 1))),
+	info(0x05, "iconst_2", Operands::None, Category::Constants, Some((0, 1))),
+	info(0x06, "iconst_3", Operands::None, Category::Constants, Some((0, 1))),
+	info(0x07, "iconst_4", Operands::None, Category::Constants, Some((0, 1))),
+	info(0x08, "iconst_5", Operands::None, Category::Constants, Some((0, 1))),
+	info(0x09, "lconst_0", Operands::None, Category::Constants, Some((0, 2))),
+	info(0x0A, "lconst_1", Operands::None, Category::Constants, Some((0, 2))),
+	info(0x0B, "fconst_0", Operands::None, Category::Constants, Some((0, 1))),
+	info(0x0C, "fconst_1", Operands::None, Category::Constants, Some((0, 1))),
+	info(0x0D, "fconst_2", Operands::None, Category::Constants, Some((0, 1))),
+	info(0x0E, "dconst_0", Operands::None, Category::Constants, Some((0, 2))),
+	info(0x0F, "dconst_1", Operands::None, Category::Constants, Some((0, 2))),
+	info(0x10, "bipush", Operands::Byte, Category::Constants, Some((0, 1))),
+	info(0x11, "sipush", Operands::Short, Category::Constants, Some((0, 1))),
+	info(0x12, "ldc", Operands::ConstantByte, Category::Constants, Some((0, 1))),
+	info(0x13, "ldc_w", Operands::Constant, Category::Constants, Some((0, 1))),
+	info(0x14, "ldc2_w", Operands::Constant, Category::Constants, Some((0, 2))),
+	info(0x15, "iload", Operands::Local, Category::Loads, Some((0, 1))),
+	info(0x16, "lload", Operands::Local, Category::Loads, Some((0, 2))),
+	info(0x17, "fload", Operands::Local, Category::Loads, Some((0, 1))),
+	info(0x18, "dload", Operands::Local, Category::Loads, Some((0, 2))),
+	info(0x19, "aload", Operands::Local, Category::Loads, Some((0, 1))),
+	info(0x1A, "iload_0", Operands::None, Category::Loads, Some((0, 1))),
+	info(0x1B, "iload_1", Operands::None, Category::Loads, Some((0, 1))),
+	info(0x1C, "iload_2", Operands::None, Category::Loads, Some((0, 1))),
+	info(0x1D, "iload_3", Operands::None, Category::Loads, Some((0, 1))),
+	info(0x1E, "lload_0", Operands::None, Category::Loads, Some((0, 2))),
+	info(0x1F, "lload_1", Operands::None, Category::Loads, Some((0, 2))),
+	info(0x20, "lload_2", Operands::None, Category::Loads, Some((0, 2))),
+	info(0x21, "lload_3", Operands::None, Category::Loads, Some((0, 2))),
+	info(0x22, "fload_0", Operands::None, Category::Loads, Some((0, 1))),
+	info(0x23, "fload_1", Operands::None, Category::Loads, Some((0, 1))),
+	info(0x24, "fload_2", Operands::None, Category::Loads, Some((0, 1))),
+	info(0x25, "fload_3", Operands::None, Category::Loads, Some((0, 1))),
+	info(0x26, "dload_0", Operands::None, Category::Loads, Some((0, 2))),
+	info(0x27, "dload_1", Operands::None, Category::Loads, Some((0, 2))),
+	info(0x28, "dload_2", Operands::None, Category::Loads, Some((0, 2))),
+	info(0x29, "dload_3", Operands::None, Category::Loads, Some((0, 2))),
+	info(0x2A, "aload_0", Operands::None, Category::Loads, Some((0, 1))),
+	info(0x2B, "aload_1", Operands::None, Category::Loads, Some((0, 1))),
+	info(0x2C, "aload_2", Operands::None, Category::Loads, Some((0, 1))),
+	info(0x2D, "aload_3", Operands::None, Category::Loads, Some((0, 1))),
+	info(0x2E, "iaload", Operands::None, Category::Loads, Some((2, 1))),
+	info(0x2F, "laload", Operands::None, Category::Loads, Some((2, 2))),
+	info(0x30, "faload", Operands::None, Category::Loads, Some((2, 1))),
+	info(0x31, "daload", Operands::None, Category::Loads, Some((2, 2))),
+	info(0x32, "aaload", Operands::None, Category::Loads, Some((2, 1))),
+	info(0x33, "baload", Operands::None, Category::Loads, Some((2, 1))),
+	info(0x34, "caload", Operands::None, Category::Loads, Some((2, 1))),
+	info(0x35, "saload", Operands::None, Category::Loads, Some((2, 1))),
+	info(0x36, "istore", Operands::Local, Category::Stores, Some((1, 0))),
+	info(0x37, "lstore", Operands::Local, Category::Stores, Some((2, 0))),
+	info(0x38, "fstore", Operands::Local, Category::Stores, Some((1, 0))),
+	info(0x39, "dstore", Operands::Local, Category::Stores, Some((2, 0))),
+	info(0x3A, "astore", Operands::Local, Category::Stores, Some((1, 0))),
+	info(0x3B, "istore_0", Operands::None, Category::Stores, Some((1, 0))),
+	info(0x3C, "istore_1", Operands::None, Category::Stores, Some((1, 0))),
+	info(0x3D, "istore_2", Operands::None, Category::Stores, Some((1, 0))),
+	info(0x3E, "istore_3", Operands::None, Category::Stores, Some((1, 0))),
+	info(0x3F, "lstore_0", Operands::None, Category::Stores, Some((2, 0))),
+	info(0x40, "lstore_1", Operands::None, Category::Stores, Some((2, 0))),
+	info(0x41, "lstore_2", Operands::None, Category::Stores, Some((2, 0))),
+	info(0x42, "lstore_3", Operands::None, Category::Stores, Some((2, 0))),
+	info(0x43, "fstore_0", Operands::None, Category::Stores, Some((1, 0))),
+	info(0x44, "fstore_1", Operands::None, Category::Stores, Some((1, 0))),
+	info(0x45, "fstore_2", Operands::None, Category::Stores, Some((1, 0))),
+	info(0x46, "fstore_3", Operands::None, Category::Stores, Some((1, 0))),
+	info(0x47, "dstore_0", Operands::None, Category::Stores, Some((2, 0))),
+	info(0x48, "dstore_1", Operands::None, Category::Stores, Some((2, 0))),
+	info(0x49, "dstore_2", Operands::None, Category::Stores, Some((2, 0))),
+	info(0x4A, "dstore_3", Operands::None, Category::Stores, Some((2, 0))),
+	info(0x4B, "astore_0", Operands::None, Category::Stores, Some((1, 0))),
+	info(0x4C, "astore_1", Operands::None, Category::Stores, Some((1, 0))),
+	info(0x4D, "astore_2", Operands::None, Category::Stores, Some((1, 0))),
+	info(0x4E, "astore_3", Operands::None, Category::Stores, Some((1, 0))),
+	info(0x4F, "iastore", Operands::None, Category::Stores, Some((3, 0))),
+	info(0x50, "lastore", Operands::None, Category::Stores, Some((4, 0))),
+	info(0x51, "fastore", Operands::None, Category::Stores, Some((3, 0))),
+	info(0x52, "dastore", Operands::None, Category::Stores, Some((4, 0))),
+	info(0x53, "aastore", Operands::None, Category::Stores, Some((3, 0))),
+	info(0x54, "bastore", Operands::None, Category::Stores, Some((3, 0))),
+	info(0x55, "castore", Operands::None, Category::Stores, Some((3, 0))),
+	info(0x56, "sastore", Operands::None, Category::Stores, Some((3, 0))),
+	info(0x57, "pop", Operands::None, Category::Stack, Some((1, 0))),
+	info(0x58, "pop2", Operands::None, Category::Stack, Some((2, 0))),
+	info(0x59, "dup", Operands::None, Category::Stack, Some((1, 2))),
+	info(0x5A, "dup_x1", Operands::None, Category::Stack, Some((2, 3))),
+	info(0x5B, "dup_x2", Operands::None, Category::Stack, Some((3, 4))),
+	info(0x5C, "dup2", Operands::None, Category::Stack, Some((2, 4))),
+	info(0x5D, "dup2_x1", Operands::None, Category::Stack, Some((3, 5))),
+	info(0x5E, "dup2_x2", Operands::None, Category::Stack, Some((4, 6))),
+	info(0x5F, "swap", Operands::None, Category::Stack, Some((2, 2))),
+	info(0x60, "iadd", Operands::None, Category::Math, Some((2, 1))),
+	info(0x61, "ladd", Operands::None, Category::Math, Some((4, 2))),
+	info(0x62, "fadd", Operands::None, Category::Math, Some((2, 1))),
+	info(0x63, "dadd", Operands::None, Category::Math, Some((4, 2))),
+	info(0x64, "isub", Operands::None, Category::Math, Some((2, 1))),
+	info(0x65, "lsub", Operands::None, Category::Math, Some((4, 2))),
+	info(0x66, "fsub", Operands::None, Category::Math, Some((2, 1))),
+	info(0x67, "dsub", Operands::None, Category::Math, Some((4, 2))),
+	info(0x68, "imul", Operands::None, Category::Math, Some((2, 1))),
+	info(0x69, "lmul", Operands::None, Category::Math, Some((4, 2))),
+	info(0x6A, "fmul", Operands::None, Category::Math, Some((2, 1))),
+	info(0x6B, "dmul", Operands::None, Category::Math, Some((4, 2))),
+	info(0x6C, "idiv", Operands::None, Category::Math, Some((2, 1))),
+	info(0x6D, "ldiv", Operands::None, Category::Math, Some((4, 2))),
+	info(0x6E, "fdiv", Operands::None, Category::Math, Some((2, 1))),
+	info(0x6F, "ddiv", Operands::None, Category::Math, Some((4, 2))),
+	info(0x70, "irem", Operands::None, Category::Math, Some((2, 1))),
+	info(0x71, "lrem", Operands::None, Category::Math, Some((4, 2))),
+	info(0x72, "frem", Operands::None, Category::Math, Some((2, 1))),
+	info(0x73, "drem", Operands::None, Category::Math, Some((4, 2))),
+	info(0x74, "ineg", Operands::None, Category::Math, Some((1, 1))),
+	info(0x75, "lneg", Operands::None, Category::Math, Some((2, 2))),
+	info(0x76, "fneg", Operands::None, Category::Math, Some((1, 1))),
+	info(0x77, "dneg", Operands::None, Category::Math, Some((2, 2))),
+	info(0x78, "ishl", Operands::None, Category::Math, Some((2, 1))),
+	info(0x79, "lshl", Operands::None, Category::Math, Some((3, 2))),
+	info(0x7A, "ishr", Operands::None, Category::Math, Some((2, 1))),
+	info(0x7B, "lshr", Operands::None, Category::Math, Some((3, 2))),
+	info(0x7C, "iushr", Operands::None, Category::Math, Some((2, 1))),
+	info(0x7D, "lushr", Operands::None, Category::Math, Some((3, 2))),
+	info(0x7E, "iand", Operands::None, Category::Math, Some((2, 1))),
+	info(0x7F, "land", Operands::None, Category::Math, Some((4, 2))),
+	info(0x80, "ior", Operands::None, Category::Math, Some((2, 1))),
+	info(0x81, "lor", Operands::None, Category::Math, Some((4, 2))),
+	info(0x82, "ixor", Operands::None, Category::Math, Some((2, 1))),
+	info(0x83, "lxor", Operands::None, Category::Math, Some((4, 2))),
+	info(0x84, "iinc", Operands::LocalIncrement, Category::Math, Some((0, 0))),
+	info(0x85, "i2l", Operands::None, Category::Conversions, Some((1, 2))),
+	info(0x86, "i2f", Operands::None, Category::Conversions, Some((1, 1))),
+	info(0x87, "i2d", Operands::None, Category::Conversions, Some((1, 2))),
+	info(0x88, "l2i", Operands::None, Category::Conversions, Some((2, 1))),
+	info(0x89, "l2f", Operands::None, Category::Conversions, Some((2, 1))),
+	info(0x8A, "l2d", Operands::None, Category::Conversions, Some((2, 2))),
+	info(0x8B, "f2i", Operands::None, Category::Conversions, Some((1, 1))),
+	info(0x8C, "f2l", Operands::None, Category::Conversions, Some((1, 2))),
+	info(0x8D, "f2d", Operands::None, Category::Conversions, Some((1, 2))),
+	info(0x8E, "d2i", Operands::None, Category::Conversions, Some((2, 1))),
+	info(0x8F, "d2l", Operands::None, Category::Conversions, Some((2, 2))),
+	info(0x90, "d2f", Operands::None, Category::Conversions, Some((2, 1))),
+	info(0x91, "i2b", Operands::None, Category::Conversions, Some((1, 1))),
+	info(0x92, "i2c", Operands::None, Category::Conversions, Some((1, 1))),
+	info(0x93, "i2s", Operands::None, Category::Conversions, Some((1, 1))),
+	info(0x94, "lcmp", Operands::None, Category::Comparisons, Some((4, 1))),
+	info(0x95, "fcmpl", Operands::None, Category::Comparisons, Some((2, 1))),
+	info(0x96, "fcmpg", Operands::None, Category::Comparisons, Some((2, 1))),
+	info(0x97, "dcmpl", Operands::None, Category::Comparisons, Some((4, 1))),
+	info(0x98, "dcmpg", Operands::None, Category::Comparisons, Some((4, 1))),
+	info(0x99, "ifeq", Operands::Branch, Category::Comparisons, Some((1, 0))),
+	info(0x9A, "ifne", Operands::Branch, Category::Comparisons, Some((1, 0))),
+	info(0x9B, "iflt", Operands::Branch, Category::Comparisons, Some((1, 0))),
+	info(0x9C, "ifge", Operands::Branch, Category::Comparisons, Some((1, 0))),
+	info(0x9D, "ifgt", Operands::Branch, Category::Comparisons, Some((1, 0))),
+	info(0x9E, "ifle", Operands::Branch, Category::Comparisons, Some((1, 0))),
+	info(0x9F, "if_icmpeq", Operands::Branch, Category::Comparisons, Some((2, 0))),
+	info(0xA0, "if_icmpne", Operands::Branch, Category::Comparisons, Some((2, 0))),
+	info(0xA1, "if_icmplt", Operands::Branch, Category::Comparisons, Some((2, 0))),
+	info(0xA2, "if_icmpge", Operands::Branch, Category::Comparisons, Some((2, 0))),
+	info(0xA3, "if_icmpgt", Operands::Branch, Category::Comparisons, Some((2, 0))),
+	info(0xA4, "if_icmple", Operands::Branch, Category::Comparisons, Some((2, 0))),
+	info(0xA5, "if_acmpeq", Operands::Branch, Category::Comparisons, Some((2, 0))),
+	info(0xA6, "if_acmpne", Operands::Branch, Category::Comparisons, Some((2, 0))),
+	info(0xA7, "goto", Operands::Branch, Category::Control, Some((0, 0))),
+	info(0xA8, "jsr", Operands::Branch, Category::Control, Some((0, 1))),
+	info(0xA9, "ret", Operands::Local, Category::Control, Some((0, 0))),
+	info(
+		0xAA,
+		"tableswitch",
+		Operands::TableSwitch,
+		Category::Control,
+		Some((1, 0)),
+	),
+	info(
+		0xAB,
+		"lookupswitch",
+		Operands::LookupSwitch,
+		Category::Control,
+		Some((1, 0)),
+	),
+	info(0xAC, "ireturn", Operands::None, Category::Control, Some((1, 0))),
+	info(0xAD, "lreturn", Operands::None, Category::Control, Some((2, 0))),
+	info(0xAE, "freturn", Operands::None, Category::Control, Some((1, 0))),
+	info(0xAF, "dreturn", Operands::None, Category::Control, Some((2, 0))),
+	info(0xB0, "areturn", Operands::None, Category::Control, Some((1, 0))),
+	info(0xB1, "return", Operands::None, Category::Control, Some((0, 0))),
+	info(0xB2, "getstatic", Operands::Constant, Category::References, None),
+	info(0xB3, "putstatic", Operands::Constant, Category::References, None),
+	info(0xB4, "getfield", Operands::Constant, Category::References, None),
+	info(0xB5, "putfield", Operands::Constant, Category::References, None),
+	info(0xB6, "invokevirtual", Operands::Constant, Category::References, None),
+	info(0xB7, "invokespecial", Operands::Constant, Category::References, None),
+	info(0xB8, "invokestatic", Operands::Constant, Category::References, None),
+	info(
+		0xB9,
+		"invokeinterface",
+		Operands::InterfaceMethod,
+		Category::References,
+		None,
+	),
+	info(0xBA, "invokedynamic", Operands::CallSite, Category::References, None),
+	info(0xBB, "new", Operands::Constant, Category::References, Some((0, 1))),
+	info(
+		0xBC,
+		"newarray",
+		Operands::ArrayType,
+		Category::References,
+		Some((1, 1)),
+	),
+	info(
+		0xBD,
+		"anewarray",
+		Operands::Constant,
+		Category::References,
+		Some((1, 1)),
+	),
+	info(0xBE, "arraylength", Operands::None, Category::References, Some((1, 1))),
+	info(0xBF, "athrow", Operands::None, Category::References, Some((1, 0))),
+	info(
+		0xC0,
+		"checkcast",
+		Operands::Constant,
+		Category::References,
+		Some((1, 1)),
+	),
+	info(
+		0xC1,
+		"instanceof",
+		Operands::Constant,
+		Category::References,
+		Some((1, 1)),
+	),
+	info(0xC2, "monitorenter", Operands::None, Category::References, Some((1, 0))),
+	info(0xC3, "monitorexit", Operands::None, Category::References, Some((1, 0))),
+	info(0xC4, "wide", Operands::Wide, Category::Extended, None),
+	info(0xC5, "multianewarray", Operands::MultiArray, Category::Extended, None),
+	info(0xC6, "ifnull", Operands::Branch, Category::Extended, Some((1, 0))),
+	info(0xC7, "ifnonnull", Operands::Branch, Category::Extended, Some((1, 0))),
+	info(0xC8, "goto_w", Operands::BranchWide, Category::Extended, Some((0, 0))),
+	info(0xC9, "jsr_w", Operands::BranchWide, Category::Extended, Some((0, 1))),
+];
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::code::Opcodes;
+
+	#[test]
+	fn opcode_table() {
+		assert!(OPCODES.iter().enumerate().all(|(i, info)| info.opcode as usize == i));
+		let iinc = OpcodeInfo::by_name("iinc").unwrap();
+		assert_eq!(iinc.opcode, Opcodes::IINC);
+		assert_eq!(iinc.operands, Operands::LocalIncrement);
+		assert_eq!(iinc.category, Category::Math);
+		let aload = OpcodeInfo::of(0x2B).unwrap();
+		assert_eq!(
+			(aload.name, aload.operands, aload.stack_effect),
+			("aload_1", Operands::None, Some((0, 1)))
+		);
+		assert_eq!(OpcodeInfo::of(Opcodes::INVOKEVIRTUAL).unwrap().stack_effect, None);
+		assert_eq!(OpcodeInfo::of(Opcodes::GOTO_W).unwrap().operands.size(), Some(4));
+		assert!(OpcodeInfo::of(0xCA).is_none());
+		assert!(OpcodeInfo::by_name("breakpoint").is_none());
+	}
+}
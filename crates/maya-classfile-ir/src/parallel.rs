@@ -0,0 +1,72 @@
+//! Parsing many classfiles at once on the rayon thread pool.
+//!
+//! Every class is read and lifted to the IR on a worker, which is why the `rayon` feature turns
+//! on `sync`. The workers parse under the allocation cap of the calling thread, see
+//! [`maya_bytes::set_max_alloc`].
+
+use std::path::Path;
+
+use maya_classfile_io::IOClassFile;
+use rayon::prelude::*;
+
+use crate::{class_pool::IRClassfileError, IRClassFile};
+
+/// Parses every file in `paths`, results are in the same order as the paths.
+pub fn parse_all<P: AsRef<Path> + Sync>(paths: &[P]) -> Vec<Result<IRClassFile, IRClassfileError>> {
+	let cap = maya_bytes::max_alloc();
+	paths
+		.par_iter()
+		.map(|path| maya_bytes::with_max_alloc(cap, || IRClassFile::from_io(IOClassFile::read_from_path(path)?)))
+		.collect()
+}
+
+/// Parses every classfile in `classes`, results are in the same order.
+pub fn parse_all_bytes<T: AsRef<[u8]> + Sync>(classes: &[T]) -> Vec<Result<IRClassFile, IRClassfileError>> {
+	let cap = maya_bytes::max_alloc();
+	classes
+		.par_iter()
+		.map(|bytes| {
+			maya_bytes::with_max_alloc(cap, || {
+				IRClassFile::from_io(IOClassFile::read_from_slice(bytes.as_ref())?)
+			})
+		})
+		.collect()
+}
+
+#[cfg(test)]
+mod tests {
+	use maya_bytes::BytesError;
+	use maya_classfile_io::IOClassfileError;
+
+	use super::*;
+	use crate::test_util::{CONSTANTS, HELLO, PAIR};
+
+	#[test]
+	fn parse_in_order() {
+		let classes = parse_all_bytes(&[HELLO, CONSTANTS, PAIR]);
+		let names = classes
+			.iter()
+			.map(|class| class.as_ref().unwrap().class_name())
+			.collect::<Vec<_>>();
+		assert_eq!(names, ["a/Hello", "a/Constants", "b/api/Pair"]);
+
+		let classes = parse_all_bytes(&[&HELLO[..10], CONSTANTS]);
+		assert!(classes[0].is_err());
+		assert!(classes[1].is_ok());
+	}
+
+	#[test]
+	fn workers_use_the_callers_cap() {
+		let previous = maya_bytes::set_max_alloc(Some(16));
+		let classes = parse_all_bytes(&[HELLO, CONSTANTS, PAIR]);
+		maya_bytes::set_max_alloc(previous);
+		for class in classes {
+			assert!(matches!(
+				class,
+				Err(IRClassfileError::IO(IOClassfileError::Bytes(
+					BytesError::AllocationTooLarge { .. }
+				)))
+			));
+		}
+	}
+}
@@ -0,0 +1,358 @@
+//! Finding sequences of instructions in an [`InsnList`] by a pattern written like the text form of
+//! [`asm`](crate::asm), see [`Pattern`].
+//!
+//! ```text
+//! aload _, getfield F, ifnull L
+//! ```
+//!
+//! A pattern is a list of instructions separated by commas. Each is a mnemonic, which matches that
+//! instruction whatever its operands, or a mnemonic with operands, which have to match one by one.
+//! An operand is written as in the text form, or is `_` to match anything, or is a capture: a name
+//! of capital letters, digits and underscores that takes the operand it matches, and only matches
+//! that again where it's used more than once. `_` in place of an instruction matches any one
+//! instruction and `*` any number of them, as few as it can. Labels, lines and frames between the
+//! instructions are skipped.
+
+use std::{collections::HashMap, io::Cursor, ops::Range, str::FromStr};
+
+use thiserror::Error;
+
+use crate::{
+	asm::{instruction_text, tokenize},
+	class_pool::IRCpTag,
+	code::{mnemonic, Instructions, Opcodes},
+	insn::{Insn, InsnList, Label},
+	opcode::{OpcodeInfo, Operands},
+};
+
+#[derive(Debug, Error)]
+pub enum PatternError {
+	#[error("instruction {index} of the pattern: {message}")]
+	Syntax { index: usize, message: String },
+}
+
+/// An operand of a matched instruction, as it's written in the text form or the label it refers
+/// to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Operand {
+	Text(String),
+	Label(Label),
+}
+
+#[derive(Debug, Clone)]
+enum Element {
+	Any,
+	Many,
+	Insn {
+		name: String,
+		/// `None` to match any operands.
+		operands: Option<Vec<OperandPattern>>,
+	},
+}
+
+#[derive(Debug, Clone)]
+enum OperandPattern {
+	Any,
+	Capture(String),
+	Text(String),
+}
+
+/// A parsed pattern, see the [module](self) for how one is written.
+#[derive(Debug, Clone)]
+pub struct Pattern {
+	elements: Vec<Element>,
+}
+
+impl FromStr for Pattern {
+	type Err = PatternError;
+
+	fn from_str(text: &str) -> Result<Self, PatternError> {
+		let elements = split(text)
+			.into_iter()
+			.enumerate()
+			.map(|(index, element)| parse_element(element).map_err(|message| PatternError::Syntax { index, message }))
+			.collect::<Result<_, _>>()?;
+		Ok(Self { elements })
+	}
+}
+
+/// Where a pattern matched.
+#[derive(Debug, Clone)]
+pub struct Match {
+	/// The indices of the first matched instruction and of the one after the last in
+	/// [`InsnList::insns`], with any labels, lines and frames between them.
+	pub range: Range<usize>,
+	captures: HashMap<String, Operand>,
+}
+
+impl Match {
+	/// What the capture called `name` took.
+	pub fn get(&self, name: &str) -> Option<&Operand> {
+		self.captures.get(name)
+	}
+
+	/// The label the capture called `name` took, `None` if it took something else.
+	pub fn label(&self, name: &str) -> Option<Label> {
+		match self.captures.get(name)? {
+			Operand::Label(label) => Some(*label),
+			Operand::Text(_) => None,
+		}
+	}
+
+	/// The text of the operand the capture called `name` took, `None` if it took a label.
+	pub fn text(&self, name: &str) -> Option<&str> {
+		match self.captures.get(name)? {
+			Operand::Text(text) => Some(text),
+			Operand::Label(_) => None,
+		}
+	}
+}
+
+impl Pattern {
+	/// The first match that starts at or after `insns.insns[from]`. `cp` is the pool the
+	/// instructions refer to.
+	pub fn find_from(&self, cp: &[IRCpTag], insns: &InsnList, from: usize) -> Option<Match> {
+		let instructions = tokens(cp, insns);
+		let first = instructions.iter().position(|(index, _)| *index >= from)?;
+		(first..instructions.len()).find_map(|start| self.match_at(&instructions, start))
+	}
+
+	pub fn find(&self, cp: &[IRCpTag], insns: &InsnList) -> Option<Match> {
+		self.find_from(cp, insns, 0)
+	}
+
+	/// Every match that doesn't overlap an earlier one, in order.
+	pub fn find_all(&self, cp: &[IRCpTag], insns: &InsnList) -> Vec<Match> {
+		let instructions = tokens(cp, insns);
+		let mut matches = Vec::new();
+		let mut start = 0;
+		while start < instructions.len() {
+			match self.match_at(&instructions, start) {
+				Some(found) => {
+					start = instructions.partition_point(|(index, _)| *index < found.range.end);
+					// a pattern of only `*` matches nothing, move past it anyway
+					start += found.range.is_empty() as usize;
+					matches.push(found);
+				}
+				None => start += 1,
+			}
+		}
+		matches
+	}
+
+	fn match_at(&self, instructions: &[(usize, Vec<Operand>)], start: usize) -> Option<Match> {
+		let mut captures = HashMap::new();
+		let end = matches(&self.elements, instructions, start, &mut captures)?;
+		let first = instructions[start].0;
+		let range = match end > start {
+			true => first..instructions[end - 1].0 + 1,
+			false => first..first,
+		};
+		Some(Match { range, captures })
+	}
+}
+
+/// The instructions of `insns` with their indices, each as its mnemonic followed by its operands.
+fn tokens(cp: &[IRCpTag], insns: &InsnList) -> Vec<(usize, Vec<Operand>)> {
+	let name = |opcode: u8| Operand::Text(mnemonic(opcode).unwrap_or("?").to_string());
+	insns
+		.insns
+		.iter()
+		.enumerate()
+		.filter_map(|(index, insn)| {
+			let tokens = match insn {
+				Insn::Label(_) | Insn::Line(_) | Insn::Frame { .. } => return None,
+				// the text form of an instruction always tokenizes
+				Insn::Op(instruction) => tokenize(&instruction_text(cp, instruction))
+					.unwrap_or_default()
+					.into_iter()
+					.map(Operand::Text)
+					.collect(),
+				Insn::Jump { opcode, target } => vec![name(*opcode), Operand::Label(*target)],
+				Insn::TableSwitch { .. } => vec![name(Opcodes::TABLESWITCH)],
+				Insn::LookupSwitch { .. } => vec![name(Opcodes::LOOKUPSWITCH)],
+			};
+			Some((index, tokens))
+		})
+		.collect()
+}
+
+/// Matches `elements` against the instructions from `at`, returning where the match ends.
+fn matches(
+	elements: &[Element],
+	instructions: &[(usize, Vec<Operand>)],
+	at: usize,
+	captures: &mut HashMap<String, Operand>,
+) -> Option<usize> {
+	let Some((element, rest)) = elements.split_first() else {
+		return Some(at);
+	};
+	match element {
+		Element::Many => (at..=instructions.len()).find_map(|skip| {
+			let mut attempt = captures.clone();
+			let end = matches(rest, instructions, skip, &mut attempt)?;
+			*captures = attempt;
+			Some(end)
+		}),
+		Element::Any => {
+			instructions.get(at)?;
+			matches(rest, instructions, at + 1, captures)
+		}
+		Element::Insn { name, operands } => {
+			let (_, tokens) = instructions.get(at)?;
+			if tokens.first() != Some(&Operand::Text(name.clone())) {
+				return None;
+			}
+			let mut attempt = captures.clone();
+			if let Some(operands) = operands {
+				if operands.len() != tokens.len() - 1 {
+					return None;
+				}
+				for (pattern, operand) in operands.iter().zip(&tokens[1..]) {
+					match pattern {
+						OperandPattern::Any => {}
+						OperandPattern::Text(text) if *operand == Operand::Text(text.clone()) => {}
+						OperandPattern::Text(_) => return None,
+						OperandPattern::Capture(capture) => match attempt.get(capture) {
+							Some(taken) if taken != operand => return None,
+							Some(_) => {}
+							None => {
+								attempt.insert(capture.clone(), operand.clone());
+							}
+						},
+					}
+				}
+			}
+			let end = matches(rest, instructions, at + 1, &mut attempt)?;
+			*captures = attempt;
+			Some(end)
+		}
+	}
+}
+
+/// Splits a pattern on the commas that aren't in a string.
+fn split(text: &str) -> Vec<&str> {
+	let mut elements = Vec::new();
+	let mut start = 0;
+	let mut quoted = false;
+	let mut escaped = false;
+	for (i, c) in text.char_indices() {
+		match c {
+			_ if escaped => escaped = false,
+			'\\' if quoted => escaped = true,
+			'"' => quoted = !quoted,
+			',' if !quoted => {
+				elements.push(&text[start..i]);
+				start = i + 1;
+			}
+			_ => {}
+		}
+	}
+	elements.push(&text[start..]);
+	elements
+}
+
+fn parse_element(text: &str) -> Result<Element, String> {
+	let tokens = tokenize(text)?;
+	let Some((name, operands)) = tokens.split_first() else {
+		return Err("it's empty".to_string());
+	};
+	match name.as_str() {
+		"_" | "*" if !operands.is_empty() => return Err(format!("`{name}` can't have operands")),
+		"_" => return Ok(Element::Any),
+		"*" => return Ok(Element::Many),
+		_ => {}
+	}
+	let info = OpcodeInfo::by_name(name)
+		.filter(|info| info.opcode != Opcodes::WIDE)
+		.ok_or_else(|| format!("unknown instruction `{name}`"))?;
+	if info.operands == Operands::None && operands.is_empty() {
+		// short forms like `aload_0` are matched as the long form they're decoded to
+		let instruction =
+			Instructions::read(&[], &mut Cursor::new([info.opcode])).map_err(|error| error.to_string())?;
+		let mut tokens = tokenize(&instruction_text(&[], &instruction))?.into_iter();
+		let name = tokens.next().unwrap();
+		let operands = tokens.map(OperandPattern::Text).collect::<Vec<_>>();
+		return Ok(Element::Insn {
+			name,
+			operands: (!operands.is_empty()).then_some(operands),
+		});
+	}
+	let operands = operands
+		.iter()
+		.map(|operand| match operand.as_str() {
+			"_" => OperandPattern::Any,
+			capture if is_capture(capture) => OperandPattern::Capture(capture.to_string()),
+			text => OperandPattern::Text(text.to_string()),
+		})
+		.collect::<Vec<_>>();
+	Ok(Element::Insn {
+		name: name.clone(),
+		operands: (!operands.is_empty()).then_some(operands),
+	})
+}
+
+fn is_capture(token: &str) -> bool {
+	token.starts_with(|c: char| c.is_ascii_uppercase())
+		&& token
+			.chars()
+			.all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		insn::InsnList,
+		test_util::{class, HELLO},
+		IRClassFile,
+	};
+
+	fn stackmapper() -> (IRClassFile, InsnList) {
+		let mut class = class(HELLO);
+		let insns = class
+			.method_insns("stackmapper", "(ILjava/lang/Object;)V")
+			.unwrap()
+			.unwrap();
+		(class, insns)
+	}
+
+	#[test]
+	fn captures() {
+		let (class, insns) = stackmapper();
+		let pattern: Pattern = "iconst_0, istore X, *, iload X, ifle L".parse().unwrap();
+		let found = pattern.find(&class.cp, &insns).unwrap();
+		assert_eq!(found.text("X"), Some("2"));
+		assert!(
+			matches!(&insns.insns[found.range.end - 1], Insn::Jump { target, .. } if Some(*target) == found.label("L"))
+		);
+
+		let increment: Pattern = "lload V, lconst_1, ladd, lstore V".parse().unwrap();
+		assert_eq!(increment.find(&class.cp, &insns).unwrap().text("V"), Some("5"));
+		let construct: Pattern = "new C, dup, invokespecial _, astore _".parse().unwrap();
+		assert_eq!(construct.find(&class.cp, &insns).unwrap().text("C"), Some("a/Hello$1"));
+	}
+
+	#[test]
+	fn mismatched_capture() {
+		let (class, insns) = stackmapper();
+		let mismatched: Pattern = "iload X, ifle _, lconst_0, lstore X".parse().unwrap();
+		assert!(mismatched.find(&class.cp, &insns).is_none());
+	}
+
+	#[test]
+	fn find_all() {
+		let (class, insns) = stackmapper();
+		let stores: Pattern = "iconst_0, istore _".parse().unwrap();
+		let all = stores.find_all(&class.cp, &insns);
+		assert_eq!(all.len(), 2);
+		assert!(stores.find_from(&class.cp, &insns, all[1].range.end).is_none());
+	}
+
+	#[test]
+	fn syntax_errors() {
+		let error = "iload X, bogus".parse::<Pattern>().unwrap_err();
+		assert!(matches!(error, PatternError::Syntax { index: 1, .. }));
+		assert!("iload X,".parse::<Pattern>().is_err());
+	}
+}
@@ -0,0 +1,171 @@
+//! Attributes introduced by JDK preview features (currently Project Valhalla).
+//!
+//! The layouts here follow the draft specs and can change between JDK releases,
+//! so they are only parsed with the `preview` feature enabled. Without it, these
+//! attributes end up in `IRAttribute::Unknown` like any other unrecognized attribute.
+
+/// Names of the preview attributes we know about.
+pub const PREVIEW_ATTRIBUTES: &[&str] = &["LoadableDescriptors", "ImplicitCreation", "NullRestricted"];
+
+pub fn is_preview_attribute(name: &str) -> bool {
+	PREVIEW_ATTRIBUTES.contains(&name)
+}
+
+#[cfg(feature = "preview")]
+pub use parse::*;
+
+#[cfg(feature = "preview")]
+mod parse {
+	use maya_bytes::{BytesReadExt, BytesWriteExt};
+
+	use crate::class_pool::{CPUtf8Ref, IRClassfileError, IRCpTag};
+
+	// https://cr.openjdk.org/~dlsmith/jep401/jep401-20240312/specs/value-objects-jvms.html
+	#[derive(Debug, Clone)]
+	#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+	pub enum PreviewAttribute {
+		/// Field descriptors of value classes that may be loaded early.
+		LoadableDescriptors { descriptors: Vec<CPUtf8Ref> },
+		/// Marks a value class as having an implicit (all-default) instance.
+		ImplicitCreation { flags: u16 },
+		/// Marks a field of a value class type as never holding null.
+		NullRestricted,
+	}
+
+	impl PreviewAttribute {
+		pub fn new<B: BytesReadExt>(name: &str, cp: &[IRCpTag], buffer: &mut B) -> Result<Self, IRClassfileError> {
+			Ok(match name {
+				"LoadableDescriptors" => {
+					let n_descriptors = buffer.read_u16()? as usize;
+					let mut descriptors = maya_bytes::vec_with_capacity(n_descriptors)?;
+
+					for _ in 0..n_descriptors {
+						descriptors.push(CPUtf8Ref::from_cp(cp, buffer.read_u16()?));
+					}
+
+					Self::LoadableDescriptors { descriptors }
+				}
+				"ImplicitCreation" => Self::ImplicitCreation {
+					flags: buffer.read_u16()?,
+				},
+				"NullRestricted" => Self::NullRestricted,
+				n => unreachable!("not a preview attribute: {n}"),
+			})
+		}
+
+		pub fn write<B: BytesWriteExt>(&self, buffer: &mut B) -> Result<(), IRClassfileError> {
+			match self {
+				Self::LoadableDescriptors { descriptors } => {
+					buffer.write_u16(crate::attribute::count("descriptors", descriptors.len())?)?;
+					for descriptor in descriptors {
+						buffer.write_u16(descriptor.index)?;
+					}
+				}
+				Self::ImplicitCreation { flags } => buffer.write_u16(*flags)?,
+				Self::NullRestricted => {}
+			}
+			Ok(())
+		}
+
+		pub const fn name(&self) -> &'static str {
+			match self {
+				Self::LoadableDescriptors { descriptors: _ } => "LoadableDescriptors",
+				Self::ImplicitCreation { flags: _ } => "ImplicitCreation",
+				Self::NullRestricted => "NullRestricted",
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use maya_classfile_io::IOAttributeInfo;
+
+	#[cfg(feature = "preview")]
+	use super::PreviewAttribute;
+	use crate::{
+		attribute::{IRAttribute, IRAttributeInfo},
+		class_pool::ConstantPool,
+		intern_utf8,
+	};
+
+	/// Parses `info` as the attribute called `name`, and what it writes back.
+	fn round_trip(cp: &mut ConstantPool, name: &str, info: Vec<u8>) -> (IRAttribute, Vec<u8>) {
+		let raw = IOAttributeInfo {
+			attribute_name_index: intern_utf8(cp, name).unwrap().index,
+			attribute_length: info.len() as u32,
+			info,
+		};
+		let attribute = IRAttributeInfo::from_io(cp, raw).unwrap();
+		let mut written = Vec::new();
+		attribute.attr.write(cp, &mut written).unwrap();
+		(attribute.attr, written)
+	}
+
+	fn loadable_descriptors(cp: &mut ConstantPool) -> Vec<u8> {
+		let point = intern_utf8(cp, "Lgen/Point;").unwrap().index;
+		let line = intern_utf8(cp, "Lgen/Line;").unwrap().index;
+		[2u16, point, line]
+			.iter()
+			.flat_map(|value| value.to_be_bytes())
+			.collect()
+	}
+
+	#[cfg(feature = "preview")]
+	#[test]
+	fn loadable_descriptors_round_trip() {
+		let mut cp = ConstantPool::new();
+		let info = loadable_descriptors(&mut cp);
+		let (attribute, written) = round_trip(&mut cp, "LoadableDescriptors", info.clone());
+		let IRAttribute::Preview(PreviewAttribute::LoadableDescriptors { descriptors }) = attribute else {
+			panic!("expected LoadableDescriptors, got {attribute:?}");
+		};
+		let descriptors = descriptors.iter().map(|utf8| utf8.data.as_str()).collect::<Vec<_>>();
+		assert_eq!(descriptors, ["Lgen/Point;", "Lgen/Line;"]);
+		assert_eq!(written, info);
+	}
+
+	#[cfg(feature = "preview")]
+	#[test]
+	fn implicit_creation_round_trip() {
+		let mut cp = ConstantPool::new();
+		let (attribute, written) = round_trip(&mut cp, "ImplicitCreation", vec![0x00, 0x01]);
+		assert!(matches!(
+			attribute,
+			IRAttribute::Preview(PreviewAttribute::ImplicitCreation { flags: 1 })
+		));
+		assert_eq!(written, [0x00, 0x01]);
+	}
+
+	#[cfg(feature = "preview")]
+	#[test]
+	fn null_restricted_round_trip() {
+		let mut cp = ConstantPool::new();
+		let (attribute, written) = round_trip(&mut cp, "NullRestricted", Vec::new());
+		assert!(matches!(
+			attribute,
+			IRAttribute::Preview(PreviewAttribute::NullRestricted)
+		));
+		assert!(written.is_empty());
+	}
+
+	#[cfg(not(feature = "preview"))]
+	#[test]
+	fn unknown_without_feature() {
+		let mut cp = ConstantPool::new();
+		let loadable = loadable_descriptors(&mut cp);
+		for (name, info) in [
+			("LoadableDescriptors", loadable),
+			("ImplicitCreation", vec![0x00, 0x01]),
+			("NullRestricted", Vec::new()),
+		] {
+			let (attribute, written) = round_trip(&mut cp, name, info.clone());
+			let IRAttribute::Unknown { name: parsed, data } = attribute else {
+				panic!("expected {name} to be unknown, got {attribute:?}");
+			};
+			assert_eq!(parsed.data.as_str(), name);
+			assert_eq!(data, info);
+			assert_eq!(written, info);
+		}
+	}
+}
@@ -0,0 +1,927 @@
+//! Renaming classes and members from a mapping file, see [`IRClassFile::remap`].
+
+use std::collections::HashMap;
+
+use thiserror::Error;
+
+use crate::{
+	attribute::{IRAttribute, IRAttributeInfo, RuntimeAnnotation, RuntimeAnnotationEVPair, RuntimeAnnotationValue},
+	class_pool::{CPNameAndTypeRef, CPUtf8Ref, ConstantPool, IRClassfileError, IRCpTag},
+	intern_name_and_type, intern_utf8, IRClassFile,
+};
+
+#[derive(Debug, Error)]
+pub enum MappingError {
+	#[error("line {line}: {message}")]
+	Syntax { line: usize, message: String },
+	#[error("not a tiny v2 file")]
+	NotTinyV2,
+	#[error("no namespace called {0}")]
+	UnknownNamespace(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct MemberKey {
+	owner: String,
+	name: String,
+	descriptor: String,
+}
+
+impl MemberKey {
+	fn new(owner: &str, name: &str, descriptor: &str) -> Self {
+		Self {
+			owner: owner.to_string(),
+			name: name.to_string(),
+			descriptor: descriptor.to_string(),
+		}
+	}
+}
+
+/// Class and member renames, keyed by the names they have before remapping. Members are keyed by
+/// the exact owner they're referenced through, inherited members aren't looked up in superclasses.
+#[derive(Debug, Clone, Default)]
+pub struct Mappings {
+	classes: HashMap<String, String>,
+	fields: HashMap<MemberKey, String>,
+	methods: HashMap<MemberKey, String>,
+}
+
+impl Mappings {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.classes.is_empty() && self.fields.is_empty() && self.methods.is_empty()
+	}
+
+	pub fn add_class(&mut self, from: impl Into<String>, to: impl Into<String>) {
+		self.classes.insert(from.into(), to.into());
+	}
+
+	pub fn add_field(&mut self, owner: &str, name: &str, descriptor: &str, to: impl Into<String>) {
+		self.fields.insert(MemberKey::new(owner, name, descriptor), to.into());
+	}
+
+	pub fn add_method(&mut self, owner: &str, name: &str, descriptor: &str, to: impl Into<String>) {
+		self.methods.insert(MemberKey::new(owner, name, descriptor), to.into());
+	}
+
+	/// The new internal name of `name`, `None` if it stays the same. Array classes are remapped by
+	/// their element type and nested classes without a mapping of their own follow their outer class.
+	pub fn map_class(&self, name: &str) -> Option<String> {
+		if let Some(to) = self.classes.get(name) {
+			return Some(to.clone());
+		}
+		if name.starts_with('[') {
+			let mapped = self.map_descriptor(name);
+			return (mapped != name).then_some(mapped);
+		}
+		let (outer, inner) = name.rsplit_once('$')?;
+		Some(format!("{}${inner}", self.map_class(outer)?))
+	}
+
+	fn class_name(&self, name: &str) -> String {
+		self.map_class(name).unwrap_or_else(|| name.to_string())
+	}
+
+	pub fn map_field(&self, owner: &str, name: &str, descriptor: &str) -> Option<&str> {
+		self.fields
+			.get(&MemberKey::new(owner, name, descriptor))
+			.map(String::as_str)
+	}
+
+	pub fn map_method(&self, owner: &str, name: &str, descriptor: &str) -> Option<&str> {
+		self.methods
+			.get(&MemberKey::new(owner, name, descriptor))
+			.map(String::as_str)
+	}
+
+	/// element_value pairs only name their element, which is a method of the annotation interface
+	/// without parameters.
+	fn map_element(&self, owner: &str, name: &str) -> Option<&str> {
+		self.methods
+			.iter()
+			.find(|(key, _)| key.owner == owner && key.name == name && key.descriptor.starts_with("()"))
+			.map(|(_, to)| to.as_str())
+	}
+
+	/// Renames every class in a field or method descriptor.
+	pub fn map_descriptor(&self, descriptor: &str) -> String {
+		let mut out = String::with_capacity(descriptor.len());
+		let mut rest = descriptor;
+		while let Some(start) = rest.find('L') {
+			let Some(len) = rest[start..].find(';') else {
+				break;
+			};
+			out.push_str(&rest[..=start]);
+			out.push_str(&self.class_name(&rest[start + 1..start + len]));
+			rest = &rest[start + len..];
+		}
+		out.push_str(rest);
+		out
+	}
+
+	/// Renames every class in a generic class, method or field signature. A malformed signature is
+	/// returned as is.
+	pub fn map_signature(&self, signature: &str) -> String {
+		self.try_map_signature(signature)
+			.unwrap_or_else(|| signature.to_string())
+	}
+
+	fn try_map_signature(&self, signature: &str) -> Option<String> {
+		let mut out = String::with_capacity(signature.len());
+		let mut i = 0;
+
+		// formal type parameters only ever come first, their names could be mistaken for types
+		if signature.starts_with('<') {
+			out.push('<');
+			i = 1;
+			while *signature.as_bytes().get(i)? != b'>' {
+				let name_len = signature[i..].find(':')?;
+				out.push_str(&signature[i..i + name_len]);
+				i += name_len;
+				while signature.as_bytes().get(i) == Some(&b':') {
+					out.push(':');
+					i += 1;
+					if !matches!(signature.as_bytes().get(i), Some(b':' | b'>')) {
+						i = self.map_signature_type(signature, i, &mut out)?;
+					}
+				}
+			}
+			out.push('>');
+			i += 1;
+		}
+
+		while i < signature.len() {
+			i = self.map_signature_type(signature, i, &mut out)?;
+		}
+		Some(out)
+	}
+
+	/// Maps the type starting at `i`, or copies a single character that isn't a class or type
+	/// variable. Returns where the type ends.
+	fn map_signature_type(&self, signature: &str, mut i: usize, out: &mut String) -> Option<usize> {
+		let bytes = signature.as_bytes();
+		match bytes[i] {
+			b'T' => {
+				let len = signature[i..].find(';')? + 1;
+				out.push_str(&signature[i..i + len]);
+				Some(i + len)
+			}
+			b'L' => {
+				let name_end = |from: usize| signature[from..].find(['<', '.', ';']).map(|len| from + len);
+
+				i += 1;
+				let end = name_end(i)?;
+				let mut name = signature[i..end].to_string();
+				let mut mapped = self.class_name(&name);
+				out.push('L');
+				out.push_str(&mapped);
+				i = end;
+
+				loop {
+					match *bytes.get(i)? {
+						b'<' => {
+							out.push('<');
+							i += 1;
+							while *bytes.get(i)? != b'>' {
+								match bytes[i] {
+									b'*' => {
+										out.push('*');
+										i += 1;
+									}
+									b'+' | b'-' => {
+										out.push(bytes[i] as char);
+										i = self.map_signature_type(signature, i + 1, out)?;
+									}
+									_ => i = self.map_signature_type(signature, i, out)?,
+								}
+							}
+							out.push('>');
+							i += 1;
+						}
+						b'.' => {
+							let end = name_end(i + 1)?;
+							name = format!("{name}${}", &signature[i + 1..end]);
+							let inner = self.class_name(&name);
+							let simple = match inner.strip_prefix(&format!("{mapped}$")) {
+								Some(simple) => simple,
+								None => inner.rsplit(['/', '$']).next().unwrap_or(&inner),
+							};
+							out.push('.');
+							out.push_str(simple);
+							mapped = inner.clone();
+							i = end;
+						}
+						b';' => {
+							out.push(';');
+							return Some(i + 1);
+						}
+						_ => return None,
+					}
+				}
+			}
+			c => {
+				out.push(c as char);
+				Some(i + 1)
+			}
+		}
+	}
+
+	/// The same mappings in the other direction, mapping the new names back to the old ones.
+	pub fn reversed(&self) -> Mappings {
+		let reverse_members = |members: &HashMap<MemberKey, String>| {
+			members
+				.iter()
+				.map(|(key, to)| {
+					let owner = self.class_name(&key.owner);
+					let descriptor = self.map_descriptor(&key.descriptor);
+					(MemberKey::new(&owner, to, &descriptor), key.name.clone())
+				})
+				.collect()
+		};
+
+		Mappings {
+			classes: self
+				.classes
+				.iter()
+				.map(|(from, to)| (to.clone(), from.clone()))
+				.collect(),
+			fields: reverse_members(&self.fields),
+			methods: reverse_members(&self.methods),
+		}
+	}
+
+	/// Parses a ProGuard/R8 `mapping.txt`. These map the original names to the obfuscated ones, use
+	/// [`reversed`](Self::reversed) to deobfuscate. Members that were inlined from other classes
+	/// are skipped.
+	pub fn from_proguard(text: &str) -> Result<Self, MappingError> {
+		let mut mappings = Self::new();
+		let mut class = None;
+
+		for (i, line) in text.lines().enumerate() {
+			let syntax = |message: &str| MappingError::Syntax {
+				line: i + 1,
+				message: message.to_string(),
+			};
+
+			let trimmed = line.trim();
+			if trimmed.is_empty() || trimmed.starts_with('#') {
+				continue;
+			}
+			let (from, to) = trimmed.split_once(" -> ").ok_or_else(|| syntax("expected `->`"))?;
+
+			if !line.starts_with(char::is_whitespace) {
+				let to = to
+					.strip_suffix(':')
+					.ok_or_else(|| syntax("expected `:` after the class"))?;
+				let from = from.replace('.', "/");
+				mappings.add_class(from.clone(), to.replace('.', "/"));
+				class = Some(from);
+				continue;
+			}
+
+			let owner = class.as_deref().ok_or_else(|| syntax("member outside of a class"))?;
+			// methods can be prefixed with the line range they cover, `1:5:void run() -> a`
+			let from = from.trim_start_matches(|c: char| c.is_ascii_digit() || c == ':');
+			let (ty, rest) = from
+				.split_once(' ')
+				.ok_or_else(|| syntax("expected a type and a name"))?;
+			let ty = java_type_descriptor(ty);
+
+			match rest.split_once('(') {
+				Some((name, params)) => {
+					let (params, _) = params
+						.split_once(')')
+						.ok_or_else(|| syntax("unclosed parameter list"))?;
+					if name.contains('.') {
+						continue;
+					}
+
+					let mut descriptor = "(".to_string();
+					for param in params.split(',').filter(|param| !param.is_empty()) {
+						descriptor.push_str(&java_type_descriptor(param));
+					}
+					descriptor.push(')');
+					descriptor.push_str(&ty);
+					mappings.add_method(owner, name, &descriptor, to);
+				}
+				None => mappings.add_field(owner, rest, &ty, to),
+			}
+		}
+		Ok(mappings)
+	}
+
+	/// Parses a Tiny v2 file, mapping the names in the `from` namespace to the ones in `to`.
+	/// Parameters, locals and comments are ignored.
+	pub fn from_tiny_v2(text: &str, from: &str, to: &str) -> Result<Self, MappingError> {
+		let mut lines = text.lines().enumerate();
+		let header = lines
+			.next()
+			.map(|(_, header)| header.split('\t').collect::<Vec<_>>())
+			.ok_or(MappingError::NotTinyV2)?;
+		if header.len() < 5 || header[0] != "tiny" || header[1] != "2" {
+			return Err(MappingError::NotTinyV2);
+		}
+		let namespaces = &header[3..];
+		let namespace = |name: &str| {
+			namespaces
+				.iter()
+				.position(|namespace| *namespace == name)
+				.ok_or_else(|| MappingError::UnknownNamespace(name.to_string()))
+		};
+		let (from, to) = (namespace(from)?, namespace(to)?);
+
+		// descriptors are written with the names of the first namespace, so every class has to be
+		// known before the members can be keyed
+		let mut escaped = false;
+		let mut classes = Vec::new();
+		let mut members = Vec::new();
+		for (i, line) in lines {
+			let syntax = |message: &str| MappingError::Syntax {
+				line: i + 1,
+				message: message.to_string(),
+			};
+
+			let depth = line.len() - line.trim_start_matches('\t').len();
+			let columns = line[depth..].split('\t').collect::<Vec<_>>();
+			let names = |columns: &[&str]| -> Result<Vec<String>, MappingError> {
+				if columns.len() != namespaces.len() {
+					return Err(syntax("wrong number of names"));
+				}
+				Ok(columns
+					.iter()
+					.map(|name| if escaped { unescape(name) } else { name.to_string() })
+					.collect())
+			};
+
+			match (depth, columns[0]) {
+				(0, "c") => classes.push(names(&columns[1..])?),
+				(1, kind @ ("f" | "m")) if !classes.is_empty() => {
+					let descriptor = columns.get(1).ok_or_else(|| syntax("expected a descriptor"))?;
+					members.push((
+						classes.len() - 1,
+						kind == "m",
+						descriptor.to_string(),
+						names(&columns[2..])?,
+					));
+				}
+				(1, "escaped-names") if classes.is_empty() => escaped = true,
+				// other header properties, parameters, locals and comments
+				(1.., _) => {}
+				_ => return Err(syntax("expected a class")),
+			}
+		}
+
+		// an empty name means the name doesn't change from the first namespace
+		let pick = |names: &[String], namespace: usize| -> String {
+			match names[namespace].as_str() {
+				"" => names[0].clone(),
+				name => name.to_string(),
+			}
+		};
+
+		let mut first_to_from = Self::new();
+		let mut mappings = Self::new();
+		for names in &classes {
+			first_to_from.add_class(names[0].clone(), pick(names, from));
+			let (class_from, class_to) = (pick(names, from), pick(names, to));
+			if class_from != class_to {
+				mappings.add_class(class_from, class_to);
+			}
+		}
+		for (class, is_method, descriptor, names) in members {
+			let owner = pick(&classes[class], from);
+			let descriptor = first_to_from.map_descriptor(&descriptor);
+			let (name_from, name_to) = (pick(&names, from), pick(&names, to));
+			if name_from == name_to {
+				continue;
+			}
+			if is_method {
+				mappings.add_method(&owner, &name_from, &descriptor, name_to);
+			} else {
+				mappings.add_field(&owner, &name_from, &descriptor, name_to);
+			}
+		}
+		Ok(mappings)
+	}
+}
+
+/// `int[]` to `[I`, `java.lang.String` to `Ljava/lang/String;`.
+fn java_type_descriptor(ty: &str) -> String {
+	let element = ty.trim_end_matches("[]");
+	let mut descriptor = "[".repeat((ty.len() - element.len()) / 2);
+	match element {
+		"void" => descriptor.push('V'),
+		"boolean" => descriptor.push('Z'),
+		"byte" => descriptor.push('B'),
+		"char" => descriptor.push('C'),
+		"short" => descriptor.push('S'),
+		"int" => descriptor.push('I'),
+		"long" => descriptor.push('J'),
+		"float" => descriptor.push('F'),
+		"double" => descriptor.push('D'),
+		class => {
+			descriptor.push('L');
+			descriptor.push_str(&class.replace('.', "/"));
+			descriptor.push(';');
+		}
+	}
+	descriptor
+}
+
+fn unescape(name: &str) -> String {
+	let mut out = String::with_capacity(name.len());
+	let mut chars = name.chars();
+	while let Some(c) = chars.next() {
+		if c != '\\' {
+			out.push(c);
+			continue;
+		}
+		match chars.next() {
+			Some('n') => out.push('\n'),
+			Some('r') => out.push('\r'),
+			Some('t') => out.push('\t'),
+			Some('0') => out.push('\0'),
+			Some(c) => out.push(c),
+			None => out.push('\\'),
+		}
+	}
+	out
+}
+
+impl IRClassFile {
+	/// Applies `mappings` to the class: its name, its members and every class, member, descriptor,
+	/// signature and annotation it refers to. String constants are left alone.
+	///
+	/// Changed names are appended to the pool and the old entries stay behind, use
+	/// [`compact_constant_pool`](Self::compact_constant_pool) to drop them.
+	pub fn remap(&mut self, mappings: &Mappings) -> Result<(), IRClassfileError> {
+		let this = self.class_name().to_string();
+		let original = self.cp.to_vec();
+		let class_name = |index: u16| match &original[index as usize - 1] {
+			IRCpTag::Class(name) => name.data.as_str(),
+			_ => "",
+		};
+
+		// Class and ref entries are rewritten in place so every index pointing at them stays valid,
+		// Utf8 and NameAndType entries can be shared and get new entries instead
+		for (index, tag) in original.iter().enumerate() {
+			let remapped = match tag {
+				IRCpTag::Class(name) => match mappings.map_class(&name.data) {
+					Some(to) => IRCpTag::Class(intern_utf8(&mut self.cp, &to)?),
+					None => continue,
+				},
+				IRCpTag::FieldRef {
+					class_index,
+					name_and_ty,
+				} => {
+					let owner = class_name(*class_index);
+					let name = mappings.map_field(owner, &name_and_ty.name.data, &name_and_ty.ty.data);
+					let Some(name_and_ty) = remap_name_and_type(&mut self.cp, mappings, name_and_ty, name)? else {
+						continue;
+					};
+					IRCpTag::FieldRef {
+						class_index: *class_index,
+						name_and_ty,
+					}
+				}
+				IRCpTag::MethodRef {
+					class_index,
+					name_and_ty,
+				} => {
+					let owner = class_name(*class_index);
+					let name = mappings.map_method(owner, &name_and_ty.name.data, &name_and_ty.ty.data);
+					let Some(name_and_ty) = remap_name_and_type(&mut self.cp, mappings, name_and_ty, name)? else {
+						continue;
+					};
+					IRCpTag::MethodRef {
+						class_index: *class_index,
+						name_and_ty,
+					}
+				}
+				IRCpTag::InterfaceMethodRef {
+					class_index,
+					name_and_ty,
+				} => {
+					let owner = class_name(*class_index);
+					let name = mappings.map_method(owner, &name_and_ty.name.data, &name_and_ty.ty.data);
+					let Some(name_and_ty) = remap_name_and_type(&mut self.cp, mappings, name_and_ty, name)? else {
+						continue;
+					};
+					IRCpTag::InterfaceMethodRef {
+						class_index: *class_index,
+						name_and_ty,
+					}
+				}
+				IRCpTag::Dynamic {
+					bootstrap_method_attr_index,
+					name_and_ty,
+				} => {
+					let Some(name_and_ty) = remap_name_and_type(&mut self.cp, mappings, name_and_ty, None)? else {
+						continue;
+					};
+					IRCpTag::Dynamic {
+						bootstrap_method_attr_index: *bootstrap_method_attr_index,
+						name_and_ty,
+					}
+				}
+				IRCpTag::InvokeDynamic {
+					bootstrap_method_attr_index,
+					name_and_ty,
+				} => {
+					let Some(name_and_ty) = remap_name_and_type(&mut self.cp, mappings, name_and_ty, None)? else {
+						continue;
+					};
+					IRCpTag::InvokeDynamic {
+						bootstrap_method_attr_index: *bootstrap_method_attr_index,
+						name_and_ty,
+					}
+				}
+				IRCpTag::MethodType(descriptor) => {
+					let mapped = mappings.map_descriptor(&descriptor.data);
+					if mapped == descriptor.data.as_str() {
+						continue;
+					}
+					IRCpTag::MethodType(intern_utf8(&mut self.cp, &mapped)?)
+				}
+				_ => continue,
+			};
+			self.cp.set(index as u16 + 1, remapped)?;
+		}
+
+		let mut remapper = Remapper {
+			cp: &mut self.cp,
+			mappings,
+			this: &this,
+		};
+		for field in &mut self.fields {
+			if let Some(to) = mappings.map_field(&this, &field.name.data, &field.descriptor.data) {
+				field.name = intern_utf8(remapper.cp, to)?;
+			}
+			remapper.descriptor(&mut field.descriptor)?;
+			remapper.attributes(field.attributes.iter_mut())?;
+		}
+		for method in &mut self.methods {
+			if let Some(to) = mappings.map_method(&this, &method.name.data, &method.descriptor.data) {
+				method.name = intern_utf8(remapper.cp, to)?;
+			}
+			remapper.descriptor(&mut method.descriptor)?;
+			remapper.attributes(method.attributes.iter_mut())?;
+		}
+		remapper.attributes(self.attributes.iter_mut())?;
+
+		// the refs all over the IR keep copies of the entries they point at, reading the class back
+		// refreshes them
+		*self = Self::from_io(self.to_io()?)?;
+		Ok(())
+	}
+}
+
+/// A NameAndType with the name changed to `name` and its descriptor remapped, `None` if neither
+/// changed.
+fn remap_name_and_type(
+	cp: &mut ConstantPool,
+	mappings: &Mappings,
+	name_and_ty: &CPNameAndTypeRef,
+	name: Option<&str>,
+) -> Result<Option<CPNameAndTypeRef>, IRClassfileError> {
+	let name = name.unwrap_or(&name_and_ty.name.data);
+	let descriptor = mappings.map_descriptor(&name_and_ty.ty.data);
+	if name == name_and_ty.name.data.as_str() && descriptor == name_and_ty.ty.data.as_str() {
+		return Ok(None);
+	}
+	intern_name_and_type(cp, name, &descriptor).map(Some)
+}
+
+struct Remapper<'a> {
+	cp: &'a mut ConstantPool,
+	mappings: &'a Mappings,
+	this: &'a str,
+}
+
+impl Remapper<'_> {
+	fn replace(&mut self, utf8: &mut CPUtf8Ref, mapped: String) -> Result<(), IRClassfileError> {
+		if mapped != utf8.data.as_str() {
+			*utf8 = intern_utf8(self.cp, &mapped)?;
+		}
+		Ok(())
+	}
+
+	fn descriptor(&mut self, utf8: &mut CPUtf8Ref) -> Result<(), IRClassfileError> {
+		let mapped = self.mappings.map_descriptor(&utf8.data);
+		self.replace(utf8, mapped)
+	}
+
+	fn signature(&mut self, utf8: &mut CPUtf8Ref) -> Result<(), IRClassfileError> {
+		let mapped = self.mappings.map_signature(&utf8.data);
+		self.replace(utf8, mapped)
+	}
+
+	fn attributes<'b>(
+		&mut self,
+		attributes: impl Iterator<Item = &'b mut IRAttributeInfo>,
+	) -> Result<(), IRClassfileError> {
+		for attribute in attributes {
+			self.attribute(&mut attribute.attr)?;
+		}
+		Ok(())
+	}
+
+	fn attribute(&mut self, attribute: &mut IRAttribute) -> Result<(), IRClassfileError> {
+		match attribute {
+			IRAttribute::Code(code) => self.attributes(code.attributes.iter_mut().map(Box::as_mut))?,
+			IRAttribute::Signature(signature) => self.signature(signature)?,
+			IRAttribute::LocalVariableTable { table } => {
+				for entry in table {
+					self.descriptor(&mut entry.descriptor)?;
+				}
+			}
+			IRAttribute::LocalVariableTypeTable { table } => {
+				for entry in table {
+					self.signature(&mut entry.signature)?;
+				}
+			}
+			IRAttribute::InnerClasses(inner) => {
+				for class in &mut inner.classes {
+					let (Some(outer), Some(name)) = (&class.outer_class_info, &mut class.inner_name) else {
+						continue;
+					};
+					let Some(mapped) = self.mappings.map_class(&class.inner_class_info.data.data) else {
+						continue;
+					};
+					let outer = self.mappings.class_name(&outer.data.data);
+					let simple = match mapped.strip_prefix(&format!("{outer}$")) {
+						Some(simple) => simple,
+						None => mapped.rsplit(['/', '$']).next().unwrap_or(&mapped),
+					};
+					let simple = simple.to_string();
+					self.replace(name, simple)?;
+				}
+			}
+			IRAttribute::EnclosingMethod { class, method } => {
+				if method.index != 0 {
+					let name = self
+						.mappings
+						.map_method(&class.data.data, &method.name.data, &method.ty.data);
+					if let Some(name_and_ty) = remap_name_and_type(self.cp, self.mappings, method, name)? {
+						*method = name_and_ty;
+					}
+				}
+			}
+			IRAttribute::RuntimeVisibleAnnotations { annotations }
+			| IRAttribute::RuntimeInvisibleAnnotations { annotations } => {
+				for annotation in annotations {
+					self.annotation(annotation)?;
+				}
+			}
+			IRAttribute::RuntimeVisibleParameterAnnotations { params }
+			| IRAttribute::RuntimeInvisibleParameterAnnotations { params } => {
+				for annotation in params.iter_mut().flatten() {
+					self.annotation(annotation)?;
+				}
+			}
+			IRAttribute::AnnotationDefault { default_value } => self.annotation_value(default_value)?,
+			IRAttribute::RuntimeVisibleTypeAnnotations { annotations }
+			| IRAttribute::RuntimeInvisibleTypeAnnotations { annotations } => {
+				for annotation in annotations {
+					let mut ty = CPUtf8Ref::from_cp(self.cp, annotation.type_index);
+					let owner = descriptor_class(&ty.data).to_string();
+					self.descriptor(&mut ty)?;
+					annotation.type_index = ty.index;
+					self.pairs(&owner, &mut annotation.pairs)?;
+				}
+			}
+			IRAttribute::Record { components } => {
+				for component in components {
+					let this = self.this;
+					if let Some(to) = self
+						.mappings
+						.map_field(this, &component.name.data, &component.descriptor.data)
+					{
+						component.name = intern_utf8(self.cp, to)?;
+					}
+					self.descriptor(&mut component.descriptor)?;
+					self.attributes(component.attributes.iter_mut())?;
+				}
+			}
+			_ => {}
+		}
+		Ok(())
+	}
+
+	fn annotation(&mut self, annotation: &mut RuntimeAnnotation) -> Result<(), IRClassfileError> {
+		let owner = descriptor_class(&annotation.ty.data).to_string();
+		self.descriptor(&mut annotation.ty)?;
+		self.pairs(&owner, &mut annotation.pairs)
+	}
+
+	fn pairs(&mut self, owner: &str, pairs: &mut [RuntimeAnnotationEVPair]) -> Result<(), IRClassfileError> {
+		for pair in pairs {
+			if let Some(to) = self.mappings.map_element(owner, &pair.name.data) {
+				pair.name = intern_utf8(self.cp, to)?;
+			}
+			self.annotation_value(&mut pair.value)?;
+		}
+		Ok(())
+	}
+
+	fn annotation_value(&mut self, value: &mut RuntimeAnnotationValue) -> Result<(), IRClassfileError> {
+		match value {
+			RuntimeAnnotationValue::ConstValueIndex { .. } => {}
+			RuntimeAnnotationValue::EnumConstValue { type_name, const_name } => {
+				let owner = descriptor_class(&type_name.data);
+				if let Some(to) = self.mappings.map_field(owner, &const_name.data, &type_name.data) {
+					*const_name = intern_utf8(self.cp, to)?;
+				}
+				self.descriptor(type_name)?;
+			}
+			RuntimeAnnotationValue::ClassInfoIndex(class) => self.descriptor(class)?,
+			RuntimeAnnotationValue::Annotation(annotation) => self.annotation(annotation)?,
+			RuntimeAnnotationValue::ArrayValue { values } => {
+				for value in values {
+					self.annotation_value(value)?;
+				}
+			}
+		}
+		Ok(())
+	}
+}
+
+/// `Lfoo/Bar;` to `foo/Bar`.
+fn descriptor_class(descriptor: &str) -> &str {
+	descriptor
+		.strip_prefix('L')
+		.and_then(|descriptor| descriptor.strip_suffix(';'))
+		.unwrap_or(descriptor)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		attribute::InnerClassesAttribute,
+		test_util::{class, find_attr, HELLO},
+	};
+
+	#[test]
+	fn descriptors_and_signatures() {
+		let mut mappings = Mappings::new();
+		mappings.add_class("a/Outer", "x/O");
+		mappings.add_class("a/Outer$Inner", "x/O$In");
+
+		assert_eq!(
+			mappings.map_descriptor("(ILa/Outer;[La/Log;)La/Outer$Inner;"),
+			"(ILx/O;[La/Log;)Lx/O$In;"
+		);
+		assert_eq!(mappings.map_class("[[La/Outer;").as_deref(), Some("[[Lx/O;"));
+		assert_eq!(mappings.map_class("a/Outer$Inner$1").as_deref(), Some("x/O$In$1"));
+		assert_eq!(mappings.map_class("a/Other"), None);
+
+		// `LIST` is a type parameter, not a class
+		assert_eq!(
+			mappings.map_signature("<LIST::Ljava/util/List<La/Outer;>;>(La/Outer<TLIST;>.Inner;)V"),
+			"<LIST::Ljava/util/List<Lx/O;>;>(Lx/O<TLIST;>.In;)V"
+		);
+		assert_eq!(
+			mappings.map_signature("Ljava/util/Map<+La/Outer;*>;"),
+			"Ljava/util/Map<+Lx/O;*>;"
+		);
+		assert_eq!(mappings.map_signature("La/Outer<"), "La/Outer<");
+	}
+
+	#[test]
+	fn proguard() {
+		let mappings = Mappings::from_proguard(
+			"# compiler: R8\n\
+			com.example.Foo -> a.a:\n    \
+				java.lang.String name -> a\n    \
+				int[] counts -> b\n    \
+				1:3:void run(int,com.example.Foo) -> c\n    \
+				com.example.Foo self():10:12 -> d\n    \
+				4:4:void com.example.Bar.inlined():5:5 -> c\n\
+			com.example.Foo$Inner -> a.b:\n",
+		)
+		.unwrap();
+		assert_eq!(mappings.map_class("com/example/Foo").as_deref(), Some("a/a"));
+		assert_eq!(mappings.map_field("com/example/Foo", "counts", "[I"), Some("b"));
+		assert_eq!(
+			mappings.map_method("com/example/Foo", "run", "(ILcom/example/Foo;)V"),
+			Some("c")
+		);
+		assert_eq!(
+			mappings.map_method("com/example/Foo", "self", "()Lcom/example/Foo;"),
+			Some("d")
+		);
+		assert_eq!(mappings.methods.len(), 2);
+
+		let reversed = mappings.reversed();
+		assert_eq!(reversed.map_class("a/b").as_deref(), Some("com/example/Foo$Inner"));
+		assert_eq!(reversed.map_method("a/a", "c", "(ILa/a;)V"), Some("run"));
+		assert_eq!(reversed.map_field("a/a", "a", "Ljava/lang/String;"), Some("name"));
+
+		assert!(matches!(
+			Mappings::from_proguard("    int x -> a\n"),
+			Err(MappingError::Syntax { line: 1, .. })
+		));
+	}
+
+	#[test]
+	fn tiny_v2() {
+		let text = "tiny\t2\t0\tofficial\tintermediary\tnamed\n\
+			\tescaped-names\n\
+			c\ta\tnet/minecraft/class_1\tnet/minecraft/Block\n\
+			\tc\tA block.\n\
+			\tf\tLa;\tb\tfield_1\tparent\n\
+			\tm\t(La;I)V\tc\tmethod_1\tplace\\ttab\n\
+			\t\tp\t1\t\t\tpos\n\
+			c\tb\tnet/minecraft/class_2\t\n";
+
+		let mappings = Mappings::from_tiny_v2(text, "intermediary", "named").unwrap();
+		assert_eq!(
+			mappings.map_class("net/minecraft/class_1").as_deref(),
+			Some("net/minecraft/Block")
+		);
+		assert_eq!(mappings.map_class("net/minecraft/class_2").as_deref(), Some("b"));
+		assert_eq!(
+			mappings.map_field("net/minecraft/class_1", "field_1", "Lnet/minecraft/class_1;"),
+			Some("parent")
+		);
+		assert_eq!(
+			mappings.map_method("net/minecraft/class_1", "method_1", "(Lnet/minecraft/class_1;I)V"),
+			Some("place\ttab")
+		);
+
+		let official = Mappings::from_tiny_v2(text, "official", "intermediary").unwrap();
+		assert_eq!(official.map_method("a", "c", "(La;I)V"), Some("method_1"));
+
+		assert!(matches!(
+			Mappings::from_tiny_v2(text, "official", "mojang"),
+			Err(MappingError::UnknownNamespace(_))
+		));
+		assert!(matches!(
+			Mappings::from_tiny_v2("v1\tofficial\tnamed\n", "official", "named"),
+			Err(MappingError::NotTinyV2)
+		));
+	}
+
+	#[test]
+	fn remap_class() {
+		let mut mappings = Mappings::new();
+		mappings.add_class("a/Hello", "x/Greeter");
+		mappings.add_class("a/Hello$HelloAnno", "x/Greeter$Tag");
+		mappings.add_method("a/Hello$HelloAnno", "value", "()Ljava/lang/String;", "text");
+		mappings.add_method("a/Hello", "stackmapper", "(ILjava/lang/Object;)V", "mapper");
+		mappings.add_field("a/Hello", "MESSAGE", "Ljava/lang/String;", "GREETING");
+
+		let mut hello = class(HELLO);
+		hello.remap(&mappings).unwrap();
+		hello.compact_constant_pool().unwrap();
+
+		let mut written = Vec::new();
+		hello.write(&mut written).unwrap();
+		let class = class(&written);
+		assert_eq!(class.class_name(), "x/Greeter");
+		assert!(class.find_field("GREETING").is_some());
+
+		// getstatic/ldc of MESSAGE went through the ref, the declaration through its own name
+		let refs_message = class.cp.iter().any(
+			|tag| matches!(tag, IRCpTag::FieldRef { name_and_ty, .. } if name_and_ty.name.data.as_str() == "MESSAGE"),
+		);
+		assert!(!refs_message);
+
+		let mapper = class.find_method("mapper", "(ILjava/lang/Object;)V").unwrap();
+		let IRAttribute::RuntimeVisibleAnnotations { annotations } =
+			find_attr(&mapper.attributes, "RuntimeVisibleAnnotations")
+		else {
+			unreachable!();
+		};
+		let RuntimeAnnotationValue::Annotation(nested) = &annotations[0].pairs[0].value else {
+			panic!("expected a nested annotation");
+		};
+		assert_eq!(nested.ty.data.as_str(), "Lx/Greeter$Tag;");
+		assert_eq!(nested.pairs[0].name.data.as_str(), "text");
+
+		let IRAttribute::InnerClasses(InnerClassesAttribute { classes: inner }) =
+			find_attr(&class.attributes, "InnerClasses")
+		else {
+			unreachable!();
+		};
+		let tag = inner
+			.iter()
+			.find(|class| class.inner_class_info.data.data.as_str() == "x/Greeter$Tag")
+			.unwrap();
+		assert_eq!(tag.inner_name.as_ref().unwrap().data.as_str(), "Tag");
+		assert!(inner
+			.iter()
+			.any(|class| class.inner_class_info.data.data.as_str() == "x/Greeter$HelloInterface"));
+
+		// nothing refers to the old names anymore
+		let stale = class.cp.iter().any(|tag| match tag {
+			IRCpTag::Utf8(data) => data.contains("a/Hello"),
+			_ => false,
+		});
+		assert!(!stale);
+	}
+}
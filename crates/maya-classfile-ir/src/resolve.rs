@@ -0,0 +1,396 @@
+//! Finding classes by name and answering questions about their hierarchy, see [`ClassResolver`]
+//! and [`ClassHierarchy`].
+
+use std::{
+	collections::{BTreeSet, HashMap, HashSet},
+	path::PathBuf,
+};
+
+use maya_classfile_io::{IOClassFile, IOClassfileError};
+use thiserror::Error;
+
+use crate::{access::ClassAccessFlags, class_pool::IRClassfileError, IRClassFile};
+
+const OBJECT: &str = "java/lang/Object";
+
+#[derive(Debug, Error)]
+pub enum ResolveError {
+	#[error("IO Error: {0}")]
+	IO(#[from] std::io::Error),
+	#[error("{name}: {source}")]
+	Classfile { name: String, source: IOClassfileError },
+	#[error("{name}: {source}")]
+	IR { name: String, source: IRClassfileError },
+	/// A provider specific failure, like a corrupt jar.
+	#[error("{0}")]
+	Provider(Box<dyn std::error::Error + Send + Sync>),
+	#[error("class {0} not found")]
+	NotFound(String),
+	#[error("class {0} is its own super type")]
+	Circular(String),
+}
+
+/// Somewhere classes can be loaded from, like a directory, a jar or the JDK.
+pub trait ClassResolver {
+	/// The class file for an internal name like `java/lang/Object`, `None` if this resolver
+	/// doesn't have it.
+	fn read_class(&mut self, name: &str) -> Result<Option<Vec<u8>>, ResolveError>;
+
+	fn load(&mut self, name: &str) -> Result<Option<IRClassFile>, ResolveError> {
+		let Some(bytes) = self.read_class(name)? else {
+			return Ok(None);
+		};
+		let class = IOClassFile::read_from_slice(&bytes).map_err(|source| ResolveError::Classfile {
+			name: name.to_string(),
+			source,
+		})?;
+		IRClassFile::from_io(class)
+			.map(Some)
+			.map_err(|source| ResolveError::IR {
+				name: name.to_string(),
+				source,
+			})
+	}
+}
+
+impl<R: ClassResolver + ?Sized> ClassResolver for Box<R> {
+	fn read_class(&mut self, name: &str) -> Result<Option<Vec<u8>>, ResolveError> {
+		(**self).read_class(name)
+	}
+}
+
+impl<R: ClassResolver + ?Sized> ClassResolver for &mut R {
+	fn read_class(&mut self, name: &str) -> Result<Option<Vec<u8>>, ResolveError> {
+		(**self).read_class(name)
+	}
+}
+
+/// Loose class files under a root directory, laid out by package like `javac -d` writes them.
+#[derive(Debug, Clone)]
+pub struct DirectoryResolver {
+	root: PathBuf,
+}
+
+impl DirectoryResolver {
+	pub fn new(root: impl Into<PathBuf>) -> Self {
+		Self { root: root.into() }
+	}
+}
+
+impl ClassResolver for DirectoryResolver {
+	fn read_class(&mut self, name: &str) -> Result<Option<Vec<u8>>, ResolveError> {
+		match std::fs::read(self.root.join(format!("{name}.class"))) {
+			Ok(bytes) => Ok(Some(bytes)),
+			Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+			Err(e) => Err(e.into()),
+		}
+	}
+}
+
+/// Class files held in memory, keyed by internal name.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryResolver {
+	classes: HashMap<String, Vec<u8>>,
+}
+
+impl MemoryResolver {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	pub fn insert(&mut self, name: impl Into<String>, bytes: Vec<u8>) {
+		self.classes.insert(name.into(), bytes);
+	}
+
+	/// Writes `class` out and stores it under its own name.
+	pub fn insert_class(&mut self, class: &IRClassFile) -> Result<(), IRClassfileError> {
+		let mut bytes = Vec::new();
+		class.write(&mut bytes)?;
+		self.insert(class.class_name(), bytes);
+		Ok(())
+	}
+}
+
+impl ClassResolver for MemoryResolver {
+	fn read_class(&mut self, name: &str) -> Result<Option<Vec<u8>>, ResolveError> {
+		Ok(self.classes.get(name).cloned())
+	}
+}
+
+/// Several resolvers searched in order, the first one that has a class wins.
+#[derive(Default)]
+pub struct ClassPath {
+	entries: Vec<Box<dyn ClassResolver>>,
+}
+
+impl ClassPath {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	pub fn push(&mut self, resolver: impl ClassResolver + 'static) {
+		self.entries.push(Box::new(resolver));
+	}
+
+	pub fn len(&self) -> usize {
+		self.entries.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.entries.is_empty()
+	}
+}
+
+impl ClassResolver for ClassPath {
+	fn read_class(&mut self, name: &str) -> Result<Option<Vec<u8>>, ResolveError> {
+		for entry in &mut self.entries {
+			if let Some(bytes) = entry.read_class(name)? {
+				return Ok(Some(bytes));
+			}
+		}
+		Ok(None)
+	}
+}
+
+/// What [`ClassHierarchy`] keeps of a class.
+#[derive(Debug, Clone)]
+pub struct ClassInfo {
+	pub access_flags: ClassAccessFlags,
+	pub super_name: Option<String>,
+	pub interfaces: Vec<String>,
+}
+
+impl ClassInfo {
+	pub fn is_interface(&self) -> bool {
+		self.access_flags.contains(ClassAccessFlags::INTERFACE)
+	}
+}
+
+impl From<&IRClassFile> for ClassInfo {
+	fn from(class: &IRClassFile) -> Self {
+		Self {
+			access_flags: class.access_flags,
+			super_name: class.super_name().map(str::to_string),
+			interfaces: class.interface_names().map(str::to_string).collect(),
+		}
+	}
+}
+
+/// Subtyping questions over classes loaded on demand from a [`ClassResolver`]. Classes are
+/// named by internal name, arrays by descriptor (`[Ljava/lang/String;`).
+///
+/// `java/lang/Object` is known without loading it, anything else missing from the resolver is a
+/// [`ResolveError::NotFound`].
+pub struct ClassHierarchy<R> {
+	resolver: R,
+	classes: HashMap<String, ClassInfo>,
+}
+
+impl<R: ClassResolver> ClassHierarchy<R> {
+	pub fn new(resolver: R) -> Self {
+		Self {
+			resolver,
+			classes: HashMap::new(),
+		}
+	}
+
+	pub fn resolver(&mut self) -> &mut R {
+		&mut self.resolver
+	}
+
+	/// Registers a class directly, shadowing whatever the resolver has under that name. Useful for
+	/// classes that are being generated.
+	pub fn add_class(&mut self, class: &IRClassFile) {
+		self.classes.insert(class.class_name().to_string(), class.into());
+	}
+
+	pub fn class(&mut self, name: &str) -> Result<&ClassInfo, ResolveError> {
+		if !self.classes.contains_key(name) {
+			let info = match self.resolver.load(name)? {
+				Some(class) => ClassInfo::from(&class),
+				None if name == OBJECT => ClassInfo {
+					access_flags: ClassAccessFlags::PUBLIC | ClassAccessFlags::SUPER,
+					super_name: None,
+					interfaces: Vec::new(),
+				},
+				None => return Err(ResolveError::NotFound(name.to_string())),
+			};
+			self.classes.insert(name.to_string(), info);
+		}
+		Ok(&self.classes[name])
+	}
+
+	/// The superclasses of `name`, nearest first, ending with `java/lang/Object`.
+	pub fn super_classes(&mut self, name: &str) -> Result<Vec<String>, ResolveError> {
+		let mut supers = Vec::new();
+		let mut current = self.class(name)?.super_name.clone();
+		while let Some(name) = current {
+			if supers.contains(&name) {
+				return Err(ResolveError::Circular(name));
+			}
+			current = self.class(&name)?.super_name.clone();
+			supers.push(name);
+		}
+		Ok(supers)
+	}
+
+	/// Every interface `name` implements, directly or through its superclasses and
+	/// superinterfaces.
+	pub fn interfaces(&mut self, name: &str) -> Result<BTreeSet<String>, ResolveError> {
+		let mut pending = self.class(name)?.interfaces.clone();
+		for class in self.super_classes(name)? {
+			pending.extend(self.class(&class)?.interfaces.iter().cloned());
+		}
+
+		let mut interfaces = BTreeSet::new();
+		while let Some(interface) = pending.pop() {
+			if interfaces.insert(interface.clone()) {
+				pending.extend(self.class(&interface)?.interfaces.iter().cloned());
+			}
+		}
+		Ok(interfaces)
+	}
+
+	/// Whether a value of type `source` can be stored in a variable of type `target`, like Java's
+	/// `Class::isAssignableFrom`.
+	pub fn is_assignable_from(&mut self, target: &str, source: &str) -> Result<bool, ResolveError> {
+		if target == source || target == OBJECT {
+			return Ok(true);
+		}
+		if let Some(source) = source.strip_prefix('[') {
+			return match target.strip_prefix('[') {
+				Some(target) => match (element_class(target), element_class(source)) {
+					(Some(target), Some(source)) => self.is_assignable_from(target, source),
+					_ => Ok(false),
+				},
+				None => Ok(matches!(target, "java/lang/Cloneable" | "java/io/Serializable")),
+			};
+		}
+		if target.starts_with('[') {
+			return Ok(false);
+		}
+
+		if self.class(target)?.is_interface() {
+			return Ok(self.interfaces(source)?.contains(target));
+		}
+
+		let mut seen = HashSet::new();
+		let mut current = self.class(source)?.super_name.clone();
+		while let Some(name) = current {
+			if name == target {
+				return Ok(true);
+			}
+			if !seen.insert(name.clone()) {
+				return Err(ResolveError::Circular(name));
+			}
+			current = self.class(&name)?.super_name.clone();
+		}
+		Ok(false)
+	}
+
+	/// The most specific class both `a` and `b` extend, `java/lang/Object` when either is an
+	/// interface. This is what the verifier merges two reference types to.
+	pub fn common_super_class(&mut self, a: &str, b: &str) -> Result<String, ResolveError> {
+		if self.is_assignable_from(a, b)? {
+			return Ok(a.to_string());
+		}
+		if self.is_assignable_from(b, a)? {
+			return Ok(b.to_string());
+		}
+		if a.starts_with('[') || b.starts_with('[') || self.class(a)?.is_interface() || self.class(b)?.is_interface() {
+			return Ok(OBJECT.to_string());
+		}
+
+		for class in self.super_classes(a)? {
+			if self.is_assignable_from(&class, b)? {
+				return Ok(class);
+			}
+		}
+		Ok(OBJECT.to_string())
+	}
+}
+
+/// The class an array element descriptor names, `None` for primitives.
+fn element_class(descriptor: &str) -> Option<&str> {
+	if descriptor.starts_with('[') {
+		Some(descriptor)
+	} else {
+		descriptor.strip_prefix('L')?.strip_suffix(';')
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		remap::Mappings,
+		test_util::{class, HELLO_INTERFACE},
+	};
+
+	const OUT: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../maya-test-bin/data/out/a");
+
+	fn hierarchy() -> ClassHierarchy<ClassPath> {
+		// HelloAnno extends Annotation, stand in an empty interface for it
+		let mut annotation = class(HELLO_INTERFACE);
+		let mut mappings = Mappings::new();
+		mappings.add_class("a/Hello$HelloInterface", "java/lang/annotation/Annotation");
+		annotation.remap(&mappings).unwrap();
+
+		let mut memory = MemoryResolver::new();
+		memory.insert_class(&annotation).unwrap();
+		let mut path = ClassPath::new();
+		path.push(DirectoryResolver::new(OUT));
+		path.push(memory);
+		ClassHierarchy::new(path)
+	}
+
+	#[test]
+	fn resolvers() {
+		let mut path = hierarchy().resolver;
+		assert_eq!(path.len(), 2);
+		assert_eq!(path.load("a/Hello").unwrap().unwrap().class_name(), "a/Hello");
+		assert!(path.read_class("a/Missing").unwrap().is_none());
+		assert!(path.read_class("java/lang/annotation/Annotation").unwrap().is_some());
+	}
+
+	#[test]
+	fn assignability() {
+		let mut hierarchy = hierarchy();
+		assert_eq!(
+			hierarchy.super_classes("a/Hello$Circle").unwrap(),
+			["a/Hello$Shape", OBJECT]
+		);
+		assert!(hierarchy.is_assignable_from("a/Hello$Shape", "a/Hello$Circle").unwrap());
+		assert!(!hierarchy.is_assignable_from("a/Hello$Circle", "a/Hello$Shape").unwrap());
+		assert!(hierarchy.is_assignable_from(OBJECT, "a/Hello$Circle").unwrap());
+		assert!(hierarchy
+			.is_assignable_from("a/Hello$HelloInterface", "a/Hello$InnerHello")
+			.unwrap());
+		assert!(!hierarchy
+			.is_assignable_from("a/Hello$HelloInterface", "a/Hello")
+			.unwrap());
+
+		assert!(hierarchy
+			.is_assignable_from("[La/Hello$Shape;", "[La/Hello$Circle;")
+			.unwrap());
+		assert!(!hierarchy.is_assignable_from("[I", "[J").unwrap());
+		assert!(hierarchy.is_assignable_from("java/lang/Cloneable", "[I").unwrap());
+
+		assert_eq!(
+			hierarchy.interfaces("a/Hello$HelloAnno").unwrap(),
+			BTreeSet::from(["java/lang/annotation/Annotation".to_string()])
+		);
+		assert_eq!(
+			hierarchy.common_super_class("a/Hello$Circle", "a/Hello$Shape").unwrap(),
+			"a/Hello$Shape"
+		);
+		assert_eq!(
+			hierarchy.common_super_class("a/Hello$Circle", "a/Hello").unwrap(),
+			OBJECT
+		);
+		assert!(matches!(
+			hierarchy.class("a/Missing"),
+			Err(ResolveError::NotFound(name)) if name == "a/Missing"
+		));
+	}
+}
@@ -0,0 +1,131 @@
+//! Sealed classes, whose PermittedSubclasses attribute lists the only classes allowed to extend
+//! them.
+
+use crate::{
+	attribute::{IRAttribute, IRAttributeInfo},
+	class_pool::{CPClassRef, IRClassfileError},
+	IRClassFile,
+};
+
+impl IRClassFile {
+	/// Classes listed in PermittedSubclasses, `None` if the class isn't sealed.
+	pub fn permitted_subclasses(&self) -> Option<&[CPClassRef]> {
+		self.attributes.iter().find_map(|attr| match &attr.attr {
+			IRAttribute::PermittedSubclasses { classes } => Some(classes.as_slice()),
+			_ => None,
+		})
+	}
+
+	pub fn is_sealed(&self) -> bool {
+		self.permitted_subclasses().is_some()
+	}
+
+	pub fn permits(&self, name: &str) -> bool {
+		self.permitted_subclasses()
+			.is_some_and(|classes| classes.iter().any(|class| class.data.data.as_str() == name))
+	}
+
+	/// Adds `name` to the permitted subclasses, sealing the class if it wasn't already.
+	pub fn permit(&mut self, name: &str) -> Result<(), IRClassfileError> {
+		if self.permits(name) {
+			return Ok(());
+		}
+
+		let class = self.intern_class(name)?;
+		match self
+			.attributes
+			.iter_mut()
+			.find(|attr| matches!(attr.attr, IRAttribute::PermittedSubclasses { .. }))
+		{
+			Some(IRAttributeInfo {
+				length,
+				attr: IRAttribute::PermittedSubclasses { classes },
+				..
+			}) => {
+				classes.push(class);
+				*length += 2;
+			}
+			_ => {
+				let name = self.intern_utf8("PermittedSubclasses")?;
+				self.attributes.push(IRAttributeInfo {
+					name,
+					length: 4,
+					attr: IRAttribute::PermittedSubclasses { classes: vec![class] },
+				});
+			}
+		}
+		Ok(())
+	}
+
+	/// Removes `name` from the permitted subclasses. Removing the last one drops the attribute, so the
+	/// class is no longer sealed. Returns whether `name` was permitted.
+	pub fn unpermit(&mut self, name: &str) -> bool {
+		let Some(position) = self
+			.attributes
+			.iter()
+			.position(|attr| matches!(attr.attr, IRAttribute::PermittedSubclasses { .. }))
+		else {
+			return false;
+		};
+
+		let IRAttributeInfo {
+			length,
+			attr: IRAttribute::PermittedSubclasses { classes },
+			..
+		} = &mut self.attributes[position]
+		else {
+			unreachable!()
+		};
+		let before = classes.len();
+		classes.retain(|class| class.data.data.as_str() != name);
+		*length = 2 + 2 * classes.len() as u32;
+		let removed = classes.len() != before;
+		if classes.is_empty() {
+			self.attributes.remove(position);
+		}
+		removed
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use maya_classfile_io::IOClassFile;
+
+	use super::*;
+	use crate::test_util::{class, HELLO, SHAPE};
+
+	#[test]
+	fn permitted_subclasses() {
+		// sealed class Shape permits Circle
+		let mut shape = class(SHAPE);
+		assert!(shape.is_sealed());
+		assert!(shape.permits("a/Hello$Circle"));
+
+		let cp_len = shape.cp.len();
+		shape.permit("a/Hello$Square").unwrap();
+		shape.permit("a/Hello$Square").unwrap();
+		assert_eq!(shape.cp.len(), cp_len + 2);
+		let names: Vec<_> = shape
+			.permitted_subclasses()
+			.unwrap()
+			.iter()
+			.map(|class| class.data.data.as_str())
+			.collect();
+		assert_eq!(names, ["a/Hello$Circle", "a/Hello$Square"]);
+
+		let mut bytes = Vec::new();
+		shape.write(&mut bytes).unwrap();
+		let reread = IRClassFile::from_io(IOClassFile::read_from_slice(&bytes).unwrap()).unwrap();
+		assert!(reread.permits("a/Hello$Square"));
+
+		assert!(shape.unpermit("a/Hello$Circle"));
+		assert!(!shape.unpermit("a/Hello$Circle"));
+		assert!(shape.unpermit("a/Hello$Square"));
+		assert!(!shape.is_sealed());
+
+		let mut hello = class(HELLO);
+		hello.permit("a/Sub").unwrap();
+		assert!(hello.permits("a/Sub"));
+		hello.write(&mut Vec::new()).unwrap();
+	}
+}
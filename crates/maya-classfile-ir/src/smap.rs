@@ -0,0 +1,444 @@
+//! Source maps from the `SourceDebugExtension` attribute (JSR-045), which map the lines of a
+//! generated class back to the Kotlin, JSP, ... sources it came from. See [`Smap`].
+
+use std::fmt;
+
+use thiserror::Error;
+
+use crate::{
+	attribute::{IRAttribute, IRAttributeInfo},
+	class_pool::{IRClassfileError, Shared},
+	intern_utf8, IRClassFile,
+};
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum SmapError {
+	#[error("not an SMAP")]
+	NotSmap,
+	#[error("line {line}: {message}")]
+	Syntax { line: usize, message: String },
+	#[error("line {0}: embedded SMAPs aren't supported")]
+	Embedded(usize),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SmapFile {
+	pub id: u32,
+	pub name: String,
+	/// Path relative to the source root, when the SMAP records one.
+	pub path: Option<String>,
+}
+
+/// `repeat` input lines starting at `input_start`, each covering `output_increment` output lines
+/// starting at `output_start`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LineInfo {
+	pub input_start: u32,
+	pub file_id: u32,
+	pub repeat: u32,
+	pub output_start: u32,
+	pub output_increment: u32,
+}
+
+impl LineInfo {
+	/// The input line `output_line` maps to, if this entry covers it.
+	pub fn map(&self, output_line: u32) -> Option<u32> {
+		if self.output_increment == 0 {
+			return (output_line == self.output_start).then_some(self.input_start);
+		}
+		let offset = output_line.checked_sub(self.output_start)? / self.output_increment;
+		(offset < self.repeat).then_some(self.input_start + offset)
+	}
+}
+
+/// A vendor section, kept as its raw lines.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VendorSection {
+	pub vendor: String,
+	pub lines: Vec<String>,
+}
+
+/// One view of the sources, like `Kotlin` or `JSP`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Stratum {
+	pub id: String,
+	pub files: Vec<SmapFile>,
+	pub lines: Vec<LineInfo>,
+	pub vendor: Vec<VendorSection>,
+}
+
+impl Stratum {
+	pub fn new(id: impl Into<String>) -> Self {
+		Self {
+			id: id.into(),
+			files: Vec::new(),
+			lines: Vec::new(),
+			vendor: Vec::new(),
+		}
+	}
+
+	/// Adds a source file, returning the id lines refer to it by.
+	pub fn add_file(&mut self, name: impl Into<String>, path: Option<String>) -> u32 {
+		let id = self.files.iter().map(|file| file.id).max().map_or(1, |id| id + 1);
+		self.files.push(SmapFile {
+			id,
+			name: name.into(),
+			path,
+		});
+		id
+	}
+
+	pub fn file(&self, id: u32) -> Option<&SmapFile> {
+		self.files.iter().find(|file| file.id == id)
+	}
+
+	/// The source file and line that `output_line` of the class was generated from.
+	pub fn map_line(&self, output_line: u32) -> Option<(&SmapFile, u32)> {
+		self.lines
+			.iter()
+			.find_map(|info| Some((self.file(info.file_id)?, info.map(output_line)?)))
+	}
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Smap {
+	/// The file the class was compiled from, like `Foo.kt`.
+	pub output_file: String,
+	pub default_stratum: String,
+	pub strata: Vec<Stratum>,
+}
+
+impl Smap {
+	pub fn new(output_file: impl Into<String>, default_stratum: impl Into<String>) -> Self {
+		Self {
+			output_file: output_file.into(),
+			default_stratum: default_stratum.into(),
+			strata: Vec::new(),
+		}
+	}
+
+	pub fn stratum(&self, id: &str) -> Option<&Stratum> {
+		self.strata.iter().find(|stratum| stratum.id == id)
+	}
+
+	/// [`Stratum::map_line`] in the default stratum.
+	pub fn map_line(&self, output_line: u32) -> Option<(&SmapFile, u32)> {
+		self.stratum(&self.default_stratum)?.map_line(output_line)
+	}
+
+	/// Parses an SMAP. The end section is optional, and a stratum can end in one too (as Kotlin
+	/// writes them).
+	pub fn parse(text: &str) -> Result<Self, SmapError> {
+		let mut lines = text.lines().map(|line| line.trim_end_matches('\r')).enumerate();
+		if lines.next().map(|(_, line)| line) != Some("SMAP") {
+			return Err(SmapError::NotSmap);
+		}
+		let mut header = || lines.next().map(|(_, line)| line.to_string()).ok_or(SmapError::NotSmap);
+		let mut smap = Smap::new(header()?, header()?);
+
+		let syntax = |line: usize, message: &str| SmapError::Syntax {
+			line: line + 1,
+			message: message.to_string(),
+		};
+		let mut section = Section::None;
+		while let Some((number, line)) = lines.next() {
+			if let Some(rest) = line.strip_prefix('*') {
+				let stratum = smap.strata.last_mut();
+				section = match (rest.split_once(' ').map_or(rest, |(tag, _)| tag), stratum) {
+					("S", _) => {
+						let id = rest[1..].trim();
+						if id.is_empty() {
+							return Err(syntax(number, "stratum without a name"));
+						}
+						smap.strata.push(Stratum::new(id));
+						Section::None
+					}
+					("F", Some(_)) => Section::Files,
+					("L", Some(_)) => Section::Lines { file_id: 0 },
+					("V", Some(stratum)) => {
+						let vendor = lines
+							.next()
+							.ok_or_else(|| syntax(number, "vendor section without a vendor"))?;
+						stratum.vendor.push(VendorSection {
+							vendor: vendor.1.to_string(),
+							lines: Vec::new(),
+						});
+						Section::Vendor
+					}
+					("E", _) => Section::None,
+					("O" | "C", _) => return Err(SmapError::Embedded(number + 1)),
+					(_, None) => return Err(syntax(number, "section outside a stratum")),
+					// unknown sections are to be ignored
+					_ => Section::Unknown,
+				};
+				continue;
+			}
+
+			let Some(stratum) = smap.strata.last_mut() else {
+				return Err(syntax(number, "line outside a stratum"));
+			};
+			match &mut section {
+				Section::Files => {
+					let (has_path, entry) = match line.strip_prefix("+ ") {
+						Some(entry) => (true, entry),
+						None => (false, line),
+					};
+					let (id, name) = entry
+						.trim()
+						.split_once(' ')
+						.ok_or_else(|| syntax(number, "expected a file id and name"))?;
+					let id = id.parse().map_err(|_| syntax(number, "invalid file id"))?;
+					let path = match has_path {
+						true => Some(
+							lines
+								.next()
+								.ok_or_else(|| syntax(number, "missing file path"))?
+								.1
+								.to_string(),
+						),
+						false => None,
+					};
+					stratum.files.push(SmapFile {
+						id,
+						name: name.trim().to_string(),
+						path,
+					});
+				}
+				Section::Lines { file_id } => {
+					let info = parse_line_info(line, file_id).ok_or_else(|| syntax(number, "invalid line info"))?;
+					stratum.lines.push(info);
+				}
+				Section::Vendor => stratum.vendor.last_mut().unwrap().lines.push(line.to_string()),
+				Section::Unknown => {}
+				Section::None => return Err(syntax(number, "line outside a section")),
+			}
+		}
+		Ok(smap)
+	}
+}
+
+enum Section {
+	None,
+	Files,
+	/// `file_id` carries over to lines that don't name one.
+	Lines {
+		file_id: u32,
+	},
+	Vendor,
+	Unknown,
+}
+
+/// `InputStartLine[#LineFileID][,RepeatCount]:OutputStartLine[,OutputLineIncrement]`
+fn parse_line_info(line: &str, file_id: &mut u32) -> Option<LineInfo> {
+	let (input, output) = line.trim().split_once(':')?;
+	let (input, repeat) = match input.split_once(',') {
+		Some((input, repeat)) => (input, repeat.parse().ok()?),
+		None => (input, 1),
+	};
+	let input_start = match input.split_once('#') {
+		Some((start, id)) => {
+			*file_id = id.parse().ok()?;
+			start.parse().ok()?
+		}
+		None => input.parse().ok()?,
+	};
+	let (output_start, output_increment) = match output.split_once(',') {
+		Some((start, increment)) => (start.parse().ok()?, increment.parse().ok()?),
+		None => (output.parse().ok()?, 1),
+	};
+	Some(LineInfo {
+		input_start,
+		file_id: *file_id,
+		repeat,
+		output_start,
+		output_increment,
+	})
+}
+
+/// Writes the SMAP out in the form [`Smap::parse`] reads.
+impl fmt::Display for Smap {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		writeln!(f, "SMAP")?;
+		writeln!(f, "{}", self.output_file)?;
+		writeln!(f, "{}", self.default_stratum)?;
+		for stratum in &self.strata {
+			writeln!(f, "*S {}", stratum.id)?;
+			writeln!(f, "*F")?;
+			for file in &stratum.files {
+				match &file.path {
+					Some(path) => writeln!(f, "+ {} {}\n{path}", file.id, file.name)?,
+					None => writeln!(f, "{} {}", file.id, file.name)?,
+				}
+			}
+			writeln!(f, "*L")?;
+			let mut file_id = None;
+			for info in &stratum.lines {
+				write!(f, "{}", info.input_start)?;
+				if file_id != Some(info.file_id) {
+					write!(f, "#{}", info.file_id)?;
+					file_id = Some(info.file_id);
+				}
+				if info.repeat != 1 {
+					write!(f, ",{}", info.repeat)?;
+				}
+				write!(f, ":{}", info.output_start)?;
+				if info.output_increment != 1 {
+					write!(f, ",{}", info.output_increment)?;
+				}
+				writeln!(f)?;
+			}
+			for vendor in &stratum.vendor {
+				writeln!(f, "*V\n{}", vendor.vendor)?;
+				for line in &vendor.lines {
+					writeln!(f, "{line}")?;
+				}
+			}
+		}
+		writeln!(f, "*E")
+	}
+}
+
+impl IRClassFile {
+	/// The contents of the `SourceDebugExtension` attribute.
+	pub fn source_debug_extension(&self) -> Option<&str> {
+		self.attributes.iter().find_map(|attribute| match &attribute.attr {
+			IRAttribute::SourceDebugExtension(debug) => Some(debug.as_str()),
+			_ => None,
+		})
+	}
+
+	/// The class's SMAP, `None` without a `SourceDebugExtension`.
+	pub fn smap(&self) -> Option<Result<Smap, SmapError>> {
+		self.source_debug_extension().map(Smap::parse)
+	}
+
+	/// Replaces the `SourceDebugExtension` attribute, adding one if the class has none.
+	pub fn set_source_debug_extension(&mut self, debug: &str) -> Result<(), IRClassfileError> {
+		let existing = self
+			.attributes
+			.iter_mut()
+			.find(|attribute| matches!(attribute.attr, IRAttribute::SourceDebugExtension(_)));
+		match existing {
+			Some(attribute) => attribute.attr = IRAttribute::SourceDebugExtension(Shared::new(debug.to_string())),
+			None => {
+				let name = intern_utf8(&mut self.cp, "SourceDebugExtension")?;
+				self.attributes.push(IRAttributeInfo {
+					name,
+					length: 0,
+					attr: IRAttribute::SourceDebugExtension(Shared::new(debug.to_string())),
+				});
+			}
+		}
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::test_util::{class, SIMPLE};
+
+	// as kotlinc writes them, with an end section after every stratum
+	const KOTLIN: &str = "SMAP
+Main.kt
+Kotlin
+*S Kotlin
+*F
++ 1 Main.kt
+MainKt
++ 2 Inline.kt
+InlineKt
+*L
+1#1,8:1
+3#2,2:9,2
+*E
+*S KotlinDebug
+*F
++ 1 Main.kt
+MainKt
+*L
+4#1:9
+*E
+";
+
+	#[test]
+	fn parse_smap() {
+		let smap = Smap::parse(KOTLIN).unwrap();
+		assert_eq!(smap.output_file, "Main.kt");
+		assert_eq!(smap.default_stratum, "Kotlin");
+		assert_eq!(smap.strata.len(), 2);
+
+		let kotlin = smap.stratum("Kotlin").unwrap();
+		assert_eq!(kotlin.files[1].path.as_deref(), Some("InlineKt"));
+		assert_eq!(
+			kotlin.lines[1],
+			LineInfo {
+				input_start: 3,
+				file_id: 2,
+				repeat: 2,
+				output_start: 9,
+				output_increment: 2,
+			}
+		);
+
+		let mapped = |line| smap.map_line(line).map(|(file, line)| (file.name.as_str(), line));
+		assert_eq!(mapped(5), Some(("Main.kt", 5)));
+		assert_eq!(mapped(10), Some(("Inline.kt", 3)));
+		assert_eq!(mapped(11), Some(("Inline.kt", 4)));
+		assert_eq!(mapped(13), None);
+
+		let debug = smap.stratum("KotlinDebug").unwrap();
+		assert_eq!(debug.map_line(9).unwrap().1, 4);
+
+		assert_eq!(Smap::parse("SMAP\nA.kt"), Err(SmapError::NotSmap));
+		assert_eq!(
+			Smap::parse("SMAP\nA.kt\nKotlin\n*S Kotlin\n*L\nx:1\n"),
+			Err(SmapError::Syntax {
+				line: 6,
+				message: "invalid line info".to_string(),
+			})
+		);
+		assert_eq!(
+			Smap::parse("SMAP\nA.kt\nKotlin\n*O Kotlin\n"),
+			Err(SmapError::Embedded(4))
+		);
+	}
+
+	#[test]
+	fn generate_smap() {
+		let mut smap = Smap::new("index.jsp", "JSP");
+		let mut jsp = Stratum::new("JSP");
+		let index = jsp.add_file("index.jsp", None);
+		let header = jsp.add_file("header.jsp", Some("include/header.jsp".to_string()));
+		for (input_start, file_id, output_start) in [(1, index, 10), (2, index, 12), (1, header, 20)] {
+			jsp.lines.push(LineInfo {
+				input_start,
+				file_id,
+				repeat: 1,
+				output_start,
+				output_increment: 1,
+			});
+		}
+		jsp.vendor.push(VendorSection {
+			vendor: "org.example".to_string(),
+			lines: vec!["anything".to_string()],
+		});
+		smap.strata.push(jsp);
+
+		let text = smap.to_string();
+		assert_eq!(
+			text,
+			"SMAP\nindex.jsp\nJSP\n*S JSP\n*F\n1 index.jsp\n+ 2 header.jsp\ninclude/header.jsp\n*L\n1#1:10\n2:12\n1#2:20\n*V\norg.example\nanything\n*E\n"
+		);
+		assert_eq!(Smap::parse(&text).unwrap(), smap);
+
+		let mut simple = class(SIMPLE);
+		assert!(simple.smap().is_none());
+		simple.set_source_debug_extension(&text).unwrap();
+		simple.set_source_debug_extension(&text).unwrap();
+
+		let mut written = Vec::new();
+		simple.write(&mut written).unwrap();
+		assert_eq!(class(&written).smap(), Some(Ok(smap)));
+	}
+}
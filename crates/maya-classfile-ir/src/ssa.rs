@@ -0,0 +1,1300 @@
+//! A method's code in SSA form, where instructions take and produce values instead of working on
+//! the operand stack and locals, see [`SsaMethod`].
+//!
+//! Lifting needs a frame at every place more than one path reaches, which is what a StackMapTable
+//! already has for class files from version 50 on: the values a frame describes become phis, and
+//! their verification types are what lowering writes in its own frames. Loads, stores and stack
+//! shuffling disappear, the other instructions become [`Stmt`]s, and `iinc` becomes an `iadd`.
+//!
+//! A handler's phis take the values a protected block starts with, so lifting splits protected
+//! code after every store and the locals don't change within a block. Lowering gives every value
+//! its own local, copies phi inputs on the way into a block and builds frames from which values
+//! are live. Local variable tables are dropped.
+
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use thiserror::Error;
+
+use crate::{
+	attribute::VerificationTypeInfo,
+	class_pool::{ConstantPool, IRClassfileError},
+	code::{Instructions, Opcodes},
+	infer::VerificationDomain,
+	insn::{FrameValue, Insn, InsnList, Label, TryCatchBlock},
+	interp::{execute, AnalysisError, Domain, Frame, Kind, KindDomain},
+	IRClassFile,
+};
+
+#[derive(Debug, Error)]
+pub enum SsaError {
+	#[error("instruction {index} is reached from more than one place but has no frame")]
+	MissingFrame { index: usize },
+	#[error("instruction {index} is a handler but is also branched or fallen into")]
+	BranchToHandler { index: usize },
+	#[error("{0:?} needs a verification type for a frame but has none")]
+	UnknownType(Value),
+	#[error("{0}")]
+	Analysis(#[from] AnalysisError),
+	#[error("{0}")]
+	Class(#[from] IRClassfileError),
+}
+
+/// A value, defined once by a parameter, a phi, a handler or a statement.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Value(u32);
+
+impl Value {
+	pub fn index(self) -> usize {
+		self.0 as usize
+	}
+}
+
+/// A basic block, an index into [`SsaMethod::blocks`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Block(u32);
+
+impl Block {
+	pub fn index(self) -> usize {
+		self.0 as usize
+	}
+}
+
+/// A method body in SSA form. Block 0 is the entry, where the parameters are defined, and it
+/// has nothing but a jump to the code.
+#[derive(Debug, Clone)]
+pub struct SsaMethod {
+	pub blocks: Vec<BasicBlock>,
+	/// The values of the entries of [`initial_locals`](Self::initial_locals), `this` first.
+	pub parameters: Vec<Value>,
+	pub initial_locals: Vec<VerificationTypeInfo>,
+	values: Vec<ValueInfo>,
+}
+
+#[derive(Debug, Clone)]
+struct ValueInfo {
+	kind: Kind,
+	ty: Option<VerificationTypeInfo>,
+}
+
+#[derive(Debug, Clone)]
+pub struct BasicBlock {
+	pub phis: Vec<Phi>,
+	/// What a handler catches, on the stack when it starts.
+	pub exception: Option<Value>,
+	pub stmts: Vec<Stmt>,
+	pub terminator: Terminator,
+	/// The handlers covering the block in the order they're tried, with the Class entry of what
+	/// they catch or 0 for anything. A handler gets the values the block starts with.
+	pub handlers: Vec<(u16, Block)>,
+}
+
+/// A value that's `inputs` of whichever block came before.
+#[derive(Debug, Clone)]
+pub struct Phi {
+	pub result: Value,
+	pub inputs: Vec<(Block, Value)>,
+}
+
+/// An instruction that doesn't branch, load, store or shuffle the stack, with the values it pops,
+/// bottom of the stack first, and the one it pushes.
+#[derive(Debug, Clone)]
+pub struct Stmt {
+	pub result: Option<Value>,
+	pub instruction: Instructions,
+	pub args: Vec<Value>,
+	/// The source line, if the code had line numbers.
+	pub line: Option<u16>,
+}
+
+#[derive(Debug, Clone)]
+pub enum Terminator {
+	Goto(Block),
+	/// One of the `if`s, jumping to `target` or going on to `next`.
+	If {
+		opcode: u8,
+		args: Vec<Value>,
+		target: Block,
+		next: Block,
+	},
+	TableSwitch {
+		key: Value,
+		low: i32,
+		targets: Vec<Block>,
+		default: Block,
+	},
+	LookupSwitch {
+		key: Value,
+		pairs: Vec<(i32, Block)>,
+		default: Block,
+	},
+	/// Returns the value, or nothing from a `void` method.
+	Return(Option<Value>),
+	Throw(Value),
+}
+
+impl Terminator {
+	/// The blocks it can go on to, each once.
+	pub fn successors(&self) -> Vec<Block> {
+		let mut successors = match self {
+			Self::Goto(target) => vec![*target],
+			Self::If { target, next, .. } => vec![*target, *next],
+			Self::TableSwitch { targets, default, .. } => targets.iter().chain([default]).copied().collect(),
+			Self::LookupSwitch { pairs, default, .. } => {
+				pairs.iter().map(|(_, target)| *target).chain([*default]).collect()
+			}
+			Self::Return(_) | Self::Throw(_) => Vec::new(),
+		};
+		let mut seen = HashSet::new();
+		successors.retain(|block| seen.insert(*block));
+		successors
+	}
+
+	/// The values it uses.
+	pub fn args(&self) -> Vec<Value> {
+		match self {
+			Self::If { args, .. } => args.clone(),
+			Self::TableSwitch { key, .. } | Self::LookupSwitch { key, .. } | Self::Throw(key) => vec![*key],
+			Self::Return(value) => value.iter().copied().collect(),
+			Self::Goto(_) => Vec::new(),
+		}
+	}
+
+	fn blocks_mut(&mut self) -> Vec<&mut Block> {
+		match self {
+			Self::Goto(target) => vec![target],
+			Self::If { target, next, .. } => vec![target, next],
+			Self::TableSwitch { targets, default, .. } => targets.iter_mut().chain([default]).collect(),
+			Self::LookupSwitch { pairs, default, .. } => {
+				pairs.iter_mut().map(|(_, target)| target).chain([default]).collect()
+			}
+			Self::Return(_) | Self::Throw(_) => Vec::new(),
+		}
+	}
+
+	fn args_mut(&mut self) -> Vec<&mut Value> {
+		match self {
+			Self::If { args, .. } => args.iter_mut().collect(),
+			Self::TableSwitch { key, .. } | Self::LookupSwitch { key, .. } | Self::Throw(key) => vec![key],
+			Self::Return(value) => value.iter_mut().collect(),
+			Self::Goto(_) => Vec::new(),
+		}
+	}
+}
+
+impl SsaMethod {
+	pub fn kind(&self, value: Value) -> Kind {
+		self.values[value.index()].kind
+	}
+
+	/// The verification type of `value`, `None` if it isn't known. Objects made by `new` have the
+	/// type they get once they're initialized.
+	pub fn ty(&self, value: Value) -> Option<&VerificationTypeInfo> {
+		self.values[value.index()].ty.as_ref()
+	}
+
+	/// A value for a new phi or statement to define.
+	pub fn new_value(&mut self, kind: Kind, ty: Option<VerificationTypeInfo>) -> Value {
+		self.values.push(ValueInfo { kind, ty });
+		Value(self.values.len() as u32 - 1)
+	}
+
+	/// The blocks each block comes after, through its terminator or as a handler.
+	pub fn predecessors(&self) -> Vec<Vec<Block>> {
+		let mut predecessors = vec![Vec::new(); self.blocks.len()];
+		for (i, block) in self.blocks.iter().enumerate() {
+			let handlers = block.handlers.iter().map(|(_, handler)| *handler);
+			for successor in block.terminator.successors().into_iter().chain(handlers) {
+				let predecessors: &mut Vec<Block> = &mut predecessors[successor.index()];
+				if !predecessors.contains(&Block(i as u32)) {
+					predecessors.push(Block(i as u32));
+				}
+			}
+		}
+		predecessors
+	}
+
+	/// Lifts `insns`, interning the classes of the values' types in `cp`.
+	pub fn lift(insns: &InsnList, cp: &mut ConstantPool) -> Result<Self, SsaError> {
+		let mut labels = HashMap::new();
+		for (i, insn) in insns.insns.iter().enumerate() {
+			if let Insn::Label(label) = insn {
+				labels.insert(*label, i);
+			}
+		}
+		let index = |label: &Label| {
+			labels
+				.get(label)
+				.copied()
+				.ok_or(IRClassfileError::UnplacedLabel(*label))
+		};
+
+		let mut ranges = Vec::with_capacity(insns.try_catch_blocks.len());
+		let mut leaders = BTreeSet::from([0]);
+		for block in &insns.try_catch_blocks {
+			let (start, end, handler) = (index(&block.start)?, index(&block.end)?, index(&block.handler)?);
+			leaders.extend([start, end, handler]);
+			ranges.push((start..end, handler, block.catch_type));
+		}
+		let mut lines = Vec::with_capacity(insns.insns.len());
+		let mut line = None;
+		for (i, insn) in insns.insns.iter().enumerate() {
+			match insn {
+				Insn::Line(number) => line = Some(*number),
+				Insn::Jump { target, .. } => {
+					leaders.extend([index(target)?, i + 1]);
+				}
+				Insn::TableSwitch { default, targets, .. } => {
+					for target in targets.iter().chain([default]) {
+						leaders.insert(index(target)?);
+					}
+					leaders.insert(i + 1);
+				}
+				Insn::LookupSwitch { default, pairs } => {
+					for target in pairs.iter().map(|(_, target)| target).chain([default]) {
+						leaders.insert(index(target)?);
+					}
+					leaders.insert(i + 1);
+				}
+				Insn::Op(instruction) if ends_block(instruction) => {
+					leaders.insert(i + 1);
+				}
+				// a handler gets the locals its blocks start with, which a store changes
+				Insn::Op(instruction)
+					if changes_local(instruction) && ranges.iter().any(|(range, ..)| range.contains(&i)) =>
+				{
+					leaders.insert(i + 1);
+				}
+				_ => {}
+			}
+			lines.push(line);
+		}
+		leaders.retain(|leader| *leader < insns.insns.len());
+		let starts = leaders.into_iter().collect::<Vec<_>>();
+		// block 0 is the entry, the code's blocks come after it in order
+		let block_at = |i: usize| Block(starts.binary_search(&i).unwrap() as u32 + 1);
+		let first = |block: Block| starts[block.index() - 1];
+		let last = |block: Block| starts.get(block.index()).copied().unwrap_or(insns.insns.len());
+		let count = starts.len() + 1;
+		let is_handler = |block: Block| ranges.iter().any(|(_, handler, _)| block_at(*handler) == block);
+		let frame_at = |block: Block| {
+			insns.insns[first(block)..last(block)]
+				.iter()
+				.take_while(|insn| matches!(insn, Insn::Label(_) | Insn::Line(_) | Insn::Frame { .. }))
+				.find_map(|insn| match insn {
+					Insn::Frame { locals, stack } => Some((locals, stack)),
+					_ => None,
+				})
+		};
+
+		let mut method = Self {
+			blocks: Vec::with_capacity(count),
+			parameters: Vec::with_capacity(insns.initial_locals.len()),
+			initial_locals: insns.initial_locals.clone(),
+			values: Vec::new(),
+		};
+		let mut entry = Frame::empty();
+		for ty in &insns.initial_locals {
+			let value = method.new_value(KindDomain.parameter(ty), Some(ty.clone()));
+			let slot = entry.locals.len() as u16;
+			entry.store(slot, value, is_wide(ty));
+			method.parameters.push(value);
+		}
+		if starts.is_empty() {
+			return Err(AnalysisError::FallsOffEnd { index: 0 }.into());
+		}
+
+		let mut blocks = vec![None; count];
+		let mut entries = vec![None; count];
+		let mut exits = vec![None; count];
+		let mut sources = HashMap::new();
+		blocks[0] = Some(BasicBlock {
+			phis: Vec::new(),
+			exception: None,
+			stmts: Vec::new(),
+			terminator: Terminator::Goto(Block(1)),
+			handlers: Vec::new(),
+		});
+		entries[0] = Some(entry.clone());
+		exits[0] = Some(entry.clone());
+		entries[1] = Some(entry);
+
+		let mut types = VerificationDomain::new(cp, &insns.insns);
+		let mut pending = vec![Block(1)];
+		while let Some(block) = pending.pop() {
+			if blocks[block.index()].is_some() {
+				continue;
+			}
+			let (start, end) = (first(block), last(block));
+			let handlers = ranges
+				.iter()
+				.filter(|(range, ..)| range.contains(&start))
+				.map(|(_, handler, catch_type)| (*catch_type, block_at(*handler)))
+				.collect::<Vec<_>>();
+
+			// a frame at the start gives every value it describes a phi
+			let mut phis = Vec::new();
+			let mut exception = None;
+			let mut frame = match frame_at(block) {
+				Some((locals, stack)) => {
+					let mut frame = Frame::empty();
+					let mut slot = 0;
+					for local in locals {
+						let wide = matches!(local, FrameValue::Type(ty) if is_wide(ty));
+						if *local != FrameValue::Type(VerificationTypeInfo::TopVariableInfo) {
+							let value = method.frame_value(local);
+							frame.store(slot, value, wide);
+							sources.insert(value, Source::Local(slot));
+							phis.push(Phi {
+								result: value,
+								inputs: Vec::new(),
+							});
+						}
+						slot += 1 + wide as u16;
+					}
+					for (i, entry) in stack.iter().enumerate() {
+						let value = method.frame_value(entry);
+						frame.push(value, matches!(entry, FrameValue::Type(ty) if is_wide(ty)));
+						match is_handler(block) {
+							true => exception = Some(value),
+							false => {
+								sources.insert(value, Source::Stack(i));
+								phis.push(Phi {
+									result: value,
+									inputs: Vec::new(),
+								});
+							}
+						}
+					}
+					frame
+				}
+				None if is_handler(block) => return Err(SsaError::MissingFrame { index: start }),
+				None => entries[block.index()].clone().unwrap(),
+			};
+			entries[block.index()] = Some(frame.clone());
+
+			let mut lift = Lift {
+				values: &mut method.values,
+				types: &mut types,
+				stmts: Vec::new(),
+				line: None,
+			};
+			let mut terminator = None;
+			for (i, (insn, line)) in insns.insns.iter().zip(&lines).enumerate().take(end).skip(start) {
+				lift.line = *line;
+				terminator = match insn {
+					Insn::Label(_) | Insn::Line(_) | Insn::Frame { .. } => continue,
+					Insn::Op(instruction) if ends_block(instruction) => {
+						let (pops, _) = instruction.stack_effect();
+						let mut args = frame.pop_values(pops, i)?;
+						Some(match instruction {
+							Instructions::ATHROW => Terminator::Throw(args.pop().unwrap()),
+							Instructions::RET(_) => return Err(AnalysisError::Subroutine { index: i }.into()),
+							_ => Terminator::Return(args.pop()),
+						})
+					}
+					Insn::Op(instruction) => {
+						execute(&mut lift, i, instruction, &mut frame)?;
+						continue;
+					}
+					Insn::Jump { opcode, target } => match *opcode {
+						Opcodes::GOTO | Opcodes::GOTO_W => Some(Terminator::Goto(block_at(index(target)?))),
+						Opcodes::JSR | Opcodes::JSR_W => return Err(AnalysisError::Subroutine { index: i }.into()),
+						opcode => {
+							let pops = match opcode {
+								Opcodes::IF_ICMPEQ..=Opcodes::IF_ACMPNE => 2,
+								_ => 1,
+							};
+							if i + 1 == insns.insns.len() {
+								return Err(AnalysisError::FallsOffEnd { index: i }.into());
+							}
+							Some(Terminator::If {
+								opcode,
+								args: frame.pop_values(pops, i)?,
+								target: block_at(index(target)?),
+								next: block_at(i + 1),
+							})
+						}
+					},
+					Insn::TableSwitch { default, low, targets } => Some(Terminator::TableSwitch {
+						key: frame.pop_values(1, i)?[0],
+						low: *low,
+						targets: targets
+							.iter()
+							.map(|target| Ok(block_at(index(target)?)))
+							.collect::<Result<_, IRClassfileError>>()?,
+						default: block_at(index(default)?),
+					}),
+					Insn::LookupSwitch { default, pairs } => Some(Terminator::LookupSwitch {
+						key: frame.pop_values(1, i)?[0],
+						pairs: pairs
+							.iter()
+							.map(|(value, target)| Ok((*value, block_at(index(target)?))))
+							.collect::<Result<_, IRClassfileError>>()?,
+						default: block_at(index(default)?),
+					}),
+				};
+			}
+			let terminator = match terminator {
+				Some(terminator) => terminator,
+				None if end == insns.insns.len() => {
+					return Err(AnalysisError::FallsOffEnd { index: end - 1 }.into());
+				}
+				None => Terminator::Goto(block_at(end)),
+			};
+
+			for successor in terminator.successors() {
+				if is_handler(successor) {
+					return Err(SsaError::BranchToHandler {
+						index: first(successor),
+					});
+				}
+				if frame_at(successor).is_none() {
+					if entries[successor.index()].is_some() {
+						return Err(SsaError::MissingFrame {
+							index: first(successor),
+						});
+					}
+					entries[successor.index()] = Some(frame.clone());
+				}
+				pending.push(successor);
+			}
+			pending.extend(handlers.iter().map(|(_, handler)| *handler));
+			exits[block.index()] = Some(frame);
+			blocks[block.index()] = Some(BasicBlock {
+				phis,
+				exception,
+				stmts: lift.stmts,
+				terminator,
+				handlers,
+			});
+		}
+
+		// every phi takes the value its slot has where each predecessor leaves off, or where it
+		// starts for a handler
+		let mut predecessors = vec![Vec::new(); count];
+		for (i, block) in blocks.iter().enumerate() {
+			let Some(block) = block else {
+				continue;
+			};
+			for successor in block.terminator.successors() {
+				predecessors[successor.index()].push((Block(i as u32), false));
+			}
+			for (_, handler) in &block.handlers {
+				if !predecessors[handler.index()].contains(&(Block(i as u32), true)) {
+					predecessors[handler.index()].push((Block(i as u32), true));
+				}
+			}
+		}
+		for (i, block) in blocks.iter_mut().enumerate() {
+			let Some(block) = block else {
+				continue;
+			};
+			let stack = entries[i].as_ref().unwrap().stack.len();
+			for phi in &mut block.phis {
+				for (predecessor, exceptional) in &predecessors[i] {
+					let frame = match exceptional {
+						true => entries[predecessor.index()].as_ref().unwrap(),
+						false => exits[predecessor.index()].as_ref().unwrap(),
+					};
+					let index = starts[i - 1];
+					let input = match sources[&phi.result] {
+						Source::Local(slot) => frame
+							.locals
+							.get(slot as usize)
+							.copied()
+							.flatten()
+							.ok_or(AnalysisError::EmptyLocal { index, local: slot })?,
+						Source::Stack(position) => *frame
+							.stack
+							.get(position)
+							.filter(|_| frame.stack.len() == stack)
+							.ok_or(AnalysisError::StackMismatch { index })?,
+					};
+					phi.inputs.push((*predecessor, input));
+				}
+			}
+		}
+
+		// only reached blocks are kept, in the order of the code
+		let mut renumbered = vec![None; count];
+		for (i, block) in blocks.iter().enumerate() {
+			if block.is_some() {
+				renumbered[i] = Some(Block(method.blocks.len() as u32));
+				method.blocks.push(BasicBlock {
+					phis: Vec::new(),
+					exception: None,
+					stmts: Vec::new(),
+					terminator: Terminator::Return(None),
+					handlers: Vec::new(),
+				});
+			}
+		}
+		let renumber = |block: &mut Block| *block = renumbered[block.index()].unwrap();
+		for (i, block) in blocks.into_iter().enumerate() {
+			let Some(mut block) = block else {
+				continue;
+			};
+			block.terminator.blocks_mut().into_iter().for_each(renumber);
+			block.handlers.iter_mut().for_each(|(_, handler)| renumber(handler));
+			for phi in &mut block.phis {
+				phi.inputs.iter_mut().for_each(|(predecessor, _)| renumber(predecessor));
+			}
+			method.blocks[renumbered[i].unwrap().index()] = block;
+		}
+		method.remove_trivial_phis();
+		Ok(method)
+	}
+
+	/// A phi for an entry of a frame, with the type the frame gives it.
+	fn frame_value(&mut self, entry: &FrameValue) -> Value {
+		match entry {
+			FrameValue::Type(ty) => self.new_value(KindDomain.parameter(ty), Some(ty.clone())),
+			FrameValue::Uninitialized(_) => self.new_value(Kind::Reference, None),
+		}
+	}
+
+	/// Drops the phis whose inputs are all one value besides the phi itself, which is used instead.
+	fn remove_trivial_phis(&mut self) {
+		let mut replaced = HashMap::new();
+		loop {
+			let mut changed = false;
+			for block in &mut self.blocks {
+				block.phis.retain(|phi| {
+					let mut inputs = phi
+						.inputs
+						.iter()
+						.map(|(_, input)| resolve(&replaced, *input))
+						.filter(|input| *input != phi.result)
+						.collect::<Vec<_>>();
+					inputs.dedup();
+					match inputs[..] {
+						[input] => {
+							replaced.insert(phi.result, input);
+							changed = true;
+							false
+						}
+						_ => true,
+					}
+				});
+			}
+			if !changed {
+				break;
+			}
+		}
+		for block in &mut self.blocks {
+			for phi in &mut block.phis {
+				for (_, input) in &mut phi.inputs {
+					*input = resolve(&replaced, *input);
+				}
+			}
+			for stmt in &mut block.stmts {
+				for arg in &mut stmt.args {
+					*arg = resolve(&replaced, *arg);
+				}
+			}
+			for arg in block.terminator.args_mut() {
+				*arg = resolve(&replaced, *arg);
+			}
+		}
+	}
+}
+
+fn resolve(replaced: &HashMap<Value, Value>, mut value: Value) -> Value {
+	while let Some(next) = replaced.get(&value) {
+		value = *next;
+	}
+	value
+}
+
+/// Where a phi's value is in the frames of the blocks before it.
+#[derive(Debug, Clone, Copy)]
+enum Source {
+	Local(u16),
+	Stack(usize),
+}
+
+/// Returns, `athrow` and `ret`, which nothing comes after.
+fn ends_block(instruction: &Instructions) -> bool {
+	use Instructions::*;
+
+	matches!(
+		instruction,
+		IRETURN | LRETURN | FRETURN | DRETURN | ARETURN | RETURN | ATHROW | RET(_)
+	)
+}
+
+fn changes_local(instruction: &Instructions) -> bool {
+	use Instructions::*;
+
+	matches!(
+		instruction,
+		ISTORE(_) | LSTORE(_) | FSTORE(_) | DSTORE(_) | ASTORE(_) | IINC(..)
+	)
+}
+
+fn is_wide(ty: &VerificationTypeInfo) -> bool {
+	matches!(
+		ty,
+		VerificationTypeInfo::LongVariableInfo | VerificationTypeInfo::DoubleVariableInfo
+	)
+}
+
+fn size(kind: Kind) -> u16 {
+	match kind {
+		Kind::Long | Kind::Double => 2,
+		_ => 1,
+	}
+}
+
+/// Turns the instructions [`execute`] hands over into statements.
+struct Lift<'a, 'b> {
+	values: &'a mut Vec<ValueInfo>,
+	types: &'a mut VerificationDomain<'b>,
+	stmts: Vec<Stmt>,
+	line: Option<u16>,
+}
+
+impl Lift<'_, '_> {
+	fn push(&mut self, index: usize, instruction: Instructions, args: Vec<Value>) -> Option<Value> {
+		let (_, pushes) = instruction.stack_effect();
+		let result = (pushes > 0).then(|| {
+			let kind = KindDomain.execute(index, &instruction, &[]).unwrap_or(Kind::Unknown);
+			let ty = match &instruction {
+				Instructions::NEW(class) => Some(VerificationTypeInfo::ObjectVariableInfo { cpool_idx: class.index }),
+				_ => {
+					let inputs = args
+						.iter()
+						.map(|arg| self.values[arg.index()].ty.clone().map(FrameValue::Type))
+						.collect::<Vec<_>>();
+					match self.types.execute(index, &instruction, &inputs).flatten() {
+						Some(FrameValue::Type(ty)) => Some(ty),
+						_ => None,
+					}
+				}
+			};
+			self.values.push(ValueInfo { kind, ty });
+			Value(self.values.len() as u32 - 1)
+		});
+		self.stmts.push(Stmt {
+			result,
+			instruction,
+			args,
+			line: self.line,
+		});
+		result
+	}
+}
+
+impl Domain for Lift<'_, '_> {
+	type Value = Value;
+
+	fn unknown(&mut self) -> Value {
+		self.values.push(ValueInfo {
+			kind: Kind::Unknown,
+			ty: None,
+		});
+		Value(self.values.len() as u32 - 1)
+	}
+
+	// lifting runs every block once, the phis do the merging
+	fn merge(&mut self, a: &Value, _b: &Value) -> Value {
+		*a
+	}
+
+	fn execute(&mut self, index: usize, instruction: &Instructions, inputs: &[Value]) -> Option<Value> {
+		match instruction {
+			Instructions::IINC(_, constant) => {
+				let constant = self.push(index, Instructions::SIPUSH(*constant), Vec::new())?;
+				self.push(index, Instructions::IADD, vec![inputs[0], constant])
+			}
+			_ => self.push(index, instruction.clone(), inputs.to_vec()),
+		}
+	}
+}
+
+impl SsaMethod {
+	/// Lowers the method back into instructions. `this_class` is the Class entry of the method's
+	/// class, which is what `this` is in a constructor once the super constructor has been called.
+	pub fn lower(&self, this_class: u16) -> Result<InsnList, SsaError> {
+		let mut used = HashSet::new();
+		for block in &self.blocks {
+			for phi in &block.phis {
+				used.extend(phi.inputs.iter().map(|(_, input)| *input));
+			}
+			for stmt in &block.stmts {
+				used.extend(stmt.args.iter().copied());
+			}
+			used.extend(block.terminator.args());
+		}
+		let handlers = self
+			.blocks
+			.iter()
+			.flat_map(|block| block.handlers.iter().map(|(_, handler)| *handler))
+			.collect::<HashSet<_>>();
+
+		// every value gets a slot of its own, the parameters keep theirs
+		let mut slots = vec![None; self.values.len()];
+		let mut next = 0;
+		let mut allocate = |size: u16| -> Result<u16, IRClassfileError> {
+			let slot = next;
+			next = u16::try_from(next as usize + size as usize).map_err(|_| IRClassfileError::TooMany {
+				what: "locals",
+				actual: next as usize + size as usize,
+			})?;
+			Ok(slot)
+		};
+		for (parameter, ty) in self.parameters.iter().zip(&self.initial_locals) {
+			slots[parameter.index()] = Some(allocate(1 + is_wide(ty) as u16)?);
+		}
+		// a handler's phis are written wherever a block it covers starts and only read into the
+		// phis' own slots when it's entered, so a value still in use isn't overwritten
+		let mut exception_slots = HashMap::new();
+		for (i, block) in self.blocks.iter().enumerate() {
+			for phi in &block.phis {
+				let size = size(self.kind(phi.result));
+				slots[phi.result.index()] = Some(allocate(size)?);
+				if handlers.contains(&Block(i as u32)) {
+					exception_slots.insert(phi.result, allocate(size)?);
+				}
+			}
+			let results = block
+				.exception
+				.iter()
+				.chain(block.stmts.iter().flat_map(|stmt| &stmt.result));
+			for result in results {
+				if used.contains(result) {
+					slots[result.index()] = Some(allocate(size(self.kind(*result)))?);
+				}
+			}
+		}
+
+		let mut insns = InsnList::new(self.initial_locals.clone());
+		let labels = self.blocks.iter().map(|_| insns.new_label()).collect::<Vec<_>>();
+		let mut new_labels = HashMap::new();
+		for stmt in self.blocks.iter().flat_map(|block| &block.stmts) {
+			if let (Instructions::NEW(_), Some(result)) = (&stmt.instruction, stmt.result) {
+				new_labels.insert(result, insns.new_label());
+			}
+		}
+		let live = self.liveness();
+		let initialized = self.initialized();
+		let mut lowering = Lowering {
+			method: self,
+			insns,
+			slots,
+			exception_slots,
+			labels,
+			new_labels,
+			this_class,
+		};
+
+		let mut line = None;
+		for (i, block) in self.blocks.iter().enumerate() {
+			let here = Block(i as u32);
+			let initialized_out = initialized[i]
+				.iter()
+				.copied()
+				.chain(block.stmts.iter().filter_map(initializes))
+				.collect::<HashSet<_>>();
+			lowering.push(Insn::Label(lowering.labels[i]));
+			if i > 0 {
+				let is_handler = handlers.contains(&here);
+				let locals = live[i].iter().map(|value| {
+					let slot = match lowering.exception_slots.get(value) {
+						Some(slot) if is_handler && block.phis.iter().any(|phi| phi.result == *value) => *slot,
+						_ => lowering.slot(*value),
+					};
+					(slot, *value)
+				});
+				let locals = lowering.frame(locals, &initialized[i])?;
+				let stack = block
+					.exception
+					.map(|exception| lowering.frame_type(exception, &initialized[i]))
+					.into_iter()
+					.collect::<Result<_, _>>()?;
+				lowering.push(Insn::Frame { locals, stack });
+			}
+			if let Some(exception) = block.exception {
+				match lowering.slots[exception.index()] {
+					Some(slot) => lowering.store(exception, slot)?,
+					None => lowering.push(Insn::Op(Instructions::POP)),
+				}
+				for phi in &block.phis {
+					if let Some(from) = lowering.exception_slots.get(&phi.result).copied() {
+						lowering.load_slot(phi.result, from)?;
+						lowering.store(phi.result, lowering.slot(phi.result))?;
+					}
+				}
+			}
+
+			// handlers get the values the block starts with
+			let copies = block
+				.handlers
+				.iter()
+				.flat_map(|(_, handler)| &self.blocks[handler.index()].phis)
+				.filter_map(|phi| {
+					let input = phi.inputs.iter().find(|(from, _)| *from == here)?.1;
+					Some((input, lowering.exception_slots[&phi.result]))
+				})
+				.collect::<Vec<_>>();
+			lowering.copy(&copies)?;
+			let range = (!block.handlers.is_empty()).then(|| (lowering.insns.new_label(), lowering.insns.new_label()));
+			if let Some((start, _)) = range {
+				lowering.push(Insn::Label(start));
+			}
+
+			for stmt in &block.stmts {
+				if stmt.line != line {
+					if let Some(number) = stmt.line {
+						lowering.push(Insn::Line(number));
+					}
+					line = stmt.line;
+				}
+				if let Some(label) = stmt.result.and_then(|result| lowering.new_labels.get(&result)) {
+					lowering.push(Insn::Label(*label));
+				}
+				for arg in &stmt.args {
+					lowering.load(*arg)?;
+				}
+				lowering.push(Insn::Op(stmt.instruction.clone()));
+				if let Some(result) = stmt.result {
+					match lowering.slots[result.index()] {
+						Some(slot) => lowering.store(result, slot)?,
+						None if stmt.instruction.stack_effect().1 == 2 => lowering.push(Insn::Op(Instructions::POP2)),
+						None => lowering.push(Insn::Op(Instructions::POP)),
+					}
+				}
+			}
+
+			let mut stubs = Vec::new();
+			let falls_into = |target: Block| target.index() == i + 1;
+			match &block.terminator {
+				Terminator::Goto(target) => {
+					lowering.copy(&lowering.phi_copies(here, *target))?;
+					if !falls_into(*target) {
+						lowering.jump(Opcodes::GOTO, lowering.labels[target.index()]);
+					}
+				}
+				Terminator::If {
+					opcode,
+					args,
+					target,
+					next,
+				} => {
+					for arg in args {
+						lowering.load(*arg)?;
+					}
+					let label = lowering.edge(here, *target, &mut stubs);
+					lowering.jump(*opcode, label);
+					lowering.copy(&lowering.phi_copies(here, *next))?;
+					// the stubs come right after the block
+					if !falls_into(*next) || !stubs.is_empty() {
+						lowering.jump(Opcodes::GOTO, lowering.labels[next.index()]);
+					}
+				}
+				Terminator::TableSwitch {
+					key,
+					low,
+					targets,
+					default,
+				} => {
+					lowering.load(*key)?;
+					let insn = Insn::TableSwitch {
+						default: lowering.edge(here, *default, &mut stubs),
+						low: *low,
+						targets: targets
+							.iter()
+							.map(|target| lowering.edge(here, *target, &mut stubs))
+							.collect(),
+					};
+					lowering.push(insn);
+				}
+				Terminator::LookupSwitch { key, pairs, default } => {
+					lowering.load(*key)?;
+					let insn = Insn::LookupSwitch {
+						default: lowering.edge(here, *default, &mut stubs),
+						pairs: pairs
+							.iter()
+							.map(|(value, target)| (*value, lowering.edge(here, *target, &mut stubs)))
+							.collect(),
+					};
+					lowering.push(insn);
+				}
+				Terminator::Return(value) => {
+					let opcode = match value {
+						Some(value) => {
+							lowering.load(*value)?;
+							match self.kind(*value) {
+								Kind::Int => Instructions::IRETURN,
+								Kind::Float => Instructions::FRETURN,
+								Kind::Long => Instructions::LRETURN,
+								Kind::Double => Instructions::DRETURN,
+								_ => Instructions::ARETURN,
+							}
+						}
+						None => Instructions::RETURN,
+					};
+					lowering.push(Insn::Op(opcode));
+				}
+				Terminator::Throw(value) => {
+					lowering.load(*value)?;
+					lowering.push(Insn::Op(Instructions::ATHROW));
+				}
+			}
+			if let Some((start, end)) = range {
+				lowering.push(Insn::Label(end));
+				for (catch_type, handler) in &block.handlers {
+					lowering.insns.try_catch_blocks.push(TryCatchBlock {
+						start,
+						end,
+						handler: lowering.labels[handler.index()],
+						catch_type: *catch_type,
+					});
+				}
+			}
+
+			// edges into blocks with phis that aren't the only way out of this one get their own copies
+			for (label, target) in stubs {
+				let phis = &self.blocks[target.index()].phis;
+				let locals = live[target.index()]
+					.iter()
+					.filter(|value| !phis.iter().any(|phi| phi.result == **value))
+					.copied()
+					.chain(phis.iter().filter_map(|phi| input(phi, here)))
+					.map(|value| (lowering.slot(value), value))
+					.collect::<Vec<_>>();
+				let locals = lowering.frame(locals, &initialized_out)?;
+				lowering.push(Insn::Label(label));
+				lowering.push(Insn::Frame {
+					locals,
+					stack: Vec::new(),
+				});
+				lowering.copy(&lowering.phi_copies(here, target))?;
+				lowering.jump(Opcodes::GOTO, lowering.labels[target.index()]);
+			}
+		}
+		Ok(lowering.insns)
+	}
+
+	/// The values each block needs in their slots when it starts, its phis included.
+	fn liveness(&self) -> Vec<HashSet<Value>> {
+		let count = self.blocks.len();
+		let mut uses = Vec::with_capacity(count);
+		let mut defs = Vec::with_capacity(count);
+		for (i, block) in self.blocks.iter().enumerate() {
+			let here = Block(i as u32);
+			let mut used = block.phis.iter().map(|phi| phi.result).collect::<HashSet<_>>();
+			for (_, handler) in &block.handlers {
+				used.extend(
+					self.blocks[handler.index()]
+						.phis
+						.iter()
+						.filter_map(|phi| input(phi, here)),
+				);
+			}
+			let mut defined = block.exception.into_iter().collect::<HashSet<_>>();
+			for stmt in &block.stmts {
+				used.extend(stmt.args.iter().filter(|arg| !defined.contains(*arg)));
+				defined.extend(stmt.result);
+			}
+			used.extend(block.terminator.args().into_iter().filter(|arg| !defined.contains(arg)));
+			uses.push(used);
+			defs.push(defined);
+		}
+
+		let mut live = vec![HashSet::new(); count];
+		loop {
+			let mut changed = false;
+			for i in (0..count).rev() {
+				let block = &self.blocks[i];
+				let here = Block(i as u32);
+				let without_phis = |target: Block| {
+					let phis = &self.blocks[target.index()].phis;
+					live[target.index()]
+						.iter()
+						.filter(|value| !phis.iter().any(|phi| phi.result == **value))
+						.copied()
+						.collect::<Vec<_>>()
+				};
+				let mut out = HashSet::new();
+				for target in block.terminator.successors() {
+					out.extend(without_phis(target));
+					out.extend(
+						self.blocks[target.index()]
+							.phis
+							.iter()
+							.filter_map(|phi| input(phi, here)),
+					);
+				}
+				// what a handler needs has to be there all through the block
+				let mut throughout = HashSet::new();
+				for (_, handler) in &block.handlers {
+					throughout.extend(without_phis(*handler));
+				}
+				let mut entry = uses[i].clone();
+				entry.extend(out.iter().chain(&throughout).filter(|value| !defs[i].contains(*value)));
+				entry.extend(throughout);
+				if entry != live[i] {
+					live[i] = entry;
+					changed = true;
+				}
+			}
+			if !changed {
+				break live;
+			}
+		}
+	}
+
+	/// The objects each block starts with that their constructor has been called on.
+	fn initialized(&self) -> Vec<HashSet<Value>> {
+		let count = self.blocks.len();
+		let mut initialized: Vec<Option<HashSet<Value>>> = vec![None; count];
+		initialized[0] = Some(HashSet::new());
+		loop {
+			let mut changed = false;
+			for (i, block) in self.blocks.iter().enumerate() {
+				let Some(entry) = initialized[i].clone() else {
+					continue;
+				};
+				let exit = entry
+					.iter()
+					.copied()
+					.chain(block.stmts.iter().filter_map(initializes))
+					.collect::<HashSet<_>>();
+				let handlers = block.handlers.iter().map(|(_, handler)| (*handler, &entry));
+				let successors = block.terminator.successors().into_iter().map(|target| (target, &exit));
+				for (target, values) in successors.chain(handlers).collect::<Vec<_>>() {
+					let merged = match &initialized[target.index()] {
+						None => values.clone(),
+						Some(existing) => existing.intersection(values).copied().collect(),
+					};
+					if initialized[target.index()].as_ref() != Some(&merged) {
+						initialized[target.index()] = Some(merged);
+						changed = true;
+					}
+				}
+			}
+			if !changed {
+				break initialized.into_iter().map(Option::unwrap_or_default).collect();
+			}
+		}
+	}
+}
+
+/// The object a constructor call initializes.
+fn initializes(stmt: &Stmt) -> Option<Value> {
+	match &stmt.instruction {
+		Instructions::INVOKESPECIAL(method) if method.name_and_ty.name.data.as_str() == "<init>" => {
+			stmt.args.first().copied()
+		}
+		_ => None,
+	}
+}
+
+/// What `phi` takes coming from `from`.
+fn input(phi: &Phi, from: Block) -> Option<Value> {
+	phi.inputs
+		.iter()
+		.find(|(predecessor, _)| *predecessor == from)
+		.map(|(_, input)| *input)
+}
+
+struct Lowering<'a> {
+	method: &'a SsaMethod,
+	insns: InsnList,
+	slots: Vec<Option<u16>>,
+	exception_slots: HashMap<Value, u16>,
+	labels: Vec<Label>,
+	/// The labels right before each `new`, for the frames of the objects it makes.
+	new_labels: HashMap<Value, Label>,
+	this_class: u16,
+}
+
+impl Lowering<'_> {
+	fn push(&mut self, insn: Insn) {
+		self.insns.insns.push(insn);
+	}
+
+	fn jump(&mut self, opcode: u8, target: Label) {
+		self.push(Insn::Jump { opcode, target });
+	}
+
+	/// The slot of a value that's used, which always has one.
+	fn slot(&self, value: Value) -> u16 {
+		self.slots[value.index()].unwrap()
+	}
+
+	fn load(&mut self, value: Value) -> Result<(), SsaError> {
+		self.load_slot(value, self.slot(value))
+	}
+
+	fn load_slot(&mut self, value: Value, slot: u16) -> Result<(), SsaError> {
+		let instruction = match self.method.kind(value) {
+			Kind::Int => Instructions::ILOAD(slot),
+			Kind::Float => Instructions::FLOAD(slot),
+			Kind::Long => Instructions::LLOAD(slot),
+			Kind::Double => Instructions::DLOAD(slot),
+			Kind::Reference => Instructions::ALOAD(slot),
+			Kind::Unknown => return Err(SsaError::UnknownType(value)),
+		};
+		self.push(Insn::Op(instruction));
+		Ok(())
+	}
+
+	fn store(&mut self, value: Value, slot: u16) -> Result<(), SsaError> {
+		let instruction = match self.method.kind(value) {
+			Kind::Int => Instructions::ISTORE(slot),
+			Kind::Float => Instructions::FSTORE(slot),
+			Kind::Long => Instructions::LSTORE(slot),
+			Kind::Double => Instructions::DSTORE(slot),
+			Kind::Reference => Instructions::ASTORE(slot),
+			Kind::Unknown => return Err(SsaError::UnknownType(value)),
+		};
+		self.push(Insn::Op(instruction));
+		Ok(())
+	}
+
+	/// Copies values into slots all at once, through the stack, so one copy can read a slot another
+	/// one writes.
+	fn copy(&mut self, copies: &[(Value, u16)]) -> Result<(), SsaError> {
+		let copies = copies
+			.iter()
+			.filter(|(value, slot)| self.slots[value.index()] != Some(*slot))
+			.collect::<Vec<_>>();
+		for (value, _) in &copies {
+			self.load(*value)?;
+		}
+		for (value, slot) in copies.into_iter().rev() {
+			self.store(*value, *slot)?;
+		}
+		Ok(())
+	}
+
+	/// The copies into `to`'s phis going there from `from`.
+	fn phi_copies(&self, from: Block, to: Block) -> Vec<(Value, u16)> {
+		self.method.blocks[to.index()]
+			.phis
+			.iter()
+			.filter_map(|phi| Some((input(phi, from)?, self.slot(phi.result))))
+			.collect()
+	}
+
+	/// Where a branch from `from` to `to` goes, a stub doing the phi copies first if there are any.
+	fn edge(&mut self, from: Block, to: Block, stubs: &mut Vec<(Label, Block)>) -> Label {
+		if self.phi_copies(from, to).is_empty() {
+			return self.labels[to.index()];
+		}
+		if let Some((label, _)) = stubs.iter().find(|(_, target)| *target == to) {
+			return *label;
+		}
+		let label = self.insns.new_label();
+		stubs.push((label, to));
+		label
+	}
+
+	fn frame(
+		&self,
+		values: impl IntoIterator<Item = (u16, Value)>,
+		initialized: &HashSet<Value>,
+	) -> Result<Vec<FrameValue>, SsaError> {
+		let mut values = values.into_iter().collect::<Vec<_>>();
+		values.sort_unstable();
+		let mut locals = Vec::with_capacity(values.len());
+		let mut slot = 0;
+		for (at, value) in values {
+			while slot < at {
+				locals.push(FrameValue::Type(VerificationTypeInfo::TopVariableInfo));
+				slot += 1;
+			}
+			locals.push(self.frame_type(value, initialized)?);
+			slot = at + size(self.method.kind(value));
+		}
+		Ok(locals)
+	}
+
+	fn frame_type(&self, value: Value, initialized: &HashSet<Value>) -> Result<FrameValue, SsaError> {
+		let initialized = initialized.contains(&value);
+		if let Some(label) = self.new_labels.get(&value).filter(|_| !initialized) {
+			return Ok(FrameValue::Uninitialized(*label));
+		}
+		match self.method.ty(value) {
+			Some(VerificationTypeInfo::UninitializedThisVariableInfo) if initialized => {
+				Ok(FrameValue::Type(VerificationTypeInfo::ObjectVariableInfo {
+					cpool_idx: self.this_class,
+				}))
+			}
+			Some(ty) => Ok(FrameValue::Type(ty.clone())),
+			None => Err(SsaError::UnknownType(value)),
+		}
+	}
+}
+
+impl IRClassFile {
+	/// Lifts a method's code into SSA form, `None` if the method doesn't exist or has no code.
+	pub fn lift_method(&mut self, name: &str, descriptor: &str) -> Result<Option<SsaMethod>, SsaError> {
+		let Some(insns) = self.method_insns(name, descriptor)? else {
+			return Ok(None);
+		};
+		SsaMethod::lift(&insns, &mut self.cp).map(Some)
+	}
+
+	/// Lowers `method` into a method's code, raising `max_stack` and `max_locals` if it needs more.
+	/// Returns whether the method exists and has code.
+	pub fn lower_method(&mut self, name: &str, descriptor: &str, method: &SsaMethod) -> Result<bool, SsaError> {
+		let insns = method.lower(self.this_class.index)?;
+		Ok(self.store_method_insns(name, descriptor, &insns)?)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::test_util::{class, CLASSES, HELLO};
+
+	#[test]
+	fn lift_and_lower() {
+		// every method lifts and lowers, and the lowered code lifts again
+		for bytes in CLASSES {
+			let mut class = class(bytes);
+			let methods = class
+				.methods
+				.iter()
+				.map(|method| (method.name().to_string(), method.descriptor().to_string()))
+				.collect::<Vec<_>>();
+			for (name, descriptor) in &methods {
+				let Some(method) = class.lift_method(name, descriptor).unwrap() else {
+					continue;
+				};
+				assert!(class.lower_method(name, descriptor, &method).unwrap());
+				assert!(class.lift_method(name, descriptor).unwrap().is_some());
+			}
+		}
+	}
+
+	#[test]
+	fn phis() {
+		// the long stackmapper adds one to on one path meets the one it doesn't add to in a phi
+		let mut class = class(HELLO);
+		let method = class
+			.lift_method("stackmapper", "(ILjava/lang/Object;)V")
+			.unwrap()
+			.unwrap();
+		let result = |opcode: u8| {
+			method
+				.blocks
+				.iter()
+				.flat_map(|block| &block.stmts)
+				.find(|stmt| stmt.instruction.opcode() == opcode)
+				.and_then(|stmt| stmt.result)
+				.unwrap()
+		};
+		let phis = method.blocks.iter().flat_map(|block| &block.phis).collect::<Vec<_>>();
+		assert_eq!(phis.len(), 1);
+		let inputs = phis[0].inputs.iter().map(|(_, input)| *input).collect::<Vec<_>>();
+		assert_eq!(inputs, [result(Opcodes::LCONST_0), result(Opcodes::LADD)]);
+		assert!(matches!(
+			&method.blocks[1].terminator,
+			Terminator::If { args, .. } if args[..] == [result(Opcodes::ICONST_0)]
+		));
+	}
+}
@@ -0,0 +1,389 @@
+//! Working with StackMapTable frames by absolute offset instead of their delta encoding, see
+//! [`StackMapTableAttribute::resolve`] and [`StackMapTableAttribute::from_resolved`].
+
+use thiserror::Error;
+
+use crate::{
+	access::MethodAccessFlags,
+	attribute::{StackMapFrame, StackMapTableAttribute, VerificationTypeInfo},
+	class_pool::IRClassfileError,
+	IRClassFile,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum StackMapError {
+	#[error("frame at offset {offset} chops {k} locals but only {locals} are live")]
+	ChopUnderflow { offset: u16, k: u8, locals: usize },
+	#[error("frame at offset {offset} doesn't come after the frame at {previous}")]
+	OutOfOrder { offset: u16, previous: u16 },
+	#[error("frame offset {0} doesn't fit in a u16")]
+	OffsetOverflow(u32),
+	#[error("frame type {0} is reserved")]
+	ReservedFrameType(u8),
+}
+
+/// A frame with its absolute bytecode offset and the full locals and stack it declares.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResolvedFrame {
+	pub offset: u16,
+	/// Long and Double take a single entry, as they do in the attribute.
+	pub locals: Vec<VerificationTypeInfo>,
+	pub stack: Vec<VerificationTypeInfo>,
+}
+
+impl StackMapFrame {
+	pub fn frame_type(&self) -> u8 {
+		match self {
+			Self::SameFrame { frame_type, .. }
+			| Self::SameLocals1StackItemFrame { frame_type, .. }
+			| Self::SameLocals1StackItemFrameExtended { frame_type, .. }
+			| Self::ChopFrame { frame_type, .. }
+			| Self::SameFrameExtended { frame_type, .. }
+			| Self::AppendFrame { frame_type, .. }
+			| Self::FullFrame { frame_type, .. } => *frame_type,
+		}
+	}
+
+	pub fn offset_delta(&self) -> u16 {
+		match self {
+			Self::SameFrame { offset_delta, .. }
+			| Self::SameLocals1StackItemFrame { offset_delta, .. }
+			| Self::SameLocals1StackItemFrameExtended { offset_delta, .. }
+			| Self::ChopFrame { offset_delta, .. }
+			| Self::SameFrameExtended { offset_delta, .. }
+			| Self::AppendFrame { offset_delta, .. }
+			| Self::FullFrame { offset_delta, .. } => *offset_delta,
+		}
+	}
+
+	/// `k`, the number of locals a chop frame removes, `251 - frame_type`.
+	pub fn chop_count(&self) -> Option<u8> {
+		match self {
+			Self::ChopFrame { frame_type, .. } => Some(251 - frame_type),
+			_ => None,
+		}
+	}
+}
+
+impl StackMapTableAttribute {
+	/// The absolute bytecode offset of every frame. The first frame is at its `offset_delta`, every
+	/// other one `offset_delta + 1` past the frame before it.
+	pub fn offsets(&self) -> Result<Vec<u16>, StackMapError> {
+		let mut offsets = Vec::with_capacity(self.entries.len());
+		let mut previous: Option<u16> = None;
+		for frame in &self.entries {
+			let offset = match previous {
+				None => frame.offset_delta() as u32,
+				Some(previous) => previous as u32 + frame.offset_delta() as u32 + 1,
+			};
+			let offset = u16::try_from(offset).map_err(|_| StackMapError::OffsetOverflow(offset))?;
+			offsets.push(offset);
+			previous = Some(offset);
+		}
+		Ok(offsets)
+	}
+
+	/// Expands every frame against the one before it, starting from `initial_locals`, the implicit
+	/// frame at the method's entry (see [`IRClassFile::initial_locals`]).
+	pub fn resolve(&self, initial_locals: &[VerificationTypeInfo]) -> Result<Vec<ResolvedFrame>, StackMapError> {
+		let mut locals = initial_locals.to_vec();
+		let mut frames = Vec::with_capacity(self.entries.len());
+		for (frame, offset) in self.entries.iter().zip(self.offsets()?) {
+			let stack = match frame {
+				StackMapFrame::SameFrame { .. } | StackMapFrame::SameFrameExtended { .. } => Vec::new(),
+				StackMapFrame::SameLocals1StackItemFrame { stack, .. }
+				| StackMapFrame::SameLocals1StackItemFrameExtended { stack, .. } => vec![stack.clone()],
+				StackMapFrame::ChopFrame { frame_type, .. } => {
+					let k = 251 - frame_type;
+					if locals.len() < k as usize {
+						return Err(StackMapError::ChopUnderflow {
+							offset,
+							k,
+							locals: locals.len(),
+						});
+					}
+					locals.truncate(locals.len() - k as usize);
+					Vec::new()
+				}
+				StackMapFrame::AppendFrame { locals: appended, .. } => {
+					locals.extend_from_slice(appended);
+					Vec::new()
+				}
+				StackMapFrame::FullFrame {
+					locals: full, stack, ..
+				} => {
+					locals.clone_from(full);
+					stack.clone()
+				}
+			};
+			frames.push(ResolvedFrame {
+				offset,
+				locals: locals.clone(),
+				stack,
+			});
+		}
+		Ok(frames)
+	}
+
+	/// Encodes `frames`, which must be in increasing offset order, back into deltas, picking the most
+	/// compact frame type for each.
+	pub fn from_resolved(
+		initial_locals: &[VerificationTypeInfo],
+		frames: &[ResolvedFrame],
+	) -> Result<Self, StackMapError> {
+		let mut entries = Vec::with_capacity(frames.len());
+		let mut locals = initial_locals;
+		let mut previous: Option<u16> = None;
+		for frame in frames {
+			let offset_delta = match previous {
+				None => frame.offset,
+				Some(previous) if frame.offset > previous => frame.offset - previous - 1,
+				Some(previous) => {
+					return Err(StackMapError::OutOfOrder {
+						offset: frame.offset,
+						previous,
+					})
+				}
+			};
+			entries.push(encode(locals, frame, offset_delta));
+			locals = &frame.locals;
+			previous = Some(frame.offset);
+		}
+		Ok(Self { entries })
+	}
+}
+
+fn encode(previous: &[VerificationTypeInfo], frame: &ResolvedFrame, offset_delta: u16) -> StackMapFrame {
+	let locals = &frame.locals;
+	let same_locals = previous == locals.as_slice();
+	match frame.stack.as_slice() {
+		[] if same_locals && offset_delta < 64 => {
+			return StackMapFrame::SameFrame {
+				frame_type: offset_delta as u8,
+				offset_delta,
+			}
+		}
+		[] if same_locals => {
+			return StackMapFrame::SameFrameExtended {
+				frame_type: 251,
+				offset_delta,
+			}
+		}
+		[stack] if same_locals && offset_delta < 64 => {
+			return StackMapFrame::SameLocals1StackItemFrame {
+				frame_type: 64 + offset_delta as u8,
+				offset_delta,
+				stack: stack.clone(),
+			}
+		}
+		[stack] if same_locals => {
+			return StackMapFrame::SameLocals1StackItemFrameExtended {
+				frame_type: 247,
+				offset_delta,
+				stack: stack.clone(),
+			}
+		}
+		[] if locals.len() < previous.len() && previous.starts_with(locals) => {
+			let k = previous.len() - locals.len();
+			if k <= 3 {
+				return StackMapFrame::ChopFrame {
+					frame_type: 251 - k as u8,
+					offset_delta,
+				};
+			}
+		}
+		[] if locals.len() > previous.len() && locals.starts_with(previous) => {
+			let k = locals.len() - previous.len();
+			if k <= 3 {
+				return StackMapFrame::AppendFrame {
+					frame_type: 251 + k as u8,
+					offset_delta,
+					locals: locals[previous.len()..].to_vec(),
+				};
+			}
+		}
+		_ => {}
+	}
+	StackMapFrame::FullFrame {
+		frame_type: 255,
+		offset_delta,
+		locals: locals.clone(),
+		stack: frame.stack.clone(),
+	}
+}
+
+impl IRClassFile {
+	/// The locals of the implicit frame at a method's entry: `this` unless the method is static
+	/// (uninitialized in constructors other than Object's) followed by its parameters. Adds the Class
+	/// entries reference parameters need. Returns `None` if the method doesn't exist.
+	pub fn initial_locals(
+		&mut self,
+		name: &str,
+		descriptor: &str,
+	) -> Result<Option<Vec<VerificationTypeInfo>>, IRClassfileError> {
+		let Some(method) = self.find_method(name, descriptor) else {
+			return Ok(None);
+		};
+		let is_static = method.access_flags.contains(MethodAccessFlags::STATIC);
+		self.entry_locals(is_static, name, descriptor).map(Some)
+	}
+
+	/// [`initial_locals`](Self::initial_locals) for a method that doesn't have to exist yet.
+	pub(crate) fn entry_locals(
+		&mut self,
+		is_static: bool,
+		name: &str,
+		descriptor: &str,
+	) -> Result<Vec<VerificationTypeInfo>, IRClassfileError> {
+		let mut locals = Vec::new();
+		if !is_static {
+			if name == "<init>" && self.class_name() != "java/lang/Object" {
+				locals.push(VerificationTypeInfo::UninitializedThisVariableInfo);
+			} else {
+				let this = self.this_class.index;
+				locals.push(VerificationTypeInfo::ObjectVariableInfo { cpool_idx: this });
+			}
+		}
+
+		let parameters = descriptor
+			.strip_prefix('(')
+			.and_then(|rest| rest.split(')').next())
+			.unwrap_or_default();
+		let mut rest = parameters;
+		while let Some(c) = rest.chars().next() {
+			let dimensions = rest.len() - rest.trim_start_matches('[').len();
+			let end = match rest.as_bytes()[dimensions] {
+				b'L' => rest.find(';').map_or(rest.len(), |end| end + 1),
+				_ => dimensions + 1,
+			};
+			let (parameter, tail) = rest.split_at(end);
+			rest = tail;
+			locals.push(match c {
+				'B' | 'C' | 'I' | 'S' | 'Z' => VerificationTypeInfo::IntegerVariableInfo,
+				'F' => VerificationTypeInfo::FloatVariableInfo,
+				'J' => VerificationTypeInfo::LongVariableInfo,
+				'D' => VerificationTypeInfo::DoubleVariableInfo,
+				// arrays are named by their descriptor, classes by their internal name
+				'[' => VerificationTypeInfo::ObjectVariableInfo {
+					cpool_idx: self.intern_class(parameter)?.index,
+				},
+				_ => VerificationTypeInfo::ObjectVariableInfo {
+					cpool_idx: self.intern_class(&parameter[1..parameter.len() - 1])?.index,
+				},
+			});
+		}
+		Ok(locals)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		attribute::IRAttribute,
+		test_util::{class, find_attr, method_code, HELLO},
+	};
+
+	fn decode(bytes: &[u8]) -> Result<StackMapFrame, IRClassfileError> {
+		StackMapFrame::new(&mut &bytes[..])
+	}
+
+	#[test]
+	fn same_locals_1_stack_item_frames() {
+		for frame_type in 64..=127u8 {
+			let frame = decode(&[frame_type, 1]).unwrap();
+			assert!(matches!(
+				frame,
+				StackMapFrame::SameLocals1StackItemFrame {
+					stack: VerificationTypeInfo::IntegerVariableInfo,
+					..
+				}
+			));
+			assert_eq!(frame.frame_type(), frame_type);
+			assert_eq!(frame.offset_delta(), frame_type as u16 - 64);
+
+			let mut out = Vec::new();
+			frame.write(&mut out).unwrap();
+			assert_eq!(out, [frame_type, 1]);
+		}
+
+		let table = StackMapTableAttribute {
+			entries: vec![decode(&[64 + 5, 1]).unwrap(), decode(&[127, 0]).unwrap()],
+		};
+		assert_eq!(table.offsets().unwrap(), [5, 5 + 63 + 1]);
+		let frames = table.resolve(&[]).unwrap();
+		assert_eq!(frames[1].stack, [VerificationTypeInfo::TopVariableInfo]);
+	}
+
+	#[test]
+	fn reserved_frame_types() {
+		for frame_type in 128..=246u8 {
+			assert!(matches!(
+				decode(&[frame_type]),
+				Err(IRClassfileError::StackMap(StackMapError::ReservedFrameType(t))) if t == frame_type
+			));
+		}
+		// the verification type of a same_locals_1_stack_item frame is missing
+		assert!(matches!(decode(&[100]), Err(IRClassfileError::Bytes(_))));
+	}
+
+	fn stackmapper_table(class: &IRClassFile) -> &StackMapTableAttribute {
+		match find_attr(&method_code(class, "stackmapper").attributes, "StackMapTable") {
+			IRAttribute::StackMapTable(table) => table,
+			_ => unreachable!(),
+		}
+	}
+
+	fn stackmapper_locals(class: &mut IRClassFile) -> Vec<VerificationTypeInfo> {
+		class
+			.initial_locals("stackmapper", "(ILjava/lang/Object;)V")
+			.unwrap()
+			.unwrap()
+	}
+
+	#[test]
+	fn initial_locals() {
+		let mut hello = class(HELLO);
+		let initial = stackmapper_locals(&mut hello);
+		let object = hello.intern_class("java/lang/Object").unwrap().index;
+		assert_eq!(
+			initial,
+			vec![
+				VerificationTypeInfo::IntegerVariableInfo,
+				VerificationTypeInfo::ObjectVariableInfo { cpool_idx: object },
+			]
+		);
+	}
+
+	#[test]
+	fn resolve_frames() {
+		let mut hello = class(HELLO);
+		let initial = stackmapper_locals(&mut hello);
+		let table = stackmapper_table(&hello);
+		assert_eq!(table.offsets().unwrap(), vec![29, 33]);
+		assert_eq!(table.entries[1].chop_count(), Some(1));
+
+		let frames = table.resolve(&initial).unwrap();
+		assert_eq!(frames[0].locals.len(), 6);
+		assert_eq!(frames[0].locals[5], VerificationTypeInfo::LongVariableInfo);
+		assert_eq!(frames[1].locals, frames[0].locals[..5]);
+		assert!(frames.iter().all(|frame| frame.stack.is_empty()));
+	}
+
+	#[test]
+	fn encode_resolved() {
+		let mut hello = class(HELLO);
+		let initial = stackmapper_locals(&mut hello);
+		let table = stackmapper_table(&hello);
+		let frames = table.resolve(&initial).unwrap();
+		let encoded = StackMapTableAttribute::from_resolved(&initial, &frames).unwrap();
+		assert_eq!(encoded.entries, table.entries);
+		assert_eq!(
+			StackMapTableAttribute::from_resolved(&initial, &[frames[1].clone(), frames[0].clone()]),
+			Err(StackMapError::OutOfOrder {
+				offset: 29,
+				previous: 33
+			})
+		);
+	}
+}
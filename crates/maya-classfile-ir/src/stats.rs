@@ -0,0 +1,263 @@
+//! How big classes are and what they're made of, see [`Stats`].
+
+use std::{
+	collections::{BTreeMap, HashSet},
+	fmt,
+	mem::size_of,
+	ops::AddAssign,
+};
+
+use maya_classfile_io::class_pool::CpTagKind;
+
+use crate::{
+	attribute::{CodeAttribute, CodeAttributeException, IRAttribute, IRAttributeInfo, RecordComponentInfo},
+	class_pool::{CPClassRef, IRClassfileError, IRCpTag},
+	code::instruction_len,
+	IRClassFile, IRFieldInfo, IRMethodInfo,
+};
+
+/// How many of something there are and the bytes they take in the classfile.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Count {
+	pub count: usize,
+	pub bytes: u64,
+}
+
+impl Count {
+	fn add(&mut self, bytes: u64) {
+		self.count += 1;
+		self.bytes += bytes;
+	}
+}
+
+impl AddAssign for Count {
+	fn add_assign(&mut self, other: Self) {
+		self.count += other.count;
+		self.bytes += other.bytes;
+	}
+}
+
+/// Counts and sizes of one class, or added up over many with [`Stats::merge`].
+#[derive(Debug, Clone, Default)]
+pub struct Stats {
+	pub classes: usize,
+	/// Bytes of the classes as they would be written.
+	pub size: u64,
+	/// Constant pool entries by kind, the slots after Long and Double aren't counted.
+	pub constants: BTreeMap<CpTagKind, Count>,
+	/// Attributes by name, including the 6 byte header. Attributes inside others, like those of Code,
+	/// are counted under their own name as well as in the bytes of the one they're in.
+	pub attributes: BTreeMap<String, Count>,
+	pub fields: usize,
+	pub methods: usize,
+	/// Code attributes and the bytes of their code arrays.
+	pub code: Count,
+	pub instructions: usize,
+	/// The longest code array.
+	pub largest_code: usize,
+	/// An estimate of the memory the IR of the classes takes, the vectors and strings it owns and
+	/// the parsed attributes by their size in the classfile. A string shared between classes is
+	/// counted by each of them.
+	pub heap_bytes: usize,
+}
+
+impl Stats {
+	/// The stats of every class in `classes` added up.
+	pub fn of<'a>(classes: impl IntoIterator<Item = &'a IRClassFile>) -> Result<Self, IRClassfileError> {
+		let mut stats = Self::default();
+		for class in classes {
+			stats.merge(&class.stats()?);
+		}
+		Ok(stats)
+	}
+
+	pub fn merge(&mut self, other: &Stats) {
+		self.classes += other.classes;
+		self.size += other.size;
+		for (kind, count) in &other.constants {
+			*self.constants.entry(*kind).or_default() += *count;
+		}
+		for (name, count) in &other.attributes {
+			*self.attributes.entry(name.clone()).or_default() += *count;
+		}
+		self.fields += other.fields;
+		self.methods += other.methods;
+		self.code += other.code;
+		self.instructions += other.instructions;
+		self.largest_code = self.largest_code.max(other.largest_code);
+		self.heap_bytes += other.heap_bytes;
+	}
+
+	/// Counts `attributes` and the ones inside them, returning their bytes and adding their size in
+	/// memory to `heap_bytes`.
+	fn add_attributes<'a>(
+		&mut self,
+		cp: &[IRCpTag],
+		attributes: impl IntoIterator<Item = &'a IRAttributeInfo>,
+	) -> Result<u64, IRClassfileError> {
+		let mut total = 0;
+		for attribute in attributes {
+			let bytes = attribute.to_io(cp)?.byte_len();
+			total += bytes;
+			self.attributes
+				.entry(attribute.attr.name().to_string())
+				.or_default()
+				.add(bytes);
+			match &attribute.attr {
+				IRAttribute::Code(code) => self.add_code(cp, code)?,
+				IRAttribute::Record { components } => {
+					self.heap_bytes += components.capacity() * size_of::<RecordComponentInfo>();
+					for component in components {
+						self.heap_bytes += component.attributes.capacity() * size_of::<IRAttributeInfo>();
+						self.add_attributes(cp, &component.attributes)?;
+					}
+				}
+				IRAttribute::Unknown { data, .. } => self.heap_bytes += data.capacity(),
+				// close enough for the rest, which are mostly indices
+				_ => self.heap_bytes += bytes as usize - 6,
+			}
+		}
+		Ok(total)
+	}
+
+	fn add_code(&mut self, cp: &[IRCpTag], code: &CodeAttribute) -> Result<(), IRClassfileError> {
+		self.code.add(code.code.len() as u64);
+		self.largest_code = self.largest_code.max(code.code.len());
+		let mut pc = 0;
+		while pc < code.code.len() {
+			pc += instruction_len(&code.code, pc)?;
+			self.instructions += 1;
+		}
+		self.heap_bytes += code.code.capacity()
+			+ code.exception_table.capacity() * size_of::<CodeAttributeException>()
+			+ code.attributes.capacity() * size_of::<Box<IRAttributeInfo>>()
+			+ code.attributes.len() * size_of::<IRAttributeInfo>();
+		self.add_attributes(cp, code.attributes.iter().map(|attribute| &**attribute))?;
+		Ok(())
+	}
+}
+
+impl IRClassFile {
+	pub fn stats(&self) -> Result<Stats, IRClassfileError> {
+		let mut stats = Stats {
+			classes: 1,
+			fields: self.fields.len(),
+			methods: self.methods.len(),
+			heap_bytes: size_of::<Self>()
+				+ self.cp.capacity() * size_of::<IRCpTag>()
+				+ self.interfaces.capacity() * size_of::<CPClassRef>()
+				+ self.fields.capacity() * size_of::<IRFieldInfo>()
+				+ self.methods.capacity() * size_of::<IRMethodInfo>()
+				+ self.attributes.capacity() * size_of::<IRAttributeInfo>(),
+			..Stats::default()
+		};
+
+		let mut size = 24 + 2 * self.interfaces.len() as u64;
+		let mut strings = HashSet::new();
+		for tag in &self.cp {
+			if let IRCpTag::Utf8(string) = tag {
+				// a string is counted once however many constants refer to it
+				if strings.insert(string.as_ptr()) {
+					stats.heap_bytes += size_of::<String>() + 2 * size_of::<usize>() + string.capacity();
+				}
+			}
+			let tag = tag.to_io()?;
+			if tag.kind() == CpTagKind::Unusable {
+				continue;
+			}
+			size += tag.byte_len();
+			stats.constants.entry(tag.kind()).or_default().add(tag.byte_len());
+		}
+		for field in &self.fields {
+			stats.heap_bytes += field.attributes.capacity() * size_of::<IRAttributeInfo>();
+			size += 8 + stats.add_attributes(&self.cp, &field.attributes)?;
+		}
+		for method in &self.methods {
+			stats.heap_bytes += method.attributes.capacity() * size_of::<IRAttributeInfo>();
+			size += 8 + stats.add_attributes(&self.cp, &method.attributes)?;
+		}
+		size += stats.add_attributes(&self.cp, &self.attributes)?;
+		stats.size = size;
+		Ok(stats)
+	}
+}
+
+impl fmt::Display for Stats {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		writeln!(f, "classes       {:>10}", self.classes)?;
+		writeln!(f, "size          {:>10} bytes", self.size)?;
+		writeln!(f, "heap (est.)   {:>10} bytes", self.heap_bytes)?;
+		writeln!(f, "fields        {:>10}", self.fields)?;
+		writeln!(f, "methods       {:>10}", self.methods)?;
+		writeln!(
+			f,
+			"code          {:>10} bytes in {} methods, {} instructions, largest {} bytes",
+			self.code.bytes, self.code.count, self.instructions, self.largest_code
+		)?;
+		writeln!(f, "constants")?;
+		for (kind, count) in &self.constants {
+			writeln!(
+				f,
+				"  {:<30}{:>8}{:>10} bytes",
+				format!("{kind:?}"),
+				count.count,
+				count.bytes
+			)?;
+		}
+		writeln!(f, "attributes")?;
+		for (name, count) in &self.attributes {
+			writeln!(f, "  {name:<30}{:>8}{:>10} bytes", count.count, count.bytes)?;
+		}
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		insn::Insn,
+		test_util::{class, A_MODULE_INFO, CONSTANTS, HELLO},
+	};
+
+	#[test]
+	fn class_stats() {
+		let hello = class(HELLO);
+		let stats = hello.stats().unwrap();
+		assert_eq!(stats.classes, 1);
+		assert_eq!(stats.size, HELLO.len() as u64);
+		assert_eq!(stats.methods, hello.methods.len());
+		let utf8 = hello.cp.iter().filter(|tag| matches!(tag, IRCpTag::Utf8(_))).count();
+		assert_eq!(stats.constants[&CpTagKind::Utf8].count, utf8);
+		assert_eq!(
+			stats.constants.values().map(|count| count.count).sum::<usize>(),
+			hello.cp.iter().filter(|tag| !matches!(tag, IRCpTag::Unusable)).count()
+		);
+		let code = stats.attributes["Code"];
+		assert_eq!(code.count, stats.code.count);
+		assert_eq!(stats.attributes["SourceFile"].bytes, 8);
+		// the line numbers are inside the Code attributes
+		assert!(stats.attributes["LineNumberTable"].bytes < code.bytes);
+		let main = class(HELLO)
+			.method_insns("main", "([Ljava/lang/String;)V")
+			.unwrap()
+			.unwrap();
+		let main = main.insns.iter().filter(|insn| matches!(insn, Insn::Op(_))).count();
+		assert!(stats.instructions > main);
+		assert!(stats.heap_bytes > stats.size as usize);
+	}
+
+	#[test]
+	fn combined_stats() {
+		let bytes = [HELLO, CONSTANTS, A_MODULE_INFO];
+		let classes = bytes.map(class);
+		let total = Stats::of(&classes).unwrap();
+		assert_eq!(total.classes, bytes.len());
+		assert_eq!(total.size, bytes.iter().map(|bytes| bytes.len() as u64).sum::<u64>());
+		assert!(total.largest_code >= classes[0].stats().unwrap().largest_code);
+		let report = total.to_string();
+		assert!(report.starts_with("classes                3\n"));
+		assert!(report.contains("\n  Utf8 "));
+		assert!(report.contains("\n  Module "));
+	}
+}
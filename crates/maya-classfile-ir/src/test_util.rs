@@ -0,0 +1,58 @@
+//! Classes compiled from `maya-test-bin/data` and helpers the unit tests share.
+
+use std::borrow::Borrow;
+
+use maya_classfile_io::IOClassFile;
+
+use crate::{
+	attribute::{CodeAttribute, IRAttribute, IRAttributeInfo},
+	IRClassFile,
+};
+
+pub(crate) const HELLO: &[u8] = include_bytes!("../../maya-test-bin/data/out/a/a/Hello.class");
+pub(crate) const HELLO_ANNO_REC: &[u8] = include_bytes!("../../maya-test-bin/data/out/a/a/Hello$HelloAnnoRec.class");
+pub(crate) const CONSTANTS: &[u8] = include_bytes!("../../maya-test-bin/data/out/a/a/Constants.class");
+pub(crate) const A_MODULE_INFO: &[u8] = include_bytes!("../../maya-test-bin/data/out/a/module-info.class");
+pub(crate) const HELLO_ANNO: &[u8] = include_bytes!("../../maya-test-bin/data/out/a/a/Hello$HelloAnno.class");
+pub(crate) const B_MODULE_INFO: &[u8] = include_bytes!("../../maya-test-bin/data/out/b/module-info.class");
+pub(crate) const PAIR: &[u8] = include_bytes!("../../maya-test-bin/data/out/b/b/api/Pair.class");
+pub(crate) const SHAPE: &[u8] = include_bytes!("../../maya-test-bin/data/out/a/a/Hello$Shape.class");
+pub(crate) const TYPED: &[u8] = include_bytes!("../../maya-test-bin/data/out/b/b/internal/Typed.class");
+pub(crate) const HELLO_INTERFACE: &[u8] = include_bytes!("../../maya-test-bin/data/out/a/a/Hello$HelloInterface.class");
+pub(crate) const SIMPLE: &[u8] = include_bytes!("../../maya-test-bin/data/out/a/a/Simple.class");
+
+/// The classes round trips are checked on, between them they have every attribute the IR parses.
+pub(crate) const CLASSES: &[&[u8]] = &[
+	HELLO,
+	HELLO_ANNO_REC,
+	CONSTANTS,
+	A_MODULE_INFO,
+	HELLO_ANNO,
+	B_MODULE_INFO,
+	PAIR,
+	SHAPE,
+	TYPED,
+];
+
+pub(crate) fn class(bytes: &[u8]) -> IRClassFile {
+	IRClassFile::from_io(IOClassFile::read_from_slice(bytes).unwrap()).unwrap()
+}
+
+/// The first attribute called `name`, from a class, member or (boxed) Code attribute list.
+pub(crate) fn find_attr<'a>(attributes: &'a [impl Borrow<IRAttributeInfo>], name: &str) -> &'a IRAttribute {
+	&attributes
+		.iter()
+		.map(Borrow::borrow)
+		.find(|attr| attr.attr.name() == name)
+		.unwrap()
+		.attr
+}
+
+/// The Code attribute of the first method called `name`.
+pub(crate) fn method_code<'a>(class: &'a IRClassFile, name: &str) -> &'a CodeAttribute {
+	let method = class.methods.iter().find(|method| method.name() == name).unwrap();
+	match find_attr(&method.attributes, "Code") {
+		IRAttribute::Code(code) => code,
+		_ => unreachable!(),
+	}
+}
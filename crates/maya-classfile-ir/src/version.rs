@@ -0,0 +1,262 @@
+//! Checking a class only uses what its class file version allows, see
+//! [`IRClassFile::validate_for_version`].
+
+use thiserror::Error;
+
+use crate::{
+	access::{ClassAccessFlags, FieldAccessFlags, MethodAccessFlags},
+	attribute::{IRAttribute, IRAttributeInfo},
+	class_pool::IRCpTag,
+	code::instruction_len,
+	disasm::cp_entry,
+	ClassFileVersion, IRClassFile,
+};
+
+/// Minor version marking a class that depends on the preview features of its major version.
+pub const PREVIEW_MINOR_VERSION: u16 = 0xFFFF;
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum VersionError {
+	#[error("{what} needs class file version {required} or later")]
+	TooOld { what: String, required: u16 },
+	#[error("{what} isn't allowed from class file version {limit} on")]
+	TooNew { what: String, limit: u16 },
+	#[error("{what} is a preview feature, which needs minor version {PREVIEW_MINOR_VERSION}")]
+	Preview { what: String },
+}
+
+impl IRClassFile {
+	/// Lists everything in the class that `version` doesn't allow: attributes, constant pool
+	/// entries, flags and instructions introduced later, and instructions removed earlier.
+	///
+	/// The class's own version is ignored, which makes this the check to run before retargeting.
+	pub fn validate_for_version(&self, version: &ClassFileVersion) -> Vec<VersionError> {
+		let mut checker = Checker {
+			class: self,
+			version: *version,
+			errors: Vec::new(),
+		};
+		checker.check_class();
+		checker.errors
+	}
+}
+
+struct Checker<'a> {
+	class: &'a IRClassFile,
+	version: ClassFileVersion,
+	errors: Vec<VersionError>,
+}
+
+impl Checker<'_> {
+	fn require(&mut self, required: u16, what: impl FnOnce() -> String) {
+		if self.version.major < required {
+			self.errors.push(VersionError::TooOld { what: what(), required });
+		}
+	}
+
+	fn check_class(&mut self) {
+		for (i, tag) in self.class.cp.iter().enumerate() {
+			let required = match tag {
+				IRCpTag::MethodHandle { .. } | IRCpTag::MethodType(_) | IRCpTag::InvokeDynamic { .. } => 51,
+				IRCpTag::Module { .. } | IRCpTag::Package { .. } => 53,
+				IRCpTag::Dynamic { .. } => 55,
+				_ => continue,
+			};
+			self.require(required, || {
+				format!("constant pool entry #{} ({})", i + 1, cp_entry(tag).0)
+			});
+		}
+
+		let flags = self.class.access_flags;
+		if flags.intersects(ClassAccessFlags::ANNOTATION | ClassAccessFlags::ENUM) {
+			self.require(49, || format!("class flags '{flags}'"));
+		}
+		if flags.contains(ClassAccessFlags::MODULE) {
+			self.require(53, || "module class flag".to_string());
+		}
+		self.check_attributes(&self.class.attributes, "class");
+
+		for field in &self.class.fields {
+			let what = format!("field {}:{}", field.name(), field.descriptor());
+			if field.access_flags.contains(FieldAccessFlags::ENUM) {
+				self.require(49, || format!("{what} enum flag"));
+			}
+			self.check_attributes(&field.attributes, &what);
+		}
+
+		let interface = flags.contains(ClassAccessFlags::INTERFACE);
+		for method in &self.class.methods {
+			let what = format!("method {}{}", method.name(), method.descriptor());
+			if method
+				.access_flags
+				.intersects(MethodAccessFlags::BRIDGE | MethodAccessFlags::VARARGS)
+			{
+				self.require(49, || format!("{what} flags '{}'", method.access_flags));
+			}
+			if interface && method.name() != "<clinit>" && !method.access_flags.contains(MethodAccessFlags::ABSTRACT) {
+				self.require(52, || format!("interface {what} with a body"));
+			}
+			self.check_attributes(&method.attributes, &what);
+		}
+	}
+
+	fn check_attributes(&mut self, attributes: &[IRAttributeInfo], owner: &str) {
+		for attribute in attributes {
+			self.check_attribute(attribute, owner);
+		}
+	}
+
+	fn check_attribute(&mut self, attribute: &IRAttributeInfo, owner: &str) {
+		let name = attribute.attr.name();
+		let required = match &attribute.attr {
+			IRAttribute::Code(code) => {
+				self.check_code(&code.code, owner);
+				for attribute in &code.attributes {
+					self.check_attribute(attribute, owner);
+				}
+				return;
+			}
+			#[cfg(feature = "preview")]
+			IRAttribute::Preview(_) => {
+				if self.version.minor != PREVIEW_MINOR_VERSION {
+					self.errors.push(VersionError::Preview {
+						what: format!("{owner} {name} attribute"),
+					});
+				}
+				return;
+			}
+			IRAttribute::Signature(_)
+			| IRAttribute::EnclosingMethod { .. }
+			| IRAttribute::LocalVariableTypeTable { .. }
+			| IRAttribute::RuntimeVisibleAnnotations { .. }
+			| IRAttribute::RuntimeInvisibleAnnotations { .. }
+			| IRAttribute::RuntimeVisibleParameterAnnotations { .. }
+			| IRAttribute::RuntimeInvisibleParameterAnnotations { .. }
+			| IRAttribute::AnnotationDefault { .. } => 49,
+			IRAttribute::StackMapTable(_) => 50,
+			IRAttribute::BootstrapMethods { .. } => 51,
+			IRAttribute::MethodParameters { .. }
+			| IRAttribute::RuntimeVisibleTypeAnnotations { .. }
+			| IRAttribute::RuntimeInvisibleTypeAnnotations { .. } => 52,
+			IRAttribute::Module { .. } | IRAttribute::ModulePackages { .. } | IRAttribute::ModuleMainClass { .. } => 53,
+			IRAttribute::NestHost(_) | IRAttribute::NestMembers { .. } => 55,
+			IRAttribute::Record { .. } => 60,
+			IRAttribute::PermittedSubclasses { .. } => 61,
+			_ => return,
+		};
+		self.require(required, || format!("{owner} {name} attribute"));
+	}
+
+	fn check_code(&mut self, code: &[u8], owner: &str) {
+		let mut pc = 0;
+		while pc < code.len() {
+			let Ok(len) = instruction_len(code, pc) else {
+				// malformed code is for the verifier to report
+				return;
+			};
+			let operand = || u16::from_be_bytes([code[pc + 1], code[pc + 2]]);
+			let what = |instruction: &str| format!("{owner} {instruction} at {pc}");
+			match code[pc] {
+				// jsr, ret, jsr_w and wide ret
+				0xA8 | 0xA9 | 0xC9 => self.forbid(51, what(if code[pc] == 0xA9 { "ret" } else { "jsr" })),
+				0xC4 if code[pc + 1] == 0xA9 => self.forbid(51, what("ret")),
+				// invokedynamic
+				0xBA => self.require(51, || what("invokedynamic")),
+				// invokespecial and invokestatic of an interface method
+				0xB7 | 0xB8 if matches!(self.tag(operand()), Some(IRCpTag::InterfaceMethodRef { .. })) => {
+					self.require(52, || what("interface method call"))
+				}
+				// ldc, ldc_w: loading classes was added later, the other new constants are checked
+				// with the pool
+				0x12 | 0x13 => {
+					let index = if code[pc] == 0x12 {
+						code[pc + 1] as u16
+					} else {
+						operand()
+					};
+					if matches!(self.tag(index), Some(IRCpTag::Class(_))) {
+						self.require(49, || what("ldc of a class"));
+					}
+				}
+				_ => {}
+			}
+			pc += len;
+		}
+	}
+
+	fn forbid(&mut self, limit: u16, what: String) {
+		if self.version.major >= limit {
+			self.errors.push(VersionError::TooNew { what, limit });
+		}
+	}
+
+	fn tag(&self, index: u16) -> Option<&IRCpTag> {
+		self.class.cp.get((index as usize).wrapping_sub(1))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::test_util::{class, A_MODULE_INFO, CLASSES, HELLO, PAIR};
+
+	#[test]
+	fn fixtures_pass_their_own_version() {
+		for (i, bytes) in CLASSES.iter().enumerate() {
+			let class = class(bytes);
+			assert_eq!(class.validate_for_version(&class.version), [], "class {i}");
+		}
+	}
+
+	#[test]
+	fn too_old() {
+		let java_6 = ClassFileVersion { major: 50, minor: 0 };
+		let errors = class(HELLO).validate_for_version(&java_6);
+		assert!(errors.contains(&VersionError::TooOld {
+			what: "class NestMembers attribute".to_string(),
+			required: 55,
+		}));
+		assert!(errors.contains(&VersionError::TooOld {
+			what: "method stackmapper(ILjava/lang/Object;)V invokedynamic at 33".to_string(),
+			required: 51,
+		}));
+		assert!(errors.contains(&VersionError::TooOld {
+			what: "constant pool entry #25 (InvokeDynamic)".to_string(),
+			required: 51,
+		}));
+		assert!(class(PAIR)
+			.validate_for_version(&ClassFileVersion { major: 59, minor: 0 })
+			.contains(&VersionError::TooOld {
+				what: "class Record attribute".to_string(),
+				required: 60,
+			}));
+		assert!(class(A_MODULE_INFO)
+			.validate_for_version(&ClassFileVersion { major: 52, minor: 0 })
+			.contains(&VersionError::TooOld {
+				what: "module class flag".to_string(),
+				required: 53,
+			}));
+	}
+
+	#[test]
+	fn too_new() {
+		let java_6 = ClassFileVersion { major: 50, minor: 0 };
+		let errors = class(HELLO).validate_for_version(&java_6);
+
+		// jsr 0 in place of main's getstatic, which Java 6 still allows
+		let mut hello = class(HELLO);
+		let main = hello.find_method_mut("main", "([Ljava/lang/String;)V").unwrap();
+		let IRAttribute::Code(code) = &mut main.attributes[0].attr else {
+			panic!("expected code");
+		};
+		code.code[..3].copy_from_slice(&[0xA8, 0, 0]);
+		assert_eq!(hello.validate_for_version(&java_6), errors);
+		assert_eq!(
+			hello.validate_for_version(&hello.version),
+			[VersionError::TooNew {
+				what: "method main([Ljava/lang/String;)V jsr at 0".to_string(),
+				limit: 51,
+			}]
+		);
+	}
+}
@@ -0,0 +1,22 @@
+//! Prints a javap-style listing of class files, see `maya_classfile_ir::disasm`.
+//!
+//! Usage: `cargo run -p maya-disasm -- <file.class>...`
+
+use maya_classfile_io::IOClassFile;
+use maya_classfile_ir::{disasm::disassemble, IRClassFile};
+
+fn main() -> eyre::Result<()> {
+	let paths: Vec<String> = std::env::args().skip(1).collect();
+	if paths.is_empty() {
+		eyre::bail!("usage: maya-disasm <file.class>...");
+	}
+
+	for path in &paths {
+		let class = IRClassFile::from_io(IOClassFile::read_from_path(path)?)?;
+		if paths.len() > 1 {
+			println!("Classfile {path}");
+		}
+		print!("{}", disassemble(&class));
+	}
+	Ok(())
+}
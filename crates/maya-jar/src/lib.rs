@@ -0,0 +1,311 @@
+//! Reading and writing `.jar` files.
+
+pub mod manifest;
+#[cfg(feature = "rayon")]
+mod parallel;
+mod remap;
+mod resolve;
+mod writer;
+
+use std::{
+	fs::File,
+	io::{BufReader, Read, Seek},
+	path::Path,
+};
+
+use manifest::MANIFEST_PATH;
+pub use manifest::{Manifest, ManifestError};
+use maya_classfile_io::{IOClassFile, IOClassfileError};
+use maya_classfile_ir::{class_pool::IRClassfileError, interner::StringInterner, stats::Stats, IRClassFile};
+#[cfg(feature = "rayon")]
+pub use parallel::parse_jar;
+pub use remap::remap_jar;
+pub use resolve::{jdk_class_path, JmodResolver};
+use thiserror::Error;
+pub use writer::{Compression, JarWriter};
+use zip::{result::ZipError, ZipArchive};
+
+#[derive(Debug, Error)]
+pub enum JarError {
+	#[error("Zip error: {0}")]
+	Zip(#[from] ZipError),
+	#[error("IO Error: {0}")]
+	IO(#[from] std::io::Error),
+	#[error("{name}: {source}")]
+	Classfile { name: String, source: IOClassfileError },
+	#[error("{name}: {source}")]
+	IR { name: String, source: IRClassfileError },
+	#[error("Invalid manifest: {0}")]
+	Manifest(#[from] ManifestError),
+}
+
+#[derive(Debug)]
+pub enum JarEntry {
+	Class { name: String, class: IOClassFile },
+	Resource { name: String, data: Vec<u8> },
+}
+
+impl JarEntry {
+	pub fn name(&self) -> &str {
+		match self {
+			Self::Class { name, .. } | Self::Resource { name, .. } => name,
+		}
+	}
+
+	/// Lifts a class entry to the IR, `None` for resources.
+	pub fn into_ir(self) -> Result<Option<IRClassFile>, JarError> {
+		match self {
+			Self::Class { name, class } => IRClassFile::from_io(class)
+				.map(Some)
+				.map_err(|source| JarError::IR { name, source }),
+			Self::Resource { .. } => Ok(None),
+		}
+	}
+
+	/// Like [`JarEntry::into_ir`], sharing strings with the other classes lifted with `interner`.
+	pub fn into_ir_interned(self, interner: &mut StringInterner) -> Result<Option<IRClassFile>, JarError> {
+		match self {
+			Self::Class { name, class } => IRClassFile::from_io_interned(class, interner)
+				.map(Some)
+				.map_err(|source| JarError::IR { name, source }),
+			Self::Resource { .. } => Ok(None),
+		}
+	}
+}
+
+pub struct JarReader<R> {
+	archive: ZipArchive<R>,
+}
+
+impl JarReader<BufReader<File>> {
+	pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, JarError> {
+		Self::new(BufReader::new(File::open(path)?))
+	}
+}
+
+impl<R: Read + Seek> JarReader<R> {
+	pub fn new(reader: R) -> Result<Self, JarError> {
+		Ok(Self {
+			archive: ZipArchive::new(reader)?,
+		})
+	}
+
+	/// Number of zip entries, directories included.
+	pub fn len(&self) -> usize {
+		self.archive.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.archive.is_empty()
+	}
+
+	pub fn names(&self) -> impl Iterator<Item = &str> {
+		self.archive.file_names()
+	}
+
+	pub fn manifest(&mut self) -> Result<Option<Manifest>, JarError> {
+		let mut file = match self.archive.by_name(MANIFEST_PATH) {
+			Ok(file) => file,
+			Err(ZipError::FileNotFound) => return Ok(None),
+			Err(e) => return Err(e.into()),
+		};
+
+		let mut text = String::new();
+		file.read_to_string(&mut text)?;
+		Ok(Some(Manifest::parse(&text)?))
+	}
+
+	/// The contents of the file called `name`, `None` if the jar has no such file.
+	pub fn read_file(&mut self, name: &str) -> Result<Option<Vec<u8>>, JarError> {
+		let mut file = match self.archive.by_name(name) {
+			Ok(file) => file,
+			Err(ZipError::FileNotFound) => return Ok(None),
+			Err(e) => return Err(e.into()),
+		};
+
+		let mut data = Vec::new();
+		file.read_to_end(&mut data)?;
+		Ok(Some(data))
+	}
+
+	/// Reads the entry at `index`. `.class` files are parsed, directories are `None`.
+	pub fn entry(&mut self, index: usize) -> Result<Option<JarEntry>, JarError> {
+		let mut file = self.archive.by_index(index)?;
+		if file.is_dir() {
+			return Ok(None);
+		}
+
+		let name = file.name().to_string();
+		let mut data = Vec::new();
+		file.read_to_end(&mut data)?;
+
+		if !name.ends_with(".class") {
+			return Ok(Some(JarEntry::Resource { name, data }));
+		}
+
+		match IOClassFile::read_from_slice(&data) {
+			Ok(class) => Ok(Some(JarEntry::Class { name, class })),
+			Err(source) => Err(JarError::Classfile { name, source }),
+		}
+	}
+
+	/// Every file in the jar, in archive order.
+	pub fn entries(&mut self) -> impl Iterator<Item = Result<JarEntry, JarError>> + '_ {
+		(0..self.len()).filter_map(|i| self.entry(i).transpose())
+	}
+
+	/// Every class in the jar, lifted to the IR.
+	pub fn ir_classes(&mut self) -> impl Iterator<Item = Result<IRClassFile, JarError>> + '_ {
+		self.entries()
+			.filter_map(|entry| entry.and_then(JarEntry::into_ir).transpose())
+	}
+
+	/// The [`Stats`] of every class in the jar added up.
+	pub fn stats(&mut self) -> Result<Stats, JarError> {
+		let mut stats = Stats::default();
+		for class in self.ir_classes() {
+			let class = class?;
+			let class_stats = class.stats().map_err(|source| JarError::IR {
+				name: class.class_name().to_string(),
+				source,
+			})?;
+			stats.merge(&class_stats);
+		}
+		Ok(stats)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::io::{Cursor, Write};
+
+	use zip::{write::SimpleFileOptions, ZipWriter};
+
+	use super::*;
+
+	const SIMPLE: &[u8] = include_bytes!("../../maya-test-bin/data/out/a/a/Simple.class");
+
+	fn test_jar() -> Vec<u8> {
+		let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+		let options = SimpleFileOptions::default();
+		zip.start_file(MANIFEST_PATH, options).unwrap();
+		zip.write_all(b"Manifest-Version: 1.0\r\nMain-Class: a.Simple\r\n\r\n")
+			.unwrap();
+		zip.add_directory("a/", options).unwrap();
+		zip.start_file("a/Simple.class", options).unwrap();
+		zip.write_all(SIMPLE).unwrap();
+		zip.start_file("a/data.txt", options).unwrap();
+		zip.write_all(b"hello").unwrap();
+		zip.finish().unwrap().into_inner()
+	}
+
+	#[test]
+	fn read_jar() {
+		let mut jar = JarReader::new(Cursor::new(test_jar())).unwrap();
+		assert_eq!(jar.manifest().unwrap().unwrap().main_class(), Some("a.Simple"));
+
+		let entries = jar.entries().collect::<Result<Vec<_>, _>>().unwrap();
+		let names = entries.iter().map(JarEntry::name).collect::<Vec<_>>();
+		assert_eq!(names, [MANIFEST_PATH, "a/Simple.class", "a/data.txt"]);
+		assert!(matches!(&entries[1], JarEntry::Class { class, .. } if class.major_version > 0));
+
+		let classes = jar.ir_classes().collect::<Result<Vec<_>, _>>().unwrap();
+		assert_eq!(classes.len(), 1);
+
+		let stats = jar.stats().unwrap();
+		assert_eq!(stats.classes, 1);
+		assert_eq!(stats.size, SIMPLE.len() as u64);
+	}
+
+	#[test]
+	fn write_jar() {
+		let mut manifest = Manifest::default();
+		manifest.main.set("Main-Class", "a.Simple");
+
+		for compression in [Compression::Stored, Compression::Deflated] {
+			let mut writer = JarWriter::new(Cursor::new(Vec::new()), &manifest).unwrap();
+			writer.set_compression(compression);
+			writer
+				.add_class(&IOClassFile::read_from_slice(SIMPLE).unwrap())
+				.unwrap();
+			writer.add_resource("a/data.txt", b"hello").unwrap();
+			let bytes = writer.finish().unwrap().into_inner();
+
+			let mut jar = JarReader::new(Cursor::new(bytes)).unwrap();
+			let read_manifest = jar.manifest().unwrap().unwrap();
+			assert_eq!(read_manifest.main.get("Manifest-Version"), Some("1.0"));
+			assert_eq!(read_manifest.main_class(), Some("a.Simple"));
+
+			let names = jar.names().collect::<Vec<_>>();
+			assert_eq!(names, ["META-INF/", MANIFEST_PATH, "a/Simple.class", "a/data.txt"]);
+
+			let Some(JarEntry::Class { class, .. }) = jar.entry(2).unwrap() else {
+				panic!("expected a class");
+			};
+			let mut written = Vec::new();
+			class.write(&mut written).unwrap();
+			assert_eq!(written, SIMPLE);
+		}
+	}
+
+	#[test]
+	fn remap_classes() {
+		let mut mappings = maya_classfile_ir::remap::Mappings::new();
+		mappings.add_class("a/Simple", "b/Renamed");
+
+		let mut jar = JarReader::new(Cursor::new(test_jar())).unwrap();
+		let bytes = remap_jar(&mut jar, Cursor::new(Vec::new()), &mappings)
+			.unwrap()
+			.into_inner();
+
+		let mut jar = JarReader::new(Cursor::new(bytes)).unwrap();
+		assert_eq!(jar.manifest().unwrap().unwrap().main_class(), Some("b.Renamed"));
+		let names = jar.names().collect::<Vec<_>>();
+		assert_eq!(names, ["META-INF/", MANIFEST_PATH, "b/Renamed.class", "a/data.txt"]);
+		let classes = jar.ir_classes().collect::<Result<Vec<_>, _>>().unwrap();
+		assert_eq!(classes[0].class_name(), "b/Renamed");
+	}
+
+	#[test]
+	fn resolve_classes() {
+		use maya_classfile_ir::resolve::{ClassHierarchy, ClassResolver};
+
+		let mut jar = JarReader::new(Cursor::new(test_jar())).unwrap();
+		assert_eq!(jar.read_file("a/data.txt").unwrap().unwrap(), b"hello");
+		assert!(jar.read_file("a/missing.txt").unwrap().is_none());
+		assert_eq!(jar.read_class("a/Simple").unwrap().unwrap(), SIMPLE);
+
+		let mut hierarchy = ClassHierarchy::new(jar);
+		assert_eq!(hierarchy.super_classes("a/Simple").unwrap(), ["java/lang/Object"]);
+
+		let mut jmod = Cursor::new(b"JM\x01\x00".to_vec());
+		jmod.set_position(4);
+		let mut zip = ZipWriter::new(jmod);
+		zip.start_file("classes/a/Simple.class", SimpleFileOptions::default())
+			.unwrap();
+		zip.write_all(SIMPLE).unwrap();
+		let jmod = zip.finish().unwrap().into_inner();
+		assert!(jmod.starts_with(b"JM"));
+		let mut jmod = JmodResolver::new(Cursor::new(jmod)).unwrap();
+		assert_eq!(jmod.load("a/Simple").unwrap().unwrap().class_name(), "a/Simple");
+		assert!(jmod.read_class("classes/a/Simple").unwrap().is_none());
+	}
+
+	#[cfg(feature = "rayon")]
+	#[test]
+	fn parse_jar_in_parallel() {
+		let path = std::env::temp_dir().join(format!("maya-jar-{}.jar", std::process::id()));
+		std::fs::write(&path, test_jar()).unwrap();
+		let classes = parse_jar(&path);
+		let previous = maya_bytes::set_max_alloc(Some(16));
+		let capped = parse_jar(&path);
+		maya_bytes::set_max_alloc(previous);
+		std::fs::remove_file(&path).unwrap();
+
+		let classes = classes.unwrap();
+		assert_eq!(classes.len(), 1);
+		assert!(classes[0].is_ok());
+		// the workers lift under the caller's cap
+		assert!(capped.unwrap()[0].is_err());
+	}
+}
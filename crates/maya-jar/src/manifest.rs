@@ -0,0 +1,161 @@
+//! `META-INF/MANIFEST.MF` parsing.
+//! https://docs.oracle.com/en/java/javase/21/docs/specs/jar/jar.html#jar-manifest
+
+use thiserror::Error;
+
+pub const MANIFEST_PATH: &str = "META-INF/MANIFEST.MF";
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum ManifestError {
+	#[error("continuation line without an attribute")]
+	DanglingContinuation,
+	#[error("malformed manifest line: {0:?}")]
+	MalformedLine(String),
+	#[error("manifest section without a Name")]
+	UnnamedSection,
+}
+
+/// Attributes in file order. Lookups are case-insensitive, like the spec asks for.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Attributes(pub Vec<(String, String)>);
+
+impl Attributes {
+	pub fn get(&self, name: &str) -> Option<&str> {
+		self.0
+			.iter()
+			.find(|(k, _)| k.eq_ignore_ascii_case(name))
+			.map(|(_, v)| v.as_str())
+	}
+
+	/// Replaces the value of an existing attribute, or appends it.
+	pub fn set(&mut self, name: &str, value: impl Into<String>) {
+		match self.0.iter_mut().find(|(k, _)| k.eq_ignore_ascii_case(name)) {
+			Some((_, v)) => *v = value.into(),
+			None => self.0.push((name.to_string(), value.into())),
+		}
+	}
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Manifest {
+	pub main: Attributes,
+	/// Per-entry sections, keyed by their `Name` attribute.
+	pub sections: Vec<(String, Attributes)>,
+}
+
+impl Manifest {
+	pub fn parse(text: &str) -> Result<Self, ManifestError> {
+		let mut manifest = Manifest::default();
+		let mut current = Attributes::default();
+		let mut in_main = true;
+
+		for line in text.lines() {
+			let line = line.strip_suffix('\r').unwrap_or(line);
+			if line.is_empty() {
+				manifest.finish_section(std::mem::take(&mut current), in_main)?;
+				in_main = false;
+			} else if let Some(continuation) = line.strip_prefix(' ') {
+				let (_, value) = current.0.last_mut().ok_or(ManifestError::DanglingContinuation)?;
+				value.push_str(continuation);
+			} else {
+				let (name, value) = line
+					.split_once(": ")
+					.ok_or_else(|| ManifestError::MalformedLine(line.to_string()))?;
+				current.0.push((name.to_string(), value.to_string()));
+			}
+		}
+		manifest.finish_section(current, in_main)?;
+
+		Ok(manifest)
+	}
+
+	fn finish_section(&mut self, attrs: Attributes, main: bool) -> Result<(), ManifestError> {
+		if main {
+			self.main = attrs;
+		} else if !attrs.0.is_empty() {
+			let name = attrs.get("Name").ok_or(ManifestError::UnnamedSection)?.to_string();
+			self.sections.push((name, attrs));
+		}
+		Ok(())
+	}
+
+	/// Serializes with CRLF line endings, wrapping lines at the 72 byte limit.
+	pub fn to_bytes(&self) -> Vec<u8> {
+		let mut out = Vec::new();
+		write_section(&mut out, &self.main);
+		for (_, attrs) in &self.sections {
+			write_section(&mut out, attrs);
+		}
+		out
+	}
+
+	pub fn main_class(&self) -> Option<&str> {
+		self.main.get("Main-Class")
+	}
+}
+
+fn write_section(out: &mut Vec<u8>, attrs: &Attributes) {
+	for (name, value) in &attrs.0 {
+		let line = format!("{name}: {value}");
+		let mut rest = line.as_str();
+		let mut first = true;
+		while !rest.is_empty() {
+			// continuation lines start with a space, which counts towards the limit
+			let limit = if first { 72 } else { 71 };
+			let mut split = rest.len().min(limit);
+			while !rest.is_char_boundary(split) {
+				split -= 1;
+			}
+
+			if !first {
+				out.push(b' ');
+			}
+			out.extend_from_slice(&rest.as_bytes()[..split]);
+			out.extend_from_slice(b"\r\n");
+			rest = &rest[split..];
+			first = false;
+		}
+	}
+	out.extend_from_slice(b"\r\n");
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn parse() {
+		let text = "Manifest-Version: 1.0\r\nMain-Class: a.Hel\r\n lo\r\nCreated-By: test\r\n\r\nName: a/Simple.class\r\nSHA-256-Digest: abc\r\n\r\n";
+		let manifest = Manifest::parse(text).unwrap();
+		assert_eq!(manifest.main_class(), Some("a.Hello"));
+		assert_eq!(manifest.main.get("created-by"), Some("test"));
+		assert_eq!(manifest.sections.len(), 1);
+		assert_eq!(manifest.sections[0].0, "a/Simple.class");
+		assert_eq!(manifest.sections[0].1.get("SHA-256-Digest"), Some("abc"));
+	}
+
+	#[test]
+	fn parse_errors() {
+		assert_eq!(Manifest::parse(" lo\r\n"), Err(ManifestError::DanglingContinuation));
+		assert_eq!(
+			Manifest::parse("Main-Class:a.Hello\r\n"),
+			Err(ManifestError::MalformedLine("Main-Class:a.Hello".to_string()))
+		);
+		assert_eq!(
+			Manifest::parse("Manifest-Version: 1.0\r\n\r\nSHA-256-Digest: abc\r\n"),
+			Err(ManifestError::UnnamedSection)
+		);
+	}
+
+	#[test]
+	fn write_wraps_long_lines() {
+		let mut manifest = Manifest::default();
+		manifest.main.set("Manifest-Version", "1.0");
+		manifest.main.set("Class-Path", "lib/".repeat(40));
+
+		let bytes = manifest.to_bytes();
+		let text = std::str::from_utf8(&bytes).unwrap();
+		assert!(text.split("\r\n").all(|line| line.len() <= 72));
+		assert_eq!(Manifest::parse(text).unwrap(), manifest);
+	}
+}
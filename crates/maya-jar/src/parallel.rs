@@ -0,0 +1,44 @@
+use std::path::Path;
+
+use maya_classfile_ir::IRClassFile;
+use rayon::prelude::*;
+
+use crate::{JarError, JarReader};
+
+/// Parses every class in the jar at `path` across the rayon thread pool, in archive order.
+///
+/// Each worker opens the jar itself so decompression and lifting to the IR run in parallel too,
+/// under the allocation cap of the calling thread.
+pub fn parse_jar<P: AsRef<Path>>(path: P) -> Result<Vec<Result<IRClassFile, JarError>>, JarError> {
+	let path = path.as_ref();
+	let jar = JarReader::open(path)?;
+	let classes = (0..jar.len())
+		.filter(|&i| {
+			jar.archive
+				.name_for_index(i)
+				.is_some_and(|name| name.ends_with(".class"))
+		})
+		.collect::<Vec<_>>();
+
+	// enough chunks to balance the load without reopening the jar for every class
+	let chunk_len = classes.len().div_ceil(rayon::current_num_threads() * 4).max(1);
+	let cap = maya_bytes::max_alloc();
+	let parsed = classes
+		.par_chunks(chunk_len)
+		.map(|chunk| -> Result<Vec<_>, JarError> {
+			maya_bytes::with_max_alloc(cap, || {
+				let mut jar = JarReader::open(path)?;
+				Ok(chunk
+					.iter()
+					.filter_map(|&i| match jar.entry(i) {
+						Ok(Some(entry)) => entry.into_ir().transpose(),
+						Ok(None) => None,
+						Err(e) => Some(Err(e)),
+					})
+					.collect())
+			})
+		})
+		.collect::<Result<Vec<_>, _>>()?;
+
+	Ok(parsed.into_iter().flatten().collect())
+}
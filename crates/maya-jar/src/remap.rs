@@ -0,0 +1,43 @@
+use std::io::{Read, Seek, Write};
+
+use maya_classfile_ir::{class_pool::IRClassfileError, remap::Mappings, IRClassFile};
+
+use crate::{manifest::MANIFEST_PATH, JarEntry, JarError, JarReader, JarWriter};
+
+/// Copies every entry of `jar` into a new jar written to `writer`, remapping the classes with
+/// `mappings`. Classes are stored under their new names and the manifest's `Main-Class` follows its
+/// class. Resources are copied as is.
+pub fn remap_jar<R: Read + Seek, W: Write + Seek>(
+	jar: &mut JarReader<R>,
+	writer: W,
+	mappings: &Mappings,
+) -> Result<W, JarError> {
+	let mut manifest = jar.manifest()?.unwrap_or_default();
+	if let Some(main) = manifest
+		.main_class()
+		.and_then(|main| mappings.map_class(&main.replace('.', "/")))
+	{
+		manifest.main.set("Main-Class", main.replace('/', "."));
+	}
+
+	let mut writer = JarWriter::new(writer, &manifest)?;
+	for entry in jar.entries() {
+		match entry? {
+			JarEntry::Class { name, class } => {
+				let remapped = IRClassFile::from_io(class).and_then(|mut class| {
+					class.remap(mappings)?;
+					// classes with attributes we can't renumber just keep the stale entries
+					match class.compact_constant_pool() {
+						Ok(_) | Err(IRClassfileError::OpaqueAttribute(_)) => Ok(class),
+						Err(e) => Err(e),
+					}
+				});
+				let class = remapped.map_err(|source| JarError::IR { name, source })?;
+				writer.add_ir_class(&class)?;
+			}
+			JarEntry::Resource { name, .. } if name == MANIFEST_PATH => {}
+			JarEntry::Resource { name, data } => writer.add_resource(&name, &data)?,
+		}
+	}
+	writer.finish()
+}
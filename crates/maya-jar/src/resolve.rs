@@ -0,0 +1,76 @@
+use std::{
+	fs::File,
+	io::{BufReader, Read, Seek},
+	path::Path,
+};
+
+use maya_classfile_ir::resolve::{ClassPath, ClassResolver, ResolveError};
+
+use crate::{JarError, JarReader};
+
+impl<R: Read + Seek> ClassResolver for JarReader<R> {
+	fn read_class(&mut self, name: &str) -> Result<Option<Vec<u8>>, ResolveError> {
+		self.read_file(&format!("{name}.class"))
+			.map_err(|e| ResolveError::Provider(Box::new(e)))
+	}
+}
+
+/// A module from a JDK's `jmods` directory. These are zips behind a short header, with the classes
+/// under `classes/`.
+pub struct JmodResolver<R> {
+	jar: JarReader<R>,
+}
+
+impl JmodResolver<BufReader<File>> {
+	pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, JarError> {
+		Self::new(BufReader::new(File::open(path)?))
+	}
+}
+
+impl<R: Read + Seek> JmodResolver<R> {
+	pub fn new(reader: R) -> Result<Self, JarError> {
+		Ok(Self {
+			jar: JarReader::new(reader)?,
+		})
+	}
+}
+
+impl<R: Read + Seek> ClassResolver for JmodResolver<R> {
+	fn read_class(&mut self, name: &str) -> Result<Option<Vec<u8>>, ResolveError> {
+		self.jar
+			.read_file(&format!("classes/{name}.class"))
+			.map_err(|e| ResolveError::Provider(Box::new(e)))
+	}
+}
+
+/// The standard library of the JDK installed at `java_home`: every module in `jmods` for Java 9
+/// and later, `rt.jar` before that.
+pub fn jdk_class_path<P: AsRef<Path>>(java_home: P) -> Result<ClassPath, JarError> {
+	let java_home = java_home.as_ref();
+	let mut path = ClassPath::new();
+
+	let jmods = java_home.join("jmods");
+	if jmods.is_dir() {
+		let mut modules = std::fs::read_dir(&jmods)?
+			.map(|entry| entry.map(|entry| entry.path()))
+			.collect::<Result<Vec<_>, _>>()?;
+		modules.retain(|module| module.extension().is_some_and(|ext| ext == "jmod"));
+		modules.sort();
+		for module in modules {
+			path.push(JmodResolver::open(module)?);
+		}
+		return Ok(path);
+	}
+
+	for rt in [java_home.join("lib/rt.jar"), java_home.join("jre/lib/rt.jar")] {
+		if rt.is_file() {
+			path.push(JarReader::open(rt)?);
+			return Ok(path);
+		}
+	}
+	Err(std::io::Error::new(
+		std::io::ErrorKind::NotFound,
+		format!("no jmods or rt.jar in {}", java_home.display()),
+	)
+	.into())
+}
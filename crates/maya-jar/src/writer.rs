@@ -0,0 +1,99 @@
+use std::{
+	fs::File,
+	io::{BufWriter, Seek, Write},
+	path::Path,
+};
+
+use maya_classfile_io::IOClassFile;
+use maya_classfile_ir::IRClassFile;
+use zip::{write::SimpleFileOptions, CompressionMethod, ZipWriter};
+
+use crate::{manifest::MANIFEST_PATH, JarError, Manifest};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Compression {
+	Stored,
+	#[default]
+	Deflated,
+}
+
+/// Writes a jar with the manifest as its first entry, like the `jar` tool does.
+/// Entry timestamps are left at the zip epoch so the output is reproducible.
+pub struct JarWriter<W: Write + Seek> {
+	zip: ZipWriter<W>,
+	compression: Compression,
+}
+
+impl JarWriter<BufWriter<File>> {
+	pub fn create<P: AsRef<Path>>(path: P, manifest: &Manifest) -> Result<Self, JarError> {
+		Self::new(BufWriter::new(File::create(path)?), manifest)
+	}
+}
+
+impl<W: Write + Seek> JarWriter<W> {
+	pub fn new(writer: W, manifest: &Manifest) -> Result<Self, JarError> {
+		let mut jar = Self {
+			zip: ZipWriter::new(writer),
+			compression: Compression::default(),
+		};
+
+		let mut manifest = manifest.clone();
+		if manifest.main.get("Manifest-Version").is_none() {
+			manifest
+				.main
+				.0
+				.insert(0, ("Manifest-Version".to_string(), "1.0".to_string()));
+		}
+
+		jar.zip.add_directory("META-INF/", jar.options())?;
+		jar.add_resource(MANIFEST_PATH, &manifest.to_bytes())?;
+		Ok(jar)
+	}
+
+	/// Compression for entries added from now on.
+	pub fn set_compression(&mut self, compression: Compression) {
+		self.compression = compression;
+	}
+
+	fn options(&self) -> SimpleFileOptions {
+		SimpleFileOptions::default().compression_method(match self.compression {
+			Compression::Stored => CompressionMethod::Stored,
+			Compression::Deflated => CompressionMethod::Deflated,
+		})
+	}
+
+	/// Adds `class` under the path of its own class name.
+	pub fn add_class(&mut self, class: &IOClassFile) -> Result<(), JarError> {
+		let name = class.this_class_name().map_err(|source| JarError::Classfile {
+			name: "<unnamed class>".to_string(),
+			source,
+		})?;
+
+		let data = class.to_bytes().map_err(|source| JarError::Classfile {
+			name: name.clone(),
+			source,
+		})?;
+		self.add_resource(&format!("{name}.class"), &data)
+	}
+
+	/// Lowers `class` and adds it like [`add_class`](Self::add_class).
+	pub fn add_ir_class(&mut self, class: &IRClassFile) -> Result<(), JarError> {
+		let name = class.this_class.data.data.to_string();
+		let class = class.to_io().map_err(|source| JarError::IR {
+			name: name.clone(),
+			source,
+		})?;
+		self.add_class(&class)
+	}
+
+	pub fn add_resource(&mut self, path: &str, data: &[u8]) -> Result<(), JarError> {
+		self.zip.start_file(path, self.options())?;
+		self.zip.write_all(data)?;
+		Ok(())
+	}
+
+	/// Writes the central directory and hands back the writer.
+	pub fn finish(self) -> Result<W, JarError> {
+		Ok(self.zip.finish()?)
+	}
+}
@@ -0,0 +1,310 @@
+use std::{path::Path, process::Command};
+
+use eyre::{bail, eyre};
+
+/// The subset of `javap -v -p` output that we know how to compare against.
+#[derive(Debug, Default)]
+pub struct JavapClass {
+	pub minor_version: u16,
+	pub major_version: u16,
+	pub access_flags: u16,
+	pub this_class: u16,
+	pub super_class: u16,
+	pub interface_count: u16,
+	pub field_count: u16,
+	pub method_count: u16,
+	pub attribute_count: u16,
+	pub cp: Vec<JavapCpEntry>,
+	pub members: Vec<JavapMember>,
+}
+
+#[derive(Debug)]
+pub struct JavapCpEntry {
+	pub index: u16,
+	pub kind: String,
+	/// Raw text after the kind, e.g. `#2.#3` or the Utf8 value.
+	pub value: String,
+}
+
+#[derive(Debug, Default)]
+pub struct JavapMember {
+	pub descriptor: String,
+	pub access_flags: u16,
+	pub max_stack: Option<u16>,
+	pub max_locals: Option<u16>,
+	pub code: Vec<JavapInstruction>,
+}
+
+impl JavapMember {
+	pub fn is_method(&self) -> bool {
+		self.descriptor.starts_with('(')
+	}
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct JavapInstruction {
+	pub offset: u32,
+	pub mnemonic: String,
+}
+
+pub fn run(path: &Path) -> eyre::Result<JavapClass> {
+	let output = Command::new("javap").arg("-v").arg("-p").arg(path).output()?;
+	if !output.status.success() {
+		bail!("javap failed: {}", String::from_utf8_lossy(&output.stderr));
+	}
+
+	parse(&String::from_utf8_lossy(&output.stdout))
+}
+
+fn parse_flags(line: &str) -> eyre::Result<u16> {
+	// flags: (0x0021) ACC_PUBLIC, ACC_SUPER
+	let start = line.find("(0x").ok_or_else(|| eyre!("no flags in: {line}"))? + 3;
+	Ok(u16::from_str_radix(&line[start..start + 4], 16)?)
+}
+
+fn parse_cp_ref(line: &str) -> eyre::Result<u16> {
+	// this_class: #7                          // a/Simple
+	let start = line.find('#').ok_or_else(|| eyre!("no cp ref in: {line}"))? + 1;
+	let digits: String = line[start..].chars().take_while(char::is_ascii_digit).collect();
+	Ok(digits.parse()?)
+}
+
+fn parse_counts(line: &str, class: &mut JavapClass) -> eyre::Result<()> {
+	// interfaces: 0, fields: 2, methods: 6, attributes: 5
+	for part in line.split(',') {
+		let (name, value) = part.split_once(':').ok_or_else(|| eyre!("bad count: {part}"))?;
+		let value = value.trim().parse()?;
+		match name.trim() {
+			"interfaces" => class.interface_count = value,
+			"fields" => class.field_count = value,
+			"methods" => class.method_count = value,
+			"attributes" => class.attribute_count = value,
+			n => bail!("unknown count: {n}"),
+		}
+	}
+	Ok(())
+}
+
+fn parse_cp_entry(line: &str) -> Option<JavapCpEntry> {
+	// #1 = Methodref          #2.#3          // java/lang/Record."<init>":()V
+	let line = line.trim_start().strip_prefix('#')?;
+	let (index, rest) = line.split_once(" = ")?;
+	let index = index.trim().parse().ok()?;
+	let (kind, value) = rest.split_once(' ').unwrap_or((rest, ""));
+	let value = value.trim_start();
+	let value = if kind == "Utf8" {
+		value.to_string()
+	} else {
+		value.split("//").next().unwrap_or_default().trim().to_string()
+	};
+
+	Some(JavapCpEntry {
+		index,
+		kind: kind.to_string(),
+		value,
+	})
+}
+
+fn parse_instruction(line: &str) -> Option<JavapInstruction> {
+	// 1: invokespecial #1                  // Method java/lang/Object."<init>":()V
+	let (offset, rest) = line.trim_start().split_once(": ")?;
+	let offset = offset.parse().ok()?;
+	let mnemonic: String = rest
+		.chars()
+		.take_while(|c| c.is_ascii_alphanumeric() || *c == '_')
+		.collect();
+	if !mnemonic.starts_with(|c: char| c.is_ascii_lowercase()) {
+		return None;
+	}
+
+	Some(JavapInstruction { offset, mnemonic })
+}
+
+pub fn parse(output: &str) -> eyre::Result<JavapClass> {
+	let mut class = JavapClass::default();
+	let mut lines = output.lines().peekable();
+
+	while let Some(line) = lines.next() {
+		let trimmed = line.trim();
+		if let Some(v) = trimmed.strip_prefix("minor version: ") {
+			class.minor_version = v.parse()?;
+		} else if let Some(v) = trimmed.strip_prefix("major version: ") {
+			class.major_version = v.parse()?;
+		} else if trimmed.starts_with("flags: ") {
+			class.access_flags = parse_flags(trimmed)?;
+		} else if trimmed.starts_with("this_class: ") {
+			class.this_class = parse_cp_ref(trimmed)?;
+		} else if trimmed.starts_with("super_class: ") {
+			class.super_class = parse_cp_ref(trimmed)?;
+		} else if trimmed.starts_with("interfaces: ") {
+			parse_counts(trimmed, &mut class)?;
+		} else if trimmed == "Constant pool:" {
+			while let Some(entry) = lines.peek().and_then(|l| parse_cp_entry(l)) {
+				class.cp.push(entry);
+				lines.next();
+			}
+		} else if trimmed == "{" {
+			break;
+		}
+	}
+
+	let mut member: Option<JavapMember> = None;
+	let mut in_code = false;
+	let mut in_switch = false;
+	for line in lines {
+		let trimmed = line.trim();
+		if trimmed == "}" && !in_switch && line.starts_with('}') {
+			break;
+		}
+
+		if let Some(descriptor) = trimmed.strip_prefix("descriptor: ") {
+			if let Some(m) = member.take() {
+				class.members.push(m);
+			}
+			member = Some(JavapMember {
+				descriptor: descriptor.to_string(),
+				..Default::default()
+			});
+			in_code = false;
+			continue;
+		}
+
+		let Some(m) = member.as_mut() else {
+			continue;
+		};
+
+		if in_switch {
+			in_switch = trimmed != "}";
+		} else if trimmed.starts_with("flags: ") && m.code.is_empty() && !in_code {
+			m.access_flags = parse_flags(trimmed)?;
+		} else if trimmed == "Code:" {
+			in_code = true;
+		} else if in_code && trimmed.starts_with("stack=") {
+			for part in trimmed.split(", ") {
+				match part.split_once('=') {
+					Some(("stack", v)) => m.max_stack = Some(v.parse()?),
+					Some(("locals", v)) => m.max_locals = Some(v.parse()?),
+					_ => {}
+				}
+			}
+		} else if in_code {
+			match parse_instruction(line) {
+				Some(insn) => {
+					in_switch = trimmed.ends_with('{') || trimmed.contains("{ //");
+					m.code.push(insn);
+				}
+				None => in_code = false,
+			}
+		}
+	}
+
+	if let Some(m) = member.take() {
+		class.members.push(m);
+	}
+
+	Ok(class)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// `javap -v -p` of a class with a field, a tableswitch and a lookupswitch.
+	const SWITCH: &str = include_str!("../data/Switch.javap");
+
+	fn mnemonics(member: &JavapMember) -> Vec<(u32, &str)> {
+		member
+			.code
+			.iter()
+			.map(|insn| (insn.offset, insn.mnemonic.as_str()))
+			.collect()
+	}
+
+	#[test]
+	fn header() {
+		let class = parse(SWITCH).unwrap();
+		assert_eq!((class.major_version, class.minor_version), (61, 0));
+		assert_eq!(class.access_flags, 0x0021);
+		assert_eq!((class.this_class, class.super_class), (7, 2));
+		assert_eq!(
+			(
+				class.interface_count,
+				class.field_count,
+				class.method_count,
+				class.attribute_count
+			),
+			(0, 1, 3, 1)
+		);
+
+		assert_eq!(class.cp.len(), 18);
+		let entry = &class.cp[0];
+		assert_eq!(
+			(entry.index, entry.kind.as_str(), entry.value.as_str()),
+			(1, "Methodref", "#2.#3")
+		);
+		// Utf8 values are kept whole, even with a `//` in them
+		let entry = &class.cp[4];
+		assert_eq!((entry.kind.as_str(), entry.value.as_str()), ("Utf8", "<init>"));
+		assert_eq!(
+			parse_cp_entry("  #30 = Utf8               http://example.com")
+				.unwrap()
+				.value,
+			"http://example.com"
+		);
+	}
+
+	#[test]
+	fn members() {
+		let class = parse(SWITCH).unwrap();
+		let [field, init, table, lookup] = &class.members[..] else {
+			panic!("{:?}", class.members);
+		};
+		assert!(!field.is_method());
+		assert_eq!((field.descriptor.as_str(), field.access_flags), ("J", 0x0002));
+		assert_eq!(field.max_stack, None);
+		assert!(init.is_method());
+		assert_eq!((init.max_stack, init.max_locals), (Some(1), Some(1)));
+		assert_eq!(mnemonics(init), [(0, "aload_0"), (1, "invokespecial"), (4, "return")]);
+
+		// the case lines of a switch aren't instructions
+		assert_eq!(table.access_flags, 0x0008);
+		assert_eq!(
+			mnemonics(table),
+			[
+				(0, "iload_0"),
+				(1, "tableswitch"),
+				(28, "bipush"),
+				(30, "ireturn"),
+				(31, "bipush"),
+				(33, "ireturn"),
+				(34, "bipush"),
+				(36, "ireturn"),
+				(37, "iconst_m1"),
+				(38, "ireturn")
+			]
+		);
+		assert_eq!(
+			mnemonics(lookup),
+			[
+				(0, "iload_0"),
+				(1, "lookupswitch"),
+				(28, "iconst_1"),
+				(29, "ireturn"),
+				(30, "iconst_2"),
+				(31, "ireturn"),
+				(32, "iconst_0"),
+				(33, "ireturn")
+			]
+		);
+	}
+
+	#[test]
+	fn malformed() {
+		assert!(parse("  flags: ACC_PUBLIC").is_err());
+		assert!(parse("  major version: sixty-one").is_err());
+		assert!(parse("  interfaces: 0, widgets: 2").is_err());
+		assert!(parse("  this_class: none").is_err());
+		assert!(parse_instruction("      LineNumberTable:").is_none());
+		assert!(parse_instruction("                    -100: 28").is_none());
+	}
+}
@@ -0,0 +1,303 @@
+//! Cross-validates maya's classfile parsing against `javap -v -p`.
+//!
+//! Usage: `cargo run -p maya-javap-check -- <dir with .class files>`
+//!
+//! Requires a JDK on PATH. Every mismatch between what javap reports and what
+//! maya-classfile-io/ir parsed is printed, and the process exits non-zero if any were found.
+
+mod javap;
+
+use std::{
+	fmt::Display,
+	io::Cursor,
+	panic::{self, AssertUnwindSafe},
+	path::{Path, PathBuf},
+};
+
+use javap::{JavapClass, JavapInstruction};
+use maya_classfile_io::{class_pool::IOCpTag, IOClassFile};
+use maya_classfile_ir::{attribute::IRAttribute, class_pool::IRCpTag, code::Instructions, IRClassFile};
+
+#[derive(Default)]
+struct Report {
+	mismatches: Vec<String>,
+}
+
+impl Report {
+	fn check<T: PartialEq + Display>(&mut self, what: impl Display, expected: T, got: T) {
+		if expected != got {
+			self.mismatches.push(format!("{what}: javap={expected} maya={got}"));
+		}
+	}
+
+	fn push(&mut self, msg: String) {
+		self.mismatches.push(msg);
+	}
+}
+
+fn io_kind(tag: &IOCpTag) -> &'static str {
+	match tag {
+		IOCpTag::Unusable => "Unusable",
+		IOCpTag::Utf8 { .. } => "Utf8",
+		IOCpTag::Integer { .. } => "Integer",
+		IOCpTag::Float { .. } => "Float",
+		IOCpTag::Long { .. } => "Long",
+		IOCpTag::Double { .. } => "Double",
+		IOCpTag::Class { .. } => "Class",
+		IOCpTag::String { .. } => "String",
+		IOCpTag::FieldRef { .. } => "Fieldref",
+		IOCpTag::MethodRef { .. } => "Methodref",
+		IOCpTag::InterfaceMethodRef { .. } => "InterfaceMethodref",
+		IOCpTag::NameAndType { .. } => "NameAndType",
+		IOCpTag::MethodHandle { .. } => "MethodHandle",
+		IOCpTag::MethodType { .. } => "MethodType",
+		IOCpTag::Dynamic { .. } => "Dynamic",
+		IOCpTag::InvokeDynamic { .. } => "InvokeDynamic",
+		IOCpTag::Module { .. } => "Module",
+		IOCpTag::Package { .. } => "Package",
+	}
+}
+
+/// Renders the operand columns the same way javap does, when the format is unambiguous.
+fn io_value(tag: &IOCpTag) -> Option<String> {
+	Some(match tag {
+		IOCpTag::Integer { bytes } => i32::from_be_bytes(*bytes).to_string(),
+		IOCpTag::Long { bytes } => format!("{}l", i64::from_be_bytes(*bytes)),
+		IOCpTag::Class { name_index } => format!("#{name_index}"),
+		IOCpTag::String { utf8_index } => format!("#{utf8_index}"),
+		IOCpTag::FieldRef {
+			class_index,
+			name_and_ty_index,
+		}
+		| IOCpTag::MethodRef {
+			class_index,
+			name_and_ty_index,
+		}
+		| IOCpTag::InterfaceMethodRef {
+			class_index,
+			name_and_ty_index,
+		} => format!("#{class_index}.#{name_and_ty_index}"),
+		IOCpTag::NameAndType {
+			name_index,
+			descriptor_index,
+		} => format!("#{name_index}:#{descriptor_index}"),
+		IOCpTag::MethodHandle {
+			reference_kind,
+			reference_index,
+		} => format!("{reference_kind}:#{reference_index}"),
+		IOCpTag::MethodType { descriptor_index } => format!("#{descriptor_index}"),
+		IOCpTag::Dynamic {
+			bootstrap_method_attr_index,
+			name_and_ty_index,
+		}
+		| IOCpTag::InvokeDynamic {
+			bootstrap_method_attr_index,
+			name_and_ty_index,
+		} => format!("#{bootstrap_method_attr_index}:#{name_and_ty_index}"),
+		IOCpTag::Module { name_index } | IOCpTag::Package { name_index } => format!("#{name_index}"),
+		_ => return None,
+	})
+}
+
+fn compare_io(javap: &JavapClass, io: &IOClassFile, report: &mut Report) {
+	report.check("minor_version", javap.minor_version, io.minor_version);
+	report.check("major_version", javap.major_version, io.major_version);
+	report.check(
+		"access_flags",
+		format!("{:#06x}", javap.access_flags),
+		format!("{:#06x}", io.access_flags),
+	);
+	report.check("this_class", javap.this_class, io.this_class);
+	report.check("super_class", javap.super_class, io.super_class);
+	report.check("interface_count", javap.interface_count, io.interface_count);
+	report.check("field_count", javap.field_count, io.field_count);
+	report.check("method_count", javap.method_count, io.method_count);
+	report.check("attribute_count", javap.attribute_count, io.attribute_count);
+
+	for entry in &javap.cp {
+		let Some(tag) = io.cp.get(entry.index as usize - 1) else {
+			report.push(format!("cp #{}: javap={} maya=<missing>", entry.index, entry.kind));
+			continue;
+		};
+
+		report.check(format!("cp #{} kind", entry.index), entry.kind.as_str(), io_kind(tag));
+		if let Some(value) = io_value(tag) {
+			report.check(
+				format!("cp #{} value", entry.index),
+				entry.value.as_str(),
+				value.as_str(),
+			);
+		}
+	}
+}
+
+/// javap prints the short forms (`aload_0`) and the wide forms (`iinc_w`), the decoder folds both
+/// into the long form.
+fn normalize_mnemonic(mnemonic: &str) -> &str {
+	let local = |base: &str| base.ends_with("load") || base.ends_with("store");
+	match mnemonic.split_once('_') {
+		Some((base, idx)) if idx.len() == 1 && idx.as_bytes()[0].is_ascii_digit() && local(base) => base,
+		Some((base, "w")) if local(base) || base == "iinc" || base == "ret" => base,
+		_ => mnemonic,
+	}
+}
+
+fn decode_code(cp: &[IRCpTag], code: &[u8]) -> Result<Vec<JavapInstruction>, String> {
+	let mut buffer = Cursor::new(code);
+	let mut insns = Vec::new();
+	while (buffer.position() as usize) < code.len() {
+		let offset = buffer.position() as u32;
+		let insn = panic::catch_unwind(AssertUnwindSafe(|| Instructions::read(cp, &mut buffer)))
+			.map_err(|e| {
+				let msg = e
+					.downcast_ref::<String>()
+					.cloned()
+					.or_else(|| e.downcast_ref::<&str>().map(|s| s.to_string()))
+					.unwrap_or_default();
+				format!("decoder panicked at offset {offset}: {msg}")
+			})?
+			.map_err(|e| format!("decoder failed at offset {offset}: {e}"))?;
+
+		let name = format!("{insn:?}");
+		let mnemonic = name.split(['(', ' ']).next().unwrap_or_default().to_ascii_lowercase();
+		insns.push(JavapInstruction { offset, mnemonic });
+	}
+
+	Ok(insns)
+}
+
+fn compare_ir(javap: &JavapClass, ir: &IRClassFile, report: &mut Report) {
+	let members = ir
+		.fields
+		.iter()
+		.map(|f| (f.access_flags.bits(), &f.descriptor, &f.name, &f.attributes))
+		.chain(
+			ir.methods
+				.iter()
+				.map(|m| (m.access_flags.bits(), &m.descriptor, &m.name, &m.attributes)),
+		);
+
+	for (i, (expected, (access_flags, descriptor, name, attributes))) in javap.members.iter().zip(members).enumerate() {
+		let what = format!("member #{i} ({})", name.data);
+		report.check(
+			format!("{what} descriptor"),
+			expected.descriptor.as_str(),
+			descriptor.data.as_str(),
+		);
+		report.check(format!("{what} access_flags"), expected.access_flags, access_flags);
+
+		if !expected.is_method() {
+			continue;
+		}
+
+		let code = attributes.iter().find_map(|a| match &a.attr {
+			IRAttribute::Code(code) => Some(code),
+			_ => None,
+		});
+		let Some(code) = code else {
+			if !expected.code.is_empty() {
+				report.push(format!("{what}: javap has Code, maya does not"));
+			}
+			continue;
+		};
+
+		if let Some(max_stack) = expected.max_stack {
+			report.check(format!("{what} max_stack"), max_stack, code.max_stack);
+		}
+		if let Some(max_locals) = expected.max_locals {
+			report.check(format!("{what} max_locals"), max_locals, code.max_locals);
+		}
+
+		let decoded = match decode_code(&ir.cp, &code.code) {
+			Ok(decoded) => decoded,
+			Err(e) => {
+				report.push(format!("{what}: {e}"));
+				continue;
+			}
+		};
+
+		report.check(format!("{what} instruction count"), expected.code.len(), decoded.len());
+		for (expected, got) in expected.code.iter().zip(&decoded) {
+			report.check(format!("{what} offset"), expected.offset, got.offset);
+			report.check(
+				format!("{what} @{}", expected.offset),
+				normalize_mnemonic(&expected.mnemonic),
+				got.mnemonic.as_str(),
+			);
+		}
+	}
+}
+
+fn check_class(path: &Path) -> eyre::Result<Report> {
+	let mut report = Report::default();
+	let javap = javap::run(path)?;
+
+	let bytes = std::fs::read(path)?;
+	let io = match panic::catch_unwind(|| IOClassFile::read(&mut Cursor::new(bytes))) {
+		Ok(io) => io?,
+		Err(_) => {
+			report.push("IO parsing panicked".to_string());
+			return Ok(report);
+		}
+	};
+	compare_io(&javap, &io, &mut report);
+
+	let ir = panic::catch_unwind(AssertUnwindSafe(|| IRClassFile::from_io(io)));
+	match ir {
+		Ok(Ok(ir)) => compare_ir(&javap, &ir, &mut report),
+		Ok(Err(e)) => report.push(format!("IR conversion failed: {e}")),
+		Err(_) => report.push("IR conversion panicked".to_string()),
+	}
+
+	Ok(report)
+}
+
+fn collect_classes(dir: &Path, out: &mut Vec<PathBuf>) -> std::io::Result<()> {
+	for entry in dir.read_dir()? {
+		let path = entry?.path();
+		if path.is_dir() {
+			collect_classes(&path, out)?;
+		} else if path.extension().is_some_and(|e| e == "class") {
+			out.push(path);
+		}
+	}
+	Ok(())
+}
+
+fn main() -> eyre::Result<()> {
+	let Some(dir) = std::env::args().nth(1) else {
+		eyre::bail!("usage: maya-javap-check <dir>");
+	};
+
+	let mut classes = Vec::new();
+	collect_classes(Path::new(&dir), &mut classes)?;
+	classes.sort();
+
+	// the decoder still panics on unknown input, we report that ourselves
+	panic::set_hook(Box::new(|_| {}));
+
+	let mut failed = 0;
+	for path in &classes {
+		match check_class(path) {
+			Ok(report) if report.mismatches.is_empty() => {}
+			Ok(report) => {
+				failed += 1;
+				println!("{}:", path.display());
+				for mismatch in report.mismatches {
+					println!("  {mismatch}");
+				}
+			}
+			Err(e) => {
+				failed += 1;
+				println!("{}: {e}", path.display());
+			}
+		}
+	}
+
+	println!("{} classes checked, {failed} with mismatches", classes.len());
+	if failed != 0 {
+		std::process::exit(1);
+	}
+
+	Ok(())
+}
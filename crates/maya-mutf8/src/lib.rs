@@ -1,138 +1,404 @@
-#![feature(portable_simd)]
+#![cfg_attr(not(feature = "stable-simd"), feature(portable_simd))]
 
-use std::{simd::u8x16, string::FromUtf8Error};
+mod simd;
+
+use std::{io::Write, string::FromUtf8Error};
 
 use thiserror::Error;
 
 #[derive(Error, Debug, PartialEq, Eq)]
 pub enum MUTFError {
-	#[error("nullbyte in input")]
-	NullByteInInput,
-	#[error("{0} byte codepoint: input length too small")]
-	CodepointBadInputLength(u8),
+	#[error("nullbyte in input at byte {offset}")]
+	NullByteInInput { offset: usize },
+	#[error("{len} byte codepoint at byte {offset}: input length too small (have {bytes:02X?})")]
+	CodepointBadInputLength { len: u8, offset: usize, bytes: Vec<u8> },
 	#[error("UTF-8 Error: {0}")]
 	FromUTF8Err(#[from] FromUtf8Error),
-	#[error("Input has wrong encoding")]
-	InvalidEncoding,
+	#[error("output buffer is {available} bytes, {needed} are needed")]
+	BufferTooSmall { needed: usize, available: usize },
+	#[error("Input has wrong encoding at byte {offset}: {bytes:02X?}")]
+	InvalidEncoding { offset: usize, bytes: Vec<u8> },
 }
 
 pub fn encode(string: &str) -> Vec<u8> {
-	let mut bytes: Vec<u8> = vec![];
+	let mut bytes = Vec::with_capacity(encoded_len(string));
+	encode_into(string, &mut bytes);
+	bytes
+}
+
+/// Length of `encode(string)`, without encoding anything.
+pub fn encoded_len(string: &str) -> usize {
+	// nullbytes grow from 1 to 2 bytes, 4 byte sequences become 6 byte surrogate pairs
+	string.len()
+		+ string
+			.bytes()
+			.map(|b| match b {
+				0 => 1,
+				0xF0.. => 2,
+				_ => 0,
+			})
+			.sum::<usize>()
+}
+
+/// Appends the encoded form of `string` to `out`.
+pub fn encode_into(string: &str, out: &mut Vec<u8>) {
+	// 1 to 3 byte UTF-8 sequences are already valid MUTF-8, only nullbytes and
+	// 4 byte sequences (lead byte >= 0xF0) have to be re-encoded
+	let input = string.as_bytes();
+	let len = input.len();
+	let mut idx = 0;
+
+	while idx < len {
+		if idx + 16 <= len && simd::is_verbatim(input[idx..idx + 16].try_into().unwrap()) {
+			out.extend_from_slice(&input[idx..idx + 16]);
+			idx += 16;
+			continue;
+		}
+
+		// a chunk may have stopped in the middle of a sequence, its continuation bytes are copied here
+		let b = input[idx];
+		if b != 0 && b < 0xF0 {
+			out.push(b);
+			idx += 1;
+		} else {
+			let c = string[idx..].chars().next().unwrap();
+			let (buf, n) = encode_char(c);
+			out.extend_from_slice(&buf[..n]);
+			idx += c.len_utf8();
+		}
+	}
+}
 
-	for c in string.chars().map(|c| c as u32) {
-		match c {
-			// nullbytes are handled weird
-			0 => bytes.extend([0xC0, 0x80]),
+/// Encodes `string` straight into a writer, batching through a small stack buffer.
+pub fn encode_to<W: Write + ?Sized>(string: &str, writer: &mut W) -> std::io::Result<()> {
+	let mut batch = [0u8; 256];
+	let mut used = 0;
+	for c in string.chars() {
+		let (buf, len) = encode_char(c);
+		if used + len > batch.len() {
+			writer.write_all(&batch[..used])?;
+			used = 0;
+		}
+
+		batch[used..used + len].copy_from_slice(&buf[..len]);
+		used += len;
+	}
+
+	writer.write_all(&batch[..used])
+}
+
+/// Encodes into a preallocated buffer, returning how many bytes were written.
+/// Nothing is written if `out` is too small.
+pub fn encode_to_slice(string: &str, out: &mut [u8]) -> Result<usize, MUTFError> {
+	let needed = encoded_len(string);
+	if needed > out.len() {
+		return Err(MUTFError::BufferTooSmall {
+			needed,
+			available: out.len(),
+		});
+	}
+
+	let mut used = 0;
+	for c in string.chars() {
+		let (buf, len) = encode_char(c);
+		out[used..used + len].copy_from_slice(&buf[..len]);
+		used += len;
+	}
 
-			// valid ascii
-			c @ 0..=0x7F => bytes.push(c as u8),
+	Ok(used)
+}
+
+fn encode_char(c: char) -> ([u8; 6], usize) {
+	match c as u32 {
+		// nullbytes are handled weird
+		0 => ([0xC0, 0x80, 0, 0, 0, 0], 2),
+
+		// valid ascii
+		c @ 0..=0x7F => ([c as u8, 0, 0, 0, 0, 0], 1),
 
-			// 2 byte encoding
-			c @ 0..=0x7FF => bytes.extend([0xC0 | 0x1F & (c >> 0x06) as u8, 0x80 | (0x3F & c) as u8]),
+		// 2 byte encoding
+		c @ 0..=0x7FF => (
+			[0xC0 | 0x1F & (c >> 0x06) as u8, 0x80 | (0x3F & c) as u8, 0, 0, 0, 0],
+			2,
+		),
 
-			// 3 byte encoding
-			c @ 0..=0x7FFF => bytes.extend([
+		// 3 byte encoding
+		c @ 0..=0xFFFF => (
+			[
 				0xE0 | 0x0F & (c >> 0x0C) as u8,
 				0x80 | 0x3F & (c >> 0x06) as u8,
 				0x80 | (0x3F & c) as u8,
-			]),
+				0,
+				0,
+				0,
+			],
+			3,
+		),
+
+		// 6 byte encoding, each half of the surrogate pair is written in the 3 byte form
+		c => {
+			let c = c - 0x10000;
+			let high = 0xD800 | (c >> 0x0A);
+			let low = 0xDC00 | (c & 0x3FF);
+			(
+				[
+					0xED,
+					0xA0 | (high >> 0x06) as u8 & 0x0F,
+					0x80 | (high & 0x3F) as u8,
+					0xED,
+					0xB0 | (low >> 0x06) as u8 & 0x0F,
+					0x80 | (low & 0x3F) as u8,
+				],
+				6,
+			)
+		}
+	}
+}
+
+/// Decodes `input` into a string. The continuation bytes of a surrogate pair aren't checked, only
+/// its `ED Ax` and `ED Bx` prefixes, as has always been the case. Use [`validate`] to reject those too.
+pub fn decode(input: &[u8]) -> Result<String, MUTFError> {
+	let mut output = String::with_capacity(input.len());
+	let mut idx = ascii_prefix_len(input);
+	// the prefix is plain ASCII, so this can't fail
+	output.push_str(std::str::from_utf8(&input[..idx]).unwrap());
+
+	while idx < input.len() {
+		let (c, n) = match loose_pair_at(input, idx) {
+			Some(c) => (c, 6),
+			None => decode_at(input, idx)?,
+		};
+		output.push(c);
+		idx += n;
+	}
 
-			// 6 byte encoding
-			_ => bytes.extend([
-				0xED,
-				0xA0 | (c >> 0x10) as u8 & 0x0F,
-				0x80 | (c >> 0x0A) as u8 & 0x3F,
-				0xED,
-				0xB0 | (c >> 0x06) as u8 & 0x0F,
-				0x80 | (c & 0x3F) as u8,
-			]),
+	Ok(output)
+}
+
+/// How unpaired surrogates are treated when decoding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Surrogates {
+	/// Only properly paired surrogates are accepted, like a `String` requires.
+	Strict,
+	/// Lone surrogates are kept in their 3 byte (CESU-8) form. `javac` emits these for
+	/// string literals like `"\uD800"`, so they do show up in real classfiles.
+	Lenient,
+}
+
+/// Decodes into UTF-8 bytes. With [`Surrogates::Lenient`] the output is only UTF-8 if the input had
+/// no lone surrogates (it's WTF-8 otherwise), with [`Surrogates::Strict`] it always is.
+pub fn decode_bytes(input: &[u8], surrogates: Surrogates) -> Result<Vec<u8>, MUTFError> {
+	let mut idx = ascii_prefix_len(input);
+	let mut output = Vec::with_capacity(input.len());
+	output.extend_from_slice(&input[..idx]);
+
+	while idx < input.len() {
+		let (c, n) = decode_unit_at(input, idx, surrogates)?;
+		match char::from_u32(c) {
+			Some(c) => output.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes()),
+			None => output.extend_from_slice(&input[idx..idx + 3]),
 		}
+		idx += n;
 	}
 
-	bytes
+	Ok(output)
 }
 
-pub fn decode(input: &[u8]) -> Result<String, MUTFError> {
-	let mut output: Vec<u8> = vec![];
-	let len = input.len();
-	let mut idx = 0;
+/// Checks that `input` is well-formed without building the string.
+/// Returns the length the decoded string would have in (standard) UTF-8.
+pub fn validate(input: &[u8]) -> Result<usize, MUTFError> {
+	let mut idx = ascii_prefix_len(input);
+	let mut utf8_len = idx;
 
-	while idx + 16 <= len {
-		let chunk = u8x16::from_slice(&input[idx..idx + 16]);
-		let is_ascii = chunk.lt(&u8x16::splat(0x80));
-		if is_ascii {
-			output.extend_from_slice(&input[idx..idx + 16]);
-			idx += 16;
-		} else {
+	while idx < input.len() {
+		let (c, n) = decode_at(input, idx)?;
+		utf8_len += c.len_utf8();
+		idx += n;
+	}
+
+	Ok(utf8_len)
+}
+
+pub fn is_valid(input: &[u8]) -> bool {
+	validate(input).is_ok()
+}
+
+/// Walks the chars of `input` without building a string. Iteration stops after the first error.
+pub fn decode_chars(input: &[u8]) -> DecodeChars<'_> {
+	DecodeChars { input, idx: 0 }
+}
+
+pub struct DecodeChars<'a> {
+	input: &'a [u8],
+	idx: usize,
+}
+
+impl DecodeChars<'_> {
+	/// Byte offset of the next char in the input.
+	pub fn offset(&self) -> usize {
+		self.idx
+	}
+}
+
+impl Iterator for DecodeChars<'_> {
+	type Item = Result<char, MUTFError>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		if self.idx >= self.input.len() {
+			return None;
+		}
+
+		match decode_at(self.input, self.idx) {
+			Ok((c, n)) => {
+				self.idx += n;
+				Some(Ok(c))
+			}
+			Err(e) => {
+				self.idx = self.input.len();
+				Some(Err(e))
+			}
+		}
+	}
+}
+
+impl std::iter::FusedIterator for DecodeChars<'_> {}
+
+/// Length of the leading run of non-null ASCII, checked 16 bytes at a time.
+fn ascii_prefix_len(input: &[u8]) -> usize {
+	let mut idx = 0;
+	while idx + 16 <= input.len() {
+		// nullbytes have to go through the slow path so they get rejected
+		if !simd::is_plain_ascii(input[idx..idx + 16].try_into().unwrap()) {
 			break;
 		}
+		idx += 16;
 	}
 
-	while idx < len {
-		let b = input[idx];
-		idx += 1;
-
-		match b {
-			0x0 => return Err(MUTFError::NullByteInInput),
-			// valid ascii
-			b if b < 0x80 => output.push(b),
-			// 2 byte encoding
-			b if (b & 0xE0) == 0xC0 => {
-				if idx >= len {
-					return Err(MUTFError::CodepointBadInputLength(2));
-				}
+	idx
+}
 
-				let b2 = input[idx];
-				idx += 1;
+impl MUTFError {
+	/// Byte offset into the input where the bad sequence starts.
+	pub fn offset(&self) -> Option<usize> {
+		match self {
+			Self::NullByteInInput { offset }
+			| Self::CodepointBadInputLength { offset, .. }
+			| Self::InvalidEncoding { offset, .. } => Some(*offset),
+			Self::FromUTF8Err(_) | Self::BufferTooSmall { .. } => None,
+		}
+	}
 
-				if b != 0xC0 || b2 != 0x80 {
-					output.extend([b, b2]);
-				} else {
-					output.push(0);
-				}
+	fn invalid(input: &[u8], offset: usize, len: usize) -> Self {
+		Self::InvalidEncoding {
+			offset,
+			bytes: input[offset..(offset + len).min(input.len())].to_vec(),
+		}
+	}
+
+	fn truncated(input: &[u8], offset: usize, len: u8) -> Self {
+		Self::CodepointBadInputLength {
+			len,
+			offset,
+			bytes: input[offset..].to_vec(),
+		}
+	}
+}
+
+fn is_continuation(b: u8) -> bool {
+	b & 0xC0 == 0x80
+}
+
+/// Decodes the 3 byte sequence at `idx` into a UTF-16 code unit.
+fn decode_3(input: &[u8], idx: usize) -> Result<u32, MUTFError> {
+	if idx + 2 >= input.len() {
+		return Err(MUTFError::truncated(input, idx, 3));
+	}
+
+	let [b, b2, b3] = [input[idx], input[idx + 1], input[idx + 2]];
+	if !is_continuation(b2) || !is_continuation(b3) {
+		return Err(MUTFError::invalid(input, idx, 3));
+	}
+
+	let unit = ((b as u32 & 0x0F) << 12) | ((b2 as u32 & 0x3F) << 6) | (b3 as u32 & 0x3F);
+	if unit < 0x800 {
+		// overlong
+		return Err(MUTFError::invalid(input, idx, 3));
+	}
+
+	Ok(unit)
+}
+
+/// The supplementary char of the surrogate pair at `idx`, from the low bits of its bytes whatever the
+/// high bits of the continuation bytes are.
+fn loose_pair_at(input: &[u8], idx: usize) -> Option<char> {
+	let &[0xED, b2, b3, 0xED, b5, b6, ..] = input.get(idx..)? else {
+		return None;
+	};
+	if b2 & 0xF0 != 0xA0 || b5 & 0xF0 != 0xB0 {
+		return None;
+	}
+
+	let high = ((b2 as u32 & 0x0F) << 6) | (b3 as u32 & 0x3F);
+	let low = ((b5 as u32 & 0x0F) << 6) | (b6 as u32 & 0x3F);
+	char::from_u32(0x10000 + (high << 10) + low)
+}
+
+/// Decodes the char starting at `idx`, returning it and how many bytes it took.
+fn decode_at(input: &[u8], idx: usize) -> Result<(char, usize), MUTFError> {
+	let (c, n) = decode_unit_at(input, idx, Surrogates::Strict)?;
+	// strict mode never returns a lone surrogate
+	Ok((char::from_u32(c).unwrap(), n))
+}
+
+/// Like [`decode_at`], but returns the code point, which can be a lone surrogate in lenient mode.
+fn decode_unit_at(input: &[u8], idx: usize, surrogates: Surrogates) -> Result<(u32, usize), MUTFError> {
+	let b = input[idx];
+	match b {
+		0x0 => Err(MUTFError::NullByteInInput { offset: idx }),
+		// valid ascii
+		b if b < 0x80 => Ok((b as u32, 1)),
+		// 2 byte encoding
+		b if (b & 0xE0) == 0xC0 => {
+			let Some(&b2) = input.get(idx + 1) else {
+				return Err(MUTFError::truncated(input, idx, 2));
+			};
+			if !is_continuation(b2) {
+				return Err(MUTFError::invalid(input, idx, 2));
 			}
 
-			// 3 byte encoding
-			b if (b & 0xF0) == 0xE0 => {
-				if idx + 1 >= len {
-					return Err(MUTFError::CodepointBadInputLength(3));
-				}
+			let c = ((b as u32 & 0x1F) << 6) | (b2 as u32 & 0x3F);
+			// everything below 0x80 is overlong, except the nullbyte which is always written like this
+			if c != 0 && c < 0x80 {
+				return Err(MUTFError::invalid(input, idx, 2));
+			}
 
-				let b2 = input[idx];
-				let b3 = input[idx + 1];
-				idx += 2;
-
-				// check for 6 byte encoding
-				if idx + 2 < len && b == 0xED && (b2 & 0xF0) == 0xA0 {
-					let b4 = input[idx];
-					let b5 = input[idx + 1];
-					let b6 = input[idx + 2];
-
-					// its 6 byte encoding!
-					if b4 == 0xED && (b5 & 0xF0) == 0xB0 {
-						idx += 3;
-
-						let mut bits: u32 = ((b2 as u32 & 0x0F) + 1) << 16;
-						bits += (b3 as u32) & 0x3F << 10;
-						bits += (b5 as u32) & 0x0F << 6;
-						bits += (b6 as u32) & 0x3F;
-
-						output.push(0xF0 + ((bits >> 18) & 0x07) as u8);
-						output.push(0x80 + ((bits >> 12) & 0x3F) as u8);
-						output.push(0x80 + ((bits >> 6) & 0x3F) as u8);
-						output.push(0x80 + (bits & 0x3F) as u8);
-						continue;
+			Ok((c, 2))
+		}
+		// 3 byte encoding, or the first half of a 6 byte surrogate pair
+		b if (b & 0xF0) == 0xE0 => {
+			let unit = decode_3(input, idx)?;
+			match unit {
+				0xD800..=0xDBFF => {
+					let low = match input.get(idx + 3) {
+						Some(0xED) => decode_3(input, idx + 3).ok(),
+						_ => None,
+					};
+					match low {
+						Some(low @ 0xDC00..=0xDFFF) => Ok((0x10000 + ((unit - 0xD800) << 10) + (low - 0xDC00), 6)),
+						// lone high surrogate
+						_ if surrogates == Surrogates::Lenient => Ok((unit, 3)),
+						Some(_) => Err(MUTFError::invalid(input, idx, 6)),
+						None => Err(MUTFError::invalid(input, idx, 3)),
 					}
 				}
-
-				output.extend([b, b2, b3]);
+				// lone low surrogate
+				0xDC00..=0xDFFF if surrogates == Surrogates::Strict => Err(MUTFError::invalid(input, idx, 3)),
+				unit => Ok((unit, 3)),
 			}
-
-			_ => return Err(MUTFError::InvalidEncoding),
 		}
-	}
 
-	Ok(String::from_utf8(output)?)
+		_ => Err(MUTFError::invalid(input, idx, 1)),
+	}
 }
 
 #[cfg(test)]
@@ -187,24 +453,166 @@ mod tests {
 		assert_eq!(STR, decoded.unwrap());
 	}
 
+	#[test]
+	fn encode_into_and_to() {
+		const STR: &str = "Hello World! Œ and 〰 and • plus more ascii!";
+		let mut into = b"prefix".to_vec();
+		encode_into(STR, &mut into);
+		assert_eq!(&into[6..], encode(STR));
+
+		// long enough to flush the batch buffer a few times
+		let long = STR.repeat(20);
+		let mut to = Vec::new();
+		encode_to(&long, &mut to).unwrap();
+		assert_eq!(to, encode(&long));
+	}
+
+	#[test]
+	fn encode_fast_path_boundaries() {
+		// multi-byte sequences, nullbytes and supplementary chars straddling the 16 byte chunks
+		for prefix in 0..16 {
+			let string = format!("{}Œ•\0〰😀{}", "a".repeat(prefix), "b".repeat(20));
+			let scalar: Vec<u8> = string
+				.chars()
+				.flat_map(|c| {
+					let (buf, n) = encode_char(c);
+					buf.into_iter().take(n)
+				})
+				.collect();
+			assert_eq!(encode(&string), scalar, "prefix {prefix}");
+		}
+	}
+
+	#[test]
+	fn validate_reports_offset() {
+		let encoded = encode("Œ and 〰");
+		assert_eq!(validate(&encoded), Ok("Œ and 〰".len()));
+		assert!(is_valid(&encoded));
+
+		// a lone high surrogate, followed by ascii
+		let input = b"0123456789abcdefgh\xED\xA0\x80xyz";
+		assert_eq!(
+			validate(input),
+			Err(MUTFError::InvalidEncoding {
+				offset: 18,
+				bytes: vec![0xED, 0xA0, 0x80],
+			})
+		);
+		// overlong 2 byte ascii
+		let err = validate(b"ab\xC1\x81").unwrap_err();
+		assert_eq!(err.offset(), Some(2));
+		assert_eq!(err.to_string(), "Input has wrong encoding at byte 2: [C1, 81]");
+	}
+
+	#[test]
+	fn encoded_len_matches() {
+		for string in ["", "hello", "\0\0", "Œ and 〰 and •", "😀a😀\0"] {
+			assert_eq!(encoded_len(string), encode(string).len(), "{string:?}");
+		}
+	}
+
+	#[test]
+	fn chars() {
+		const STR: &str = "a\0Œ〰";
+		let encoded = encode(STR);
+		let chars: Result<String, _> = decode_chars(&encoded).collect();
+		assert_eq!(chars.unwrap(), STR);
+
+		let mut chars = decode_chars(b"ab\x80cd");
+		assert_eq!(chars.next(), Some(Ok('a')));
+		assert_eq!(chars.next(), Some(Ok('b')));
+		assert!(matches!(
+			chars.next(),
+			Some(Err(MUTFError::InvalidEncoding { offset: 2, .. }))
+		));
+		assert_eq!(chars.next(), None);
+	}
+
+	#[test]
+	fn every_code_point_round_trips() {
+		let all: String = (0..=0x10FFFF).filter_map(char::from_u32).collect();
+		let encoded = encode(&all);
+		assert_eq!(decode(&encoded).unwrap(), all);
+		assert_eq!(decode_bytes(&encoded, Surrogates::Strict).unwrap(), all.as_bytes());
+		assert_eq!(decode_bytes(&encoded, Surrogates::Lenient).unwrap(), all.as_bytes());
+	}
+
+	#[test]
+	fn encode_spec_bytes() {
+		// the whole BMP above 0x7FF takes 3 bytes, not just up to 0x7FFF
+		assert_eq!(encode("\u{8000}"), [0xE8, 0x80, 0x80]);
+		assert_eq!(encode("\u{FFFF}"), [0xEF, 0xBF, 0xBF]);
+		// supplementary chars are a surrogate pair, each half in the 3 byte form
+		assert_eq!(encode("\u{10000}"), [0xED, 0xA0, 0x80, 0xED, 0xB0, 0x80]);
+		assert_eq!(encode("\u{1F600}"), [0xED, 0xA0, 0xBD, 0xED, 0xB8, 0x80]);
+		assert_eq!(encode("\u{10FFFF}"), [0xED, 0xAF, 0xBF, 0xED, 0xBF, 0xBF]);
+		assert_eq!(decode(&[0xED, 0xA0, 0xBD, 0xED, 0xB8, 0x80]).unwrap(), "\u{1F600}");
+	}
+
+	#[test]
+	fn decode_is_loose_in_surrogate_pairs() {
+		// 0x41 and 0xC0 aren't continuation bytes
+		for input in [&b"\xED\xA0\x41\xED\xB2\x80"[..], b"\xED\xA0\x80\xED\xB0\xC0"] {
+			assert!(decode(input).is_ok());
+			assert!(matches!(
+				validate(input),
+				Err(MUTFError::InvalidEncoding { offset: 0, .. })
+			));
+			assert!(decode_chars(input).next().unwrap().is_err());
+		}
+		assert_eq!(decode(b"\xED\xA0\x49\xED\xB2\x80").unwrap(), "\u{12480}");
+	}
+
+	#[test]
+	fn lone_surrogates() {
+		// lone high, lone low, and a high surrogate followed by something that isn't a low one
+		for input in [&b"a\xED\xA0\x80b"[..], b"a\xED\xB0\x80b", b"a\xED\xA0\x80\xE2\x80\xA2"] {
+			assert!(matches!(
+				decode_bytes(input, Surrogates::Strict),
+				Err(MUTFError::InvalidEncoding { offset: 1, .. })
+			));
+			assert!(decode(input).is_err());
+			assert_eq!(decode_bytes(input, Surrogates::Lenient).unwrap(), input);
+		}
+	}
+
+	#[test]
+	fn encode_slice() {
+		const STR: &str = "a\0〰";
+		let mut out = [0u8; 16];
+		let n = encode_to_slice(STR, &mut out).unwrap();
+		assert_eq!(&out[..n], encode(STR));
+
+		assert_eq!(
+			encode_to_slice(STR, &mut out[..5]),
+			Err(MUTFError::BufferTooSmall {
+				needed: 6,
+				available: 5,
+			})
+		);
+	}
+
 	#[test]
 	fn decode_null_byte_in_input() {
 		let input = b"\x00";
 		let result = decode(input);
-		assert!(matches!(result, Err(MUTFError::NullByteInInput)));
+		assert!(matches!(result, Err(MUTFError::NullByteInInput { offset: 0 })));
 	}
 
 	#[test]
 	fn decode_invalid_encoding() {
 		let input = b"\x80";
 		let result = decode(input);
-		assert!(matches!(result, Err(MUTFError::InvalidEncoding)));
+		assert!(matches!(result, Err(MUTFError::InvalidEncoding { offset: 0, .. })));
 	}
 
 	#[test]
 	fn decode_codepoint_bad_input_length() {
 		let input = b"\xC2";
 		let result = decode(input);
-		assert!(matches!(result, Err(MUTFError::CodepointBadInputLength(2))));
+		assert!(matches!(
+			result,
+			Err(MUTFError::CodepointBadInputLength { len: 2, offset: 0, .. })
+		));
 	}
 }
@@ -0,0 +1,91 @@
+//! 16 byte chunk checks for the ASCII/verbatim fast paths.
+//!
+//! By default these use `portable_simd`, which needs nightly. The `stable-simd` feature swaps in
+//! SSE2/NEON intrinsics from `std::arch` (both are baseline on x86_64/aarch64, so no runtime
+//! detection is needed) and a scalar loop everywhere else.
+
+/// All bytes are in `0x01..0x80`, i.e. ASCII that needs no re-encoding.
+pub(crate) fn is_plain_ascii(chunk: &[u8; 16]) -> bool {
+	imp::is_plain_ascii(chunk)
+}
+
+/// No nullbytes and no 4 byte UTF-8 lead bytes, so UTF-8 can be copied to MUTF-8 as is.
+pub(crate) fn is_verbatim(chunk: &[u8; 16]) -> bool {
+	imp::is_verbatim(chunk)
+}
+
+#[cfg(not(feature = "stable-simd"))]
+mod imp {
+	use std::simd::{
+		cmp::{SimdPartialEq, SimdPartialOrd},
+		u8x16,
+	};
+
+	pub fn is_plain_ascii(chunk: &[u8; 16]) -> bool {
+		let chunk = u8x16::from_array(*chunk);
+		(chunk.simd_gt(u8x16::splat(0)) & chunk.simd_lt(u8x16::splat(0x80))).all()
+	}
+
+	pub fn is_verbatim(chunk: &[u8; 16]) -> bool {
+		let chunk = u8x16::from_array(*chunk);
+		!(chunk.simd_eq(u8x16::splat(0)) | chunk.simd_ge(u8x16::splat(0xF0))).any()
+	}
+}
+
+#[cfg(all(feature = "stable-simd", target_arch = "x86_64"))]
+mod imp {
+	use std::arch::x86_64::*;
+
+	pub fn is_plain_ascii(chunk: &[u8; 16]) -> bool {
+		// SAFETY: SSE2 is part of the x86_64 baseline and the load is unaligned
+		unsafe {
+			let v = _mm_loadu_si128(chunk.as_ptr().cast());
+			let zero = _mm_cmpeq_epi8(v, _mm_setzero_si128());
+			// movemask picks the high bit of every byte, so this also catches non-ASCII
+			_mm_movemask_epi8(_mm_or_si128(v, zero)) == 0
+		}
+	}
+
+	pub fn is_verbatim(chunk: &[u8; 16]) -> bool {
+		// SAFETY: see above
+		unsafe {
+			let v = _mm_loadu_si128(chunk.as_ptr().cast());
+			let zero = _mm_cmpeq_epi8(v, _mm_setzero_si128());
+			// there is no unsigned compare, v >= 0xF0 is max(v, 0xF0) == v
+			let lead4 = _mm_cmpeq_epi8(_mm_max_epu8(v, _mm_set1_epi8(0xF0u8 as i8)), v);
+			_mm_movemask_epi8(_mm_or_si128(zero, lead4)) == 0
+		}
+	}
+}
+
+#[cfg(all(feature = "stable-simd", target_arch = "aarch64"))]
+mod imp {
+	use std::arch::aarch64::*;
+
+	pub fn is_plain_ascii(chunk: &[u8; 16]) -> bool {
+		// SAFETY: NEON is part of the aarch64 baseline and the load is unaligned
+		unsafe {
+			let v = vld1q_u8(chunk.as_ptr());
+			vminvq_u8(v) > 0 && vmaxvq_u8(v) < 0x80
+		}
+	}
+
+	pub fn is_verbatim(chunk: &[u8; 16]) -> bool {
+		// SAFETY: see above
+		unsafe {
+			let v = vld1q_u8(chunk.as_ptr());
+			vminvq_u8(v) > 0 && vmaxvq_u8(v) < 0xF0
+		}
+	}
+}
+
+#[cfg(all(feature = "stable-simd", not(any(target_arch = "x86_64", target_arch = "aarch64"))))]
+mod imp {
+	pub fn is_plain_ascii(chunk: &[u8; 16]) -> bool {
+		chunk.iter().all(|&b| b != 0 && b < 0x80)
+	}
+
+	pub fn is_verbatim(chunk: &[u8; 16]) -> bool {
+		chunk.iter().all(|&b| b != 0 && b < 0xF0)
+	}
+}
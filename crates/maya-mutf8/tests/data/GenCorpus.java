@@ -0,0 +1,60 @@
+import java.io.*;
+import java.net.URI;
+import java.nio.file.*;
+import java.util.*;
+import java.util.stream.*;
+
+// Builds corpus.bin: every CONSTANT_Utf8 from java.base's java/lang classes plus boundary code points
+// and lone surrogates. Each record is `u4 units, u2[units] UTF-16, <DataOutputStream.writeUTF output>`,
+// so the JDK itself is the reference Modified UTF-8 encoder. UTF-16 keeps the lone surrogates,
+// which standard UTF-8 can't hold.
+public class GenCorpus {
+	public static void main(String[] args) throws IOException {
+		Set<String> strings = new LinkedHashSet<>(List.of(
+			"", "\0", "a\0b", "\u0001", "\u007f", "\u0080", "\u00ff", "\u07ff", "\u0800", "\u7fff", "\u8000",
+			"\ud7ff", "\ue000", "\ufffd", "\uffff", "\ud800\udc00", "\udbff\udfff", "\ud83d\ude00",
+			"mixed \u00e9\u4e2d\ud83d\ude00\0end",
+			// lone surrogates, at the edges of their ranges and of the string, and pairs the wrong way round
+			"\ud800", "\udbff", "\udc00", "\udfff", "a\ud800b", "a\udc00b", "\ud800\ud800", "\udc00\ud800",
+			"\ud83d", "x\ude00", "\ud83d\ude00\ud83d", "\udfff\ud83d\ude00\0"
+		));
+
+		FileSystem jrt = FileSystems.getFileSystem(URI.create("jrt:/"));
+		List<Path> classes;
+		try (Stream<Path> s = Files.list(jrt.getPath("/modules/java.base/java/lang"))) {
+			classes = s.filter(p -> p.toString().endsWith(".class")).sorted().collect(Collectors.toList());
+		}
+		for (Path p : classes) {
+			collect(Files.readAllBytes(p), strings);
+		}
+
+		try (DataOutputStream out = new DataOutputStream(new BufferedOutputStream(new FileOutputStream(args[0])))) {
+			for (String s : strings) {
+				out.writeInt(s.length());
+				out.writeChars(s);
+				out.writeUTF(s);
+			}
+		}
+		System.out.println(strings.size() + " strings");
+	}
+
+	static void collect(byte[] bytes, Set<String> strings) throws IOException {
+		DataInputStream in = new DataInputStream(new ByteArrayInputStream(bytes));
+		in.readInt();
+		in.readUnsignedShort();
+		in.readUnsignedShort();
+		int count = in.readUnsignedShort();
+		for (int i = 1; i < count; i++) {
+			int tag = in.readUnsignedByte();
+			switch (tag) {
+				case 1 -> strings.add(in.readUTF());
+				case 3, 4 -> in.skipBytes(4);
+				case 5, 6 -> { in.skipBytes(8); i++; }
+				case 7, 8, 16, 19, 20 -> in.skipBytes(2);
+				case 15 -> in.skipBytes(3);
+				case 9, 10, 11, 12, 17, 18 -> in.skipBytes(4);
+				default -> throw new IOException("unknown tag " + tag);
+			}
+		}
+	}
+}
@@ -0,0 +1,2 @@
+#!/bin/sh
+cd "$(dirname "$0")" && java GenCorpus.java corpus.bin
@@ -0,0 +1,113 @@
+//! Round-trips real constant pool strings against the JDK's Modified UTF-8 encoder.
+//! The corpus is produced by `data/generate.sh`, see `data/GenCorpus.java` for the format.
+
+use maya_mutf8::{MUTFError, Surrogates};
+
+const CORPUS: &[u8] = include_bytes!("data/corpus.bin");
+
+struct Entry<'a> {
+	utf16: Vec<u16>,
+	mutf8: &'a [u8],
+}
+
+impl Entry<'_> {
+	/// `None` if the string has lone surrogates.
+	fn string(&self) -> Option<String> {
+		String::from_utf16(&self.utf16).ok()
+	}
+
+	/// The string as UTF-8, with lone surrogates in their 3 byte form.
+	fn wtf8(&self) -> Vec<u8> {
+		let mut out = Vec::new();
+		for c in char::decode_utf16(self.utf16.iter().copied()) {
+			match c {
+				Ok(c) => out.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes()),
+				Err(e) => {
+					let unit = e.unpaired_surrogate();
+					out.extend([
+						0xE0 | (unit >> 12) as u8,
+						0x80 | (unit >> 6) as u8 & 0x3F,
+						0x80 | unit as u8 & 0x3F,
+					]);
+				}
+			}
+		}
+		out
+	}
+}
+
+fn corpus() -> Vec<Entry<'static>> {
+	let mut entries = Vec::new();
+	let mut data = CORPUS;
+
+	while !data.is_empty() {
+		let units = u32::from_be_bytes(data[..4].try_into().unwrap()) as usize;
+		let utf16 = data[4..4 + 2 * units]
+			.chunks_exact(2)
+			.map(|unit| u16::from_be_bytes([unit[0], unit[1]]))
+			.collect();
+		data = &data[4 + 2 * units..];
+
+		let mutf8_len = u16::from_be_bytes(data[..2].try_into().unwrap()) as usize;
+		let mutf8 = &data[2..2 + mutf8_len];
+		data = &data[2 + mutf8_len..];
+
+		entries.push(Entry { utf16, mutf8 });
+	}
+
+	entries
+}
+
+#[test]
+fn corpus_is_not_empty() {
+	let corpus = corpus();
+	assert!(corpus.len() > 1000);
+	assert!(corpus.iter().filter(|entry| entry.string().is_none()).count() >= 12);
+}
+
+#[test]
+fn encode_matches_jdk() {
+	for entry in corpus() {
+		// a str can't hold a lone surrogate, so there's nothing to encode
+		let Some(string) = entry.string() else {
+			continue;
+		};
+		assert_eq!(
+			maya_mutf8::encode(&string),
+			entry.mutf8,
+			"encoding mismatch for {string:?}"
+		);
+	}
+}
+
+#[test]
+fn decode_matches_jdk() {
+	for entry in corpus() {
+		let string = entry.string();
+		assert_eq!(
+			maya_mutf8::decode(entry.mutf8).ok(),
+			string,
+			"decoding mismatch for {:X?}",
+			entry.mutf8
+		);
+		assert_eq!(
+			maya_mutf8::decode_bytes(entry.mutf8, Surrogates::Lenient).as_ref(),
+			Ok(&entry.wtf8()),
+			"lenient decoding mismatch for {:X?}",
+			entry.mutf8
+		);
+		assert_eq!(maya_mutf8::validate(entry.mutf8).ok(), string.as_ref().map(String::len));
+	}
+}
+
+#[test]
+fn lone_surrogates_are_strict_errors() {
+	for entry in corpus().iter().filter(|entry| entry.string().is_none()) {
+		let error = maya_mutf8::decode_bytes(entry.mutf8, Surrogates::Strict).unwrap_err();
+		assert!(
+			matches!(error, MUTFError::InvalidEncoding { .. }),
+			"{error} for {:X?}",
+			entry.mutf8
+		);
+	}
+}
@@ -0,0 +1,11 @@
+package a;
+
+public class Constants {
+    // long and double constants take two constant pool slots
+    public static final long LONG = 1234567890123L;
+    public static final double DOUBLE = 3.5;
+    public static final int INT = 123456;
+    public static final float FLOAT = 1.5f;
+    public static final long LONG2 = -9876543210L;
+    public static final String STRING = "after the wide constants";
+}
@@ -0,0 +1,16 @@
+package b.api;
+
+import java.lang.annotation.ElementType;
+import java.lang.annotation.Retention;
+import java.lang.annotation.RetentionPolicy;
+import java.lang.annotation.Target;
+import java.util.List;
+
+public record Pair<T>(@Pair.Named("head") T first, List<T> rest, int size) {
+
+    @Retention(RetentionPolicy.RUNTIME)
+    @Target(ElementType.RECORD_COMPONENT)
+    public @interface Named {
+        String value();
+    }
+}
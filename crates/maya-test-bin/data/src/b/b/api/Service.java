@@ -0,0 +1,5 @@
+package b.api;
+
+public interface Service {
+    String name();
+}
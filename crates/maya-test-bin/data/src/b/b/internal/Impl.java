@@ -0,0 +1,9 @@
+package b.internal;
+
+import b.api.Service;
+
+public class Impl implements Service {
+    public String name() {
+        return "impl";
+    }
+}
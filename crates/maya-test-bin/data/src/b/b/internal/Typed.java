@@ -0,0 +1,37 @@
+package b.internal;
+
+import java.util.List;
+import java.util.Map;
+import java.util.function.Function;
+import java.util.function.Supplier;
+
+public class Typed<@TypeUse A extends @TypeUse Comparable<A>> extends @TypeUse Object implements @TypeUse Runnable {
+
+    public @TypeUse(1) String @TypeUse(2) [] array;
+    public List<@TypeUse(3) ? extends @TypeUse(4) Number> wildcard;
+    public Map.@TypeUse(5) Entry<String, String> nested;
+
+    public <@TypeUse B> @TypeUse String method(@TypeUse int param) throws @TypeUse RuntimeException {
+        @TypeUse Object local = new @TypeUse Object();
+        if (local instanceof @TypeUse String) {
+            return (@TypeUse String) local;
+        }
+        try {
+            Function<Object, String> f = @TypeUse String::valueOf;
+            Supplier<Object> s = @TypeUse Object::new;
+            return this.<@TypeUse String>generic(f.apply(s.get()));
+        } catch (@TypeUse IllegalStateException e) {
+            return null;
+        }
+    }
+
+    public <C> C generic(C value) {
+        return value;
+    }
+
+    public void run() {
+        try (@TypeUse AutoCloseable resource = () -> {}) {
+        } catch (Exception e) {
+        }
+    }
+}
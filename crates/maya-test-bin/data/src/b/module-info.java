@@ -0,0 +1,11 @@
+module b {
+    requires transitive java.logging;
+    requires static java.sql;
+
+    exports b.api;
+    exports b.internal to java.logging;
+    opens b.internal;
+
+    uses b.api.Service;
+    provides b.api.Service with b.internal.Impl, b.internal.OtherImpl;
+}
@@ -1,11 +1,7 @@
 use std::{io::Cursor, path::Path};
 
 use maya_classfile_io::IOClassFile;
-use maya_classfile_ir::{
-	attribute::{IRAttribute, IRAttributeInfo},
-	code::Instructions,
-	IRClassFile,
-};
+use maya_classfile_ir::{attribute::IRAttribute, code::Instructions, IRClassFile};
 
 fn main() -> eyre::Result<()> {
 	// let simple = include_bytes!("../data/out/a/a/Simple.class");
@@ -37,7 +33,7 @@ fn main() -> eyre::Result<()> {
 
 	let path = Path::new("crates/maya-test-bin/data");
 
-	fn compile_classes<'a>(dir: &'a Path) {
+	fn compile_classes(dir: &Path) {
 		println!("{dir:?}");
 		let mut dir = dir.read_dir().unwrap();
 		while let Some(Ok(entry)) = dir.next() {
@@ -48,11 +44,8 @@ fn main() -> eyre::Result<()> {
 
 			let name = entry.file_name();
 			if name.to_str().unwrap().ends_with(".class") {
-				let class_content = std::fs::read(entry.path()).unwrap();
-				let mut buffer = Cursor::new(class_content);
-
 				println!("Parsing: {name:?}");
-				let cf = IOClassFile::read(&mut buffer).unwrap();
+				let cf = IOClassFile::read_from_path(entry.path()).unwrap();
 				let cf = IRClassFile::from_io(cf).unwrap();
 				println!("Parsed: {name:?}");
 